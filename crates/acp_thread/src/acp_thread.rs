@@ -1,5 +1,6 @@
 mod connection;
 mod diff;
+mod location_detail;
 mod mention;
 mod terminal;
 
@@ -8,6 +9,7 @@ use collections::HashSet;
 pub use connection::*;
 pub use diff::*;
 use language::language_settings::FormatOnSave;
+pub use location_detail::*;
 pub use mention::*;
 use project::lsp_store::{FormatTrigger, LspFormatTarget};
 use serde::{Deserialize, Serialize};
@@ -375,11 +377,15 @@ impl ToolCall {
             })
             .ok()??;
         let buffer = buffer.await.log_err()?;
+        let columns = LocationDetail::from_location(&location).and_then(|detail| detail.columns);
         let position = buffer
             .update(cx, |buffer, _| {
                 let snapshot = buffer.snapshot();
                 if let Some(row) = location.line {
-                    let column = snapshot.indent_size_for_line(row).len;
+                    let column = match columns {
+                        Some(columns) => columns.start,
+                        None => snapshot.indent_size_for_line(row).len,
+                    };
                     let point = snapshot.clip_point(Point::new(row, column), Bias::Left);
                     snapshot.anchor_before(point)
                 } else {
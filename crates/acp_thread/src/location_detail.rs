@@ -0,0 +1,118 @@
+use agent_client_protocol as acp;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use util::ResultExt as _;
+
+/// The `_meta` key under which a [`LocationDetail`] is attached to an `acp::ToolCallLocation`.
+pub const LOCATION_DETAIL_META_KEY: &str = "zed_location_detail";
+
+/// What a tool call location points at, beyond the path and line that ACP carries natively.
+///
+/// It travels in the location's `_meta`, so clients and agents that don't know about it still
+/// see a valid location, while those that do can navigate to the exact token and hand the anchor
+/// back to Zed's tools unchanged.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocationDetail {
+    /// The 0-based range of columns on the location's line, in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub columns: Option<Range<u32>>,
+    /// The contextual anchor that the location was resolved from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<LocationAnchor>,
+    /// The version of the file when the tool read it, so that callers can tell whether it has
+    /// changed since and pass it back to tools that modify the file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+/// A token located by a snippet of surrounding text rather than by line and column, in the shape
+/// that Zed's context-based navigation tools accept as input.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocationAnchor {
+    pub path: String,
+    pub context: String,
+    pub token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub whole_word: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approximate_line: Option<u32>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub case_insensitive: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unicode_normalize: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_index: Option<u32>,
+}
+
+impl LocationDetail {
+    /// Reads the detail attached to `location`, if any. Malformed details are logged and ignored,
+    /// because they can come from external agents.
+    pub fn from_location(location: &acp::ToolCallLocation) -> Option<Self> {
+        let detail = location.meta.as_ref()?.get(LOCATION_DETAIL_META_KEY)?;
+        serde_json::from_value(detail.clone()).log_err()
+    }
+
+    /// Attaches this detail to `location`, preserving any other `_meta` entries it has.
+    pub fn attach_to(&self, mut location: acp::ToolCallLocation) -> acp::ToolCallLocation {
+        let Some(detail) = serde_json::to_value(self).log_err() else {
+            return location;
+        };
+        location
+            .meta
+            .get_or_insert_default()
+            .insert(LOCATION_DETAIL_META_KEY.into(), detail);
+        location
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location_detail_round_trip() {
+        let detail = LocationDetail {
+            columns: Some(4..10),
+            anchor: Some(LocationAnchor {
+                path: "src/main.rs".into(),
+                context: "fn helper() {".into(),
+                token: "helper".into(),
+                index: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            }),
+            version: Some("0:3".into()),
+        };
+        let location =
+            detail.attach_to(acp::ToolCallLocation::new("/root/src/main.rs").line(Some(2)));
+
+        let json = serde_json::to_value(&location).unwrap();
+        let location: acp::ToolCallLocation = serde_json::from_value(json).unwrap();
+        assert_eq!(location.line, Some(2));
+        assert_eq!(LocationDetail::from_location(&location), Some(detail));
+    }
+
+    #[test]
+    fn test_location_without_detail() {
+        let location = acp::ToolCallLocation::new("/root/src/main.rs");
+        assert_eq!(LocationDetail::from_location(&location), None);
+
+        let mut location = location;
+        location.meta = Some(acp::Meta::from_iter([(
+            LOCATION_DETAIL_META_KEY.into(),
+            "not a detail".into(),
+        )]));
+        assert_eq!(LocationDetail::from_location(&location), None);
+    }
+}
@@ -8,6 +8,9 @@ mod templates;
 #[cfg(test)]
 mod tests;
 mod thread;
+mod tool_audit_log;
+mod tool_permissions;
+mod tool_scheduler;
 mod tools;
 
 use context_server::ContextServerId;
@@ -16,6 +19,9 @@ pub use history_store::*;
 pub use native_agent_server::NativeAgentServer;
 pub use templates::*;
 pub use thread::*;
+pub use tool_audit_log::*;
+pub use tool_permissions::*;
+pub use tool_scheduler::*;
 pub use tools::*;
 
 use acp_thread::{AcpThread, AgentModelSelector, UserMessageId};
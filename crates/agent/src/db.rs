@@ -50,6 +50,8 @@ pub struct DbThread {
     pub completion_mode: Option<CompletionMode>,
     #[serde(default)]
     pub profile: Option<AgentProfileId>,
+    #[serde(default)]
+    pub tool_audit_log: crate::ToolAuditLog,
 }
 
 impl DbThread {
@@ -209,6 +211,7 @@ impl DbThread {
             model: thread.model,
             completion_mode: thread.completion_mode,
             profile: thread.profile,
+            tool_audit_log: crate::ToolAuditLog::default(),
         })
     }
 }
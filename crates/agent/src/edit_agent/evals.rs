@@ -1,6 +1,7 @@
 use super::*;
 use crate::{
-    EditFileMode, EditFileToolInput, GrepToolInput, ListDirectoryToolInput, ReadFileToolInput,
+    EditFileMode, EditFileToolInput, GrepToolInput, ListDirectoryToolInput, ReadFileMode,
+    ReadFileToolInput,
 };
 use Role::*;
 use client::{Client, UserStore};
@@ -124,6 +125,8 @@ fn eval_extract_handle_command_output() {
                             path: input_file_path.into(),
                             start_line: None,
                             end_line: None,
+                            mode: ReadFileMode::Auto,
+                            max_depth: None,
                         },
                     )],
                 ),
@@ -140,6 +143,7 @@ fn eval_extract_handle_command_output() {
                             display_description: edit_description.into(),
                             path: input_file_path.into(),
                             mode: EditFileMode::Edit,
+                            expected_version: None,
                         },
                     )],
                 ),
@@ -185,6 +189,8 @@ fn eval_delete_run_git_blame() {
                             path: input_file_path.into(),
                             start_line: None,
                             end_line: None,
+                            mode: ReadFileMode::Auto,
+                            max_depth: None,
                         },
                     )],
                 ),
@@ -201,6 +207,7 @@ fn eval_delete_run_git_blame() {
                             display_description: edit_description.into(),
                             path: input_file_path.into(),
                             mode: EditFileMode::Edit,
+                            expected_version: None,
                         },
                     )],
                 ),
@@ -246,6 +253,8 @@ fn eval_translate_doc_comments() {
                             path: input_file_path.into(),
                             start_line: None,
                             end_line: None,
+                            mode: ReadFileMode::Auto,
+                            max_depth: None,
                         },
                     )],
                 ),
@@ -262,6 +271,7 @@ fn eval_translate_doc_comments() {
                             display_description: edit_description.into(),
                             path: input_file_path.into(),
                             mode: EditFileMode::Edit,
+                            expected_version: None,
                         },
                     )],
                 ),
@@ -323,6 +333,8 @@ fn eval_use_wasi_sdk_in_compile_parser_to_wasm() {
                             path: input_file_path.into(),
                             start_line: Some(971),
                             end_line: Some(1050),
+                            mode: ReadFileMode::Auto,
+                            max_depth: None,
                         },
                     )],
                 ),
@@ -343,6 +355,8 @@ fn eval_use_wasi_sdk_in_compile_parser_to_wasm() {
                             path: input_file_path.into(),
                             start_line: Some(1050),
                             end_line: Some(1100),
+                            mode: ReadFileMode::Auto,
+                            max_depth: None,
                         },
                     )],
                 ),
@@ -363,6 +377,8 @@ fn eval_use_wasi_sdk_in_compile_parser_to_wasm() {
                             path: input_file_path.into(),
                             start_line: Some(1100),
                             end_line: Some(1150),
+                            mode: ReadFileMode::Auto,
+                            max_depth: None,
                         },
                     )],
                 ),
@@ -383,6 +399,7 @@ fn eval_use_wasi_sdk_in_compile_parser_to_wasm() {
                             display_description: edit_description.into(),
                             path: input_file_path.into(),
                             mode: EditFileMode::Edit,
+                            expected_version: None,
                         },
                     )],
                 ),
@@ -431,6 +448,8 @@ fn eval_disable_cursor_blinking() {
                             include_pattern: None,
                             offset: 0,
                             case_sensitive: false,
+                            literal: false,
+                            context_lines: None,
                         },
                     )],
                 ),
@@ -469,6 +488,7 @@ fn eval_disable_cursor_blinking() {
                             display_description: edit_description.into(),
                             path: input_file_path.into(),
                             mode: EditFileMode::Edit,
+                            expected_version: None,
                         },
                     )],
                 ),
@@ -521,6 +541,8 @@ fn eval_from_pixels_constructor() {
                             path: input_file_path.into(),
                             start_line: None,
                             end_line: None,
+                            mode: ReadFileMode::Auto,
+                            max_depth: None,
                         },
                     )],
                 ),
@@ -538,6 +560,8 @@ fn eval_from_pixels_constructor() {
                             include_pattern: Some("font-kit/src/canvas.rs".into()),
                             offset: 0,
                             case_sensitive: false,
+                            literal: false,
+                            context_lines: None,
                         },
                     )],
                 ),
@@ -552,6 +576,8 @@ fn eval_from_pixels_constructor() {
                             include_pattern: Some("font-kit/src/**/*.rs".into()),
                             offset: 0,
                             case_sensitive: false,
+                            literal: false,
+                            context_lines: None,
                         },
                     )],
                 ),
@@ -566,6 +592,8 @@ fn eval_from_pixels_constructor() {
                             include_pattern: Some("font-kit/src/**/*.rs".into()),
                             offset: 0,
                             case_sensitive: false,
+                            literal: false,
+                            context_lines: None,
                         },
                     )],
                 ),
@@ -672,6 +700,7 @@ fn eval_from_pixels_constructor() {
                             display_description: edit_description.into(),
                             path: input_file_path.into(),
                             mode: EditFileMode::Edit,
+                            expected_version: None,
                         },
                     )],
                 ),
@@ -715,6 +744,8 @@ fn eval_zode() {
                                 path: "root/eval/react.py".into(),
                                 start_line: None,
                                 end_line: None,
+                                mode: ReadFileMode::Auto,
+                                max_depth: None,
                             },
                         ),
                         tool_use(
@@ -724,6 +755,8 @@ fn eval_zode() {
                                 path: "root/eval/react_test.py".into(),
                                 start_line: None,
                                 end_line: None,
+                                mode: ReadFileMode::Auto,
+                                max_depth: None,
                             },
                         ),
                     ],
@@ -756,6 +789,7 @@ fn eval_zode() {
                                 display_description: edit_description.into(),
                                 path: input_file_path.into(),
                                 mode: EditFileMode::Create,
+                                expected_version: None,
                             },
                         ),
                     ],
@@ -826,6 +860,8 @@ fn eval_add_overwrite_test() {
                             path: input_file_path.into(),
                             start_line: None,
                             end_line: None,
+                            mode: ReadFileMode::Auto,
+                            max_depth: None,
                         },
                     )],
                 ),
@@ -925,6 +961,8 @@ fn eval_add_overwrite_test() {
                                 path: input_file_path.into(),
                                 start_line: Some(953),
                                 end_line: Some(1010),
+                                mode: ReadFileMode::Auto,
+                                max_depth: None,
                             },
                         ),
                     ],
@@ -950,6 +988,8 @@ fn eval_add_overwrite_test() {
                                 path: input_file_path.into(),
                                 start_line: Some(1012),
                                 end_line: Some(1120),
+                                mode: ReadFileMode::Auto,
+                                max_depth: None,
                             },
                         ),
                     ],
@@ -973,6 +1013,8 @@ fn eval_add_overwrite_test() {
                                 path: input_file_path.into(),
                                 start_line: Some(271),
                                 end_line: Some(276),
+                                mode: ReadFileMode::Auto,
+                                max_depth: None,
                             },
                         ),
                     ],
@@ -1007,6 +1049,7 @@ fn eval_add_overwrite_test() {
                                 display_description: edit_description.into(),
                                 path: input_file_path.into(),
                                 mode: EditFileMode::Edit,
+                                expected_version: None,
                             },
                         ),
                     ],
@@ -1059,6 +1102,9 @@ fn eval_create_empty_file() {
                             "list_directory",
                             ListDirectoryToolInput {
                                 path: "root".to_string(),
+                                depth: None,
+                                include_ignored: false,
+                                details: false,
                             },
                         ),
                     ],
@@ -1084,6 +1130,7 @@ fn eval_create_empty_file() {
                                 display_description: "Create empty TODO3 file".to_string(),
                                 mode: EditFileMode::Create,
                                 path: "root/TODO3".into(),
+                                expected_version: None,
                             },
                         ),
                     ],
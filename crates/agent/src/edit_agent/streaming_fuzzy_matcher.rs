@@ -235,7 +235,7 @@ impl StreamingFuzzyMatcher {
     }
 }
 
-fn fuzzy_eq(left: &str, right: &str) -> bool {
+pub(crate) fn fuzzy_eq(left: &str, right: &str) -> bool {
     const THRESHOLD: f64 = 0.8;
 
     let min_levenshtein = left.len().abs_diff(right.len());
@@ -1,13 +1,23 @@
+use crate::truncate_results;
+use agent_settings::AgentSettings;
 use anyhow::Result;
 use gpui::{AsyncApp, Entity};
-use language::{Buffer, OutlineItem};
+use language::{Buffer, BufferSnapshot, LanguageName, LanguageScope, OutlineItem};
+use language_model::{
+    LanguageModel, LanguageModelRequest, LanguageModelRequestMessage, MessageContent, Role,
+};
 use regex::Regex;
-use std::fmt::Write;
-use text::Point;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{fmt::Write, sync::Arc};
+use text::{OffsetRangeExt as _, Point};
+use util::{ResultExt as _, size::format_file_size};
 
-/// For files over this size, instead of reading them (or including them in context),
-/// we automatically provide the file's symbol outline instead, with line numbers.
-pub const AUTO_OUTLINE_SIZE: usize = 16384;
+mod outline_cache;
+mod structured_summary;
+
+use outline_cache::{OutlineCache, OutlineVersion};
 
 /// Result of getting buffer content, which can be either full content or an outline.
 pub struct BufferContent {
@@ -15,96 +25,627 @@ pub struct BufferContent {
     pub text: String,
     /// Whether this is an outline (true) or full content (false)
     pub is_outline: bool,
+    /// How many lines the whole buffer has
+    pub total_lines: u32,
+    /// How many bytes the whole buffer has
+    pub total_bytes: usize,
+    /// The buffer's language, if it has one
+    pub language: Option<LanguageName>,
+    /// Where the content stops, in bytes, when it is only the start of the buffer
+    pub truncated_at: Option<usize>,
 }
 
-/// Returns either the full content of a buffer or its outline, depending on size.
-/// For files larger than AUTO_OUTLINE_SIZE, returns an outline with a header.
-/// For smaller files, returns the full content.
+impl BufferContent {
+    /// Describes the whole buffer, e.g. "1200 lines, 48.2KiB, Rust", so that the agent knows
+    /// how much it isn't seeing of a file it was given an outline or part of.
+    pub fn file_summary(&self) -> String {
+        let mut summary = format!(
+            "{} {}, {}",
+            self.total_lines,
+            if self.total_lines == 1 {
+                "line"
+            } else {
+                "lines"
+            },
+            format_file_size(self.total_bytes as u64, false)
+        );
+        if let Some(language) = &self.language {
+            write!(summary, ", {language}").ok();
+        }
+        summary
+    }
+}
+
+/// How many tokens a buffer's content may take up, as counted by the model it is for.
+#[derive(Clone)]
+pub struct TokenBudget {
+    pub model: Arc<dyn LanguageModel>,
+    pub max_tokens: u64,
+}
+
+impl TokenBudget {
+    /// The budget for a single file: a quarter of the `remaining_tokens` in `model`'s context
+    /// window, so that reading a file leaves room for the rest of the conversation.
+    pub fn for_file(model: Arc<dyn LanguageModel>, remaining_tokens: u64) -> Self {
+        Self {
+            model,
+            max_tokens: remaining_tokens / 4,
+        }
+    }
+
+    pub(crate) async fn count_tokens(&self, text: &str, cx: &AsyncApp) -> Result<u64> {
+        let request = LanguageModelRequest {
+            messages: vec![LanguageModelRequestMessage {
+                role: Role::User,
+                content: vec![MessageContent::Text(text.to_string())],
+                cache: false,
+                reasoning_details: None,
+            }],
+            ..Default::default()
+        };
+        cx.update(|cx| self.model.count_tokens(request, cx))?.await
+    }
+}
+
+/// Returns either the full content of a buffer, its outline, or the start of its content.
+///
+/// With a `token_budget`, the full content is returned whenever it fits in the budget, however
+/// large the file is, and otherwise the outline if that fits. Without one, or when the model
+/// can't count tokens, files larger than the `agent.auto_outline_size` setting get their outline.
+/// Files whose language provides no outline get a summary instead if they are in a structured
+/// format, such as JSON, YAML, CSV or a log. Otherwise, or when nothing fits, the start of the
+/// file is returned with a header saying so.
 pub async fn get_buffer_content_or_outline(
     buffer: Entity<Buffer>,
     path: Option<&str>,
+    token_budget: Option<TokenBudget>,
     cx: &AsyncApp,
 ) -> Result<BufferContent> {
-    let file_size = buffer.read_with(cx, |buffer, _| buffer.text().len())?;
+    let (text, total_lines, language, auto_outline_size) = buffer.read_with(cx, |buffer, cx| {
+        (
+            buffer.text(),
+            buffer.max_point().row + 1,
+            buffer.language().map(|language| language.name()),
+            AgentSettings::get_global(cx).auto_outline_size,
+        )
+    })?;
+    let total_bytes = text.len();
+    let buffer_content =
+        |text: String, is_outline: bool, truncated_at: Option<usize>| BufferContent {
+            text,
+            is_outline,
+            total_lines,
+            total_bytes,
+            language: language.clone(),
+            truncated_at,
+        };
 
-    if file_size > AUTO_OUTLINE_SIZE {
-        // For large files, use outline instead of full content
-        // Wait until the buffer has been fully parsed, so we can read its outline
-        buffer
-            .read_with(cx, |buffer, _| buffer.parsing_idle())?
-            .await;
+    let mut budget = None;
+    if let Some(token_budget) = token_budget
+        && let Some(text_tokens) = token_budget.count_tokens(&text, cx).await.log_err()
+    {
+        budget = Some((token_budget, text_tokens));
+    }
+    let fits = match &budget {
+        Some((token_budget, text_tokens)) => *text_tokens <= token_budget.max_tokens,
+        None => text.len() <= auto_outline_size,
+    };
+    if fits {
+        return Ok(buffer_content(text, false, None));
+    }
+
+    let (outline_version, outline_entries) = versioned_outline_entries(buffer.clone(), cx).await?;
+    let has_outline = !outline_entries.is_empty();
+    if has_outline {
+        // Files with thousands of nested items can have an outline too large for the budget
+        // while their top-level symbols still fit, so shallower outlines are tried next.
+        for max_depth in [None, Some(1), Some(0)] {
+            let outline =
+                render_outline_with_header(&outline_version, &outline_entries, path, max_depth, cx)
+                    .await?;
+            let outline_fits = match &budget {
+                Some((token_budget, _)) => token_budget
+                    .count_tokens(&outline, cx)
+                    .await
+                    .log_err()
+                    .is_none_or(|outline_tokens| outline_tokens <= token_budget.max_tokens),
+                None => true,
+            };
+            if outline_fits {
+                return Ok(buffer_content(outline, true, None));
+            }
+        }
+    }
+
+    // Data files and logs have no outline, but their structure says more than their first
+    // kilobyte does.
+    if !has_outline
+        && let Some((format, summary)) = structured_summary::summarize_structured_text(&text, path)
+    {
+        let summary = match path {
+            Some(path) => format!(
+                "# Summary of {path} ({format} file too large to show full content, and no outline available)\n\n{summary}"
+            ),
+            None => format!(
+                "# Summary of file ({format} file too large to show full content, and no outline available)\n\n{summary}"
+            ),
+        };
+        let summary_fits = match &budget {
+            Some((token_budget, _)) => token_budget
+                .count_tokens(&summary, cx)
+                .await
+                .log_err()
+                .is_none_or(|summary_tokens| summary_tokens <= token_budget.max_tokens),
+            None => true,
+        };
+        if summary_fits {
+            return Ok(buffer_content(summary, true, None));
+        }
+    }
+
+    // Give the agent the start of the file, so it has some context. Tokens are assumed to be
+    // spread evenly over the file, to know how much of it fits in the budget.
+    let (truncated_len, size) = match &budget {
+        Some((token_budget, text_tokens)) => {
+            let truncated_len =
+                (text.len() as u64 * token_budget.max_tokens / text_tokens) as usize;
+            (truncated_len, format!("{truncated_len} bytes"))
+        }
+        None => (1024, "1KB".to_string()),
+    };
+    let reason = if has_outline {
+        "file and its outline too large for the model's context window"
+    } else {
+        "file too large to show full content, and no outline available"
+    };
+    let (text, truncated_at) = buffer.read_with(cx, |buffer, _| {
+        let snapshot = buffer.snapshot();
+        let len = snapshot
+            .len()
+            .min(snapshot.as_rope().floor_char_boundary(truncated_len));
+        let content = snapshot.text_for_range(0..len).collect::<String>();
+        let text = if let Some(path) = path {
+            format!("# First {size} of {path} ({reason})\n\n{content}")
+        } else {
+            format!("# First {size} of file ({reason})\n\n{content}")
+        };
+        (text, len)
+    })?;
 
-        let outline_items = buffer.read_with(cx, |buffer, _| {
-            let snapshot = buffer.snapshot();
+    Ok(buffer_content(text, false, Some(truncated_at)))
+}
+
+/// Returns the buffer's symbol outline with line numbers, regardless of its size, or `None` if
+/// its language doesn't provide an outline. Only symbols nested at most `max_depth` levels deep
+/// are listed.
+pub async fn get_buffer_outline(
+    buffer: Entity<Buffer>,
+    path: Option<&str>,
+    max_depth: Option<usize>,
+    cx: &AsyncApp,
+) -> Result<Option<String>> {
+    let (outline_version, outline_entries) = versioned_outline_entries(buffer, cx).await?;
+    if outline_entries.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(
+        render_outline_with_header(&outline_version, &outline_entries, path, max_depth, cx).await?,
+    ))
+}
+
+async fn render_outline_with_header(
+    version: &OutlineVersion,
+    entries: &[OutlineEntry],
+    path: Option<&str>,
+    max_depth: Option<usize>,
+    cx: &AsyncApp,
+) -> Result<String> {
+    // `render_outline` reads the setting again before its first await, so the text it renders
+    // always has the layout it's cached under.
+    let legacy_layout = cx.update(|cx| AgentSettings::get_global(cx).legacy_outline_format)?;
+    if let Some(text) =
+        cx.update(|cx| OutlineCache::rendered(version, path, max_depth, legacy_layout, cx))?
+    {
+        return Ok(text);
+    }
+    let outline_text = render_outline(
+        entries.to_vec(),
+        None,
+        max_depth,
+        0,
+        usize::MAX,
+        false,
+        None,
+        None,
+        cx,
+    )
+    .await?;
+    let text = if let Some(path) = path {
+        format!("# File outline for {path}\n\n{outline_text}",)
+    } else {
+        format!("# File outline\n\n{outline_text}",)
+    };
+    cx.update(|cx| {
+        OutlineCache::insert_rendered(version, path, max_depth, legacy_layout, text.clone(), cx)
+    })?;
+    Ok(text)
+}
+
+/// An item of a buffer's symbol outline, with the kind of symbol it is.
+#[derive(Clone)]
+pub struct OutlineEntry {
+    pub item: OutlineItem<Point>,
+    /// The kind of symbol, such as "fn" or "struct", derived from the kind of syntax node the item
+    /// was captured from, or `None` when that isn't recognized.
+    pub kind: Option<&'static str>,
+    /// The first sentence of the comment right above the item, which is usually its documentation.
+    pub doc_summary: Option<String>,
+    /// The source of the item up to its body, for functions, e.g.
+    /// `pub fn run(self: Arc<Self>, input: Input) -> Result<String>`.
+    pub signature: Option<String>,
+}
+
+impl OutlineEntry {
+    /// Returns the item's outline text, prefixed with its kind when the text is only its name
+    /// (e.g. for fields), and followed by `(...)` for functions, e.g. `pub fn run(...)`.
+    pub fn label(&self) -> String {
+        let Some(kind) = self.kind else {
+            return self.item.text.clone();
+        };
+        let mut text_without_names = self.item.text.clone();
+        for name_range in self.item.name_ranges.iter().rev() {
+            if text_without_names.get(name_range.clone()).is_some() {
+                text_without_names.replace_range(name_range.clone(), "");
+            }
+        }
+        let mut label = if text_without_names.trim().is_empty() {
+            format!("{kind} {}", self.item.text)
+        } else {
+            self.item.text.clone()
+        };
+        if kind == "fn" {
+            label.push_str("(...)");
+        }
+        label
+    }
+
+    /// Returns the item's name, e.g. `load` for `pub fn load`, or its whole outline text when it
+    /// has no name.
+    pub fn name(&self) -> String {
+        let names = self
+            .item
+            .name_ranges
+            .iter()
+            .filter_map(|range| self.item.text.get(range.clone()))
+            .collect::<Vec<_>>();
+        if names.is_empty() {
+            self.item.text.clone()
+        } else {
+            names.join(" ")
+        }
+    }
+}
+
+/// How many characters of a doc comment's first sentence an outline entry shows.
+const MAX_DOC_SUMMARY_CHARS: usize = 120;
+/// How many characters of a function's signature an outline entry shows.
+const MAX_SIGNATURE_CHARS: usize = 160;
+
+/// Syntax node kinds, or parts of them, and the symbol kinds they are shown as. The first match
+/// wins, so more specific parts come first.
+const SYMBOL_KINDS: [(&str, &str); 18] = [
+    ("function", "fn"),
+    ("method", "fn"),
+    ("enum_variant", "variant"),
+    ("enum", "enum"),
+    ("struct", "struct"),
+    ("union", "union"),
+    ("impl", "impl"),
+    ("trait", "trait"),
+    ("interface", "interface"),
+    ("class", "class"),
+    ("mod", "mod"),
+    ("namespace", "mod"),
+    ("macro", "macro"),
+    ("const", "const"),
+    ("static", "static"),
+    ("field", "field"),
+    ("property", "field"),
+    ("type", "type"),
+];
+
+/// Returns the outline of the buffer with the kind of each symbol, once the buffer has been
+/// parsed.
+pub async fn buffer_outline_entries(
+    buffer: Entity<Buffer>,
+    cx: &AsyncApp,
+) -> Result<Vec<OutlineEntry>> {
+    let (_, entries) = versioned_outline_entries(buffer, cx).await?;
+    Ok(entries)
+}
+
+/// Returns the buffer's outline entries along with the state of the buffer they are for, reusing
+/// those in the [`OutlineCache`] when the buffer hasn't changed since they were collected.
+async fn versioned_outline_entries(
+    buffer: Entity<Buffer>,
+    cx: &AsyncApp,
+) -> Result<(OutlineVersion, Vec<OutlineEntry>)> {
+    // Wait until the buffer has been fully parsed, so we can read its outline
+    buffer
+        .read_with(cx, |buffer, _| buffer.parsing_idle())?
+        .await;
+
+    let (snapshot, version, cached_entries) = cx.update(|cx| {
+        let snapshot = buffer.read(cx).snapshot();
+        let version = OutlineVersion::new(&buffer, &snapshot);
+        let cached_entries = OutlineCache::entries(&version, cx);
+        (snapshot, version, cached_entries)
+    })?;
+    if let Some(entries) = cached_entries {
+        return Ok((version, entries));
+    }
+
+    let entries = cx
+        .background_spawn(async move {
             snapshot
                 .outline(None)
                 .items
                 .into_iter()
-                .map(|item| item.to_point(&snapshot))
+                .map(|item| {
+                    let item = item.to_point(&snapshot);
+                    let kind = symbol_kind(&snapshot, &item);
+                    OutlineEntry {
+                        kind,
+                        doc_summary: doc_summary(&snapshot, &item),
+                        signature: (kind == Some("fn"))
+                            .then(|| signature(&snapshot, &item))
+                            .flatten(),
+                        item,
+                    }
+                })
                 .collect::<Vec<_>>()
-        })?;
-
-        // If no outline exists, fall back to first 1KB so the agent has some context
-        if outline_items.is_empty() {
-            let text = buffer.read_with(cx, |buffer, _| {
-                let snapshot = buffer.snapshot();
-                let len = snapshot.len().min(snapshot.as_rope().floor_char_boundary(1024));
-                let content = snapshot.text_for_range(0..len).collect::<String>();
-                if let Some(path) = path {
-                    format!("# First 1KB of {path} (file too large to show full content, and no outline available)\n\n{content}")
-                } else {
-                    format!("# First 1KB of file (file too large to show full content, and no outline available)\n\n{content}")
-                }
-            })?;
+        })
+        .await;
+    cx.update(|cx| OutlineCache::insert(&buffer, version.clone(), entries.clone(), cx))?;
+    Ok((version, entries))
+}
 
-            return Ok(BufferContent {
-                text,
-                is_outline: false,
-            });
+/// Returns the kind of symbol that `item` is, from the syntax node that spans exactly its range.
+fn symbol_kind(snapshot: &BufferSnapshot, item: &OutlineItem<Point>) -> Option<&'static str> {
+    let range = item.range.to_offset(snapshot);
+    let first_character_end = range.start + snapshot.chars_at(range.start).next()?.len_utf8();
+    let mut node = snapshot.syntax_ancestor(range.start..first_character_end)?;
+    while node.byte_range() != range {
+        if node.start_byte() < range.start || node.end_byte() > range.end {
+            return None;
         }
+        node = node.parent()?;
+    }
+    SYMBOL_KINDS
+        .iter()
+        .find(|(node_kind, _)| node.kind().contains(node_kind))
+        .map(|(_, kind)| *kind)
+}
 
-        let outline_text = render_outline(outline_items, None, 0, usize::MAX).await?;
-
-        let text = if let Some(path) = path {
-            format!("# File outline for {path}\n\n{outline_text}",)
+/// Returns the source of `item` up to its body, or up to the end of its first line outside of
+/// parentheses, with runs of whitespace collapsed into single spaces.
+fn signature(snapshot: &BufferSnapshot, item: &OutlineItem<Point>) -> Option<String> {
+    let mut signature = String::new();
+    let mut nesting = 0_usize;
+    for character in snapshot.chars_for_range(item.range.clone()) {
+        match character {
+            '(' | '[' => nesting += 1,
+            ')' | ']' => {
+                nesting = nesting.saturating_sub(1);
+                // Parameters listed one per line end in a comma and a line break.
+                while signature.ends_with([' ', ',']) {
+                    signature.pop();
+                }
+            }
+            '{' | ';' | '\n' if nesting == 0 => break,
+            _ => {}
+        }
+        if character.is_whitespace() {
+            if !signature.is_empty() && !signature.ends_with([' ', '(', '[']) {
+                signature.push(' ');
+            }
         } else {
-            format!("# File outline\n\n{outline_text}",)
+            signature.push(character);
+        }
+    }
+
+    let signature = signature.trim_end();
+    if signature.is_empty() {
+        return None;
+    }
+    if signature.chars().count() <= MAX_SIGNATURE_CHARS {
+        return Some(signature.to_string());
+    }
+    let truncated = signature
+        .chars()
+        .take(MAX_SIGNATURE_CHARS)
+        .collect::<String>();
+    Some(format!("{truncated}…"))
+}
+
+/// Returns the first sentence of the comment in the lines annotating `item`, skipping the
+/// lines that aren't comments, such as attributes.
+fn doc_summary(snapshot: &BufferSnapshot, item: &OutlineItem<Point>) -> Option<String> {
+    let annotation_range = item.annotation_range.as_ref()?;
+    let language = snapshot.language_scope_at(item.range.start)?;
+    let mut comment = String::new();
+    for row in annotation_range.start.row..=annotation_range.end.row {
+        let line = snapshot
+            .text_for_range(Point::new(row, 0)..Point::new(row, snapshot.line_len(row)))
+            .collect::<String>();
+        let Some(text) = comment_text(line.trim(), &language) else {
+            continue;
         };
-        Ok(BufferContent {
-            text,
-            is_outline: true,
-        })
-    } else {
-        // File is small enough, return full content
-        let text = buffer.read_with(cx, |buffer, _| buffer.text())?;
-        Ok(BufferContent {
-            text,
-            is_outline: false,
-        })
+        if text.is_empty() {
+            // The first paragraph is enough to summarize the item.
+            if comment.is_empty() {
+                continue;
+            }
+            break;
+        }
+        if !comment.is_empty() {
+            comment.push(' ');
+        }
+        comment.push_str(text);
+    }
+
+    let sentence = match comment.find(". ") {
+        Some(period) => &comment[..=period],
+        None => comment.as_str(),
+    };
+    if sentence.is_empty() {
+        return None;
+    }
+    if sentence.chars().count() <= MAX_DOC_SUMMARY_CHARS {
+        return Some(sentence.to_string());
+    }
+    let truncated = sentence
+        .chars()
+        .take(MAX_DOC_SUMMARY_CHARS)
+        .collect::<String>();
+    Some(format!("{}…", truncated.trim_end()))
+}
+
+/// Returns the text of `line` without its comment markers, or `None` if it isn't a comment.
+fn comment_text<'a>(line: &'a str, language: &LanguageScope) -> Option<&'a str> {
+    // The shortest remainder comes from the longest prefix, so that `///` isn't taken for `//`
+    // followed by a slash.
+    let line_comment = language
+        .line_comment_prefixes()
+        .iter()
+        .filter_map(|prefix| line.strip_prefix(prefix.trim_end()))
+        .min_by_key(|text| text.len());
+    if let Some(text) = line_comment {
+        return Some(text.trim());
+    }
+
+    for block_comment in [language.documentation_comment(), language.block_comment()]
+        .into_iter()
+        .flatten()
+    {
+        let prefix = block_comment.prefix.trim();
+        let mut text = line;
+        let mut is_comment = false;
+        if let Some(rest) = text.strip_prefix(block_comment.start.trim()) {
+            text = if prefix.is_empty() {
+                rest
+            } else {
+                rest.trim_start_matches(prefix)
+            };
+            is_comment = true;
+        } else if !prefix.is_empty()
+            && let Some(rest) = text.strip_prefix(prefix)
+        {
+            text = rest;
+            is_comment = true;
+        }
+        if let Some(rest) = text.strip_suffix(block_comment.end.trim()) {
+            text = rest;
+            is_comment = true;
+        }
+        if is_comment {
+            return Some(text.trim());
+        }
     }
+    None
 }
 
-async fn render_outline(
-    items: impl IntoIterator<Item = OutlineItem<Point>>,
+/// How many outline entries are rendered at a time, so that rendering the outlines of files with
+/// tens of thousands of symbols doesn't hold up the executor.
+const RENDER_CHUNK_SIZE: usize = 1000;
+
+/// Renders a page of `entries` with their line numbers. Only entries nested at most `max_depth`
+/// levels deep whose label or name matches `regex` are listed, and `offset` counts those.
+/// Functions are listed with their signatures when `include_signatures` is set.
+///
+/// Entries are filtered and rendered on the background executor, `RENDER_CHUNK_SIZE` at a time,
+/// and each rendered chunk is passed to `on_chunk` as soon as it is ready.
+/// With a `token_budget`, the page ends at the last entry that fits in it.
+pub async fn render_outline(
+    entries: Vec<OutlineEntry>,
     regex: Option<Regex>,
+    max_depth: Option<usize>,
     offset: usize,
     results_per_page: usize,
+    include_signatures: bool,
+    mut on_chunk: Option<&mut dyn FnMut(&str)>,
+    token_budget: Option<&TokenBudget>,
+    cx: &AsyncApp,
 ) -> Result<String> {
-    let mut items = items.into_iter().skip(offset);
-
-    let entries = items
-        .by_ref()
-        .filter(|item| {
-            regex
-                .as_ref()
-                .is_none_or(|regex| regex.is_match(&item.text))
+    let legacy_outline_format =
+        cx.update(|cx| AgentSettings::get_global(cx).legacy_outline_format)?;
+    let (page, layout, hidden_by_depth, mut has_more) = cx
+        .background_spawn(async move {
+            let (entries, hidden_by_depth): (Vec<_>, Vec<_>) = entries
+                .into_iter()
+                .filter(|entry| {
+                    regex.as_ref().is_none_or(|regex| {
+                        regex.is_match(&entry.label())
+                            || entry
+                                .item
+                                .name_ranges
+                                .iter()
+                                .filter_map(|range| entry.item.text.get(range.clone()))
+                                .any(|name| regex.is_match(name.trim()))
+                    })
+                })
+                .partition(|entry| max_depth.is_none_or(|max_depth| entry.item.depth <= max_depth));
+            let mut entries = entries.into_iter().skip(offset);
+            let page = entries.by_ref().take(results_per_page).collect::<Vec<_>>();
+            let has_more = entries.next().is_some();
+            let layout = OutlineLayout::new(&page, legacy_outline_format, include_signatures);
+            (page, layout, hidden_by_depth.len(), has_more)
         })
-        .take(results_per_page)
-        .collect::<Vec<_>>();
-    let has_more = items.next().is_some();
+        .await;
 
     let mut output = String::new();
-    let entries_rendered = render_entries(&mut output, entries);
+    let mut entries_rendered = 0;
+    let mut page = page.into_iter();
+    loop {
+        let chunk = page.by_ref().take(RENDER_CHUNK_SIZE).collect::<Vec<_>>();
+        if chunk.is_empty() {
+            break;
+        }
+        let (chunk_text, chunk_entries) = cx
+            .background_spawn(async move {
+                let mut chunk_text = String::new();
+                let chunk_entries = render_entries(&mut chunk_text, chunk, layout);
+                (chunk_text, chunk_entries)
+            })
+            .await;
+        if let Some(on_chunk) = on_chunk.as_mut() {
+            on_chunk(&chunk_text);
+        }
+        output.push_str(&chunk_text);
+        entries_rendered += chunk_entries;
+    }
+
+    if entries_rendered == 0 {
+        if offset == 0 {
+            output.push_str("No symbols match.\n");
+        } else {
+            writeln!(
+                &mut output,
+                "No more matching symbols after offset {offset}."
+            )
+            .ok();
+        }
+        return Ok(output);
+    }
+
+    if token_budget.is_some() {
+        // Each entry is rendered on a line of its own.
+        let rendered_entries = output.lines().map(|line| format!("{line}\n")).collect();
+        let truncated = truncate_results(rendered_entries, token_budget, cx).await;
+        output = truncated.text;
+        entries_rendered = truncated.kept;
+        has_more |= truncated.omitted > 0;
+    }
 
     // Calculate pagination information
     let page_start = offset + 1;
@@ -126,43 +667,218 @@ async fn render_outline(
         )
     }
     .ok();
+    if hidden_by_depth > 0 {
+        writeln!(
+            &mut output,
+            "{hidden_by_depth} more deeply nested symbols are hidden; pass a larger `max_depth` to see them."
+        )
+        .ok();
+    }
 
     Ok(output)
 }
 
-fn render_entries(
+/// Labels longer than this aren't padded to, so that one long signature doesn't push every
+/// line range far to the right.
+const MAX_ALIGNED_LABEL_WIDTH: usize = 60;
+
+/// How [`render_entries`] lays out outline entries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutlineLayout {
+    /// The column that line ranges are aligned to, with two spaces of indentation per level, or
+    /// `None` for the `agent.legacy_outline_format` setting's layout: one space of indentation
+    /// per level, with line ranges right after labels.
+    range_column: Option<usize>,
+    /// Whether functions are shown with their signature after their outline text.
+    include_signatures: bool,
+}
+
+impl OutlineLayout {
+    /// Returns the layout for rendering `entries` together, with the line ranges aligned just
+    /// past the widest label unless `legacy` is set.
+    pub fn new(entries: &[OutlineEntry], legacy: bool, include_signatures: bool) -> Self {
+        let mut layout = Self {
+            range_column: None,
+            include_signatures,
+        };
+        if !legacy {
+            let label_width = entries
+                .iter()
+                .map(|entry| 2 * entry.item.depth + layout.label(entry).chars().count())
+                .filter(|width| *width <= MAX_ALIGNED_LABEL_WIDTH)
+                .max()
+                .unwrap_or(0);
+            layout.range_column = Some(label_width + 1);
+        }
+        layout
+    }
+
+    /// Returns the entry's label, or its outline text followed by its signature when signatures
+    /// are included, e.g. `fn run — fn run(self: Arc<Self>, input: Input)`.
+    fn label(&self, entry: &OutlineEntry) -> String {
+        match &entry.signature {
+            Some(signature) if self.include_signatures => {
+                format!("{} — {signature}", entry.item.text)
+            }
+            _ => entry.label(),
+        }
+    }
+}
+
+/// Writes one line per entry to `output`, indented by its depth and followed by its line range,
+/// and returns how many entries were written.
+pub fn render_entries(
     output: &mut String,
-    items: impl IntoIterator<Item = OutlineItem<Point>>,
+    entries: impl IntoIterator<Item = OutlineEntry>,
+    layout: OutlineLayout,
 ) -> usize {
     let mut entries_rendered = 0;
 
-    for item in items {
-        // Indent based on depth ("" for level 0, "  " for level 1, etc.)
-        for _ in 0..item.depth {
-            output.push(' ');
+    for entry in entries {
+        let item = &entry.item;
+        let label = layout.label(&entry);
+        match layout.range_column {
+            Some(range_column) => {
+                let indent = 2 * item.depth;
+                let width = indent + label.chars().count();
+                write!(
+                    output,
+                    "{:indent$}{label}{:padding$}",
+                    "",
+                    "",
+                    padding = range_column.saturating_sub(width).max(1)
+                )
+                .ok();
+            }
+            None => {
+                write!(output, "{:indent$}{label} ", "", indent = item.depth).ok();
+            }
         }
-        output.push_str(&item.text);
 
         // Add position information - convert to 1-based line numbers for display
         let start_line = item.range.start.row + 1;
         let end_line = item.range.end.row + 1;
 
         if start_line == end_line {
-            writeln!(output, " [L{}]", start_line).ok();
+            write!(output, "[L{}]", start_line).ok();
         } else {
-            writeln!(output, " [L{}-{}]", start_line, end_line).ok();
+            write!(output, "[L{}-{}]", start_line, end_line).ok();
         }
+        if let Some(doc_summary) = &entry.doc_summary {
+            write!(output, " — {doc_summary}").ok();
+        }
+        output.push('\n');
         entries_rendered += 1;
     }
 
     entries_rendered
 }
 
+/// The order in which an outline lists symbols.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutlineOrder {
+    /// In the order they appear in the file.
+    #[default]
+    Document,
+    /// Alphabetically by name.
+    Alphabetical,
+    /// Grouped by kind: modules, then types, then impls, then functions and so on, each group in
+    /// the order it appears in the file.
+    ByKind,
+}
+
+/// Symbol kinds in the order `OutlineOrder::ByKind` groups them. Other kinds come last.
+const KIND_ORDER: [&str; 15] = [
+    "mod",
+    "struct",
+    "enum",
+    "union",
+    "type",
+    "trait",
+    "interface",
+    "class",
+    "impl",
+    "const",
+    "static",
+    "macro",
+    "fn",
+    "field",
+    "variant",
+];
+
+/// Reorders `entries`, which are in document order, keeping each symbol under its parent: only
+/// the children of each symbol, and the top-level symbols, are sorted among themselves.
+pub fn order_entries(entries: Vec<OutlineEntry>, order: OutlineOrder) -> Vec<OutlineEntry> {
+    struct Node {
+        entry: OutlineEntry,
+        children: Vec<Node>,
+    }
+
+    fn sort(nodes: &mut [Node], order: OutlineOrder) {
+        match order {
+            OutlineOrder::Document => {}
+            OutlineOrder::Alphabetical => {
+                nodes.sort_by_cached_key(|node| node.entry.name().to_lowercase())
+            }
+            OutlineOrder::ByKind => nodes.sort_by_key(|node| {
+                node.entry
+                    .kind
+                    .and_then(|kind| KIND_ORDER.iter().position(|ordered| *ordered == kind))
+                    .unwrap_or(KIND_ORDER.len())
+            }),
+        }
+        for node in nodes {
+            sort(&mut node.children, order);
+        }
+    }
+
+    fn flatten(nodes: Vec<Node>, entries: &mut Vec<OutlineEntry>) {
+        for node in nodes {
+            entries.push(node.entry);
+            flatten(node.children, entries);
+        }
+    }
+
+    if order == OutlineOrder::Document {
+        return entries;
+    }
+
+    // The symbols whose children are still being collected, outermost first.
+    let mut open: Vec<Node> = Vec::new();
+    let mut roots = Vec::new();
+    let entry_count = entries.len();
+    for entry in entries {
+        while let Some(node) = open.pop_if(|node| node.entry.item.depth >= entry.item.depth) {
+            match open.last_mut() {
+                Some(parent) => parent.children.push(node),
+                None => roots.push(node),
+            }
+        }
+        open.push(Node {
+            entry,
+            children: Vec::new(),
+        });
+    }
+    while let Some(node) = open.pop() {
+        match open.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => roots.push(node),
+        }
+    }
+
+    sort(&mut roots, order);
+    let mut entries = Vec::with_capacity(entry_count);
+    flatten(roots, &mut entries);
+    entries
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use fs::FakeFs;
-    use gpui::TestAppContext;
+    use gpui::{AppContext as _, TestAppContext};
+    use language_model::fake_provider::FakeLanguageModel;
     use project::Project;
     use settings::SettingsStore;
 
@@ -186,7 +902,7 @@ mod tests {
         buffer.update(cx, |buffer, cx| buffer.set_text(content, cx));
 
         let result = cx
-            .spawn(|cx| async move { get_buffer_content_or_outline(buffer, None, &cx).await })
+            .spawn(|cx| async move { get_buffer_content_or_outline(buffer, None, None, &cx).await })
             .await
             .unwrap();
 
@@ -214,5 +930,233 @@ mod tests {
             result.text.len() < content_len / 10,
             "Result should be much smaller than original content"
         );
+
+        // Should describe the whole file and where the content stops
+        assert_eq!(result.total_lines, 1);
+        assert_eq!(result.total_bytes, content_len);
+        assert_eq!(result.language, None);
+        assert!(
+            result
+                .truncated_at
+                .is_some_and(|truncated_at| truncated_at <= 1024)
+        );
+        assert_eq!(result.file_summary(), "1 line, 300.0KiB");
+    }
+
+    #[gpui::test]
+    async fn test_token_budget_keeps_large_files_whole(cx: &mut TestAppContext) {
+        cx.update(|cx| {
+            let settings = SettingsStore::test(cx);
+            cx.set_global(settings);
+        });
+
+        let fs = FakeFs::new(cx.executor());
+        let project = Project::test(fs, [], cx).await;
+        let content = "let value = 1;\n".repeat(4 * 1024);
+        let buffer = project
+            .update(cx, |project, cx| project.create_buffer(true, cx))
+            .await
+            .expect("failed to create buffer");
+        buffer.update(cx, |buffer, cx| buffer.set_text(content.clone(), cx));
+
+        // Without a budget, the file is over `auto_outline_size` and has no outline.
+        let without_budget = cx
+            .spawn({
+                let buffer = buffer.clone();
+                |cx| async move { get_buffer_content_or_outline(buffer, None, None, &cx).await }
+            })
+            .await
+            .unwrap();
+        assert!(without_budget.text.starts_with("# First 1KB of file"));
+
+        // A model with room for the whole file gets all of it.
+        let model: Arc<dyn LanguageModel> = Arc::new(FakeLanguageModel::default());
+        let token_budget = TokenBudget::for_file(model.clone(), model.max_token_count());
+        let with_budget = cx
+            .spawn(|cx| async move {
+                get_buffer_content_or_outline(buffer, None, Some(token_budget), &cx).await
+            })
+            .await
+            .unwrap();
+        assert!(!with_budget.is_outline);
+        assert_eq!(with_budget.text, content);
+    }
+
+    #[gpui::test]
+    async fn test_large_structured_file_is_summarized(cx: &mut TestAppContext) {
+        cx.update(|cx| {
+            let settings = SettingsStore::test(cx);
+            cx.set_global(settings);
+        });
+
+        let fs = FakeFs::new(cx.executor());
+        let project = Project::test(fs, [], cx).await;
+        let items = (0..2000)
+            .map(|index| format!("    {{\"id\": {index}}}"))
+            .collect::<Vec<_>>()
+            .join(",\n");
+        let content = format!("{{\n  \"version\": 2,\n  \"items\": [\n{items}\n  ]\n}}\n");
+        let buffer = project
+            .update(cx, |project, cx| project.create_buffer(true, cx))
+            .await
+            .expect("failed to create buffer");
+        buffer.update(cx, |buffer, cx| buffer.set_text(content, cx));
+
+        let result = cx
+            .spawn(|cx| async move {
+                get_buffer_content_or_outline(buffer, Some("root/data.json"), None, &cx).await
+            })
+            .await
+            .unwrap();
+        assert!(result.is_outline);
+        assert_eq!(result.truncated_at, None);
+        assert_eq!(
+            result.text,
+            "# Summary of root/data.json (JSON file too large to show full content, and no outline available)\n\nTop-level object with 2 keys [L1-2005]:\n\"version\": number [L2]\n\"items\": array with 2000 items [L3-2004]\n"
+        );
+    }
+
+    #[gpui::test]
+    async fn test_render_outline_in_chunks(cx: &mut TestAppContext) {
+        cx.update(|cx| {
+            let settings = SettingsStore::test(cx);
+            cx.set_global(settings);
+        });
+        let symbol_count = RENDER_CHUNK_SIZE + 10;
+        let text = (0..symbol_count)
+            .map(|index| format!("fn f{index}() {{}}\n"))
+            .collect::<String>();
+        let buffer = cx.new(|cx| Buffer::local(text, cx).with_language(language::rust_lang(), cx));
+        let (outline, chunks) = cx
+            .spawn(|cx| async move {
+                let entries = buffer_outline_entries(buffer, &cx).await?;
+                let mut chunks = Vec::new();
+                let mut on_chunk = |chunk: &str| chunks.push(chunk.lines().count());
+                let outline = render_outline(
+                    entries,
+                    None,
+                    None,
+                    0,
+                    usize::MAX,
+                    false,
+                    Some(&mut on_chunk),
+                    None,
+                    &cx,
+                )
+                .await?;
+                anyhow::Ok((outline, chunks))
+            })
+            .await
+            .unwrap();
+        assert_eq!(chunks, vec![RENDER_CHUNK_SIZE, 10]);
+        assert!(outline.starts_with("fn f0(...)    [L1]\nfn f1(...)    [L2]\n"));
+        assert!(outline.ends_with(&format!(
+            "\nShowing symbols 1-{symbol_count} (total symbols: {symbol_count})\n"
+        )));
+    }
+
+    #[gpui::test]
+    async fn test_outline_doc_summaries(cx: &mut TestAppContext) {
+        cx.update(|cx| {
+            let settings = SettingsStore::test(cx);
+            cx.set_global(settings);
+        });
+        let text = "/// Loads the config. Falls back to the defaults.\n#[inline]\nfn load() {}\n\n/// Saves the config\n///\n/// More details.\nfn save() {}\n\n// Not about `main`.\n\nfn main() {}\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx).with_language(language::rust_lang(), cx));
+        let outline = cx
+            .spawn(|cx| async move {
+                let entries = buffer_outline_entries(buffer, &cx).await?;
+                render_outline(entries, None, None, 0, usize::MAX, false, None, None, &cx).await
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            outline,
+            "fn load(...) [L3] — Loads the config.\nfn save(...) [L8] — Saves the config\nfn main(...) [L12]\n\nShowing symbols 1-3 (total symbols: 3)\n"
+        );
+    }
+
+    #[gpui::test]
+    async fn test_outline_layouts(cx: &mut TestAppContext) {
+        let text = "struct Config {\n    value: u32,\n}\n\nfn load(\n    path: &str,\n) -> Config {\n    todo!()\n}\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx).with_language(language::rust_lang(), cx));
+        let entries = cx
+            .spawn(|cx| async move { buffer_outline_entries(buffer, &cx).await })
+            .await
+            .unwrap();
+        let render = |layout| {
+            let mut output = String::new();
+            render_entries(&mut output, entries.clone(), layout);
+            output
+        };
+        assert_eq!(
+            render(OutlineLayout::new(&entries, false, false)),
+            "struct Config [L1-3]\n  field value [L2]\nfn load(...)  [L5-9]\n"
+        );
+        assert_eq!(
+            render(OutlineLayout::new(&entries, true, false)),
+            "struct Config [L1-3]\n field value [L2]\nfn load(...) [L5-9]\n"
+        );
+        assert_eq!(
+            render(OutlineLayout::new(&entries, false, true)),
+            "struct Config                           [L1-3]\n  field value                           [L2]\nfn load — fn load(path: &str) -> Config [L5-9]\n"
+        );
+    }
+
+    #[gpui::test]
+    async fn test_cached_outline_follows_legacy_format_setting(cx: &mut TestAppContext) {
+        cx.update(|cx| {
+            let settings = SettingsStore::test(cx);
+            cx.set_global(settings);
+        });
+        let buffer = cx.new(|cx| {
+            Buffer::local("struct Config {\n    value: u32,\n}\n", cx)
+                .with_language(language::rust_lang(), cx)
+        });
+        let outline = |cx: &mut TestAppContext| {
+            let buffer = buffer.clone();
+            cx.spawn(|cx| async move { get_buffer_outline(buffer, None, None, &cx).await })
+        };
+
+        let text = outline(cx).await.unwrap().unwrap();
+        assert!(text.contains("\n  field value [L2]\n"), "{text}");
+
+        cx.update(|cx| {
+            SettingsStore::update_global(cx, |store, cx| {
+                store.update_user_settings(cx, |settings| {
+                    settings.agent.get_or_insert_default().legacy_outline_format = Some(true);
+                });
+            });
+        });
+        let text = outline(cx).await.unwrap().unwrap();
+        assert!(text.contains("\n field value [L2]\n"), "{text}");
+    }
+
+    #[gpui::test]
+    async fn test_order_entries(cx: &mut TestAppContext) {
+        let text = "fn b() {}\nstruct A {\n    z: u32,\n    y: u32,\n}\nfn a() {}\nimpl A {\n    fn d() {}\n    fn c() {}\n}\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx).with_language(language::rust_lang(), cx));
+        let entries = cx
+            .spawn(|cx| async move { buffer_outline_entries(buffer, &cx).await })
+            .await
+            .unwrap();
+        let names = |order| {
+            order_entries(entries.clone(), order)
+                .iter()
+                .map(|entry| format!("{}{}", " ".repeat(entry.item.depth), entry.name()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(OutlineOrder::Document),
+            ["b", "A", " z", " y", "a", "A", " d", " c"]
+        );
+        assert_eq!(
+            names(OutlineOrder::Alphabetical),
+            ["a", "A", " y", " z", "A", " c", " d", "b"]
+        );
+        assert_eq!(
+            names(OutlineOrder::ByKind),
+            ["A", " z", " y", "A", " d", " c", "b", "a"]
+        );
     }
 }
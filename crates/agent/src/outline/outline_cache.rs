@@ -0,0 +1,188 @@
+use super::OutlineEntry;
+use collections::HashMap;
+use gpui::{App, Entity, EntityId, Global, Subscription};
+use language::{Buffer, BufferEvent, BufferSnapshot};
+
+/// How many buffers' outlines are kept.
+const MAX_CACHED_OUTLINES: usize = 64;
+
+/// The state of a buffer an outline was built from: its text and its syntax tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutlineVersion {
+    buffer_id: EntityId,
+    version: clock::Global,
+    syntax_update_count: usize,
+}
+
+impl OutlineVersion {
+    pub fn new(buffer: &Entity<Buffer>, snapshot: &BufferSnapshot) -> Self {
+        Self {
+            buffer_id: buffer.entity_id(),
+            version: snapshot.version().clone(),
+            syntax_update_count: snapshot.syntax_update_count(),
+        }
+    }
+}
+
+/// The outlines of recently outlined buffers, so that reading the same large files again during
+/// a session doesn't collect and render thousands of outline items each time.
+///
+/// An outline is only reused for the exact buffer version and syntax tree it was built from, and
+/// is dropped as soon as its buffer is edited or released.
+#[derive(Default)]
+pub struct OutlineCache {
+    /// Least recently used first.
+    outlines: Vec<CachedOutline>,
+}
+
+struct CachedOutline {
+    version: OutlineVersion,
+    entries: Vec<OutlineEntry>,
+    /// Outlines rendered from `entries` with a header, by path, maximum depth and whether the
+    /// `agent.legacy_outline_format` layout was used.
+    rendered: HashMap<(Option<String>, Option<usize>, bool), String>,
+    _subscriptions: [Subscription; 2],
+}
+
+impl Global for OutlineCache {}
+
+impl OutlineCache {
+    /// Returns the outline entries cached for `version`.
+    pub fn entries(version: &OutlineVersion, cx: &mut App) -> Option<Vec<OutlineEntry>> {
+        let cache = cx.default_global::<Self>();
+        let outline = cache.touch(version)?;
+        Some(outline.entries.clone())
+    }
+
+    /// Caches the outline `entries` of `buffer` at `version`, replacing those of earlier
+    /// versions.
+    pub fn insert(
+        buffer: &Entity<Buffer>,
+        version: OutlineVersion,
+        entries: Vec<OutlineEntry>,
+        cx: &mut App,
+    ) {
+        let buffer_id = version.buffer_id;
+        let subscriptions = [
+            cx.subscribe(buffer, move |_, event: &BufferEvent, cx| {
+                if matches!(event, BufferEvent::Edited | BufferEvent::LanguageChanged(_)) {
+                    cx.default_global::<Self>().remove(buffer_id);
+                }
+            }),
+            cx.observe_release(buffer, move |_, cx| {
+                cx.default_global::<Self>().remove(buffer_id);
+            }),
+        ];
+
+        let cache = cx.default_global::<Self>();
+        cache.remove(buffer_id);
+        if cache.outlines.len() >= MAX_CACHED_OUTLINES {
+            cache.outlines.remove(0);
+        }
+        cache.outlines.push(CachedOutline {
+            version,
+            entries,
+            rendered: HashMap::default(),
+            _subscriptions: subscriptions,
+        });
+    }
+
+    /// Returns the outline rendered with a header for `path` and `max_depth` in the legacy layout
+    /// or not from the entries cached for `version`.
+    pub fn rendered(
+        version: &OutlineVersion,
+        path: Option<&str>,
+        max_depth: Option<usize>,
+        legacy_layout: bool,
+        cx: &mut App,
+    ) -> Option<String> {
+        let cache = cx.default_global::<Self>();
+        let outline = cache.touch(version)?;
+        outline
+            .rendered
+            .get(&(path.map(str::to_string), max_depth, legacy_layout))
+            .cloned()
+    }
+
+    /// Caches an outline rendered with a header from the entries cached for `version`, if they
+    /// still are.
+    pub fn insert_rendered(
+        version: &OutlineVersion,
+        path: Option<&str>,
+        max_depth: Option<usize>,
+        legacy_layout: bool,
+        text: String,
+        cx: &mut App,
+    ) {
+        let cache = cx.default_global::<Self>();
+        if let Some(outline) = cache.touch(version) {
+            outline
+                .rendered
+                .insert((path.map(str::to_string), max_depth, legacy_layout), text);
+        }
+    }
+
+    /// Finds the outline cached for `version` and marks it as the most recently used.
+    fn touch(&mut self, version: &OutlineVersion) -> Option<&mut CachedOutline> {
+        let index = self
+            .outlines
+            .iter()
+            .position(|outline| outline.version == *version)?;
+        let outline = self.outlines.remove(index);
+        self.outlines.push(outline);
+        self.outlines.last_mut()
+    }
+
+    fn remove(&mut self, buffer_id: EntityId) {
+        self.outlines
+            .retain(|outline| outline.version.buffer_id != buffer_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outline::buffer_outline_entries;
+    use gpui::{AppContext as _, TestAppContext};
+
+    #[gpui::test]
+    async fn test_outline_cache_invalidation(cx: &mut TestAppContext) {
+        let buffer = cx
+            .new(|cx| Buffer::local("fn load() {}\n", cx).with_language(language::rust_lang(), cx));
+        let cached = |cx: &mut TestAppContext| {
+            cx.update(|cx| {
+                let version = OutlineVersion::new(&buffer, &buffer.read(cx).snapshot());
+                OutlineCache::entries(&version, cx).map(|entries| entries.len())
+            })
+        };
+        assert_eq!(cached(cx), None);
+
+        cx.spawn({
+            let buffer = buffer.clone();
+            |cx| async move { buffer_outline_entries(buffer, &cx).await }
+        })
+        .await
+        .unwrap();
+        assert_eq!(cached(cx), Some(1));
+
+        buffer.update(cx, |buffer, cx| {
+            buffer.edit([(0..0, "fn save() {}\n")], None, cx)
+        });
+        cx.run_until_parked();
+        assert_eq!(cached(cx), None);
+        assert_eq!(
+            cx.update(|cx| cx.default_global::<OutlineCache>().outlines.len()),
+            0
+        );
+
+        let entries = cx
+            .spawn({
+                let buffer = buffer.clone();
+                |cx| async move { buffer_outline_entries(buffer, &cx).await }
+            })
+            .await
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(cached(cx), Some(2));
+    }
+}
@@ -0,0 +1,387 @@
+use std::{fmt::Write as _, path::Path};
+
+/// How many keys, items or rows a summary lists before it stops.
+const MAX_SUMMARY_ENTRIES: usize = 100;
+/// How many lines a log summary shows from each end of the file.
+const LOG_LINES_PER_END: usize = 20;
+/// How many characters of a single line a summary shows.
+const MAX_LINE_CHARS: usize = 300;
+
+/// Summarizes a large file that has no outline according to its format, which is detected from
+/// the extension of `path` or, for JSON, from the content. Returns the name of the format and the
+/// summary, with line numbers, or `None` for formats without a summary.
+pub fn summarize_structured_text(text: &str, path: Option<&str>) -> Option<(&'static str, String)> {
+    let extension = path
+        .and_then(|path| Path::new(path).extension())
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("json" | "jsonc" | "geojson") => Some(("JSON", summarize_json(text)?)),
+        Some("yaml" | "yml") => Some(("YAML", summarize_yaml(text)?)),
+        Some("csv") => Some(("CSV", summarize_csv(text, ',')?)),
+        Some("tsv") => Some(("TSV", summarize_csv(text, '\t')?)),
+        Some("log") => Some(("log", summarize_log(text)?)),
+        _ if text.trim_start().starts_with(['{', '[']) => Some(("JSON", summarize_json(text)?)),
+        _ => None,
+    }
+}
+
+/// A key of a top-level JSON object, or an item of a top-level JSON array.
+struct JsonEntry {
+    key: Option<String>,
+    kind: &'static str,
+    start_row: usize,
+    end_row: usize,
+    /// How many keys or items the value has, if it's an object or array.
+    children: usize,
+    /// Whether a comma was seen after the last child, which only counts once another child
+    /// follows, so that trailing commas aren't counted.
+    pending_comma: bool,
+}
+
+impl JsonEntry {
+    fn describe(&self) -> String {
+        let kind = match self.kind {
+            "object" => format!("object with {}", pluralize(self.children, "key")),
+            "array" => format!("array with {}", pluralize(self.children, "item")),
+            kind => kind.to_string(),
+        };
+        let lines = line_range(self.start_row, self.end_row);
+        match &self.key {
+            Some(key) => format!("\"{key}\": {kind} {lines}"),
+            None => format!("{kind} {lines}"),
+        }
+    }
+}
+
+/// Lists the keys of a top-level object, or the items of a top-level array, with the kind and
+/// lines of their values. Scans the text rather than parsing it, so that comments and trailing
+/// commas don't get in the way.
+fn summarize_json(text: &str) -> Option<String> {
+    let top_level_kind = match text.trim_start().chars().next()? {
+        '{' => "object",
+        '[' => "array",
+        _ => return None,
+    };
+
+    let mut entries = Vec::new();
+    let mut current: Option<JsonEntry> = None;
+    let mut depth = 0;
+    let mut row = 0;
+    let mut last_key = None;
+    let mut awaiting_value = top_level_kind == "array";
+    let mut closed_row = None;
+    let mut characters = text.char_indices().peekable();
+    while let Some((offset, character)) = characters.next() {
+        if character == '\n' {
+            row += 1;
+        }
+        if character.is_whitespace() {
+            continue;
+        }
+
+        if character == '"' {
+            let mut escaped = false;
+            let mut string_end = text.len();
+            for (string_offset, string_character) in characters.by_ref() {
+                if escaped {
+                    escaped = false;
+                } else if string_character == '\\' {
+                    escaped = true;
+                } else if string_character == '"' {
+                    string_end = string_offset;
+                    break;
+                }
+            }
+            if depth == 1 && top_level_kind == "object" && current.is_none() && !awaiting_value {
+                last_key = Some(text[offset + 1..string_end].to_string());
+            }
+        } else if character == '/' && characters.peek().is_some_and(|(_, next)| *next == '/') {
+            while characters.next_if(|(_, next)| *next != '\n').is_some() {}
+            continue;
+        } else if character == '/' && characters.peek().is_some_and(|(_, next)| *next == '*') {
+            characters.next();
+            let mut previous = ' ';
+            for (_, comment_character) in characters.by_ref() {
+                if comment_character == '\n' {
+                    row += 1;
+                }
+                if previous == '*' && comment_character == '/' {
+                    break;
+                }
+                previous = comment_character;
+            }
+            continue;
+        }
+
+        let is_closing = matches!(character, '}' | ']');
+        if depth == 1 && character == ',' {
+            entries.extend(current.take());
+            awaiting_value = top_level_kind == "array";
+            continue;
+        }
+        if depth == 1 && character == ':' && top_level_kind == "object" {
+            awaiting_value = true;
+            continue;
+        }
+        if depth == 1 && awaiting_value && !is_closing {
+            awaiting_value = false;
+            current = Some(JsonEntry {
+                key: last_key.take(),
+                kind: match character {
+                    '{' => "object",
+                    '[' => "array",
+                    '"' => "string",
+                    't' | 'f' => "boolean",
+                    'n' => "null",
+                    _ => "number",
+                },
+                start_row: row,
+                end_row: row,
+                children: 0,
+                pending_comma: false,
+            });
+        }
+        if depth == 2
+            && !is_closing
+            && let Some(entry) = current.as_mut()
+        {
+            if character == ',' {
+                entry.pending_comma = true;
+            } else if entry.children == 0 || entry.pending_comma {
+                entry.children += 1;
+                entry.pending_comma = false;
+            }
+        }
+
+        match character {
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    entries.extend(current.take());
+                    closed_row = Some(row);
+                    break;
+                }
+            }
+            _ => {}
+        }
+        if let Some(entry) = current.as_mut() {
+            entry.end_row = row;
+        }
+    }
+
+    // Text that isn't balanced JSON isn't summarized as JSON.
+    let closed_row = closed_row?;
+    let mut summary = format!(
+        "Top-level {top_level_kind} with {} {}:\n",
+        pluralize(
+            entries.len(),
+            if top_level_kind == "object" {
+                "key"
+            } else {
+                "item"
+            }
+        ),
+        line_range(0, closed_row)
+    );
+    for entry in entries.iter().take(MAX_SUMMARY_ENTRIES) {
+        writeln!(summary, "{}", entry.describe()).ok();
+    }
+    if entries.len() > MAX_SUMMARY_ENTRIES {
+        writeln!(
+            summary,
+            "… and {} more",
+            entries.len() - MAX_SUMMARY_ENTRIES
+        )
+        .ok();
+    }
+    Some(summary)
+}
+
+/// Lists the top-level keys of each YAML document with their lines.
+fn summarize_yaml(text: &str) -> Option<String> {
+    let lines = text.lines().collect::<Vec<_>>();
+    let mut summary = String::new();
+    let mut listed = 0;
+    let mut skipped = 0;
+    let mut documents = 0;
+    let mut top_level_items = 0;
+    // The key whose value is being read, and the row it started on.
+    let mut open_key: Option<(&str, usize)> = None;
+    let mut last_content_row = 0;
+
+    let mut close_key = |summary: &mut String, open_key: &mut Option<(&str, usize)>, end_row| {
+        if let Some((key, start_row)) = open_key.take() {
+            if listed < MAX_SUMMARY_ENTRIES {
+                writeln!(summary, "{key}: {}", line_range(start_row, end_row)).ok();
+                listed += 1;
+            } else {
+                skipped += 1;
+            }
+        }
+    };
+
+    for (row, line) in lines.iter().enumerate() {
+        if line.starts_with("---") {
+            close_key(&mut summary, &mut open_key, last_content_row);
+            documents += 1;
+            writeln!(summary, "--- document {documents} [L{}]", row + 1).ok();
+            continue;
+        }
+        let trimmed = line.trim_end();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if !line.starts_with([' ', '\t']) {
+            if trimmed.starts_with("- ") || trimmed == "-" {
+                top_level_items += 1;
+            } else if let Some((key, _)) = trimmed.split_once(':') {
+                close_key(&mut summary, &mut open_key, last_content_row);
+                open_key = Some((key, row));
+            }
+        }
+        last_content_row = row;
+    }
+    close_key(&mut summary, &mut open_key, last_content_row);
+
+    if top_level_items > 0 {
+        writeln!(summary, "Top-level list with {top_level_items} items").ok();
+    }
+    if skipped > 0 {
+        writeln!(summary, "… and {skipped} more keys").ok();
+    }
+    if summary.lines().all(|line| line.starts_with("--- document")) {
+        return None;
+    }
+    Some(summary)
+}
+
+/// Shows the header, the number of rows, and the first rows.
+fn summarize_csv(text: &str, delimiter: char) -> Option<String> {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let header = lines.next()?;
+    let columns = header.split(delimiter).count();
+    let rows = lines.clone().count();
+    let mut summary = format!(
+        "Header [L1] ({columns} columns): {}\nRows: {rows}\n\nFirst rows:\n",
+        truncate_line(header)
+    );
+    for line in lines.take(5) {
+        writeln!(summary, "{}", truncate_line(line)).ok();
+    }
+    Some(summary)
+}
+
+/// Shows the first and last lines of a log, where most of the interesting entries usually are.
+fn summarize_log(text: &str) -> Option<String> {
+    let lines = text.lines().collect::<Vec<_>>();
+    if lines.is_empty() {
+        return None;
+    }
+    let mut summary = String::new();
+    let head_end = lines.len().min(LOG_LINES_PER_END);
+    let tail_start = lines.len().saturating_sub(LOG_LINES_PER_END).max(head_end);
+    for (row, line) in lines[..head_end].iter().enumerate() {
+        writeln!(summary, "L{}: {}", row + 1, truncate_line(line)).ok();
+    }
+    if tail_start > head_end {
+        writeln!(
+            summary,
+            "… {} lines omitted (L{}-{}) …",
+            tail_start - head_end,
+            head_end + 1,
+            tail_start
+        )
+        .ok();
+    }
+    for (row, line) in lines.iter().enumerate().skip(tail_start) {
+        writeln!(summary, "L{}: {}", row + 1, truncate_line(line)).ok();
+    }
+    Some(summary)
+}
+
+fn line_range(start_row: usize, end_row: usize) -> String {
+    if start_row == end_row {
+        format!("[L{}]", start_row + 1)
+    } else {
+        format!("[L{}-{}]", start_row + 1, end_row + 1)
+    }
+}
+
+fn pluralize(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {noun}")
+    } else {
+        format!("{count} {noun}s")
+    }
+}
+
+fn truncate_line(line: &str) -> String {
+    if line.chars().count() <= MAX_LINE_CHARS {
+        return line.to_string();
+    }
+    let truncated = line.chars().take(MAX_LINE_CHARS).collect::<String>();
+    format!("{truncated}…")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_structured_text() {
+        let json = "{\n  \"name\": \"app\",\n  \"dependencies\": {\n    \"a\": \"1\",\n    \"b\": \"2\"\n  },\n  \"files\": [1, 2, 3],\n  \"private\": true\n}\n";
+        assert_eq!(
+            summarize_structured_text(json, Some("root/package.json")),
+            Some((
+                "JSON",
+                "Top-level object with 4 keys [L1-9]:\n\"name\": string [L2]\n\"dependencies\": object with 2 keys [L3-6]\n\"files\": array with 3 items [L7]\n\"private\": boolean [L8]\n".to_string()
+            ))
+        );
+        assert_eq!(
+            summarize_structured_text("[{\"a\": 1}, {}]", None).map(|(_, summary)| summary),
+            Some("Top-level array with 2 items [L1]:\nobject with 1 key [L1]\nobject with 0 keys [L1]\n".to_string())
+        );
+        assert_eq!(
+            summarize_structured_text(
+                "{\n  // \"ignored\": {\n  \"a\": [1, 2,],\n}\n",
+                Some("settings.jsonc")
+            )
+            .map(|(_, summary)| summary),
+            Some(
+                "Top-level object with 1 key [L1-4]:\n\"a\": array with 2 items [L3]\n".to_string()
+            )
+        );
+        assert_eq!(summarize_structured_text("{\"unclosed\": [", None), None);
+
+        let yaml = "# config\nname: app\nservices:\n  web:\n    image: nginx\n---\nkind: Service\n";
+        assert_eq!(
+            summarize_structured_text(yaml, Some("deploy.yaml")).map(|(_, summary)| summary),
+            Some("name: [L2]\nservices: [L3-5]\n--- document 1 [L6]\nkind: [L7]\n".to_string())
+        );
+
+        let csv = "id,name\n1,a\n2,b\n";
+        assert_eq!(
+            summarize_structured_text(csv, Some("data.csv")).map(|(_, summary)| summary),
+            Some(
+                "Header [L1] (2 columns): id,name\nRows: 2\n\nFirst rows:\n1,a\n2,b\n".to_string()
+            )
+        );
+
+        let log = (1..=50)
+            .map(|line| format!("entry {line}\n"))
+            .collect::<String>();
+        let summary = summarize_structured_text(&log, Some("server.log"))
+            .map(|(_, summary)| summary)
+            .unwrap();
+        assert!(summary.starts_with("L1: entry 1\n"));
+        assert!(summary.contains("L20: entry 20\n… 10 lines omitted (L21-30) …\nL31: entry 31\n"));
+        assert!(summary.ends_with("L50: entry 50\n"));
+
+        assert_eq!(
+            summarize_structured_text("plain text", Some("notes.txt")),
+            None
+        );
+    }
+}
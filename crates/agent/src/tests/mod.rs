@@ -36,7 +36,7 @@ use reqwest_client::ReqwestClient;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use settings::{Settings, SettingsStore};
+use settings::{Settings, SettingsStore, ToolPermissionMode};
 use std::{
     path::Path,
     pin::Pin,
@@ -284,6 +284,72 @@ async fn test_terminal_tool_without_timeout_does_not_kill_handle(cx: &mut TestAp
     );
 }
 
+#[gpui::test]
+async fn test_run_command_tool_streams_output_and_times_out(cx: &mut TestAppContext) {
+    init_test(cx);
+    always_allow_tools(cx);
+
+    let fs = FakeFs::new(cx.executor());
+    let project = Project::test(fs, [], cx).await;
+
+    let handle = Rc::new(cx.update(|cx| FakeTerminalHandle::new_never_exits(cx)));
+    let environment = Rc::new(FakeThreadEnvironment {
+        handle: handle.clone(),
+    });
+
+    #[allow(clippy::arc_with_non_send_sync)]
+    let tool = Arc::new(crate::RunCommandTool::new(project, environment));
+    let (event_stream, mut rx) = crate::ToolCallEventStream::test();
+
+    let task = cx.update(|cx| {
+        tool.run(
+            crate::RunCommandToolInput {
+                command: "cargo test".to_string(),
+                cd: None,
+                timeout_ms: Some(300),
+            },
+            event_stream,
+            cx,
+        )
+    });
+
+    let update = rx.expect_update_fields().await;
+    assert!(
+        update.content.iter().any(|blocks| {
+            blocks
+                .iter()
+                .any(|c| matches!(c, acp::ToolCallContent::Terminal(_)))
+        }),
+        "expected tool call update to include terminal content"
+    );
+
+    let mut task_future: Pin<Box<Fuse<Task<Result<crate::RunCommandToolOutput>>>>> =
+        Box::pin(task.fuse());
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    let output = loop {
+        if let Some(result) = task_future.as_mut().now_or_never() {
+            break result.expect("run command task should complete");
+        }
+        if std::time::Instant::now() >= deadline {
+            panic!("timed out waiting for run command task to complete");
+        }
+        cx.run_until_parked();
+        cx.background_executor.timer(Duration::from_millis(1)).await;
+    };
+
+    assert!(handle.was_killed(), "expected the command to be killed");
+    assert!(output.timed_out);
+    assert_eq!(output.exit_code, None);
+    assert_eq!(output.output, "partial output");
+
+    let update = rx.expect_update_fields().await;
+    assert_eq!(
+        update.raw_output,
+        Some(json!({ "chunk": "partial output" })),
+        "expected output to be streamed before the command finished"
+    );
+}
+
 #[gpui::test]
 async fn test_thinking(cx: &mut TestAppContext) {
     let ThreadTest { model, thread, .. } = setup(cx, TestModel::Fake).await;
@@ -833,6 +899,226 @@ async fn test_tool_hallucination(cx: &mut TestAppContext) {
     assert_eq!(update.fields.status, Some(acp::ToolCallStatus::Failed));
 }
 
+#[gpui::test]
+async fn test_tool_permissions(cx: &mut TestAppContext) {
+    let ThreadTest { model, thread, .. } = setup(cx, TestModel::Fake).await;
+    let fake_model = model.as_fake();
+    cx.update(|cx| {
+        let mut settings = agent_settings::AgentSettings::get_global(cx).clone();
+        settings.tool_permissions.tools = [
+            (EchoTool::name().into(), ToolPermissionMode::Deny),
+            (
+                ToolRequiringPermission::name().into(),
+                ToolPermissionMode::Allow,
+            ),
+        ]
+        .into_iter()
+        .collect();
+        agent_settings::AgentSettings::override_global(settings, cx);
+    });
+
+    let mut events = thread
+        .update(cx, |thread, cx| {
+            thread.add_tool(EchoTool);
+            thread.add_tool(ToolRequiringPermission);
+            thread.send(UserMessageId::new(), ["abc"], cx)
+        })
+        .unwrap();
+    cx.run_until_parked();
+    fake_model.send_last_completion_stream_event(LanguageModelCompletionEvent::ToolUse(
+        LanguageModelToolUse {
+            id: "tool_id_1".into(),
+            name: EchoTool::name().into(),
+            raw_input: json!({"text": "hello"}).to_string(),
+            input: json!({"text": "hello"}),
+            is_input_complete: true,
+            thought_signature: None,
+        },
+    ));
+    fake_model.send_last_completion_stream_event(LanguageModelCompletionEvent::ToolUse(
+        LanguageModelToolUse {
+            id: "tool_id_2".into(),
+            name: ToolRequiringPermission::name().into(),
+            raw_input: "{}".into(),
+            input: json!({}),
+            is_input_complete: true,
+            thought_signature: None,
+        },
+    ));
+    fake_model.end_last_completion_stream();
+    cx.run_until_parked();
+
+    // The denied tool doesn't run, and the allowed one runs without asking.
+    let completion = fake_model.pending_completions().pop().unwrap();
+    let message = completion.messages.last().unwrap();
+    assert_eq!(
+        message.content,
+        vec![
+            language_model::MessageContent::ToolResult(LanguageModelToolResult {
+                tool_use_id: "tool_id_1".into(),
+                tool_name: EchoTool::name().into(),
+                is_error: true,
+                content: "The echo tool is not allowed to run by the user's \
+                    agent.tool_permissions setting"
+                    .into(),
+                output: None
+            }),
+            language_model::MessageContent::ToolResult(LanguageModelToolResult {
+                tool_use_id: "tool_id_2".into(),
+                tool_name: ToolRequiringPermission::name().into(),
+                is_error: false,
+                content: "Allowed".into(),
+                output: Some("Allowed".into())
+            })
+        ]
+    );
+
+    // A tool set to ask is confirmed once, and doesn't ask again while running.
+    cx.update(|cx| {
+        let mut settings = agent_settings::AgentSettings::get_global(cx).clone();
+        settings.tool_permissions.tools.insert(
+            ToolRequiringPermission::name().into(),
+            ToolPermissionMode::Ask,
+        );
+        agent_settings::AgentSettings::override_global(settings, cx);
+    });
+    fake_model.send_last_completion_stream_event(LanguageModelCompletionEvent::ToolUse(
+        LanguageModelToolUse {
+            id: "tool_id_3".into(),
+            name: ToolRequiringPermission::name().into(),
+            raw_input: "{}".into(),
+            input: json!({}),
+            is_input_complete: true,
+            thought_signature: None,
+        },
+    ));
+    fake_model.end_last_completion_stream();
+    let tool_call_auth = next_tool_call_authorization(&mut events).await;
+    tool_call_auth
+        .response
+        .send(tool_call_auth.options[1].option_id.clone())
+        .unwrap();
+    cx.run_until_parked();
+    let completion = fake_model.pending_completions().pop().unwrap();
+    let message = completion.messages.last().unwrap();
+    assert_eq!(
+        message.content,
+        vec![language_model::MessageContent::ToolResult(
+            LanguageModelToolResult {
+                tool_use_id: "tool_id_3".into(),
+                tool_name: ToolRequiringPermission::name().into(),
+                is_error: false,
+                content: "Allowed".into(),
+                output: Some("Allowed".into())
+            }
+        )]
+    );
+}
+
+#[gpui::test]
+async fn test_tool_audit_log(cx: &mut TestAppContext) {
+    let ThreadTest { model, thread, .. } = setup(cx, TestModel::Fake).await;
+    let fake_model = model.as_fake();
+
+    thread
+        .update(cx, |thread, cx| {
+            thread.add_tool(EchoTool);
+            thread.send(UserMessageId::new(), ["abc"], cx)
+        })
+        .unwrap();
+    cx.run_until_parked();
+    fake_model.send_last_completion_stream_event(LanguageModelCompletionEvent::ToolUse(
+        LanguageModelToolUse {
+            id: "tool_id_1".into(),
+            name: EchoTool::name().into(),
+            raw_input: json!({"text": "hello"}).to_string(),
+            input: json!({"text": "hello"}),
+            is_input_complete: true,
+            thought_signature: None,
+        },
+    ));
+    fake_model.send_last_completion_stream_event(LanguageModelCompletionEvent::ToolUse(
+        LanguageModelToolUse {
+            id: "tool_id_2".into(),
+            name: "nonexistent_tool".into(),
+            raw_input: "{}".into(),
+            input: json!({}),
+            is_input_complete: true,
+            thought_signature: None,
+        },
+    ));
+    fake_model.end_last_completion_stream();
+    cx.run_until_parked();
+
+    thread.read_with(cx, |thread, _| {
+        let entries = thread.tool_audit_log().entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].tool_name.as_ref(), "nonexistent_tool");
+        assert!(!entries[0].succeeded);
+        assert_eq!(entries[1].tool_name.as_ref(), EchoTool::name());
+        assert_eq!(entries[1].input, json!({"text": "hello"}));
+        assert_eq!(entries[1].output_summary, "hello");
+        assert!(entries[1].succeeded);
+    });
+
+    let replayed = thread
+        .update(cx, |thread, cx| thread.replay_audited_tool_call(1, cx))
+        .await
+        .unwrap();
+    assert_eq!(replayed.tool_use_id, "tool_id_1".into());
+    assert_eq!(replayed.output_summary, "hello");
+    assert!(replayed.succeeded);
+    thread
+        .update(cx, |thread, cx| thread.replay_audited_tool_call(2, cx))
+        .await
+        .unwrap_err();
+
+    let db_thread = thread.read_with(cx, |thread, cx| thread.to_db(cx)).await;
+    assert_eq!(db_thread.tool_audit_log.entries().len(), 2);
+}
+
+#[gpui::test]
+async fn test_replay_refuses_truncated_tool_input(cx: &mut TestAppContext) {
+    let ThreadTest { model, thread, .. } = setup(cx, TestModel::Fake).await;
+    let fake_model = model.as_fake();
+
+    thread
+        .update(cx, |thread, cx| {
+            thread.add_tool(EchoTool);
+            thread.send(UserMessageId::new(), ["abc"], cx)
+        })
+        .unwrap();
+    cx.run_until_parked();
+    let long_text = "z".repeat(300);
+    fake_model.send_last_completion_stream_event(LanguageModelCompletionEvent::ToolUse(
+        LanguageModelToolUse {
+            id: "tool_id_1".into(),
+            name: EchoTool::name().into(),
+            raw_input: json!({"text": long_text}).to_string(),
+            input: json!({"text": long_text}),
+            is_input_complete: true,
+            thought_signature: None,
+        },
+    ));
+    fake_model.end_last_completion_stream();
+    cx.run_until_parked();
+
+    thread.read_with(cx, |thread, _| {
+        let entries = thread.tool_audit_log().entries();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].input_truncated);
+        assert_ne!(entries[0].input, json!({"text": long_text}));
+    });
+    let error = thread
+        .update(cx, |thread, cx| thread.replay_audited_tool_call(0, cx))
+        .await
+        .unwrap_err();
+    assert!(
+        error.to_string().contains("can't be replayed"),
+        "unexpected error: {error}"
+    );
+}
+
 #[gpui::test]
 async fn test_resume_after_tool_use_limit(cx: &mut TestAppContext) {
     let ThreadTest { model, thread, .. } = setup(cx, TestModel::Fake).await;
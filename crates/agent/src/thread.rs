@@ -1,10 +1,18 @@
 use crate::{
-    ContextServerRegistry, CopyPathTool, CreateDirectoryTool, DbLanguageModel, DbThread,
-    DeletePathTool, DiagnosticsTool, EditFileTool, FetchTool, FindPathTool,
-    FindReferencesByContextTool, GotoDefinitionByContextTool, GrepTool, ListDirectoryTool,
-    MovePathTool, NowTool, OpenTool, ProjectSnapshot, ReadFileTool, RestoreFileFromDiskTool,
-    SaveFileTool, SystemPromptTemplate, Template, Templates, TerminalTool, ThinkingTool,
-    WebSearchTool,
+    AnchorMemory, AnchorResolutionError, BatchEditByContextTool, CallHierarchyByContextTool,
+    CargoCheckTool, CodeActionsByContextTool, CompletionsByContextTool, ContextServerRegistry,
+    CopyPathTool, CreateDirectoryTool, CreateFileTool, DbLanguageModel, DbThread, DeletePathTool,
+    DiagnosticsTool, DocumentSymbolsTool, EditByContextTool, EditFileTool, ExpandSymbolTool,
+    ExplainSymbolByContextTool, FetchTool, FileOutlineTool, FindImplementationsByContextTool,
+    FindPathTool, FindReferencesByContextTool, FormatTool, GitBlameTool, GitCommitTool,
+    GitDiffTool, GitLogTool, GitStageTool, GitStatusTool, GotoDefinitionByContextTool, GrepTool,
+    HoverInfoByContextTool, InlayHintsTool, ListDirectoryTool, MovePathTool, NavigateByContextTool,
+    NowTool, OpenTool, OrganizeImportsTool, ProjectMapTool, ProjectSnapshot, ReadFileTool,
+    RenameSymbolByContextTool, ResolveAnchorsTool, RestoreFileFromDiskTool, RunCommandTool,
+    RunTestsTool, SaveFileTool, SearchReplaceTool, SelectionAnchor, SemanticSearchTool,
+    SymbolDocsByContextTool, SystemPromptTemplate, Template, Templates, TerminalTool, ThinkingTool,
+    ToolAuditEntry, ToolAuditLog, ToolCallAccess, ToolPermissionClass, ToolScheduler, ToolSchema,
+    WebSearchTool, anchor_for_selection, outline, schema_to_value, tool_permission_mode,
 };
 use acp_thread::{MentionUri, UserMessageId};
 use action_log::ActionLog;
@@ -19,6 +27,7 @@ use chrono::{DateTime, Utc};
 use client::{ModelRequestUsage, RequestUsage, UserStore};
 use cloud_llm_client::{CompletionIntent, Plan, UsageLimit};
 use collections::{HashMap, HashSet, IndexMap};
+use feature_flags::{AgentExperimentalToolsFeatureFlag, FeatureFlagAppExt as _};
 use fs::Fs;
 use futures::stream;
 use futures::{
@@ -30,6 +39,7 @@ use futures::{
 use gpui::{
     App, AppContext, AsyncApp, Context, Entity, EventEmitter, SharedString, Task, WeakEntity,
 };
+use language::Buffer;
 use language_model::{
     LanguageModel, LanguageModelCompletionError, LanguageModelCompletionEvent, LanguageModelExt,
     LanguageModelId, LanguageModelImage, LanguageModelProviderId, LanguageModelRegistry,
@@ -38,15 +48,17 @@ use language_model::{
     LanguageModelToolUse, LanguageModelToolUseId, Role, SelectedModel, StopReason, TokenUsage,
     ZED_CLOUD_PROVIDER_ID,
 };
+use parking_lot::Mutex;
 use project::Project;
 use prompt_store::ProjectContext;
 use schemars::{JsonSchema, Schema};
 use serde::{Deserialize, Serialize};
-use settings::{LanguageModelSelection, Settings, update_settings_file};
+use settings::{LanguageModelSelection, Settings, ToolPermissionMode, update_settings_file};
 use smol::stream::StreamExt;
 use std::{
+    any::Any,
     collections::BTreeMap,
-    ops::RangeInclusive,
+    ops::{Range, RangeInclusive},
     path::Path,
     rc::Rc,
     sync::Arc,
@@ -428,7 +440,8 @@ impl AgentMessage {
             }
 
             match &tool_result.content {
-                LanguageModelToolResultContent::Text(text) => {
+                LanguageModelToolResultContent::Text(text)
+                | LanguageModelToolResultContent::Structured { text, .. } => {
                     writeln!(markdown, "{text}\n").ok();
                 }
                 LanguageModelToolResultContent::Image(_) => {
@@ -623,6 +636,17 @@ pub struct Thread {
     pub(crate) action_log: Entity<ActionLog>,
     /// Tracks the last time files were read by the agent, to detect external modifications
     pub(crate) file_read_times: HashMap<PathBuf, fs::MTime>,
+    /// Anchors resolved by the navigation tools, so later calls can refer to them by handle
+    anchor_memory: Entity<AnchorMemory>,
+    /// Work tools started while their input was streaming, kept alive until the call finishes
+    tool_preparations: HashMap<LanguageModelToolUseId, Task<Result<ToolPreparation>>>,
+    /// Whether mutating tools only report the changes they would make, so that a plan can be
+    /// reviewed before anything is applied
+    dry_run: bool,
+    /// Every tool call made in the thread, persisted with it for debugging what the agent did
+    tool_audit_log: ToolAuditLog,
+    /// Makes tool calls that change the same files run one after the other
+    tool_scheduler: ToolScheduler,
 }
 
 impl Thread {
@@ -679,6 +703,11 @@ impl Thread {
             project,
             action_log,
             file_read_times: HashMap::default(),
+            anchor_memory: cx.new(|_| AnchorMemory::default()),
+            tool_preparations: HashMap::default(),
+            dry_run: false,
+            tool_audit_log: ToolAuditLog::default(),
+            tool_scheduler: ToolScheduler::default(),
         }
     }
 
@@ -770,6 +799,8 @@ impl Thread {
                 tool_use.id.clone(),
                 stream.clone(),
                 Some(self.project.read(cx).fs().clone()),
+                watch::Receiver::constant(false),
+                false,
             );
             tool.replay(tool_use.input.clone(), output, tool_event_stream, cx)
                 .log_err();
@@ -867,6 +898,11 @@ impl Thread {
             prompt_capabilities_tx,
             prompt_capabilities_rx,
             file_read_times: HashMap::default(),
+            anchor_memory: cx.new(|_| AnchorMemory::default()),
+            tool_preparations: HashMap::default(),
+            dry_run: false,
+            tool_audit_log: db_thread.tool_audit_log,
+            tool_scheduler: ToolScheduler::default(),
         }
     }
 
@@ -886,6 +922,7 @@ impl Thread {
             }),
             completion_mode: Some(self.completion_mode),
             profile: Some(self.profile_id.clone()),
+            tool_audit_log: self.tool_audit_log.clone(),
         };
 
         cx.background_spawn(async move {
@@ -923,6 +960,21 @@ impl Thread {
         &self.action_log
     }
 
+    /// Builds an anchor for the user's `selection` in `buffer` and remembers it for the thread, so
+    /// that the user can hand the agent a precise location by adding its description to a message.
+    pub fn anchor_selection(
+        &mut self,
+        buffer: &Entity<Buffer>,
+        selection: Range<text::Anchor>,
+        cx: &mut Context<Self>,
+    ) -> Option<SelectionAnchor> {
+        let anchor = anchor_for_selection(&self.project, buffer, selection, cx)?;
+        let handles = self
+            .anchor_memory
+            .update(cx, |anchor_memory, _| anchor_memory.remember(&anchor));
+        Some(SelectionAnchor { anchor, handles })
+    }
+
     pub fn is_empty(&self) -> bool {
         self.messages.is_empty() && self.title.is_none()
     }
@@ -970,6 +1022,95 @@ impl Thread {
         cx.notify()
     }
 
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Makes mutating tools report the changes they would make instead of applying them, starting
+    /// with the next tool call.
+    pub fn set_dry_run(&mut self, dry_run: bool, cx: &mut Context<Self>) {
+        self.dry_run = dry_run;
+        cx.notify()
+    }
+
+    pub fn tool_audit_log(&self) -> &ToolAuditLog {
+        &self.tool_audit_log
+    }
+
+    /// Runs the audit log's `index`th tool call again as a dry run, to compare what it resolves to
+    /// now with what it did then, without changing anything. Tools that could run commands aren't
+    /// replayed, because a dry run doesn't stop all of them.
+    pub fn replay_audited_tool_call(
+        &self,
+        index: usize,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<ToolAuditEntry>> {
+        let Some(entry) = self.tool_audit_log.entries().get(index) else {
+            return Task::ready(Err(anyhow!(
+                "No tool call at index {index} in the audit log"
+            )));
+        };
+        let Some(tool) = self.tool(&entry.tool_name) else {
+            return Task::ready(Err(anyhow!("No tool named {} exists", entry.tool_name)));
+        };
+        if entry.input_truncated {
+            return Task::ready(Err(anyhow!(
+                "The {} call can't be replayed, because the audit log only kept the start of its \
+                long input",
+                entry.tool_name
+            )));
+        }
+        if tool.permission_class() == ToolPermissionClass::SystemExecuting {
+            return Task::ready(Err(anyhow!(
+                "The {} tool can't be replayed, because it could run commands",
+                entry.tool_name
+            )));
+        }
+
+        // Nobody listens to the replay's events, so tools that ask for authorization are denied.
+        let (events_tx, _) = mpsc::unbounded();
+        let tool_event_stream = ToolCallEventStream::new(
+            entry.tool_use_id.clone(),
+            ThreadEventStream(events_tx),
+            Some(self.project.read(cx).fs().clone()),
+            watch::Receiver::constant(false),
+            true,
+        );
+        let reported_locations = tool_event_stream.reported_locations();
+        let input = entry.input.clone();
+        let tool_use_id = entry.tool_use_id.clone();
+        let tool_name = entry.tool_name.clone();
+        let started_at = Utc::now();
+        let start = Instant::now();
+        let tool_result = tool.run(input.clone(), tool_event_stream, cx);
+        cx.foreground_executor().spawn(async move {
+            let tool_result = match tool_result.await {
+                Ok(output) => LanguageModelToolResult {
+                    tool_use_id,
+                    tool_name,
+                    is_error: false,
+                    content: output.llm_output,
+                    output: Some(output.raw_output),
+                },
+                Err(error) => LanguageModelToolResult {
+                    tool_use_id,
+                    tool_name,
+                    is_error: true,
+                    content: LanguageModelToolResultContent::Text(Arc::from(error.to_string())),
+                    output: Some(tool_error_output(&error)),
+                },
+            };
+            let locations = reported_locations.lock().clone();
+            Ok(ToolAuditEntry::new(
+                input,
+                &tool_result,
+                locations,
+                started_at,
+                start.elapsed(),
+            ))
+        })
+    }
+
     #[cfg(any(test, feature = "test-support"))]
     pub fn last_message(&self) -> Option<Message> {
         if let Some(message) = self.pending_message.clone() {
@@ -985,45 +1126,166 @@ impl Thread {
         cx: &mut Context<Self>,
     ) {
         let language_registry = self.project.read(cx).languages().clone();
+        let experimental_tools_enabled = cx.has_flag::<AgentExperimentalToolsFeatureFlag>()
+            || AgentSettings::get_global(cx).enable_experimental_tools;
+        self.add_tool(BatchEditByContextTool::new(
+            self.project.clone(),
+            self.action_log.clone(),
+            self.anchor_memory.clone(),
+        ));
+        self.add_tool(CallHierarchyByContextTool::new(
+            self.project.clone(),
+            self.anchor_memory.clone(),
+        ));
+        self.add_tool(CodeActionsByContextTool::new(
+            self.project.clone(),
+            self.action_log.clone(),
+            self.anchor_memory.clone(),
+        ));
+        self.add_tool(CompletionsByContextTool::new(
+            self.project.clone(),
+            self.anchor_memory.clone(),
+        ));
         self.add_tool(CopyPathTool::new(self.project.clone()));
         self.add_tool(CreateDirectoryTool::new(self.project.clone()));
+        self.add_tool(CreateFileTool::new(
+            self.project.clone(),
+            self.action_log.clone(),
+        ));
         self.add_tool(DeletePathTool::new(
             self.project.clone(),
             self.action_log.clone(),
         ));
         self.add_tool(DiagnosticsTool::new(self.project.clone()));
+        self.add_tool(DocumentSymbolsTool::new(self.project.clone()));
+        self.add_tool(EditByContextTool::new(
+            self.project.clone(),
+            self.action_log.clone(),
+            self.anchor_memory.clone(),
+        ));
         self.add_tool(EditFileTool::new(
             self.project.clone(),
             cx.weak_entity(),
             language_registry,
             Templates::new(),
         ));
+        self.add_tool(ExplainSymbolByContextTool::new(
+            self.project.clone(),
+            self.anchor_memory.clone(),
+        ));
         self.add_tool(FetchTool::new(self.project.read(cx).client().http_client()));
+        self.add_tool(FileOutlineTool::new(self.project.clone()));
+        self.add_tool(ExpandSymbolTool::new(self.project.clone()));
+        self.add_tool(ProjectMapTool::new(self.project.clone()));
+        self.add_tool(FindImplementationsByContextTool::new(
+            self.project.clone(),
+            self.anchor_memory.clone(),
+        ));
         self.add_tool(FindPathTool::new(self.project.clone()));
         self.add_tool(GrepTool::new(self.project.clone()));
-        // Agent LSP helpers: enable context-based goto-definition & find-references tools
-        self.add_tool(GotoDefinitionByContextTool::new(self.project.clone()));
-        self.add_tool(FindReferencesByContextTool::new(self.project.clone()));
+        self.add_tool(GotoDefinitionByContextTool::new(
+            self.project.clone(),
+            self.anchor_memory.clone(),
+        ));
+        self.add_tool(FindReferencesByContextTool::new(
+            self.project.clone(),
+            self.anchor_memory.clone(),
+        ));
+        self.add_tool(FormatTool::new(
+            self.project.clone(),
+            self.action_log.clone(),
+            self.anchor_memory.clone(),
+        ));
+        self.add_tool(GitBlameTool::new(
+            self.project.clone(),
+            self.anchor_memory.clone(),
+        ));
+        self.add_tool(GitCommitTool::new(self.project.clone()));
+        self.add_tool(GitDiffTool::new(self.project.clone()));
+        self.add_tool(GitLogTool::new(
+            self.project.clone(),
+            self.anchor_memory.clone(),
+        ));
+        self.add_tool(GitStageTool::new(self.project.clone()));
+        self.add_tool(GitStatusTool::new(self.project.clone()));
+        self.add_tool(HoverInfoByContextTool::new(
+            self.project.clone(),
+            self.anchor_memory.clone(),
+        ));
+        self.add_tool(InlayHintsTool::new(self.project.clone()));
         self.add_tool(ListDirectoryTool::new(self.project.clone()));
         self.add_tool(MovePathTool::new(self.project.clone()));
+        self.add_tool(NavigateByContextTool::new(
+            self.project.clone(),
+            self.anchor_memory.clone(),
+        ));
         self.add_tool(NowTool);
         self.add_tool(OpenTool::new(self.project.clone()));
+        self.add_tool(OrganizeImportsTool::new(
+            self.project.clone(),
+            self.action_log.clone(),
+        ));
         self.add_tool(ReadFileTool::new(
             cx.weak_entity(),
             self.project.clone(),
             self.action_log.clone(),
         ));
+        self.add_tool(RenameSymbolByContextTool::new(
+            self.project.clone(),
+            self.action_log.clone(),
+            self.anchor_memory.clone(),
+        ));
+        self.add_tool(ResolveAnchorsTool::new(
+            self.project.clone(),
+            self.anchor_memory.clone(),
+        ));
         self.add_tool(SaveFileTool::new(self.project.clone()));
+        self.add_tool(SearchReplaceTool::new(
+            self.project.clone(),
+            self.action_log.clone(),
+        ));
+        self.add_tool(SemanticSearchTool::new(self.project.clone()));
+        self.add_tool(SymbolDocsByContextTool::new(
+            self.project.clone(),
+            self.anchor_memory.clone(),
+        ));
         self.add_tool(RestoreFileFromDiskTool::new(self.project.clone()));
+        self.add_tool(CargoCheckTool::new(
+            self.project.clone(),
+            environment.clone(),
+        ));
+        self.add_tool(RunCommandTool::new(
+            self.project.clone(),
+            environment.clone(),
+        ));
+        self.add_tool(RunTestsTool::new(self.project.clone(), environment.clone()));
         self.add_tool(TerminalTool::new(self.project.clone(), environment));
         self.add_tool(ThinkingTool);
         self.add_tool(WebSearchTool);
+        if !experimental_tools_enabled {
+            self.tools.retain(|_, tool| !tool.is_experimental());
+        }
     }
 
     pub fn add_tool<T: AgentTool>(&mut self, tool: T) {
         self.tools.insert(T::name().into(), tool.erase());
     }
 
+    /// Returns the schemas of every tool added to this thread, whether or not the current profile
+    /// enables it, in the form they are declared to providers that use `format`.
+    pub fn tool_schemas(&self, format: LanguageModelToolSchemaFormat) -> Result<Vec<ToolSchema>> {
+        self.tools
+            .values()
+            .map(|tool| ToolSchema::for_any_tool(tool.as_ref(), format))
+            .collect()
+    }
+
+    /// Returns a tool added to this thread, whether or not the current profile enables it.
+    #[cfg(feature = "eval")]
+    pub fn registered_tool(&self, name: &str) -> Option<Arc<dyn AnyAgentTool>> {
+        self.tools.get(name).cloned()
+    }
+
     pub fn remove_tool(&mut self, name: &str) -> bool {
         self.tools.remove(name).is_some()
     }
@@ -1049,6 +1311,7 @@ impl Thread {
         if let Some(running_turn) = self.running_turn.take() {
             running_turn.cancel();
         }
+        self.tool_preparations.clear();
         self.flush_pending_message(cx);
     }
 
@@ -1099,6 +1362,20 @@ impl Thread {
         })
     }
 
+    /// The budget for the content of a file the agent reads without a line range: a share of
+    /// what the latest request left of the model's context window.
+    pub fn file_token_budget(&self) -> Option<outline::TokenBudget> {
+        let model = self.model.clone()?;
+        let context_window = model.max_token_count_for_mode(self.completion_mode.into());
+        let used_tokens = self
+            .latest_request_token_usage()
+            .map_or(0, |usage| usage.total_tokens());
+        Some(outline::TokenBudget::for_file(
+            model,
+            context_window.saturating_sub(used_tokens),
+        ))
+    }
+
     /// Get the total input token count as of the message before the given message.
     ///
     /// Returns `None` if:
@@ -1259,6 +1536,7 @@ impl Thread {
         self.clear_summary();
         self.running_turn = Some(RunningTurn {
             event_stream: event_stream.clone(),
+            cancellation_tx: watch::channel(false).0,
             tools: self.enabled_tools(profile, &model, cx),
             _task: cx.spawn(async move |this, cx| {
                 log::debug!("Starting agent turn execution");
@@ -1617,7 +1895,7 @@ impl Thread {
             event_stream.send_tool_call(
                 &tool_use.id,
                 &tool_use.name,
-                title,
+                title.clone(),
                 kind,
                 tool_use.input.clone(),
             );
@@ -1635,30 +1913,99 @@ impl Thread {
         }
 
         if !tool_use.is_input_complete {
+            if let Some(tool) = tool
+                && !self.tool_preparations.contains_key(&tool_use.id)
+                && let Some(preparation) = tool.prepare(tool_use.input, cx)
+            {
+                self.tool_preparations.insert(tool_use.id, preparation);
+            }
             return None;
         }
+        let preparation = self.tool_preparations.remove(&tool_use.id);
 
         let Some(tool) = tool else {
             let content = format!("No tool named {} exists", tool_use.name);
-            return Some(Task::ready(LanguageModelToolResult {
-                content: LanguageModelToolResultContent::Text(Arc::from(content)),
-                tool_use_id: tool_use.id,
-                tool_name: tool_use.name,
-                is_error: true,
-                output: None,
-            }));
+            return Some(self.fail_tool_use(tool_use, content));
         };
 
+        let permission_mode = tool_permission_mode(&tool_use.name, tool.permission_class(), cx);
+        if permission_mode == Some(ToolPermissionMode::Deny) {
+            let content = format!(
+                "The {} tool is not allowed to run by the user's agent.tool_permissions setting",
+                tool_use.name
+            );
+            return Some(self.fail_tool_use(tool_use, content));
+        }
+
         let fs = self.project.read(cx).fs().clone();
-        let tool_event_stream =
-            ToolCallEventStream::new(tool_use.id.clone(), event_stream.clone(), Some(fs));
-        tool_event_stream.update_fields(
-            acp::ToolCallUpdateFields::new().status(acp::ToolCallStatus::InProgress),
+        let cancellation_rx = self.running_turn.as_ref().map_or_else(
+            || watch::Receiver::constant(false),
+            |running_turn| running_turn.cancellation_tx.receiver(),
         );
+        let tool_event_stream = ToolCallEventStream::new(
+            tool_use.id.clone(),
+            event_stream.clone(),
+            Some(fs),
+            cancellation_rx,
+            self.dry_run,
+        )
+        .with_token_budget(self.file_token_budget());
+        let reported_locations = tool_event_stream.reported_locations();
+        let scheduled_call = self
+            .tool_scheduler
+            .schedule(&tool.call_access(&tool_use.input, cx));
+        let earlier_calls_finished = scheduled_call
+            .is_blocked()
+            .then(|| scheduled_call.earlier_calls_finished());
+        tool_event_stream.update_fields(acp::ToolCallUpdateFields::new().status(
+            if earlier_calls_finished.is_some() {
+                acp::ToolCallStatus::Pending
+            } else {
+                acp::ToolCallStatus::InProgress
+            },
+        ));
         let supports_images = self.model().is_some_and(|model| model.supports_images());
-        let tool_result = tool.run(tool_use.input, tool_event_stream, cx);
-        log::debug!("Running tool {}", tool_use.name);
-        Some(cx.foreground_executor().spawn(async move {
+        let tool_span = ztracing::info_span!(
+            "agent_tool",
+            tool = %tool_use.name,
+            duration_ms = ztracing::field::Empty,
+            output_len = ztracing::field::Empty,
+            is_error = ztracing::field::Empty
+        );
+        let audited_input = tool_use.input.clone();
+        let audited_at = Utc::now();
+        let started_at = Instant::now();
+        let tool_result = {
+            let _enter = tool_span.enter();
+            let mut tool_event_stream = if permission_mode == Some(ToolPermissionMode::Allow) {
+                tool_event_stream.into_authorized()
+            } else {
+                tool_event_stream
+            };
+            let authorization = (permission_mode == Some(ToolPermissionMode::Ask))
+                .then(|| tool_event_stream.authorize(title.to_string(), cx));
+            if authorization.is_none() && earlier_calls_finished.is_none() {
+                tool.run(tool_use.input, tool_event_stream, cx)
+            } else {
+                let input = tool_use.input;
+                cx.spawn(async move |_, cx| {
+                    if let Some(authorization) = authorization {
+                        authorization.await?;
+                        tool_event_stream = tool_event_stream.into_authorized();
+                    }
+                    if let Some(earlier_calls_finished) = earlier_calls_finished {
+                        earlier_calls_finished.await;
+                        tool_event_stream.update_fields(
+                            acp::ToolCallUpdateFields::new()
+                                .status(acp::ToolCallStatus::InProgress),
+                        );
+                    }
+                    cx.update(|cx| tool.run(input, tool_event_stream, cx))?
+                        .await
+                })
+            }
+        };
+        Some(cx.spawn(async move |this, cx| {
             let tool_result = tool_result.await.and_then(|output| {
                 if let LanguageModelToolResultContent::Image(_) = &output.llm_output
                     && !supports_images
@@ -1669,8 +2016,17 @@ impl Thread {
                 }
                 Ok(output)
             });
+            drop(preparation);
+            drop(scheduled_call);
+            tool_span.record("duration_ms", started_at.elapsed().as_millis() as u64);
+            tool_span.record("is_error", tool_result.is_err());
+            if let Ok(output) = &tool_result
+                && let Some(text) = output.llm_output.to_str()
+            {
+                tool_span.record("output_len", text.len());
+            }
 
-            match tool_result {
+            let tool_result = match tool_result {
                 Ok(output) => LanguageModelToolResult {
                     tool_use_id: tool_use.id,
                     tool_name: tool_use.name,
@@ -1683,12 +2039,45 @@ impl Thread {
                     tool_name: tool_use.name,
                     is_error: true,
                     content: LanguageModelToolResultContent::Text(Arc::from(error.to_string())),
-                    output: Some(error.to_string().into()),
+                    output: Some(tool_error_output(&error)),
                 },
-            }
+            };
+            let entry = ToolAuditEntry::new(
+                audited_input,
+                &tool_result,
+                reported_locations.lock().clone(),
+                audited_at,
+                started_at.elapsed(),
+            );
+            this.update(cx, |this, _| this.tool_audit_log.record(entry))
+                .ok();
+            tool_result
         }))
     }
 
+    /// Fails a tool call without running the tool.
+    fn fail_tool_use(
+        &mut self,
+        tool_use: LanguageModelToolUse,
+        content: String,
+    ) -> Task<LanguageModelToolResult> {
+        let tool_result = LanguageModelToolResult {
+            content: LanguageModelToolResultContent::Text(Arc::from(content)),
+            tool_use_id: tool_use.id,
+            tool_name: tool_use.name,
+            is_error: true,
+            output: None,
+        };
+        self.tool_audit_log.record(ToolAuditEntry::new(
+            tool_use.input,
+            &tool_result,
+            Vec::new(),
+            Utc::now(),
+            Duration::ZERO,
+        ));
+        Task::ready(tool_result)
+    }
+
     fn handle_tool_use_json_parse_error_event(
         &mut self,
         tool_use_id: LanguageModelToolUseId,
@@ -1697,13 +2086,21 @@ impl Thread {
         json_parse_error: String,
     ) -> LanguageModelToolResult {
         let tool_output = format!("Error parsing input JSON: {json_parse_error}");
-        LanguageModelToolResult {
+        let tool_result = LanguageModelToolResult {
             tool_use_id,
             tool_name,
             is_error: true,
             content: LanguageModelToolResultContent::Text(tool_output.into()),
             output: Some(serde_json::Value::String(raw_input.to_string())),
-        }
+        };
+        self.tool_audit_log.record(ToolAuditEntry::new(
+            serde_json::Value::String(raw_input.to_string()),
+            &tool_result,
+            Vec::new(),
+            Utc::now(),
+            Duration::ZERO,
+        ));
+        tool_result
     }
 
     fn update_model_request_usage(&self, amount: usize, limit: UsageLimit, cx: &mut Context<Self>) {
@@ -2231,13 +2628,16 @@ struct RunningTurn {
     /// The current event stream for the running turn. Used to report a final
     /// cancellation event if we cancel the turn.
     event_stream: ThreadEventStream,
+    /// Set when the turn is canceled, for tools that wait on something they can give up on.
+    cancellation_tx: watch::Sender<bool>,
     /// The tools that were enabled for this turn.
     tools: BTreeMap<SharedString, Arc<dyn AnyAgentTool>>,
 }
 
 impl RunningTurn {
-    fn cancel(self) {
+    fn cancel(mut self) {
         log::debug!("Cancelling in progress turn");
+        self.cancellation_tx.send(true).ok();
         self.event_stream.send_canceled();
     }
 }
@@ -2271,6 +2671,17 @@ where
 
     fn kind() -> acp::ToolKind;
 
+    /// Which `agent.tool_permissions` group the tool falls under, when its kind doesn't say.
+    fn permission_class() -> ToolPermissionClass {
+        ToolPermissionClass::for_kind(Self::kind())
+    }
+
+    /// What a call with the given input may change. Calls changing the same files run one after
+    /// the other, while the rest run concurrently.
+    fn call_access(&self, _input: &Self::Input, _cx: &App) -> ToolCallAccess {
+        ToolCallAccess::for_class(Self::permission_class())
+    }
+
     /// The initial tool title to display. Can be updated during the tool run.
     fn initial_title(
         &self,
@@ -2283,12 +2694,41 @@ where
         language_model::tool_schema::root_schema_for::<Self::Input>(format)
     }
 
+    /// Returns the JSON schema that describes the tool's raw output, for tools whose output is
+    /// structured rather than plain text.
+    fn output_schema(_format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        None
+    }
+
+    /// Example inputs, exported alongside the tool's schemas to document typical usage.
+    fn input_examples() -> Vec<Self::Input> {
+        Vec::new()
+    }
+
     /// Some tools rely on a provider for the underlying billing or other reasons.
     /// Allow the tool to check if they are compatible, or should be filtered out.
     fn supports_provider(_provider: &LanguageModelProviderId) -> bool {
         true
     }
 
+    /// Experimental tools are left out by [`Thread::add_default_tools`] unless the user has the
+    /// `agent-experimental-tools` feature flag or enabled `agent.enable_experimental_tools`, so
+    /// that new tools can ship dark and be rolled out gradually.
+    fn is_experimental() -> bool {
+        false
+    }
+
+    /// Called while a call's input is still streaming in, with the part of it that parses so far,
+    /// so that the tool can start slow work the call will need, such as opening its file and
+    /// letting language servers index it. The preparation is kept alive until the call finishes.
+    fn prepare(
+        self: Arc<Self>,
+        _input: Self::Input,
+        _cx: &mut App,
+    ) -> Option<Task<Result<ToolPreparation>>> {
+        None
+    }
+
     /// Runs the tool with the provided input.
     fn run(
         self: Arc<Self>,
@@ -2320,15 +2760,44 @@ pub struct AgentToolOutput {
     pub raw_output: serde_json::Value,
 }
 
+/// Whatever a tool started in [`AgentTool::prepare`] and needs to keep alive, such as an open
+/// buffer's registration with its language servers.
+pub type ToolPreparation = Box<dyn Any>;
+
 pub trait AnyAgentTool {
     fn name(&self) -> SharedString;
     fn description(&self) -> SharedString;
     fn kind(&self) -> acp::ToolKind;
+    fn permission_class(&self) -> ToolPermissionClass {
+        ToolPermissionClass::for_kind(self.kind())
+    }
+    fn call_access(&self, _input: &serde_json::Value, _cx: &App) -> ToolCallAccess {
+        ToolCallAccess::for_class(self.permission_class())
+    }
     fn initial_title(&self, input: serde_json::Value, _cx: &mut App) -> SharedString;
     fn input_schema(&self, format: LanguageModelToolSchemaFormat) -> Result<serde_json::Value>;
+    fn output_schema(
+        &self,
+        _format: LanguageModelToolSchemaFormat,
+    ) -> Result<Option<serde_json::Value>> {
+        Ok(None)
+    }
+    fn input_examples(&self) -> Result<Vec<serde_json::Value>> {
+        Ok(Vec::new())
+    }
     fn supports_provider(&self, _provider: &LanguageModelProviderId) -> bool {
         true
     }
+    fn is_experimental(&self) -> bool {
+        false
+    }
+    fn prepare(
+        self: Arc<Self>,
+        _input: serde_json::Value,
+        _cx: &mut App,
+    ) -> Option<Task<Result<ToolPreparation>>> {
+        None
+    }
     fn run(
         self: Arc<Self>,
         input: serde_json::Value,
@@ -2360,21 +2829,60 @@ where
         T::kind()
     }
 
+    fn permission_class(&self) -> ToolPermissionClass {
+        T::permission_class()
+    }
+
+    fn call_access(&self, input: &serde_json::Value, cx: &App) -> ToolCallAccess {
+        match serde_json::from_value(input.clone()) {
+            Ok(input) => self.0.call_access(&input, cx),
+            Err(_) => ToolCallAccess::for_class(T::permission_class()),
+        }
+    }
+
     fn initial_title(&self, input: serde_json::Value, _cx: &mut App) -> SharedString {
         let parsed_input = serde_json::from_value(input.clone()).map_err(|_| input);
         self.0.initial_title(parsed_input, _cx)
     }
 
     fn input_schema(&self, format: LanguageModelToolSchemaFormat) -> Result<serde_json::Value> {
-        let mut json = serde_json::to_value(T::input_schema(format))?;
-        language_model::tool_schema::adapt_schema_to_format(&mut json, format)?;
-        Ok(json)
+        schema_to_value(T::input_schema(format), format)
+    }
+
+    fn output_schema(
+        &self,
+        format: LanguageModelToolSchemaFormat,
+    ) -> Result<Option<serde_json::Value>> {
+        T::output_schema(format)
+            .map(|schema| schema_to_value(schema, format))
+            .transpose()
+    }
+
+    fn input_examples(&self) -> Result<Vec<serde_json::Value>> {
+        T::input_examples()
+            .into_iter()
+            .map(|example| Ok(serde_json::to_value(example)?))
+            .collect()
     }
 
     fn supports_provider(&self, provider: &LanguageModelProviderId) -> bool {
         T::supports_provider(provider)
     }
 
+    fn is_experimental(&self) -> bool {
+        T::is_experimental()
+    }
+
+    fn prepare(
+        self: Arc<Self>,
+        input: serde_json::Value,
+        cx: &mut App,
+    ) -> Option<Task<Result<ToolPreparation>>> {
+        // Partial input often doesn't deserialize yet; the next chunk will be tried again.
+        let input = serde_json::from_value(input).ok()?;
+        self.0.clone().prepare(input, cx)
+    }
+
     fn run(
         self: Arc<Self>,
         input: serde_json::Value,
@@ -2387,8 +2895,13 @@ where
                 .update(|cx| self.0.clone().run(input, event_stream, cx))?
                 .await?;
             let raw_output = serde_json::to_value(&output)?;
+            let mut llm_output = LanguageModelToolResultContent::from(output);
+            // Outputs that serialize to a plain string have nothing to add to their rendering.
+            if raw_output.is_object() {
+                llm_output = llm_output.with_structured_output(raw_output.clone());
+            }
             Ok(AgentToolOutput {
-                llm_output: output.into(),
+                llm_output,
                 raw_output,
             })
         })
@@ -2500,31 +3013,119 @@ pub struct ToolCallEventStream {
     tool_use_id: LanguageModelToolUseId,
     stream: ThreadEventStream,
     fs: Option<Arc<dyn Fs>>,
+    cancellation_rx: watch::Receiver<bool>,
+    dry_run: bool,
+    /// Whether the user already allowed this call, so that [`Self::authorize`] doesn't ask again.
+    authorized: bool,
+    /// Every location the tool reported, for the thread's [`ToolAuditLog`].
+    reported_locations: Arc<Mutex<Vec<acp::ToolCallLocation>>>,
+    token_budget: Option<outline::TokenBudget>,
 }
 
 impl ToolCallEventStream {
     #[cfg(any(test, feature = "test-support"))]
     pub fn test() -> (Self, ToolCallEventStreamReceiver) {
+        Self::test_with_dry_run(false)
+    }
+
+    #[cfg(any(test, feature = "test-support"))]
+    pub fn test_with_dry_run(dry_run: bool) -> (Self, ToolCallEventStreamReceiver) {
         let (events_tx, events_rx) = mpsc::unbounded::<Result<ThreadEvent>>();
 
-        let stream = ToolCallEventStream::new("test_id".into(), ThreadEventStream(events_tx), None);
+        let stream = ToolCallEventStream::new(
+            "test_id".into(),
+            ThreadEventStream(events_tx),
+            None,
+            watch::Receiver::constant(false),
+            dry_run,
+        );
 
         (stream, ToolCallEventStreamReceiver(events_rx))
     }
 
+    /// Creates a stream for running a tool outside of a thread's turn, e.g. from a command-line
+    /// harness. The tool's events are delivered to the returned receiver.
+    #[cfg(feature = "eval")]
+    pub fn detached(
+        tool_use_id: LanguageModelToolUseId,
+        fs: Option<Arc<dyn Fs>>,
+    ) -> (Self, mpsc::UnboundedReceiver<Result<ThreadEvent>>) {
+        let (events_tx, events_rx) = mpsc::unbounded::<Result<ThreadEvent>>();
+        let stream = ToolCallEventStream::new(
+            tool_use_id,
+            ThreadEventStream(events_tx),
+            fs,
+            watch::Receiver::constant(false),
+            false,
+        );
+        (stream, events_rx)
+    }
+
     fn new(
         tool_use_id: LanguageModelToolUseId,
         stream: ThreadEventStream,
         fs: Option<Arc<dyn Fs>>,
+        cancellation_rx: watch::Receiver<bool>,
+        dry_run: bool,
     ) -> Self {
         Self {
             tool_use_id,
             stream,
             fs,
+            cancellation_rx,
+            dry_run,
+            authorized: false,
+            reported_locations: Arc::default(),
+            token_budget: None,
+        }
+    }
+
+    fn with_token_budget(self, token_budget: Option<outline::TokenBudget>) -> Self {
+        Self {
+            token_budget,
+            ..self
+        }
+    }
+
+    /// How many tokens the tool's output may take up: the same share of what's left of the
+    /// model's context window as a file read in full. `None` when there's no model to count with.
+    pub fn token_budget(&self) -> Option<&outline::TokenBudget> {
+        self.token_budget.as_ref()
+    }
+
+    fn reported_locations(&self) -> Arc<Mutex<Vec<acp::ToolCallLocation>>> {
+        self.reported_locations.clone()
+    }
+
+    fn into_authorized(self) -> Self {
+        Self {
+            authorized: true,
+            ..self
+        }
+    }
+
+    /// Whether the tool should only report the changes it would make, without applying them.
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Resolves once the user cancels the turn this tool call belongs to, and never if the turn
+    /// ends otherwise.
+    pub async fn cancelled_by_user(&self) {
+        let mut cancellation_rx = self.cancellation_rx.clone();
+        while !*cancellation_rx.borrow() {
+            if cancellation_rx.changed().await.is_err() {
+                futures::future::pending::<()>().await;
+            }
         }
     }
 
     pub fn update_fields(&self, fields: acp::ToolCallUpdateFields) {
+        if let Some(locations) = &fields.locations {
+            self.reported_locations
+                .lock()
+                .extend(locations.iter().cloned());
+        }
         self.stream
             .update_tool_call_fields(&self.tool_use_id, fields);
     }
@@ -2543,7 +3144,9 @@ impl ToolCallEventStream {
     }
 
     pub fn authorize(&self, title: impl Into<String>, cx: &mut App) -> Task<Result<()>> {
-        if agent_settings::AgentSettings::get_global(cx).always_allow_tool_actions {
+        if self.authorized
+            || agent_settings::AgentSettings::get_global(cx).always_allow_tool_actions
+        {
             return Task::ready(Ok(()));
         }
 
@@ -2751,3 +3354,15 @@ fn convert_image(image_content: acp::ImageContent) -> LanguageModelImage {
         size: None,
     }
 }
+
+/// Returns the raw output reported for a tool that failed. Anchor failures carry their reason as
+/// well as the message, so that clients can recover without parsing the message.
+fn tool_error_output(error: &anyhow::Error) -> serde_json::Value {
+    match error.downcast_ref::<AnchorResolutionError>() {
+        Some(reason) => serde_json::json!({
+            "error": error.to_string(),
+            "anchor_resolution_error": reason,
+        }),
+        None => error.to_string().into(),
+    }
+}
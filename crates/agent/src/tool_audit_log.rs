@@ -0,0 +1,182 @@
+//! A record of every tool call a thread made, kept with the thread so that it can be looked at
+//! after the fact, e.g. to find out why the agent edited the wrong place.
+
+use agent_client_protocol as acp;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use language_model::{LanguageModelToolResult, LanguageModelToolResultContent};
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+
+/// How much of a tool's output each [`ToolAuditEntry`] keeps.
+const OUTPUT_SUMMARY_MAX_CHARS: usize = 200;
+/// How much of each string in a tool's input each [`ToolAuditEntry`] keeps, since inputs such as
+/// a created file's contents would otherwise grow every saved thread without bound.
+const INPUT_STRING_MAX_CHARS: usize = 200;
+
+/// The tool calls a thread made, in the order they finished.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolAuditLog {
+    entries: Vec<ToolAuditEntry>,
+}
+
+impl ToolAuditLog {
+    pub fn entries(&self) -> &[ToolAuditEntry] {
+        &self.entries
+    }
+
+    pub fn record(&mut self, entry: ToolAuditEntry) {
+        self.entries.push(entry);
+    }
+
+    /// Exports the log with one JSON object per line, one for each call.
+    pub fn to_json_lines(&self) -> Result<String> {
+        let mut json_lines = String::new();
+        for entry in &self.entries {
+            json_lines.push_str(&serde_json::to_string(entry)?);
+            json_lines.push('\n');
+        }
+        Ok(json_lines)
+    }
+}
+
+/// One tool call, as recorded in a [`ToolAuditLog`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolAuditEntry {
+    pub tool_use_id: language_model::LanguageModelToolUseId,
+    pub tool_name: Arc<str>,
+    /// The tool's input, with long strings cut short.
+    pub input: serde_json::Value,
+    /// Whether any string in `input` was cut short, in which case it isn't the input the tool ran
+    /// with and the call can't be replayed.
+    #[serde(default)]
+    pub input_truncated: bool,
+    /// Where the tool reported working, in the order it reported them.
+    pub locations: Vec<acp::ToolCallLocation>,
+    /// The start of the tool's output, or of its error if it failed.
+    pub output_summary: String,
+    pub started_at: DateTime<Utc>,
+    pub duration: Duration,
+    pub succeeded: bool,
+}
+
+impl ToolAuditEntry {
+    pub fn new(
+        mut input: serde_json::Value,
+        result: &LanguageModelToolResult,
+        locations: Vec<acp::ToolCallLocation>,
+        started_at: DateTime<Utc>,
+        duration: Duration,
+    ) -> Self {
+        let output_summary = match &result.content {
+            LanguageModelToolResultContent::Text(text)
+            | LanguageModelToolResultContent::Structured { text, .. } => {
+                util::truncate_and_trailoff(text.trim(), OUTPUT_SUMMARY_MAX_CHARS)
+            }
+            LanguageModelToolResultContent::Image(_) => "[image]".to_string(),
+        };
+        let input_truncated = truncate_long_strings(&mut input);
+        Self {
+            tool_use_id: result.tool_use_id.clone(),
+            tool_name: result.tool_name.clone(),
+            input,
+            input_truncated,
+            locations,
+            output_summary,
+            started_at,
+            duration,
+            succeeded: !result.is_error,
+        }
+    }
+}
+
+/// Cuts every long string in `input` short, noting how long it was, and returns whether any was.
+fn truncate_long_strings(input: &mut serde_json::Value) -> bool {
+    match input {
+        serde_json::Value::String(text) => {
+            let length = text.chars().count();
+            if length > INPUT_STRING_MAX_CHARS {
+                *text = format!(
+                    "{} ({length} chars)",
+                    util::truncate_and_trailoff(text, INPUT_STRING_MAX_CHARS)
+                );
+                true
+            } else {
+                false
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().fold(false, |truncated, value| {
+            truncate_long_strings(value) || truncated
+        }),
+        serde_json::Value::Object(fields) => fields.values_mut().fold(false, |truncated, value| {
+            truncate_long_strings(value) || truncated
+        }),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_tool_audit_log_export() {
+        let mut log = ToolAuditLog::default();
+        let long_output = "x".repeat(OUTPUT_SUMMARY_MAX_CHARS * 2);
+        let long_contents = "y".repeat(INPUT_STRING_MAX_CHARS * 10);
+        log.record(ToolAuditEntry::new(
+            json!({"path": "root/a.rs", "edits": [{"new_text": long_contents}]}),
+            &LanguageModelToolResult {
+                tool_use_id: "tool_id_1".into(),
+                tool_name: "read_file".into(),
+                is_error: false,
+                content: LanguageModelToolResultContent::Text(long_output.into()),
+                output: None,
+            },
+            vec![acp::ToolCallLocation::new("/root/a.rs").line(Some(3))],
+            Utc::now(),
+            Duration::from_millis(5),
+        ));
+        log.record(ToolAuditEntry::new(
+            json!({}),
+            &LanguageModelToolResult {
+                tool_use_id: "tool_id_2".into(),
+                tool_name: "terminal".into(),
+                is_error: true,
+                content: LanguageModelToolResultContent::Text("  denied\n".into()),
+                output: None,
+            },
+            Vec::new(),
+            Utc::now(),
+            Duration::ZERO,
+        ));
+
+        let entries = log.entries();
+        assert!(entries[0].succeeded);
+        assert!(entries[0].input_truncated);
+        assert!(!entries[1].input_truncated);
+        assert_eq!(
+            entries[0].input,
+            json!({
+                "path": "root/a.rs",
+                "edits": [{
+                    "new_text": format!("{}… (2000 chars)", "y".repeat(INPUT_STRING_MAX_CHARS))
+                }]
+            })
+        );
+        assert_eq!(
+            entries[0].output_summary.chars().count(),
+            OUTPUT_SUMMARY_MAX_CHARS + 1
+        );
+        assert!(!entries[1].succeeded);
+        assert_eq!(entries[1].output_summary, "denied");
+
+        let json_lines = log.to_json_lines().unwrap();
+        let exported = json_lines
+            .lines()
+            .map(|line| serde_json::from_str::<ToolAuditEntry>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(exported, entries);
+    }
+}
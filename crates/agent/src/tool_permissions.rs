@@ -0,0 +1,97 @@
+//! Decides whether the agent may run a tool without asking, per the `agent.tool_permissions`
+//! setting, which groups tools by what they can do.
+
+use agent_client_protocol as acp;
+use agent_settings::AgentSettings;
+use gpui::App;
+use settings::{Settings as _, ToolPermissionMode};
+
+/// What a tool can do, which decides the `agent.tool_permissions` group it falls under.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ToolPermissionClass {
+    /// Only reads the project or the web.
+    ReadOnly,
+    /// Changes the project's files or git state.
+    WorkspaceMutating,
+    /// Runs commands, opens things outside of Zed, or calls context servers.
+    SystemExecuting,
+}
+
+impl ToolPermissionClass {
+    /// The class of a tool of the given kind, for tools that don't pick their own.
+    pub fn for_kind(kind: acp::ToolKind) -> Self {
+        match kind {
+            acp::ToolKind::Execute => Self::SystemExecuting,
+            acp::ToolKind::Edit | acp::ToolKind::Delete | acp::ToolKind::Move => {
+                Self::WorkspaceMutating
+            }
+            _ => Self::ReadOnly,
+        }
+    }
+}
+
+/// The mode the user set for running the tool named `tool_name`, either by name or for its
+/// class. `None` leaves it to the tool to ask before the actions it considers risky.
+pub fn tool_permission_mode(
+    tool_name: &str,
+    class: ToolPermissionClass,
+    cx: &App,
+) -> Option<ToolPermissionMode> {
+    let permissions = &AgentSettings::get_global(cx).tool_permissions;
+    if let Some(mode) = permissions.tools.get(tool_name) {
+        return Some(*mode);
+    }
+    match class {
+        ToolPermissionClass::ReadOnly => permissions.read_only,
+        ToolPermissionClass::WorkspaceMutating => permissions.workspace_mutating,
+        ToolPermissionClass::SystemExecuting => permissions.system_executing,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpui::TestAppContext;
+    use settings::{SettingsStore, ToolPermissionsContent};
+
+    #[gpui::test]
+    fn test_tool_permission_mode(cx: &mut TestAppContext) {
+        cx.update(|cx| {
+            let settings_store = SettingsStore::test(cx);
+            cx.set_global(settings_store);
+
+            assert_eq!(
+                tool_permission_mode("terminal", ToolPermissionClass::SystemExecuting, cx),
+                None
+            );
+
+            let mut settings = AgentSettings::get_global(cx).clone();
+            settings.tool_permissions = ToolPermissionsContent {
+                read_only: None,
+                workspace_mutating: Some(ToolPermissionMode::Ask),
+                system_executing: Some(ToolPermissionMode::Allow),
+                tools: [("terminal".into(), ToolPermissionMode::Deny)]
+                    .into_iter()
+                    .collect(),
+            };
+            AgentSettings::override_global(settings, cx);
+
+            assert_eq!(
+                tool_permission_mode("terminal", ToolPermissionClass::SystemExecuting, cx),
+                Some(ToolPermissionMode::Deny)
+            );
+            assert_eq!(
+                tool_permission_mode("run_tests", ToolPermissionClass::SystemExecuting, cx),
+                Some(ToolPermissionMode::Allow)
+            );
+            assert_eq!(
+                tool_permission_mode("edit_file", ToolPermissionClass::WorkspaceMutating, cx),
+                Some(ToolPermissionMode::Ask)
+            );
+            assert_eq!(
+                tool_permission_mode("read_file", ToolPermissionClass::ReadOnly, cx),
+                None
+            );
+        });
+    }
+}
@@ -0,0 +1,207 @@
+//! Orders the tool calls of a thread by what they change. Calls start as soon as the model makes
+//! them, so that e.g. outlining ten files happens all at once, except that a call changing files
+//! waits for the earlier calls changing the same ones, or the directories containing them, so
+//! that edits land in the order the model made them.
+
+use crate::ToolPermissionClass;
+use collections::{HashMap, HashSet};
+use futures::{
+    FutureExt as _,
+    channel::oneshot,
+    future::{self, Shared},
+};
+use gpui::App;
+use project::{Project, ProjectPath};
+use std::{future::Future, path::Path};
+
+/// What a tool call may change, which decides the earlier calls it waits for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ToolCallAccess {
+    /// Changes nothing, so it never waits.
+    ReadOnly,
+    /// Changes the files at these paths, or in the directories at them.
+    Mutates(Vec<ProjectPath>),
+    /// May change anything, such as a command run in a terminal.
+    MutatesAnything,
+}
+
+impl ToolCallAccess {
+    /// The access of a call to a tool of the given class, for tools that don't say which files
+    /// they change.
+    pub fn for_class(class: ToolPermissionClass) -> Self {
+        match class {
+            ToolPermissionClass::ReadOnly => Self::ReadOnly,
+            ToolPermissionClass::WorkspaceMutating | ToolPermissionClass::SystemExecuting => {
+                Self::MutatesAnything
+            }
+        }
+    }
+
+    /// A call changing the files at `paths`, or anything when one of them isn't in the project.
+    pub fn mutates_paths<P: AsRef<Path>>(
+        project: &Project,
+        paths: impl IntoIterator<Item = P>,
+        cx: &App,
+    ) -> Self {
+        paths
+            .into_iter()
+            .map(|path| {
+                let path = path.as_ref();
+                if path.as_os_str().is_empty() {
+                    None
+                } else {
+                    project.find_project_path(path, cx)
+                }
+            })
+            .collect::<Option<Vec<_>>>()
+            .map_or(Self::MutatesAnything, Self::Mutates)
+    }
+}
+
+/// Resolves once a scheduled call has finished.
+type CallFinished = Shared<oneshot::Receiver<()>>;
+
+fn is_running(finished: &CallFinished) -> bool {
+    finished.clone().now_or_never().is_none()
+}
+
+/// Whether changing one path may change the other, because they're the same or one is in the
+/// directory at the other, e.g. when deleting `src` while editing `src/lib.rs`.
+fn paths_overlap(path: &ProjectPath, other: &ProjectPath) -> bool {
+    path.worktree_id == other.worktree_id
+        && (path.path.starts_with(&other.path) || other.path.starts_with(&path.path))
+}
+
+/// Schedules a thread's tool calls after the earlier calls that change the same files, or the
+/// directories containing them.
+#[derive(Default)]
+pub struct ToolScheduler {
+    /// The last call scheduled to change each file.
+    last_mutations: HashMap<ProjectPath, CallFinished>,
+    /// The last call scheduled that may change anything.
+    last_exclusive_call: Option<CallFinished>,
+}
+
+impl ToolScheduler {
+    /// Schedules a call with the given access after the earlier calls it conflicts with. The
+    /// returned [`ScheduledToolCall`] must be kept until the call finishes.
+    pub fn schedule(&mut self, access: &ToolCallAccess) -> ScheduledToolCall {
+        self.last_mutations
+            .retain(|_, finished| is_running(finished));
+        if !self.last_exclusive_call.as_ref().is_some_and(is_running) {
+            self.last_exclusive_call = None;
+        }
+
+        let (finished_tx, finished_rx) = oneshot::channel();
+        let finished = finished_rx.shared();
+        let mut dependencies = Vec::new();
+        match access {
+            ToolCallAccess::ReadOnly => {}
+            ToolCallAccess::Mutates(paths) => {
+                dependencies.extend(self.last_exclusive_call.clone());
+                let paths = paths.iter().collect::<HashSet<_>>();
+                dependencies.extend(
+                    self.last_mutations
+                        .iter()
+                        .filter(|(scheduled_path, _)| {
+                            paths.iter().any(|path| paths_overlap(path, scheduled_path))
+                        })
+                        .map(|(_, finished)| finished.clone()),
+                );
+                for path in paths {
+                    self.last_mutations.insert(path.clone(), finished.clone());
+                }
+            }
+            ToolCallAccess::MutatesAnything => {
+                dependencies.extend(self.last_exclusive_call.replace(finished));
+                dependencies.extend(self.last_mutations.drain().map(|(_, finished)| finished));
+            }
+        }
+        ScheduledToolCall {
+            dependencies,
+            _finished_tx: finished_tx,
+        }
+    }
+}
+
+/// A call's place in a [`ToolScheduler`]. Dropping it lets the calls waiting for it start.
+pub struct ScheduledToolCall {
+    dependencies: Vec<CallFinished>,
+    _finished_tx: oneshot::Sender<()>,
+}
+
+impl ScheduledToolCall {
+    /// Whether the call has to wait for earlier calls before it can start.
+    pub fn is_blocked(&self) -> bool {
+        self.dependencies.iter().any(is_running)
+    }
+
+    /// Resolves once every earlier call this one waits for has finished.
+    pub fn earlier_calls_finished(&self) -> impl Future<Output = ()> + 'static {
+        future::join_all(self.dependencies.clone()).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use project::WorktreeId;
+    use util::rel_path::rel_path;
+
+    fn mutates(path: &str) -> ToolCallAccess {
+        ToolCallAccess::Mutates(vec![ProjectPath {
+            worktree_id: WorktreeId::from_usize(1),
+            path: rel_path(path).into(),
+        }])
+    }
+
+    #[test]
+    fn test_tool_scheduler() {
+        let mut scheduler = ToolScheduler::default();
+        let read = scheduler.schedule(&ToolCallAccess::ReadOnly);
+        let first_edit = scheduler.schedule(&mutates("a.rs"));
+        let second_edit = scheduler.schedule(&mutates("a.rs"));
+        let other_edit = scheduler.schedule(&mutates("b.rs"));
+        assert!(!read.is_blocked());
+        assert!(!first_edit.is_blocked());
+        assert!(second_edit.is_blocked());
+        assert!(!other_edit.is_blocked());
+
+        let command = scheduler.schedule(&ToolCallAccess::MutatesAnything);
+        let later_read = scheduler.schedule(&ToolCallAccess::ReadOnly);
+        assert!(command.is_blocked());
+        assert!(!later_read.is_blocked());
+
+        drop(first_edit);
+        assert!(!second_edit.is_blocked());
+        assert!(command.is_blocked());
+        drop(second_edit);
+        drop(other_edit);
+        assert!(!command.is_blocked());
+        assert_eq!(command.earlier_calls_finished().now_or_never(), Some(()));
+
+        let edit_after_command = scheduler.schedule(&mutates("b.rs"));
+        assert!(edit_after_command.is_blocked());
+        drop(command);
+        assert!(!edit_after_command.is_blocked());
+    }
+
+    #[test]
+    fn test_tool_scheduler_orders_directories_and_their_files() {
+        let mut scheduler = ToolScheduler::default();
+        let file_edit = scheduler.schedule(&mutates("src/lib.rs"));
+        let directory_delete = scheduler.schedule(&mutates("src"));
+        let sibling_edit = scheduler.schedule(&mutates("src2/lib.rs"));
+        assert!(!file_edit.is_blocked());
+        assert!(directory_delete.is_blocked());
+        assert!(!sibling_edit.is_blocked());
+
+        let later_file_edit = scheduler.schedule(&mutates("src/main.rs"));
+        assert!(later_file_edit.is_blocked());
+        drop(file_edit);
+        assert!(!directory_delete.is_blocked());
+        assert!(later_file_edit.is_blocked());
+        drop(directory_delete);
+        assert!(!later_file_edit.is_blocked());
+    }
+}
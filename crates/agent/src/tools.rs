@@ -1,52 +1,141 @@
+mod anchor_cache;
+mod anchor_memory;
+mod batch_edit_by_context_tool;
+mod buffer_version;
+mod call_hierarchy_by_context_tool;
+mod cargo_check_tool;
+mod code_actions_by_context_tool;
+mod completions_by_context_tool;
 mod context_server_registry;
+mod contextual_anchor;
 mod copy_path_tool;
 mod create_directory_tool;
+mod create_file_tool;
 mod delete_path_tool;
 mod diagnostics_tool;
+mod document_symbols_tool;
+mod edit_by_context_tool;
 mod edit_file_tool;
+mod expand_symbol_tool;
+mod explain_symbol_by_context_tool;
 mod fetch_tool;
+mod file_outline_tool;
+mod find_implementations_by_context_tool;
 mod find_path_tool;
+mod format_tool;
+mod git_blame_tool;
+mod git_commit_tool;
+mod git_diff_tool;
+mod git_log_tool;
+mod git_stage_tool;
+mod git_status_tool;
 mod grep_tool;
+mod hover_info_by_context_tool;
+mod inlay_hints_tool;
 mod list_directory_tool;
 mod move_path_tool;
+mod navigate_by_context_tool;
 mod now_tool;
 mod open_tool;
+mod organize_imports_tool;
+mod project_map_tool;
 mod read_file_tool;
+mod rename_symbol_by_context_tool;
+mod resolve_anchors_tool;
 mod restore_file_from_disk_tool;
+mod run_command_tool;
+mod run_tests_tool;
 mod save_file_tool;
+mod search_replace_tool;
+mod semantic_search_tool;
+mod symbol_docs_by_context_tool;
+mod support;
 
 mod find_references_by_context_tool;
 mod goto_definition_by_context_tool;
 
 mod terminal_tool;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
 mod thinking_tool;
+mod truncation;
 mod web_search_tool;
 
-use crate::AgentTool;
+use crate::{AgentTool, AnyAgentTool, ToolCallEventStream, ToolPreparation};
+use agent_settings::AgentSettings;
+use anyhow::Result;
+use collections::{HashMap, HashSet};
+use futures::FutureExt as _;
+use gpui::{App, AppContext as _, AsyncApp, Entity, EntityId, Task};
+use language::{Buffer, Location};
 use language_model::{LanguageModelRequestTool, LanguageModelToolSchemaFormat};
+use lsp::LanguageServerId;
+use project::{Project, ProjectPath, WorktreeSettings};
+use schemars::Schema;
+use serde::Serialize;
+use settings::Settings as _;
+use std::{ops::Range, time::Duration};
+use text::OffsetRangeExt as _;
+use util::ResultExt as _;
 
+pub use anchor_cache::*;
+pub use anchor_memory::*;
+pub use batch_edit_by_context_tool::*;
+pub use buffer_version::*;
+pub use call_hierarchy_by_context_tool::*;
+pub use cargo_check_tool::*;
+pub use code_actions_by_context_tool::*;
+pub use completions_by_context_tool::*;
 pub use context_server_registry::*;
+pub use contextual_anchor::*;
 pub use copy_path_tool::*;
 pub use create_directory_tool::*;
+pub use create_file_tool::*;
 pub use delete_path_tool::*;
 pub use diagnostics_tool::*;
+pub use document_symbols_tool::*;
+pub use edit_by_context_tool::*;
 pub use edit_file_tool::*;
+pub use expand_symbol_tool::*;
+pub use explain_symbol_by_context_tool::*;
 pub use fetch_tool::*;
+pub use file_outline_tool::*;
+pub use find_implementations_by_context_tool::*;
 pub use find_path_tool::*;
+pub use format_tool::*;
+pub use git_blame_tool::*;
+pub use git_commit_tool::*;
+pub use git_diff_tool::*;
+pub use git_log_tool::*;
+pub use git_stage_tool::*;
+pub use git_status_tool::*;
 pub use grep_tool::*;
+pub use hover_info_by_context_tool::*;
+pub use inlay_hints_tool::*;
 pub use list_directory_tool::*;
 pub use move_path_tool::*;
+pub use navigate_by_context_tool::*;
 pub use now_tool::*;
 pub use open_tool::*;
+pub use organize_imports_tool::*;
+pub use project_map_tool::*;
 pub use read_file_tool::*;
+pub use rename_symbol_by_context_tool::*;
+pub use resolve_anchors_tool::*;
 pub use restore_file_from_disk_tool::*;
+pub use run_command_tool::*;
+pub use run_tests_tool::*;
 pub use save_file_tool::*;
+pub use search_replace_tool::*;
+pub use semantic_search_tool::*;
+pub use symbol_docs_by_context_tool::*;
 
 pub use find_references_by_context_tool::*;
 pub use goto_definition_by_context_tool::*;
 
 pub use terminal_tool::*;
 pub use thinking_tool::*;
+pub use truncation::*;
 pub use web_search_tool::*;
 
 macro_rules! tools {
@@ -69,42 +158,780 @@ macro_rules! tools {
 
         /// A list of all built-in tools
         pub fn built_in_tools() -> impl Iterator<Item = LanguageModelRequestTool> {
-            fn language_model_tool<T: AgentTool>() -> LanguageModelRequestTool {
-                LanguageModelRequestTool {
-                    name: T::name().to_string(),
-                    description: T::description().to_string(),
-                    input_schema: T::input_schema(LanguageModelToolSchemaFormat::JsonSchema).to_value(),
-                }
-            }
-            [
+            built_in_tool_schemas(LanguageModelToolSchemaFormat::JsonSchema)
+                .into_iter()
+                .filter_map(|schema| schema.log_err())
+                .map(LanguageModelRequestTool::from)
+        }
+
+        /// The schemas of all built-in tools, as declared to providers that use `format`
+        pub fn built_in_tool_schemas(format: LanguageModelToolSchemaFormat) -> Vec<Result<ToolSchema>> {
+            vec![
                 $(
-                    language_model_tool::<$tool>(),
+                    ToolSchema::for_tool::<$tool>(format),
                 )*
             ]
-            .into_iter()
         }
     };
 }
 
 tools! {
+    BatchEditByContextTool,
+    CallHierarchyByContextTool,
+    CargoCheckTool,
+    CodeActionsByContextTool,
+    CompletionsByContextTool,
     CopyPathTool,
     CreateDirectoryTool,
+    CreateFileTool,
     DeletePathTool,
     DiagnosticsTool,
+    DocumentSymbolsTool,
+    EditByContextTool,
     EditFileTool,
+    ExpandSymbolTool,
+    ExplainSymbolByContextTool,
     FetchTool,
+    FileOutlineTool,
+    FindImplementationsByContextTool,
     FindPathTool,
     FindReferencesByContextTool,
+    FormatTool,
+    GitBlameTool,
+    GitCommitTool,
+    GitDiffTool,
+    GitLogTool,
+    GitStageTool,
+    GitStatusTool,
     GotoDefinitionByContextTool,
     GrepTool,
+    HoverInfoByContextTool,
+    InlayHintsTool,
     ListDirectoryTool,
     MovePathTool,
+    NavigateByContextTool,
     NowTool,
     OpenTool,
+    OrganizeImportsTool,
+    ProjectMapTool,
     ReadFileTool,
+    RenameSymbolByContextTool,
+    ResolveAnchorsTool,
     RestoreFileFromDiskTool,
+    RunCommandTool,
+    RunTestsTool,
     SaveFileTool,
+    SearchReplaceTool,
+    SemanticSearchTool,
+    SymbolDocsByContextTool,
     TerminalTool,
     ThinkingTool,
     WebSearchTool,
 }
+
+/// A tool's declaration as sent to language model providers, along with the schema of its raw
+/// output and example inputs, so that declarations can be exported and checked against the Rust
+/// types they are derived from.
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolSchema {
+    pub name: String,
+    pub description: String,
+    pub input_schema: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_schema: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub input_examples: Vec<serde_json::Value>,
+}
+
+impl ToolSchema {
+    pub fn for_tool<T: AgentTool>(format: LanguageModelToolSchemaFormat) -> Result<Self> {
+        Ok(Self {
+            name: T::name().to_string(),
+            description: T::description().to_string(),
+            input_schema: schema_to_value(T::input_schema(format), format)?,
+            output_schema: T::output_schema(format)
+                .map(|schema| schema_to_value(schema, format))
+                .transpose()?,
+            input_examples: T::input_examples()
+                .into_iter()
+                .map(|example| Ok(serde_json::to_value(example)?))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    pub fn for_any_tool(
+        tool: &dyn AnyAgentTool,
+        format: LanguageModelToolSchemaFormat,
+    ) -> Result<Self> {
+        Ok(Self {
+            name: tool.name().to_string(),
+            description: tool.description().to_string(),
+            input_schema: tool.input_schema(format)?,
+            output_schema: tool.output_schema(format)?,
+            input_examples: tool.input_examples()?,
+        })
+    }
+}
+
+impl From<ToolSchema> for LanguageModelRequestTool {
+    fn from(schema: ToolSchema) -> Self {
+        LanguageModelRequestTool {
+            name: schema.name,
+            description: schema.description,
+            input_schema: schema.input_schema,
+        }
+    }
+}
+
+/// Serializes `schema` and adapts it to what providers that use `format` accept.
+pub(crate) fn schema_to_value(
+    schema: Schema,
+    format: LanguageModelToolSchemaFormat,
+) -> Result<serde_json::Value> {
+    let mut json = serde_json::to_value(schema)?;
+    language_model::tool_schema::adapt_schema_to_format(&mut json, format)?;
+    Ok(json)
+}
+
+/// Formats `project_path` the way `Project::find_project_path` accepts it, so paths emitted by one
+/// tool can be passed to another unchanged: prefixed with the worktree's root name, or absolute for
+/// worktrees that aren't visible (e.g. dependencies opened by a language server) and for worktrees
+/// whose root name another visible worktree shares.
+pub(crate) fn display_project_path(
+    project: &Project,
+    project_path: &ProjectPath,
+    cx: &App,
+) -> Option<String> {
+    let worktree = project.worktree_for_id(project_path.worktree_id, cx)?;
+    let worktree = worktree.read(cx);
+    let root_name_is_shared = project.visible_worktrees(cx).any(|other| {
+        let other = other.read(cx);
+        other.id() != worktree.id() && other.root_name() == worktree.root_name()
+    });
+    let path = if worktree.is_visible() && !root_name_is_shared {
+        worktree
+            .root_name()
+            .join(&project_path.path)
+            .display(worktree.path_style())
+            .to_string()
+    } else {
+        worktree
+            .absolutize(&project_path.path)
+            .to_string_lossy()
+            .into_owned()
+    };
+    Some(path)
+}
+
+/// How many similarly named files to suggest when a path from a tool's input doesn't exist.
+const MAX_PATH_SUGGESTIONS: usize = 5;
+
+/// Resolves a path from a tool's input, given either relative to its worktree or prefixed with the
+/// worktree's root name. Unlike `Project::find_project_path`, which picks the first match, this
+/// fails when the path is ambiguous across worktrees, listing the unique forms to use instead, and
+/// suggests similarly named files when nothing matches.
+pub(crate) fn resolve_project_path(project: &Project, path: &str, cx: &App) -> Result<ProjectPath> {
+    let mut project_paths = project.find_project_paths(path, cx);
+    if project_paths.len() > 1 {
+        let unique_paths = project_paths
+            .iter()
+            .filter_map(|project_path| display_project_path(project, project_path, cx))
+            .collect::<Vec<_>>();
+        anyhow::bail!(
+            "Path {path} matches files in more than one worktree. Use one of these paths instead:\n{}",
+            unique_paths.join("\n")
+        );
+    }
+    if let Some(project_path) = project_paths.pop() {
+        return Ok(project_path);
+    }
+
+    let suggestions = similar_project_paths(project, path, cx);
+    if suggestions.is_empty() {
+        anyhow::bail!("Path {path} not found in project");
+    }
+    anyhow::bail!(
+        "Path {path} not found in project. Did you mean one of these?\n{}",
+        suggestions.join("\n")
+    )
+}
+
+/// Returns files in visible worktrees with the same file name as `path`, preferring those that
+/// share more trailing path components with it.
+fn similar_project_paths(project: &Project, path: &str, cx: &App) -> Vec<String> {
+    let path_components = path
+        .split(['/', '\\'])
+        .filter(|component| !component.is_empty())
+        .collect::<Vec<_>>();
+    let Some(file_name) = path_components.last() else {
+        return Vec::new();
+    };
+
+    let mut matches = Vec::new();
+    for worktree in project.visible_worktrees(cx) {
+        let worktree = worktree.read(cx);
+        for entry in worktree.files(false, 0) {
+            if !entry
+                .path
+                .file_name()
+                .is_some_and(|name| name.eq_ignore_ascii_case(file_name))
+            {
+                continue;
+            }
+            let shared_components = entry
+                .path
+                .components()
+                .rev()
+                .zip(path_components.iter().rev())
+                .take_while(|(entry_component, path_component)| {
+                    entry_component.eq_ignore_ascii_case(path_component)
+                })
+                .count();
+            let project_path = ProjectPath {
+                worktree_id: worktree.id(),
+                path: entry.path.clone(),
+            };
+            matches.push((shared_components, project_path));
+        }
+    }
+    matches.sort_by(|(a_shared, a_path), (b_shared, b_path)| {
+        b_shared
+            .cmp(a_shared)
+            .then_with(|| a_path.path.cmp(&b_path.path))
+    });
+    matches
+        .into_iter()
+        .take(MAX_PATH_SUGGESTIONS)
+        .filter_map(|(_, project_path)| display_project_path(project, &project_path, cx))
+        .collect()
+}
+
+/// Opens the file at `path` and registers it with its language servers, so that a navigation
+/// request made once the call's input is complete doesn't wait for the servers to start and index
+/// the file. Returns `None` while `path` doesn't name a file the tools may read, since it may still
+/// be streaming in.
+pub(crate) fn warm_up_language_servers(
+    project: &Entity<Project>,
+    path: &str,
+    cx: &mut App,
+) -> Option<Task<Result<ToolPreparation>>> {
+    let project_path = project.read(cx).find_project_path(path, cx)?;
+    if !project
+        .read(cx)
+        .entry_for_path(&project_path, cx)
+        .is_some_and(|entry| entry.is_file())
+    {
+        return None;
+    }
+    let settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+    if settings.is_path_excluded(&project_path.path) || settings.is_path_private(&project_path.path)
+    {
+        return None;
+    }
+
+    let open_buffer = project.update(cx, |project, cx| project.open_buffer(project_path, cx));
+    let project = project.clone();
+    Some(cx.spawn(async move |cx| {
+        let buffer = open_buffer.await?;
+        let lsp_handle = project.update(cx, |project, cx| {
+            project.register_buffer_with_language_servers(&buffer, cx)
+        })?;
+        Ok(Box::new((buffer, lsp_handle)) as ToolPreparation)
+    }))
+}
+
+/// Waits for a language server's answer to `request`, giving up once the
+/// `agent.language_server_timeout_ms` setting's time has passed or the user cancels the tool call.
+/// Returns `None` when it gave up, so that tools can return what they have rather than hang while
+/// a server is busy, e.g. indexing. Giving up drops `request`, which cancels it.
+pub(crate) async fn wait_for_language_server<T>(
+    request: impl Future<Output = T>,
+    event_stream: &ToolCallEventStream,
+    cx: &mut AsyncApp,
+) -> Result<Option<T>> {
+    let timeout = cx.update(|cx| AgentSettings::get_global(cx).language_server_timeout)?;
+    let mut timer = cx.background_executor().timer(timeout).fuse();
+    let request = request.fuse();
+    let cancelled = event_stream.cancelled_by_user().fuse();
+    futures::pin_mut!(request, cancelled);
+    Ok(futures::select_biased! {
+        response = request => Some(response),
+        _ = timer => None,
+        _ = cancelled => None,
+    })
+}
+
+/// How many times [`request_language_server`] sends a request that keeps failing transiently.
+const MAX_LANGUAGE_SERVER_ATTEMPTS: usize = 3;
+
+/// How long [`request_language_server`] waits before its first retry. Each retry after it waits
+/// twice as long as the one before, plus up to half as long again at random, so that the retries
+/// of concurrent tool calls don't hit a restarting server all at once.
+const LANGUAGE_SERVER_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// The codes of the errors that language servers fail requests with while they catch up with an
+/// edit, which a retry usually gets past.
+const TRANSIENT_LANGUAGE_SERVER_ERROR_CODES: &[i64] = &[
+    lsp::error_codes::CONTENT_MODIFIED,
+    lsp::error_codes::REQUEST_CANCELLED,
+];
+
+/// Parts of the messages of transient errors that come without an error code, such as a server
+/// restarting or the responses relayed from the host of a remote project.
+const TRANSIENT_LANGUAGE_SERVER_ERRORS: &[&str] = &[
+    "content modified",
+    "Server reset the connection",
+    "server shut down",
+    "failed to write to language server's stdin",
+];
+
+fn is_transient_language_server_error(error: &anyhow::Error) -> bool {
+    if let Some(response_error) = error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<lsp::LspResponseError>())
+    {
+        return TRANSIENT_LANGUAGE_SERVER_ERROR_CODES.contains(&response_error.code);
+    }
+    let message = format!("{error:#}");
+    TRANSIENT_LANGUAGE_SERVER_ERRORS
+        .iter()
+        .any(|transient_error| message.contains(transient_error))
+}
+
+/// Keeps the results of the language servers that answered a request sent to each of them. A
+/// transient failure of any server fails the whole request, so that [`request_language_server`]
+/// sends it again rather than settling for the other servers' results. Other failures only drop
+/// that server's results, unless no server answered at all.
+pub(crate) fn language_server_results<T>(
+    responses: Vec<(LanguageServerId, Result<T>)>,
+) -> Result<Vec<(LanguageServerId, T)>> {
+    let mut results = Vec::with_capacity(responses.len());
+    let mut first_error = None;
+    for (server_id, response) in responses {
+        match response {
+            Ok(result) => results.push((server_id, result)),
+            Err(error) if is_transient_language_server_error(&error) => return Err(error),
+            Err(error) => {
+                log::error!("Language server {server_id} failed: {error:#}");
+                first_error.get_or_insert(error);
+            }
+        }
+    }
+    match first_error {
+        Some(error) if results.is_empty() => Err(error),
+        _ => Ok(results),
+    }
+}
+
+/// A language server's response to a request sent by [`request_language_server`].
+pub(crate) struct LanguageServerResponse<T> {
+    /// The response, or `None` when the server didn't answer in time.
+    pub response: Option<Result<T>>,
+    /// How many times the request was sent again after failing transiently.
+    pub retries: usize,
+}
+
+/// Sends the request made by `request` and waits for the response like
+/// [`wait_for_language_server`]. A request that fails transiently, e.g. because its file changed
+/// while the server was answering or because the server restarted, is sent again after a backoff,
+/// up to [`MAX_LANGUAGE_SERVER_ATTEMPTS`] times in all, rather than failing the tool call.
+pub(crate) async fn request_language_server<T>(
+    mut request: impl FnMut(&mut App) -> Task<Result<T>>,
+    event_stream: &ToolCallEventStream,
+    cx: &mut AsyncApp,
+) -> Result<LanguageServerResponse<T>> {
+    let mut retries = 0;
+    loop {
+        let response =
+            wait_for_language_server(cx.update(|cx| request(cx))?, event_stream, cx).await?;
+        let error = match response {
+            Some(Err(error))
+                if is_transient_language_server_error(&error)
+                    && retries + 1 < MAX_LANGUAGE_SERVER_ATTEMPTS =>
+            {
+                error
+            }
+            response => return Ok(LanguageServerResponse { response, retries }),
+        };
+
+        let delay = LANGUAGE_SERVER_RETRY_DELAY * 2u32.pow(retries as u32);
+        let jitter = Duration::from_millis(rand::random_range(0..=delay.as_millis() as u64 / 2));
+        log::debug!("Retrying language server request in {delay:?} after it failed: {error:#}");
+        let mut backoff = cx.background_executor().timer(delay + jitter).fuse();
+        let cancelled = event_stream.cancelled_by_user().fuse();
+        futures::pin_mut!(cancelled);
+        futures::select_biased! {
+            _ = cancelled => {
+                return Ok(LanguageServerResponse {
+                    response: Some(Err(error)),
+                    retries,
+                });
+            }
+            _ = backoff => {}
+        }
+        retries += 1;
+    }
+}
+
+/// Tells the model that a response only came after retries, since it may be worth waiting for the
+/// language server to settle before relying on it.
+pub(crate) fn language_server_retry_note(retries: usize) -> Option<String> {
+    (retries > 0).then(|| {
+        format!(
+            "The language server failed transiently (e.g. while catching up with an edit or restarting), so the request was retried {retries} time(s).\n"
+        )
+    })
+}
+
+/// How often [`LanguageServerReadiness::wait`] checks whether the servers became ready.
+const READINESS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Whether the language servers for a buffer can be expected to answer requests about it fully.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LanguageServerReadiness {
+    Ready,
+    /// No language server for the buffer's language has started yet.
+    NotRunning,
+    /// The named language servers are still working, e.g. indexing the project, so their answers
+    /// may be missing results.
+    Busy(Vec<String>),
+}
+
+impl LanguageServerReadiness {
+    pub(crate) fn of_buffer(project: &Project, buffer: &Buffer, cx: &App) -> Self {
+        let Some(language) = buffer.language() else {
+            return Self::NotRunning;
+        };
+        let relevant_language_servers = project
+            .languages()
+            .lsp_adapters(&language.name())
+            .into_iter()
+            .map(|adapter| adapter.name())
+            .collect::<HashSet<_>>();
+        let mut running = false;
+        let mut busy = Vec::new();
+        for (_, status) in project.language_server_statuses(cx) {
+            if !relevant_language_servers.contains(&status.name) {
+                continue;
+            }
+            running = true;
+            // Checks that only produce diagnostics, like `cargo check`, don't hold up navigation.
+            if status
+                .pending_work
+                .values()
+                .any(|progress| !progress.is_disk_based_diagnostics_progress)
+            {
+                busy.push(status.name.to_string());
+            }
+        }
+        if !running {
+            Self::NotRunning
+        } else if busy.is_empty() {
+            Self::Ready
+        } else {
+            Self::Busy(busy)
+        }
+    }
+
+    /// Waits up to `timeout` for the language servers for `buffer` to become ready, returning how
+    /// ready they were when it stopped waiting.
+    pub(crate) async fn wait(
+        project: &Entity<Project>,
+        buffer: &Entity<Buffer>,
+        timeout: Duration,
+        cx: &mut AsyncApp,
+    ) -> Result<Self> {
+        let mut waited = Duration::ZERO;
+        loop {
+            let readiness = buffer.read_with(cx, |buffer, cx| {
+                Self::of_buffer(project.read(cx), buffer, cx)
+            })?;
+            if readiness == Self::Ready || waited >= timeout {
+                return Ok(readiness);
+            }
+            cx.background_executor()
+                .timer(READINESS_POLL_INTERVAL)
+                .await;
+            waited += READINESS_POLL_INTERVAL;
+        }
+    }
+
+    /// Explains why results may be missing, unless the servers are ready.
+    pub(crate) fn note(&self) -> Option<String> {
+        match self {
+            Self::Ready => None,
+            Self::NotRunning => Some(
+                "No language server was running for this file yet, so results may be missing. Pass `wait_for_ready_ms` to wait for one to start.".to_string(),
+            ),
+            Self::Busy(language_servers) => Some(format!(
+                "{} still indexing, so results may be missing. Pass `wait_for_ready_ms` to wait for indexing to finish.",
+                match language_servers.as_slice() {
+                    [language_server] => format!("The language server ({language_server}) was"),
+                    _ => format!("The language servers ({}) were", language_servers.join(", ")),
+                }
+            )),
+        }
+    }
+}
+
+/// A result that one or more language servers reported, e.g. a reference or a definition.
+pub(crate) struct LanguageServerResult<T> {
+    pub result: T,
+    /// The names of the language servers that reported the result, in the order they did.
+    pub language_servers: Vec<String>,
+}
+
+/// The name of a language server to show the user and the model, or its id when it has stopped.
+pub(crate) fn language_server_name(
+    project: &Project,
+    server_id: LanguageServerId,
+    cx: &App,
+) -> String {
+    project
+        .language_server_statuses(cx)
+        .find(|(id, _)| *id == server_id)
+        .map_or_else(
+            || server_id.to_string(),
+            |(_, status)| status.name.to_string(),
+        )
+}
+
+/// Merges the results of the language servers that answered the same request, listing a result
+/// that several of them reported once, so that a buffer with more than one server (e.g.
+/// rust-analyzer plus Tailwind) doesn't list each location twice. `location` returns where a
+/// result points, which is what tells results apart.
+pub(crate) fn merge_language_server_results<T>(
+    project: &Project,
+    responses: Vec<(LanguageServerId, Vec<T>)>,
+    location: impl Fn(&T) -> &Location,
+    cx: &App,
+) -> Vec<LanguageServerResult<T>> {
+    let mut merged = Vec::<LanguageServerResult<T>>::new();
+    let mut indices = HashMap::<(EntityId, Range<usize>), usize>::default();
+    for (server_id, results) in responses {
+        let server_name = language_server_name(project, server_id, cx);
+        for result in results {
+            let result_location = location(&result);
+            let key = (
+                result_location.buffer.entity_id(),
+                result_location
+                    .range
+                    .to_offset(result_location.buffer.read(cx)),
+            );
+            if let Some(&index) = indices.get(&key) {
+                let language_servers = &mut merged[index].language_servers;
+                if !language_servers.contains(&server_name) {
+                    language_servers.push(server_name.clone());
+                }
+            } else {
+                indices.insert(key, merged.len());
+                merged.push(LanguageServerResult {
+                    result,
+                    language_servers: vec![server_name.clone()],
+                });
+            }
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use collections::HashSet;
+    use fs::FakeFs;
+    use gpui::TestAppContext;
+    use serde_json::json;
+    use util::path;
+
+    #[gpui::test]
+    async fn test_resolve_project_path_across_worktrees(cx: &mut TestAppContext) {
+        test_support::init_test(cx);
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(path!("/a/app"), json!({ "src": { "main.rs": "" } }))
+            .await;
+        fs.insert_tree(
+            path!("/b/app"),
+            json!({ "src": { "main.rs": "" }, "build.rs": "" }),
+        )
+        .await;
+        let project =
+            Project::test(fs, [path!("/a/app").as_ref(), path!("/b/app").as_ref()], cx).await;
+
+        project.read_with(cx, |project, cx| {
+            let resolve = |path: &str| {
+                resolve_project_path(project, path, cx)
+                    .map(|project_path| display_project_path(project, &project_path, cx).unwrap())
+                    .map_err(|error| error.to_string())
+            };
+
+            assert_eq!(
+                resolve("src/main.rs"),
+                Err(format!(
+                    "Path src/main.rs matches files in more than one worktree. Use one of these paths instead:\n{}\n{}",
+                    path!("/a/app/src/main.rs"),
+                    path!("/b/app/src/main.rs")
+                ))
+            );
+            assert!(resolve("app/src/main.rs").is_err());
+            assert_eq!(
+                resolve(path!("/a/app/src/main.rs")),
+                Ok(path!("/a/app/src/main.rs").to_string())
+            );
+            assert_eq!(
+                resolve("app/build.rs"),
+                Ok(path!("/b/app/build.rs").to_string())
+            );
+            assert_eq!(
+                resolve("main.rs"),
+                Err(format!(
+                    "Path main.rs not found in project. Did you mean one of these?\n{}\n{}",
+                    path!("/a/app/src/main.rs"),
+                    path!("/b/app/src/main.rs")
+                ))
+            );
+            assert_eq!(
+                resolve("missing.rs"),
+                Err("Path missing.rs not found in project".to_string())
+            );
+        });
+    }
+
+    #[test]
+    fn test_built_in_tool_examples_match_input_schemas() {
+        for format in [
+            LanguageModelToolSchemaFormat::JsonSchema,
+            LanguageModelToolSchemaFormat::JsonSchemaSubset,
+        ] {
+            let mut names = HashSet::default();
+            for schema in built_in_tool_schemas(format) {
+                let schema = schema.unwrap_or_else(|error| {
+                    panic!("failed to build a {format:?} tool schema: {error:#}")
+                });
+                assert!(
+                    names.insert(schema.name.clone()),
+                    "duplicate tool {}",
+                    schema.name
+                );
+
+                let properties = schema.input_schema["properties"]
+                    .as_object()
+                    .cloned()
+                    .unwrap_or_default();
+                let required = schema.input_schema["required"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default();
+                for example in &schema.input_examples {
+                    let example = example
+                        .as_object()
+                        .unwrap_or_else(|| panic!("{} example is not an object", schema.name));
+                    for key in example.keys() {
+                        assert!(
+                            properties.contains_key(key),
+                            "{} example sets `{key}`, which its input schema lacks",
+                            schema.name
+                        );
+                    }
+                    for key in &required {
+                        let key = key.as_str().unwrap_or_default();
+                        assert!(
+                            example.contains_key(key),
+                            "{} example lacks required `{key}`",
+                            schema.name
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[gpui::test]
+    async fn test_request_language_server_retries_transient_failures(cx: &mut TestAppContext) {
+        test_support::init_test(cx);
+        let transient_error =
+            || anyhow::anyhow!("Find all references via rust-analyzer failed: content modified");
+
+        let response = request_with_responses(
+            vec![Err(transient_error()), Err(transient_error()), Ok(1)],
+            cx,
+        )
+        .await;
+        assert_eq!(response.retries, 2);
+        assert_eq!(response.response.and_then(Result::ok), Some(1));
+
+        let response =
+            request_with_responses(vec![Err(anyhow::anyhow!("no symbol here")), Ok(1)], cx).await;
+        assert_eq!(response.retries, 0);
+        assert!(matches!(response.response, Some(Err(_))));
+
+        let response = request_with_responses(
+            (0..MAX_LANGUAGE_SERVER_ATTEMPTS)
+                .map(|_| Err(transient_error()))
+                .collect(),
+            cx,
+        )
+        .await;
+        assert_eq!(response.retries, MAX_LANGUAGE_SERVER_ATTEMPTS - 1);
+        assert!(matches!(response.response, Some(Err(_))));
+
+        let response_error = |code, message: &str| {
+            anyhow::Error::new(lsp::LspResponseError {
+                code,
+                message: message.to_string(),
+            })
+        };
+        let response = request_with_responses(
+            vec![
+                Err(response_error(
+                    lsp::error_codes::CONTENT_MODIFIED,
+                    "file changed",
+                )),
+                Err(response_error(
+                    lsp::error_codes::REQUEST_CANCELLED,
+                    "cancelled",
+                )),
+                Ok(1),
+            ],
+            cx,
+        )
+        .await;
+        assert_eq!(response.retries, 2);
+        assert_eq!(response.response.and_then(Result::ok), Some(1));
+
+        let response = request_with_responses(
+            vec![
+                Err(response_error(
+                    lsp::error_codes::REQUEST_FAILED,
+                    "no definition: content modified",
+                )),
+                Ok(1),
+            ],
+            cx,
+        )
+        .await;
+        assert_eq!(response.retries, 0);
+        assert!(matches!(response.response, Some(Err(_))));
+    }
+
+    async fn request_with_responses(
+        responses: Vec<Result<usize>>,
+        cx: &mut TestAppContext,
+    ) -> LanguageServerResponse<usize> {
+        let (event_stream, _events) = ToolCallEventStream::test();
+        let mut responses = responses.into_iter();
+        let request = cx.spawn(|mut cx| async move {
+            request_language_server(
+                |_| Task::ready(responses.next().expect("sent more requests than expected")),
+                &event_stream,
+                &mut cx,
+            )
+            .await
+        });
+        for _ in 0..MAX_LANGUAGE_SERVER_ATTEMPTS {
+            cx.executor().advance_clock(Duration::from_secs(1));
+            cx.run_until_parked();
+        }
+        request.await.expect("app was dropped")
+    }
+}
@@ -0,0 +1,281 @@
+use super::{AnchorMatch, ContextualAnchor, TokenSyntax, match_token_offsets};
+use anyhow::Result;
+use collections::HashMap;
+use gpui::{App, AppContext as _, Context, Entity, EntityId, Global, Subscription};
+use language::{Buffer, BufferEvent, BufferId, BufferSnapshot, Point, ToOffset as _};
+use project::Project;
+use std::ops::Range;
+
+/// How many resolved anchors each project's cache keeps.
+const MAX_CACHED_ANCHORS: usize = 256;
+
+/// Where anchors were recently resolved in a project's buffers, so that resolving the same anchor
+/// again skips scanning the whole buffer for its `context`.
+///
+/// Only anchors whose `context` matched exactly once are cached. An entry follows edits to its
+/// buffer, and is dropped as soon as an edit touches the lines its `context` matched or makes
+/// `context` match somewhere else too.
+#[derive(Default)]
+pub struct AnchorCache {
+    /// Least recently used first.
+    entries: Vec<CachedAnchor>,
+    buffer_subscriptions: HashMap<BufferId, Vec<Subscription>>,
+}
+
+struct CachedAnchor {
+    buffer_id: BufferId,
+    anchor: ContextualAnchor,
+    token_start: text::Anchor,
+    /// The whole lines `context` matched.
+    context: Range<text::Anchor>,
+    /// The buffer version that `token_start` and `context` are known to be correct for.
+    version: clock::Global,
+}
+
+#[derive(Default)]
+struct ProjectAnchorCaches(HashMap<EntityId, Entity<AnchorCache>>);
+
+impl Global for ProjectAnchorCaches {}
+
+impl AnchorCache {
+    /// Returns `project`'s cache, which lives as long as the project.
+    pub fn for_project(project: &Entity<Project>, cx: &mut App) -> Entity<Self> {
+        let project_id = project.entity_id();
+        if let Some(cache) = cx
+            .default_global::<ProjectAnchorCaches>()
+            .0
+            .get(&project_id)
+        {
+            return cache.clone();
+        }
+        let cache = cx.new(|_| Self::default());
+        cx.default_global::<ProjectAnchorCaches>()
+            .0
+            .insert(project_id, cache.clone());
+        cx.observe_release(project, move |_, cx| {
+            cx.default_global::<ProjectAnchorCaches>()
+                .0
+                .remove(&project_id);
+        })
+        .detach();
+        cache
+    }
+
+    /// Resolves `anchor` in `snapshot`, a snapshot of `buffer`, like [`super::resolve_in_buffer`], reusing
+    /// where it resolved before when no edit since then can have changed that.
+    pub fn resolve(
+        &mut self,
+        buffer: &Entity<Buffer>,
+        snapshot: &BufferSnapshot,
+        anchor: &ContextualAnchor,
+        display_path: &str,
+        syntax: Option<TokenSyntax>,
+        cx: &mut Context<Self>,
+    ) -> Result<AnchorMatch> {
+        let buffer_id = snapshot.remote_id();
+        if let Some(index) = self
+            .entries
+            .iter()
+            .position(|entry| entry.buffer_id == buffer_id && entry.anchor == *anchor)
+        {
+            let entry = self.entries.remove(index);
+            let token_start = entry.token_start.to_offset(snapshot);
+            // The syntax tree can change without the lines around the token being edited, e.g.
+            // when a block comment is opened above them.
+            if entry.version == *snapshot.version()
+                && anchor.syntax_confirms_token(snapshot, token_start)
+            {
+                self.entries.push(entry);
+                return match_token_offsets(
+                    anchor,
+                    snapshot,
+                    vec![token_start],
+                    display_path,
+                    syntax,
+                );
+            }
+        }
+
+        let token_offsets = anchor.resolve_token_offsets(snapshot);
+        if let [token_start] = token_offsets.as_slice() {
+            self.insert(buffer, snapshot, anchor, *token_start, cx);
+        }
+        match_token_offsets(anchor, snapshot, token_offsets, display_path, syntax)
+    }
+
+    fn insert(
+        &mut self,
+        buffer: &Entity<Buffer>,
+        snapshot: &BufferSnapshot,
+        anchor: &ContextualAnchor,
+        token_start: usize,
+        cx: &mut Context<Self>,
+    ) {
+        let buffer_id = snapshot.remote_id();
+        if self.entries.len() >= MAX_CACHED_ANCHORS {
+            let evicted = self.entries.remove(0);
+            if !self
+                .entries
+                .iter()
+                .any(|entry| entry.buffer_id == evicted.buffer_id)
+            {
+                self.buffer_subscriptions.remove(&evicted.buffer_id);
+            }
+        }
+
+        let context_rows = anchor.context_rows(
+            snapshot.offset_to_point(token_start).row,
+            snapshot.max_point().row,
+        );
+        let last_row = context_rows.end.saturating_sub(1).max(context_rows.start);
+        self.entries.push(CachedAnchor {
+            buffer_id,
+            anchor: anchor.clone(),
+            token_start: snapshot.anchor_after(token_start),
+            // Text inserted right before or after the lines counts as touching them.
+            context: snapshot.anchor_after(Point::new(context_rows.start, 0))
+                ..snapshot.anchor_before(Point::new(last_row, snapshot.line_len(last_row))),
+            version: snapshot.version().clone(),
+        });
+
+        self.buffer_subscriptions
+            .entry(buffer_id)
+            .or_insert_with(|| {
+                vec![
+                    cx.subscribe(buffer, Self::handle_buffer_event),
+                    cx.observe_release(buffer, move |this, _, _| {
+                        this.entries.retain(|entry| entry.buffer_id != buffer_id);
+                        this.buffer_subscriptions.remove(&buffer_id);
+                    }),
+                ]
+            });
+    }
+
+    fn handle_buffer_event(
+        &mut self,
+        buffer: Entity<Buffer>,
+        event: &BufferEvent,
+        cx: &mut Context<Self>,
+    ) {
+        if !matches!(event, BufferEvent::Edited) {
+            return;
+        }
+        let snapshot = buffer.read(cx).snapshot();
+        let buffer_id = snapshot.remote_id();
+        self.entries.retain_mut(|entry| {
+            if entry.buffer_id != buffer_id {
+                return true;
+            }
+            let survives = entry.survives_edits(&snapshot);
+            entry.version = snapshot.version().clone();
+            survives
+        });
+    }
+}
+
+impl CachedAnchor {
+    /// Whether the edits made to the buffer since [`Self::version`] leave the anchor resolving to
+    /// the same token, and only there.
+    fn survives_edits(&self, snapshot: &BufferSnapshot) -> bool {
+        let context = self.context.start.to_offset(snapshot)..self.context.end.to_offset(snapshot);
+        let token_start = self.token_start.to_offset(snapshot);
+        let context_line_count = self.anchor.context.trim_end().lines().count().max(1) as u32;
+        snapshot.edits_since::<usize>(&self.version).all(|edit| {
+            if edit.new.start <= context.end && context.start <= edit.new.end {
+                return false;
+            }
+            // An edit elsewhere only matters if `context` now matches around it, so only those
+            // lines are searched rather than the whole buffer.
+            let start_row = snapshot
+                .offset_to_point(edit.new.start)
+                .row
+                .saturating_sub(context_line_count);
+            let end_row = (snapshot.offset_to_point(edit.new.end).row + context_line_count)
+                .min(snapshot.max_point().row);
+            let surrounding_start = Point::new(start_row, 0).to_offset(snapshot);
+            let surrounding_text = snapshot
+                .text_for_range(surrounding_start..Point::new(end_row, snapshot.line_len(end_row)))
+                .collect::<String>();
+            self.anchor
+                .candidate_token_offsets(&surrounding_text)
+                .into_iter()
+                .all(|offset| surrounding_start + offset == token_start)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpui::TestAppContext;
+
+    fn anchor(context: &str, token: &str) -> ContextualAnchor {
+        ContextualAnchor {
+            path: "root/src/main.rs".into(),
+            context: context.into(),
+            token: token.into(),
+            index: None,
+            handle: None,
+            revision: None,
+            whole_word: false,
+            approximate_line: None,
+            case_insensitive: false,
+            unicode_normalize: false,
+            end_token: None,
+            end_index: None,
+        }
+    }
+
+    #[gpui::test]
+    fn test_anchor_cache_invalidation(cx: &mut TestAppContext) {
+        let text = "fn a() {\n    let total = compute(1);\n}\n\nfn b() {\n    other();\n}\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx));
+        let cache = cx.new(|_| AnchorCache::default());
+        let path = "root/src/main.rs";
+        let compute = anchor("let total = compute(1);", "compute");
+        let resolve = |cx: &mut TestAppContext| {
+            let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot());
+            let token_start = cache
+                .update(cx, |cache, cx| {
+                    cache.resolve(&buffer, &snapshot, &compute, path, None, cx)
+                })
+                .map(|anchor_match| match anchor_match {
+                    AnchorMatch::Unique(token_match) => Some(token_match.range.start),
+                    AnchorMatch::Ambiguous(_) => None,
+                });
+            let cached = cache.read_with(cx, |cache, _| cache.entries.len());
+            (token_start.ok().flatten(), cached)
+        };
+
+        let compute_start = text.find("compute").unwrap();
+        assert_eq!(resolve(cx), (Some(compute_start), 1));
+
+        // Edits elsewhere shift the cached token without dropping it.
+        buffer.update(cx, |buffer, cx| buffer.edit([(0..0, "// a\n")], None, cx));
+        assert_eq!(cache.read_with(cx, |cache, _| cache.entries.len()), 1);
+        assert_eq!(resolve(cx), (Some(compute_start + 5), 1));
+
+        // Editing the matched lines drops the entry, even when `context` still matches.
+        let line_end = buffer.read_with(cx, |buffer, _| buffer.text().find(";").unwrap() + 1);
+        buffer.update(cx, |buffer, cx| {
+            buffer.edit([(line_end..line_end, " ")], None, cx)
+        });
+        assert_eq!(cache.read_with(cx, |cache, _| cache.entries.len()), 0);
+        assert_eq!(resolve(cx), (Some(compute_start + 5), 1));
+
+        // So does an edit elsewhere that makes `context` match a second time.
+        let other_start = buffer.read_with(cx, |buffer, _| buffer.text().find("other").unwrap());
+        buffer.update(cx, |buffer, cx| {
+            buffer.edit(
+                [(
+                    other_start..other_start + "other();".len(),
+                    "let total = compute(1);",
+                )],
+                None,
+                cx,
+            )
+        });
+        assert_eq!(cache.read_with(cx, |cache, _| cache.entries.len()), 0);
+        assert_eq!(resolve(cx), (None, 0));
+    }
+}
@@ -0,0 +1,199 @@
+use super::ContextualAnchor;
+use anyhow::{Context as _, Result, anyhow};
+use collections::HashMap;
+
+const REFERENCE_HANDLE_PREFIX: &str = "ref#";
+const SYMBOL_HANDLE_PREFIX: &str = "sym:";
+
+/// Anchors that tools resolved during a thread, remembered under short handles so that later tool
+/// calls can pass `ref#3` or `sym:load` instead of repeating the whole anchor.
+///
+/// Every resolved anchor gets a `ref#N` handle. It also gets a `sym:<token>` handle as long as no
+/// other remembered anchor has the same token, so that the symbol handle never silently picks one
+/// of several symbols.
+#[derive(Default)]
+pub struct AnchorMemory {
+    anchors: Vec<ContextualAnchor>,
+    symbols: HashMap<String, SymbolHandle>,
+}
+
+#[derive(Clone, Copy)]
+enum SymbolHandle {
+    Unique(usize),
+    Ambiguous,
+}
+
+/// The handles under which an anchor was remembered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnchorHandles {
+    pub reference: String,
+    pub symbol: Option<String>,
+}
+
+impl AnchorHandles {
+    /// Tells the model how to refer to the anchor in later tool calls.
+    pub fn describe(&self) -> String {
+        match &self.symbol {
+            Some(symbol) => format!(
+                "Later tool calls can refer to this anchor with `\"handle\": \"{}\"` or `\"handle\": \"{symbol}\"`.\n",
+                self.reference
+            ),
+            None => format!(
+                "Later tool calls can refer to this anchor with `\"handle\": \"{}\"`.\n",
+                self.reference
+            ),
+        }
+    }
+}
+
+impl AnchorMemory {
+    /// Remembers `anchor`, returning the handles that refer to it. Remembering the same anchor
+    /// again returns the handles it already has.
+    pub fn remember(&mut self, anchor: &ContextualAnchor) -> AnchorHandles {
+        let anchor = ContextualAnchor {
+            handle: None,
+            ..anchor.clone()
+        };
+        let ix = match self.anchors.iter().position(|existing| *existing == anchor) {
+            Some(ix) => ix,
+            None => {
+                self.anchors.push(anchor.clone());
+                let ix = self.anchors.len() - 1;
+                self.symbols
+                    .entry(anchor.token.clone())
+                    .and_modify(|symbol| *symbol = SymbolHandle::Ambiguous)
+                    .or_insert(SymbolHandle::Unique(ix));
+                ix
+            }
+        };
+
+        let symbol = match self.symbols.get(&anchor.token) {
+            Some(SymbolHandle::Unique(symbol_ix)) if *symbol_ix == ix => {
+                Some(format!("{SYMBOL_HANDLE_PREFIX}{}", anchor.token))
+            }
+            _ => None,
+        };
+        AnchorHandles {
+            reference: format!("{REFERENCE_HANDLE_PREFIX}{}", ix + 1),
+            symbol,
+        }
+    }
+
+    /// Replaces an anchor that only names a handle with the anchor remembered under it. Anchors
+    /// without a handle are returned unchanged.
+    pub fn expand(&self, anchor: ContextualAnchor) -> Result<ContextualAnchor> {
+        match &anchor.handle {
+            Some(handle) => self.resolve(handle),
+            None => Ok(anchor),
+        }
+    }
+
+    fn resolve(&self, handle: &str) -> Result<ContextualAnchor> {
+        let ix = if let Some(number) = handle.strip_prefix(REFERENCE_HANDLE_PREFIX) {
+            let number = number
+                .parse::<usize>()
+                .with_context(|| format!("Invalid anchor handle `{handle}`"))?;
+            number.checked_sub(1)
+        } else if let Some(token) = handle.strip_prefix(SYMBOL_HANDLE_PREFIX) {
+            match self.symbols.get(token) {
+                Some(SymbolHandle::Unique(ix)) => Some(*ix),
+                Some(SymbolHandle::Ambiguous) => {
+                    return Err(anyhow!(
+                        "`{handle}` refers to more than one anchor. Use its `ref#` handle instead."
+                    ));
+                }
+                None => None,
+            }
+        } else {
+            return Err(anyhow!(
+                "Invalid anchor handle `{handle}`. Handles look like `ref#3` or `sym:name`."
+            ));
+        };
+
+        ix.and_then(|ix| self.anchors.get(ix))
+            .cloned()
+            .with_context(|| {
+                format!(
+                    "No anchor has been remembered as `{handle}`. Pass `path`, `context` and `token` instead."
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anchor(context: &str, token: &str) -> ContextualAnchor {
+        ContextualAnchor {
+            path: "root/src/main.rs".into(),
+            context: context.into(),
+            token: token.into(),
+            index: None,
+            handle: None,
+            revision: None,
+            whole_word: false,
+            approximate_line: None,
+            case_insensitive: false,
+            unicode_normalize: false,
+            end_token: None,
+            end_index: None,
+        }
+    }
+
+    fn handle(handle: &str) -> ContextualAnchor {
+        ContextualAnchor {
+            path: String::new(),
+            context: String::new(),
+            token: String::new(),
+            index: None,
+            handle: Some(handle.into()),
+            revision: None,
+            whole_word: false,
+            approximate_line: None,
+            case_insensitive: false,
+            unicode_normalize: false,
+            end_token: None,
+            end_index: None,
+        }
+    }
+
+    #[test]
+    fn test_anchor_memory_handles() {
+        let mut memory = AnchorMemory::default();
+        let load = anchor("pub fn load(path: &Path) {", "load");
+        let first_helper = anchor("let first = helper();", "helper");
+
+        assert_eq!(
+            memory.remember(&load),
+            AnchorHandles {
+                reference: "ref#1".into(),
+                symbol: Some("sym:load".into()),
+            }
+        );
+        assert_eq!(memory.remember(&first_helper).reference, "ref#2");
+        assert_eq!(memory.remember(&load).reference, "ref#1");
+
+        assert_eq!(memory.expand(handle("ref#1")).unwrap(), load);
+        assert_eq!(memory.expand(handle("sym:helper")).unwrap(), first_helper);
+        assert_eq!(memory.expand(first_helper.clone()).unwrap(), first_helper);
+
+        // Once a second `helper` is remembered, `sym:helper` no longer identifies a single anchor.
+        let second_helper = anchor("let second = helper();", "helper");
+        assert_eq!(
+            memory.remember(&second_helper),
+            AnchorHandles {
+                reference: "ref#3".into(),
+                symbol: None,
+            }
+        );
+        assert_eq!(memory.remember(&first_helper).symbol, None);
+        assert!(memory.expand(handle("sym:helper")).is_err());
+        assert_eq!(memory.expand(handle("ref#3")).unwrap(), second_helper);
+
+        assert!(memory.expand(handle("ref#0")).is_err());
+        assert!(memory.expand(handle("ref#4")).is_err());
+        assert!(memory.expand(handle("sym:missing")).is_err());
+        assert!(memory.expand(handle("load")).is_err());
+    }
+}
@@ -0,0 +1,576 @@
+use super::support::DRY_RUN_NOTE;
+use super::{
+    AnchorMemory, ContextualAnchor, EditByContextToolInput, display_project_path,
+    resolve_project_path,
+};
+use crate::{AgentTool, ToolCallAccess, ToolCallEventStream};
+use acp_thread::Diff;
+use action_log::ActionLog;
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use gpui::{App, AppContext as _, Entity, SharedString, Task};
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::{Project, ProjectPath, WorktreeSettings};
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{fmt::Write as _, ops::Range, sync::Arc, time::Instant};
+
+/// Applies several anchor-based edits, possibly across files, all or nothing.
+///
+/// Every anchor is resolved against the files as they are before any edit is made, so later
+/// edits must not anchor on text that earlier edits introduce. If any anchor fails to resolve,
+/// or two edits overlap, no edit is applied and the reason for each failure is returned.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct BatchEditByContextToolInput {
+    /// The edits to apply, each as for the `edit_by_context` tool.
+    pub edits: Vec<EditByContextToolInput>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct BatchEditByContextToolOutput {
+    /// Whether the edits were applied. When false, `failures` explains why, unless this was a dry
+    /// run.
+    pub applied: bool,
+    /// The files that were edited, sorted by path.
+    pub files: Vec<BatchEditedFile>,
+    pub failures: Vec<BatchEditFailure>,
+    /// Whether the edits were only computed, without being applied.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct BatchEditedFile {
+    pub path: String,
+    /// How many of the edits were made to this file.
+    pub edit_count: u32,
+    /// A unified diff of all edits to this file.
+    pub diff: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct BatchEditFailure {
+    /// The 0-based index of the failed edit in `edits`.
+    pub index: u32,
+    pub reason: String,
+}
+
+impl From<BatchEditByContextToolOutput> for LanguageModelToolResultContent {
+    fn from(output: BatchEditByContextToolOutput) -> Self {
+        let mut text = String::new();
+        if output.failures.is_empty() {
+            let edit_count = output.files.iter().map(|file| file.edit_count).sum::<u32>();
+            if output.dry_run {
+                write!(text, "{DRY_RUN_NOTE} Would apply").ok();
+            } else {
+                write!(text, "Applied").ok();
+            }
+            writeln!(
+                text,
+                " {edit_count} edit(s) to {} file(s).",
+                output.files.len()
+            )
+            .ok();
+            for file in &output.files {
+                write!(text, "\n## {}\n\n```diff\n{}\n```\n", file.path, file.diff).ok();
+            }
+        } else {
+            writeln!(
+                text,
+                "No edits were applied because {} edit(s) failed:",
+                output.failures.len()
+            )
+            .ok();
+            for failure in &output.failures {
+                writeln!(text, "- Edit {}: {}", failure.index, failure.reason).ok();
+            }
+        }
+        text.into()
+    }
+}
+
+/// Tool: batch_edit_by_context
+pub struct BatchEditByContextTool {
+    project: Entity<Project>,
+    action_log: Entity<ActionLog>,
+    anchor_memory: Entity<AnchorMemory>,
+}
+
+impl BatchEditByContextTool {
+    pub fn new(
+        project: Entity<Project>,
+        action_log: Entity<ActionLog>,
+        anchor_memory: Entity<AnchorMemory>,
+    ) -> Self {
+        Self {
+            project,
+            action_log,
+            anchor_memory,
+        }
+    }
+
+    fn validate(
+        &self,
+        anchor: ContextualAnchor,
+        cx: &App,
+    ) -> Result<(ContextualAnchor, ProjectPath)> {
+        let anchor = self.anchor_memory.read(cx).expand(anchor)?;
+        anchor.validate_basic()?;
+        if let Some(revision) = &anchor.revision {
+            return Err(anyhow!(
+                "Only the project's current files can be edited, not {revision}."
+            ));
+        }
+        let project_path = resolve_project_path(self.project.read(cx), &anchor.path, cx)?;
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Err(anyhow!(
+                "Cannot edit file because its path matches the file_scan_exclusions or private_files setting: {}",
+                &anchor.path
+            ));
+        }
+        Ok((anchor, project_path))
+    }
+}
+
+/// The edits to one file, resolved before anything is applied.
+struct FileEdits {
+    project_path: ProjectPath,
+    display_path: String,
+    /// Indices into the input's `edits`.
+    edit_indices: Vec<usize>,
+}
+
+impl AgentTool for BatchEditByContextTool {
+    type Input = BatchEditByContextToolInput;
+    type Output = BatchEditByContextToolOutput;
+
+    fn name() -> &'static str {
+        "batch_edit_by_context"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Edit
+    }
+
+    fn call_access(&self, input: &Self::Input, cx: &App) -> ToolCallAccess {
+        let anchor_memory = self.anchor_memory.read(cx);
+        match input
+            .edits
+            .iter()
+            .map(|edit| Ok(anchor_memory.expand(edit.anchor.clone())?.path))
+            .collect::<Result<Vec<_>>>()
+        {
+            Ok(paths) => ToolCallAccess::mutates_paths(self.project.read(cx), paths, cx),
+            Err(_) => ToolCallAccess::MutatesAnything,
+        }
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => format!("Apply {} edit(s)", input.edits.len()).into(),
+            Err(_) => "Apply edits".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        if input.edits.is_empty() {
+            return Task::ready(Err(anyhow!("Provide at least one edit.")));
+        }
+        let project = self.project.clone();
+        let action_log = self.action_log.clone();
+        let dry_run = event_stream.dry_run();
+
+        let mut failures = Vec::new();
+        let mut anchors = Vec::new();
+        let mut files: Vec<FileEdits> = Vec::new();
+        for (index, edit) in input.edits.iter().enumerate() {
+            match self.validate(edit.anchor.clone(), cx) {
+                Ok((anchor, project_path)) => {
+                    match files
+                        .iter_mut()
+                        .find(|file| file.project_path == project_path)
+                    {
+                        Some(file) => file.edit_indices.push(index),
+                        None => files.push(FileEdits {
+                            display_path: display_project_path(project.read(cx), &project_path, cx)
+                                .unwrap_or_else(|| anchor.path.clone()),
+                            project_path,
+                            edit_indices: vec![index],
+                        }),
+                    }
+                    anchors.push(Some(anchor));
+                }
+                Err(error) => {
+                    failures.push(BatchEditFailure {
+                        index: index as u32,
+                        reason: format!("{error:#}"),
+                    });
+                    anchors.push(None);
+                }
+            }
+        }
+
+        cx.spawn(async move |cx| {
+            let mut buffers = Vec::new();
+            for file in &files {
+                let buffer = project
+                    .update(cx, |project, cx| {
+                        project.open_buffer(file.project_path.clone(), cx)
+                    })?
+                    .await?;
+                buffer
+                    .read_with(cx, |buffer, _| buffer.parsing_idle())?
+                    .await;
+                buffers.push(buffer);
+            }
+
+            // From here on nothing is awaited until every edit is applied, so the buffers can't
+            // change between resolving the anchors and editing.
+            let mut resolved = Vec::new();
+            for (file, buffer) in files.iter().zip(&buffers) {
+                let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot())?;
+                let mut ranges: Vec<(usize, Range<usize>)> = Vec::new();
+                for &index in &file.edit_indices {
+                    let Some(anchor) = &anchors[index] else {
+                        continue;
+                    };
+                    match anchor.resolve_unique_token_offset(&snapshot, &file.display_path) {
+                        Ok(token_offset) => {
+                            let range =
+                                input.edits[index]
+                                    .edit_mode
+                                    .range(anchor, &snapshot, token_offset);
+                            ranges.push((index, range));
+                        }
+                        Err(error) => failures.push(BatchEditFailure {
+                            index: index as u32,
+                            reason: format!("{error:#}"),
+                        }),
+                    }
+                }
+                ranges.sort_by_key(|(index, range)| (range.start, range.end, *index));
+                // Compare each range with the one reaching furthest so far rather than with its
+                // neighbor, which may end before a longer range that both lie within.
+                let mut furthest: Option<(usize, usize)> = None;
+                for (index, range) in &ranges {
+                    match furthest {
+                        Some((furthest_index, furthest_end)) if range.start < furthest_end => {
+                            failures.push(BatchEditFailure {
+                                index: *index as u32,
+                                reason: format!(
+                                    "It overlaps edit {furthest_index} in {}.",
+                                    file.display_path
+                                ),
+                            });
+                            if range.end > furthest_end {
+                                furthest = Some((*index, range.end));
+                            }
+                        }
+                        _ => furthest = Some((*index, range.end)),
+                    }
+                }
+                resolved.push((snapshot.text(), ranges));
+            }
+
+            if !failures.is_empty() {
+                failures.sort_by_key(|failure| failure.index);
+                return Ok(BatchEditByContextToolOutput {
+                    applied: false,
+                    files: Vec::new(),
+                    failures,
+                    dry_run,
+                });
+            }
+
+            // Apply every file's edits in one update, so that nothing can land between them, and
+            // each file's as one transaction of its own, so that a single undo reverts all of them
+            // without also reverting edits made just before or after the batch.
+            let edited = cx.update(|cx| {
+                let now = Instant::now();
+                let mut edited = Vec::new();
+                for ((file, buffer), (old_text, ranges)) in files.iter().zip(&buffers).zip(resolved)
+                {
+                    action_log.update(cx, |action_log, cx| {
+                        action_log.buffer_read(buffer.clone(), cx)
+                    });
+                    let edited_buffer = if dry_run {
+                        buffer.update(cx, |buffer, cx| buffer.branch(cx))
+                    } else {
+                        buffer.clone()
+                    };
+                    let diff = cx.new(|cx| Diff::new(edited_buffer.clone(), cx));
+                    event_stream.update_diff(diff.clone());
+                    let edit_count = ranges.len() as u32;
+                    edited_buffer.update(cx, |buffer, cx| {
+                        buffer.finalize_last_transaction();
+                        buffer.start_transaction_at(now);
+                        buffer.edit(
+                            ranges.into_iter().map(|(index, range)| {
+                                (range, input.edits[index].replacement.clone())
+                            }),
+                            None,
+                            cx,
+                        );
+                        buffer.end_transaction_at(now, cx);
+                        buffer.finalize_last_transaction();
+                    });
+                    if !dry_run {
+                        action_log.update(cx, |action_log, cx| {
+                            action_log.buffer_edited(buffer.clone(), cx)
+                        });
+                    }
+                    diff.update(cx, |diff, cx| diff.finalize(cx));
+                    edited.push((
+                        file.display_path.clone(),
+                        edit_count,
+                        old_text,
+                        edited_buffer.read(cx).text(),
+                    ));
+                }
+                edited
+            })?;
+
+            let mut edited_files = Vec::new();
+            for (path, edit_count, old_text, new_text) in edited {
+                let diff = cx
+                    .background_spawn(async move { language::unified_diff(&old_text, &new_text) })
+                    .await;
+                edited_files.push(BatchEditedFile {
+                    path,
+                    edit_count,
+                    diff,
+                });
+            }
+            edited_files.sort_by(|left, right| left.path.cmp(&right.path));
+
+            Ok(BatchEditByContextToolOutput {
+                applied: !dry_run,
+                files: edited_files,
+                failures: Vec::new(),
+                dry_run,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EditMode;
+    use crate::test_support::{ToolTestProject, init_test, run_tool};
+    use gpui::TestAppContext;
+    use serde_json::json;
+
+    fn edit(path: &str, context: &str, token: &str, replacement: &str) -> EditByContextToolInput {
+        EditByContextToolInput {
+            anchor: ContextualAnchor {
+                path: path.into(),
+                context: context.into(),
+                token: token.into(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            replacement: replacement.into(),
+            edit_mode: EditMode::ReplaceToken,
+        }
+    }
+
+    #[gpui::test]
+    async fn test_batch_edit_is_all_or_nothing(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "lib.rs": "pub fn compute(value: u32) -> u32 {\n    value + 1\n}\n",
+                    "main.rs": "fn main() {\n    let total = lib::compute(1);\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let action_log = cx.new(|_| ActionLog::new(test_project.project.clone()));
+        let tool = Arc::new(BatchEditByContextTool::new(
+            test_project.project.clone(),
+            action_log.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let rename_definition = edit(
+            "root/src/lib.rs",
+            "pub fn compute(value: u32) -> u32 {",
+            "compute",
+            "increment",
+        );
+        let rename_call = edit(
+            "root/src/main.rs",
+            "let total = lib::compute(1);",
+            "compute",
+            "increment",
+        );
+
+        let (output, _) = run_tool(
+            tool.clone(),
+            BatchEditByContextToolInput {
+                edits: vec![
+                    rename_definition.clone(),
+                    edit("root/src/main.rs", "let missing = 1;", "missing", "found"),
+                    rename_call.clone(),
+                ],
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+        assert!(!output.applied);
+        assert_eq!(
+            output
+                .failures
+                .iter()
+                .map(|failure| failure.index)
+                .collect::<Vec<_>>(),
+            vec![1]
+        );
+        cx.run_until_parked();
+        assert_eq!(
+            action_log.read_with(cx, |log, cx| log.changed_buffers(cx).len()),
+            0
+        );
+
+        let (output, _) = run_tool(
+            tool,
+            BatchEditByContextToolInput {
+                edits: vec![rename_definition, rename_call],
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+        assert!(output.applied);
+        assert_eq!(
+            output
+                .files
+                .iter()
+                .map(|file| (file.path.as_str(), file.edit_count))
+                .collect::<Vec<_>>(),
+            vec![("root/src/lib.rs", 1), ("root/src/main.rs", 1)]
+        );
+        assert!(
+            output.files[1]
+                .diff
+                .contains("+    let total = lib::increment(1);")
+        );
+        cx.run_until_parked();
+        assert_eq!(
+            action_log.read_with(cx, |log, cx| log.changed_buffers(cx).len()),
+            2
+        );
+    }
+
+    #[gpui::test]
+    async fn test_batch_edit_overlaps_and_undo(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "lib.rs": "pub fn add(first: u32, second: u32) -> u32 {\n    first + second\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let tool = Arc::new(BatchEditByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| ActionLog::new(test_project.project.clone())),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let signature = EditByContextToolInput {
+            edit_mode: EditMode::ReplaceContext,
+            ..edit(
+                "root/src/lib.rs",
+                "pub fn add(first: u32, second: u32) -> u32 {",
+                "add",
+                "pub fn sum(left: u32, right: u32) -> u32 {",
+            )
+        };
+        let rename_first = edit("root/src/lib.rs", "add(first: u32", "first", "left");
+        let rename_second = edit("root/src/lib.rs", "second: u32)", "second", "right");
+
+        // Both renames lie within the signature, though only the first is next to it once sorted.
+        let (output, _) = run_tool(
+            tool.clone(),
+            BatchEditByContextToolInput {
+                edits: vec![signature, rename_first.clone(), rename_second.clone()],
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+        assert!(!output.applied);
+        assert_eq!(
+            output.failures,
+            [1, 2]
+                .map(|index| BatchEditFailure {
+                    index,
+                    reason: "It overlaps edit 0 in root/src/lib.rs.".to_string(),
+                })
+                .to_vec()
+        );
+
+        let (output, _) = run_tool(
+            tool,
+            BatchEditByContextToolInput {
+                edits: vec![rename_first, rename_second],
+            },
+            cx,
+        )
+        .await;
+        assert!(output.expect("tool failed").applied);
+        let buffer = test_project
+            .project
+            .update(cx, |project, cx| {
+                project.open_local_buffer(test_project.abs_path("src/lib.rs"), cx)
+            })
+            .await
+            .expect("failed to open buffer");
+        buffer.update(cx, |buffer, cx| {
+            assert!(
+                buffer
+                    .text()
+                    .starts_with("pub fn add(left: u32, right: u32) -> u32 {")
+            );
+            buffer.undo(cx);
+            assert!(
+                buffer
+                    .text()
+                    .starts_with("pub fn add(first: u32, second: u32) -> u32 {")
+            );
+        });
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::{Context as _, Result};
+use clock::{Lamport, ReplicaId};
+use language::Buffer;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, str::FromStr};
+
+/// The version of a buffer when a tool read or wrote it, in a compact form that can travel in
+/// structured tool output and be passed back to a later tool call.
+///
+/// It is written as the buffer's non-zero `replica:seq` pairs joined by commas (e.g. `0:12,2:3`),
+/// so it stays short for local buffers and can be compared exactly against the current version.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BufferVersion(clock::Global);
+
+impl BufferVersion {
+    pub fn of(buffer: &Buffer) -> Self {
+        Self(buffer.version())
+    }
+
+    /// Returns whether `buffer` has changed since this version was taken.
+    pub fn is_stale(&self, buffer: &Buffer) -> bool {
+        let current = buffer.version();
+        !(self.0.observed_all(&current) && current.observed_all(&self.0))
+    }
+}
+
+impl fmt::Display for BufferVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut separator = "";
+        for timestamp in self.0.iter().filter(|timestamp| timestamp.value > 0) {
+            write!(
+                f,
+                "{separator}{}:{}",
+                timestamp.replica_id.as_u16(),
+                timestamp.value
+            )?;
+            separator = ",";
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for BufferVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(stamp: &str) -> Result<Self> {
+        stamp
+            .split(',')
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| -> Result<Lamport> {
+                let (replica_id, value) = entry
+                    .split_once(':')
+                    .with_context(|| format!("invalid buffer version entry {entry:?}"))?;
+                Ok(Lamport {
+                    replica_id: ReplicaId::new(replica_id.parse()?),
+                    value: value.parse()?,
+                })
+            })
+            .collect::<Result<_>>()
+            .map(Self)
+    }
+}
+
+impl Serialize for BufferVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for BufferVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let stamp = String::deserialize(deserializer)?;
+        stamp.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffer_version_round_trip() {
+        let version = BufferVersion(clock::Global::from_iter([
+            Lamport {
+                replica_id: ReplicaId::LOCAL,
+                value: 12,
+            },
+            Lamport {
+                replica_id: ReplicaId::AGENT,
+                value: 3,
+            },
+        ]));
+        assert_eq!(version.to_string(), "0:12,2:3");
+        assert_eq!("0:12,2:3".parse::<BufferVersion>().unwrap(), version);
+        assert_eq!(
+            serde_json::from_value::<BufferVersion>(serde_json::to_value(&version).unwrap())
+                .unwrap(),
+            version
+        );
+
+        assert_eq!(BufferVersion::default().to_string(), "");
+        assert_eq!(
+            "".parse::<BufferVersion>().unwrap(),
+            BufferVersion::default()
+        );
+        assert!("0-12".parse::<BufferVersion>().is_err());
+    }
+}
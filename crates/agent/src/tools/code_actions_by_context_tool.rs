@@ -0,0 +1,564 @@
+use super::explain_symbol_by_context_tool::buffer_display_path;
+use super::rename_symbol_by_context_tool::revert_transaction;
+use super::support::{DRY_RUN_NOTE, buffer_to_edit};
+use super::{
+    AnchorHandles, AnchorMemory, ContextualAnchor, display_project_path, resolve_project_path,
+    warm_up_language_servers,
+};
+use crate::{AgentTool, ToolCallEventStream, ToolPreparation};
+use acp_thread::Diff;
+use action_log::ActionLog;
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use gpui::{App, AppContext as _, AsyncApp, Entity, SharedString, Task};
+use language::Buffer;
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::{CodeAction, LspAction, Project, WorktreeSettings};
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{fmt::Write as _, sync::Arc};
+
+/// Lists the code actions (quick fixes and refactorings) the language server offers at an anchor,
+/// or applies one of them.
+///
+/// Call it without `apply_index` first to see the actions, then again with the index of the
+/// action to apply.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CodeActionsByContextToolInput {
+    #[serde(flatten)]
+    pub anchor: ContextualAnchor,
+    /// The index of the action to apply, as listed by a previous call for the same anchor.
+    #[serde(default)]
+    pub apply_index: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CodeActionsByContextToolOutput {
+    pub token: String,
+    /// The actions available at the anchor. Empty when an action was applied.
+    pub actions: Vec<CodeActionSummary>,
+    /// The action that was applied, when `apply_index` was given.
+    pub applied: Option<AppliedCodeAction>,
+    /// The handle under which the input anchor was remembered.
+    pub handle: String,
+    /// The symbol handle under which the input anchor was remembered, if it is unambiguous.
+    pub symbol_handle: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CodeActionSummary {
+    /// The index to pass as `apply_index` to apply this action.
+    pub index: u32,
+    pub title: String,
+    /// The action's kind (e.g. `quickfix` or `refactor.extract`), if the server reported one.
+    pub kind: Option<String>,
+    /// Whether the server marked this as the preferred fix.
+    pub is_preferred: bool,
+    /// The messages of the diagnostics this action fixes.
+    pub fixes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AppliedCodeAction {
+    pub title: String,
+    /// The paths of the files the action changed.
+    pub changed_paths: Vec<String>,
+    /// Whether the action's changes were only computed, without being applied.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl From<CodeActionsByContextToolOutput> for LanguageModelToolResultContent {
+    fn from(output: CodeActionsByContextToolOutput) -> Self {
+        let mut text = String::new();
+        match &output.applied {
+            Some(applied) if applied.dry_run && applied.changed_paths.is_empty() => {
+                writeln!(
+                    text,
+                    "{DRY_RUN_NOTE} Applying `{}` wouldn't change any files.\n",
+                    applied.title
+                )
+                .ok();
+            }
+            Some(applied) if applied.dry_run => {
+                writeln!(
+                    text,
+                    "{DRY_RUN_NOTE} Applying `{}` would change:",
+                    applied.title
+                )
+                .ok();
+                for path in &applied.changed_paths {
+                    writeln!(text, "- {path}").ok();
+                }
+                text.push('\n');
+            }
+            Some(applied) if applied.changed_paths.is_empty() => {
+                writeln!(
+                    text,
+                    "Applied `{}`, which didn't change any files.\n",
+                    applied.title
+                )
+                .ok();
+            }
+            Some(applied) => {
+                writeln!(text, "Applied `{}`, which changed:", applied.title).ok();
+                for path in &applied.changed_paths {
+                    writeln!(text, "- {path}").ok();
+                }
+                text.push('\n');
+            }
+            None if output.actions.is_empty() => {
+                writeln!(
+                    text,
+                    "No code actions available at `{}` (or language server not capable).\n",
+                    output.token
+                )
+                .ok();
+            }
+            None => {
+                writeln!(text, "Code actions at `{}`:", output.token).ok();
+                for action in &output.actions {
+                    write!(text, "{}. {}", action.index, action.title).ok();
+                    if let Some(kind) = &action.kind {
+                        write!(text, " ({kind})").ok();
+                    }
+                    if action.is_preferred {
+                        text.push_str(" [preferred]");
+                    }
+                    text.push('\n');
+                    for fix in &action.fixes {
+                        writeln!(text, "   fixes: {fix}").ok();
+                    }
+                }
+                text.push_str("\nPass an action's number as `apply_index` to apply it.\n\n");
+            }
+        }
+        let handles = AnchorHandles {
+            reference: output.handle,
+            symbol: output.symbol_handle,
+        };
+        text.push_str(&handles.describe());
+        text.into()
+    }
+}
+
+/// Tool: code_actions_by_context
+pub struct CodeActionsByContextTool {
+    project: Entity<Project>,
+    action_log: Entity<ActionLog>,
+    anchor_memory: Entity<AnchorMemory>,
+}
+
+impl CodeActionsByContextTool {
+    pub fn new(
+        project: Entity<Project>,
+        action_log: Entity<ActionLog>,
+        anchor_memory: Entity<AnchorMemory>,
+    ) -> Self {
+        Self {
+            project,
+            action_log,
+            anchor_memory,
+        }
+    }
+}
+
+impl AgentTool for CodeActionsByContextTool {
+    type Input = CodeActionsByContextToolInput;
+    type Output = CodeActionsByContextToolOutput;
+
+    fn name() -> &'static str {
+        "code_actions_by_context"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Edit
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn input_examples() -> Vec<Self::Input> {
+        vec![CodeActionsByContextToolInput {
+            anchor: ContextualAnchor {
+                path: "project/src/main.rs".into(),
+                context: "let config = Config::load(&path)?;".into(),
+                token: "Config".into(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            apply_index: None,
+        }]
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => {
+                let symbol = input.anchor.handle.unwrap_or(input.anchor.token);
+                match input.apply_index {
+                    Some(index) => format!("Apply code action {index} at `{symbol}`").into(),
+                    None => format!("List code actions at `{symbol}`").into(),
+                }
+            }
+            Err(_) => "Code actions".into(),
+        }
+    }
+
+    fn prepare(
+        self: Arc<Self>,
+        input: Self::Input,
+        cx: &mut App,
+    ) -> Option<Task<Result<ToolPreparation>>> {
+        let anchor = self.anchor_memory.read(cx).expand(input.anchor).ok()?;
+        if anchor.revision.is_some() {
+            return None;
+        }
+        warm_up_language_servers(&self.project, &anchor.path, cx)
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let action_log = self.action_log.clone();
+        let anchor_memory = self.anchor_memory.clone();
+
+        let anchor = match anchor_memory.read(cx).expand(input.anchor) {
+            Ok(anchor) => anchor,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        if let Err(error) = anchor.validate_basic() {
+            return Task::ready(Err(error));
+        }
+        if let Some(revision) = &anchor.revision {
+            return Task::ready(Err(anyhow!(
+                "Code actions only apply to the project's current files, not to {revision}."
+            )));
+        }
+        let project_path = match resolve_project_path(project.read(cx), &anchor.path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Task::ready(Err(anyhow!(
+                "Cannot edit file because its path matches the file_scan_exclusions or private_files setting: {}",
+                &anchor.path
+            )));
+        }
+        let display_path = display_project_path(project.read(cx), &project_path, cx)
+            .unwrap_or_else(|| anchor.path.clone());
+
+        cx.spawn(async move |cx| {
+            let buffer = project
+                .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+                .await?;
+            buffer
+                .read_with(cx, |buffer, _| buffer.parsing_idle())?
+                .await;
+            let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot())?;
+            let token_offset = anchor.resolve_unique_token_offset(&snapshot, &display_path)?;
+            let handles =
+                anchor_memory.update(cx, |anchor_memory, _| anchor_memory.remember(&anchor))?;
+
+            let actions = project
+                .update(cx, |project, cx| {
+                    project.code_actions(
+                        &buffer,
+                        anchor.token_range(&snapshot, token_offset),
+                        None,
+                        cx,
+                    )
+                })?
+                .await?
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|action| !is_disabled(action))
+                .collect::<Vec<_>>();
+
+            let Some(apply_index) = input.apply_index else {
+                return Ok(CodeActionsByContextToolOutput {
+                    token: anchor.token,
+                    actions: actions
+                        .iter()
+                        .zip(0..)
+                        .map(|(action, index)| summarize(action, index))
+                        .collect(),
+                    applied: None,
+                    handle: handles.reference,
+                    symbol_handle: handles.symbol,
+                });
+            };
+            let Some(action) = actions.into_iter().nth(apply_index as usize) else {
+                anyhow::bail!(
+                    "There is no code action {apply_index} at `{}`. Call the tool without `apply_index` to list the available actions.",
+                    anchor.token
+                );
+            };
+            let title = action.lsp_action.title().to_string();
+
+            let changed_paths = apply_code_action_for_review(
+                &project,
+                &action_log,
+                buffer,
+                action,
+                &event_stream,
+                cx,
+            )
+            .await?
+            .into_iter()
+            .map(|changed| changed.path)
+            .collect();
+
+            Ok(CodeActionsByContextToolOutput {
+                token: anchor.token,
+                actions: Vec::new(),
+                applied: Some(AppliedCodeAction {
+                    title,
+                    changed_paths,
+                    dry_run: event_stream.dry_run(),
+                }),
+                handle: handles.reference,
+                symbol_handle: handles.symbol,
+            })
+        })
+    }
+}
+
+/// A buffer that a code action changed.
+pub(crate) struct ChangedBuffer {
+    pub path: String,
+    pub old_text: String,
+    pub new_text: String,
+}
+
+/// Applies a code action and returns the buffers it changed, sorted by path.
+///
+/// As with renames, the server's edits are captured and undone, then made again through the
+/// action log, so the user sees them as a diff they can review. In a dry run they are made again
+/// in branches of the buffers instead, so that nothing changes.
+pub(crate) async fn apply_code_action_for_review(
+    project: &Entity<Project>,
+    action_log: &Entity<ActionLog>,
+    buffer: Entity<Buffer>,
+    action: CodeAction,
+    event_stream: &ToolCallEventStream,
+    cx: &mut AsyncApp,
+) -> Result<Vec<ChangedBuffer>> {
+    let transaction = project
+        .update(cx, |project, cx| {
+            project.apply_code_action(buffer, action, true, cx)
+        })?
+        .await?;
+    let mut changes = Vec::new();
+    for (buffer, transaction) in transaction.0 {
+        let (path, edits) = buffer.update(cx, |buffer, cx| {
+            let edits = revert_transaction(buffer, &transaction, cx);
+            (buffer_display_path(project, buffer, cx), edits)
+        })?;
+        if !edits.is_empty() {
+            changes.push((path, buffer, edits));
+        }
+    }
+    changes.sort_by(|(left, _, _), (right, _, _)| left.cmp(right));
+
+    let mut changed_buffers = Vec::new();
+    for (path, buffer, edits) in changes {
+        action_log.update(cx, |action_log, cx| {
+            action_log.buffer_read(buffer.clone(), cx)
+        })?;
+        let old_text = buffer.read_with(cx, |buffer, _| buffer.text())?;
+        let edited_buffer = buffer_to_edit(&buffer, event_stream, cx)?;
+        let diff = cx.new(|cx| Diff::new(edited_buffer.clone(), cx))?;
+        event_stream.update_diff(diff.clone());
+        edited_buffer.update(cx, |buffer, cx| buffer.edit(edits, None, cx))?;
+        if !event_stream.dry_run() {
+            action_log.update(cx, |action_log, cx| {
+                action_log.buffer_edited(buffer.clone(), cx)
+            })?;
+        }
+        diff.update(cx, |diff, cx| diff.finalize(cx))?;
+        let new_text = edited_buffer.read_with(cx, |buffer, _| buffer.text())?;
+        changed_buffers.push(ChangedBuffer {
+            path,
+            old_text,
+            new_text,
+        });
+    }
+    Ok(changed_buffers)
+}
+
+pub(crate) fn is_disabled(action: &CodeAction) -> bool {
+    matches!(&action.lsp_action, LspAction::Action(action) if action.disabled.is_some())
+}
+
+fn summarize(action: &CodeAction, index: u32) -> CodeActionSummary {
+    let (kind, is_preferred, fixes) = match &action.lsp_action {
+        LspAction::Action(action) => (
+            action.kind.as_ref().map(|kind| kind.as_str().to_string()),
+            action.is_preferred.unwrap_or(false),
+            action
+                .diagnostics
+                .iter()
+                .flatten()
+                .map(|diagnostic| diagnostic.message.clone())
+                .collect(),
+        ),
+        LspAction::Command(_) => (Some("command".to_string()), false, Vec::new()),
+        LspAction::CodeLens(_) => (Some("code lens".to_string()), false, Vec::new()),
+    };
+    CodeActionSummary {
+        index,
+        title: action.lsp_action.title().to_string(),
+        kind,
+        is_preferred,
+        fixes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{RecordedToolEvent, ToolTestProject, init_test, run_tool};
+    use futures::StreamExt as _;
+    use gpui::TestAppContext;
+    use serde_json::json;
+
+    #[gpui::test]
+    async fn test_code_actions_list_and_apply(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "fn main() {\n    let total = 1;\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let mut fake_servers = test_project.register_fake_rust_server(
+            lsp::ServerCapabilities {
+                code_action_provider: Some(lsp::CodeActionProviderCapability::Simple(true)),
+                ..Default::default()
+            },
+            cx,
+        );
+        let (buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/main.rs", cx).await;
+        let fake_server = fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+        let uri = lsp::Uri::from_file_path(test_project.abs_path("src/main.rs"))
+            .expect("fake project paths are absolute");
+        fake_server.set_request_handler::<lsp::request::CodeActionRequest, _, _>(move |_, _| {
+            let uri = uri.clone();
+            async move {
+                Ok(Some(vec![lsp::CodeActionOrCommand::CodeAction(
+                    lsp::CodeAction {
+                        title: "Add explicit type".into(),
+                        kind: Some(lsp::CodeActionKind::REFACTOR_REWRITE),
+                        edit: Some(lsp::WorkspaceEdit {
+                            changes: Some(
+                                [(
+                                    uri,
+                                    vec![lsp::TextEdit::new(
+                                        lsp::Range::new(
+                                            lsp::Position::new(1, 13),
+                                            lsp::Position::new(1, 13),
+                                        ),
+                                        ": i32".into(),
+                                    )],
+                                )]
+                                .into_iter()
+                                .collect(),
+                            ),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    },
+                )]))
+            }
+        });
+
+        let action_log = cx.new(|_| ActionLog::new(test_project.project.clone()));
+        let tool = Arc::new(CodeActionsByContextTool::new(
+            test_project.project.clone(),
+            action_log.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let input = |apply_index| CodeActionsByContextToolInput {
+            anchor: ContextualAnchor {
+                path: "root/src/main.rs".into(),
+                context: "let total = 1;".into(),
+                token: "total".into(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            apply_index,
+        };
+
+        let (output, _) = run_tool(tool.clone(), input(None), cx).await;
+        assert_eq!(
+            output.expect("tool failed").actions,
+            vec![CodeActionSummary {
+                index: 0,
+                title: "Add explicit type".into(),
+                kind: Some("refactor.rewrite".into()),
+                is_preferred: false,
+                fixes: Vec::new(),
+            }]
+        );
+
+        let (output, _) = run_tool(tool.clone(), input(Some(1)), cx).await;
+        assert!(output.is_err());
+
+        let (output, events) = run_tool(tool, input(Some(0)), cx).await;
+        assert_eq!(
+            output.expect("tool failed").applied,
+            Some(AppliedCodeAction {
+                title: "Add explicit type".into(),
+                changed_paths: vec!["root/src/main.rs".into()],
+                dry_run: false,
+            })
+        );
+        assert!(events.contains(&RecordedToolEvent::Diff));
+        assert_eq!(
+            buffer.read_with(cx, |buffer, _| buffer.text()),
+            "fn main() {\n    let total: i32 = 1;\n}\n"
+        );
+        cx.run_until_parked();
+        assert_eq!(
+            action_log.read_with(cx, |log, cx| log.changed_buffers(cx).len()),
+            1
+        );
+    }
+}
@@ -0,0 +1,411 @@
+use super::{
+    AnchorHandles, AnchorMemory, ContextualAnchor, display_project_path, resolve_project_path,
+    warm_up_language_servers,
+};
+use crate::{AgentTool, ToolCallEventStream, ToolPreparation};
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use gpui::{App, Entity, SharedString, Task};
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::{DEFAULT_COMPLETION_CONTEXT, Project, WorktreeSettings};
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{fmt::Write as _, sync::Arc};
+
+const DEFAULT_LIMIT: u32 = 20;
+const MAX_LIMIT: u32 = 100;
+
+/// Lists the completions the language server offers right after an anchor's token, such as the
+/// methods and fields available on a value whose type you don't know.
+///
+/// To list a value's members, anchor a token that ends with the member access, such as
+/// `config.` or `Config::`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CompletionsByContextToolInput {
+    #[serde(flatten)]
+    pub anchor: ContextualAnchor,
+    /// The maximum number of completions to return. Defaults to 20, at most 100.
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CompletionsByContextToolOutput {
+    pub token: String,
+    /// The completions in the order the language server ranks them.
+    pub completions: Vec<CompletionSummary>,
+    /// How many completions the language server offered in total.
+    pub total: u32,
+    /// The handle under which the input anchor was remembered.
+    pub handle: String,
+    /// The symbol handle under which the input anchor was remembered, if it is unambiguous.
+    pub symbol_handle: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CompletionSummary {
+    pub label: String,
+    /// The completion's kind (e.g. `method` or `field`), if the server reported one.
+    pub kind: Option<String>,
+    /// Extra information such as the completion's type or signature.
+    pub detail: Option<String>,
+}
+
+impl From<CompletionsByContextToolOutput> for LanguageModelToolResultContent {
+    fn from(output: CompletionsByContextToolOutput) -> Self {
+        let mut text = String::new();
+        if output.completions.is_empty() {
+            writeln!(
+                text,
+                "No completions after `{}` (or language server not capable).\n",
+                output.token
+            )
+            .ok();
+        } else {
+            writeln!(
+                text,
+                "Completions after `{}` ({} of {}):",
+                output.token,
+                output.completions.len(),
+                output.total
+            )
+            .ok();
+            for completion in &output.completions {
+                write!(text, "- {}", completion.label).ok();
+                if let Some(kind) = &completion.kind {
+                    write!(text, " ({kind})").ok();
+                }
+                if let Some(detail) = &completion.detail {
+                    write!(text, ": {detail}").ok();
+                }
+                text.push('\n');
+            }
+            text.push('\n');
+        }
+        let handles = AnchorHandles {
+            reference: output.handle,
+            symbol: output.symbol_handle,
+        };
+        text.push_str(&handles.describe());
+        text.into()
+    }
+}
+
+/// Tool: completions_by_context
+pub struct CompletionsByContextTool {
+    project: Entity<Project>,
+    anchor_memory: Entity<AnchorMemory>,
+}
+
+impl CompletionsByContextTool {
+    pub fn new(project: Entity<Project>, anchor_memory: Entity<AnchorMemory>) -> Self {
+        Self {
+            project,
+            anchor_memory,
+        }
+    }
+}
+
+impl AgentTool for CompletionsByContextTool {
+    type Input = CompletionsByContextToolInput;
+    type Output = CompletionsByContextToolOutput;
+
+    fn name() -> &'static str {
+        "completions_by_context"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Search
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn input_examples() -> Vec<Self::Input> {
+        vec![CompletionsByContextToolInput {
+            anchor: ContextualAnchor {
+                path: "project/src/main.rs".into(),
+                context: "let name = config.".into(),
+                token: "config.".into(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            limit: None,
+        }]
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => {
+                let symbol = input.anchor.handle.unwrap_or(input.anchor.token);
+                format!("List completions after `{symbol}`").into()
+            }
+            Err(_) => "List completions".into(),
+        }
+    }
+
+    fn prepare(
+        self: Arc<Self>,
+        input: Self::Input,
+        cx: &mut App,
+    ) -> Option<Task<Result<ToolPreparation>>> {
+        let anchor = self.anchor_memory.read(cx).expand(input.anchor).ok()?;
+        if anchor.revision.is_some() {
+            return None;
+        }
+        warm_up_language_servers(&self.project, &anchor.path, cx)
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        _event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let anchor_memory = self.anchor_memory.clone();
+        let limit = input.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT) as usize;
+
+        let anchor = match anchor_memory.read(cx).expand(input.anchor) {
+            Ok(anchor) => anchor,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        if let Err(error) = anchor.validate_basic() {
+            return Task::ready(Err(error));
+        }
+        if let Some(revision) = &anchor.revision {
+            return Task::ready(Err(anyhow!(
+                "Language servers only know the project's current files, so they can't complete at {revision}."
+            )));
+        }
+        let project_path = match resolve_project_path(project.read(cx), &anchor.path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Task::ready(Err(anyhow!(
+                "Cannot read file because its path matches the file_scan_exclusions or private_files setting: {}",
+                &anchor.path
+            )));
+        }
+        let display_path = display_project_path(project.read(cx), &project_path, cx)
+            .unwrap_or_else(|| anchor.path.clone());
+
+        cx.spawn(async move |cx| {
+            let buffer = project
+                .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+                .await?;
+            buffer
+                .read_with(cx, |buffer, _| buffer.parsing_idle())?
+                .await;
+            let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot())?;
+            let token_offset = anchor.resolve_unique_token_offset(&snapshot, &display_path)?;
+            let handles =
+                anchor_memory.update(cx, |anchor_memory, _| anchor_memory.remember(&anchor))?;
+
+            let responses = project
+                .update(cx, |project, cx| {
+                    project.completions(
+                        &buffer,
+                        anchor.token_range(&snapshot, token_offset).end,
+                        DEFAULT_COMPLETION_CONTEXT,
+                        cx,
+                    )
+                })?
+                .await?;
+            // Servers leave ranking to the client through `sort_text`, so apply it before
+            // truncating.
+            let mut completions = responses
+                .iter()
+                .flat_map(|response| &response.completions)
+                .filter_map(|completion| {
+                    let lsp_completion = completion.source.lsp_completion(false)?;
+                    let sort_key = lsp_completion
+                        .sort_text
+                        .clone()
+                        .unwrap_or_else(|| lsp_completion.label.clone());
+                    let detail = lsp_completion.detail.clone().or_else(|| {
+                        lsp_completion
+                            .label_details
+                            .as_ref()
+                            .and_then(|details| details.description.clone())
+                    });
+                    Some((
+                        sort_key,
+                        CompletionSummary {
+                            label: lsp_completion.label.clone(),
+                            kind: lsp_completion.kind.map(|kind| kind_name(kind).to_string()),
+                            detail,
+                        },
+                    ))
+                })
+                .collect::<Vec<_>>();
+            completions.sort_by(|(left, _), (right, _)| left.cmp(right));
+            let total = completions.len() as u32;
+
+            Ok(CompletionsByContextToolOutput {
+                token: anchor.token,
+                completions: completions
+                    .into_iter()
+                    .take(limit)
+                    .map(|(_, completion)| completion)
+                    .collect(),
+                total,
+                handle: handles.reference,
+                symbol_handle: handles.symbol,
+            })
+        })
+    }
+}
+
+fn kind_name(kind: lsp::CompletionItemKind) -> &'static str {
+    match kind {
+        lsp::CompletionItemKind::TEXT => "text",
+        lsp::CompletionItemKind::METHOD => "method",
+        lsp::CompletionItemKind::FUNCTION => "function",
+        lsp::CompletionItemKind::CONSTRUCTOR => "constructor",
+        lsp::CompletionItemKind::FIELD => "field",
+        lsp::CompletionItemKind::VARIABLE => "variable",
+        lsp::CompletionItemKind::CLASS => "class",
+        lsp::CompletionItemKind::INTERFACE => "interface",
+        lsp::CompletionItemKind::MODULE => "module",
+        lsp::CompletionItemKind::PROPERTY => "property",
+        lsp::CompletionItemKind::UNIT => "unit",
+        lsp::CompletionItemKind::VALUE => "value",
+        lsp::CompletionItemKind::ENUM => "enum",
+        lsp::CompletionItemKind::KEYWORD => "keyword",
+        lsp::CompletionItemKind::SNIPPET => "snippet",
+        lsp::CompletionItemKind::COLOR => "color",
+        lsp::CompletionItemKind::FILE => "file",
+        lsp::CompletionItemKind::REFERENCE => "reference",
+        lsp::CompletionItemKind::FOLDER => "folder",
+        lsp::CompletionItemKind::ENUM_MEMBER => "enum_member",
+        lsp::CompletionItemKind::CONSTANT => "constant",
+        lsp::CompletionItemKind::STRUCT => "struct",
+        lsp::CompletionItemKind::EVENT => "event",
+        lsp::CompletionItemKind::OPERATOR => "operator",
+        lsp::CompletionItemKind::TYPE_PARAMETER => "type_parameter",
+        _ => "other",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, run_tool};
+    use futures::StreamExt as _;
+    use gpui::{AppContext as _, TestAppContext};
+    use serde_json::json;
+
+    #[gpui::test]
+    async fn test_completions_are_ranked_and_limited(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "fn main() {\n    let config = Config::default();\n    config.\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let mut fake_servers = test_project.register_fake_rust_server(
+            lsp::ServerCapabilities {
+                completion_provider: Some(lsp::CompletionOptions {
+                    trigger_characters: Some(vec![".".to_string()]),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            cx,
+        );
+        let (_buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/main.rs", cx).await;
+        let fake_server = fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+        fake_server.set_request_handler::<lsp::request::Completion, _, _>(|params, _| async move {
+            assert_eq!(
+                params.text_document_position.position,
+                lsp::Position::new(2, 11)
+            );
+            let item = |label: &str, sort_text: &str, kind, detail: &str| lsp::CompletionItem {
+                label: label.into(),
+                sort_text: Some(sort_text.into()),
+                kind: Some(kind),
+                detail: Some(detail.into()),
+                ..Default::default()
+            };
+            Ok(Some(lsp::CompletionResponse::Array(vec![
+                item("save", "2", lsp::CompletionItemKind::METHOD, "fn(&self)"),
+                item("path", "0", lsp::CompletionItemKind::FIELD, "PathBuf"),
+                item("load", "1", lsp::CompletionItemKind::METHOD, "fn() -> Self"),
+            ])))
+        });
+
+        let tool = Arc::new(CompletionsByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let (output, _) = run_tool(
+            tool,
+            CompletionsByContextToolInput {
+                anchor: ContextualAnchor {
+                    path: "root/src/main.rs".into(),
+                    context: "let config = Config::default();\n    config.".into(),
+                    token: "config.".into(),
+                    index: None,
+                    handle: None,
+                    revision: None,
+                    whole_word: false,
+                    approximate_line: None,
+                    case_insensitive: false,
+                    unicode_normalize: false,
+                    end_token: None,
+                    end_index: None,
+                },
+                limit: Some(2),
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+
+        assert_eq!(output.total, 3);
+        assert_eq!(
+            output.completions,
+            vec![
+                CompletionSummary {
+                    label: "path".into(),
+                    kind: Some("field".into()),
+                    detail: Some("PathBuf".into()),
+                },
+                CompletionSummary {
+                    label: "load".into(),
+                    kind: Some("method".into()),
+                    detail: Some("fn() -> Self".into()),
+                },
+            ]
+        );
+    }
+}
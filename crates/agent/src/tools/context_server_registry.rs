@@ -1,9 +1,10 @@
-use crate::{AgentToolOutput, AnyAgentTool, ToolCallEventStream};
+use crate::{AgentToolOutput, AnyAgentTool, ToolCallEventStream, ToolPermissionClass};
 use agent_client_protocol::ToolKind;
 use anyhow::{Result, anyhow, bail};
 use collections::{BTreeMap, HashMap};
 use context_server::{ContextServerId, client::NotificationSubscription};
 use gpui::{App, AppContext, AsyncApp, Context, Entity, EventEmitter, SharedString, Task};
+use language_model::LanguageModelToolResultContent;
 use project::context_server_store::{ContextServerStatus, ContextServerStore};
 use std::sync::Arc;
 use util::ResultExt;
@@ -300,6 +301,10 @@ impl AnyAgentTool for ContextServerTool {
         ToolKind::Other
     }
 
+    fn permission_class(&self) -> ToolPermissionClass {
+        ToolPermissionClass::SystemExecuting
+    }
+
     fn initial_title(&self, _input: serde_json::Value, _cx: &mut App) -> SharedString {
         format!("Run MCP tool `{}`", self.tool.name).into()
     }
@@ -378,9 +383,13 @@ impl AnyAgentTool for ContextServerTool {
                     }
                 }
             }
+            let mut llm_output = LanguageModelToolResultContent::from(result.as_str());
+            if let Some(structured_content) = response.structured_content {
+                llm_output = llm_output.with_structured_output(structured_content);
+            }
             Ok(AgentToolOutput {
-                raw_output: result.clone().into(),
-                llm_output: result.into(),
+                raw_output: result.into(),
+                llm_output,
             })
         })
     }
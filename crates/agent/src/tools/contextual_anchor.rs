@@ -0,0 +1,2596 @@
+#[cfg(test)]
+mod evals;
+mod selection_anchor;
+mod symbol_anchor;
+
+pub use selection_anchor::*;
+pub use symbol_anchor::*;
+
+use super::{AnchorCache, display_project_path, resolve_project_path};
+use crate::{BufferVersion, edit_agent::streaming_fuzzy_matcher::fuzzy_eq};
+use acp_thread::{LocationAnchor, LocationDetail};
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use gpui::{App, AppContext as _, AsyncApp, Entity, Task};
+use language::{Buffer, BufferId, BufferSnapshot, Point, PointUtf16, ToOffset as _};
+use project::{Project, ProjectPath, WorktreeSettings};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{
+    fmt::Write as _,
+    hash::{DefaultHasher, Hasher as _},
+    iter,
+    ops::Range,
+};
+use text::OffsetRangeExt as _;
+use unicode_normalization::{UnicodeNormalization as _, char::is_combining_mark};
+
+/// How many lines a token's preview may span before it is cut down to the lines around the token.
+pub const MAX_SNIPPET_LINES: u32 = 42;
+
+/// Contexts with fewer non-whitespace characters than this (e.g. `} else {`) tend to match in
+/// many places, so an ambiguous one is told to include more code, and anchors handed back to the
+/// model are at least this long so that they survive edits nearby.
+const MIN_CONTEXT_SIGNIFICANT_CHARS: usize = 8;
+
+const SHORT_CONTEXT_HINT: &str = "The provided `context` is short, which is why it matches in several places. Include the surrounding code (e.g. the whole line containing `token`).";
+
+/// How many lines above and below a match are tried when suggesting a unique context.
+const MAX_CONTEXT_EXPANSION_LINES: usize = 3;
+
+/// How many of the regions most similar to a `context` that wasn't found are suggested.
+const MAX_NEAR_MISSES: usize = 3;
+
+/// Regions less similar to `context` than this aren't worth suggesting.
+const MIN_NEAR_MISS_SIMILARITY: f64 = 0.5;
+
+/// How `context` is compared against the file's text when resolving a [`ContextualAnchor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextMatchStrategy {
+    /// `context` must occur verbatim.
+    Exact,
+    /// Every run of whitespace in `context` matches any non-empty run of whitespace in the text,
+    /// so reindented or rewrapped code still resolves.
+    WhitespaceNormalized,
+    /// Each line of `context` only has to be similar to the corresponding line of the text, as
+    /// judged by the same line comparison the edit agent uses to locate `old_text`.
+    Fuzzy,
+}
+
+impl ContextMatchStrategy {
+    pub const ALL: [Self; 3] = [Self::Exact, Self::WhitespaceNormalized, Self::Fuzzy];
+}
+
+/// What the syntax tree must show a resolved token to be, checked by
+/// [`ContextualAnchor::validate_token_syntax`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenSyntax {
+    /// Code rather than part of a comment or string literal.
+    Code,
+    /// An identifier, field or type in code, the only tokens language servers resolve to symbols.
+    Symbol,
+}
+
+/// Why an anchor couldn't be resolved. Tools report it next to the message in their output, so
+/// that clients can act on the failure without parsing prose.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema, thiserror::Error)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AnchorResolutionError {
+    /// `context` wasn't found in the file.
+    #[error("{message}")]
+    NoContextMatch {
+        #[serde(skip)]
+        message: String,
+        /// The 1-based first lines of the regions most similar to `context`, most similar first.
+        nearest_lines: Vec<u32>,
+    },
+    /// `context` was found in more than one place.
+    #[error("{message}")]
+    AmbiguousContext {
+        #[serde(skip)]
+        message: String,
+        /// The 1-based lines of the token in each place.
+        occurrence_lines: Vec<u32>,
+    },
+    /// `token` doesn't occur in `context`.
+    #[error("The provided `context` must contain the `token`.")]
+    TokenNotInContext,
+    /// `token` occurs more than once in `context` and no `index` was given.
+    #[error(
+        "`token` occurs {count} times in `context`. Provide `index` (0-based) to select one, or use a `context` that contains `token` only once."
+    )]
+    AmbiguousToken { count: usize },
+    /// `index` selects an occurrence of `token` that `context` doesn't have.
+    #[error(
+        "`index` {index} is out of range: `token` occurs {count} time(s) in `context`, so `index` must be less than {count}."
+    )]
+    IndexOutOfRange { index: u32, count: usize },
+}
+
+/// Locates a token in a file by searching for a multi-word `context` snippet that contains it,
+/// rather than by line and column, which models get wrong far more often.
+///
+/// The `context` MUST contain `token` and should match exactly one place in the file, unless
+/// `approximate_line` says which of its matches is meant.
+/// When `token` occurs more than once inside `context`, `index` (0-based) selects the occurrence.
+/// An anchor that an earlier tool call resolved can instead be referred to by its `handle`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ContextualAnchor {
+    /// Project-relative path to the file containing the symbol (e.g. "src/main.rs").
+    #[serde(default)]
+    pub path: String,
+
+    /// A multi-word snippet from the file which explicitly contains `token`.
+    /// The tool will search the file for this exact snippet to locate the token's position.
+    #[serde(default)]
+    pub context: String,
+
+    /// The exact token inside `context` to locate.
+    #[serde(default)]
+    pub token: String,
+
+    /// Optional 0-based index selecting which occurrence of `token` inside `context` to use.
+    /// Required when `token` appears more than once in `context`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<u32>,
+
+    /// Optional handle (e.g. "ref#3" or "sym:load") that an earlier tool call reported for an
+    /// anchor it resolved. When given, `path`, `context`, `token` and `index` can be omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
+
+    /// Optional git revision (e.g. "HEAD~1" or a commit SHA) whose version of the file `context`
+    /// is resolved against, for reviewing or investigating code that has since changed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+
+    /// Whether `token` must be a whole word: an occurrence that is part of a longer identifier
+    /// (e.g. `id` in `identifier`) isn't counted by `index` and never matches.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub whole_word: bool,
+
+    /// Optional 1-based line where `token` is expected. When `context` matches in more than one
+    /// place, the match nearest this line is used instead of failing as ambiguous, so the line
+    /// only needs to be roughly right.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approximate_line: Option<u32>,
+
+    /// Whether `context` and `token` match regardless of case, e.g. `Config` matching `config`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub case_insensitive: bool,
+
+    /// Whether `context` and `token` match text that encodes the same characters differently,
+    /// e.g. `é` as one character or as `e` followed by a combining accent, as happens with code
+    /// copied from rendered documentation.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unicode_normalize: bool,
+
+    /// Optional token inside `context`, at or after `token`, that ends the anchor's span. With it
+    /// the anchor covers everything from the start of `token` to the end of `end_token`, e.g. a
+    /// whole function from `fn` to its closing `}`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_token: Option<String>,
+
+    /// Optional 0-based index selecting which occurrence of `end_token` inside `context` ends the
+    /// span. Defaults to the last occurrence.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_index: Option<u32>,
+}
+
+impl ContextualAnchor {
+    /// Returns how many non-overlapping times `token` occurs in `context`.
+    pub fn token_occurrences_in_context(&self) -> usize {
+        if self.token.is_empty() {
+            return 0;
+        }
+        self.token_matches(&self.context).len()
+    }
+
+    /// Returns the byte offsets of the non-overlapping occurrences of `token` in `text`, leaving
+    /// out those inside longer identifiers when `whole_word` is set.
+    fn token_matches(&self, text: &str) -> Vec<usize> {
+        if self.folds_text() {
+            let folded = self.fold(text);
+            return self
+                .folded()
+                .token_matches(&folded.text)
+                .into_iter()
+                .map(|offset| folded.offsets[offset])
+                .collect();
+        }
+        text.match_indices(self.token.as_str())
+            .map(|(offset, _)| offset)
+            .filter(|&offset| self.is_whole_word_at(text, offset))
+            .collect()
+    }
+
+    /// Whether `case_insensitive` or `unicode_normalize` is set, so that text has to be folded
+    /// before it is compared.
+    fn folds_text(&self) -> bool {
+        self.case_insensitive || self.unicode_normalize
+    }
+
+    /// Returns this anchor with `context` and `token` folded, so that matching it exactly against
+    /// text folded with [`Self::fold`] applies this anchor's matching options.
+    fn folded(&self) -> Self {
+        Self {
+            context: self.fold(&self.context).text,
+            token: self.fold(&self.token).text,
+            case_insensitive: false,
+            unicode_normalize: false,
+            end_token: None,
+            end_index: None,
+            ..self.clone()
+        }
+    }
+
+    /// Lowercases `text` and composes its combining characters, as far as this anchor's matching
+    /// options ask for.
+    fn fold(&self, text: &str) -> FoldedText {
+        let mut folded = FoldedText {
+            text: String::with_capacity(text.len()),
+            offsets: Vec::with_capacity(text.len() + 1),
+        };
+        for (segment_start, segment) in self.fold_segments(text) {
+            folded
+                .offsets
+                .extend(iter::repeat_n(segment_start, segment.len()));
+            folded.text.push_str(&segment);
+        }
+        folded.offsets.push(text.len());
+        folded
+    }
+
+    /// Splits `text` into the pieces that are folded independently, each with the offset it
+    /// starts at: every character together with the combining marks that follow it.
+    fn fold_segments<'a>(&'a self, text: &'a str) -> impl Iterator<Item = (usize, String)> + 'a {
+        let mut characters = text.char_indices().peekable();
+        iter::from_fn(move || {
+            let (segment_start, character) = characters.next()?;
+            let mut segment_end = segment_start + character.len_utf8();
+            if self.unicode_normalize {
+                while let Some(&(offset, mark)) = characters.peek()
+                    && is_combining_mark(mark)
+                {
+                    segment_end = offset + mark.len_utf8();
+                    characters.next();
+                }
+            }
+            let mut segment = text[segment_start..segment_end].to_string();
+            if self.unicode_normalize {
+                segment = segment.nfc().collect();
+            }
+            if self.case_insensitive {
+                segment = segment.to_lowercase();
+            }
+            Some((segment_start, segment))
+        })
+    }
+
+    /// Whether `text` is the token, as far as this anchor's matching options are concerned.
+    fn is_token(&self, text: &str) -> bool {
+        if self.folds_text() {
+            self.fold(text).text == self.fold(&self.token).text
+        } else {
+            text == self.token
+        }
+    }
+
+    /// Returns the byte range of the token starting at `token_offset` in `snapshot`. It differs
+    /// in length from `token` when the token only matched after folding case or normalizing
+    /// Unicode.
+    pub fn token_range(&self, snapshot: &BufferSnapshot, token_offset: usize) -> Range<usize> {
+        if !self.folds_text() {
+            return token_offset..token_offset + self.token.len();
+        }
+        // Folding changes a character into at most a few, so this is enough text to cover the
+        // token however it is written.
+        let text = snapshot
+            .chars_at(token_offset)
+            .take(self.token.chars().count() * 4 + 8)
+            .collect::<String>();
+        let folded_token_len = self.fold(&self.token).text.len();
+        let mut folded_len = 0;
+        for (segment_start, segment) in self.fold_segments(&text) {
+            if folded_len >= folded_token_len {
+                return token_offset..token_offset + segment_start;
+            }
+            folded_len += segment.len();
+        }
+        token_offset..token_offset + text.len()
+    }
+
+    /// Returns whether the occurrence of `token` at `offset` in `text` isn't joined to identifier
+    /// characters on either side, or `true` when `whole_word` isn't set. Only the ends of `token`
+    /// that are identifier characters need a boundary, so tokens like `&mut` still match.
+    fn is_whole_word_at(&self, text: &str, offset: usize) -> bool {
+        if !self.whole_word {
+            return true;
+        }
+        let joined_before = self.token.chars().next().is_some_and(is_word_character)
+            && text[..offset]
+                .chars()
+                .next_back()
+                .is_some_and(is_word_character);
+        let joined_after = self
+            .token
+            .chars()
+            .next_back()
+            .is_some_and(is_word_character)
+            && text[offset + self.token.len()..]
+                .chars()
+                .next()
+                .is_some_and(is_word_character);
+        !joined_before && !joined_after
+    }
+
+    /// Whether `context` is little more than `token`, which is fine when it's unique but explains
+    /// why it's ambiguous when it isn't.
+    fn is_short_context(&self) -> bool {
+        let significant_chars = self
+            .context
+            .chars()
+            .filter(|character| !character.is_whitespace())
+            .count();
+        self.context.trim() == self.token || significant_chars < MIN_CONTEXT_SIGNIFICANT_CHARS
+    }
+
+    /// Checks everything that can be verified without opening the buffer, so that malformed
+    /// anchors are rejected before paying for a file load and a syntax parse.
+    pub fn validate_basic(&self) -> Result<()> {
+        if self.path.is_empty() {
+            return Err(anyhow!(
+                "Provide `path`, `context` and `token`, or the `handle` of an anchor that an earlier tool call resolved."
+            ));
+        }
+        if self.context.is_empty() {
+            return Err(anyhow!("The provided `context` must not be empty."));
+        }
+        if self.token.is_empty() {
+            return Err(anyhow!("The provided `token` must not be empty."));
+        }
+
+        if self.approximate_line == Some(0) {
+            return Err(anyhow!(
+                "`approximate_line` is 1-based, so it must be at least 1."
+            ));
+        }
+
+        match &self.end_token {
+            Some(end_token) if end_token.is_empty() => {
+                return Err(anyhow!("The provided `end_token` must not be empty."));
+            }
+            Some(_) => {
+                let Some(end_offset_in_context) = self.end_token_offset_in_context() else {
+                    return Err(match self.end_index {
+                        Some(end_index) => anyhow!(
+                            "`end_index` {end_index} is out of range: `end_token` occurs fewer times in `context`."
+                        ),
+                        None => anyhow!("The provided `context` must contain the `end_token`."),
+                    });
+                };
+                if self
+                    .token_offset_in_context()
+                    .is_some_and(|token_offset| end_offset_in_context < token_offset)
+                {
+                    return Err(anyhow!(
+                        "The selected `end_token` comes before `token` in `context`, but the span must end at or after `token`."
+                    ));
+                }
+            }
+            None if self.end_index.is_some() => {
+                return Err(anyhow!("`end_index` can only be given with `end_token`."));
+            }
+            None => {}
+        }
+
+        let occurrences = self.token_occurrences_in_context();
+        match self.index {
+            _ if occurrences == 0 => Err(AnchorResolutionError::TokenNotInContext.into()),
+            Some(index) if index as usize >= occurrences => {
+                Err(AnchorResolutionError::IndexOutOfRange {
+                    index,
+                    count: occurrences,
+                }
+                .into())
+            }
+            None if occurrences > 1 => {
+                Err(AnchorResolutionError::AmbiguousToken { count: occurrences }.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the byte offset of the selected `token` occurrence relative to the start of `context`.
+    pub fn token_offset_in_context(&self) -> Option<usize> {
+        if self.token.is_empty() {
+            return None;
+        }
+        self.token_matches(&self.context)
+            .get(self.index.unwrap_or(0) as usize)
+            .copied()
+    }
+
+    /// Returns an anchor whose `token` is this anchor's `end_token`, so that it is matched with the
+    /// same options.
+    fn end_anchor(&self) -> Option<Self> {
+        Some(Self {
+            token: self.end_token.clone()?,
+            index: self.end_index,
+            end_token: None,
+            end_index: None,
+            ..self.clone()
+        })
+    }
+
+    /// Returns the byte offset of the selected `end_token` occurrence relative to the start of
+    /// `context`.
+    pub fn end_token_offset_in_context(&self) -> Option<usize> {
+        let occurrences = self.end_anchor()?.token_matches(&self.context);
+        match self.end_index {
+            Some(end_index) => occurrences.get(end_index as usize).copied(),
+            None => occurrences.last().copied(),
+        }
+    }
+
+    /// Returns the byte range from the start of the token at `token_offset` in `snapshot` to the
+    /// end of the selected `end_token`, or just the token's range without an `end_token`.
+    pub fn span_range(&self, snapshot: &BufferSnapshot, token_offset: usize) -> Range<usize> {
+        let token_range = self.token_range(snapshot, token_offset);
+        let (Some(end_anchor), Some(token_offset_in_context), Some(end_offset_in_context)) = (
+            self.end_anchor(),
+            self.token_offset_in_context(),
+            self.end_token_offset_in_context(),
+        ) else {
+            return token_range;
+        };
+        // Offsets in `context` don't carry over to the buffer when `context` only matched after
+        // normalizing, so count the end tokens to skip instead.
+        let skipped_end_tokens = end_anchor
+            .token_matches(&self.context)
+            .into_iter()
+            .filter(|&offset| token_offset_in_context <= offset && offset < end_offset_in_context)
+            .count();
+        let text = snapshot
+            .chars_at(token_offset)
+            .take(self.context.chars().count() * 4 + 16)
+            .collect::<String>();
+        match end_anchor.token_matches(&text).get(skipped_end_tokens) {
+            Some(&end_token_start) => {
+                token_offset
+                    ..end_anchor
+                        .token_range(snapshot, token_offset + end_token_start)
+                        .end
+            }
+            None => token_range,
+        }
+    }
+
+    /// Returns the 0-based rows that `context` spans, given the row its token resolved to and the
+    /// last row of the file.
+    pub fn context_rows(&self, token_row: u32, max_row: u32) -> Range<u32> {
+        let token_offset_in_context = self.token_offset_in_context().unwrap_or_default();
+        let rows_before_token = self.context[..token_offset_in_context]
+            .matches('\n')
+            .count() as u32;
+        let context_rows = self.context.trim_end().lines().count().max(1) as u32;
+        let start_row = token_row.saturating_sub(rows_before_token);
+        start_row..(start_row + context_rows).min(max_row + 1)
+    }
+
+    /// Returns the byte offset in `text` of the selected `token` for every place `context` occurs,
+    /// including overlapping occurrences. Each offset lies on a character boundary and is followed
+    /// by `token`, so callers can slice `text` or convert the offset to a point without re-checking.
+    pub fn candidate_token_offsets(&self, text: &str) -> Vec<usize> {
+        if self.folds_text() {
+            return self.candidate_token_offsets_with_strategy(text, ContextMatchStrategy::Exact);
+        }
+        let Some(token_offset_in_context) = self.token_offset_in_context() else {
+            return Vec::new();
+        };
+
+        let mut offsets = Vec::new();
+        let mut start = 0;
+        while let Some(position) = text[start..].find(&self.context) {
+            let context_start = start + position;
+            // The token's boundaries at the edges of `context` are only known in `text`.
+            if self.is_whole_word_at(text, context_start + token_offset_in_context) {
+                offsets.push(context_start + token_offset_in_context);
+            }
+
+            // Advance by a whole character so overlapping matches are found without slicing in
+            // the middle of a multi-byte character.
+            start = context_start
+                + text[context_start..]
+                    .chars()
+                    .next()
+                    .map_or(1, char::len_utf8);
+            if start >= text.len() {
+                break;
+            }
+        }
+        offsets
+    }
+
+    /// Like [`Self::candidate_token_offsets`], but compares `context` against `text` using
+    /// `strategy`. Every returned offset is followed by `token` in `text`.
+    pub fn candidate_token_offsets_with_strategy(
+        &self,
+        text: &str,
+        strategy: ContextMatchStrategy,
+    ) -> Vec<usize> {
+        if self.folds_text() {
+            let folded = self.fold(text);
+            return self
+                .folded()
+                .candidate_token_offsets_with_strategy(&folded.text, strategy)
+                .into_iter()
+                .map(|offset| folded.offsets[offset])
+                .collect();
+        }
+        match strategy {
+            ContextMatchStrategy::Exact => self.candidate_token_offsets(text),
+            ContextMatchStrategy::WhitespaceNormalized => {
+                self.whitespace_normalized_token_offsets(text)
+            }
+            ContextMatchStrategy::Fuzzy => self.fuzzy_token_offsets(text),
+        }
+    }
+
+    fn whitespace_normalized_token_offsets(&self, text: &str) -> Vec<usize> {
+        let Some(token_offset_in_context) = self.token_offset_in_context() else {
+            return Vec::new();
+        };
+        let pattern = self
+            .context
+            .split_whitespace()
+            .map(regex::escape)
+            .collect::<Vec<_>>()
+            .join(r"\s+");
+        if pattern.is_empty() {
+            return Vec::new();
+        }
+        let Ok(regex) = Regex::new(&pattern) else {
+            return Vec::new();
+        };
+
+        // Whitespace can change length, so the token is located by how many non-whitespace
+        // characters precede it in `context`.
+        let significant_chars_before_token = self.context[..token_offset_in_context]
+            .chars()
+            .filter(|character| !character.is_whitespace())
+            .count();
+
+        let mut offsets = Vec::new();
+        let mut start = 0;
+        while let Some(found) = regex.find_at(text, start) {
+            let token_start = text[found.range()]
+                .char_indices()
+                .filter(|(_, character)| !character.is_whitespace())
+                .nth(significant_chars_before_token)
+                .map(|(offset, _)| found.start() + offset);
+            if let Some(token_start) = token_start
+                && text[token_start..].starts_with(&self.token)
+                && self.is_whole_word_at(text, token_start)
+            {
+                offsets.push(token_start);
+            }
+
+            start = found.start()
+                + text[found.start()..]
+                    .chars()
+                    .next()
+                    .map_or(1, char::len_utf8);
+            if start >= text.len() {
+                break;
+            }
+        }
+        offsets
+    }
+
+    fn fuzzy_token_offsets(&self, text: &str) -> Vec<usize> {
+        let Some(token_offset_in_context) = self.token_offset_in_context() else {
+            return Vec::new();
+        };
+        let context_lines = self.context.lines().map(str::trim).collect::<Vec<_>>();
+        if context_lines.is_empty() {
+            return Vec::new();
+        }
+
+        // Where the token sits within its (trimmed) line of `context`, used to pick the nearest
+        // occurrence of the token in the matching line of `text`.
+        let token_row = self.context[..token_offset_in_context]
+            .matches('\n')
+            .count();
+        let token_line_start = self.context[..token_offset_in_context]
+            .rfind('\n')
+            .map_or(0, |ix| ix + 1);
+        let expected_column = self.context[token_line_start..token_offset_in_context]
+            .trim_start()
+            .len();
+
+        let mut text_lines = Vec::new();
+        let mut line_start = 0;
+        for line in text.split_inclusive('\n') {
+            text_lines.push((line_start, line.trim_end_matches(['\n', '\r'])));
+            line_start += line.len();
+        }
+
+        let mut offsets = Vec::new();
+        for window in text_lines.windows(context_lines.len()) {
+            let lines_match =
+                window
+                    .iter()
+                    .zip(&context_lines)
+                    .all(|((_, text_line), context_line)| {
+                        let text_line = text_line.trim();
+                        text_line == *context_line || fuzzy_eq(text_line, context_line)
+                    });
+            if !lines_match {
+                continue;
+            }
+
+            let (line_start, line) = window[token_row];
+            let indent = line.len() - line.trim_start().len();
+            let nearest = self
+                .token_matches(line)
+                .into_iter()
+                .min_by_key(|offset| offset.saturating_sub(indent).abs_diff(expected_column));
+            if let Some(offset) = nearest {
+                offsets.push(line_start + offset);
+            }
+        }
+        offsets
+    }
+
+    /// Describes `range` of `buffer` as an ACP tool call location that also carries its columns,
+    /// this anchor and the buffer's version, so clients can navigate to the exact token and
+    /// external agents can hand the anchor back. Returns `None` for buffers without a local file.
+    pub fn tool_call_location(
+        &self,
+        buffer: &Buffer,
+        range: Range<text::Anchor>,
+        cx: &App,
+    ) -> Option<acp::ToolCallLocation> {
+        let abs_path = buffer.file()?.as_local()?.abs_path(cx);
+        let snapshot = buffer.snapshot();
+        let range = range.to_point(&snapshot);
+        let end_column = if range.end.row == range.start.row {
+            range.end.column
+        } else {
+            snapshot.line_len(range.start.row)
+        };
+        let detail = LocationDetail {
+            columns: Some(range.start.column..end_column),
+            anchor: Some(LocationAnchor {
+                path: self.path.clone(),
+                context: self.context.clone(),
+                token: self.token.clone(),
+                index: self.index,
+                revision: self.revision.clone(),
+                whole_word: self.whole_word,
+                approximate_line: self.approximate_line,
+                case_insensitive: self.case_insensitive,
+                unicode_normalize: self.unicode_normalize,
+                end_token: self.end_token.clone(),
+                end_index: self.end_index,
+            }),
+            version: Some(BufferVersion::of(buffer).to_string()),
+        };
+        Some(detail.attach_to(acp::ToolCallLocation::new(abs_path).line(Some(range.start.row))))
+    }
+
+    /// Describes where this anchor resolved in a buffer loaded at `revision`: the largest syntax
+    /// node around the token that spans at most `max_scope_lines` lines. Language servers only index
+    /// the project's current files, so this is as far as an anchor at a revision can be followed.
+    pub fn describe_at_revision(
+        &self,
+        revision: &str,
+        snapshot: &BufferSnapshot,
+        token_offset: usize,
+        max_scope_lines: u32,
+    ) -> String {
+        let token_row = snapshot.offset_to_point(token_offset).row;
+        let mut rows = token_row..token_row;
+        let mut node = snapshot.syntax_ancestor(self.token_range(snapshot, token_offset));
+        while let Some(current) = node {
+            let range = current.byte_range().to_point(snapshot);
+            if range.end.row - range.start.row > max_scope_lines {
+                break;
+            }
+            rows = range.start.row..range.end.row;
+            node = current.parent();
+        }
+        let scope = snapshot
+            .text_for_range(
+                Point::new(rows.start, 0)..Point::new(rows.end, snapshot.line_len(rows.end)),
+            )
+            .collect::<String>();
+        format!(
+            "{} at {revision} [L{}-{}]\n\n```\n{scope}\n```\n\n`{}` was found at {revision}, but language servers only index the current contents of the project, so it can't be followed to other code at that revision. Omit `revision` to navigate the current code.",
+            self.path,
+            rows.start + 1,
+            rows.end + 1,
+            self.token
+        )
+    }
+
+    /// Grows `context` to whole lines around the match starting at `context_start` in `text` until
+    /// the snippet occurs only once, returning an anchor that resolves to the same token.
+    pub fn expand_to_unique(&self, text: &str, context_start: usize) -> Option<ContextualAnchor> {
+        let token_start = context_start + self.token_offset_in_context()?;
+        let context_end = context_start + self.context.len();
+        if context_end > text.len() || text.get(context_start..context_end)? != self.context {
+            return None;
+        }
+
+        let mut line_start = text[..context_start].rfind('\n').map_or(0, |ix| ix + 1);
+        let mut line_end = text[context_end..]
+            .find('\n')
+            .map_or(text.len(), |ix| context_end + ix);
+        for _ in 0..=MAX_CONTEXT_EXPANSION_LINES {
+            let indent = text[line_start..context_start].len()
+                - text[line_start..context_start].trim_start().len();
+            let start = line_start + indent;
+            let snippet = &text[start..line_end];
+            if snippet != self.context && occurs_once(text, snippet) {
+                let index = self
+                    .token_matches(snippet)
+                    .into_iter()
+                    .position(|offset| start + offset == token_start)?;
+                let end_index = match (self.end_anchor(), self.end_token_offset_in_context()) {
+                    (Some(end_anchor), Some(end_offset_in_context)) => {
+                        let end_token_start = context_start + end_offset_in_context;
+                        let end_index = end_anchor
+                            .token_matches(snippet)
+                            .into_iter()
+                            .position(|offset| start + offset == end_token_start)?;
+                        Some(end_index as u32)
+                    }
+                    _ => None,
+                };
+                return Some(ContextualAnchor {
+                    path: self.path.clone(),
+                    context: snippet.to_string(),
+                    token: self.token.clone(),
+                    index: (index > 0).then_some(index as u32),
+                    handle: None,
+                    revision: self.revision.clone(),
+                    whole_word: self.whole_word,
+                    approximate_line: None,
+                    case_insensitive: self.case_insensitive,
+                    unicode_normalize: self.unicode_normalize,
+                    end_token: self.end_token.clone(),
+                    end_index,
+                });
+            }
+
+            if line_start == 0 && line_end == text.len() {
+                break;
+            }
+            if line_start > 0 {
+                line_start = text[..line_start - 1].rfind('\n').map_or(0, |ix| ix + 1);
+            }
+            if line_end < text.len() {
+                line_end = text[line_end + 1..]
+                    .find('\n')
+                    .map_or(text.len(), |ix| line_end + 1 + ix);
+            }
+        }
+        None
+    }
+
+    /// Returns the offsets of the candidate tokens in `snapshot` that Tree-sitter confirms are the
+    /// token itself, rather than part of a longer identifier, a comment or a string. Without a
+    /// syntax tree every candidate is accepted.
+    pub fn resolve_token_offsets(&self, snapshot: &BufferSnapshot) -> Vec<usize> {
+        self.candidate_token_offsets(&snapshot.text())
+            .into_iter()
+            .filter(|&token_start| self.syntax_confirms_token(snapshot, token_start))
+            .collect()
+    }
+
+    /// Whether Tree-sitter confirms that the candidate token at `token_start` is the token itself
+    /// rather than part of a longer identifier, a comment or a string. Keywords and punctuation
+    /// are only confirmed as the start of a span. Buffers without a syntax tree always confirm it.
+    pub fn syntax_confirms_token(&self, snapshot: &BufferSnapshot, token_start: usize) -> bool {
+        let token_end = self.token_range(snapshot, token_start).end;
+        let Some(node) = snapshot.syntax_ancestor(token_start..token_end) else {
+            return true;
+        };
+        if !node.is_named() {
+            return false;
+        }
+        // Keywords such as `fn` are anonymous nodes, which a span may start at to cover e.g. a
+        // whole function.
+        if let Some(leaf) = node.descendant_for_byte_range(token_start, token_end)
+            && !leaf.is_named()
+            && leaf.byte_range() == (token_start..token_end)
+        {
+            return self.end_token.is_some();
+        }
+        let node = node
+            .named_descendant_for_byte_range(token_start, token_end)
+            .unwrap_or(node);
+        let text = snapshot
+            .text_for_range(node.byte_range())
+            .collect::<String>();
+        self.is_token(text.trim()) && node.kind() != "comment" && node.kind() != "string"
+    }
+
+    /// Checks that the token at `token_offset` in `snapshot` is what `syntax` requires, so that
+    /// an anchor landing in a comment, a string literal or on a keyword is rejected with an
+    /// explanation instead of sending a language server request that can't succeed. Buffers
+    /// without a syntax tree always pass.
+    pub fn validate_token_syntax(
+        &self,
+        snapshot: &BufferSnapshot,
+        token_offset: usize,
+        display_path: &str,
+        syntax: TokenSyntax,
+    ) -> Result<()> {
+        let token_end = self.token_range(snapshot, token_offset).end;
+        let Some(node) = snapshot.syntax_ancestor(token_offset..token_end) else {
+            return Ok(());
+        };
+        let node = node
+            .named_descendant_for_byte_range(token_offset, token_end)
+            .unwrap_or(node);
+        let line = snapshot.offset_to_point(token_offset).row + 1;
+
+        let mut current = Some(node);
+        while let Some(ancestor) = current {
+            let kind = ancestor.kind();
+            // Code interpolated into a string (e.g. `${value}`) is still code.
+            if kind.contains("interpolation") || kind.contains("substitution") {
+                break;
+            }
+            if kind.contains("comment") || kind.contains("string") {
+                return Err(anyhow!(
+                    "`{}` at L{line} in {display_path} is inside a {}, not code. Use a `context` where `token` is used in code.",
+                    self.token,
+                    kind.replace('_', " ")
+                ));
+            }
+            current = ancestor.parent();
+        }
+
+        let kind = node.kind();
+        let is_symbol = kind.contains("identifier")
+            || kind.contains("name")
+            || kind.contains("type")
+            || kind.contains("constant")
+            || kind.contains("property")
+            || kind.contains("field")
+            || matches!(kind, "self" | "super" | "crate" | "this" | "metavariable");
+        let token_is_node = self.is_token(
+            snapshot
+                .text_for_range(node.byte_range())
+                .collect::<String>()
+                .trim(),
+        );
+        if syntax == TokenSyntax::Symbol && !(is_symbol && token_is_node) {
+            return Err(anyhow!(
+                "`{}` at L{line} in {display_path} is {}, not an identifier, field or type, so it can't be resolved to a symbol. Set `token` to the name of the symbol.",
+                self.token,
+                if token_is_node {
+                    format!("a {}", kind.replace('_', " "))
+                } else {
+                    "part of a larger expression or a keyword".to_string()
+                }
+            ));
+        }
+        Ok(())
+    }
+
+    /// Resolves the token to a single offset in `snapshot`, failing with a suggested unique
+    /// `context` for every match when the anchor is ambiguous.
+    pub fn resolve_unique_token_offset(
+        &self,
+        snapshot: &BufferSnapshot,
+        display_path: &str,
+    ) -> Result<usize> {
+        Ok(
+            resolve_unique_in_buffer(self, snapshot, display_path, None)?
+                .range
+                .start,
+        )
+    }
+
+    /// Explains that the anchor matched more than once, suggesting a unique `context` for every
+    /// match.
+    pub fn ambiguous_error(
+        &self,
+        snapshot: &BufferSnapshot,
+        matches: &[TokenMatch],
+        display_path: &str,
+    ) -> anyhow::Error {
+        let text = snapshot.text();
+        let token_offset_in_context = self.token_offset_in_context().unwrap_or_default();
+        let mut message = format!(
+            "Ambiguous context: found {} matches in {display_path}.",
+            matches.len()
+        );
+        let mut occurrence_lines = Vec::new();
+        for token_match in matches {
+            let row = snapshot.offset_to_point(token_match.range.start).row;
+            occurrence_lines.push(row + 1);
+            // A match found after folding the text can be shorter than `context`, so there may be
+            // no exact copy of `context` to expand from.
+            if let Some(context_start) =
+                token_match.range.start.checked_sub(token_offset_in_context)
+                && let Some(expanded) = self.expand_to_unique(&text, context_start)
+                && let Ok(context) = serde_json::to_string(&expanded.context)
+            {
+                write!(message, "\nL{}: suggested context {context}", row + 1).ok();
+                if let Some(index) = expanded.index {
+                    write!(message, " (index: {index})").ok();
+                }
+            }
+        }
+        if self.is_short_context() {
+            write!(message, "\n{SHORT_CONTEXT_HINT}").ok();
+        }
+        AnchorResolutionError::AmbiguousContext {
+            message,
+            occurrence_lines,
+        }
+        .into()
+    }
+
+    /// Lists every match of an ambiguous anchor with a preview of the code around it and a
+    /// suggested `context` that only matches there. Matches whose previews overlap share one
+    /// preview, which keeps the listing short when they are close together.
+    pub fn describe_ambiguous_matches(
+        &self,
+        snapshot: &BufferSnapshot,
+        matches: &[TokenMatch],
+        display_path: &str,
+    ) -> String {
+        let mut match_indices = (0..matches.len()).collect::<Vec<_>>();
+        match_indices.sort_by_key(|&match_index| matches[match_index].snippet_range.start.row);
+        let mut previews: Vec<Range<Point>> = Vec::new();
+        let mut preview_indices = vec![0; matches.len()];
+        for match_index in match_indices {
+            let snippet_range = &matches[match_index].snippet_range;
+            match previews.last_mut() {
+                Some(preview) if snippet_range.start.row <= preview.end.row => {
+                    if snippet_range.end.row > preview.end.row {
+                        preview.end = snippet_range.end;
+                    }
+                }
+                _ => previews.push(snippet_range.clone()),
+            }
+            preview_indices[match_index] = previews.len() - 1;
+        }
+
+        let text = snapshot.text();
+        let token_offset_in_context = self.token_offset_in_context().unwrap_or_default();
+        let mut listing = format!(
+            "Ambiguous context: found {} matches in {display_path}:\n\n",
+            matches.len()
+        );
+        for (match_index, token_match) in matches.iter().enumerate() {
+            let row = snapshot.offset_to_point(token_match.range.start).row;
+            let preview = snapshot
+                .text_for_range(previews[preview_indices[match_index]].clone())
+                .collect::<String>();
+            write!(
+                listing,
+                "[{match_index}] L{}:\n\n``` \n{}\n```\n",
+                row + 1,
+                preview.trim()
+            )
+            .ok();
+            if let Some(context_start) =
+                token_match.range.start.checked_sub(token_offset_in_context)
+                && let Some(expanded) = self.expand_to_unique(&text, context_start)
+                && let Ok(context) = serde_json::to_string(&expanded.context)
+            {
+                match expanded.index {
+                    Some(index) => {
+                        writeln!(listing, "Suggested context: {context} (index: {index})")
+                    }
+                    None => writeln!(listing, "Suggested context: {context}"),
+                }
+                .ok();
+            }
+            listing.push('\n');
+        }
+        listing.push_str(
+            "\nExtend `context` with neighbouring text so that it matches exactly once, e.g. by using one of the suggested contexts above.",
+        );
+        if self.is_short_context() {
+            write!(listing, " {SHORT_CONTEXT_HINT}").ok();
+        }
+        listing
+    }
+
+    /// Explains why no candidate in `snapshot` resolved: either `context` only occurs where the
+    /// syntax tree shows the token isn't code, or it doesn't occur at all.
+    pub fn unresolved_error(&self, snapshot: &BufferSnapshot, display_path: &str) -> anyhow::Error {
+        let text = snapshot.text();
+        for token_offset in self.candidate_token_offsets(&text) {
+            if let Err(error) =
+                self.validate_token_syntax(snapshot, token_offset, display_path, TokenSyntax::Code)
+            {
+                return error;
+            }
+        }
+        self.not_found_error(&text, display_path)
+    }
+
+    /// Explains that `context` wasn't found in `text`, listing the regions most similar to it so
+    /// that the anchor can be corrected rather than guessed again.
+    pub fn not_found_error(&self, text: &str, display_path: &str) -> anyhow::Error {
+        let mut message = format!("context/token not found in {display_path}");
+        let near_misses = self.near_misses(text, MAX_NEAR_MISSES);
+        if !near_misses.is_empty() {
+            message.push_str(". The most similar regions are:");
+        }
+        let nearest_lines = near_misses
+            .iter()
+            .map(|near_miss| near_miss.rows.start + 1)
+            .collect();
+        for near_miss in near_misses {
+            let Ok(context) = serde_json::to_string(&near_miss.text) else {
+                continue;
+            };
+            let lines = if near_miss.rows.len() > 1 {
+                format!("L{}-{}", near_miss.rows.start + 1, near_miss.rows.end)
+            } else {
+                format!("L{}", near_miss.rows.start + 1)
+            };
+            write!(
+                message,
+                "\n{lines} (similarity {:.2}): {context}",
+                near_miss.similarity
+            )
+            .ok();
+        }
+        AnchorResolutionError::NoContextMatch {
+            message,
+            nearest_lines,
+        }
+        .into()
+    }
+
+    /// Returns up to `limit` non-overlapping regions of `text`, each as many lines long as
+    /// `context`, whose lines are most similar to those of `context`, best first.
+    pub fn near_misses(&self, text: &str, limit: usize) -> Vec<NearMiss> {
+        let context_lines = self.context.lines().map(str::trim).collect::<Vec<_>>();
+        let text_lines = text.lines().collect::<Vec<_>>();
+        if context_lines.is_empty() || context_lines.len() > text_lines.len() {
+            return Vec::new();
+        }
+
+        let mut candidates = text_lines
+            .windows(context_lines.len())
+            .enumerate()
+            .map(|(start_row, window)| {
+                let total = window
+                    .iter()
+                    .zip(&context_lines)
+                    .map(|(text_line, context_line)| {
+                        line_similarity(text_line.trim(), context_line)
+                    })
+                    .sum::<f64>();
+                (start_row, total / context_lines.len() as f64)
+            })
+            .filter(|(_, similarity)| *similarity >= MIN_NEAR_MISS_SIMILARITY)
+            .collect::<Vec<_>>();
+        candidates.sort_by(|(left_row, left), (right_row, right)| {
+            right.total_cmp(left).then(left_row.cmp(right_row))
+        });
+
+        let mut near_misses: Vec<NearMiss> = Vec::new();
+        for (start_row, similarity) in candidates {
+            if near_misses.len() == limit {
+                break;
+            }
+            let rows = start_row as u32..(start_row + context_lines.len()) as u32;
+            if near_misses
+                .iter()
+                .any(|near_miss| near_miss.rows.start < rows.end && rows.start < near_miss.rows.end)
+            {
+                continue;
+            }
+            near_misses.push(NearMiss {
+                text: text_lines[start_row..start_row + context_lines.len()]
+                    .join("\n")
+                    .trim()
+                    .to_string(),
+                rows,
+                similarity,
+            });
+        }
+        near_misses
+    }
+
+    /// Builds an anchor for the token at `range` in `snapshot`, with the shortest `context` that
+    /// is unique (see [`Self::minimize`]). Returns `None` when `range` spans several lines or is
+    /// empty, since that isn't a token.
+    pub fn for_range(
+        path: String,
+        snapshot: &BufferSnapshot,
+        range: Range<usize>,
+    ) -> Option<ContextualAnchor> {
+        let start = snapshot.offset_to_point(range.start);
+        let end = snapshot.offset_to_point(range.end);
+        if range.is_empty() || start.row != end.row {
+            return None;
+        }
+        let token = snapshot.text_for_range(range.clone()).collect::<String>();
+        let anchor = ContextualAnchor {
+            path,
+            context: token.clone(),
+            token,
+            index: None,
+            handle: None,
+            revision: None,
+            whole_word: false,
+            approximate_line: None,
+            case_insensitive: false,
+            unicode_normalize: false,
+            end_token: None,
+            end_index: None,
+        };
+        anchor.minimize(snapshot, range.start)
+    }
+
+    /// Returns this anchor, resolved to the token at `token_offset` in `snapshot`, with the
+    /// shortest `context` that matches only there. `context` grows from the token's span a word at
+    /// a time on both sides until it is unique, up to the whole lines the span is on, and then a
+    /// line at a time like [`Self::expand_to_unique`]. Anchors handed back to the model should be
+    /// minimized, so that follow-up calls neither fail as ambiguous nor carry more text than needed.
+    ///
+    /// Returns `None` when no `context` within a few lines of the token is unique.
+    pub fn minimize(
+        &self,
+        snapshot: &BufferSnapshot,
+        token_offset: usize,
+    ) -> Option<ContextualAnchor> {
+        let text = snapshot.text();
+        let token = self.token_range(snapshot, token_offset);
+        let span = self.span_range(snapshot, token_offset);
+        let end_token = match self.end_anchor() {
+            Some(end_anchor) => {
+                let end_token_start =
+                    span.start + *end_anchor.token_matches(&text[span.clone()]).last()?;
+                Some(end_anchor.token_range(snapshot, end_token_start))
+            }
+            None => None,
+        };
+
+        let start_row = snapshot.offset_to_point(span.start).row;
+        let end_row = snapshot.offset_to_point(span.end).row;
+        let lines = trim_whitespace(
+            &text,
+            Point::new(start_row, 0).to_offset(snapshot)
+                ..Point::new(end_row, snapshot.line_len(end_row)).to_offset(snapshot),
+        );
+        let mut context = span;
+        loop {
+            let anchor =
+                self.with_context(&text, context.clone(), token.clone(), end_token.clone());
+            if anchor.validate_basic().is_ok()
+                && !anchor.is_short_context()
+                && anchor.candidate_token_offsets(&text) == [token.start]
+            {
+                return Some(anchor);
+            }
+            if context == lines {
+                return anchor.expand_to_unique(&text, lines.start);
+            }
+            context = grow_left(&text, context.start, lines.start)
+                ..grow_right(&text, context.end, lines.end);
+        }
+    }
+
+    /// Returns a copy of this anchor whose `context`, `token` and `end_token` are the given ranges
+    /// of `text`, with `index` and `end_index` selecting those occurrences.
+    fn with_context(
+        &self,
+        text: &str,
+        context: Range<usize>,
+        token: Range<usize>,
+        end_token: Option<Range<usize>>,
+    ) -> ContextualAnchor {
+        let mut anchor = ContextualAnchor {
+            context: text[context.clone()].to_string(),
+            token: text[token.clone()].to_string(),
+            index: None,
+            handle: None,
+            approximate_line: None,
+            end_token: end_token
+                .as_ref()
+                .map(|end_token| text[end_token.clone()].to_string()),
+            end_index: None,
+            ..self.clone()
+        };
+        anchor.index = anchor
+            .token_matches(&anchor.context)
+            .into_iter()
+            .position(|offset| context.start + offset == token.start)
+            .filter(|&index| index > 0)
+            .map(|index| index as u32);
+        if let Some(end_token) = end_token
+            && let Some(end_anchor) = anchor.end_anchor()
+        {
+            let end_offsets = end_anchor.token_matches(&anchor.context);
+            // Without `end_index` the last occurrence is used, so only an earlier one needs it.
+            anchor.end_index = end_offsets
+                .iter()
+                .position(|&offset| context.start + offset == end_token.start)
+                .filter(|&index| index + 1 < end_offsets.len())
+                .map(|index| index as u32);
+        }
+        anchor
+    }
+}
+
+/// Loads `project_path` as of git `revision` into a buffer that isn't part of the project, with
+/// the file's language so that anchors are validated against its syntax as usual.
+pub fn open_buffer_at_revision(
+    project: &Entity<Project>,
+    project_path: &ProjectPath,
+    revision: String,
+    cx: &mut App,
+) -> Task<Result<Entity<Buffer>>> {
+    let Some((repository, repo_path)) = project
+        .read(cx)
+        .git_store()
+        .read(cx)
+        .repository_and_path_for_project_path(project_path, cx)
+    else {
+        return Task::ready(Err(anyhow!(
+            "The file is not in a git repository, so it can't be read at {revision}."
+        )));
+    };
+    let text = repository.update(cx, |repository, _| {
+        repository.load_text_at_revision(revision, repo_path)
+    });
+    let languages = project.read(cx).languages().clone();
+    let path = project_path.path.as_std_path().to_path_buf();
+    cx.spawn(async move |cx| {
+        let text = text.await??;
+        // Files without a known language still resolve, just without syntax validation.
+        let language = languages.load_language_for_file_path(&path).await.ok();
+        cx.new(|cx| {
+            let mut buffer = Buffer::local(text, cx);
+            buffer.set_language(language, cx);
+            buffer
+        })
+    })
+}
+
+/// Text folded by [`ContextualAnchor::fold`], with the offset in the original text that each of its
+/// bytes came from.
+struct FoldedText {
+    text: String,
+    /// One entry per byte of `text`, plus one for its end.
+    offsets: Vec<usize>,
+}
+
+/// A region of a file that resembles an anchor's `context` which wasn't found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NearMiss {
+    /// The 0-based rows the region spans.
+    pub rows: Range<u32>,
+    /// How similar the region's lines are to those of `context`, from 0 to 1.
+    pub similarity: f64,
+    pub text: String,
+}
+
+/// An anchor resolved in a buffer, remembered in a form that follows later edits to the buffer so
+/// that it can be checked before it's used again rather than trusting a stale offset.
+#[derive(Debug, Clone)]
+pub struct AnchorResolution {
+    pub anchor: ContextualAnchor,
+    buffer_id: BufferId,
+    token: Range<text::Anchor>,
+    context: Range<text::Anchor>,
+    /// A hash of the text `context` matched when the anchor was resolved.
+    fingerprint: u64,
+}
+
+/// How an [`AnchorResolution`] still applies after its buffer changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AnchorRevalidation {
+    /// The text around the token is unchanged, though edits elsewhere may have shifted it to
+    /// `range`.
+    Unchanged { range: Range<usize> },
+    /// The text around the token was edited or moved, and `context` was found again at `range`.
+    Reresolved { range: Range<usize> },
+}
+
+impl AnchorResolution {
+    /// Remembers that `anchor`'s token was found at `token_offset` in `snapshot`.
+    pub fn new(anchor: ContextualAnchor, snapshot: &BufferSnapshot, token_offset: usize) -> Self {
+        let token = anchor.token_range(snapshot, token_offset);
+        let context_start =
+            token_offset.saturating_sub(anchor.token_offset_in_context().unwrap_or_default());
+        let context = context_start..(context_start + anchor.context.len()).min(snapshot.len());
+        Self {
+            buffer_id: snapshot.remote_id(),
+            token: snapshot.anchor_after(token.start)..snapshot.anchor_before(token.end),
+            // Text inserted right before or after `context` doesn't change what it matched.
+            context: snapshot.anchor_after(context.start)..snapshot.anchor_before(context.end),
+            fingerprint: fingerprint(snapshot, context),
+            anchor,
+        }
+    }
+
+    /// Checks whether the anchor still points at the same token in `snapshot`, a later version of
+    /// the buffer it was resolved in. When the text around the token changed, the anchor is
+    /// resolved again from scratch, failing if its `context` is gone or now ambiguous.
+    pub fn revalidate(
+        &mut self,
+        snapshot: &BufferSnapshot,
+        display_path: &str,
+    ) -> Result<AnchorRevalidation> {
+        if snapshot.remote_id() != self.buffer_id {
+            return Err(anyhow!(
+                "The anchor was resolved in a different buffer than {display_path}."
+            ));
+        }
+        let context = self.context.start.to_offset(snapshot)..self.context.end.to_offset(snapshot);
+        let token = self.token.start.to_offset(snapshot)..self.token.end.to_offset(snapshot);
+        if fingerprint(snapshot, context.clone()) == self.fingerprint
+            && context.start <= token.start
+            && token.end <= context.end
+        {
+            return Ok(AnchorRevalidation::Unchanged { range: token });
+        }
+
+        let token_offset = self
+            .anchor
+            .resolve_unique_token_offset(snapshot, display_path)?;
+        *self = Self::new(self.anchor.clone(), snapshot, token_offset);
+        Ok(AnchorRevalidation::Reresolved {
+            range: self.anchor.token_range(snapshot, token_offset),
+        })
+    }
+}
+
+fn fingerprint(snapshot: &BufferSnapshot, range: Range<usize>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for chunk in snapshot.text_for_range(range) {
+        hasher.write(chunk.as_bytes());
+    }
+    hasher.finish()
+}
+
+/// Where an anchor's token was found in a buffer by [`resolve_in_buffer`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenMatch {
+    /// The token's byte range in the buffer.
+    pub range: Range<usize>,
+    /// The token's range in UTF-16 code units, the way language servers address positions.
+    pub range_utf16: Range<PointUtf16>,
+    /// The byte range from the token to the end of the anchor's `end_token`, which is the token's
+    /// range when the anchor has no `end_token`.
+    pub span: Range<usize>,
+    /// [`Self::span`] in UTF-16 code units.
+    pub span_utf16: Range<PointUtf16>,
+    /// The whole lines to show when previewing the token: the largest syntax node around it that
+    /// spans at most [`MAX_SNIPPET_LINES`] lines, or two lines either side of it without a syntax
+    /// tree.
+    pub snippet_range: Range<Point>,
+}
+
+impl TokenMatch {
+    fn new(snapshot: &BufferSnapshot, range: Range<usize>, span: Range<usize>) -> Self {
+        let token_row = snapshot.offset_to_point(range.start).row;
+        let mut start_row = token_row.saturating_sub(2);
+        let mut end_row = (token_row + 2).min(snapshot.max_point().row);
+        if let Some(mut node) = snapshot.syntax_ancestor(range.clone()) {
+            while let Some(parent) = node.parent() {
+                let parent_range = parent.byte_range().to_point(snapshot);
+                if parent_range.end.row.saturating_sub(parent_range.start.row) > MAX_SNIPPET_LINES {
+                    break;
+                }
+                node = parent;
+            }
+            let node_range = node.byte_range().to_point(snapshot);
+            start_row = node_range.start.row;
+            end_row = node_range.end.row;
+        }
+        Self {
+            range_utf16: snapshot.offset_to_point_utf16(range.start)
+                ..snapshot.offset_to_point_utf16(range.end),
+            range,
+            span_utf16: snapshot.offset_to_point_utf16(span.start)
+                ..snapshot.offset_to_point_utf16(span.end),
+            span,
+            snippet_range: Point::new(start_row, 0)
+                ..Point::new(end_row, snapshot.line_len(end_row)),
+        }
+    }
+
+    /// The text of [`Self::snippet_range`].
+    pub fn snippet(&self, snapshot: &BufferSnapshot) -> String {
+        snapshot
+            .text_for_range(self.snippet_range.clone())
+            .collect()
+    }
+}
+
+/// How an anchor resolved in a buffer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnchorMatch {
+    Unique(TokenMatch),
+    /// The anchor matched more than once, so `context` needs extending. The matches are in the
+    /// order they appear in the buffer.
+    Ambiguous(Vec<TokenMatch>),
+}
+
+/// Finds `anchor`'s token in `snapshot`: searches for every occurrence of `context`, keeps the
+/// ones where Tree-sitter confirms the selected token is the token itself rather than part of a
+/// longer identifier, a comment or a string, and checks the remaining match against `syntax`.
+///
+/// Fails when nothing matches, explaining why; an ambiguous anchor isn't an error, so callers can
+/// decide how to present the matches.
+pub fn resolve_in_buffer(
+    anchor: &ContextualAnchor,
+    snapshot: &BufferSnapshot,
+    display_path: &str,
+    syntax: Option<TokenSyntax>,
+) -> Result<AnchorMatch> {
+    let resolve_span = ztracing::info_span!(
+        "resolve_contextual_anchor",
+        candidates = ztracing::field::Empty
+    );
+    let _enter = resolve_span.enter();
+    let token_offsets = anchor.resolve_token_offsets(snapshot);
+    resolve_span.record("candidates", token_offsets.len());
+    match_token_offsets(anchor, snapshot, token_offsets, display_path, syntax)
+}
+
+/// Turns the offsets in `snapshot` where `anchor`'s token was found into an [`AnchorMatch`] the
+/// way [`resolve_in_buffer`] does, for callers that already know the offsets.
+pub(crate) fn match_token_offsets(
+    anchor: &ContextualAnchor,
+    snapshot: &BufferSnapshot,
+    token_offsets: Vec<usize>,
+    display_path: &str,
+    syntax: Option<TokenSyntax>,
+) -> Result<AnchorMatch> {
+    let mut matches = token_offsets
+        .into_iter()
+        .map(|token_offset| {
+            TokenMatch::new(
+                snapshot,
+                anchor.token_range(snapshot, token_offset),
+                anchor.span_range(snapshot, token_offset),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    if matches.len() > 1 {
+        match anchor.approximate_line {
+            Some(approximate_line) => {
+                matches = nearest_to_row(snapshot, matches, approximate_line.saturating_sub(1));
+            }
+            None => return Ok(AnchorMatch::Ambiguous(matches)),
+        }
+        if matches.len() > 1 {
+            return Ok(AnchorMatch::Ambiguous(matches));
+        }
+    }
+    let Some(token_match) = matches.pop() else {
+        return Err(anchor.unresolved_error(snapshot, display_path));
+    };
+    if let Some(syntax) = syntax {
+        anchor.validate_token_syntax(snapshot, token_match.range.start, display_path, syntax)?;
+    }
+    Ok(AnchorMatch::Unique(token_match))
+}
+
+/// Keeps the matches whose token is nearest `row`, which is more than one only when several are
+/// equally near.
+fn nearest_to_row(
+    snapshot: &BufferSnapshot,
+    matches: Vec<TokenMatch>,
+    row: u32,
+) -> Vec<TokenMatch> {
+    let distance = |token_match: &TokenMatch| {
+        snapshot
+            .offset_to_point(token_match.range.start)
+            .row
+            .abs_diff(row)
+    };
+    let Some(nearest_distance) = matches.iter().map(distance).min() else {
+        return matches;
+    };
+    matches
+        .into_iter()
+        .filter(|token_match| distance(token_match) == nearest_distance)
+        .collect()
+}
+
+/// Like [`resolve_in_buffer`], but also fails when the anchor is ambiguous, suggesting a unique
+/// `context` for every match.
+pub fn resolve_unique_in_buffer(
+    anchor: &ContextualAnchor,
+    snapshot: &BufferSnapshot,
+    display_path: &str,
+    syntax: Option<TokenSyntax>,
+) -> Result<TokenMatch> {
+    match resolve_in_buffer(anchor, snapshot, display_path, syntax)? {
+        AnchorMatch::Unique(token_match) => Ok(token_match),
+        AnchorMatch::Ambiguous(matches) => {
+            Err(anchor.ambiguous_error(snapshot, &matches, display_path))
+        }
+    }
+}
+
+/// Where an anchor passed to [`resolve_anchors`] resolved.
+#[derive(Debug, Clone)]
+pub struct ResolvedAnchor {
+    /// The anchor, with the same fields it was given, or the contextual anchor that a symbol
+    /// anchor was turned into.
+    pub anchor: ContextualAnchor,
+    /// The buffer the anchor resolved in, which is detached from the project when the anchor has
+    /// a `revision`.
+    pub buffer: Entity<Buffer>,
+    pub display_path: String,
+    /// The token's byte range in the buffer.
+    pub range: Range<usize>,
+    /// The token's range in UTF-16 code units, the way language servers address positions.
+    pub range_utf16: Range<PointUtf16>,
+    /// The byte range from the token to the end of the anchor's `end_token`.
+    pub span: Range<usize>,
+    /// [`Self::span`] in UTF-16 code units.
+    pub span_utf16: Range<PointUtf16>,
+    /// Whether the anchor resolved against edits that aren't saved to disk yet.
+    pub unsaved: bool,
+}
+
+/// The anchors of one [`resolve_anchors`] call that are resolved against the same buffer.
+struct AnchorGroup {
+    project_path: ProjectPath,
+    revision: Option<String>,
+    display_path: String,
+    /// Indices into the anchors passed to [`resolve_anchors`].
+    anchor_indices: Vec<usize>,
+}
+
+/// Resolves many anchors at once, opening and parsing each file (or each file at a revision) only
+/// once however many anchors point into it. The results are in the same order as `anchors`, and
+/// one anchor failing to resolve doesn't affect the others.
+///
+/// Handles must already be expanded with [`super::AnchorMemory::expand`].
+pub fn resolve_anchors(
+    project: &Entity<Project>,
+    anchors: Vec<AnchorInput>,
+    cx: &mut App,
+) -> Task<Vec<Result<ResolvedAnchor>>> {
+    let mut results = anchors.iter().map(|_| None).collect::<Vec<_>>();
+    let mut groups: Vec<AnchorGroup> = Vec::new();
+    for (index, anchor) in anchors.iter().enumerate() {
+        match validate_for_resolution(project.read(cx), anchor, cx) {
+            Ok(project_path) => match groups.iter_mut().find(|group| {
+                group.project_path == project_path && group.revision.as_deref() == anchor.revision()
+            }) {
+                Some(group) => group.anchor_indices.push(index),
+                None => groups.push(AnchorGroup {
+                    display_path: display_project_path(project.read(cx), &project_path, cx)
+                        .unwrap_or_else(|| anchor.path().to_string()),
+                    project_path,
+                    revision: anchor.revision().map(str::to_string),
+                    anchor_indices: vec![index],
+                }),
+            },
+            Err(error) => results[index] = Some(Err(error)),
+        }
+    }
+
+    let project = project.clone();
+    cx.spawn(async move |cx| {
+        for group in groups {
+            let anchor_buffer = match open_anchor_buffer(
+                &project,
+                &group.project_path,
+                group.revision.clone(),
+                &group.display_path,
+                cx,
+            )
+            .await
+            {
+                Ok(anchor_buffer) => anchor_buffer,
+                Err(error) => {
+                    for &index in &group.anchor_indices {
+                        results[index] = Some(Err(anyhow!("{error:#}")));
+                    }
+                    continue;
+                }
+            };
+            for &index in &group.anchor_indices {
+                results[index] = Some(
+                    anchors[index]
+                        .to_contextual_anchor(&anchor_buffer.snapshot, &group.display_path)
+                        .and_then(|anchor| {
+                            let token_match = anchor_buffer.resolve_unique(
+                                &anchor,
+                                &group.display_path,
+                                None,
+                                cx,
+                            )?;
+                            Ok(ResolvedAnchor {
+                                anchor,
+                                buffer: anchor_buffer.buffer.clone(),
+                                display_path: group.display_path.clone(),
+                                range: token_match.range,
+                                range_utf16: token_match.range_utf16,
+                                span: token_match.span,
+                                span_utf16: token_match.span_utf16,
+                                unsaved: anchor_buffer.unsaved,
+                            })
+                        }),
+                );
+            }
+        }
+        results
+            .into_iter()
+            .map(|result| result.unwrap_or_else(|| Err(anyhow!("The anchor wasn't resolved."))))
+            .collect()
+    })
+}
+
+fn validate_for_resolution(
+    project: &Project,
+    anchor: &AnchorInput,
+    cx: &App,
+) -> Result<ProjectPath> {
+    anchor.validate_basic()?;
+    let project_path = resolve_project_path(project, anchor.path(), cx)?;
+    let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+    if worktree_settings.is_path_excluded(&project_path.path)
+        || worktree_settings.is_path_private(&project_path.path)
+    {
+        return Err(anyhow!(
+            "Cannot read file because its path matches the file_scan_exclusions or private_files setting: {}",
+            anchor.path()
+        ));
+    }
+    Ok(project_path)
+}
+
+/// A buffer opened by [`open_anchor_buffer`].
+pub struct AnchorBuffer {
+    pub buffer: Entity<Buffer>,
+    pub snapshot: BufferSnapshot,
+    /// Whether the buffer has edits that aren't saved to disk, which commands reading the file
+    /// won't see.
+    pub unsaved: bool,
+    /// The project's cache of resolved anchors, which buffers at a revision don't use.
+    pub cache: Option<Entity<AnchorCache>>,
+}
+
+impl AnchorBuffer {
+    /// Resolves `anchor` like [`resolve_in_buffer`], going through the project's
+    /// [`AnchorCache`] when the buffer has one.
+    pub fn resolve(
+        &self,
+        anchor: &ContextualAnchor,
+        display_path: &str,
+        syntax: Option<TokenSyntax>,
+        cx: &mut AsyncApp,
+    ) -> Result<AnchorMatch> {
+        match &self.cache {
+            Some(cache) => cache.update(cx, |cache, cx| {
+                cache.resolve(
+                    &self.buffer,
+                    &self.snapshot,
+                    anchor,
+                    display_path,
+                    syntax,
+                    cx,
+                )
+            })?,
+            None => resolve_in_buffer(anchor, &self.snapshot, display_path, syntax),
+        }
+    }
+
+    /// Like [`Self::resolve`], but also fails when the anchor is ambiguous, like
+    /// [`resolve_unique_in_buffer`].
+    pub fn resolve_unique(
+        &self,
+        anchor: &ContextualAnchor,
+        display_path: &str,
+        syntax: Option<TokenSyntax>,
+        cx: &mut AsyncApp,
+    ) -> Result<TokenMatch> {
+        match self.resolve(anchor, display_path, syntax, cx)? {
+            AnchorMatch::Unique(token_match) => Ok(token_match),
+            AnchorMatch::Ambiguous(matches) => {
+                Err(anchor.ambiguous_error(&self.snapshot, &matches, display_path))
+            }
+        }
+    }
+}
+
+/// Opens the buffer that anchors into `project_path` resolve against, and waits for it to be
+/// parsed. Without a `revision` this is the project's buffer for the file, including any unsaved
+/// edits, so anchors find what earlier edits left rather than what is on disk; a file that only
+/// exists in an unsaved buffer resolves too.
+pub async fn open_anchor_buffer(
+    project: &Entity<Project>,
+    project_path: &ProjectPath,
+    revision: Option<String>,
+    display_path: &str,
+    cx: &mut AsyncApp,
+) -> Result<AnchorBuffer> {
+    let cache = match revision {
+        Some(_) => None,
+        None => Some(cx.update(|cx| AnchorCache::for_project(project, cx))?),
+    };
+    let buffer = match revision {
+        Some(revision) => {
+            cx.update(|cx| open_buffer_at_revision(project, project_path, revision, cx))?
+                .await?
+        }
+        None => {
+            let buffer = project
+                .update(cx, |project, cx| {
+                    project.open_buffer(project_path.clone(), cx)
+                })?
+                .await?;
+            if buffer.read_with(cx, |buffer, _| {
+                buffer.file().is_none_or(|file| !file.disk_state().exists()) && !buffer.is_dirty()
+            })? {
+                anyhow::bail!("{display_path} not found");
+            }
+            buffer
+        }
+    };
+    buffer
+        .read_with(cx, |buffer, _| buffer.parsing_idle())?
+        .await;
+    let (snapshot, unsaved) =
+        buffer.read_with(cx, |buffer, _| (buffer.snapshot(), buffer.is_dirty()))?;
+    Ok(AnchorBuffer {
+        buffer,
+        snapshot,
+        unsaved,
+        cache,
+    })
+}
+
+/// Tells the model that a location it was given reflects unsaved edits.
+pub fn unsaved_changes_note(display_path: &str) -> String {
+    format!(
+        "{display_path} has unsaved changes, which this location reflects. Save it before running commands that read it from disk.\n"
+    )
+}
+
+fn line_similarity(left: &str, right: &str) -> f64 {
+    let max_len = left.len().max(right.len());
+    if max_len == 0 {
+        return 1.;
+    }
+    // The length difference bounds the similarity, so most lines are ruled out without computing
+    // the edit distance.
+    let upper_bound = 1. - left.len().abs_diff(right.len()) as f64 / max_len as f64;
+    if upper_bound < MIN_NEAR_MISS_SIMILARITY {
+        return 0.;
+    }
+    strsim::normalized_levenshtein(left, right)
+}
+
+fn trim_whitespace(text: &str, range: Range<usize>) -> Range<usize> {
+    let slice = &text[range.clone()];
+    let start = range.start + (slice.len() - slice.trim_start().len());
+    let end = range.end - (slice.len() - slice.trim_end().len());
+    start..end.max(start)
+}
+
+fn is_word_character(character: char) -> bool {
+    character.is_alphanumeric() || character == '_'
+}
+
+/// Returns where a snippet starting at `offset` starts once it takes in the word or punctuation
+/// character before it, and the whitespace in between, without going before `limit`.
+fn grow_left(text: &str, offset: usize, limit: usize) -> usize {
+    let mut characters = text[limit..offset].char_indices().rev().peekable();
+    let mut start = offset;
+    while let Some((index, _)) = characters.next_if(|(_, character)| character.is_whitespace()) {
+        start = limit + index;
+    }
+    if let Some((index, character)) = characters.next() {
+        start = limit + index;
+        if is_word_character(character) {
+            while let Some((index, _)) =
+                characters.next_if(|&(_, character)| is_word_character(character))
+            {
+                start = limit + index;
+            }
+        }
+    }
+    start
+}
+
+/// Like [`grow_left`], but takes in the word or punctuation character after `offset`, without
+/// going past `limit`.
+fn grow_right(text: &str, offset: usize, limit: usize) -> usize {
+    let mut characters = text[offset..limit].char_indices().peekable();
+    let mut end = offset;
+    while let Some((index, character)) =
+        characters.next_if(|(_, character)| character.is_whitespace())
+    {
+        end = offset + index + character.len_utf8();
+    }
+    if let Some((index, character)) = characters.next() {
+        end = offset + index + character.len_utf8();
+        if is_word_character(character) {
+            while let Some((index, character)) =
+                characters.next_if(|&(_, character)| is_word_character(character))
+            {
+                end = offset + index + character.len_utf8();
+            }
+        }
+    }
+    end
+}
+
+fn occurs_once(text: &str, snippet: &str) -> bool {
+    let Some(first) = text.find(snippet) else {
+        return false;
+    };
+    let next = first + text[first..].chars().next().map_or(1, char::len_utf8);
+    !text[next..].contains(snippet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpui::TestAppContext;
+    use rand::prelude::*;
+
+    fn anchor(context: &str, token: &str, index: Option<u32>) -> ContextualAnchor {
+        ContextualAnchor {
+            path: "root/src/main.rs".into(),
+            context: context.into(),
+            token: token.into(),
+            index,
+            handle: None,
+            revision: None,
+            whole_word: false,
+            approximate_line: None,
+            case_insensitive: false,
+            unicode_normalize: false,
+            end_token: None,
+            end_index: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_basic() {
+        assert!(
+            anchor("let value = compute();", "value", None)
+                .validate_basic()
+                .is_ok()
+        );
+        assert!(
+            anchor("let value = compute();", "", None)
+                .validate_basic()
+                .is_err()
+        );
+        assert!(anchor("", "value", None).validate_basic().is_err());
+        assert!(
+            anchor("let value = compute();", "other", None)
+                .validate_basic()
+                .is_err()
+        );
+
+        // Short contexts are fine until they turn out to be ambiguous.
+        assert!(anchor("} else {", "else", None).validate_basic().is_ok());
+        assert!(anchor("value", "value", None).validate_basic().is_ok());
+
+        let repeated = "call(foo, foo)";
+        assert_eq!(
+            anchor(repeated, "foo", None).token_occurrences_in_context(),
+            2
+        );
+        assert!(anchor(repeated, "foo", None).validate_basic().is_err());
+        assert!(anchor(repeated, "foo", Some(0)).validate_basic().is_ok());
+        assert!(anchor(repeated, "foo", Some(1)).validate_basic().is_ok());
+        assert!(anchor(repeated, "foo", Some(2)).validate_basic().is_err());
+
+        let reason = |anchor: ContextualAnchor| {
+            anchor
+                .validate_basic()
+                .unwrap_err()
+                .downcast::<AnchorResolutionError>()
+                .ok()
+        };
+        assert_eq!(
+            reason(anchor(repeated, "foo", None)),
+            Some(AnchorResolutionError::AmbiguousToken { count: 2 })
+        );
+        assert_eq!(
+            reason(anchor(repeated, "foo", Some(2))),
+            Some(AnchorResolutionError::IndexOutOfRange { index: 2, count: 2 })
+        );
+        assert_eq!(
+            reason(anchor("let value = compute();", "other", None)),
+            Some(AnchorResolutionError::TokenNotInContext)
+        );
+    }
+
+    #[test]
+    fn test_token_offset_in_context() {
+        assert_eq!(
+            anchor("foo(foo)", "foo", None).token_offset_in_context(),
+            Some(0)
+        );
+        assert_eq!(
+            anchor("foo(foo)", "foo", Some(1)).token_offset_in_context(),
+            Some(4)
+        );
+        assert_eq!(
+            anchor("foo(foo)", "foo", Some(2)).token_offset_in_context(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_expand_to_unique() {
+        let text = "fn a() {\n    let x = value;\n}\nfn b() {\n    let x = value;\n}\n";
+        let ambiguous = anchor("let x = value;", "value", None);
+        let second_match = text.rfind("let x = value;").unwrap();
+
+        let expanded = ambiguous.expand_to_unique(text, second_match).unwrap();
+        assert_eq!(expanded.context, "fn b() {\n    let x = value;\n}");
+        assert_eq!(expanded.index, None);
+        assert_eq!(
+            text.find(&expanded.context).unwrap() + expanded.token_offset_in_context().unwrap(),
+            second_match + "let x = ".len()
+        );
+
+        assert!(ambiguous.expand_to_unique(text, second_match + 1).is_none());
+    }
+
+    #[gpui::test]
+    fn test_anchor_for_range(cx: &mut App) {
+        let text = "fn a() {\n    let x = value;\n}\nfn b() {\n    let x = value;\n}\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx));
+        let snapshot = buffer.read(cx).snapshot();
+        let path = "root/src/main.rs".to_string();
+
+        let second_value = text.rfind("value").unwrap();
+        let second_anchor = ContextualAnchor::for_range(
+            path.clone(),
+            &snapshot,
+            second_value..second_value + "value".len(),
+        )
+        .unwrap();
+        assert_eq!(
+            second_anchor,
+            anchor("fn b() {\n    let x = value;\n}", "value", None)
+        );
+        assert_eq!(
+            second_anchor.candidate_token_offsets(text),
+            vec![second_value]
+        );
+
+        // `fn a() {` alone is too short for an anchor handed back to the model.
+        let a_anchor = ContextualAnchor::for_range(path.clone(), &snapshot, 3..4).unwrap();
+        assert_eq!(a_anchor.context, "fn a() {\n    let x = value;");
+        assert_eq!(a_anchor.candidate_token_offsets(text), vec![3]);
+
+        assert_eq!(ContextualAnchor::for_range(path, &snapshot, 3..3), None);
+    }
+
+    #[gpui::test]
+    fn test_minimize(cx: &mut App) {
+        let text = "fn a() {\n    let total = compute(1, 2);\n    report(total);\n}\nfn b() {\n    let other = compute(3, 4);\n}\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx));
+        let snapshot = buffer.read(cx).snapshot();
+        let compute = text.find("compute").unwrap();
+
+        let long = anchor(
+            "fn a() {\n    let total = compute(1, 2);\n    report(total);",
+            "compute",
+            None,
+        );
+        let minimized = long.minimize(&snapshot, compute).unwrap();
+        assert_eq!(minimized.context, "total = compute(1");
+        assert_eq!(minimized.candidate_token_offsets(text), vec![compute]);
+
+        // A span keeps covering the same text.
+        let span = ContextualAnchor {
+            end_token: Some("report".into()),
+            ..anchor(
+                "let total = compute(1, 2);\n    report(total);",
+                "compute",
+                None,
+            )
+        };
+        let minimized = span.minimize(&snapshot, compute).unwrap();
+        assert_eq!(minimized.context, "compute(1, 2);\n    report");
+        assert_eq!(
+            minimized.span_range(&snapshot, compute),
+            span.span_range(&snapshot, compute)
+        );
+    }
+
+    #[gpui::test]
+    fn test_anchor_revalidation(cx: &mut App) {
+        let text = "fn main() {\n    let total = compute(a, b);\n    report(total);\n}\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx));
+        let anchor = anchor("let total = compute(a, b);", "compute", None);
+        let path = "root/src/main.rs";
+        let snapshot = buffer.read(cx).snapshot();
+        let token_offset = anchor.resolve_unique_token_offset(&snapshot, path).unwrap();
+        let mut resolution = AnchorResolution::new(anchor, &snapshot, token_offset);
+
+        // Edits elsewhere shift the token without invalidating it.
+        buffer.update(cx, |buffer, cx| {
+            buffer.edit([(0..0, "// header\n")], None, cx);
+        });
+        let snapshot = buffer.read(cx).snapshot();
+        let compute_offset = snapshot.text().find("compute").unwrap();
+        assert_eq!(
+            resolution.revalidate(&snapshot, path).unwrap(),
+            AnchorRevalidation::Unchanged {
+                range: compute_offset..compute_offset + "compute".len()
+            }
+        );
+
+        // Moving the line loses the original position, so the context is searched for again.
+        buffer.update(cx, |buffer, cx| {
+            buffer.set_text(
+                "fn main() {\n    report(total);\n    let total = compute(a, b);\n}\n",
+                cx,
+            );
+        });
+        let snapshot = buffer.read(cx).snapshot();
+        let compute_offset = snapshot.text().find("compute").unwrap();
+        assert_eq!(
+            resolution.revalidate(&snapshot, path).unwrap(),
+            AnchorRevalidation::Reresolved {
+                range: compute_offset..compute_offset + "compute".len()
+            }
+        );
+        assert_eq!(
+            resolution.revalidate(&snapshot, path).unwrap(),
+            AnchorRevalidation::Unchanged {
+                range: compute_offset..compute_offset + "compute".len()
+            }
+        );
+
+        // Once the context itself is edited, the anchor no longer resolves.
+        let total_offset = snapshot.text().find("total = ").unwrap();
+        buffer.update(cx, |buffer, cx| {
+            buffer.edit([(total_offset..total_offset + 5, "sum")], None, cx);
+        });
+        let snapshot = buffer.read(cx).snapshot();
+        assert!(resolution.revalidate(&snapshot, path).is_err());
+    }
+
+    #[test]
+    fn test_candidate_token_offsets() {
+        let text = "aaaa";
+        assert_eq!(
+            anchor("aa", "a", Some(1)).candidate_token_offsets(text),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            anchor("é🦀é", "🦀", None).candidate_token_offsets("é🦀é🦀é"),
+            vec![2, 8]
+        );
+        assert!(
+            anchor("", "a", None)
+                .candidate_token_offsets(text)
+                .is_empty()
+        );
+        assert!(
+            anchor("aa", "b", None)
+                .candidate_token_offsets(text)
+                .is_empty()
+        );
+    }
+
+    #[gpui::test]
+    fn test_short_contexts(cx: &mut App) {
+        let path = "root/src/main.rs";
+        let text =
+            "fn main() {\n    if a {\n    } else {\n    }\n    if b {\n    } else {\n    }\n}\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx));
+        let snapshot = buffer.read(cx).snapshot();
+
+        let main = anchor("fn main", "main", None);
+        let AnchorMatch::Unique(token_match) =
+            resolve_in_buffer(&main, &snapshot, path, None).unwrap()
+        else {
+            panic!("expected a unique match");
+        };
+        assert_eq!(token_match.range, 3..7);
+
+        let short = anchor("} else {", "else", None);
+        let AnchorMatch::Ambiguous(matches) =
+            resolve_in_buffer(&short, &snapshot, path, None).unwrap()
+        else {
+            panic!("expected an ambiguous match");
+        };
+        assert_eq!(matches.len(), 2);
+        let error = short.ambiguous_error(&snapshot, &matches, path).to_string();
+        assert!(error.contains(SHORT_CONTEXT_HINT), "{error}");
+        assert!(
+            short
+                .describe_ambiguous_matches(&snapshot, &matches, path)
+                .ends_with(SHORT_CONTEXT_HINT)
+        );
+
+        let long = anchor("let value = compute();", "value", None);
+        assert!(!long.is_short_context());
+    }
+
+    #[test]
+    fn test_whole_word_tokens() {
+        let mut id = anchor("let identifier = id + 1;", "id", None);
+        assert_eq!(id.token_occurrences_in_context(), 2);
+        assert!(id.validate_basic().is_err());
+        id.whole_word = true;
+        assert_eq!(id.token_occurrences_in_context(), 1);
+        assert!(id.validate_basic().is_ok());
+        let text = "fn f() {\n    let identifier = id + 1;\n}\n";
+        assert_eq!(
+            id.candidate_token_offsets(text),
+            vec![text.find("id +").unwrap()]
+        );
+
+        // At the edges of `context`, the boundary is checked against the surrounding text.
+        let mut edge = anchor("id + 1; // next", "id", None);
+        let text = "let valid = valid + 1; // next";
+        assert_eq!(
+            edge.candidate_token_offsets(text),
+            vec![text.rfind("id +").unwrap()]
+        );
+        edge.whole_word = true;
+        assert!(edge.candidate_token_offsets(text).is_empty());
+
+        let mut reference = anchor("let items = &mut values;", "&mut", None);
+        reference.whole_word = true;
+        assert_eq!(reference.token_offset_in_context(), Some(12));
+    }
+
+    #[gpui::test]
+    async fn test_validate_token_syntax(cx: &mut TestAppContext) {
+        let text = "// Calls compute twice.\nfn main() {\n    let total = compute(1) + compute(2);\n    println!(\"compute\");\n}\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx).with_language(language::rust_lang(), cx));
+        buffer
+            .read_with(cx, |buffer, _| buffer.parsing_idle())
+            .await;
+        let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot());
+        let path = "root/src/main.rs";
+
+        let call = anchor("let total = compute(1) + compute(2);", "compute", Some(0));
+        let call_offset = call.resolve_unique_token_offset(&snapshot, path).unwrap();
+        assert!(
+            call.validate_token_syntax(&snapshot, call_offset, path, TokenSyntax::Symbol)
+                .is_ok()
+        );
+
+        let comment = anchor("// Calls compute twice.", "compute", None);
+        assert_eq!(
+            comment
+                .resolve_unique_token_offset(&snapshot, path)
+                .unwrap_err()
+                .to_string(),
+            "`compute` at L1 in root/src/main.rs is inside a line comment, not code. Use a `context` where `token` is used in code."
+        );
+
+        let string = anchor("println!(\"compute\");", "compute", None);
+        let string_offset = text.find("\"compute\"").unwrap() + 1;
+        assert!(
+            string
+                .validate_token_syntax(&snapshot, string_offset, path, TokenSyntax::Code)
+                .is_err()
+        );
+
+        let keyword = anchor("let total = compute(1)", "let", None);
+        let keyword_offset = text.find("let").unwrap();
+        assert!(
+            keyword
+                .validate_token_syntax(&snapshot, keyword_offset, path, TokenSyntax::Code)
+                .is_ok()
+        );
+        assert!(
+            keyword
+                .validate_token_syntax(&snapshot, keyword_offset, path, TokenSyntax::Symbol)
+                .is_err()
+        );
+    }
+
+    #[gpui::test]
+    async fn test_resolve_in_buffer(cx: &mut TestAppContext) {
+        let path = "root/src/main.rs";
+
+        // Without a syntax tree the snippet is the two lines either side of the token.
+        let text = "one\ntwo\nthree\nlet café = compute(1);\nfour\nfive\nsix\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx));
+        let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot());
+        let call = anchor("let café = compute(1);", "compute", None);
+        let AnchorMatch::Unique(token_match) =
+            resolve_in_buffer(&call, &snapshot, path, None).unwrap()
+        else {
+            panic!("expected a unique match");
+        };
+        let compute_offset = text.find("compute").unwrap();
+        assert_eq!(
+            token_match.range,
+            compute_offset..compute_offset + "compute".len()
+        );
+        // `é` is two bytes but one UTF-16 code unit.
+        assert_eq!(
+            token_match.range_utf16,
+            PointUtf16::new(3, 11)..PointUtf16::new(3, 18)
+        );
+        assert_eq!(
+            token_match.snippet(&snapshot),
+            "two\nthree\nlet café = compute(1);\nfour\nfive"
+        );
+
+        let missing = anchor("let café = missing(1);", "missing", None);
+        assert!(
+            resolve_in_buffer(&missing, &snapshot, path, None)
+                .unwrap_err()
+                .to_string()
+                .starts_with("context/token not found in root/src/main.rs")
+        );
+
+        let text = "fn a() {\n    let x = value;\n}\n\nfn b() {\n    let x = value;\n}\n\nfn c() -> u32 {\n    42\n}\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx).with_language(language::rust_lang(), cx));
+        buffer
+            .read_with(cx, |buffer, _| buffer.parsing_idle())
+            .await;
+        let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot());
+
+        let value = anchor("let x = value;", "value", None);
+        let AnchorMatch::Ambiguous(matches) =
+            resolve_in_buffer(&value, &snapshot, path, Some(TokenSyntax::Symbol)).unwrap()
+        else {
+            panic!("expected an ambiguous match");
+        };
+        assert_eq!(
+            matches
+                .iter()
+                .map(|token_match| token_match.range_utf16.start)
+                .collect::<Vec<_>>(),
+            vec![PointUtf16::new(1, 12), PointUtf16::new(5, 12)]
+        );
+        assert_eq!(
+            resolve_unique_in_buffer(&value, &snapshot, path, None)
+                .unwrap_err()
+                .to_string(),
+            "Ambiguous context: found 2 matches in root/src/main.rs.\nL2: suggested context \"fn a() {\\n    let x = value;\\n}\"\nL6: suggested context \"fn b() {\\n    let x = value;\\n}\""
+        );
+
+        let literal = anchor("fn c() -> u32 {\n    42", "42", None);
+        assert!(resolve_unique_in_buffer(&literal, &snapshot, path, None).is_ok());
+        assert!(
+            resolve_unique_in_buffer(&literal, &snapshot, path, Some(TokenSyntax::Symbol)).is_err()
+        );
+    }
+
+    #[gpui::test]
+    fn test_approximate_line(cx: &mut App) {
+        let text = "fn a() {\n    let x = value;\n}\n\nfn b() {\n    let x = value;\n}\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx));
+        let snapshot = buffer.read(cx).snapshot();
+        let path = "root/src/main.rs";
+        let mut value = anchor("let x = value;", "value", None);
+
+        value.approximate_line = Some(8);
+        let token_offset = value.resolve_unique_token_offset(&snapshot, path).unwrap();
+        assert_eq!(snapshot.offset_to_point(token_offset).row, 5);
+
+        value.approximate_line = Some(1);
+        let token_offset = value.resolve_unique_token_offset(&snapshot, path).unwrap();
+        assert_eq!(snapshot.offset_to_point(token_offset).row, 1);
+
+        // Line 4 is as near to one match as to the other.
+        value.approximate_line = Some(4);
+        assert!(matches!(
+            resolve_in_buffer(&value, &snapshot, path, None).unwrap(),
+            AnchorMatch::Ambiguous(matches) if matches.len() == 2
+        ));
+
+        value.approximate_line = Some(0);
+        assert!(value.validate_basic().is_err());
+    }
+
+    #[gpui::test]
+    fn test_case_insensitive_and_unicode_normalized_matching(cx: &mut App) {
+        let path = "root/src/main.rs";
+        let text = "fn main() {\n    let config = Config::new();\n}\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx));
+        let snapshot = buffer.read(cx).snapshot();
+
+        let mut shouting = anchor("let CONFIG = CONFIG::new();", "config", Some(0));
+        assert!(shouting.validate_basic().is_err());
+        shouting.case_insensitive = true;
+        assert!(shouting.validate_basic().is_ok());
+        let token_offset = shouting
+            .resolve_unique_token_offset(&snapshot, path)
+            .unwrap();
+        assert_eq!(token_offset, text.find("config").unwrap());
+        assert_eq!(
+            shouting.token_range(&snapshot, token_offset),
+            token_offset..token_offset + "config".len()
+        );
+
+        // `é` precomposed in the file, but decomposed into `e` and a combining accent in the
+        // anchor.
+        let text = "fn main() {\n    let caf\u{e9} = 1;\n}\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx));
+        let snapshot = buffer.read(cx).snapshot();
+        let mut decomposed = anchor("let cafe\u{301} = 1;", "cafe\u{301}", None);
+        assert!(
+            decomposed
+                .resolve_unique_token_offset(&snapshot, path)
+                .is_err()
+        );
+        decomposed.unicode_normalize = true;
+        let token_offset = decomposed
+            .resolve_unique_token_offset(&snapshot, path)
+            .unwrap();
+        assert_eq!(token_offset, text.find("caf").unwrap());
+        assert_eq!(
+            decomposed.token_range(&snapshot, token_offset),
+            token_offset..token_offset + "caf\u{e9}".len()
+        );
+
+        // And the other way around.
+        let text = "fn main() {\n    let Cafe\u{301} = 1;\n}\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx));
+        let snapshot = buffer.read(cx).snapshot();
+        let mut precomposed = anchor("let caf\u{e9} = 1;", "caf\u{e9}", None);
+        precomposed.unicode_normalize = true;
+        precomposed.case_insensitive = true;
+        let token_offset = precomposed
+            .resolve_unique_token_offset(&snapshot, path)
+            .unwrap();
+        assert_eq!(
+            precomposed.token_range(&snapshot, token_offset),
+            token_offset..token_offset + "Cafe\u{301}".len()
+        );
+    }
+
+    #[gpui::test]
+    fn test_ambiguous_folded_matches(cx: &mut App) {
+        let path = "root/src/main.rs";
+        // The first match starts fewer bytes into the buffer than the token is into `context`.
+        let text = "caf\u{e9} = 1;\ncaf\u{e9} = 1;\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx));
+        let snapshot = buffer.read(cx).snapshot();
+        let mut decomposed = anchor("cafe\u{301} = 1;", "1", None);
+        decomposed.unicode_normalize = true;
+        let AnchorMatch::Ambiguous(matches) =
+            resolve_in_buffer(&decomposed, &snapshot, path, None).unwrap()
+        else {
+            panic!("expected an ambiguous match");
+        };
+        assert_eq!(matches.len(), 2);
+        let error = decomposed
+            .ambiguous_error(&snapshot, &matches, path)
+            .to_string();
+        assert!(
+            error.starts_with("Ambiguous context: found 2 matches"),
+            "{error}"
+        );
+        assert!(!error.contains("suggested context"), "{error}");
+        let listing = decomposed.describe_ambiguous_matches(&snapshot, &matches, path);
+        assert!(listing.contains("[1] L2:"), "{listing}");
+    }
+
+    #[gpui::test]
+    async fn test_span_anchors(cx: &mut TestAppContext) {
+        let path = "root/src/main.rs";
+        let text = "fn a() {\n    if x { y(); }\n}\nfn b() {}\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx).with_language(language::rust_lang(), cx));
+        buffer
+            .read_with(cx, |buffer, _| buffer.parsing_idle())
+            .await;
+        let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot());
+        let function = "fn a() {\n    if x { y(); }\n}";
+
+        let mut span = anchor(function, "fn", None);
+        span.end_token = Some("}".into());
+        assert!(span.validate_basic().is_ok());
+        let AnchorMatch::Unique(token_match) =
+            resolve_in_buffer(&span, &snapshot, path, None).unwrap()
+        else {
+            panic!("expected a unique match");
+        };
+        assert_eq!(token_match.range, 0..2);
+        assert_eq!(token_match.span, 0..function.len());
+        assert_eq!(
+            token_match.span_utf16,
+            PointUtf16::new(0, 0)..PointUtf16::new(2, 1)
+        );
+
+        span.end_index = Some(0);
+        let inner_end = function.find("}").unwrap() + 1;
+        assert_eq!(span.span_range(&snapshot, 0), 0..inner_end);
+
+        let mut condition = anchor(function, "if", None);
+        condition.end_token = Some("{".into());
+        condition.end_index = Some(0);
+        assert!(condition.validate_basic().is_err());
+        condition.end_index = Some(1);
+        let condition_start = function.find("if").unwrap();
+        assert_eq!(
+            condition.span_range(&snapshot, condition_start),
+            condition_start..function.find("{ y").unwrap() + 1
+        );
+        condition.end_index = Some(2);
+        assert!(condition.validate_basic().is_err());
+        condition.end_token = Some(String::new());
+        assert!(condition.validate_basic().is_err());
+        condition.end_token = None;
+        assert!(condition.validate_basic().is_err());
+
+        let token = anchor(function, "fn", None);
+        assert_eq!(token.span_range(&snapshot, 0), 0..2);
+        // Only a span may start at a keyword, since a language server can't resolve one.
+        assert!(!token.syntax_confirms_token(&snapshot, 0));
+        assert!(span.syntax_confirms_token(&snapshot, 0));
+    }
+
+    #[test]
+    fn test_context_match_strategies() {
+        let anchor = anchor("let total = compute(a, b);", "compute", None);
+
+        let rewrapped = "fn main() {\n    let total =\n        compute(a,  b);\n}\n";
+        let compute_offset = rewrapped.find("compute").unwrap();
+        assert!(
+            anchor
+                .candidate_token_offsets_with_strategy(rewrapped, ContextMatchStrategy::Exact)
+                .is_empty()
+        );
+        assert_eq!(
+            anchor.candidate_token_offsets_with_strategy(
+                rewrapped,
+                ContextMatchStrategy::WhitespaceNormalized
+            ),
+            vec![compute_offset]
+        );
+
+        let edited = "fn main() {\n    let total = compute(a, b)?;\n}\n";
+        let compute_offset = edited.find("compute").unwrap();
+        assert!(
+            anchor
+                .candidate_token_offsets_with_strategy(
+                    edited,
+                    ContextMatchStrategy::WhitespaceNormalized
+                )
+                .is_empty()
+        );
+        assert_eq!(
+            anchor.candidate_token_offsets_with_strategy(edited, ContextMatchStrategy::Fuzzy),
+            vec![compute_offset]
+        );
+    }
+
+    #[test]
+    fn test_near_misses() {
+        let anchor = anchor("let total = compute(a, b);", "compute", None);
+        let text = "fn main() {\n    let totals = compute_all(a, b);\n    let total = other(a);\n}\nfn unrelated_function_name() {}\n";
+
+        let near_misses = anchor.near_misses(text, 3);
+        assert_eq!(
+            near_misses
+                .iter()
+                .map(|near_miss| (near_miss.rows.clone(), near_miss.text.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (1..2, "let totals = compute_all(a, b);"),
+                (2..3, "let total = other(a);")
+            ]
+        );
+        assert_eq!(
+            anchor.not_found_error(text, "root/src/main.rs").to_string(),
+            "context/token not found in root/src/main.rs. The most similar regions are:\nL2 (similarity 0.84): \"let totals = compute_all(a, b);\"\nL3 (similarity 0.65): \"let total = other(a);\""
+        );
+        assert_eq!(
+            anchor
+                .not_found_error(text, "root/src/main.rs")
+                .downcast_ref::<AnchorResolutionError>()
+                .map(|error| serde_json::to_value(error).unwrap()),
+            Some(serde_json::json!({ "kind": "no_context_match", "nearest_lines": [2, 3] }))
+        );
+
+        assert_eq!(
+            anchor
+                .not_found_error("}\n", "root/src/main.rs")
+                .to_string(),
+            "context/token not found in root/src/main.rs"
+        );
+    }
+
+    #[gpui::test(iterations = 1000)]
+    fn test_random_anchor_resolution(mut rng: StdRng) {
+        const FRAGMENTS: &[&str] = &[
+            "a", "foo", "foo(", ")", " ", "\n", "    ", "é", "🦀", "ab", "aa", "let ", " = ", ";",
+        ];
+        let text = random_string(FRAGMENTS, rng.random_range(0..40), &mut rng);
+
+        // Build contexts both from slices of the text, which should resolve, and from random
+        // fragments, which mostly should not.
+        let context = if !text.is_empty() && rng.random_bool(0.8) {
+            let boundaries = char_boundaries(&text);
+            let start = *boundaries.choose(&mut rng).unwrap_or(&0);
+            let end = *boundaries
+                .iter()
+                .filter(|boundary| **boundary >= start)
+                .choose(&mut rng)
+                .unwrap_or(&start);
+            text[start..end].to_string()
+        } else {
+            random_string(FRAGMENTS, rng.random_range(0..4), &mut rng)
+        };
+        let token = if !context.is_empty() && rng.random_bool(0.8) {
+            let boundaries = char_boundaries(&context);
+            let start = *boundaries.choose(&mut rng).unwrap_or(&0);
+            let end = *boundaries
+                .iter()
+                .filter(|boundary| **boundary >= start)
+                .choose(&mut rng)
+                .unwrap_or(&start);
+            context[start..end].to_string()
+        } else {
+            random_string(FRAGMENTS, rng.random_range(0..2), &mut rng)
+        };
+        let index = rng.random_bool(0.5).then(|| rng.random_range(0..4));
+        let anchor = anchor(&context, &token, index);
+
+        let is_valid = anchor.validate_basic().is_ok();
+        let token_offset_in_context = anchor.token_offset_in_context();
+        if is_valid {
+            assert!(token_offset_in_context.is_some());
+        }
+
+        for offset in anchor.candidate_token_offsets(&text) {
+            let token_end = offset + token.len();
+            assert!(token_end <= text.len());
+            assert!(text.is_char_boundary(offset));
+            assert_eq!(&text[offset..token_end], token);
+
+            let Some(token_offset_in_context) = token_offset_in_context else {
+                panic!("candidates were returned without a token offset");
+            };
+            let context_start = offset - token_offset_in_context;
+            assert_eq!(&text[context_start..context_start + context.len()], context);
+
+            if let Some(expanded) = anchor.expand_to_unique(&text, context_start) {
+                assert_eq!(expanded.candidate_token_offsets(&text), vec![offset]);
+            }
+        }
+
+        for strategy in ContextMatchStrategy::ALL {
+            for offset in anchor.candidate_token_offsets_with_strategy(&text, strategy) {
+                assert!(text.is_char_boundary(offset), "{strategy:?}");
+                assert!(text[offset..].starts_with(&token), "{strategy:?}");
+            }
+        }
+    }
+
+    fn random_string(fragments: &[&str], len: usize, rng: &mut StdRng) -> String {
+        (0..len)
+            .filter_map(|_| fragments.choose(rng).copied())
+            .collect()
+    }
+
+    fn char_boundaries(text: &str) -> Vec<usize> {
+        text.char_indices()
+            .map(|(offset, _)| offset)
+            .chain([text.len()])
+            .collect()
+    }
+}
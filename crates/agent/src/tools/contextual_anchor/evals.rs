@@ -0,0 +1,252 @@
+use super::*;
+use std::fmt::Write as _;
+use util::test::marked_text_offsets;
+
+/// A file with `ˇ` marking where `token` starts, along with the anchor a model would send for it.
+struct AnchorCase {
+    name: &'static str,
+    marked_text: &'static str,
+    context: &'static str,
+    token: &'static str,
+    index: Option<u32>,
+}
+
+/// An edit applied to a file after the model read it, but before its anchor is resolved.
+struct Mutation {
+    name: &'static str,
+    apply: fn(&str) -> String,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Tally {
+    resolved: usize,
+    ambiguous: usize,
+    wrong: usize,
+    missed: usize,
+}
+
+impl Tally {
+    fn record(&mut self, candidates: &[usize], expected: usize) {
+        match candidates {
+            [] => self.missed += 1,
+            [offset] if *offset == expected => self.resolved += 1,
+            candidates if candidates.contains(&expected) => self.ambiguous += 1,
+            _ => self.wrong += 1,
+        }
+    }
+
+    fn total(&self) -> usize {
+        self.resolved + self.ambiguous + self.wrong + self.missed
+    }
+}
+
+const CASES: &[AnchorCase] = &[
+    AnchorCase {
+        name: "call argument",
+        marked_text: indoc::indoc! {"
+            fn total(items: &[Item]) -> u32 {
+                let sum = items.iter().map(|item| item.price).sum::<u32>();
+                apply_discount(ˇsum, DISCOUNT)
+            }
+        "},
+        context: "apply_discount(sum, DISCOUNT)",
+        token: "sum",
+        index: None,
+    },
+    AnchorCase {
+        name: "repeated token",
+        marked_text: indoc::indoc! {"
+            fn area(width: f64) -> f64 {
+                let area = width * ˇwidth;
+                area
+            }
+        "},
+        context: "let area = width * width;",
+        token: "width",
+        index: Some(1),
+    },
+    AnchorCase {
+        name: "struct field",
+        marked_text: indoc::indoc! {"
+            pub struct Config {
+                pub name: String,
+                pub ˇretries: usize,
+                pub verbose: bool,
+            }
+        "},
+        context: "pub retries: usize,",
+        token: "retries",
+        index: None,
+    },
+    AnchorCase {
+        name: "duplicated line disambiguated by its function",
+        marked_text: indoc::indoc! {"
+            fn first() {
+                let value = load();
+                process(value);
+            }
+
+            fn second() {
+                let value = load();
+                process(ˇvalue);
+            }
+        "},
+        context: "fn second() {\n    let value = load();\n    process(value);",
+        token: "value",
+        index: Some(1),
+    },
+    AnchorCase {
+        name: "method chain",
+        marked_text: indoc::indoc! {"
+            fn names(users: &[User]) -> Vec<String> {
+                users
+                    .iter()
+                    .filter(|user| user.active)
+                    .map(|user| user.ˇname.clone())
+                    .collect()
+            }
+        "},
+        context: ".map(|user| user.name.clone())",
+        token: "name",
+        index: None,
+    },
+    AnchorCase {
+        name: "non-ascii string before token",
+        marked_text: indoc::indoc! {r#"
+            fn greet() {
+                let greeting = "héllo 🦀";
+                print_greeting(greeting, ˇlocale());
+            }
+        "#},
+        context: "print_greeting(greeting, locale());",
+        token: "locale",
+        index: None,
+    },
+];
+
+const MUTATIONS: &[Mutation] = &[
+    Mutation {
+        name: "unchanged",
+        apply: |text| text.to_string(),
+    },
+    Mutation {
+        name: "lines inserted above",
+        apply: |text| format!("use std::fmt;\n\n// Added by a later edit.\n{text}"),
+    },
+    Mutation {
+        name: "reindented",
+        apply: |text| {
+            text.lines()
+                .map(|line| {
+                    if line.is_empty() {
+                        "\n".to_string()
+                    } else {
+                        format!("    {line}\n")
+                    }
+                })
+                .collect()
+        },
+    },
+    Mutation {
+        name: "arguments wrapped",
+        apply: |text| text.replace(", ", ",\n        "),
+    },
+    Mutation {
+        name: "marked line edited",
+        apply: |text| {
+            text.lines()
+                .map(|line| {
+                    if line.contains('ˇ')
+                        && let Some(semicolon) = line.rfind(';')
+                    {
+                        format!("{}?{}\n", &line[..semicolon], &line[semicolon..])
+                    } else {
+                        format!("{line}\n")
+                    }
+                })
+                .collect()
+        },
+    },
+];
+
+/// Replays every case under every mutation and reports how often each strategy resolves the
+/// anchor to the expected position, so changes to the matching heuristics can be measured.
+#[test]
+fn eval_contextual_anchor_resolution() {
+    let mut tallies =
+        vec![vec![Tally::default(); MUTATIONS.len()]; ContextMatchStrategy::ALL.len()];
+    let mut failures = String::new();
+
+    for case in CASES {
+        let anchor = ContextualAnchor {
+            path: "root/src/lib.rs".into(),
+            context: case.context.into(),
+            token: case.token.into(),
+            index: case.index,
+            handle: None,
+            revision: None,
+            whole_word: false,
+            approximate_line: None,
+            case_insensitive: false,
+            unicode_normalize: false,
+            end_token: None,
+            end_index: None,
+        };
+        assert!(
+            anchor.validate_basic().is_ok(),
+            "case {:?} has an invalid anchor",
+            case.name
+        );
+
+        for (mutation_ix, mutation) in MUTATIONS.iter().enumerate() {
+            let (text, offsets) = marked_text_offsets(&(mutation.apply)(case.marked_text));
+            let &[expected] = offsets.as_slice() else {
+                panic!("case {:?} must mark exactly one position", case.name);
+            };
+
+            for (strategy_ix, strategy) in ContextMatchStrategy::ALL.iter().enumerate() {
+                let candidates = anchor.candidate_token_offsets_with_strategy(&text, *strategy);
+                tallies[strategy_ix][mutation_ix].record(&candidates, expected);
+                if *strategy == ContextMatchStrategy::Exact
+                    && mutation.name == "unchanged"
+                    && candidates != [expected]
+                {
+                    writeln!(
+                        failures,
+                        "exact match of {:?} resolved to {candidates:?}, expected {expected}",
+                        case.name
+                    )
+                    .ok();
+                }
+            }
+        }
+    }
+
+    let mut report =
+        String::from("Contextual anchor resolution (resolved/ambiguous/wrong/missed)\n");
+    for (strategy, strategy_tallies) in ContextMatchStrategy::ALL.iter().zip(&tallies) {
+        let mut overall = Tally::default();
+        writeln!(report, "\n{strategy:?}").ok();
+        for (mutation, tally) in MUTATIONS.iter().zip(strategy_tallies) {
+            writeln!(
+                report,
+                "  {:<24} {}/{}/{}/{}",
+                mutation.name, tally.resolved, tally.ambiguous, tally.wrong, tally.missed
+            )
+            .ok();
+            overall.resolved += tally.resolved;
+            overall.ambiguous += tally.ambiguous;
+            overall.wrong += tally.wrong;
+            overall.missed += tally.missed;
+        }
+        writeln!(
+            report,
+            "  accuracy: {:.1}%",
+            overall.resolved as f64 / overall.total() as f64 * 100.
+        )
+        .ok();
+    }
+    println!("{report}");
+
+    assert!(failures.is_empty(), "{failures}");
+}
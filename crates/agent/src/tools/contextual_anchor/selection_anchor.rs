@@ -0,0 +1,154 @@
+use super::{ContextualAnchor, display_project_path, trim_whitespace};
+use crate::AnchorHandles;
+use gpui::{App, Entity};
+use language::{Buffer, BufferSnapshot, CharKind, ToOffset as _};
+use project::{Project, ProjectPath};
+use std::ops::Range;
+
+/// An anchor built from the user's selection in an editor, and the handles under which it was
+/// remembered for the thread.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectionAnchor {
+    pub anchor: ContextualAnchor,
+    pub handles: AnchorHandles,
+}
+
+impl SelectionAnchor {
+    /// Tells the model what the user selected and how to pass it to tools, for inserting into the
+    /// user's message.
+    pub fn describe(&self) -> String {
+        let selected = match &self.anchor.end_token {
+            Some(end_token) => format!("`{}` … `{end_token}`", self.anchor.token),
+            None => format!("`{}`", self.anchor.token),
+        };
+        format!(
+            "The user selected {selected} in {}. {}",
+            self.anchor.path,
+            self.handles.describe()
+        )
+    }
+}
+
+/// Builds an anchor for `selection` in `buffer`, with `path` in the form tools accept. Returns
+/// `None` for buffers that aren't part of the project and for selections no anchor can describe.
+pub fn anchor_for_selection(
+    project: &Entity<Project>,
+    buffer: &Entity<Buffer>,
+    selection: Range<text::Anchor>,
+    cx: &App,
+) -> Option<ContextualAnchor> {
+    let buffer = buffer.read(cx);
+    let project_path = ProjectPath::from_file(buffer.file()?.as_ref(), cx);
+    let path = display_project_path(project.read(cx), &project_path, cx)?;
+    let snapshot = buffer.snapshot();
+    let selection = selection.start.to_offset(&snapshot)..selection.end.to_offset(&snapshot);
+    ContextualAnchor::for_selection(path, &snapshot, selection)
+}
+
+impl ContextualAnchor {
+    /// Builds an anchor for what the user selected in `snapshot`, with the shortest `context` that
+    /// is unique (see [`Self::minimize`]).
+    ///
+    /// An empty selection anchors the word under the cursor. A selection within one line anchors
+    /// the selected text as `token`. A selection spanning several lines anchors its first and last
+    /// words as `token` and `end_token`, so that the anchor's span covers the whole selection.
+    pub fn for_selection(
+        path: String,
+        snapshot: &BufferSnapshot,
+        selection: Range<usize>,
+    ) -> Option<ContextualAnchor> {
+        let text = snapshot.text();
+        let selection = trim_whitespace(&text, selection);
+        let (token, end_token) = if selection.is_empty() {
+            let (word, kind) = snapshot.surrounding_word(selection.start, None);
+            if word.is_empty() || kind != Some(CharKind::Word) {
+                return None;
+            }
+            (word, None)
+        } else if snapshot.offset_to_point(selection.start).row
+            == snapshot.offset_to_point(selection.end).row
+        {
+            (selection, None)
+        } else {
+            let selected = &text[selection.clone()];
+            let token_len = selected.find(char::is_whitespace).unwrap_or(selected.len());
+            let end_token_start = selected
+                .rfind(char::is_whitespace)
+                .map_or(0, |offset| offset + 1);
+            (
+                selection.start..selection.start + token_len,
+                Some(selection.start + end_token_start..selection.end),
+            )
+        };
+
+        let anchor = ContextualAnchor {
+            path,
+            context: text[token.start..end_token.as_ref().map_or(token.end, |end| end.end)]
+                .to_string(),
+            token: text[token.clone()].to_string(),
+            index: None,
+            handle: None,
+            revision: None,
+            whole_word: false,
+            approximate_line: None,
+            case_insensitive: false,
+            unicode_normalize: false,
+            end_token: end_token.map(|end_token| text[end_token].to_string()),
+            end_index: None,
+        };
+        anchor.minimize(snapshot, token.start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpui::{AppContext as _, TestAppContext};
+
+    #[gpui::test]
+    fn test_anchor_for_selection(cx: &mut TestAppContext) {
+        let text = "fn main() {\n    let config = load_config(path);\n    run(config);\n    let other = load_config(fallback);\n}\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx));
+        let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot());
+        let path = "root/src/main.rs";
+        let select = |selection: Range<usize>| {
+            let anchor = ContextualAnchor::for_selection(path.into(), &snapshot, selection)
+                .expect("no anchor for selection");
+            anchor.validate_basic().unwrap();
+            let token_offset = anchor.resolve_unique_token_offset(&snapshot, path).unwrap();
+            let span = anchor.span_range(&snapshot, token_offset);
+            (anchor, span)
+        };
+
+        let load_config = text.find("load_config").unwrap();
+        let (anchor, span) = select(load_config..load_config + "load_config".len());
+        assert_eq!(anchor.context, "config = load_config(path");
+        assert_eq!(anchor.token, "load_config");
+        assert_eq!(span, load_config..load_config + "load_config".len());
+
+        // An empty selection anchors the word under the cursor.
+        let run = text.find("run").unwrap();
+        let (anchor, span) = select(run + 1..run + 1);
+        assert_eq!(anchor.context, "run(config");
+        assert_eq!(span, run..run + "run".len());
+
+        // Selections spanning lines anchor their first and last words.
+        let selection_start = text.find("run").unwrap();
+        let selection_end = text.find("(fallback);").unwrap() + "(fallback);".len();
+        let (anchor, span) = select(selection_start..selection_end + 1);
+        assert_eq!(anchor.token, "run(config);");
+        assert_eq!(anchor.end_token.as_deref(), Some("load_config(fallback);"));
+        assert_eq!(span, selection_start..selection_end);
+
+        assert_eq!(
+            ContextualAnchor::for_selection(path.into(), &snapshot, 0..0)
+                .map(|anchor| anchor.token),
+            Some("fn".into())
+        );
+        let indentation = text.find("\n    let").unwrap() + 2;
+        assert_eq!(
+            ContextualAnchor::for_selection(path.into(), &snapshot, indentation..indentation),
+            None
+        );
+    }
+}
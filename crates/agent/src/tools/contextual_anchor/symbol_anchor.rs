@@ -0,0 +1,351 @@
+use super::ContextualAnchor;
+use anyhow::{Result, anyhow};
+use language::{
+    Anchor, BufferSnapshot, OffsetRangeExt as _, OutlineItem, Point, ToOffset as _, ToPoint as _,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+
+/// How many candidate symbols an error lists before it stops.
+const MAX_LISTED_SYMBOLS: usize = 12;
+
+/// Locates a symbol by the names of the items that enclose it in the file's outline, e.g.
+/// `["impl FindReferencesTool", "run"]` for the `run` method in that impl block, instead of by a
+/// snippet of its text.
+///
+/// Each element matches an outline item by its outline text (e.g. "pub fn run" or "impl Display
+/// for Config"), that text without leading modifiers (e.g. "fn run"), or its name (e.g. "run" or
+/// "Config"). The elements must name consecutive levels of the outline, ending at the symbol
+/// itself, but the path may start below the top level when the rest of it is unambiguous.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SymbolAnchor {
+    /// Project-relative path to the file containing the symbol (e.g. "src/main.rs").
+    pub path: String,
+
+    /// The names of the enclosing items, outermost first, ending with the symbol itself.
+    pub symbol_path: Vec<String>,
+}
+
+/// An anchor given either as a snippet of the file's text or as a path through its outline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum AnchorInput {
+    Symbol(SymbolAnchor),
+    Contextual(ContextualAnchor),
+}
+
+impl AnchorInput {
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Symbol(anchor) => &anchor.path,
+            Self::Contextual(anchor) => &anchor.path,
+        }
+    }
+
+    /// The git revision to resolve the anchor against. Symbol anchors always resolve against the
+    /// project's current files.
+    pub fn revision(&self) -> Option<&str> {
+        match self {
+            Self::Symbol(_) => None,
+            Self::Contextual(anchor) => anchor.revision.as_deref(),
+        }
+    }
+
+    /// A short description of what the anchor points at, for titles and results.
+    pub fn token(&self) -> String {
+        match self {
+            Self::Symbol(anchor) => anchor.describe(),
+            Self::Contextual(anchor) => anchor.token.clone(),
+        }
+    }
+
+    /// Performs the checks that don't need the file.
+    pub fn validate_basic(&self) -> Result<()> {
+        match self {
+            Self::Symbol(anchor) => anchor.validate_basic(),
+            Self::Contextual(anchor) => anchor.validate_basic(),
+        }
+    }
+
+    /// Returns the contextual anchor to resolve in `snapshot`, which is the anchor itself unless
+    /// it is a symbol anchor.
+    pub fn to_contextual_anchor(
+        &self,
+        snapshot: &BufferSnapshot,
+        display_path: &str,
+    ) -> Result<ContextualAnchor> {
+        match self {
+            Self::Symbol(anchor) => anchor.to_contextual_anchor(snapshot, display_path),
+            Self::Contextual(anchor) => Ok(anchor.clone()),
+        }
+    }
+}
+
+impl SymbolAnchor {
+    pub fn validate_basic(&self) -> Result<()> {
+        if self.path.is_empty() {
+            return Err(anyhow!("The provided `path` must not be empty."));
+        }
+        if self.symbol_path.is_empty() {
+            return Err(anyhow!(
+                "The provided `symbol_path` must name at least the symbol itself."
+            ));
+        }
+        if self
+            .symbol_path
+            .iter()
+            .any(|element| element.trim().is_empty())
+        {
+            return Err(anyhow!("The elements of `symbol_path` must not be empty."));
+        }
+        Ok(())
+    }
+
+    /// Finds the symbol in the outline of `snapshot` and returns a contextual anchor for its
+    /// name, so that it resolves, and is remembered, like any other anchor.
+    pub fn to_contextual_anchor(
+        &self,
+        snapshot: &BufferSnapshot,
+        display_path: &str,
+    ) -> Result<ContextualAnchor> {
+        let item = self.outline_item(snapshot, display_path)?;
+        self.anchor_for_item(snapshot, &item, display_path)
+    }
+
+    /// Finds the symbol in the outline of `snapshot`, with an error listing the candidates when
+    /// `symbol_path` matches no symbol or several.
+    pub fn outline_item(
+        &self,
+        snapshot: &BufferSnapshot,
+        display_path: &str,
+    ) -> Result<OutlineItem<Anchor>> {
+        self.validate_basic()?;
+        let items = snapshot.outline_items_containing(0..snapshot.len(), false, None);
+        if items.is_empty() {
+            return Err(anyhow!(
+                "{display_path} has no outline to find `{}` in. Use a `context` and `token` instead.",
+                self.describe()
+            ));
+        }
+
+        let mut ancestors: Vec<usize> = Vec::new();
+        let mut matches = Vec::new();
+        for (index, item) in items.iter().enumerate() {
+            while ancestors
+                .last()
+                .is_some_and(|&ancestor| items[ancestor].depth >= item.depth)
+            {
+                ancestors.pop();
+            }
+            ancestors.push(index);
+            if ancestors.len() >= self.symbol_path.len()
+                && ancestors[ancestors.len() - self.symbol_path.len()..]
+                    .iter()
+                    .zip(&self.symbol_path)
+                    .all(|(&ancestor, element)| item_matches(&items[ancestor], element))
+            {
+                matches.push((index, ancestors.clone()));
+            }
+        }
+
+        match matches.as_slice() {
+            [(index, _)] => Ok(items[*index].clone()),
+            [] => {
+                let mut message = format!(
+                    "No symbol `{}` in {display_path}. The top-level symbols are:",
+                    self.describe()
+                );
+                for item in items
+                    .iter()
+                    .filter(|item| item.depth == 0)
+                    .take(MAX_LISTED_SYMBOLS)
+                {
+                    write!(message, "\n- {}", item.text).ok();
+                }
+                Err(anyhow!(message))
+            }
+            matches => {
+                let mut message = format!(
+                    "`{}` matches {} symbols in {display_path}:",
+                    self.describe(),
+                    matches.len()
+                );
+                for (index, ancestors) in matches.iter().take(MAX_LISTED_SYMBOLS) {
+                    let row = items[*index].range.start.to_point(snapshot).row;
+                    let path = ancestors
+                        .iter()
+                        .map(|&ancestor| items[ancestor].text.as_str())
+                        .collect::<Vec<_>>()
+                        .join(" > ");
+                    write!(message, "\n- L{}: {path}", row + 1).ok();
+                }
+                message.push_str("\nAdd the enclosing items to `symbol_path` to select one.");
+                Err(anyhow!(message))
+            }
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        self.symbol_path.join(" > ")
+    }
+
+    /// Builds an anchor whose token is the item's name and whose context runs from the start of
+    /// the item to the end of the line with its name, extended until it is long enough.
+    fn anchor_for_item(
+        &self,
+        snapshot: &BufferSnapshot,
+        item: &OutlineItem<Anchor>,
+        display_path: &str,
+    ) -> Result<ContextualAnchor> {
+        let name = item_name(item);
+        let source_range = item.source_range_for_text.to_offset(snapshot);
+        let source = snapshot
+            .text_for_range(source_range.clone())
+            .collect::<String>();
+        let name_start = source
+            .rfind(name)
+            .map(|offset| source_range.start + offset)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Couldn't find the name of `{}` in {display_path}.",
+                    self.describe()
+                )
+            })?;
+
+        let item_start = item.range.start.to_offset(snapshot);
+        let last_row = item.range.end.to_point(snapshot).row;
+        let mut row = snapshot.offset_to_point(name_start).row;
+        loop {
+            let context_end = Point::new(row, snapshot.line_len(row));
+            let context = snapshot
+                .text_for_range(item_start..context_end.to_offset(snapshot))
+                .collect::<String>();
+            let name_offset_in_context = name_start - item_start;
+            let mut anchor = ContextualAnchor {
+                path: self.path.clone(),
+                context,
+                token: name.to_string(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: true,
+                approximate_line: Some(snapshot.offset_to_point(name_start).row + 1),
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            };
+            let index = anchor
+                .token_matches(&anchor.context)
+                .into_iter()
+                .position(|offset| offset == name_offset_in_context);
+            anchor.index = index.filter(|&index| index > 0).map(|index| index as u32);
+            if anchor.validate_basic().is_ok() || row >= last_row {
+                return Ok(anchor);
+            }
+            row += 1;
+        }
+    }
+}
+
+/// Returns the item's name: the last name the outline query captured, e.g. the type rather than
+/// the trait of an `impl Trait for Type` block.
+fn item_name(item: &OutlineItem<Anchor>) -> &str {
+    item.name_ranges
+        .last()
+        .and_then(|range| item.text.get(range.clone()))
+        .map(str::trim)
+        .unwrap_or(item.text.as_str())
+}
+
+fn item_matches(item: &OutlineItem<Anchor>, element: &str) -> bool {
+    let element = element.split_whitespace().collect::<Vec<_>>().join(" ");
+    let text = item.text.split_whitespace().collect::<Vec<_>>().join(" ");
+    text == element
+        || text
+            .strip_suffix(element.as_str())
+            .is_some_and(|prefix| prefix.ends_with(' '))
+        || item_name(item) == element
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpui::{AppContext as _, TestAppContext};
+    use language::Buffer;
+
+    #[gpui::test]
+    async fn test_symbol_anchor(cx: &mut TestAppContext) {
+        let text = "struct Config {\n    value: u32,\n}\n\nimpl Config {\n    pub fn run(&self) -> u32 {\n        self.value\n    }\n}\n\nimpl Display for Config {\n    fn fmt(&self) {}\n}\n\nfn main() {}\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx).with_language(language::rust_lang(), cx));
+        buffer
+            .read_with(cx, |buffer, _| buffer.parsing_idle())
+            .await;
+        let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot());
+        let path = "root/src/main.rs";
+        let symbol = |symbol_path: &[&str]| SymbolAnchor {
+            path: path.into(),
+            symbol_path: symbol_path
+                .iter()
+                .map(|element| element.to_string())
+                .collect(),
+        };
+
+        let method = symbol(&["impl Config", "run"])
+            .to_contextual_anchor(&snapshot, path)
+            .unwrap();
+        assert_eq!(method.context, "pub fn run(&self) -> u32 {");
+        assert_eq!(method.token, "run");
+        let token_offset = method.resolve_unique_token_offset(&snapshot, path).unwrap();
+        assert_eq!(token_offset, text.find("run(&self)").unwrap());
+
+        let function = symbol(&["fn main"])
+            .to_contextual_anchor(&snapshot, path)
+            .unwrap();
+        assert_eq!(function.context, "fn main() {}");
+        assert_eq!(function.approximate_line, Some(15));
+
+        let fmt = symbol(&["impl Display for Config", "fmt"])
+            .to_contextual_anchor(&snapshot, path)
+            .unwrap();
+        assert_eq!(fmt.token, "fmt");
+        let field = symbol(&["Config", "value"])
+            .to_contextual_anchor(&snapshot, path)
+            .unwrap();
+        assert_eq!(field.context, "value: u32,");
+
+        let error = symbol(&["Config"])
+            .to_contextual_anchor(&snapshot, path)
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("`Config` matches 3 symbols"), "{error}");
+        let error = symbol(&["impl Config", "missing"])
+            .to_contextual_anchor(&snapshot, path)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("\n- struct Config"), "{error}");
+        assert!(symbol(&[]).validate_basic().is_err());
+    }
+
+    #[test]
+    fn test_anchor_input_forms() {
+        let symbol: AnchorInput = serde_json::from_value(serde_json::json!({
+            "path": "root/src/main.rs",
+            "symbol_path": ["impl Config", "run"],
+        }))
+        .unwrap();
+        assert!(matches!(symbol, AnchorInput::Symbol(_)));
+
+        let contextual: AnchorInput = serde_json::from_value(serde_json::json!({
+            "path": "root/src/main.rs",
+            "context": "pub fn run(&self) -> u32 {",
+            "token": "run",
+        }))
+        .unwrap();
+        assert!(matches!(contextual, AnchorInput::Contextual(_)));
+        let handle: AnchorInput =
+            serde_json::from_value(serde_json::json!({ "handle": "ref#1" })).unwrap();
+        assert!(matches!(handle, AnchorInput::Contextual(_)));
+    }
+}
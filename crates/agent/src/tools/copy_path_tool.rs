@@ -1,3 +1,4 @@
+use super::support::DRY_RUN_NOTE;
 use crate::{AgentTool, ToolCallEventStream};
 use agent_client_protocol::ToolKind;
 use anyhow::{Context as _, Result, anyhow};
@@ -75,15 +76,17 @@ impl AgentTool for CopyPathTool {
     fn run(
         self: Arc<Self>,
         input: Self::Input,
-        _event_stream: ToolCallEventStream,
+        event_stream: ToolCallEventStream,
         cx: &mut App,
     ) -> Task<Result<Self::Output>> {
+        let dry_run = event_stream.dry_run();
         let copy_task = self.project.update(cx, |project, cx| {
             match project
                 .find_project_path(&input.source_path, cx)
                 .and_then(|project_path| project.entry_for_path(&project_path, cx))
             {
                 Some(entity) => match project.find_project_path(&input.destination_path, cx) {
+                    Some(_) if dry_run => Task::ready(Ok(None)),
                     Some(project_path) => project.copy_entry(entity.id, project_path, cx),
                     None => Task::ready(Err(anyhow!(
                         "Destination path {} was outside the project.",
@@ -104,6 +107,12 @@ impl AgentTool for CopyPathTool {
                     input.source_path, input.destination_path
                 )
             })?;
+            if dry_run {
+                return Ok(format!(
+                    "{DRY_RUN_NOTE} Would copy {} to {}.",
+                    input.source_path, input.destination_path
+                ));
+            }
             Ok(format!(
                 "Copied {} to {}",
                 input.source_path, input.destination_path
@@ -7,7 +7,8 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use util::markdown::MarkdownInlineCode;
 
-use crate::{AgentTool, ToolCallEventStream};
+use super::support::DRY_RUN_NOTE;
+use crate::{AgentTool, ToolCallEventStream, ToolPermissionClass};
 
 /// Creates a new directory at the specified path within the project. Returns confirmation that the directory was created.
 ///
@@ -49,6 +50,10 @@ impl AgentTool for CreateDirectoryTool {
         ToolKind::Read
     }
 
+    fn permission_class() -> ToolPermissionClass {
+        ToolPermissionClass::WorkspaceMutating
+    }
+
     fn initial_title(
         &self,
         input: Result<Self::Input, serde_json::Value>,
@@ -64,7 +69,7 @@ impl AgentTool for CreateDirectoryTool {
     fn run(
         self: Arc<Self>,
         input: Self::Input,
-        _event_stream: ToolCallEventStream,
+        event_stream: ToolCallEventStream,
         cx: &mut App,
     ) -> Task<Result<Self::Output>> {
         let project_path = match self.project.read(cx).find_project_path(&input.path, cx) {
@@ -74,6 +79,11 @@ impl AgentTool for CreateDirectoryTool {
             }
         };
         let destination_path: Arc<str> = input.path.as_str().into();
+        if event_stream.dry_run() {
+            return Task::ready(Ok(format!(
+                "{DRY_RUN_NOTE} Would create directory {destination_path}."
+            )));
+        }
 
         let create_entry = self.project.update(cx, |project, cx| {
             project.create_entry(project_path.clone(), true, cx)
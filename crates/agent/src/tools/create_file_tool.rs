@@ -0,0 +1,237 @@
+use super::support::DRY_RUN_NOTE;
+use crate::{AgentTool, ToolCallAccess, ToolCallEventStream};
+use action_log::ActionLog;
+use agent_client_protocol::{self as acp, ToolKind};
+use anyhow::{Context as _, Result, anyhow};
+use gpui::{App, Entity, SharedString, Task};
+use project::{Project, WorktreeSettings};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::sync::Arc;
+use util::markdown::MarkdownInlineCode;
+
+/// Creates a new file at the specified path in the project, with the given contents, and returns confirmation that the file was created.
+///
+/// The file's parent directories are created as needed. This fails if something already exists at the path; use the editing tools to change existing files.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CreateFileToolInput {
+    /// The path of the new file.
+    ///
+    /// <example>
+    /// If the project has the following structure:
+    ///
+    /// - directory1/
+    /// - directory2/
+    ///
+    /// You can create a new file by providing a path of "directory1/new_file.txt"
+    /// </example>
+    pub path: String,
+    /// The contents of the new file. The file is empty when omitted.
+    #[serde(default)]
+    pub contents: String,
+}
+
+pub struct CreateFileTool {
+    project: Entity<Project>,
+    action_log: Entity<ActionLog>,
+}
+
+impl CreateFileTool {
+    pub fn new(project: Entity<Project>, action_log: Entity<ActionLog>) -> Self {
+        Self {
+            project,
+            action_log,
+        }
+    }
+}
+
+impl AgentTool for CreateFileTool {
+    type Input = CreateFileToolInput;
+    type Output = String;
+
+    fn name() -> &'static str {
+        "create_file"
+    }
+
+    fn kind() -> ToolKind {
+        ToolKind::Edit
+    }
+
+    fn call_access(&self, input: &Self::Input, cx: &App) -> ToolCallAccess {
+        ToolCallAccess::mutates_paths(self.project.read(cx), [&input.path], cx)
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        if let Ok(input) = input {
+            format!("Create file {}", MarkdownInlineCode(&input.path)).into()
+        } else {
+            "Create file".into()
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let action_log = self.action_log.clone();
+        let path = input.path;
+        let Some(project_path) = project.read(cx).find_project_path(&path, cx) else {
+            return Task::ready(Err(anyhow!(
+                "Couldn't create {path} because that path isn't in this project."
+            )));
+        };
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Task::ready(Err(anyhow!(
+                "Cannot create file because its path matches the file_scan_exclusions or private_files setting: {path}"
+            )));
+        }
+        if project.read(cx).entry_for_path(&project_path, cx).is_some() {
+            return Task::ready(Err(anyhow!(
+                "Couldn't create {path} because it already exists."
+            )));
+        }
+        if let Some(abs_path) = project.read(cx).absolute_path(&project_path, cx) {
+            event_stream.update_fields(
+                acp::ToolCallUpdateFields::new()
+                    .locations(vec![acp::ToolCallLocation::new(abs_path)]),
+            );
+        }
+        if event_stream.dry_run() {
+            return Task::ready(Ok(format!(
+                "{DRY_RUN_NOTE} Would create file {path} with {} line(s).",
+                input.contents.lines().count()
+            )));
+        }
+
+        let create_entry = project.update(cx, |project, cx| {
+            project.create_entry(project_path.clone(), false, cx)
+        });
+        cx.spawn(async move |cx| {
+            create_entry
+                .await
+                .with_context(|| format!("Creating file {path}"))?;
+            let buffer = project
+                .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+                .await?;
+            action_log.update(cx, |action_log, cx| {
+                action_log.buffer_created(buffer.clone(), cx)
+            })?;
+            if !input.contents.is_empty() {
+                buffer.update(cx, |buffer, cx| buffer.set_text(input.contents, cx))?;
+                action_log.update(cx, |action_log, cx| {
+                    action_log.buffer_edited(buffer.clone(), cx)
+                })?;
+                project
+                    .update(cx, |project, cx| project.save_buffer(buffer, cx))?
+                    .await
+                    .with_context(|| format!("Saving {path}"))?;
+            }
+            Ok(format!("Created file {path}"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, recorded_locations, run_tool};
+    use crate::{MovePathTool, MovePathToolInput};
+    use gpui::{AppContext as _, TestAppContext};
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    #[gpui::test]
+    async fn test_create_then_move_file(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project =
+            ToolTestProject::new(json!({ "src": { "main.rs": "fn main() {}\n" } }), cx).await;
+        let project = test_project.project.clone();
+        let action_log = cx.new(|_| ActionLog::new(project.clone()));
+        let tool = Arc::new(CreateFileTool::new(project.clone(), action_log.clone()));
+
+        let (output, events) = run_tool(
+            tool.clone(),
+            CreateFileToolInput {
+                path: "root/src/util/math.rs".into(),
+                contents: "pub fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n".into(),
+            },
+            cx,
+        )
+        .await;
+        assert_eq!(
+            output.expect("tool failed"),
+            "Created file root/src/util/math.rs"
+        );
+        assert_eq!(
+            recorded_locations(&events),
+            vec![acp::ToolCallLocation::new(
+                test_project.abs_path("src/util/math.rs")
+            )]
+        );
+        let buffer = project
+            .update(cx, |project, cx| {
+                project.open_local_buffer(test_project.abs_path("src/util/math.rs"), cx)
+            })
+            .await
+            .expect("failed to open created file");
+        assert_eq!(
+            buffer.read_with(cx, |buffer, _| buffer.text()),
+            "pub fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n"
+        );
+        cx.run_until_parked();
+        assert_eq!(
+            action_log.read_with(cx, |log, cx| log.changed_buffers(cx).len()),
+            1
+        );
+
+        let (output, _) = run_tool(
+            tool,
+            CreateFileToolInput {
+                path: "root/src/main.rs".into(),
+                contents: String::new(),
+            },
+            cx,
+        )
+        .await;
+        assert!(output.is_err(), "creating an existing file should fail");
+
+        let (output, events) = run_tool(
+            Arc::new(MovePathTool::new(project.clone())),
+            MovePathToolInput {
+                source_path: "root/src/util/math.rs".into(),
+                destination_path: "root/src/math.rs".into(),
+            },
+            cx,
+        )
+        .await;
+        output.expect("tool failed");
+        assert_eq!(
+            recorded_locations(&events),
+            vec![acp::ToolCallLocation::new(
+                test_project.abs_path("src/math.rs")
+            )]
+        );
+        cx.run_until_parked();
+        assert_eq!(
+            buffer.read_with(cx, |buffer, cx| buffer
+                .file()
+                .map(|file| file.full_path(cx))),
+            Some(PathBuf::from("root/src/math.rs"))
+        );
+    }
+}
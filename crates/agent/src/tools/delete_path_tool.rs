@@ -1,6 +1,7 @@
-use crate::{AgentTool, ToolCallEventStream};
+use super::support::DRY_RUN_NOTE;
+use crate::{AgentTool, ToolCallAccess, ToolCallEventStream};
 use action_log::ActionLog;
-use agent_client_protocol::ToolKind;
+use agent_client_protocol::{self as acp, ToolKind};
 use anyhow::{Context as _, Result, anyhow};
 use futures::{SinkExt, StreamExt, channel::mpsc};
 use gpui::{App, AppContext, Entity, SharedString, Task};
@@ -52,6 +53,10 @@ impl AgentTool for DeletePathTool {
         ToolKind::Delete
     }
 
+    fn call_access(&self, input: &Self::Input, cx: &App) -> ToolCallAccess {
+        ToolCallAccess::mutates_paths(self.project.read(cx), [&input.path], cx)
+    }
+
     fn initial_title(
         &self,
         input: Result<Self::Input, serde_json::Value>,
@@ -67,7 +72,7 @@ impl AgentTool for DeletePathTool {
     fn run(
         self: Arc<Self>,
         input: Self::Input,
-        _event_stream: ToolCallEventStream,
+        event_stream: ToolCallEventStream,
         cx: &mut App,
     ) -> Task<Result<Self::Output>> {
         let path = input.path;
@@ -87,6 +92,16 @@ impl AgentTool for DeletePathTool {
             )));
         };
 
+        if let Some(abs_path) = self.project.read(cx).absolute_path(&project_path, cx) {
+            event_stream.update_fields(
+                acp::ToolCallUpdateFields::new()
+                    .locations(vec![acp::ToolCallLocation::new(abs_path)]),
+            );
+        }
+        if event_stream.dry_run() {
+            return Task::ready(Ok(format!("{DRY_RUN_NOTE} Would delete {path}.")));
+        }
+
         let worktree_snapshot = worktree.read(cx).snapshot();
         let (mut paths_tx, mut paths_rx) = mpsc::channel(256);
         cx.background_spawn({
@@ -1,8 +1,10 @@
+use super::{display_project_path, truncate_results};
 use crate::{AgentTool, ToolCallEventStream};
 use agent_client_protocol as acp;
 use anyhow::{Result, anyhow};
+use collections::BTreeMap;
 use gpui::{App, Entity, Task};
-use language::{DiagnosticSeverity, OffsetRangeExt};
+use language::{BufferSnapshot, DiagnosticSeverity, OffsetRangeExt, Point};
 use project::Project;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -10,12 +12,20 @@ use std::{fmt::Write, sync::Arc};
 use ui::SharedString;
 use util::markdown::MarkdownInlineCode;
 
+/// Project-wide diagnostics are listed in full for this many files, and only counted for the
+/// rest, so that a broken build doesn't flood the context.
+const MAX_DETAILED_FILES: usize = 10;
+
 /// Get errors and warnings for the project or a specific file.
 ///
 /// This tool can be invoked after a series of edits to determine if further edits are necessary, or if the user asks to fix errors or warnings in their codebase.
 ///
+/// Errors and warnings are grouped by severity, each with its line number and the source line it
+/// refers to.
+///
 /// When a path is provided, shows all diagnostics for that specific file.
-/// When no path is provided, shows a summary of error and warning counts for all files in the project.
+/// When no path is provided, shows the error and warning counts for every file in the project,
+/// along with the diagnostics of the first few of them.
 ///
 /// <example>
 /// To get diagnostics for a specific file:
@@ -33,7 +43,7 @@ use util::markdown::MarkdownInlineCode;
 /// </guidelines>
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct DiagnosticsToolInput {
-    /// The path to get diagnostics for. If not provided, returns a project-wide summary.
+    /// The path to get diagnostics for. If not provided, returns diagnostics for the whole project.
     ///
     /// This path should never be absolute, and the first component
     /// of the path should always be a root directory in a project.
@@ -89,7 +99,7 @@ impl AgentTool for DiagnosticsTool {
     fn run(
         self: Arc<Self>,
         input: Self::Input,
-        _event_stream: ToolCallEventStream,
+        event_stream: ToolCallEventStream,
         cx: &mut App,
     ) -> Task<Result<Self::Output>> {
         match input.path {
@@ -103,63 +113,208 @@ impl AgentTool for DiagnosticsTool {
                     .update(cx, |project, cx| project.open_buffer(project_path, cx));
 
                 cx.spawn(async move |cx| {
-                    let mut output = String::new();
                     let buffer = buffer.await?;
                     let snapshot = buffer.read_with(cx, |buffer, _cx| buffer.snapshot())?;
 
-                    for (_, group) in snapshot.diagnostic_groups(None) {
-                        let entry = &group.entries[group.primary_ix];
-                        let range = entry.range.to_point(&snapshot);
-                        let severity = match entry.diagnostic.severity {
-                            DiagnosticSeverity::ERROR => "error",
-                            DiagnosticSeverity::WARNING => "warning",
-                            _ => continue,
-                        };
-
-                        writeln!(
-                            output,
-                            "{} at line {}: {}",
-                            severity,
-                            range.start.row + 1,
-                            entry.diagnostic.message
-                        )?;
-                    }
-
-                    if output.is_empty() {
+                    let diagnostics = render_diagnostics(&snapshot)?;
+                    if diagnostics.is_empty() {
                         Ok("File doesn't have errors or warnings!".to_string())
                     } else {
-                        Ok(output)
+                        Ok(
+                            truncate_results(diagnostics, event_stream.token_budget(), cx)
+                                .await
+                                .text,
+                        )
                     }
                 })
             }
             _ => {
                 let project = self.project.read(cx);
-                let mut output = String::new();
-                let mut has_diagnostics = false;
-
+                // Each language server reports its own summary, so a file can appear more than once.
+                let mut summaries = BTreeMap::default();
                 for (project_path, _, summary) in project.diagnostic_summaries(true, cx) {
-                    if summary.error_count > 0 || summary.warning_count > 0 {
-                        let Some(worktree) = project.worktree_for_id(project_path.worktree_id, cx)
-                        else {
-                            continue;
-                        };
-
-                        has_diagnostics = true;
-                        output.push_str(&format!(
-                            "{}: {} error(s), {} warning(s)\n",
-                            worktree.read(cx).absolutize(&project_path.path).display(),
-                            summary.error_count,
-                            summary.warning_count
-                        ));
+                    if summary.error_count == 0 && summary.warning_count == 0 {
+                        continue;
                     }
+                    let Some(path) = display_project_path(project, &project_path, cx) else {
+                        continue;
+                    };
+                    let (_, error_count, warning_count) =
+                        summaries.entry(path).or_insert((project_path, 0, 0));
+                    *error_count += summary.error_count;
+                    *warning_count += summary.warning_count;
                 }
 
-                if has_diagnostics {
-                    Task::ready(Ok(output))
-                } else {
-                    Task::ready(Ok("No errors or warnings found in the project.".into()))
+                if summaries.is_empty() {
+                    return Task::ready(Ok("No errors or warnings found in the project.".into()));
                 }
+
+                let project = self.project.clone();
+                cx.spawn(async move |cx| {
+                    let file_count = summaries.len();
+                    let mut results = Vec::new();
+                    for (index, (path, (project_path, error_count, warning_count))) in
+                        summaries.into_iter().enumerate()
+                    {
+                        results.push(format!(
+                            "# {path}: {error_count} error(s), {warning_count} warning(s)\n\n"
+                        ));
+                        if index < MAX_DETAILED_FILES {
+                            let buffer = project
+                                .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+                                .await?;
+                            let snapshot = buffer.read_with(cx, |buffer, _cx| buffer.snapshot())?;
+                            results.extend(render_diagnostics(&snapshot)?);
+                        }
+                    }
+                    let mut output = truncate_results(results, event_stream.token_budget(), cx)
+                        .await
+                        .text;
+                    if file_count > MAX_DETAILED_FILES {
+                        writeln!(
+                            output,
+                            "Only the first {MAX_DETAILED_FILES} files are shown in detail. Pass a file's path to see its diagnostics."
+                        )?;
+                    }
+                    Ok(output)
+                })
             }
         }
     }
 }
+
+/// Writes the errors and warnings in `snapshot` under a heading for each severity, with the source
+/// line each one starts on. Writes nothing when there are none.
+/// Renders each of the buffer's errors and warnings on its own, so that they can be truncated one
+/// at a time.
+fn render_diagnostics(snapshot: &BufferSnapshot) -> Result<Vec<String>> {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    for (_, group) in snapshot.diagnostic_groups(None) {
+        let entry = &group.entries[group.primary_ix];
+        let entries = match entry.diagnostic.severity {
+            DiagnosticSeverity::ERROR => &mut errors,
+            DiagnosticSeverity::WARNING => &mut warnings,
+            _ => continue,
+        };
+        entries.push((
+            entry.range.to_point(snapshot).start.row,
+            entry.diagnostic.message.clone(),
+        ));
+    }
+
+    let mut rendered = Vec::new();
+    for (title, mut entries) in [("Errors", errors), ("Warnings", warnings)] {
+        if entries.is_empty() {
+            continue;
+        }
+        entries.sort_by_key(|(row, _)| *row);
+        for (index, (row, message)) in entries.into_iter().enumerate() {
+            let mut output = String::new();
+            if index == 0 {
+                writeln!(output, "## {title}\n")?;
+            }
+            writeln!(
+                output,
+                "- Line {}: {}",
+                row + 1,
+                message.trim().replace('\n', "\n  ")
+            )?;
+            let line = snapshot
+                .text_for_range(Point::new(row, 0)..Point::new(row, snapshot.line_len(row)))
+                .collect::<String>();
+            let line = line.trim();
+            if !line.is_empty() {
+                writeln!(output, "  {}", MarkdownInlineCode(line))?;
+            }
+            rendered.push(output);
+        }
+        if let Some(last) = rendered.last_mut() {
+            last.push('\n');
+        }
+    }
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, run_tool};
+    use futures::StreamExt as _;
+    use gpui::TestAppContext;
+    use serde_json::json;
+
+    #[gpui::test]
+    async fn test_diagnostics_are_grouped_by_severity(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "fn main() {\n    let unused = 1;\n    let total: u32 = \"one\";\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let mut fake_servers =
+            test_project.register_fake_rust_server(lsp::ServerCapabilities::default(), cx);
+        let (_buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/main.rs", cx).await;
+        let fake_server = fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+        let diagnostic = |line, severity, message: &str| lsp::Diagnostic {
+            range: lsp::Range::new(lsp::Position::new(line, 8), lsp::Position::new(line, 13)),
+            severity: Some(severity),
+            message: message.to_string(),
+            ..lsp::Diagnostic::default()
+        };
+        fake_server.notify::<lsp::notification::PublishDiagnostics>(
+            lsp::PublishDiagnosticsParams {
+                uri: lsp::Uri::from_file_path(test_project.abs_path("src/main.rs"))
+                    .expect("fake project paths are absolute"),
+                diagnostics: vec![
+                    diagnostic(
+                        2,
+                        lsp::DiagnosticSeverity::ERROR,
+                        "mismatched types\nexpected `u32`, found `&str`",
+                    ),
+                    diagnostic(1, lsp::DiagnosticSeverity::WARNING, "unused variable"),
+                    diagnostic(1, lsp::DiagnosticSeverity::HINT, "consider prefixing"),
+                ],
+                version: None,
+            },
+        );
+        cx.run_until_parked();
+
+        let tool = Arc::new(DiagnosticsTool::new(test_project.project.clone()));
+        let expected_diagnostics = "## Errors
+
+- Line 3: mismatched types
+  expected `u32`, found `&str`
+  `let total: u32 = \"one\";`
+
+## Warnings
+
+- Line 2: unused variable
+  `let unused = 1;`
+
+";
+
+        let (output, _) = run_tool(
+            tool.clone(),
+            DiagnosticsToolInput {
+                path: Some("root/src/main.rs".into()),
+            },
+            cx,
+        )
+        .await;
+        assert_eq!(output.expect("tool failed"), expected_diagnostics);
+
+        let (output, _) = run_tool(tool, DiagnosticsToolInput { path: None }, cx).await;
+        assert_eq!(
+            output.expect("tool failed"),
+            format!("# root/src/main.rs: 1 error(s), 1 warning(s)\n\n{expected_diagnostics}")
+        );
+    }
+}
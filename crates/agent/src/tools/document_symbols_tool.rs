@@ -0,0 +1,318 @@
+use super::{display_project_path, resolve_project_path, warm_up_language_servers};
+use crate::{AgentTool, ToolCallEventStream, ToolPreparation};
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use gpui::{App, Entity, SharedString, Task};
+use language::{PointUtf16, Unclipped};
+use language_model::LanguageModelToolResultContent;
+use project::{DocumentSymbol, Project, WorktreeSettings};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{ops::Range, sync::Arc};
+use util::markdown::MarkdownInlineCode;
+
+/// Returns the symbols a language server reports for a file, as a JSON tree with each symbol's
+/// kind, range and children.
+///
+/// Unlike the outline that `read_file` shows for large files, this comes from the language server,
+/// so it includes details such as signatures and whether a symbol is deprecated.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct DocumentSymbolsToolInput {
+    /// The relative path of the file.
+    ///
+    /// This path should never be absolute, and the first component of the path should always be a root directory in a project.
+    pub path: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentSymbolsToolOutput {
+    pub path: String,
+    pub symbols: Vec<DocumentSymbolEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentSymbolEntry {
+    pub name: String,
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deprecated: bool,
+    /// The whole symbol, including its body and documentation.
+    pub range: SymbolRange,
+    /// The symbol's name.
+    pub selection_range: SymbolRange,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<DocumentSymbolEntry>,
+}
+
+/// A 1-based, inclusive range. Columns count UTF-16 code units, as language servers do.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SymbolRange {
+    pub start_line: u32,
+    pub start_column: u32,
+    pub end_line: u32,
+    pub end_column: u32,
+}
+
+impl From<DocumentSymbolsToolOutput> for LanguageModelToolResultContent {
+    fn from(output: DocumentSymbolsToolOutput) -> Self {
+        if output.symbols.is_empty() {
+            return format!(
+                "No symbols found in {} (or language server not capable).",
+                output.path
+            )
+            .into();
+        }
+        serde_json::to_string_pretty(&output)
+            .unwrap_or_else(|error| format!("Failed to serialize the document symbols: {error}"))
+            .into()
+    }
+}
+
+pub struct DocumentSymbolsTool {
+    project: Entity<Project>,
+}
+
+impl DocumentSymbolsTool {
+    pub fn new(project: Entity<Project>) -> Self {
+        Self { project }
+    }
+}
+
+// There is no output schema, since the symbol tree is recursive and some providers require
+// schemas with every subschema inlined.
+impl AgentTool for DocumentSymbolsTool {
+    type Input = DocumentSymbolsToolInput;
+    type Output = DocumentSymbolsToolOutput;
+
+    fn name() -> &'static str {
+        "document_symbols"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Read
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => format!("List symbols in {}", MarkdownInlineCode(&input.path)).into(),
+            Err(_) => "List document symbols".into(),
+        }
+    }
+
+    fn prepare(
+        self: Arc<Self>,
+        input: Self::Input,
+        cx: &mut App,
+    ) -> Option<Task<Result<ToolPreparation>>> {
+        warm_up_language_servers(&self.project, &input.path, cx)
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        _event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let project_path = match resolve_project_path(project.read(cx), &input.path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Task::ready(Err(anyhow!(
+                "Cannot read file because its path matches the file_scan_exclusions or private_files setting: {}",
+                &input.path
+            )));
+        }
+        let display_path = display_project_path(project.read(cx), &project_path, cx)
+            .unwrap_or_else(|| input.path.clone());
+
+        cx.spawn(async move |cx| {
+            let buffer = project
+                .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+                .await?;
+            // Keep the buffer registered until the server has answered.
+            let _lsp_handle = project.update(cx, |project, cx| {
+                project.register_buffer_with_language_servers(&buffer, cx)
+            })?;
+            let symbols = project
+                .update(cx, |project, cx| project.document_symbols(&buffer, cx))?
+                .await?;
+            Ok(DocumentSymbolsToolOutput {
+                path: display_path,
+                symbols: symbols.into_iter().map(DocumentSymbolEntry::from).collect(),
+            })
+        })
+    }
+}
+
+impl From<DocumentSymbol> for DocumentSymbolEntry {
+    fn from(symbol: DocumentSymbol) -> Self {
+        Self {
+            name: symbol.name,
+            kind: symbol_kind_name(symbol.kind).to_string(),
+            detail: symbol.detail,
+            deprecated: symbol.deprecated,
+            range: SymbolRange::from(symbol.range),
+            selection_range: SymbolRange::from(symbol.selection_range),
+            children: symbol.children.into_iter().map(Self::from).collect(),
+        }
+    }
+}
+
+impl From<Range<Unclipped<PointUtf16>>> for SymbolRange {
+    fn from(range: Range<Unclipped<PointUtf16>>) -> Self {
+        Self {
+            start_line: range.start.0.row + 1,
+            start_column: range.start.0.column + 1,
+            end_line: range.end.0.row + 1,
+            end_column: range.end.0.column + 1,
+        }
+    }
+}
+
+fn symbol_kind_name(kind: lsp::SymbolKind) -> &'static str {
+    match kind {
+        lsp::SymbolKind::FILE => "file",
+        lsp::SymbolKind::MODULE => "module",
+        lsp::SymbolKind::NAMESPACE => "namespace",
+        lsp::SymbolKind::PACKAGE => "package",
+        lsp::SymbolKind::CLASS => "class",
+        lsp::SymbolKind::METHOD => "method",
+        lsp::SymbolKind::PROPERTY => "property",
+        lsp::SymbolKind::FIELD => "field",
+        lsp::SymbolKind::CONSTRUCTOR => "constructor",
+        lsp::SymbolKind::ENUM => "enum",
+        lsp::SymbolKind::INTERFACE => "interface",
+        lsp::SymbolKind::FUNCTION => "function",
+        lsp::SymbolKind::VARIABLE => "variable",
+        lsp::SymbolKind::CONSTANT => "constant",
+        lsp::SymbolKind::STRING => "string",
+        lsp::SymbolKind::NUMBER => "number",
+        lsp::SymbolKind::BOOLEAN => "boolean",
+        lsp::SymbolKind::ARRAY => "array",
+        lsp::SymbolKind::OBJECT => "object",
+        lsp::SymbolKind::KEY => "key",
+        lsp::SymbolKind::NULL => "null",
+        lsp::SymbolKind::ENUM_MEMBER => "enum_member",
+        lsp::SymbolKind::STRUCT => "struct",
+        lsp::SymbolKind::EVENT => "event",
+        lsp::SymbolKind::OPERATOR => "operator",
+        lsp::SymbolKind::TYPE_PARAMETER => "type_parameter",
+        _ => "other",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, run_tool};
+    use futures::StreamExt as _;
+    use gpui::TestAppContext;
+    use serde_json::json;
+
+    #[gpui::test]
+    async fn test_document_symbols_keep_hierarchy_and_details(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "struct Config {\n    path: String,\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let mut fake_servers = test_project.register_fake_rust_server(
+            lsp::ServerCapabilities {
+                document_symbol_provider: Some(lsp::OneOf::Left(true)),
+                ..Default::default()
+            },
+            cx,
+        );
+        let (_buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/main.rs", cx).await;
+        let fake_server = fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+        fake_server.set_request_handler::<lsp::request::DocumentSymbolRequest, _, _>(
+            |_, _| async move {
+                let range = |start: (u32, u32), end: (u32, u32)| {
+                    lsp::Range::new(
+                        lsp::Position::new(start.0, start.1),
+                        lsp::Position::new(end.0, end.1),
+                    )
+                };
+                #[allow(deprecated)]
+                let field = lsp::DocumentSymbol {
+                    name: "path".into(),
+                    detail: Some("String".into()),
+                    kind: lsp::SymbolKind::FIELD,
+                    tags: Some(vec![lsp::SymbolTag::DEPRECATED]),
+                    deprecated: None,
+                    range: range((1, 4), (1, 16)),
+                    selection_range: range((1, 4), (1, 8)),
+                    children: None,
+                };
+                #[allow(deprecated)]
+                let structure = lsp::DocumentSymbol {
+                    name: "Config".into(),
+                    detail: None,
+                    kind: lsp::SymbolKind::STRUCT,
+                    tags: None,
+                    deprecated: None,
+                    range: range((0, 0), (2, 1)),
+                    selection_range: range((0, 7), (0, 13)),
+                    children: Some(vec![field]),
+                };
+                Ok(Some(lsp::DocumentSymbolResponse::Nested(vec![structure])))
+            },
+        );
+
+        let tool = Arc::new(DocumentSymbolsTool::new(test_project.project.clone()));
+        let (output, _) = run_tool(
+            tool,
+            DocumentSymbolsToolInput {
+                path: "root/src/main.rs".into(),
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+
+        assert_eq!(
+            serde_json::to_value(&output).expect("output is serializable"),
+            json!({
+                "path": "root/src/main.rs",
+                "symbols": [{
+                    "name": "Config",
+                    "kind": "struct",
+                    "range": { "start_line": 1, "start_column": 1, "end_line": 3, "end_column": 2 },
+                    "selection_range": { "start_line": 1, "start_column": 8, "end_line": 1, "end_column": 14 },
+                    "children": [{
+                        "name": "path",
+                        "kind": "field",
+                        "detail": "String",
+                        "deprecated": true,
+                        "range": { "start_line": 2, "start_column": 5, "end_line": 2, "end_column": 17 },
+                        "selection_range": { "start_line": 2, "start_column": 5, "end_line": 2, "end_column": 9 }
+                    }]
+                }]
+            })
+        );
+    }
+}
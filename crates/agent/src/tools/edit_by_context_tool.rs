@@ -0,0 +1,461 @@
+use super::support::{DRY_RUN_NOTE, buffer_to_edit};
+use super::{AnchorMemory, ContextualAnchor, display_project_path, resolve_project_path};
+use crate::{AgentTool, ToolCallAccess, ToolCallEventStream};
+use acp_thread::Diff;
+use action_log::ActionLog;
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use gpui::{App, AppContext as _, Entity, SharedString, Task};
+use language::BufferSnapshot;
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::{Project, WorktreeSettings};
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{ops::Range, sync::Arc};
+
+/// Edits a file at an anchor: replaces the anchor's `token` or its whole `context`, or inserts
+/// text right before or after the `context`.
+///
+/// The anchor is resolved exactly as the other `_by_context` tools resolve it, so it must match
+/// one place in the file.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EditByContextToolInput {
+    #[serde(flatten)]
+    pub anchor: ContextualAnchor,
+    /// The text to put in place of the token or context, or to insert.
+    pub replacement: String,
+    pub edit_mode: EditMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EditMode {
+    /// Replace the anchor's `token`, or everything from `token` through `end_token` when the anchor
+    /// has an `end_token`.
+    ReplaceToken,
+    /// Replace the anchor's whole `context`.
+    ReplaceContext,
+    /// Insert `replacement` right before the anchor's `context`.
+    InsertBefore,
+    /// Insert `replacement` right after the anchor's `context`.
+    InsertAfter,
+}
+
+impl EditMode {
+    /// Returns the range to replace, given where `anchor`'s token was resolved to.
+    pub(crate) fn range(
+        self,
+        anchor: &ContextualAnchor,
+        snapshot: &BufferSnapshot,
+        token_offset: usize,
+    ) -> Range<usize> {
+        let context_start = token_offset - anchor.token_offset_in_context().unwrap_or_default();
+        let context_end = context_start + anchor.context.len();
+        match self {
+            Self::ReplaceToken => anchor.span_range(snapshot, token_offset),
+            Self::ReplaceContext => context_start..context_end,
+            Self::InsertBefore => context_start..context_start,
+            Self::InsertAfter => context_end..context_end,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct EditByContextToolOutput {
+    pub path: String,
+    pub edit_mode: EditMode,
+    /// The 1-based line where the edit starts.
+    pub line: u32,
+    /// A unified diff of the edit.
+    pub diff: String,
+    /// Whether the edit was only computed, without being applied.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl From<EditByContextToolOutput> for LanguageModelToolResultContent {
+    fn from(output: EditByContextToolOutput) -> Self {
+        let verb = if output.dry_run {
+            format!("{DRY_RUN_NOTE} Would edit")
+        } else {
+            "Edited".to_string()
+        };
+        format!(
+            "{verb} {} [L{}]:\n\n```diff\n{}\n```",
+            output.path, output.line, output.diff
+        )
+        .into()
+    }
+}
+
+/// Tool: edit_by_context
+pub struct EditByContextTool {
+    project: Entity<Project>,
+    action_log: Entity<ActionLog>,
+    anchor_memory: Entity<AnchorMemory>,
+}
+
+impl EditByContextTool {
+    pub fn new(
+        project: Entity<Project>,
+        action_log: Entity<ActionLog>,
+        anchor_memory: Entity<AnchorMemory>,
+    ) -> Self {
+        Self {
+            project,
+            action_log,
+            anchor_memory,
+        }
+    }
+}
+
+impl AgentTool for EditByContextTool {
+    type Input = EditByContextToolInput;
+    type Output = EditByContextToolOutput;
+
+    fn name() -> &'static str {
+        "edit_by_context"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Edit
+    }
+
+    fn call_access(&self, input: &Self::Input, cx: &App) -> ToolCallAccess {
+        match self.anchor_memory.read(cx).expand(input.anchor.clone()) {
+            Ok(anchor) => ToolCallAccess::mutates_paths(self.project.read(cx), [anchor.path], cx),
+            Err(_) => ToolCallAccess::MutatesAnything,
+        }
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn input_examples() -> Vec<Self::Input> {
+        vec![EditByContextToolInput {
+            anchor: ContextualAnchor {
+                path: "project/src/main.rs".into(),
+                context: "let config = Config::load(&path)?;".into(),
+                token: "load".into(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            replacement: "load_or_default".into(),
+            edit_mode: EditMode::ReplaceToken,
+        }]
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => {
+                let symbol = input.anchor.handle.unwrap_or(input.anchor.token);
+                match input.edit_mode {
+                    EditMode::ReplaceToken => format!("Replace `{symbol}`").into(),
+                    EditMode::ReplaceContext => {
+                        format!("Replace the code around `{symbol}`").into()
+                    }
+                    EditMode::InsertBefore => format!("Insert before `{symbol}`").into(),
+                    EditMode::InsertAfter => format!("Insert after `{symbol}`").into(),
+                }
+            }
+            Err(_) => "Edit at anchor".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let action_log = self.action_log.clone();
+
+        let anchor = match self.anchor_memory.read(cx).expand(input.anchor) {
+            Ok(anchor) => anchor,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        if let Err(error) = anchor.validate_basic() {
+            return Task::ready(Err(error));
+        }
+        if let Some(revision) = &anchor.revision {
+            return Task::ready(Err(anyhow!(
+                "Only the project's current files can be edited, not {revision}."
+            )));
+        }
+        let project_path = match resolve_project_path(project.read(cx), &anchor.path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Task::ready(Err(anyhow!(
+                "Cannot edit file because its path matches the file_scan_exclusions or private_files setting: {}",
+                &anchor.path
+            )));
+        }
+        let display_path = display_project_path(project.read(cx), &project_path, cx)
+            .unwrap_or_else(|| anchor.path.clone());
+
+        cx.spawn(async move |cx| {
+            let buffer = project
+                .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+                .await?;
+            buffer
+                .read_with(cx, |buffer, _| buffer.parsing_idle())?
+                .await;
+            let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot())?;
+            let token_offset = anchor.resolve_unique_token_offset(&snapshot, &display_path)?;
+            let range = input.edit_mode.range(&anchor, &snapshot, token_offset);
+
+            let old_text = snapshot.text();
+            action_log.update(cx, |action_log, cx| {
+                action_log.buffer_read(buffer.clone(), cx)
+            })?;
+            let dry_run = event_stream.dry_run();
+            let edited_buffer = buffer_to_edit(&buffer, &event_stream, cx)?;
+            let diff = cx.new(|cx| Diff::new(edited_buffer.clone(), cx))?;
+            event_stream.update_diff(diff.clone());
+            edited_buffer.update(cx, |buffer, cx| {
+                buffer.edit([(range.clone(), input.replacement)], None, cx)
+            })?;
+            if !dry_run {
+                action_log.update(cx, |action_log, cx| {
+                    action_log.buffer_edited(buffer.clone(), cx)
+                })?;
+            }
+            diff.update(cx, |diff, cx| diff.finalize(cx))?;
+
+            let new_text = edited_buffer.read_with(cx, |buffer, _| buffer.text())?;
+            let unified_diff = cx
+                .background_spawn(async move { language::unified_diff(&old_text, &new_text) })
+                .await;
+
+            Ok(EditByContextToolOutput {
+                path: display_path,
+                edit_mode: input.edit_mode,
+                line: snapshot.offset_to_point(range.start).row + 1,
+                diff: unified_diff,
+                dry_run,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        RecordedToolEvent, ToolTestProject, init_test, run_tool, run_tool_dry_run,
+    };
+    use gpui::TestAppContext;
+    use serde_json::json;
+
+    #[gpui::test]
+    async fn test_edit_modes(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "fn main() {\n    let total = compute(1);\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let action_log = cx.new(|_| ActionLog::new(test_project.project.clone()));
+        let tool = Arc::new(EditByContextTool::new(
+            test_project.project.clone(),
+            action_log.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let input =
+            |context: &str, token: &str, replacement: &str, edit_mode| EditByContextToolInput {
+                anchor: ContextualAnchor {
+                    path: "root/src/main.rs".into(),
+                    context: context.into(),
+                    token: token.into(),
+                    index: None,
+                    handle: None,
+                    revision: None,
+                    whole_word: false,
+                    approximate_line: None,
+                    case_insensitive: false,
+                    unicode_normalize: false,
+                    end_token: None,
+                    end_index: None,
+                },
+                replacement: replacement.into(),
+                edit_mode,
+            };
+        let buffer = test_project
+            .project
+            .update(cx, |project, cx| {
+                project.open_local_buffer(test_project.abs_path("src/main.rs"), cx)
+            })
+            .await
+            .expect("failed to open buffer");
+        let text = |cx: &mut TestAppContext| buffer.read_with(cx, |buffer, _| buffer.text());
+
+        let (output, events) = run_tool(
+            tool.clone(),
+            input(
+                "let total = compute(1);",
+                "compute",
+                "compute_sum",
+                EditMode::ReplaceToken,
+            ),
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+        assert_eq!(output.line, 2);
+        assert!(output.diff.contains("+    let total = compute_sum(1);"));
+        assert!(events.contains(&RecordedToolEvent::Diff));
+        assert_eq!(
+            text(cx),
+            "fn main() {\n    let total = compute_sum(1);\n}\n"
+        );
+
+        let (output, _) = run_tool(
+            tool.clone(),
+            input(
+                "let total = compute_sum(1);",
+                "total",
+                "\n    println!(\"{total}\");",
+                EditMode::InsertAfter,
+            ),
+            cx,
+        )
+        .await;
+        output.expect("tool failed");
+        assert_eq!(
+            text(cx),
+            "fn main() {\n    let total = compute_sum(1);\n    println!(\"{total}\");\n}\n"
+        );
+
+        let (output, _) = run_tool(
+            tool.clone(),
+            input(
+                "let total = compute_sum(1);",
+                "total",
+                "// Sum of one.\n    ",
+                EditMode::InsertBefore,
+            ),
+            cx,
+        )
+        .await;
+        output.expect("tool failed");
+
+        let (output, _) = run_tool(
+            tool,
+            input(
+                "println!(\"{total}\");",
+                "println",
+                "dbg!(total);",
+                EditMode::ReplaceContext,
+            ),
+            cx,
+        )
+        .await;
+        output.expect("tool failed");
+        assert_eq!(
+            text(cx),
+            "fn main() {\n    // Sum of one.\n    let total = compute_sum(1);\n    dbg!(total);\n}\n"
+        );
+        cx.run_until_parked();
+        assert_eq!(
+            action_log.read_with(cx, |log, cx| log.changed_buffers(cx).len()),
+            1
+        );
+    }
+
+    #[gpui::test]
+    async fn test_dry_run_leaves_buffer_unchanged(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "fn main() {\n    let total = compute(1);\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let action_log = cx.new(|_| ActionLog::new(test_project.project.clone()));
+        let tool = Arc::new(EditByContextTool::new(
+            test_project.project.clone(),
+            action_log.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let buffer = test_project
+            .project
+            .update(cx, |project, cx| {
+                project.open_local_buffer(test_project.abs_path("src/main.rs"), cx)
+            })
+            .await
+            .expect("failed to open buffer");
+
+        let (output, events) = run_tool_dry_run(
+            tool,
+            EditByContextToolInput {
+                anchor: ContextualAnchor {
+                    path: "root/src/main.rs".into(),
+                    context: "let total = compute(1);".into(),
+                    token: "compute".into(),
+                    index: None,
+                    handle: None,
+                    revision: None,
+                    whole_word: false,
+                    approximate_line: None,
+                    case_insensitive: false,
+                    unicode_normalize: false,
+                    end_token: None,
+                    end_index: None,
+                },
+                replacement: "compute_sum".into(),
+                edit_mode: EditMode::ReplaceToken,
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+        assert!(output.dry_run);
+        assert!(output.diff.contains("+    let total = compute_sum(1);"));
+        assert!(events.contains(&RecordedToolEvent::Diff));
+        let LanguageModelToolResultContent::Text(text) =
+            LanguageModelToolResultContent::from(output)
+        else {
+            panic!("expected text output");
+        };
+        assert!(
+            text.starts_with("Dry run, so nothing was changed. Would edit root/src/main.rs [L2]"),
+            "{text}"
+        );
+        assert_eq!(
+            buffer.read_with(cx, |buffer, _| buffer.text()),
+            "fn main() {\n    let total = compute(1);\n}\n"
+        );
+        cx.run_until_parked();
+        assert!(action_log.read_with(cx, |log, cx| log.changed_buffers(cx).is_empty()));
+    }
+}
@@ -1,8 +1,10 @@
+use super::support::{DRY_RUN_NOTE, buffer_to_edit};
 use crate::{
-    AgentTool, Templates, Thread, ToolCallEventStream,
+    AgentTool, BufferVersion, Templates, Thread, ToolCallAccess, ToolCallEventStream,
     edit_agent::{EditAgent, EditAgentOutput, EditAgentOutputEvent, EditFormat},
 };
 use acp_thread::Diff;
+use action_log::ActionLog;
 use agent_client_protocol::{self as acp, ToolCallLocation, ToolCallUpdateFields};
 use anyhow::{Context as _, Result, anyhow};
 use cloud_llm_client::CompletionIntent;
@@ -75,6 +77,12 @@ pub struct EditFileToolInput {
     ///
     /// When a file already exists or you just created it, prefer editing it as opposed to recreating it from scratch.
     pub mode: EditFileMode,
+    /// The version of the file that the edit was planned against, as reported by a previous tool
+    /// call. The edit is rejected if the file has changed since. Hidden from models, which can't
+    /// see versions, and meant for clients that drive tools directly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(skip)]
+    pub expected_version: Option<BufferVersion>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
@@ -104,12 +112,26 @@ pub struct EditFileToolOutput {
     diff: String,
     #[serde(alias = "raw_output")]
     edit_agent_output: EditAgentOutput,
+    /// The version of the file after the edit, which a subsequent edit can pass back as its
+    /// `expected_version`.
+    #[serde(default)]
+    version: Option<BufferVersion>,
+    /// Whether the edits were only computed, without being applied.
+    #[serde(default)]
+    dry_run: bool,
 }
 
 impl From<EditFileToolOutput> for LanguageModelToolResultContent {
     fn from(output: EditFileToolOutput) -> Self {
         if output.diff.is_empty() {
             "No edits were made.".into()
+        } else if output.dry_run {
+            format!(
+                "{DRY_RUN_NOTE} Would edit {}:\n\n```diff\n{}\n```",
+                output.input_path.display(),
+                output.diff
+            )
+            .into()
         } else {
             format!(
                 "Edited {}:\n\n```diff\n{}\n```",
@@ -208,6 +230,10 @@ impl AgentTool for EditFileTool {
         acp::ToolKind::Edit
     }
 
+    fn call_access(&self, input: &Self::Input, cx: &App) -> ToolCallAccess {
+        ToolCallAccess::mutates_paths(self.project.read(cx), [&input.path], cx)
+    }
+
     fn initial_title(
         &self,
         input: Result<Self::Input, serde_json::Value>,
@@ -289,11 +315,19 @@ impl AgentTool for EditFileTool {
             let request = request?;
             let model = model.context("No language model configured")?;
 
+            let dry_run = event_stream.dry_run();
+            // In a dry run the edit agent records its edits in a log of its own, so that they
+            // aren't offered to the user for review.
+            let edit_agent_action_log = if dry_run {
+                cx.new(|_| ActionLog::new(project.clone()))?
+            } else {
+                action_log.clone()
+            };
             let edit_format = EditFormat::from_model(model.clone())?;
             let edit_agent = EditAgent::new(
                 model,
                 project.clone(),
-                action_log.clone(),
+                edit_agent_action_log,
                 self.templates.clone(),
                 edit_format,
             );
@@ -304,6 +338,16 @@ impl AgentTool for EditFileTool {
                 })?
                 .await?;
 
+            if let Some(expected_version) = input.expected_version.as_ref()
+                && buffer.read_with(cx, |buffer, _| expected_version.is_stale(buffer))?
+            {
+                let current_version = buffer.read_with(cx, |buffer, _| BufferVersion::of(buffer))?;
+                anyhow::bail!(
+                    "{} has changed since version {expected_version} (it is now at version {current_version}). Read it again before editing it.",
+                    input.path.display()
+                );
+            }
+
             // Check if the file has been modified since the agent last read it
             if let Some(abs_path) = abs_path.as_ref() {
                 let (last_read_mtime, current_mtime, is_dirty, has_save_tool, has_restore_tool) = self.thread.update(cx, |thread, cx| {
@@ -357,6 +401,8 @@ impl AgentTool for EditFileTool {
                 }
             }
 
+            // From here on a dry run edits a branch of the buffer, which is never saved.
+            let buffer = buffer_to_edit(&buffer, &event_stream, cx)?;
             let diff = cx.new(|cx| Diff::new(buffer.clone(), cx))?;
             event_stream.update_diff(diff.clone());
             let _finalize_diff = util::defer({
@@ -441,7 +487,7 @@ impl AgentTool for EditFileTool {
 
             let edit_agent_output = output.await?;
 
-            if format_on_save_enabled {
+            if format_on_save_enabled && !dry_run {
                 action_log.update(cx, |log, cx| {
                     log.buffer_edited(buffer.clone(), cx);
                 })?;
@@ -458,26 +504,29 @@ impl AgentTool for EditFileTool {
                 format_task.await.log_err();
             }
 
-            project
-                .update(cx, |project, cx| project.save_buffer(buffer.clone(), cx))?
-                .await?;
+            if !dry_run {
+                project
+                    .update(cx, |project, cx| project.save_buffer(buffer.clone(), cx))?
+                    .await?;
 
-            action_log.update(cx, |log, cx| {
-                log.buffer_edited(buffer.clone(), cx);
-            })?;
+                action_log.update(cx, |log, cx| {
+                    log.buffer_edited(buffer.clone(), cx);
+                })?;
 
-            // Update the recorded read time after a successful edit so consecutive edits work
-            if let Some(abs_path) = abs_path.as_ref() {
-                if let Some(new_mtime) = buffer.read_with(cx, |buffer, _| {
-                    buffer.file().and_then(|file| file.disk_state().mtime())
-                })? {
-                    self.thread.update(cx, |thread, _| {
-                        thread.file_read_times.insert(abs_path.to_path_buf(), new_mtime);
-                    })?;
+                // Update the recorded read time after a successful edit so consecutive edits work
+                if let Some(abs_path) = abs_path.as_ref() {
+                    if let Some(new_mtime) = buffer.read_with(cx, |buffer, _| {
+                        buffer.file().and_then(|file| file.disk_state().mtime())
+                    })? {
+                        self.thread.update(cx, |thread, _| {
+                            thread.file_read_times.insert(abs_path.to_path_buf(), new_mtime);
+                        })?;
+                    }
                 }
             }
 
-            let new_snapshot = buffer.read_with(cx, |buffer, _cx| buffer.snapshot())?;
+            let (new_snapshot, version) =
+                buffer.read_with(cx, |buffer, _cx| (buffer.snapshot(), BufferVersion::of(buffer)))?;
             let (new_text, unified_diff) = cx
                 .background_spawn({
                     let new_snapshot = new_snapshot.clone();
@@ -523,6 +572,8 @@ impl AgentTool for EditFileTool {
                 old_text,
                 diff: unified_diff,
                 edit_agent_output,
+                version: (!dry_run).then_some(version),
+                dry_run,
             })
         })
     }
@@ -653,6 +704,7 @@ mod tests {
                     display_description: "Some edit".into(),
                     path: "root/nonexistent_file.txt".into(),
                     mode: EditFileMode::Edit,
+                    expected_version: None,
                 };
                 Arc::new(EditFileTool::new(
                     project,
@@ -669,6 +721,52 @@ mod tests {
         );
     }
 
+    #[gpui::test]
+    async fn test_edit_rejects_stale_expected_version(cx: &mut TestAppContext) {
+        init_test(cx);
+
+        let fs = project::FakeFs::new(cx.executor());
+        fs.insert_tree("/root", json!({"file.txt": "original content"}))
+            .await;
+        let project = Project::test(fs.clone(), [path!("/root").as_ref()], cx).await;
+        let language_registry = project.read_with(cx, |project, _cx| project.languages().clone());
+        let context_server_registry =
+            cx.new(|cx| ContextServerRegistry::new(project.read(cx).context_server_store(), cx));
+        let model = Arc::new(FakeLanguageModel::default());
+        let thread = cx.new(|cx| {
+            Thread::new(
+                project.clone(),
+                cx.new(|_cx| ProjectContext::default()),
+                context_server_registry,
+                Templates::new(),
+                Some(model),
+                cx,
+            )
+        });
+        let result = cx
+            .update(|cx| {
+                let input = EditFileToolInput {
+                    display_description: "Some edit".into(),
+                    path: "root/file.txt".into(),
+                    mode: EditFileMode::Edit,
+                    expected_version: Some("0:999".parse().unwrap()),
+                };
+                Arc::new(EditFileTool::new(
+                    project,
+                    thread.downgrade(),
+                    language_registry,
+                    Templates::new(),
+                ))
+                .run(input, ToolCallEventStream::test().0, cx)
+            })
+            .await;
+        let error = result.unwrap_err().to_string();
+        assert!(
+            error.starts_with("root/file.txt has changed since version 0:999"),
+            "{error}"
+        );
+    }
+
     #[gpui::test]
     async fn test_resolve_path_for_creating_file(cx: &mut TestAppContext) {
         let mode = &EditFileMode::Create;
@@ -745,6 +843,7 @@ mod tests {
             display_description: "Some edit".into(),
             path: path.into(),
             mode: mode.clone(),
+            expected_version: None,
         };
 
         cx.update(|cx| resolve_path(&input, project, cx))
@@ -862,6 +961,7 @@ mod tests {
                     display_description: "Create main function".into(),
                     path: "root/src/main.rs".into(),
                     mode: EditFileMode::Overwrite,
+                    expected_version: None,
                 };
                 Arc::new(EditFileTool::new(
                     project.clone(),
@@ -921,6 +1021,7 @@ mod tests {
                     display_description: "Update main function".into(),
                     path: "root/src/main.rs".into(),
                     mode: EditFileMode::Overwrite,
+                    expected_version: None,
                 };
                 Arc::new(EditFileTool::new(
                     project.clone(),
@@ -1008,6 +1109,7 @@ mod tests {
                     display_description: "Create main function".into(),
                     path: "root/src/main.rs".into(),
                     mode: EditFileMode::Overwrite,
+                    expected_version: None,
                 };
                 Arc::new(EditFileTool::new(
                     project.clone(),
@@ -1063,6 +1165,7 @@ mod tests {
                     display_description: "Update main function".into(),
                     path: "root/src/main.rs".into(),
                     mode: EditFileMode::Overwrite,
+                    expected_version: None,
                 };
                 Arc::new(EditFileTool::new(
                     project.clone(),
@@ -1133,6 +1236,7 @@ mod tests {
                     display_description: "test 1".into(),
                     path: ".zed/settings.json".into(),
                     mode: EditFileMode::Edit,
+                    expected_version: None,
                 },
                 &stream_tx,
                 cx,
@@ -1153,6 +1257,7 @@ mod tests {
                     display_description: "test 2".into(),
                     path: "/etc/hosts".into(),
                     mode: EditFileMode::Edit,
+                    expected_version: None,
                 },
                 &stream_tx,
                 cx,
@@ -1170,6 +1275,7 @@ mod tests {
                     display_description: "test 3".into(),
                     path: "root/src/main.rs".into(),
                     mode: EditFileMode::Edit,
+                    expected_version: None,
                 },
                 &stream_tx,
                 cx,
@@ -1187,6 +1293,7 @@ mod tests {
                     display_description: "test 4".into(),
                     path: "root/.zed/tasks.json".into(),
                     mode: EditFileMode::Edit,
+                    expected_version: None,
                 },
                 &stream_tx,
                 cx,
@@ -1212,6 +1319,7 @@ mod tests {
                     display_description: "test 5.1".into(),
                     path: ".zed/settings.json".into(),
                     mode: EditFileMode::Edit,
+                    expected_version: None,
                 },
                 &stream_tx,
                 cx,
@@ -1228,6 +1336,7 @@ mod tests {
                     display_description: "test 5.2".into(),
                     path: "/etc/hosts".into(),
                     mode: EditFileMode::Edit,
+                    expected_version: None,
                 },
                 &stream_tx,
                 cx,
@@ -1292,6 +1401,7 @@ mod tests {
                         display_description: "Edit file".into(),
                         path: path.into(),
                         mode: EditFileMode::Edit,
+                        expected_version: None,
                     },
                     &stream_tx,
                     cx,
@@ -1403,6 +1513,7 @@ mod tests {
                         display_description: "Edit file".into(),
                         path: path.into(),
                         mode: EditFileMode::Edit,
+                        expected_version: None,
                     },
                     &stream_tx,
                     cx,
@@ -1495,6 +1606,7 @@ mod tests {
                         display_description: "Edit file".into(),
                         path: path.into(),
                         mode: EditFileMode::Edit,
+                        expected_version: None,
                     },
                     &stream_tx,
                     cx,
@@ -1569,6 +1681,7 @@ mod tests {
                         display_description: "Edit settings".into(),
                         path: "project/.zed/settings.json".into(),
                         mode: mode.clone(),
+                        expected_version: None,
                     },
                     &stream_tx,
                     cx,
@@ -1585,6 +1698,7 @@ mod tests {
                         display_description: "Edit file".into(),
                         path: "/outside/file.txt".into(),
                         mode: mode.clone(),
+                        expected_version: None,
                     },
                     &stream_tx,
                     cx,
@@ -1601,6 +1715,7 @@ mod tests {
                         display_description: "Edit file".into(),
                         path: "project/normal.txt".into(),
                         mode: mode.clone(),
+                        expected_version: None,
                     },
                     &stream_tx,
                     cx,
@@ -1732,6 +1847,7 @@ mod tests {
                         display_description: "Edit file".into(),
                         path: path!("/main.rs").into(),
                         mode: EditFileMode::Edit,
+                        expected_version: None,
                     },
                     stream_tx,
                     cx,
@@ -1762,6 +1878,7 @@ mod tests {
                         display_description: "Edit file".into(),
                         path: path!("/main.rs").into(),
                         mode: EditFileMode::Edit,
+                        expected_version: None,
                     },
                     stream_tx,
                     cx,
@@ -1790,6 +1907,7 @@ mod tests {
                         display_description: "Edit file".into(),
                         path: path!("/main.rs").into(),
                         mode: EditFileMode::Edit,
+                        expected_version: None,
                     },
                     stream_tx,
                     cx,
@@ -1850,6 +1968,8 @@ mod tests {
                     path: "root/test.txt".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: crate::ReadFileMode::Auto,
+                    max_depth: None,
                 },
                 ToolCallEventStream::test().0,
                 cx,
@@ -1878,6 +1998,8 @@ mod tests {
                     path: "root/test.txt".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: crate::ReadFileMode::Auto,
+                    max_depth: None,
                 },
                 ToolCallEventStream::test().0,
                 cx,
@@ -1894,6 +2016,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_edit_file_output_rendering() {
+        let output = EditFileToolOutput {
+            input_path: PathBuf::from("root/src/main.rs"),
+            new_text: "fn main() {\n    println!(\"Hello, world!\");\n}\n".into(),
+            old_text: Arc::new("fn main() {\n    println!(\"Hello\");\n}\n".into()),
+            diff: "@@ -1,3 +1,3 @@\n fn main() {\n-    println!(\"Hello\");\n+    println!(\"Hello, world!\");\n }\n".into(),
+            edit_agent_output: EditAgentOutput {
+                raw_edits: String::new(),
+                parser_metrics: Default::default(),
+            },
+            version: None,
+            dry_run: false,
+        };
+        let LanguageModelToolResultContent::Text(text) = output.into() else {
+            panic!("expected text output");
+        };
+        crate::test_support::assert_snapshot("edit_file_diff", &text);
+    }
+
     fn init_test(cx: &mut TestAppContext) {
         cx.update(|cx| {
             let settings_store = SettingsStore::test(cx);
@@ -1949,6 +2091,8 @@ mod tests {
                     path: "root/test.txt".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: crate::ReadFileMode::Auto,
+                    max_depth: None,
                 },
                 ToolCallEventStream::test().0,
                 cx,
@@ -1965,6 +2109,7 @@ mod tests {
                         display_description: "First edit".into(),
                         path: "root/test.txt".into(),
                         mode: EditFileMode::Edit,
+                        expected_version: None,
                     },
                     ToolCallEventStream::test().0,
                     cx,
@@ -1994,6 +2139,7 @@ mod tests {
                         display_description: "Second edit".into(),
                         path: "root/test.txt".into(),
                         mode: EditFileMode::Edit,
+                        expected_version: None,
                     },
                     ToolCallEventStream::test().0,
                     cx,
@@ -2063,6 +2209,8 @@ mod tests {
                     path: "root/test.txt".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: crate::ReadFileMode::Auto,
+                    max_depth: None,
                 },
                 ToolCallEventStream::test().0,
                 cx,
@@ -2107,6 +2255,7 @@ mod tests {
                         display_description: "Edit after external change".into(),
                         path: "root/test.txt".into(),
                         mode: EditFileMode::Edit,
+                        expected_version: None,
                     },
                     ToolCallEventStream::test().0,
                     cx,
@@ -2174,6 +2323,8 @@ mod tests {
                     path: "root/test.txt".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: crate::ReadFileMode::Auto,
+                    max_depth: None,
                 },
                 ToolCallEventStream::test().0,
                 cx,
@@ -2211,6 +2362,7 @@ mod tests {
                         display_description: "Edit with dirty buffer".into(),
                         path: "root/test.txt".into(),
                         mode: EditFileMode::Edit,
+                        expected_version: None,
                     },
                     ToolCallEventStream::test().0,
                     cx,
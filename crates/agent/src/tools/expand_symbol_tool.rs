@@ -0,0 +1,183 @@
+use super::{SymbolAnchor, display_project_path, resolve_project_path};
+use crate::{AgentTool, ToolCallEventStream};
+use agent_client_protocol as acp;
+use anyhow::{Context as _, Result, anyhow};
+use gpui::{App, Entity, SharedString, Task};
+use language::{Point, ToPoint as _};
+use project::{Project, WorktreeSettings};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::sync::Arc;
+use util::markdown::MarkdownInlineCode;
+
+/// Returns the full source of one symbol in a file, found by name in the file's outline, along
+/// with the comments and attributes right above it.
+///
+/// Use it together with `file_outline` to navigate large files: list the outline, then expand
+/// only the symbols you need instead of reading the whole file.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ExpandSymbolToolInput {
+    /// The relative path of the file.
+    ///
+    /// This path should never be absolute, and the first component of the path should always be a root directory in a project.
+    pub path: String,
+
+    /// The names of the items enclosing the symbol, outermost first, ending with the symbol
+    /// itself, e.g. `["impl Config", "load"]`. A single name such as `["load"]` is enough when
+    /// only one symbol in the file has it.
+    ///
+    /// Each element matches an outline item by its outline text (e.g. "pub fn load"), that text
+    /// without leading modifiers (e.g. "fn load"), or its name (e.g. "load").
+    pub symbol_path: Vec<String>,
+}
+
+pub struct ExpandSymbolTool {
+    project: Entity<Project>,
+}
+
+impl ExpandSymbolTool {
+    pub fn new(project: Entity<Project>) -> Self {
+        Self { project }
+    }
+}
+
+impl AgentTool for ExpandSymbolTool {
+    type Input = ExpandSymbolToolInput;
+    type Output = String;
+
+    fn name() -> &'static str {
+        "expand_symbol"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Read
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => format!(
+                "Expand {} in {}",
+                MarkdownInlineCode(&input.symbol_path.join(" > ")),
+                MarkdownInlineCode(&input.path)
+            )
+            .into(),
+            Err(_) => "Expand symbol".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        _event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let symbol = SymbolAnchor {
+            path: input.path,
+            symbol_path: input.symbol_path,
+        };
+        if let Err(error) = symbol.validate_basic() {
+            return Task::ready(Err(error));
+        }
+
+        let project = self.project.clone();
+        let project_path = match resolve_project_path(project.read(cx), &symbol.path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Task::ready(Err(anyhow!(
+                "Cannot read file because its path matches the file_scan_exclusions or private_files setting: {}",
+                &symbol.path
+            )));
+        }
+        let display_path = display_project_path(project.read(cx), &project_path, cx)
+            .unwrap_or_else(|| symbol.path.clone());
+
+        cx.spawn(async move |cx| {
+            let buffer = project
+                .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+                .await
+                .with_context(|| format!("Couldn't open {display_path}"))?;
+            buffer
+                .read_with(cx, |buffer, _| buffer.parsing_idle())?
+                .await;
+            let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot())?;
+            let item = symbol.outline_item(&snapshot, &display_path)?;
+
+            let start = item.range.start.to_point(&snapshot);
+            let start = match &item.annotation_range {
+                Some(annotation_range) => start.min(annotation_range.start.to_point(&snapshot)),
+                None => start,
+            };
+            let start = Point::new(start.row, 0);
+            let end = item.range.end.to_point(&snapshot);
+            let end = Point::new(end.row, snapshot.line_len(end.row));
+            let source = snapshot.text_for_range(start..end).collect::<String>();
+            Ok(format!(
+                "`{}` in {display_path} [L{}-{}]\n\n```\n{source}\n```\n",
+                symbol.describe(),
+                start.row + 1,
+                end.row + 1
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, run_tool};
+    use gpui::TestAppContext;
+    use serde_json::json;
+
+    #[gpui::test]
+    async fn test_expand_symbol(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "struct Config {\n    value: u32,\n}\n\nimpl Config {\n    /// Loads the config.\n    #[inline]\n    pub fn load() -> Self {\n        Self { value: 1 }\n    }\n}\n\nfn main() {}\n",
+                }
+            }),
+            cx,
+        )
+        .await;
+        let tool = Arc::new(ExpandSymbolTool::new(test_project.project.clone()));
+        let input = |symbol_path: &[&str]| ExpandSymbolToolInput {
+            path: "root/src/main.rs".into(),
+            symbol_path: symbol_path
+                .iter()
+                .map(|element| element.to_string())
+                .collect(),
+        };
+
+        let (output, _) = run_tool(tool.clone(), input(&["load"]), cx).await;
+        assert_eq!(
+            output.expect("tool failed"),
+            "`load` in root/src/main.rs [L6-10]\n\n```\n    /// Loads the config.\n    #[inline]\n    pub fn load() -> Self {\n        Self { value: 1 }\n    }\n```\n"
+        );
+
+        let (output, _) = run_tool(tool.clone(), input(&["struct Config"]), cx).await;
+        assert!(
+            output
+                .expect("tool failed")
+                .ends_with("```\nstruct Config {\n    value: u32,\n}\n```\n")
+        );
+
+        let (output, _) = run_tool(tool, input(&["missing"]), cx).await;
+        let error = output.expect_err("missing symbol was expanded").to_string();
+        assert!(error.starts_with("No symbol `missing`"), "{error}");
+    }
+}
@@ -0,0 +1,534 @@
+use super::symbol_docs_by_context_tool::{SymbolDocs, declaration_for_name};
+use super::{
+    AnchorMemory, ContextualAnchor, TokenSyntax, display_project_path, resolve_project_path,
+    resolve_unique_in_buffer, warm_up_language_servers,
+};
+use crate::{AgentTool, ToolCallEventStream, ToolPreparation};
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use collections::{BTreeMap, HashSet};
+use gpui::{App, AsyncApp, Entity, SharedString, Task};
+use language::{Buffer, BufferSnapshot, Node, Point};
+use language_model::LanguageModelToolResultContent;
+use project::{HoverBlockKind, Location, Project, ProjectPath, WorktreeSettings};
+use settings::Settings as _;
+use std::{fmt::Write as _, ops::Range, sync::Arc};
+use text::ToOffset as _;
+
+const MAX_DEFINITION_LINES: u32 = 42;
+const MAX_REFERENCE_FILES: usize = 20;
+const MAX_CALLERS: usize = 20;
+
+/// Tool: explain_symbol_by_context
+pub struct ExplainSymbolByContextTool {
+    project: Entity<Project>,
+    anchor_memory: Entity<AnchorMemory>,
+}
+
+impl ExplainSymbolByContextTool {
+    pub fn new(project: Entity<Project>, anchor_memory: Entity<AnchorMemory>) -> Self {
+        Self {
+            project,
+            anchor_memory,
+        }
+    }
+}
+
+impl AgentTool for ExplainSymbolByContextTool {
+    type Input = ContextualAnchor;
+    type Output = LanguageModelToolResultContent;
+
+    fn name() -> &'static str {
+        "explain_symbol_by_context"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Read
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn input_examples() -> Vec<Self::Input> {
+        vec![ContextualAnchor {
+            path: "project/src/main.rs".into(),
+            context: "let config = Config::load(&path)?;".into(),
+            token: "load".into(),
+            index: None,
+            handle: None,
+            revision: None,
+            whole_word: false,
+            approximate_line: None,
+            case_insensitive: false,
+            unicode_normalize: false,
+            end_token: None,
+            end_index: None,
+        }]
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => match input.handle {
+                Some(handle) => format!("Explain `{handle}`").into(),
+                None => format!("Explain `{}` in `{}`", input.token, input.path).into(),
+            },
+            Err(_) => "Explain symbol".into(),
+        }
+    }
+
+    fn prepare(
+        self: Arc<Self>,
+        input: Self::Input,
+        cx: &mut App,
+    ) -> Option<Task<Result<ToolPreparation>>> {
+        let input = self.anchor_memory.read(cx).expand(input).ok()?;
+        if input.revision.is_some() {
+            return None;
+        }
+        warm_up_language_servers(&self.project, &input.path, cx)
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        _event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let anchor_memory = self.anchor_memory.clone();
+
+        let input = match anchor_memory.read(cx).expand(input) {
+            Ok(input) => input,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        if let Err(error) = input.validate_basic() {
+            return Task::ready(Err(error));
+        }
+        if let Some(revision) = &input.revision {
+            return Task::ready(Err(anyhow!(
+                "Language servers only index the project's current files, so symbols can't be explained at {revision}. Use `goto_definition_by_context` to read the code at that revision."
+            )));
+        }
+        let project_path = match resolve_project_path(project.read(cx), &input.path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Task::ready(Err(anyhow!(
+                "Cannot read file because its path matches the file_scan_exclusions or private_files setting: {}",
+                &input.path
+            )));
+        }
+        let display_path = display_project_path(project.read(cx), &project_path, cx)
+            .unwrap_or_else(|| input.path.clone());
+
+        cx.spawn(async move |cx| {
+            let buffer = project
+                .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+                .await?;
+            buffer
+                .read_with(cx, |buffer, _| buffer.parsing_idle())?
+                .await;
+            let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot())?;
+            let token_offset = resolve_unique_in_buffer(
+                &input,
+                &snapshot,
+                &display_path,
+                Some(TokenSyntax::Symbol),
+            )?
+            .range
+            .start;
+            let handles =
+                anchor_memory.update(cx, |anchor_memory, _| anchor_memory.remember(&input))?;
+
+            // The lookups are independent, so they run concurrently rather than one after another
+            // as separate tool calls would.
+            let (hovers, definitions, references) = project.update(cx, |project, cx| {
+                (
+                    project.hover(&buffer, token_offset, cx),
+                    project.definitions(&buffer, token_offset, cx),
+                    project.references(&buffer, token_offset, cx),
+                )
+            })?;
+            let (hovers, definitions, references) = futures::join!(hovers, definitions, references);
+            let hovers = hovers.unwrap_or_default();
+            let definitions = definitions?.unwrap_or_default();
+            let references = references?.unwrap_or_default();
+
+            let mut output = String::new();
+            let definition = match definitions.into_iter().next() {
+                Some(definition) => {
+                    Some(describe_definition(&project, &definition.target, cx).await?)
+                }
+                None => None,
+            };
+
+            match &definition {
+                Some(definition) => writeln!(
+                    output,
+                    "`{}` is defined in {} [L{}-{}]\n",
+                    input.token, definition.path, definition.rows.start, definition.rows.end
+                )?,
+                None => writeln!(
+                    output,
+                    "No definition found for `{}` (or language server not capable)\n",
+                    input.token
+                )?,
+            }
+
+            let signature = hover_signature(&hovers).or_else(|| {
+                definition
+                    .as_ref()
+                    .and_then(|definition| definition.signature.clone())
+            });
+            if let Some(signature) = signature {
+                writeln!(output, "## Signature\n\n```\n{signature}\n```\n")?;
+            }
+            if let Some(definition) = &definition {
+                writeln!(
+                    output,
+                    "## Definition\n\n```\n{}\n```\n",
+                    definition.excerpt
+                )?;
+            }
+
+            let usages = describe_references(&project, &references, cx)?;
+            writeln!(output, "## References\n")?;
+            if usages.counts_by_path.is_empty() {
+                writeln!(output, "No references found.\n")?;
+            } else {
+                let file_count = usages.counts_by_path.len();
+                writeln!(
+                    output,
+                    "{} references in {file_count} {}:",
+                    usages.counts_by_path.values().sum::<usize>(),
+                    if file_count == 1 { "file" } else { "files" }
+                )?;
+                let mut counts = usages.counts_by_path.iter().collect::<Vec<_>>();
+                counts.sort_by(|(path_a, count_a), (path_b, count_b)| {
+                    count_b.cmp(count_a).then_with(|| path_a.cmp(path_b))
+                });
+                for (path, count) in counts.iter().take(MAX_REFERENCE_FILES) {
+                    writeln!(output, "- {path}: {count}")?;
+                }
+                if counts.len() > MAX_REFERENCE_FILES {
+                    writeln!(
+                        output,
+                        "- … and {} more files",
+                        counts.len() - MAX_REFERENCE_FILES
+                    )?;
+                }
+                writeln!(output)?;
+            }
+
+            if !usages.callers.is_empty() {
+                writeln!(output, "## Callers\n")?;
+                for (caller, rows) in usages.callers.iter().take(MAX_CALLERS) {
+                    let rows = rows
+                        .iter()
+                        .map(|row| format!("L{}", row + 1))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    writeln!(output, "- `{}` in {} ({rows})", caller.name, caller.path)?;
+                }
+                if usages.callers.len() > MAX_CALLERS {
+                    writeln!(
+                        output,
+                        "- … and {} more callers",
+                        usages.callers.len() - MAX_CALLERS
+                    )?;
+                }
+                writeln!(output)?;
+            }
+
+            output.push_str(&handles.describe());
+            Ok(LanguageModelToolResultContent::Text(Arc::from(output)))
+        })
+    }
+}
+
+struct DefinitionDescription {
+    path: String,
+    /// 1-based, inclusive.
+    rows: Range<u32>,
+    excerpt: String,
+    signature: Option<String>,
+}
+
+/// Describes the declaration at `target`, including its doc comment and attributes, or the lines
+/// around it when no declaration can be found there.
+async fn describe_definition(
+    project: &Entity<Project>,
+    target: &Location,
+    cx: &mut AsyncApp,
+) -> Result<DefinitionDescription> {
+    target
+        .buffer
+        .read_with(cx, |buffer, _| buffer.parsing_idle())?
+        .await;
+    target.buffer.read_with(cx, |buffer, cx| {
+        let snapshot = buffer.snapshot();
+        let name_range =
+            target.range.start.to_offset(&snapshot)..target.range.end.to_offset(&snapshot);
+        let (rows, signature) = match declaration_for_name(&snapshot, name_range.clone()) {
+            Some(declaration) => {
+                let docs = SymbolDocs::extract(&snapshot, declaration);
+                (docs.rows, Some(docs.signature))
+            }
+            None => {
+                let row = snapshot.offset_to_point(name_range.start).row;
+                (row.saturating_sub(2)..row + 2, None)
+            }
+        };
+        let max_row = snapshot.max_point().row;
+        let end_row = rows
+            .end
+            .min(max_row)
+            .min(rows.start + MAX_DEFINITION_LINES - 1);
+        let mut excerpt = snapshot
+            .text_for_range(
+                Point::new(rows.start, 0)..Point::new(end_row, snapshot.line_len(end_row)),
+            )
+            .collect::<String>();
+        if end_row < rows.end {
+            excerpt.push_str("\n…");
+        }
+        Ok(DefinitionDescription {
+            path: buffer_display_path(project, buffer, cx),
+            rows: rows.start + 1..rows.end.min(max_row) + 1,
+            excerpt,
+            signature,
+        })
+    })?
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct Caller {
+    path: String,
+    name: String,
+}
+
+#[derive(Default)]
+struct Usages {
+    counts_by_path: BTreeMap<String, usize>,
+    /// The 0-based rows at which each caller refers to the symbol.
+    callers: BTreeMap<Caller, Vec<u32>>,
+}
+
+/// Counts `references` per file and finds the functions they occur in.
+fn describe_references(
+    project: &Entity<Project>,
+    references: &[Location],
+    cx: &mut AsyncApp,
+) -> Result<Usages> {
+    let mut usages = Usages::default();
+    let mut seen = HashSet::default();
+    for reference in references {
+        reference.buffer.read_with(cx, |buffer, cx| {
+            let snapshot = buffer.snapshot();
+            let range = reference.range.start.to_offset(&snapshot)
+                ..reference.range.end.to_offset(&snapshot);
+            let path = buffer_display_path(project, buffer, cx);
+            if !seen.insert((path.clone(), range.start)) {
+                return;
+            }
+            *usages.counts_by_path.entry(path.clone()).or_default() += 1;
+            if let Some(name) = enclosing_function_name(&snapshot, range.clone()) {
+                usages
+                    .callers
+                    .entry(Caller { path, name })
+                    .or_default()
+                    .push(snapshot.offset_to_point(range.start).row);
+            }
+        })?;
+    }
+    Ok(usages)
+}
+
+/// Returns the name of the innermost function or method around `range`, unless `range` is that
+/// function's own name.
+fn enclosing_function_name(snapshot: &BufferSnapshot, range: Range<usize>) -> Option<String> {
+    let name_range = enclosing_function_name_range(snapshot, range)?;
+    Some(snapshot.text_for_range(name_range).collect::<String>())
+}
+
+/// Returns the range of the name of the innermost function or method around `range`, unless
+/// `range` is that function's own name.
+pub(crate) fn enclosing_function_name_range(
+    snapshot: &BufferSnapshot,
+    range: Range<usize>,
+) -> Option<Range<usize>> {
+    if declaration_for_name(snapshot, range.clone()).is_some() {
+        return None;
+    }
+    let mut node = snapshot.syntax_ancestor(range);
+    while let Some(candidate) = node {
+        if is_function(candidate)
+            && let Some(name) = candidate.child_by_field_name("name")
+        {
+            return Some(name.byte_range());
+        }
+        node = candidate.parent();
+    }
+    None
+}
+
+/// Whether `node` declares a function or method, judged by its kind since grammars name these
+/// differently (e.g. `function_item`, `function_definition` or `method_declaration`).
+pub(crate) fn is_function(node: Node<'_>) -> bool {
+    node.kind().contains("function") || node.kind().contains("method")
+}
+
+pub(crate) fn buffer_display_path(project: &Entity<Project>, buffer: &Buffer, cx: &App) -> String {
+    buffer
+        .file()
+        .and_then(|file| {
+            display_project_path(
+                project.read(cx),
+                &ProjectPath::from_file(file.as_ref(), cx),
+                cx,
+            )
+        })
+        .unwrap_or_else(|| "<buffer>".to_string())
+}
+
+/// Returns the first code block of the hover, which language servers use for the signature.
+/// Markdown hovers carry it as a fenced code block.
+fn hover_signature(hovers: &[project::Hover]) -> Option<String> {
+    hovers
+        .iter()
+        .flat_map(|hover| &hover.contents)
+        .find_map(|block| match block.kind {
+            HoverBlockKind::Code { .. } => Some(block.text.as_str()),
+            HoverBlockKind::Markdown => {
+                let (_, fenced) = block.text.split_once("```")?;
+                let (_, code) = fenced.split_once('\n')?;
+                code.split_once("```").map(|(code, _)| code)
+            }
+            HoverBlockKind::PlainText => None,
+        })
+        .map(|code| code.trim().to_string())
+        .filter(|code| !code.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, run_tool};
+    use futures::StreamExt as _;
+    use gpui::{AppContext as _, TestAppContext};
+    use serde_json::json;
+
+    #[gpui::test]
+    async fn test_explain_symbol_combines_lookups(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "/// Returns one.\nfn helper() -> u32 {\n    1\n}\n\nfn main() {\n    let first = helper();\n    let second = helper();\n}\n\nfn other() {\n    helper();\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let mut fake_servers = test_project.register_fake_rust_server(
+            lsp::ServerCapabilities {
+                definition_provider: Some(lsp::OneOf::Left(true)),
+                references_provider: Some(lsp::OneOf::Left(true)),
+                hover_provider: Some(lsp::HoverProviderCapability::Simple(true)),
+                ..Default::default()
+            },
+            cx,
+        );
+        let (_buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/main.rs", cx).await;
+        let fake_server = fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+
+        let definition = test_project.lsp_location("src/main.rs", (1, 3), (1, 9));
+        fake_server.set_request_handler::<lsp::request::GotoDefinition, _, _>(move |_, _| {
+            let definition = definition.clone();
+            async move { Ok(Some(lsp::GotoDefinitionResponse::Scalar(definition))) }
+        });
+        let references = vec![
+            test_project.lsp_location("src/main.rs", (1, 3), (1, 9)),
+            test_project.lsp_location("src/main.rs", (6, 16), (6, 22)),
+            test_project.lsp_location("src/main.rs", (7, 17), (7, 23)),
+            test_project.lsp_location("src/main.rs", (11, 4), (11, 10)),
+        ];
+        fake_server.set_request_handler::<lsp::request::References, _, _>(move |_, _| {
+            let references = references.clone();
+            async move { Ok(Some(references)) }
+        });
+        fake_server.set_request_handler::<lsp::request::HoverRequest, _, _>(|_, _| async move {
+            Ok(Some(lsp::Hover {
+                contents: lsp::HoverContents::Markup(lsp::MarkupContent {
+                    kind: lsp::MarkupKind::Markdown,
+                    value: "```rust\nfn helper() -> u32\n```\n\nReturns one.".into(),
+                }),
+                range: None,
+            }))
+        });
+
+        let tool = Arc::new(ExplainSymbolByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let (output, _) = run_tool(
+            tool,
+            ContextualAnchor {
+                path: "root/src/main.rs".into(),
+                context: "let first = helper();".into(),
+                token: "helper".into(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            cx,
+        )
+        .await;
+        let LanguageModelToolResultContent::Text(text) = output.expect("tool failed") else {
+            panic!("expected text output");
+        };
+
+        assert!(
+            text.starts_with("`helper` is defined in root/src/main.rs [L1-4]\n"),
+            "{text}"
+        );
+        assert!(
+            text.contains("## Signature\n\n```\nfn helper() -> u32\n```\n"),
+            "{text}"
+        );
+        assert!(
+            text.contains(
+                "## Definition\n\n```\n/// Returns one.\nfn helper() -> u32 {\n    1\n}\n```\n"
+            ),
+            "{text}"
+        );
+        assert!(
+            text.contains("4 references in 1 file:\n- root/src/main.rs: 4\n"),
+            "{text}"
+        );
+        assert!(
+            text.contains(
+                "## Callers\n\n- `main` in root/src/main.rs (L7, L8)\n- `other` in root/src/main.rs (L12)\n"
+            ),
+            "{text}"
+        );
+    }
+}
@@ -1,17 +1,18 @@
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Duration;
 use std::{borrow::Cow, cell::RefCell};
 
 use agent_client_protocol as acp;
 use anyhow::{Context as _, Result, bail};
-use futures::AsyncReadExt as _;
+use futures::{AsyncReadExt as _, FutureExt as _};
 use gpui::{App, AppContext as _, Task};
 use html_to_markdown::{TagHandler, convert_html_to_markdown, markdown};
 use http_client::{AsyncBody, HttpClientWithUrl};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use ui::SharedString;
-use util::markdown::MarkdownEscaped;
+use util::{markdown::MarkdownEscaped, truncate_lines_to_byte_limit};
 
 use crate::{AgentTool, ToolCallEventStream};
 
@@ -22,11 +23,27 @@ enum ContentType {
     Json,
 }
 
+const DEFAULT_TIMEOUT_MS: u64 = 30 * 1000;
+const MAX_TIMEOUT_MS: u64 = 2 * 60 * 1000;
+const DEFAULT_MAX_BYTES: usize = 64 * 1024;
+const MAX_BYTES: usize = 256 * 1024;
+/// Responses larger than this are rejected rather than converted, since even a long
+/// documentation page is far smaller.
+const MAX_RESPONSE_BYTES: u64 = 10 * 1024 * 1024;
+
 /// Fetches a URL and returns the content as Markdown.
+///
+/// HTML pages are converted to Markdown, and content longer than `max_bytes` is truncated.
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct FetchToolInput {
     /// The URL to fetch.
     url: String,
+    /// Optional maximum size of the returned content in bytes, up to 256KiB. Defaults to 64KiB.
+    #[serde(default)]
+    max_bytes: Option<usize>,
+    /// Optional timeout in milliseconds, up to 2 minutes. Defaults to 30 seconds.
+    #[serde(default)]
+    timeout_ms: Option<u64>,
 }
 
 pub struct FetchTool {
@@ -50,9 +67,16 @@ impl FetchTool {
         let mut body = Vec::new();
         response
             .body_mut()
+            .take(MAX_RESPONSE_BYTES + 1)
             .read_to_end(&mut body)
             .await
             .context("error reading response body")?;
+        if body.len() as u64 > MAX_RESPONSE_BYTES {
+            bail!(
+                "the response is larger than {} MiB",
+                MAX_RESPONSE_BYTES / 1024 / 1024
+            );
+        }
 
         if response.status().is_client_error() {
             let text = String::from_utf8_lossy(body.as_slice());
@@ -144,12 +168,24 @@ impl AgentTool for FetchTool {
         cx: &mut App,
     ) -> Task<Result<Self::Output>> {
         let authorize = event_stream.authorize(input.url.clone(), cx);
+        let timeout = Duration::from_millis(
+            input
+                .timeout_ms
+                .unwrap_or(DEFAULT_TIMEOUT_MS)
+                .min(MAX_TIMEOUT_MS),
+        );
+        let max_bytes = input.max_bytes.unwrap_or(DEFAULT_MAX_BYTES).min(MAX_BYTES);
 
         let text = cx.background_spawn({
             let http_client = self.http_client.clone();
             async move {
                 authorize.await?;
-                Self::build_message(http_client, &input.url).await
+                futures::select! {
+                    text = Self::build_message(http_client, &input.url).fuse() => text,
+                    _ = smol::Timer::after(timeout).fuse() => {
+                        bail!("timed out after {} seconds", timeout.as_secs())
+                    }
+                }
             }
         });
 
@@ -158,7 +194,76 @@ impl AgentTool for FetchTool {
             if text.trim().is_empty() {
                 bail!("no textual content found");
             }
-            Ok(text)
+            Ok(truncate_content(text, max_bytes))
         })
     }
 }
+
+fn truncate_content(text: String, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let truncated = truncate_lines_to_byte_limit(&text, max_bytes);
+    format!(
+        "{truncated}\n\n[Content truncated: showing the first {} of {} bytes. Pass a larger `max_bytes` to see more.]",
+        truncated.len(),
+        text.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpui::TestAppContext;
+    use http_client::{FakeHttpClient, Response};
+
+    #[gpui::test]
+    async fn test_fetch_converts_html_and_truncates(cx: &mut TestAppContext) {
+        let paragraph = "Lorem ipsum dolor sit amet. ".repeat(8);
+        let html = format!(
+            "<html><body><h1>Docs</h1>{}</body></html>",
+            format!("<p>{paragraph}</p>").repeat(10)
+        );
+        let http_client = FakeHttpClient::create(move |_| {
+            let html = html.clone();
+            async move {
+                Ok(Response::builder()
+                    .status(200)
+                    .header("content-type", "text/html; charset=utf-8")
+                    .body(html.into())?)
+            }
+        });
+        let tool = Arc::new(FetchTool::new(http_client));
+
+        let (event_stream, mut receiver) = ToolCallEventStream::test();
+        let task = cx.update(|cx| {
+            tool.run(
+                FetchToolInput {
+                    url: "docs.example.com".into(),
+                    max_bytes: Some(600),
+                    timeout_ms: None,
+                },
+                event_stream,
+                cx,
+            )
+        });
+        receiver
+            .expect_authorization()
+            .await
+            .response
+            .send(acp::PermissionOptionId::new("allow"))
+            .expect("tool stopped waiting for authorization");
+        let text = task.await.expect("tool failed");
+
+        assert!(text.starts_with("# Docs"), "{text}");
+        assert!(text.contains("Lorem ipsum dolor sit amet."), "{text}");
+        assert!(
+            text.ends_with("Pass a larger `max_bytes` to see more.]"),
+            "{text}"
+        );
+        let (content, _) = text
+            .split_once("\n\n[Content truncated")
+            .expect("content should be truncated");
+        assert!(content.len() <= 600);
+    }
+}
@@ -0,0 +1,229 @@
+use super::{display_project_path, resolve_project_path};
+use crate::{
+    AgentTool, ToolCallEventStream,
+    outline::{self, OutlineOrder},
+};
+use agent_client_protocol as acp;
+use anyhow::{Context as _, Result, anyhow};
+use gpui::{App, Entity, SharedString, Task};
+use project::{Project, WorktreeSettings};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::sync::Arc;
+use util::markdown::MarkdownInlineCode;
+
+/// How many symbols a page lists when `results_per_page` isn't given.
+const DEFAULT_RESULTS_PER_PAGE: u32 = 100;
+
+/// Lists the symbols in a file's outline (functions, types, fields, modules and so on) with their
+/// line numbers, a page at a time.
+///
+/// Unlike the outline that `read_file` shows for large files, this can be filtered and paged, so
+/// use it to find your way around huge files: e.g. only the top-level items with `max_depth: 0`,
+/// or only the tests with `regex: "^test_"`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct FileOutlineToolInput {
+    /// The relative path of the file.
+    ///
+    /// This path should never be absolute, and the first component of the path should always be a root directory in a project.
+    pub path: String,
+
+    /// Only list symbols whose outline text (e.g. "pub fn load") or name (e.g. "load") matches
+    /// this regular expression.
+    #[serde(default)]
+    pub regex: Option<String>,
+
+    /// Only list symbols nested at most this many levels deep, where 0 is the top level.
+    #[serde(default)]
+    pub max_depth: Option<u32>,
+
+    /// How many of the listed symbols to skip, to see the next page.
+    #[serde(default)]
+    pub offset: u32,
+
+    /// How many symbols to list at most. Defaults to 100.
+    #[serde(default)]
+    pub results_per_page: Option<u32>,
+
+    /// Whether to show each function's full signature next to its name, e.g.
+    /// `fn run — fn run(self: Arc<Self>, input: Input) -> Result<String>`, rather than only
+    /// `fn run(...)`.
+    #[serde(default)]
+    pub include_signatures: bool,
+
+    /// The order to list symbols in: `document` (the default), `alphabetical`, or `by_kind`,
+    /// which groups e.g. all types before all functions. Symbols are always listed under their
+    /// parent, so with `max_depth: 0` and `by_kind`, e.g., all top-level functions are listed
+    /// together.
+    #[serde(default)]
+    pub order: OutlineOrder,
+}
+
+pub struct FileOutlineTool {
+    project: Entity<Project>,
+}
+
+impl FileOutlineTool {
+    pub fn new(project: Entity<Project>) -> Self {
+        Self { project }
+    }
+}
+
+impl AgentTool for FileOutlineTool {
+    type Input = FileOutlineToolInput;
+    type Output = String;
+
+    fn name() -> &'static str {
+        "file_outline"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Read
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => format!("Outline of {}", MarkdownInlineCode(&input.path)).into(),
+            Err(_) => "Outline file".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let regex = match input.regex.as_deref().map(Regex::new).transpose() {
+            Ok(regex) => regex,
+            Err(error) => {
+                return Task::ready(Err(anyhow!("Invalid `regex`: {error}")));
+            }
+        };
+        let results_per_page = input.results_per_page.unwrap_or(DEFAULT_RESULTS_PER_PAGE);
+        if results_per_page == 0 {
+            return Task::ready(Err(anyhow!("`results_per_page` must be at least 1.")));
+        }
+
+        let project = self.project.clone();
+        let project_path = match resolve_project_path(project.read(cx), &input.path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Task::ready(Err(anyhow!(
+                "Cannot read file because its path matches the file_scan_exclusions or private_files setting: {}",
+                &input.path
+            )));
+        }
+        let display_path = display_project_path(project.read(cx), &project_path, cx)
+            .unwrap_or_else(|| input.path.clone());
+
+        cx.spawn(async move |cx| {
+            let buffer = project
+                .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+                .await
+                .with_context(|| format!("Couldn't open {display_path}"))?;
+            let entries = outline::buffer_outline_entries(buffer, cx).await?;
+            if entries.is_empty() {
+                return Err(anyhow!(
+                    "{display_path} has no outline, because its language doesn't provide one. Read it with `read_file` instead."
+                ));
+            }
+            let order = input.order;
+            let entries = cx
+                .background_spawn(async move { outline::order_entries(entries, order) })
+                .await;
+            // Show large pages while they are rendered, rather than only once they are complete.
+            let header = format!("# File outline for {display_path}\n\n");
+            let mut rendered = header.clone();
+            let mut on_chunk = |chunk: &str| {
+                rendered.push_str(chunk);
+                event_stream.update_fields(acp::ToolCallUpdateFields::new().content(vec![
+                    acp::ToolCallContent::Content(acp::Content::new(rendered.clone())),
+                ]));
+            };
+            let outline_text = outline::render_outline(
+                entries,
+                regex,
+                input.max_depth.map(|max_depth| max_depth as usize),
+                input.offset as usize,
+                results_per_page as usize,
+                input.include_signatures,
+                Some(&mut on_chunk),
+                event_stream.token_budget(),
+                cx,
+            )
+            .await?;
+            Ok(format!("{header}{outline_text}"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, run_tool};
+    use gpui::TestAppContext;
+    use serde_json::json;
+
+    #[gpui::test]
+    async fn test_file_outline_filters_and_pages(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "fn helper() {}\n\nstruct Config {\n    value: u32,\n}\n\nmod tests {\n    fn test_load() {}\n    fn test_save() {}\n}\n",
+                }
+            }),
+            cx,
+        )
+        .await;
+        let tool = Arc::new(FileOutlineTool::new(test_project.project.clone()));
+        let input =
+            |regex: Option<&str>, max_depth, offset, results_per_page| FileOutlineToolInput {
+                path: "root/src/main.rs".into(),
+                regex: regex.map(str::to_string),
+                max_depth,
+                offset,
+                results_per_page,
+                include_signatures: false,
+                order: OutlineOrder::Document,
+            };
+
+        let (output, _) = run_tool(tool.clone(), input(None, Some(0), 0, None), cx).await;
+        assert_eq!(
+            output.expect("tool failed"),
+            "# File outline for root/src/main.rs\n\nfn helper(...) [L1]\nstruct Config  [L3-5]\nmod tests      [L7-10]\n\nShowing symbols 1-3 (total symbols: 3)\n3 more deeply nested symbols are hidden; pass a larger `max_depth` to see them.\n"
+        );
+
+        let (output, _) = run_tool(tool.clone(), input(Some("^test_"), None, 0, Some(1)), cx).await;
+        assert!(
+            output
+                .expect("tool failed")
+                .ends_with("\n  fn test_load(...) [L8]\n\nShowing symbols 1-1 (there were more symbols found; use offset: 1 to see next page)\n")
+        );
+        let (output, _) = run_tool(tool.clone(), input(Some("^test_"), None, 1, Some(1)), cx).await;
+        assert!(
+            output.expect("tool failed").ends_with(
+                "\n  fn test_save(...) [L9]\n\nShowing symbols 2-2 (total symbols: 2)\n"
+            )
+        );
+
+        let (output, _) = run_tool(tool, input(Some("("), None, 0, None), cx).await;
+        assert!(output.is_err());
+    }
+}
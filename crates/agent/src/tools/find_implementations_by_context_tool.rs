@@ -0,0 +1,432 @@
+use super::explain_symbol_by_context_tool::{buffer_display_path, is_function};
+use super::symbol_docs_by_context_tool::{SymbolDocs, declaration_for_name};
+use super::{
+    AnchorHandles, AnchorMemory, ContextualAnchor, TokenSyntax, display_project_path,
+    resolve_project_path, resolve_unique_in_buffer, warm_up_language_servers,
+};
+use crate::{AgentTool, ToolCallEventStream, ToolPreparation};
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use collections::HashSet;
+use gpui::{App, Entity, SharedString, Task};
+use language::{BufferSnapshot, Node, Point};
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::{Project, WorktreeSettings};
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{fmt::Write as _, ops::Range, sync::Arc};
+use text::ToOffset as _;
+
+/// Tool: find_implementations_by_context
+///
+/// Lists the implementations of the trait, interface or abstract method at an anchor, using the
+/// language server's implementations request.
+pub struct FindImplementationsByContextTool {
+    project: Entity<Project>,
+    anchor_memory: Entity<AnchorMemory>,
+}
+
+impl FindImplementationsByContextTool {
+    pub fn new(project: Entity<Project>, anchor_memory: Entity<AnchorMemory>) -> Self {
+        Self {
+            project,
+            anchor_memory,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FindImplementationsByContextToolOutput {
+    pub symbol: String,
+    /// The implementations, ordered by path and line.
+    pub implementations: Vec<Implementation>,
+    /// The handle under which the input anchor was remembered.
+    pub handle: String,
+    /// The symbol handle under which the input anchor was remembered, if it is unambiguous.
+    pub symbol_handle: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Implementation {
+    pub path: String,
+    /// The 1-based line range of the implementing declaration.
+    pub start_line: u32,
+    pub end_line: u32,
+    /// The type that provides the implementation, when it can be told from the syntax.
+    pub implementing_type: Option<String>,
+    /// The implementing declaration up to its body.
+    pub signature: String,
+    /// An anchor for the location the language server reported, to continue from with other
+    /// tools.
+    pub anchor: Option<ContextualAnchor>,
+}
+
+impl From<FindImplementationsByContextToolOutput> for LanguageModelToolResultContent {
+    fn from(output: FindImplementationsByContextToolOutput) -> Self {
+        let mut text = if output.implementations.is_empty() {
+            format!(
+                "No implementations found for `{}` (or language server not capable).\n\n",
+                output.symbol
+            )
+        } else {
+            format!(
+                "Found {} implementations of `{}`:\n\n",
+                output.implementations.len(),
+                output.symbol
+            )
+        };
+        for implementation in &output.implementations {
+            write!(
+                text,
+                "## {} [L{}-{}]",
+                implementation.path, implementation.start_line, implementation.end_line
+            )
+            .ok();
+            if let Some(implementing_type) = &implementation.implementing_type {
+                write!(text, " `{implementing_type}`").ok();
+            }
+            writeln!(text, "\n\n```\n{}\n```", implementation.signature).ok();
+            if let Some(anchor) = &implementation.anchor
+                && let Ok(anchor) = serde_json::to_string(anchor)
+            {
+                writeln!(text, "Anchor: {anchor}").ok();
+            }
+            text.push('\n');
+        }
+        let handles = AnchorHandles {
+            reference: output.handle,
+            symbol: output.symbol_handle,
+        };
+        text.push_str(&handles.describe());
+        text.into()
+    }
+}
+
+impl AgentTool for FindImplementationsByContextTool {
+    type Input = ContextualAnchor;
+    type Output = FindImplementationsByContextToolOutput;
+
+    fn name() -> &'static str {
+        "find_implementations_by_context"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Search
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn input_examples() -> Vec<Self::Input> {
+        vec![ContextualAnchor {
+            path: "project/src/storage.rs".into(),
+            context: "pub trait Storage: Send + Sync {".into(),
+            token: "Storage".into(),
+            index: None,
+            handle: None,
+            revision: None,
+            whole_word: false,
+            approximate_line: None,
+            case_insensitive: false,
+            unicode_normalize: false,
+            end_token: None,
+            end_index: None,
+        }]
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => match input.handle {
+                Some(handle) => format!("Find implementations of `{handle}`").into(),
+                None => format!("Find implementations of `{}`", input.token).into(),
+            },
+            Err(_) => "Find implementations".into(),
+        }
+    }
+
+    fn prepare(
+        self: Arc<Self>,
+        input: Self::Input,
+        cx: &mut App,
+    ) -> Option<Task<Result<ToolPreparation>>> {
+        let input = self.anchor_memory.read(cx).expand(input).ok()?;
+        if input.revision.is_some() {
+            return None;
+        }
+        warm_up_language_servers(&self.project, &input.path, cx)
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        _event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let anchor_memory = self.anchor_memory.clone();
+
+        let input = match anchor_memory.read(cx).expand(input) {
+            Ok(input) => input,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        if let Err(error) = input.validate_basic() {
+            return Task::ready(Err(error));
+        }
+        if let Some(revision) = &input.revision {
+            return Task::ready(Err(anyhow!(
+                "Language servers only know the project's current files, so they can't find implementations at {revision}."
+            )));
+        }
+        let project_path = match resolve_project_path(project.read(cx), &input.path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Task::ready(Err(anyhow!(
+                "Cannot read file because its path matches the file_scan_exclusions or private_files setting: {}",
+                &input.path
+            )));
+        }
+        let display_path = display_project_path(project.read(cx), &project_path, cx)
+            .unwrap_or_else(|| input.path.clone());
+
+        cx.spawn(async move |cx| {
+            let buffer = project
+                .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+                .await?;
+            buffer
+                .read_with(cx, |buffer, _| buffer.parsing_idle())?
+                .await;
+            let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot())?;
+            let token_offset = resolve_unique_in_buffer(
+                &input,
+                &snapshot,
+                &display_path,
+                Some(TokenSyntax::Symbol),
+            )?
+            .range
+            .start;
+            let handles =
+                anchor_memory.update(cx, |anchor_memory, _| anchor_memory.remember(&input))?;
+
+            let links = project
+                .update(cx, |project, cx| {
+                    project.implementations(&buffer, token_offset, cx)
+                })?
+                .await?
+                .unwrap_or_default();
+
+            let mut seen = HashSet::default();
+            let mut implementations = Vec::new();
+            for link in links {
+                let target = link.target;
+                target
+                    .buffer
+                    .read_with(cx, |buffer, _| buffer.parsing_idle())?
+                    .await;
+                let implementation = target.buffer.read_with(cx, |buffer, cx| {
+                    let snapshot = buffer.snapshot();
+                    let range = target.range.start.to_offset(&snapshot)
+                        ..target.range.end.to_offset(&snapshot);
+                    let path = buffer_display_path(&project, buffer, cx);
+                    if !seen.insert((path.clone(), range.start)) {
+                        return None;
+                    }
+                    Some(describe_implementation(path, &snapshot, range))
+                })?;
+                implementations.extend(implementation);
+            }
+            implementations.sort_by(|left, right| {
+                (&left.path, left.start_line).cmp(&(&right.path, right.start_line))
+            });
+
+            Ok(FindImplementationsByContextToolOutput {
+                symbol: input.token,
+                implementations,
+                handle: handles.reference,
+                symbol_handle: handles.symbol,
+            })
+        })
+    }
+}
+
+fn describe_implementation(
+    path: String,
+    snapshot: &BufferSnapshot,
+    range: Range<usize>,
+) -> Implementation {
+    let anchor = ContextualAnchor::for_range(path.clone(), snapshot, range.clone());
+    let Some(declaration) = implementing_declaration(snapshot, range.clone()) else {
+        let row = snapshot.offset_to_point(range.start).row;
+        let line = snapshot
+            .text_for_range(Point::new(row, 0)..Point::new(row, snapshot.line_len(row)))
+            .collect::<String>();
+        return Implementation {
+            path,
+            start_line: row + 1,
+            end_line: row + 1,
+            implementing_type: None,
+            signature: line.trim().to_string(),
+            anchor,
+        };
+    };
+    let docs = SymbolDocs::extract(snapshot, declaration);
+    Implementation {
+        path,
+        start_line: declaration.start_position().row as u32 + 1,
+        end_line: docs.rows.end + 1,
+        implementing_type: implementing_type(snapshot, declaration),
+        signature: docs.signature,
+        anchor,
+    }
+}
+
+/// Returns the declaration that the language server's implementation `range` is part of. Servers
+/// report either the name of the implementing declaration or a span inside it (e.g. the type in
+/// an `impl` header).
+fn implementing_declaration(snapshot: &BufferSnapshot, range: Range<usize>) -> Option<Node<'_>> {
+    if let Some(declaration) = declaration_for_name(snapshot, range.clone()) {
+        return Some(declaration);
+    }
+    let mut node = snapshot.syntax_ancestor(range);
+    while let Some(candidate) = node {
+        if is_type_declaration(candidate) || is_function(candidate) {
+            return Some(candidate);
+        }
+        node = candidate.parent();
+    }
+    None
+}
+
+/// Returns the name of the type that `declaration` belongs to: the type of the `impl` block or
+/// class around it, or its own name when it declares a type.
+fn implementing_type(snapshot: &BufferSnapshot, declaration: Node<'_>) -> Option<String> {
+    let mut node = Some(declaration);
+    while let Some(candidate) = node {
+        if is_type_declaration(candidate)
+            && let Some(name) = candidate
+                .child_by_field_name("type")
+                .or_else(|| candidate.child_by_field_name("name"))
+        {
+            return Some(snapshot.text_for_range(name.byte_range()).collect());
+        }
+        node = candidate.parent();
+    }
+    None
+}
+
+fn is_type_declaration(node: Node<'_>) -> bool {
+    let kind = node.kind();
+    kind.starts_with("impl")
+        || ["class", "struct", "enum", "interface"]
+            .iter()
+            .any(|type_kind| kind.contains(type_kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, run_tool};
+    use futures::StreamExt as _;
+    use gpui::{AppContext as _, TestAppContext};
+    use serde_json::json;
+
+    const SOURCE: &str = "trait Shape {
+    fn area(&self) -> f64;
+}
+
+struct Square;
+
+impl Shape for Square {
+    fn area(&self) -> f64 {
+        1.0
+    }
+}
+";
+
+    #[gpui::test]
+    async fn test_find_implementations_describes_impl_blocks(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(json!({ "src": { "main.rs": SOURCE } }), cx).await;
+        let mut fake_servers = test_project.register_fake_rust_server(
+            lsp::ServerCapabilities {
+                implementation_provider: Some(lsp::ImplementationProviderCapability::Simple(true)),
+                ..Default::default()
+            },
+            cx,
+        );
+        let (_buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/main.rs", cx).await;
+        let fake_server = fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+        let implementation = test_project.lsp_location("src/main.rs", (6, 15), (6, 21));
+        fake_server.set_request_handler::<lsp::request::GotoImplementation, _, _>(move |_, _| {
+            let implementation = implementation.clone();
+            async move {
+                Ok(Some(lsp::GotoImplementationResponse::Scalar(
+                    implementation,
+                )))
+            }
+        });
+
+        let tool = Arc::new(FindImplementationsByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let (output, _) = run_tool(
+            tool,
+            ContextualAnchor {
+                path: "root/src/main.rs".into(),
+                context: "trait Shape {".into(),
+                token: "Shape".into(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+
+        let [implementation] = output.implementations.as_slice() else {
+            panic!(
+                "expected a single implementation, got {:?}",
+                output.implementations
+            );
+        };
+        assert_eq!(implementation.path, "root/src/main.rs");
+        assert_eq!(
+            (implementation.start_line, implementation.end_line),
+            (7, 11)
+        );
+        assert_eq!(implementation.implementing_type.as_deref(), Some("Square"));
+        assert_eq!(implementation.signature, "impl Shape for Square");
+        assert_eq!(
+            implementation
+                .anchor
+                .as_ref()
+                .map(|anchor| anchor.context.as_str()),
+            Some("for Square {")
+        );
+    }
+}
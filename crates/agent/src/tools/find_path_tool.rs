@@ -2,9 +2,9 @@ use crate::{AgentTool, ToolCallEventStream};
 use agent_client_protocol as acp;
 use anyhow::{Result, anyhow};
 use gpui::{App, AppContext, Entity, SharedString, Task};
-use language_model::LanguageModelToolResultContent;
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
 use project::Project;
-use schemars::JsonSchema;
+use schemars::{JsonSchema, Schema};
 use serde::{Deserialize, Serialize};
 use std::fmt::Write;
 use std::{cmp, path::PathBuf, sync::Arc};
@@ -37,7 +37,7 @@ pub struct FindPathToolInput {
     pub offset: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct FindPathToolOutput {
     offset: usize,
     current_matches_page: Vec<PathBuf>,
@@ -93,6 +93,23 @@ impl AgentTool for FindPathTool {
         acp::ToolKind::Search
     }
 
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn input_examples() -> Vec<Self::Input> {
+        vec![
+            FindPathToolInput {
+                glob: "**/*.rs".into(),
+                offset: 0,
+            },
+            FindPathToolInput {
+                glob: "project/src/**/*_test.rs".into(),
+                offset: 50,
+            },
+        ]
+    }
+
     fn initial_title(
         &self,
         input: Result<Self::Input, serde_json::Value>,
@@ -238,6 +255,22 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_find_path_output_rendering() {
+        let output = FindPathToolOutput {
+            offset: 50,
+            current_matches_page: vec![
+                PathBuf::from("root/src/first.rs"),
+                PathBuf::from("root/src/second.rs"),
+            ],
+            all_matches_len: 52,
+        };
+        let LanguageModelToolResultContent::Text(text) = output.into() else {
+            panic!("expected text output");
+        };
+        crate::test_support::assert_snapshot("find_path_paginated", &text);
+    }
+
     fn init_test(cx: &mut TestAppContext) {
         cx.update(|cx| {
             let settings_store = SettingsStore::test(cx);
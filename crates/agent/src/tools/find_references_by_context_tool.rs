@@ -1,34 +1,552 @@
-use crate::{AgentTool, ToolCallEventStream};
+use crate::{AgentTool, ToolCallEventStream, ToolPreparation};
 use agent_client_protocol as acp;
+use agent_settings::AgentSettings;
 use anyhow::{Result, anyhow};
-use gpui::{App, Entity, SharedString, Task};
-use language::Point;
+use collections::{HashMap, HashSet};
+use futures::StreamExt as _;
+use gpui::{App, Entity, EntityId, SharedString, Task};
+use itertools::Itertools as _;
+use language::{Buffer, BufferSnapshot};
 use language_model::LanguageModelToolResultContent;
-use project::{Project, WorktreeSettings};
-
-use std::sync::Arc;
+use project::{AgentLocation, Location, Project, ProjectPath};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Write as _,
+    ops::Range,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use text::OffsetRangeExt;
-use text::ToPoint as _;
+use util::{ResultExt as _, paths::PathMatcher};
+
+use super::support::{AnchorResolution, AnchorTarget, Excerpt, ResolvedToken, report_locations};
+use super::symbol_docs_by_context_tool::declaration_for_name;
+use super::{
+    AnchorHandles, AnchorMemory, ContextualAnchor, LanguageServerReadiness, LanguageServerResult,
+    TokenSyntax, display_project_path, language_server_name, language_server_results,
+    language_server_retry_note, merge_language_server_results, omitted_note,
+    request_language_server, resolve_project_path, truncate_results, unsaved_changes_note,
+    warm_up_language_servers,
+};
+
+/// Finds the references to the symbol at an anchor across the project, using the language
+/// server, and shows the code around each of them. Each reference is labeled with how it uses
+/// the symbol (declaration, import, call, write, type or read), to find the calls and writes that
+/// a change affects most. Each reference also comes with an anchor for it, to pass on to other
+/// tools as is.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct FindReferencesByContextToolInput {
+    #[serde(flatten)]
+    pub anchor: ContextualAnchor,
+    /// Show each reference with this many lines before and after it, at most 20, instead of the
+    /// whole enclosing function or block. Use a small number, e.g. 2, to skim many references.
+    #[serde(default)]
+    pub excerpt_context_lines: Option<u32>,
+    /// Leave out the symbol's declaration, listing only its uses.
+    #[serde(default)]
+    pub exclude_declaration: bool,
+    /// Glob patterns for the paths of files whose references to leave out, e.g. `**/tests/**`.
+    /// They are matched against the full path including the project root directory.
+    #[serde(default)]
+    pub exclude_patterns: Vec<String>,
+    /// Only list references in files under this directory, e.g. `project/src/editor`.
+    #[serde(default)]
+    pub within: Option<String>,
+    /// How many of the references to skip, to see the next page.
+    #[serde(default)]
+    pub offset: u32,
+    /// How many references to show at most. Defaults to 24, at most 100.
+    #[serde(default)]
+    pub max_results: Option<u32>,
+    /// Where to move the user's view when they follow the agent: to the first reference (the
+    /// default), through every reference as a trail they can step through, or nowhere. This only
+    /// happens when the user turned on following the agent's navigation.
+    #[serde(default)]
+    pub navigation: Option<ReferencesNavigation>,
+    /// How many milliseconds to wait, at most 120000, for the language server to start and finish
+    /// indexing before asking it, e.g. right after the project was opened. Without it, references
+    /// are looked for right away, with a note when the language server wasn't ready.
+    #[serde(default)]
+    pub wait_for_ready_ms: u64,
+}
+
+/// Where finding references moves the agent location, which users following the agent see.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferencesNavigation {
+    /// Move to the first reference.
+    #[default]
+    First,
+    /// Move to the first reference and record all of them, in order, as a trail to step through.
+    Trail,
+    /// Don't move.
+    Stay,
+}
+
+/// The references found by `find_references_by_context`, grouped by the file containing them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FindReferencesByContextToolOutput {
+    /// The files containing the shown references, in the order the language server first
+    /// reported a reference in each.
+    pub files: Vec<FileReferences>,
+    /// How many references the language server reported in total, including those not shown.
+    pub total_references: usize,
+    /// How many files contain references, including those not shown.
+    pub total_files: usize,
+    /// How many references were skipped before the shown ones.
+    pub offset: usize,
+    /// Whether there are more references after the shown ones.
+    pub has_more: bool,
+    /// The offset to pass to see the next page, if there is one.
+    pub next_offset: Option<usize>,
+    /// The handle under which the input anchor was remembered.
+    pub handle: String,
+    /// The symbol handle under which the input anchor was remembered, if it is unambiguous.
+    pub symbol_handle: Option<String>,
+    /// A note that the anchor's file has unsaved changes, if it does.
+    pub unsaved_changes_note: Option<String>,
+    /// Whether the language server didn't answer in time, so that the references may be
+    /// incomplete.
+    pub timed_out: bool,
+    /// A note that the language server wasn't running or was still indexing, so that references
+    /// may be missing, if it was.
+    pub readiness_note: Option<String>,
+    /// How many references of the page were left out so that the rest fit in the model's context.
+    pub omitted_to_fit: usize,
+    /// How many times requests to the language server were retried after failing transiently.
+    pub retries: usize,
+    /// A note that the requested navigation was ignored, if it was.
+    pub navigation_note: Option<String>,
+}
+
+/// The shown references in one file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FileReferences {
+    pub path: String,
+    /// How many references the file contains, including those not shown.
+    pub total: usize,
+    pub locations: Vec<FindReferencesLocation>,
+}
+
+impl From<FindReferencesByContextToolOutput> for LanguageModelToolResultContent {
+    fn from(output: FindReferencesByContextToolOutput) -> Self {
+        let mut text = String::new();
+        if output.timed_out {
+            text.push_str(
+                "The language server didn't answer in time (it may still be indexing), so these results may be incomplete.\n\n",
+            );
+        }
+        if let Some(note) = &output.readiness_note {
+            writeln!(text, "{note}\n").ok();
+        }
+        if output.total_references == 0 {
+            text.push_str("No references found (or language server not capable)\n\n");
+        } else if output.files.is_empty() {
+            writeln!(
+                text,
+                "No more references after offset {} (total references: {}).\n",
+                output.offset, output.total_references
+            )
+            .ok();
+        } else {
+            let shown = output
+                .files
+                .iter()
+                .map(|file| file.locations.len())
+                .sum::<usize>();
+            // Only name the servers when they disagree, since with a single server every
+            // reference would name the same one.
+            let language_servers = output
+                .files
+                .iter()
+                .flat_map(|file| &file.locations)
+                .flat_map(|location| &location.language_servers)
+                .collect::<HashSet<_>>();
+            let show_language_servers = language_servers.len() > 1;
+            writeln!(
+                text,
+                "Found {} in {}.\n",
+                counted(output.total_references, "reference"),
+                counted(output.total_files, "file")
+            )
+            .ok();
+
+            for file in &output.files {
+                write!(
+                    text,
+                    "## {} ({}",
+                    file.path,
+                    counted(file.total, "reference")
+                )
+                .ok();
+                if file.locations.len() < file.total {
+                    write!(text, ", {} shown", file.locations.len()).ok();
+                }
+                text.push_str(")\n\n");
+                for location in &file.locations {
+                    location.render(&mut text, show_language_servers);
+                }
+            }
+            if output.omitted_to_fit > 0 {
+                writeln!(text, "{}", omitted_note(output.omitted_to_fit)).ok();
+            }
+
+            let page_start = output.offset + 1;
+            let page_end = output.offset + shown;
+            if let Some(next_offset) = output.next_offset {
+                writeln!(
+                    text,
+                    "Showing references {page_start}-{page_end} (there were more references found; use offset: {next_offset} to see next page)\n"
+                )
+            } else {
+                writeln!(
+                    text,
+                    "Showing references {page_start}-{page_end} (total references: {})\n",
+                    output.total_references
+                )
+            }
+            .ok();
+        }
+
+        let handles = AnchorHandles {
+            reference: output.handle,
+            symbol: output.symbol_handle,
+        };
+        text.push_str(&handles.describe());
+        if let Some(note) = &output.unsaved_changes_note {
+            text.push_str(note);
+        }
+        if let Some(note) = language_server_retry_note(output.retries) {
+            text.push_str(&note);
+        }
+        if let Some(note) = &output.navigation_note {
+            text.push_str(note);
+        }
+        text.into()
+    }
+}
 
-use super::goto_definition_by_context_tool::ContextPositionInput;
+fn counted(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {noun}")
+    } else {
+        format!("{count} {noun}s")
+    }
+}
+
+/// A reference found by `find_references_by_context`, with the code around it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FindReferencesLocation {
+    /// The path of the file containing the reference, relative to the project, if the file
+    /// belongs to one of its worktrees.
+    pub path: Option<String>,
+    /// The absolute path of the file containing the reference, if it has one.
+    pub abs_path: Option<PathBuf>,
+    /// The first line of `preview`, 1-based.
+    pub start_line: u32,
+    /// The last line of `preview`, 1-based.
+    pub end_line: u32,
+    pub preview: String,
+    /// How the reference uses the symbol, if the file has a syntax tree to tell from.
+    pub usage_kind: Option<ReferenceUsageKind>,
+    /// The names of the language servers that reported the reference.
+    pub language_servers: Vec<String>,
+    /// An anchor for the reference itself, to continue from with other tools (e.g. hover, rename
+    /// or edit) without copying context out of `preview`.
+    pub anchor: Option<ContextualAnchor>,
+}
+
+/// How a reference uses its symbol, as told from the syntax around it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferenceUsageKind {
+    /// The symbol's own declaration.
+    Declaration,
+    /// An import of the symbol, e.g. in a `use` or `import` statement.
+    Import,
+    /// A call of the symbol, e.g. `helper()` or `value.helper()`.
+    Call,
+    /// An assignment to the symbol, e.g. `count = 1` or `count += 1`.
+    Write,
+    /// A use of the symbol as a type.
+    Type,
+    /// Any other use, such as reading a value.
+    Read,
+}
+
+impl ReferenceUsageKind {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Declaration => "declaration",
+            Self::Import => "import",
+            Self::Call => "call",
+            Self::Write => "write",
+            Self::Type => "type",
+            Self::Read => "read",
+        }
+    }
+}
+
+/// Node kinds of paths and member accesses, e.g. `a::b` or `a.b`, whose last part is the symbol
+/// being used.
+const PATH_KINDS: &[&str] = &[
+    "scoped",
+    "field_expression",
+    "member_expression",
+    "selector_expression",
+    "field_access",
+    "attribute",
+    "generic_function",
+    "qualified",
+];
+
+/// Classifies the reference at `range` in `snapshot` from the syntax tree around it. Node kinds
+/// differ between grammars, so this matches the parts of names that most grammars share.
+fn usage_kind(snapshot: &BufferSnapshot, range: Range<usize>) -> Option<ReferenceUsageKind> {
+    let ancestor = snapshot.syntax_ancestor(range.clone())?;
+    let node = ancestor
+        .named_descendant_for_byte_range(range.start, range.end)
+        .unwrap_or(ancestor);
+    if declaration_for_name(snapshot, range).is_some() {
+        return Some(ReferenceUsageKind::Declaration);
+    }
+
+    let mut ancestor = node.parent();
+    while let Some(current) = ancestor {
+        let kind = current.kind();
+        if kind.contains("import") || kind.starts_with("use_") {
+            return Some(ReferenceUsageKind::Import);
+        }
+        ancestor = current.parent();
+    }
+    if node.kind().contains("type") {
+        return Some(ReferenceUsageKind::Type);
+    }
+
+    let mut current = node;
+    while let Some(parent) = current.parent() {
+        let kind = parent.kind();
+        if PATH_KINDS.iter().any(|path_kind| kind.contains(path_kind))
+            && parent.end_byte() == current.end_byte()
+        {
+            current = parent;
+            continue;
+        }
+
+        let within_field = |field: &str| {
+            parent
+                .child_by_field_name(field)
+                .is_some_and(|child| child.byte_range().contains(&current.start_byte()))
+        };
+        if (kind.contains("call") || kind.contains("invocation")) && !within_field("arguments") {
+            return Some(ReferenceUsageKind::Call);
+        }
+        if (kind.contains("assignment") && within_field("left"))
+            || kind.contains("update_expression")
+        {
+            return Some(ReferenceUsageKind::Write);
+        }
+        if kind.contains("type") {
+            return Some(ReferenceUsageKind::Type);
+        }
+        break;
+    }
+    Some(ReferenceUsageKind::Read)
+}
+
+impl FindReferencesLocation {
+    fn render(&self, text: &mut String, show_language_servers: bool) {
+        write!(text, "[L{}-{}]", self.start_line, self.end_line).ok();
+        if let Some(usage_kind) = self.usage_kind {
+            write!(text, " {}", usage_kind.label()).ok();
+        }
+        if show_language_servers {
+            write!(text, " ({})", self.language_servers.join(", ")).ok();
+        }
+        writeln!(text, "\n```\n{}\n```", self.preview).ok();
+        if let Some(anchor) = &self.anchor
+            && let Ok(anchor) = serde_json::to_string(anchor)
+        {
+            writeln!(text, "Anchor: {anchor}").ok();
+        }
+        text.push('\n');
+    }
+
+    /// Renders the reference at `range` in `buffer`, with `excerpt_context_lines` around it or,
+    /// by default, its enclosing scope.
+    fn new(
+        project: &Project,
+        buffer: &Buffer,
+        range: Range<text::Anchor>,
+        excerpt_context_lines: Option<u32>,
+        language_servers: Vec<String>,
+        cx: &App,
+    ) -> Self {
+        let snapshot = buffer.snapshot();
+        let excerpt = Excerpt::around(
+            &snapshot,
+            range.to_point(&snapshot),
+            excerpt_context_lines,
+            MAX_SCOPE_LINES,
+        );
+        let offset_range = range.to_offset(&snapshot);
+        let usage_kind = usage_kind(&snapshot, offset_range.clone());
+
+        // Derive the paths from the buffer containing this reference, which is often not the
+        // anchor's own file.
+        let (path, abs_path) = match buffer.file() {
+            Some(file) => {
+                let project_path = ProjectPath::from_file(file.as_ref(), cx);
+                (
+                    display_project_path(project, &project_path, cx),
+                    project.absolute_path(&project_path, cx),
+                )
+            }
+            None => (None, None),
+        };
+        let anchor = path
+            .clone()
+            .and_then(|path| ContextualAnchor::for_range(path, &snapshot, offset_range));
+
+        Self {
+            path,
+            abs_path,
+            start_line: excerpt.start_row + 1,
+            end_line: excerpt.end_row + 1,
+            preview: excerpt.text,
+            usage_kind,
+            language_servers,
+            anchor,
+        }
+    }
+
+    /// The path to show for this reference: project-relative when possible, since that's how
+    /// paths are given to the other tools.
+    fn display_path(&self) -> String {
+        self.path
+            .clone()
+            .or_else(|| {
+                self.abs_path
+                    .as_ref()
+                    .map(|abs_path| abs_path.display().to_string())
+            })
+            .unwrap_or_else(|| "<buffer>".to_string())
+    }
+}
+
+/// Whether `location` and `other` are in the same buffer and overlap, e.g. because `location` is
+/// the declaration that `other` points to.
+fn overlaps(location: &Location, other: &Location, cx: &App) -> bool {
+    if location.buffer != other.buffer {
+        return false;
+    }
+    let buffer = location.buffer.read(cx);
+    location.range.start.cmp(&other.range.end, buffer).is_le()
+        && other.range.start.cmp(&location.range.end, buffer).is_le()
+}
+
+/// Whether `location` is in a file under `within` that matches none of the excluded patterns.
+fn is_path_included(
+    project: &Project,
+    location: &Location,
+    exclude_matcher: Option<&PathMatcher>,
+    within: Option<&ProjectPath>,
+    cx: &App,
+) -> bool {
+    let Some(file) = location.buffer.read(cx).file() else {
+        return exclude_matcher.is_none() && within.is_none();
+    };
+    let project_path = ProjectPath::from_file(file.as_ref(), cx);
+    if let Some(within) = within
+        && (within.worktree_id != project_path.worktree_id
+            || !project_path.path.starts_with(&within.path))
+    {
+        return false;
+    }
+    if let Some(exclude_matcher) = exclude_matcher
+        && let Some(worktree) = project.worktree_for_id(project_path.worktree_id, cx)
+    {
+        let full_path = worktree.read(cx).root_name().join(&project_path.path);
+        if exclude_matcher.is_match(&full_path) {
+            return false;
+        }
+    }
+    true
+}
+
+/// A step of finding references, shown as the tool call's title while the language server works,
+/// since large projects can take it a while.
+enum ReferencesProgress {
+    ResolvedAnchor {
+        path: String,
+        line: u32,
+    },
+    WaitingForLanguageServer,
+    QueryingLanguageServer,
+    /// How many references each language server that answered so far reported.
+    ResultsSoFar(Vec<(String, usize)>),
+    Done {
+        references: usize,
+        files: usize,
+    },
+}
+
+impl ReferencesProgress {
+    fn title(&self, token: &str) -> String {
+        match self {
+            Self::ResolvedAnchor { path, line } => {
+                format!("Finding references to `{token}` at {path}:{line}")
+            }
+            Self::WaitingForLanguageServer => {
+                format!("Finding references to `{token}`: waiting for the language server")
+            }
+            Self::QueryingLanguageServer => {
+                format!("Finding references to `{token}`: querying the language server")
+            }
+            Self::ResultsSoFar(counts) => format!(
+                "Finding references to `{token}`: {} so far ({})",
+                counted(counts.iter().map(|(_, count)| count).sum(), "result"),
+                counts
+                    .iter()
+                    .map(|(server_name, count)| format!("{server_name}: {count}"))
+                    .join(", ")
+            ),
+            Self::Done { references, files } => format!(
+                "Found {} to `{token}` in {}",
+                counted(*references, "reference"),
+                counted(*files, "file")
+            ),
+        }
+    }
+}
 
 /// Tool: find_references_by_context
 pub struct FindReferencesByContextTool {
     project: Entity<Project>,
+    anchor_memory: Entity<AnchorMemory>,
 }
 
 // Config
 const MAX_SCOPE_LINES: usize = 42;
-const PAGINATE_LIMIT: usize = 24;
+const DEFAULT_MAX_RESULTS: u32 = 24;
+const MAX_RESULTS_LIMIT: u32 = 100;
+const MAX_EXCERPT_CONTEXT_LINES: u32 = 20;
+const MAX_WAIT_FOR_READY_MS: u64 = 120_000;
+const IGNORED_NAVIGATION_NOTE: &str = "The user's view was not moved to the references, since they haven't turned on following the agent's navigation (`agent.follow_agent_navigation`).\n";
 
 impl FindReferencesByContextTool {
-    pub fn new(project: Entity<Project>) -> Self {
-        Self { project }
+    pub fn new(project: Entity<Project>, anchor_memory: Entity<AnchorMemory>) -> Self {
+        Self {
+            project,
+            anchor_memory,
+        }
     }
 }
 
 impl AgentTool for FindReferencesByContextTool {
-    type Input = ContextPositionInput;
+    type Input = FindReferencesByContextToolInput;
     type Output = LanguageModelToolResultContent;
 
     fn name() -> &'static str {
@@ -39,406 +557,1357 @@ impl AgentTool for FindReferencesByContextTool {
         acp::ToolKind::Read
     }
 
+    fn input_examples() -> Vec<Self::Input> {
+        vec![FindReferencesByContextToolInput {
+            anchor: ContextualAnchor {
+                path: "project/src/config.rs".into(),
+                context: "pub fn load(path: &Path) -> Result<Self> {".into(),
+                token: "load".into(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            excerpt_context_lines: None,
+            exclude_declaration: false,
+            exclude_patterns: Vec::new(),
+            within: None,
+            offset: 0,
+            max_results: None,
+            navigation: None,
+            wait_for_ready_ms: 0,
+        }]
+    }
+
     fn initial_title(
         &self,
         input: Result<Self::Input, serde_json::Value>,
         _cx: &mut App,
     ) -> SharedString {
-        if let Ok(input) = input {
-            format!("Find references for `{}` in `{}`", input.token, input.path).into()
+        if let Ok(FindReferencesByContextToolInput { anchor: input, .. }) = input {
+            if let Some(handle) = input.handle {
+                format!("Find references for `{handle}`").into()
+            } else {
+                format!("Find references for `{}` in `{}`", input.token, input.path).into()
+            }
         } else {
             "Find references by context".into()
         }
     }
 
+    fn prepare(
+        self: Arc<Self>,
+        input: Self::Input,
+        cx: &mut App,
+    ) -> Option<Task<Result<ToolPreparation>>> {
+        let input = self.anchor_memory.read(cx).expand(input.anchor).ok()?;
+        if input.revision.is_some() {
+            return None;
+        }
+        warm_up_language_servers(&self.project, &input.path, cx)
+    }
+
     fn run(
         self: Arc<Self>,
         input: Self::Input,
-        _event_stream: ToolCallEventStream,
+        event_stream: ToolCallEventStream,
         cx: &mut App,
     ) -> Task<Result<Self::Output>> {
         let project = self.project.clone();
-
-        // Validate early
-        if !input.context.contains(&input.token) {
-            return Task::ready(Err(anyhow!(
-                "The provided `context` must contain the `token`."
-            )));
+        let anchor_memory = self.anchor_memory.clone();
+        let excerpt_context_lines = input
+            .excerpt_context_lines
+            .map(|lines| lines.min(MAX_EXCERPT_CONTEXT_LINES));
+        let offset = input.offset as usize;
+        let navigation = input.navigation;
+        let wait_for_ready =
+            Duration::from_millis(input.wait_for_ready_ms.min(MAX_WAIT_FOR_READY_MS));
+        let max_results = input.max_results.unwrap_or(DEFAULT_MAX_RESULTS);
+        if max_results == 0 {
+            return Task::ready(Err(anyhow!("`max_results` must be at least 1.")));
         }
-
-        // Resolve project path and perform WorktreeSettings checks on the foreground thread (cx: &mut App).
-        let project_path = match project.read(cx).find_project_path(&input.path, cx) {
-            Some(p) => p,
-            None => return Task::ready(Err(anyhow!("Path {} not found in project", &input.path))),
+        let max_results = max_results.min(MAX_RESULTS_LIMIT) as usize;
+        let exclude_declaration = input.exclude_declaration;
+        let exclude_matcher = if input.exclude_patterns.is_empty() {
+            None
+        } else {
+            match PathMatcher::new(&input.exclude_patterns, project.read(cx).path_style(cx)) {
+                Ok(matcher) => Some(matcher),
+                Err(error) => {
+                    return Task::ready(Err(anyhow!("invalid exclude glob pattern: {error}")));
+                }
+            }
+        };
+        let within = match input
+            .within
+            .as_deref()
+            .map(|within| resolve_project_path(project.read(cx), within, cx))
+            .transpose()
+        {
+            Ok(within) => within,
+            Err(error) => return Task::ready(Err(error)),
         };
 
-        // Security checks (mirror read_file behavior)
-        let global_settings = <WorktreeSettings as settings::Settings>::get_global(cx);
-        if global_settings.is_path_excluded(&project_path.path) {
-            return Task::ready(Err(anyhow!(
-                "Cannot read file because its path matches global file_scan_exclusions: {}",
-                &input.path
-            )));
-        }
-        if global_settings.is_path_private(&project_path.path) {
-            return Task::ready(Err(anyhow!(
-                "Cannot read file because its path matches global private_files: {}",
-                &input.path
-            )));
-        }
-        let worktree_settings =
-            <WorktreeSettings as settings::Settings>::get(Some((&project_path).into()), cx);
-        if worktree_settings.is_path_excluded(&project_path.path) {
-            return Task::ready(Err(anyhow!(
-                "Cannot read file because its path matches worktree file_scan_exclusions: {}",
-                &input.path
-            )));
-        }
-        if worktree_settings.is_path_private(&project_path.path) {
-            return Task::ready(Err(anyhow!(
-                "Cannot read file because its path matches worktree private_files: {}",
-                &input.path
-            )));
-        }
+        let target =
+            match AnchorTarget::new(project.read(cx), anchor_memory.read(cx), input.anchor, cx) {
+                Ok(target) => target,
+                Err(error) => return Task::ready(Err(error)),
+            };
+
+        // Only move the agent location when the user opted in, since following the agent
+        // would otherwise jump to every lookup result. Say so when the model asked for a move, so
+        // that it doesn't tell the user to look at a trail that isn't there.
+        let follow_agent =
+            <AgentSettings as settings::Settings>::get_global(cx).follow_agent_navigation;
+        let (navigation, navigation_note) = match navigation {
+            _ if follow_agent => (navigation.unwrap_or_default(), None),
+            None | Some(ReferencesNavigation::Stay) => (ReferencesNavigation::Stay, None),
+            Some(_) => (
+                ReferencesNavigation::Stay,
+                Some(IGNORED_NAVIGATION_NOTE.to_string()),
+            ),
+        };
 
-        // Clone project_path for the async closure and proceed on the async thread.
-        let project_path_clone = project_path.clone();
         cx.spawn(async move |cx| {
-            let project_path = project_path_clone;
-            // Open buffer
-            let buffer = project
-                .update(cx, |project, cx| {
-                    project.open_buffer(project_path.clone(), cx)
-                })?
-                .await?;
+            let ResolvedToken {
+                anchor: input,
+                display_path,
+                buffer,
+                snapshot,
+                unsaved,
+                offset: chosen_offset,
+            } = match target.open(&project, Some(TokenSyntax::Symbol), cx).await? {
+                AnchorResolution::Resolved(token) => token,
+                AnchorResolution::Ambiguous(description) => {
+                    return Ok(LanguageModelToolResultContent::Text(Arc::from(description)));
+                }
+            };
 
-            if buffer.read_with(cx, |buffer, _| {
-                buffer
-                    .file()
-                    .as_ref()
-                    .is_none_or(|file| !file.disk_state().exists())
-            })? {
-                anyhow::bail!("{} not found", &input.path);
+            let handles =
+                anchor_memory.update(cx, |anchor_memory, _| anchor_memory.remember(&input))?;
+            let report_progress = |progress: ReferencesProgress| {
+                event_stream.update_fields(
+                    acp::ToolCallUpdateFields::new().title(progress.title(&input.token)),
+                );
+            };
+            report_progress(ReferencesProgress::ResolvedAnchor {
+                path: display_path.clone(),
+                line: snapshot.offset_to_point(chosen_offset).row + 1,
+            });
+
+            if let Some(revision) = &input.revision {
+                let output = input.describe_at_revision(
+                    revision,
+                    &snapshot,
+                    chosen_offset,
+                    MAX_SCOPE_LINES as u32,
+                );
+                let output = format!("{output}\n\n{}", handles.describe());
+                return Ok(LanguageModelToolResultContent::Text(Arc::from(output)));
             }
 
-            buffer
-                .read_with(cx, |buffer, _| buffer.parsing_idle())?
-                .await;
-
-            // Find candidates by substring search (cheap) and then rely on tree-sitter for scope/validation.
-            // Validate each token occurrence with Tree-sitter via snapshot.syntax_ancestor so that
-            // substrings inside other identifiers (e.g. `is_path_excluded`) are not treated as the token.
-            let candidates: Vec<usize> = buffer.read_with(cx, |buffer, _| {
-                let text = buffer.text();
-                let mut found = Vec::new();
-                let mut start = 0usize;
-                while let Some(pos) = text[start..].find(&input.context) {
-                    let ctx_start = start + pos;
-                    let ctx_end = ctx_start + input.context.len();
-                    let ctx_slice = &text[ctx_start..ctx_end];
-                    let mut inner = 0usize;
-                    while let Some(tokpos) = ctx_slice[inner..].find(&input.token) {
-                        let tok_abs = ctx_start + inner + tokpos;
-
-                        // Validate candidate using tree-sitter via the buffer snapshot.
-                        // Accept if:
-                        // - there is no syntax node available (permissive fallback), OR
-                        // - the ancestor node is named, not a comment/string, and its text equals the token.
-                        let snapshot = buffer.snapshot();
-                        let pt = snapshot.offset_to_point(tok_abs);
-                        let token_point = Point::new(pt.row, pt.column);
-                        let token_point_end = Point::new(
-                            pt.row,
-                            pt.column.saturating_add(input.token.len() as u32),
-                        );
-                        let token_point_range = token_point..token_point_end;
-
-                        // debug logging removed
-
-
-                        let mut accept = false;
-                        if let Some(node) = snapshot.syntax_ancestor(token_point_range.clone()) {
-                            if node.is_named() {
-                                // Prefer a named descendant that exactly covers the token's byte range.
-                                let tok_end = tok_abs + input.token.len();
-                                if let Some(desc) = node.named_descendant_for_byte_range(tok_abs, tok_end) {
-                                    let desc_range = desc.byte_range().to_point(&snapshot);
-                                    let desc_text = snapshot
-                                        .text_for_range(snapshot.anchor_before(desc_range.start)..snapshot.anchor_after(desc_range.end))
-                                        .collect::<String>();
-                                    let desc_kind = desc.kind();
-                                    if desc_text.trim() == input.token && desc_kind != "comment" && desc_kind != "string" {
-                                        accept = true;
-                                    }
-                                } else {
-                                    // Fallback: compare the enclosing node's text (exact match).
-                                    let node_range = node.byte_range().to_point(&snapshot);
-                                    let node_text = snapshot
-                                        .text_for_range(snapshot.anchor_before(node_range.start)..snapshot.anchor_after(node_range.end))
-                                        .collect::<String>();
-                                    let node_kind = node.kind();
-                                    if node_text.trim() == input.token && node_kind != "comment" && node_kind != "string" {
-                                        accept = true;
-                                    }
-                                }
+            let anchor = snapshot.anchor_before(chosen_offset);
+
+            let readiness = buffer.read_with(cx, |buffer, cx| {
+                LanguageServerReadiness::of_buffer(project.read(cx), buffer, cx)
+            })?;
+            let readiness =
+                if readiness != LanguageServerReadiness::Ready && !wait_for_ready.is_zero() {
+                    report_progress(ReferencesProgress::WaitingForLanguageServer);
+                    LanguageServerReadiness::wait(&project, &buffer, wait_for_ready, cx).await?
+                } else {
+                    readiness
+                };
+
+            report_progress(ReferencesProgress::QueryingLanguageServer);
+            let lsp_span = ztracing::info_span!(
+                "lsp_references",
+                duration_ms = ztracing::field::Empty,
+                results = ztracing::field::Empty
+            );
+            let lsp_started_at = Instant::now();
+            let refs = request_language_server(
+                |cx| {
+                    let references = project.update(cx, |project, cx| {
+                        project.references_by_language_server(&buffer, anchor, cx)
+                    });
+                    let project = project.clone();
+                    let event_stream = event_stream.clone();
+                    let token = input.token.clone();
+                    cx.spawn(async move |cx| {
+                        let Some(mut references) = references.await? else {
+                            return Ok(None);
+                        };
+                        let mut responses = Vec::new();
+                        let mut counts = Vec::new();
+                        while let Some((server_id, response)) = references.next().await {
+                            if let Ok(locations) = &response {
+                                let server_name = project.read_with(cx, |project, cx| {
+                                    language_server_name(project, server_id, cx)
+                                })?;
+                                counts.push((server_name, locations.len()));
+                                let progress = ReferencesProgress::ResultsSoFar(counts.clone());
+                                event_stream.update_fields(
+                                    acp::ToolCallUpdateFields::new().title(progress.title(&token)),
+                                );
                             }
-                        } else {
-                            // No parse tree available: permissive fallback — accept candidate.
-                            accept = true;
+                            responses.push((server_id, response));
                         }
+                        language_server_results(responses).map(Some)
+                    })
+                },
+                &event_stream,
+                cx,
+            )
+            .await?;
+            let mut timed_out = refs.response.is_none();
+            let mut retries = refs.retries;
+            let refs = refs.response.transpose()?.flatten();
+            lsp_span.record("duration_ms", lsp_started_at.elapsed().as_millis() as u64);
+            lsp_span.record(
+                "results",
+                refs.iter()
+                    .flatten()
+                    .map(|(_, locations)| locations.len())
+                    .sum::<usize>(),
+            );
 
-                        if accept {
-                            found.push(tok_abs);
-                        }
+            let declarations = if exclude_declaration && !timed_out {
+                let definitions = request_language_server(
+                    |cx| {
+                        let definitions = project.update(cx, |project, cx| {
+                            project.definitions_by_language_server(&buffer, anchor, cx)
+                        });
+                        cx.background_spawn(async move {
+                            definitions.await?.map(language_server_results).transpose()
+                        })
+                    },
+                    &event_stream,
+                    cx,
+                )
+                .await?;
+                retries += definitions.retries;
+                match definitions.response {
+                    Some(definitions) => definitions
+                        .log_err()
+                        .flatten()
+                        .unwrap_or_default()
+                        .into_iter()
+                        .flat_map(|(_, definitions)| definitions)
+                        .collect(),
+                    None => {
+                        // Show the references unfiltered rather than none at all.
+                        timed_out = true;
+                        Vec::new()
+                    }
+                }
+            } else {
+                Vec::new()
+            };
+            let locations = cx.update(|cx| {
+                let project = project.read(cx);
+                let mut locations = merge_language_server_results(
+                    project,
+                    refs.unwrap_or_default(),
+                    |location| location,
+                    cx,
+                );
+                locations.retain(
+                    |LanguageServerResult {
+                         result: location, ..
+                     }| {
+                        !declarations
+                            .iter()
+                            .any(|declaration| overlaps(location, &declaration.target, cx))
+                            && is_path_included(
+                                project,
+                                location,
+                                exclude_matcher.as_ref(),
+                                within.as_ref(),
+                                cx,
+                            )
+                    },
+                );
+                locations
+            })?;
 
-                        inner += tokpos + input.token.len();
-                        if inner >= ctx_slice.len() {
-                            break;
-                        }
+            match navigation {
+                ReferencesNavigation::Stay => {}
+                ReferencesNavigation::First => {
+                    if let Some(LanguageServerResult { result: first, .. }) = locations.first() {
+                        project.update(cx, |project, cx| {
+                            project.set_agent_location(
+                                Some(AgentLocation {
+                                    buffer: first.buffer.downgrade(),
+                                    position: first.range.start,
+                                }),
+                                cx,
+                            );
+                        })?;
                     }
-                    start = ctx_start + 1;
-                    if start >= text.len() {
-                        break;
+                }
+                ReferencesNavigation::Trail => {
+                    if !locations.is_empty() {
+                        let trail = locations
+                            .iter()
+                            .map(
+                                |LanguageServerResult {
+                                     result: location, ..
+                                 }| AgentLocation {
+                                    buffer: location.buffer.downgrade(),
+                                    position: location.range.start,
+                                },
+                            )
+                            .collect();
+                        project.update(cx, |project, cx| {
+                            project.set_agent_location_trail(trail, cx)
+                        })?;
                     }
                 }
-                found
+            }
+
+            let render_span =
+                ztracing::info_span!("render_references", output_len = ztracing::field::Empty);
+            let _enter = render_span.enter();
+            let mut references_per_buffer = HashMap::<EntityId, usize>::default();
+            for LanguageServerResult {
+                result: location, ..
+            } in &locations
+            {
+                *references_per_buffer
+                    .entry(location.buffer.entity_id())
+                    .or_default() += 1;
+            }
+
+            // Show a page of the results, grouped by the file containing them
+            let mut files = Vec::<FileReferences>::new();
+            let mut file_indices = HashMap::<EntityId, usize>::default();
+            for LanguageServerResult {
+                result: loc,
+                language_servers,
+            } in locations.iter().skip(offset).take(max_results)
+            {
+                let location = loc.buffer.read_with(cx, |buffer, cx| {
+                    FindReferencesLocation::new(
+                        project.read(cx),
+                        buffer,
+                        loc.range.clone(),
+                        excerpt_context_lines,
+                        language_servers.clone(),
+                        cx,
+                    )
+                })?;
+                let buffer_id = loc.buffer.entity_id();
+                let file_index = *file_indices.entry(buffer_id).or_insert_with(|| {
+                    files.push(FileReferences {
+                        path: location.display_path(),
+                        total: references_per_buffer
+                            .get(&buffer_id)
+                            .copied()
+                            .unwrap_or_default(),
+                        locations: Vec::new(),
+                    });
+                    files.len() - 1
+                });
+                files[file_index].locations.push(location);
+            }
+
+            // Only count the locations' own rendering against the budget, which leaves the
+            // headers and notes around them some room.
+            let rendered_locations = files
+                .iter()
+                .flat_map(|file| {
+                    file.locations.iter().map(|location| {
+                        let mut text = String::new();
+                        location.render(&mut text, false);
+                        text
+                    })
+                })
+                .collect();
+            let truncated =
+                truncate_results(rendered_locations, event_stream.token_budget(), cx).await;
+            let mut remaining = truncated.kept;
+            for file in &mut files {
+                let kept = file.locations.len().min(remaining);
+                file.locations.truncate(kept);
+                remaining -= kept;
+            }
+            files.retain(|file| !file.locations.is_empty());
+
+            let shown = truncated.kept;
+            let has_more = locations.len() > offset + shown;
+            // Whether or not the agent location moved, list the shown references on the tool
+            // call, which holds only those to keep the update small.
+            cx.update(|cx| {
+                report_locations(
+                    &event_stream,
+                    &input,
+                    locations.iter().skip(offset).take(shown).map(
+                        |LanguageServerResult {
+                             result: location, ..
+                         }| location,
+                    ),
+                    cx,
+                )
             })?;
+            report_progress(ReferencesProgress::Done {
+                references: locations.len(),
+                files: references_per_buffer.len(),
+            });
+            let output = LanguageModelToolResultContent::from(FindReferencesByContextToolOutput {
+                files,
+                total_references: locations.len(),
+                total_files: references_per_buffer.len(),
+                offset,
+                has_more,
+                next_offset: has_more.then_some(offset + shown),
+                handle: handles.reference,
+                symbol_handle: handles.symbol,
+                unsaved_changes_note: unsaved.then(|| unsaved_changes_note(&display_path)),
+                timed_out,
+                readiness_note: readiness.note(),
+                omitted_to_fit: truncated.omitted,
+                retries,
+                navigation_note,
+            });
+            if let LanguageModelToolResultContent::Text(text) = &output {
+                render_span.record("output_len", text.len());
+            }
+            Ok(output)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        RecordedToolEvent, ToolTestProject, assert_snapshot, init_test, recorded_locations,
+        run_tool,
+    };
+    use acp_thread::{LocationAnchor, LocationDetail};
+    use gpui::{AppContext as _, TestAppContext, UpdateGlobal as _};
+    use language::Point;
+    use serde_json::json;
+    use settings::SettingsStore;
+    use std::{
+        path::Path,
+        sync::atomic::{AtomicUsize, Ordering::SeqCst},
+    };
+    use text::ToPoint as _;
+    use util::path;
+
+    #[gpui::test]
+    async fn test_find_references_with_fake_language_server(cx: &mut TestAppContext) {
+        init_test(cx);
+        let (output, events, _) = find_helper_references(None, None, cx).await;
+
+        let titles = events
+            .iter()
+            .filter_map(|event| match event {
+                RecordedToolEvent::Fields(fields) => fields.title.clone(),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            titles,
+            [
+                "Finding references to `helper` at root/src/main.rs:6",
+                "Finding references to `helper`: querying the language server",
+                "Finding references to `helper`: 2 results so far (fake-rust-analyzer: 2)",
+                "Found 2 references to `helper` in 1 file",
+            ]
+        );
+        let LanguageModelToolResultContent::Text(text) = output.expect("tool failed") else {
+            panic!("expected text output");
+        };
+        assert_eq!(text.matches("[L1-9] call\n").count(), 2, "{text}");
+
+        // The references are listed on the tool call even though the agent location stays put.
+        let locations = recorded_locations(&events);
+        let [first, second] = locations.as_slice() else {
+            panic!("expected two locations, got {locations:?}");
+        };
+        assert_eq!(first.path, Path::new(path!("/root/src/main.rs")));
+        assert_eq!((first.line, second.line), (Some(5), Some(6)));
+        let detail = LocationDetail::from_location(first).expect("location has no detail");
+        assert_eq!(detail.columns, Some(16..22));
+        assert_eq!(
+            detail.anchor,
+            Some(LocationAnchor {
+                path: "root/src/main.rs".into(),
+                context: "let first = helper();".into(),
+                token: "helper".into(),
+                index: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            })
+        );
+        assert!(detail.version.is_some(), "{detail:?}");
+        assert_snapshot("find_references_by_context_reference_list", &text);
+    }
+
+    #[gpui::test]
+    async fn test_find_references_with_excerpts(cx: &mut TestAppContext) {
+        init_test(cx);
+        let (output, _, _) = find_helper_references(Some(1), None, cx).await;
+        let LanguageModelToolResultContent::Text(text) = output.expect("tool failed") else {
+            panic!("expected text output");
+        };
+        assert!(
+            text.starts_with(
+                "Found 2 references in 1 file.\n\n## root/src/main.rs (2 references)\n\n[L5-7] call\n```\nfn main() {\n    let first = helper();\n    let second = helper();\n```\nAnchor: {\"path\":\"root/src/main.rs\",\"context\":\"first = helper()\",\"token\":\"helper\"}\n\n[L6-8] call\n```\n    let first = helper();\n    let second = helper();\n}\n```\nAnchor: {\"path\":\"root/src/main.rs\",\"context\":\"second = helper()\",\"token\":\"helper\"}\n"
+            ),
+            "{text}"
+        );
+    }
+
+    #[gpui::test]
+    async fn test_find_references_reports_each_reference_file(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "mod util;\n\npub fn helper() -> u32 {\n    1\n}\n\nfn main() {\n    helper();\n}\n",
+                    "util.rs": "pub fn twice() -> u32 {\n    crate::helper() * 2\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let mut fake_servers = test_project.register_fake_rust_server(
+            lsp::ServerCapabilities {
+                references_provider: Some(lsp::OneOf::Left(true)),
+                ..Default::default()
+            },
+            cx,
+        );
+        let (_buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/main.rs", cx).await;
+        let fake_server = fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+
+        let references = vec![
+            test_project.lsp_location("src/main.rs", (7, 4), (7, 10)),
+            test_project.lsp_location("src/util.rs", (1, 11), (1, 17)),
+        ];
+        fake_server.set_request_handler::<lsp::request::References, _, _>(move |_, _| {
+            let references = references.clone();
+            async move { Ok(Some(references)) }
+        });
+
+        let tool = Arc::new(FindReferencesByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let (output, _) = run_tool(
+            tool,
+            FindReferencesByContextToolInput {
+                anchor: ContextualAnchor {
+                    path: "root/src/main.rs".into(),
+                    context: "pub fn helper() -> u32 {".into(),
+                    token: "helper".into(),
+                    index: None,
+                    handle: None,
+                    revision: None,
+                    whole_word: false,
+                    approximate_line: None,
+                    case_insensitive: false,
+                    unicode_normalize: false,
+                    end_token: None,
+                    end_index: None,
+                },
+                excerpt_context_lines: Some(0),
+                exclude_declaration: false,
+                exclude_patterns: Vec::new(),
+                within: None,
+                offset: 0,
+                max_results: None,
+                navigation: None,
+                wait_for_ready_ms: 0,
+            },
+            cx,
+        )
+        .await;
+        let LanguageModelToolResultContent::Text(text) = output.expect("tool failed") else {
+            panic!("expected text output");
+        };
+        assert!(
+            text.starts_with(
+                "Found 2 references in 2 files.\n\n## root/src/main.rs (1 reference)\n\n[L8-8] call\n```\n    helper();\n```\nAnchor: {\"path\":\"root/src/main.rs\",\"context\":\"helper();\",\"token\":\"helper\"}\n\n## root/src/util.rs (1 reference)\n\n[L2-2] call\n```\n    crate::helper() * 2\n```\nAnchor: {\"path\":\"root/src/util.rs\",\"context\":\":helper(\",\"token\":\"helper\"}\n"
+            ),
+            "{text}"
+        );
+
+        let (util_buffer, _util_lsp_handle) =
+            test_project.open_buffer_with_lsp("src/util.rs", cx).await;
+        let location = cx.update(|cx| {
+            let buffer = util_buffer.read(cx);
+            let snapshot = buffer.snapshot();
+            FindReferencesLocation::new(
+                test_project.project.read(cx),
+                buffer,
+                snapshot.anchor_before(Point::new(1, 11))..snapshot.anchor_after(Point::new(1, 17)),
+                Some(0),
+                Vec::new(),
+                cx,
+            )
+        });
+        assert_eq!(location.path.as_deref(), Some("root/src/util.rs"));
+        assert_eq!(
+            location
+                .anchor
+                .as_ref()
+                .map(|anchor| anchor.context.as_str()),
+            Some(":helper(")
+        );
+        assert_eq!(
+            location.abs_path.as_deref(),
+            Some(Path::new(path!("/root/src/util.rs")))
+        );
+    }
+
+    #[gpui::test]
+    async fn test_find_references_merges_language_servers(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "fn helper() -> u32 {\n    1\n}\n\nfn main() {\n    let first = helper();\n    let second = helper();\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let capabilities = lsp::ServerCapabilities {
+            references_provider: Some(lsp::OneOf::Left(true)),
+            ..Default::default()
+        };
+        let mut rust_servers = test_project.register_fake_rust_server(capabilities.clone(), cx);
+        let mut other_servers = test_project.project.read_with(cx, |project, _| {
+            project.languages().register_fake_lsp(
+                "Rust",
+                language::FakeLspAdapter {
+                    name: "fake-other-server",
+                    capabilities,
+                    ..Default::default()
+                },
+            )
+        });
+        let (_buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/main.rs", cx).await;
+        let rust_server = rust_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+        let other_server = other_servers
+            .next()
+            .await
+            .expect("fake server was not started");
 
-            if candidates.is_empty() {
-                anyhow::bail!("context/token not found in file");
+        let first = test_project.lsp_location("src/main.rs", (5, 16), (5, 22));
+        let second = test_project.lsp_location("src/main.rs", (6, 17), (6, 23));
+        rust_server.set_request_handler::<lsp::request::References, _, _>({
+            let references = vec![first.clone(), second];
+            move |_, _| {
+                let references = references.clone();
+                async move { Ok(Some(references)) }
             }
+        });
+        other_server.set_request_handler::<lsp::request::References, _, _>(move |_, _| {
+            let references = vec![first.clone()];
+            async move { Ok(Some(references)) }
+        });
 
-            // Choose a candidate by index or produce an ambiguous listing with richer previews.
-            let chosen_offset = if let Some(idx) = input.index {
-                let idx_usize = idx as usize;
-                if idx_usize >= candidates.len() {
-                    anyhow::bail!("index out of range ({} candidates)", candidates.len());
+        let tool = Arc::new(FindReferencesByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let (output, events) = run_tool(
+            tool,
+            FindReferencesByContextToolInput {
+                anchor: ContextualAnchor {
+                    path: "root/src/main.rs".into(),
+                    context: "let first = helper();".into(),
+                    token: "helper".into(),
+                    index: None,
+                    handle: None,
+                    revision: None,
+                    whole_word: false,
+                    approximate_line: None,
+                    case_insensitive: false,
+                    unicode_normalize: false,
+                    end_token: None,
+                    end_index: None,
+                },
+                excerpt_context_lines: Some(0),
+                exclude_declaration: false,
+                exclude_patterns: Vec::new(),
+                within: None,
+                offset: 0,
+                max_results: None,
+                navigation: None,
+                wait_for_ready_ms: 0,
+            },
+            cx,
+        )
+        .await;
+        let LanguageModelToolResultContent::Text(text) = output.expect("tool failed") else {
+            panic!("expected text output");
+        };
+        assert!(
+            text.starts_with("Found 2 references in 1 file.\n"),
+            "{text}"
+        );
+        let results_so_far = events
+            .iter()
+            .filter_map(|event| match event {
+                RecordedToolEvent::Fields(fields) => fields.title.clone(),
+                _ => None,
+            })
+            .filter(|title| title.contains(" so far "))
+            .collect::<Vec<_>>();
+        // Each server's results are reported as it answers, in whichever order that is.
+        assert_eq!(results_so_far.len(), 2, "{results_so_far:?}");
+        let last = &results_so_far[1];
+        assert!(
+            last.starts_with("Finding references to `helper`: 3 results so far (")
+                && last.contains("fake-rust-analyzer: 2")
+                && last.contains("fake-other-server: 1"),
+            "{last}"
+        );
+        assert!(
+            text.contains("[L6-6] call (fake-rust-analyzer, fake-other-server)\n")
+                || text.contains("[L6-6] call (fake-other-server, fake-rust-analyzer)\n"),
+            "{text}"
+        );
+        assert!(
+            text.contains("[L7-7] call (fake-rust-analyzer)\n"),
+            "{text}"
+        );
+    }
+
+    #[gpui::test]
+    async fn test_find_references_with_filters(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "mod util;\n\npub fn helper() -> u32 {\n    1\n}\n\nfn main() {\n    helper();\n}\n",
+                    "util.rs": "pub fn twice() -> u32 {\n    crate::helper() * 2\n}\n"
+                },
+                "tests": {
+                    "smoke.rs": "fn smoke() {\n    helper();\n}\n"
                 }
-                candidates[idx_usize]
-            } else if candidates.len() == 1 {
-                candidates[0]
-            } else {
-                // Ambiguous: produce multi-line, syntax-aware previews for each match so the LLM can pick index.
-                // Implementation split into two phases:
-                // 1) compute preferred preview ranges (Point start/end) for each candidate and merge overlapping ranges
-                // 2) extract text for each merged range once and then map candidates into those previews
-                let out = buffer.read_with(cx, |buffer, _| {
-                    let snapshot = buffer.snapshot();
-
-                    // Collect candidate info: (candidate_index, token_row, preview_start_point, preview_end_point)
-                    let mut candidate_infos: Vec<(usize, u32, Point, Point)> = Vec::new();
-                    for (i, &off) in candidates.iter().enumerate() {
-                        let pt = snapshot.offset_to_point(off);
-                        let token_point = Point::new(pt.row, pt.column);
-                        let token_point_end = Point::new(
-                            pt.row,
-                            pt.column.saturating_add(input.token.len() as u32),
-                        );
-
-                        // Default small-clamped preview around token row (-2..+2 lines)
-                        let start_row = pt.row.saturating_sub(2);
-                        let end_row = (pt.row + 2).min(snapshot.max_point().row);
-                        let mut preview_start = Point::new(start_row, 0);
-                        let mut preview_end = Point::new(end_row, snapshot.line_len(end_row));
-
-                        // Prefer enclosing syntax node scope; climb to the largest ancestor within MAX_SCOPE_LINES
-                        if let Some(node) = snapshot.syntax_ancestor(token_point..token_point_end) {
-                            let mut candidate_node = node;
-                            loop {
-                                if let Some(parent) = candidate_node.parent() {
-                                    let parent_range = parent.byte_range().to_point(&snapshot);
-                                    let parent_span_lines =
-                                        parent_range.end.row.saturating_sub(parent_range.start.row);
-                                    if (parent_span_lines as usize) <= MAX_SCOPE_LINES {
-                                        candidate_node = parent;
-                                        continue;
-                                    }
-                                }
-                                break;
-                            }
-                            let full_range = candidate_node.byte_range().to_point(&snapshot);
-                            preview_start = Point::new(full_range.start.row, 0);
-                            preview_end = Point::new(full_range.end.row, snapshot.line_len(full_range.end.row));
-                        }
+            }),
+            cx,
+        )
+        .await;
+        let mut fake_servers = test_project.register_fake_rust_server(
+            lsp::ServerCapabilities {
+                references_provider: Some(lsp::OneOf::Left(true)),
+                definition_provider: Some(lsp::OneOf::Left(true)),
+                ..Default::default()
+            },
+            cx,
+        );
+        let (_buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/main.rs", cx).await;
+        let fake_server = fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
 
-                        candidate_infos.push((i, pt.row, preview_start, preview_end));
-                    }
+        let declaration = test_project.lsp_location("src/main.rs", (2, 7), (2, 13));
+        let references = vec![
+            declaration.clone(),
+            test_project.lsp_location("src/main.rs", (7, 4), (7, 10)),
+            test_project.lsp_location("src/util.rs", (1, 11), (1, 17)),
+            test_project.lsp_location("tests/smoke.rs", (1, 4), (1, 10)),
+        ];
+        fake_server.set_request_handler::<lsp::request::References, _, _>(move |_, _| {
+            let references = references.clone();
+            async move { Ok(Some(references)) }
+        });
+        fake_server.set_request_handler::<lsp::request::GotoDefinition, _, _>(move |_, _| {
+            let declaration = declaration.clone();
+            async move { Ok(Some(lsp::GotoDefinitionResponse::Scalar(declaration))) }
+        });
 
-                    // Sort candidate infos by preview start row to make merging easy
-                    candidate_infos.sort_by_key(|(_, _tokrow, s, _e)| s.row);
-
-                    // Merge overlapping/adjacent ranges (by row), collecting candidate indices per merged range
-                    let mut merged: Vec<(u32, u32, Point, Point, Vec<usize>)> = Vec::new();
-                    for (idx, _tokrow, s_pt, e_pt) in candidate_infos.iter() {
-                        let srow = s_pt.row;
-                        let erow = e_pt.row;
-                        if let Some(last) = merged.last_mut() {
-                            // if this start row intersects or touches previous range, merge it
-                            if srow <= last.1 {
-                                if erow > last.1 {
-                                    last.1 = erow;
-                                    last.3 = *e_pt;
-                                }
-                                last.4.push(*idx);
-                                continue;
-                            }
-                        }
-                        merged.push((srow, erow, *s_pt, *e_pt, vec![*idx]));
-                    }
+        let tool = Arc::new(FindReferencesByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let input = |exclude_declaration: bool, exclude_patterns: &[&str], within: Option<&str>| {
+            FindReferencesByContextToolInput {
+                anchor: ContextualAnchor {
+                    path: "root/src/main.rs".into(),
+                    context: "pub fn helper() -> u32 {".into(),
+                    token: "helper".into(),
+                    index: None,
+                    handle: None,
+                    revision: None,
+                    whole_word: false,
+                    approximate_line: None,
+                    case_insensitive: false,
+                    unicode_normalize: false,
+                    end_token: None,
+                    end_index: None,
+                },
+                excerpt_context_lines: Some(0),
+                exclude_declaration,
+                exclude_patterns: exclude_patterns
+                    .iter()
+                    .map(|pattern| pattern.to_string())
+                    .collect(),
+                within: within.map(str::to_string),
+                offset: 0,
+                max_results: None,
+                navigation: None,
+                wait_for_ready_ms: 0,
+            }
+        };
+        let summary = |output: Result<LanguageModelToolResultContent>| {
+            let LanguageModelToolResultContent::Text(text) = output.expect("tool failed") else {
+                panic!("expected text output");
+            };
+            text.lines().next().unwrap_or_default().to_string()
+        };
 
-                    // Extract text previews for each merged range
-                    let mut merged_previews: Vec<String> = Vec::new();
-                    for (_srow, _erow, start_pt, end_pt, _cidxs) in merged.iter() {
-                        let text = snapshot
-                            .text_for_range(snapshot.anchor_before(start_pt)..snapshot.anchor_after(end_pt))
-                            .collect::<String>();
-                        merged_previews.push(text);
-                    }
+        let (output, _) = run_tool(tool.clone(), input(false, &[], None), cx).await;
+        assert_eq!(summary(output), "Found 4 references in 3 files.");
 
-                    // Map candidate index -> merged preview index and compute display row
-                    // We'll build a vec entries in candidate order for deterministic output
-                    let mut candidate_to_preview: Vec<(usize, usize, u32)> = Vec::new(); // (candidate_idx, merged_idx, display_row)
-                    for (merged_idx, (_srow, _erow, _s_pt, _e_pt, cidxs)) in merged.iter().enumerate() {
-                        for &c in cidxs.iter() {
-                            // find the token row for candidate c from candidate_infos
-                            // candidate_infos was sorted; find the tuple where first element == c
-                            let token_row = candidate_infos
-                                .iter()
-                                .find(|(ci, _, _, _)| *ci == c)
-                                .map(|(_, tok_row, _, _)| *tok_row)
-                                .unwrap_or(0);
-                            candidate_to_preview.push((c, merged_idx, token_row + 1)); // display rows are 1-based
-                        }
-                    }
+        let (output, _) = run_tool(tool.clone(), input(true, &[], None), cx).await;
+        assert_eq!(summary(output), "Found 3 references in 3 files.");
 
-                    // Sort candidate_to_preview by candidate index so we output in candidate order
-                    candidate_to_preview.sort_by_key(|(c, _, _)| *c);
+        let (output, _) = run_tool(tool.clone(), input(false, &["**/tests/**"], None), cx).await;
+        assert_eq!(summary(output), "Found 3 references in 2 files.");
 
-                    // Build final ambiguous output string
-                    let mut out = format!("Ambiguous token: found {} matches in {}:\n\n", candidates.len(), input.path);
-                    for (_c, merged_idx, row) in candidate_to_preview.iter() {
-                        let preview = &merged_previews[*merged_idx];
-                        out.push_str(&format!("[{}] L{}:\n\n``` \n{}\n```\n\n", _c, row, preview.trim()));
-                    }
-                    out.push_str("\nProvide `index` (0-based) to disambiguate.");
-                    out
-                })?;
-                return Ok(LanguageModelToolResultContent::Text(Arc::from(out)));
+        let (output, _) = run_tool(tool.clone(), input(true, &[], Some("root/src")), cx).await;
+        assert_eq!(summary(output), "Found 2 references in 2 files.");
+
+        let (output, _) = run_tool(tool.clone(), input(false, &[], Some("root/missing")), cx).await;
+        assert!(output.is_err());
+
+        let mut paged = input(false, &[], None);
+        paged.offset = 1;
+        paged.max_results = Some(2);
+        let (output, _) = run_tool(tool.clone(), paged, cx).await;
+        let LanguageModelToolResultContent::Text(text) = output.expect("tool failed") else {
+            panic!("expected text output");
+        };
+        assert!(
+            text.contains("[L8-8] call\n```\n    helper();\n```\nAnchor: {\"path\":\"root/src/main.rs\",\"context\":\"helper();\",\"token\":\"helper\"}\n\n## root/src/util.rs (1 reference)\n\n[L2-2] call\n```\n    crate::helper() * 2\n```\nAnchor: {\"path\":\"root/src/util.rs\",\"context\":\":helper(\",\"token\":\"helper\"}\n\nShowing references 2-3 (there were more references found; use offset: 3 to see next page)\n"),
+            "{text}"
+        );
+
+        let mut past_end = input(false, &[], None);
+        past_end.offset = 10;
+        let (output, _) = run_tool(tool, past_end, cx).await;
+        assert_eq!(
+            summary(output),
+            "No more references after offset 10 (total references: 4)."
+        );
+    }
+
+    #[gpui::test]
+    fn test_usage_kind(cx: &mut TestAppContext) {
+        let text = "use crate::config::Config;\n\nfn bump(counter: &mut Counter, config: Config) {\n    counter.count += 1;\n    counter.count = config.limit();\n    let total = counter.count;\n    bump(counter, config);\n}\n";
+        let buffer = cx.new(|cx| Buffer::local(text, cx).with_language(language::rust_lang(), cx));
+        cx.run_until_parked();
+        let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot());
+        // A leading `.` in `needle` is only there to tell a field from a longer identifier.
+        let usage_kind_at = |line: usize, needle: &str| {
+            let line_start = text
+                .split_inclusive('\n')
+                .take(line)
+                .map(str::len)
+                .sum::<usize>();
+            let token = needle.trim_start_matches('.');
+            let start = line_start
+                + text[line_start..].find(needle).expect("needle not found")
+                + (needle.len() - token.len());
+            usage_kind(&snapshot, start..start + token.len())
+        };
+
+        assert_eq!(usage_kind_at(0, "Config"), Some(ReferenceUsageKind::Import));
+        assert_eq!(
+            usage_kind_at(2, "bump"),
+            Some(ReferenceUsageKind::Declaration)
+        );
+        assert_eq!(usage_kind_at(2, "Counter"), Some(ReferenceUsageKind::Type));
+        assert_eq!(usage_kind_at(3, ".count"), Some(ReferenceUsageKind::Write));
+        assert_eq!(usage_kind_at(4, ".count"), Some(ReferenceUsageKind::Write));
+        assert_eq!(usage_kind_at(4, ".limit"), Some(ReferenceUsageKind::Call));
+        assert_eq!(usage_kind_at(5, ".count"), Some(ReferenceUsageKind::Read));
+        assert_eq!(usage_kind_at(6, "bump"), Some(ReferenceUsageKind::Call));
+        assert_eq!(usage_kind_at(6, "config"), Some(ReferenceUsageKind::Read));
+    }
+
+    #[gpui::test]
+    async fn test_find_references_notes_indexing_language_server(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "fn helper() -> u32 {\n    1\n}\n\nfn main() {\n    helper();\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let mut fake_servers = test_project.register_fake_rust_server(
+            lsp::ServerCapabilities {
+                references_provider: Some(lsp::OneOf::Left(true)),
+                ..Default::default()
+            },
+            cx,
+        );
+        let (_buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/main.rs", cx).await;
+        let fake_server = fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+        fake_server.set_request_handler::<lsp::request::References, _, _>(|_, _| async move {
+            Ok(Some(Vec::new()))
+        });
+        fake_server.start_progress("indexing").await;
+        cx.run_until_parked();
+
+        let tool = Arc::new(FindReferencesByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let input = |wait_for_ready_ms| FindReferencesByContextToolInput {
+            anchor: ContextualAnchor {
+                path: "root/src/main.rs".into(),
+                context: "fn helper() -> u32 {".into(),
+                token: "helper".into(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            excerpt_context_lines: None,
+            exclude_declaration: false,
+            exclude_patterns: Vec::new(),
+            within: None,
+            offset: 0,
+            max_results: None,
+            navigation: None,
+            wait_for_ready_ms,
+        };
+        let text = |output: Result<LanguageModelToolResultContent>| {
+            let LanguageModelToolResultContent::Text(text) = output.expect("tool failed") else {
+                panic!("expected text output");
             };
+            text
+        };
+        let indexing_note = "The language server (fake-rust-analyzer) was still indexing, so results may be missing. Pass `wait_for_ready_ms` to wait for indexing to finish.\n\nNo references found";
 
-            // Anchor for references
-            let anchor = buffer.read_with(cx, |buffer, _| {
-                let snapshot = buffer.snapshot();
-                let point = snapshot.offset_to_point(chosen_offset);
-                snapshot.anchor_before(point)
-            })?;
+        let waited = |events: &[RecordedToolEvent]| {
+            events.iter().any(|event| match event {
+                RecordedToolEvent::Fields(fields) => {
+                    fields.title.as_deref()
+                        == Some("Finding references to `helper`: waiting for the language server")
+                }
+                _ => false,
+            })
+        };
 
-            // Call project.references
-            let refs_task =
-                project.update(cx, |project, cx| project.references(&buffer, anchor, cx))?;
-            let refs = refs_task.await?;
-
-            let output = match refs {
-                Some(locs) if !locs.is_empty() => {
-                    let mut out = String::new();
-                    let total = locs.len();
-                    // Paginate: include up to PAGINATE_LIMIT results
-                    for loc in locs.into_iter().take(PAGINATE_LIMIT) {
-                        // render preview for each location using tree-sitter scope (or fallback)
-                        let (start_line, end_line, preview, maybe_path) =
-                            loc.buffer.read_with(cx, |buffer, cx| {
-                                let snapshot = buffer.snapshot();
-                                let start_pt = loc.range.start.to_point(&snapshot);
-                                let end_pt = loc.range.end.to_point(&snapshot);
-
-                                // Try syntax ancestor for the full logical scope, but climb to a larger parent
-                                // when available so we return the most useful context that's still <= MAX_SCOPE_LINES.
-                                if let Some(node) = snapshot.syntax_ancestor(start_pt..end_pt) {
-                                    // climb to the largest ancestor within MAX_SCOPE_LINES
-                                    let mut candidate = node;
-                                    loop {
-                                        if let Some(parent) = candidate.parent() {
-                                            let parent_range = parent.byte_range().to_point(&snapshot);
-                                            let parent_span_lines =
-                                                parent_range.end.row.saturating_sub(parent_range.start.row);
-                                            if (parent_span_lines as usize) <= MAX_SCOPE_LINES {
-                                                candidate = parent;
-                                                continue;
-                                            }
-                                        }
-                                        break;
-                                    }
-                                    let node_range = candidate.byte_range().to_point(&snapshot);
-                                    let span_lines =
-                                        node_range.end.row.saturating_sub(node_range.start.row);
-                                    if (span_lines as usize) <= MAX_SCOPE_LINES {
-                                        let start_anchor = Point::new(node_range.start.row, 0);
-                                        let end_row = node_range.end.row;
-                                        let end_anchor = Point::new(end_row, snapshot.line_len(end_row));
-                                        let preview = snapshot
-                                            .text_for_range(
-                                                snapshot.anchor_before(start_anchor)
-                                                    ..snapshot.anchor_after(end_anchor),
-                                            )
-                                            .collect::<String>();
-                                        let path = buffer.file().map(|f| f.full_path(cx));
-                                        (node_range.start.row + 1, node_range.end.row + 1, preview, path)
-                                    } else {
-                                        // If the syntax node is too large, provide a clamped preview around node start
-                                        let preview_start_row = node_range.start.row.saturating_sub(10);
-                                        let preview_end_row = (node_range.start.row + 9).min(snapshot.max_point().row);
-                                        let start_anchor = Point::new(preview_start_row, 0);
-                                        let end_anchor = Point::new(preview_end_row, snapshot.line_len(preview_end_row));
-                                        let preview = snapshot
-                                            .text_for_range(
-                                                snapshot.anchor_before(start_anchor)..snapshot.anchor_after(end_anchor),
-                                            )
-                                            .collect::<String>();
-                                        let path = buffer.file().map(|f| f.full_path(cx));
-                                        (node_range.start.row + 1, node_range.end.row + 1, preview, path)
-                                    }
-                                } else {
-                                    // fallback to -10..+9 around start_pt
-                                    let preview_start_row = start_pt.row.saturating_sub(10);
-                                    let preview_end_row = (start_pt.row + 9).min(snapshot.max_point().row);
-                                    let start_anchor = Point::new(preview_start_row, 0);
-                                    let end_anchor = Point::new(preview_end_row, snapshot.line_len(preview_end_row));
-                                    let preview = snapshot
-                                        .text_for_range(
-                                            snapshot.anchor_before(start_anchor)..snapshot.anchor_after(end_anchor),
-                                        )
-                                        .collect::<String>();
-                                    let path = buffer.file().map(|f| f.full_path(cx));
-                                    (start_pt.row + 1, end_pt.row + 1, preview, path)
-                                }
-                            })?;
-
-                        let path_display = maybe_path
-                            .as_ref()
-                            .map(|p: &std::path::PathBuf| p.display().to_string())
-                            .unwrap_or_else(|| "<buffer>".to_string());
-                        out.push_str(&format!(
-                            "{} [L{}-{}]\n\n",
-                            path_display, start_line, end_line
-                        ));
-                        out.push_str("```\n");
-                        out.push_str(&preview);
-                        out.push_str("\n```\n\n");
-                    }
+        let (output, events) = run_tool(tool.clone(), input(0), cx).await;
+        assert!(text(output).starts_with(indexing_note));
+        assert!(!waited(&events));
+
+        let (output, events) = run_tool(tool.clone(), input(1000), cx).await;
+        assert!(text(output).starts_with(indexing_note));
+        assert!(waited(&events));
 
-                    if total > PAGINATE_LIMIT {
-                        out.push_str(&format!(
-                            "... ({} more results omitted) - request additional pages to see more.\n",
-                            total - PAGINATE_LIMIT
-                        ));
+        fake_server.end_progress("indexing");
+        cx.run_until_parked();
+        let (output, _) = run_tool(tool, input(1000), cx).await;
+        assert!(text(output).starts_with("No references found"));
+    }
+
+    #[gpui::test]
+    async fn test_find_references_retries_content_modified(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "fn helper() -> u32 {\n    1\n}\n\nfn main() {\n    helper();\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let mut fake_servers = test_project.register_fake_rust_server(
+            lsp::ServerCapabilities {
+                references_provider: Some(lsp::OneOf::Left(true)),
+                ..Default::default()
+            },
+            cx,
+        );
+        let (_buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/main.rs", cx).await;
+        let fake_server = fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+        let references = vec![test_project.lsp_location("src/main.rs", (5, 4), (5, 10))];
+        let requests = Arc::new(AtomicUsize::new(0));
+        fake_server.set_request_handler::<lsp::request::References, _, _>({
+            let requests = requests.clone();
+            move |_, _| {
+                let references = references.clone();
+                let first_request = requests.fetch_add(1, SeqCst) == 0;
+                async move {
+                    if first_request {
+                        // Not the usual message, so that only the error code marks it transient.
+                        Err(anyhow::Error::new(lsp::LspResponseError {
+                            code: lsp::error_codes::CONTENT_MODIFIED,
+                            message: "the file changed".into(),
+                        }))
+                    } else {
+                        Ok(Some(references))
                     }
+                }
+            }
+        });
 
-                    out
+        let tool = Arc::new(FindReferencesByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let input = FindReferencesByContextToolInput {
+            anchor: ContextualAnchor {
+                path: "root/src/main.rs".into(),
+                context: "fn helper() -> u32 {".into(),
+                token: "helper".into(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            excerpt_context_lines: Some(0),
+            exclude_declaration: false,
+            exclude_patterns: Vec::new(),
+            within: None,
+            offset: 0,
+            max_results: None,
+            navigation: None,
+            wait_for_ready_ms: 0,
+        };
+        let (event_stream, _events) = ToolCallEventStream::test();
+        let output = cx.update(|cx| tool.run(input, event_stream, cx));
+        cx.executor()
+            .advance_clock(crate::tools::LANGUAGE_SERVER_RETRY_DELAY * 2);
+        cx.run_until_parked();
+        let LanguageModelToolResultContent::Text(text) = output.await.expect("tool failed") else {
+            panic!("expected text output");
+        };
+        assert_eq!(requests.load(SeqCst), 2);
+        assert!(text.starts_with("Found 1 reference"), "{text}");
+        assert!(text.contains("retried 1 time(s)"), "{text}");
+    }
+
+    #[test]
+    fn test_render_references_grouped_by_file() {
+        let location = |path: &str, line: u32| FindReferencesLocation {
+            path: Some(path.to_string()),
+            abs_path: None,
+            start_line: line,
+            end_line: line,
+            preview: "helper();".to_string(),
+            usage_kind: None,
+            language_servers: Vec::new(),
+            anchor: None,
+        };
+        let output = FindReferencesByContextToolOutput {
+            files: vec![
+                FileReferences {
+                    path: "root/src/a.rs".to_string(),
+                    total: 1,
+                    locations: vec![location("root/src/a.rs", 3)],
+                },
+                FileReferences {
+                    path: "root/src/b.rs".to_string(),
+                    total: 30,
+                    locations: vec![location("root/src/b.rs", 1), location("root/src/b.rs", 7)],
+                },
+            ],
+            total_references: 40,
+            total_files: 3,
+            offset: 0,
+            has_more: true,
+            next_offset: Some(3),
+            handle: "ref#1".to_string(),
+            symbol_handle: None,
+            unsaved_changes_note: None,
+            timed_out: false,
+            readiness_note: None,
+            omitted_to_fit: 0,
+            retries: 0,
+            navigation_note: None,
+        };
+        let LanguageModelToolResultContent::Text(text) = output.into() else {
+            panic!("expected text output");
+        };
+        assert_eq!(
+            text.as_ref(),
+            "Found 40 references in 3 files.\n\n\
+            ## root/src/a.rs (1 reference)\n\n[L3-3]\n```\nhelper();\n```\n\n\
+            ## root/src/b.rs (30 references, 2 shown)\n\n\
+            [L1-1]\n```\nhelper();\n```\n\n[L7-7]\n```\nhelper();\n```\n\n\
+            Showing references 1-3 (there were more references found; use offset: 3 to see next page)\n\n\
+            Later tool calls can refer to this anchor with `\"handle\": \"ref#1\"`.\n"
+        );
+    }
+
+    #[gpui::test]
+    async fn test_follow_agent_navigation_moves_to_first_reference(cx: &mut TestAppContext) {
+        init_test(cx);
+        cx.update(|cx| {
+            SettingsStore::update_global(cx, |store, cx| {
+                store.update_user_settings(cx, |settings| {
+                    settings
+                        .agent
+                        .get_or_insert_default()
+                        .follow_agent_navigation = Some(true);
+                });
+            });
+        });
+        let (output, events, project) =
+            find_helper_references(None, Some(ReferencesNavigation::First), cx).await;
+        output.expect("tool failed");
+
+        assert_eq!(recorded_locations(&events).len(), 2);
+        project.read_with(cx, |project, cx| {
+            let location = project.agent_location().expect("no agent location");
+            let buffer = location.buffer.upgrade().expect("buffer was released");
+            assert_eq!(
+                location.position.to_point(&buffer.read(cx).snapshot()),
+                Point::new(5, 16)
+            );
+            assert!(project.agent_location_trail().is_empty());
+        });
+    }
+
+    #[gpui::test]
+    async fn test_prepare_starts_language_server(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "fn main() {}\n"
                 }
-                _ => "No references found (or language server not capable)".to_string(),
-            };
+            }),
+            cx,
+        )
+        .await;
+        let mut fake_servers =
+            test_project.register_fake_rust_server(lsp::ServerCapabilities::default(), cx);
+        let tool = Arc::new(FindReferencesByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        cx.run_until_parked();
 
-            Ok(LanguageModelToolResultContent::Text(Arc::from(output)))
-        })
+        let partial_input = |path: &str| FindReferencesByContextToolInput {
+            anchor: ContextualAnchor {
+                path: path.into(),
+                context: String::new(),
+                token: String::new(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            excerpt_context_lines: None,
+            exclude_declaration: false,
+            exclude_patterns: Vec::new(),
+            within: None,
+            offset: 0,
+            max_results: None,
+            navigation: None,
+            wait_for_ready_ms: 0,
+        };
+        assert!(
+            cx.update(|cx| tool.clone().prepare(partial_input("root/src/ma"), cx))
+                .is_none()
+        );
+
+        let preparation = cx
+            .update(|cx| tool.clone().prepare(partial_input("root/src/main.rs"), cx))
+            .expect("existing file was not prepared");
+        let _preparation = preparation.await.expect("preparation failed");
+        fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+    }
+
+    #[gpui::test]
+    async fn test_follow_agent_navigation_trail_and_stay(cx: &mut TestAppContext) {
+        init_test(cx);
+        cx.update(|cx| {
+            SettingsStore::update_global(cx, |store, cx| {
+                store.update_user_settings(cx, |settings| {
+                    settings
+                        .agent
+                        .get_or_insert_default()
+                        .follow_agent_navigation = Some(true);
+                });
+            });
+        });
+        let row = |location: Option<AgentLocation>, cx: &App| {
+            let location = location.expect("no agent location");
+            let buffer = location.buffer.upgrade().expect("buffer was released");
+            location.position.to_point(&buffer.read(cx).snapshot()).row
+        };
+
+        let (output, events, project) =
+            find_helper_references(None, Some(ReferencesNavigation::Trail), cx).await;
+        output.expect("tool failed");
+        assert_eq!(recorded_locations(&events).len(), 2);
+        project.update(cx, |project, cx| {
+            assert_eq!(project.agent_location_trail().len(), 2);
+            assert_eq!(row(project.agent_location(), cx), 5);
+            assert_eq!(row(project.step_agent_location_trail(1, cx), cx), 6);
+            assert_eq!(row(project.step_agent_location_trail(5, cx), cx), 6);
+            assert_eq!(row(project.step_agent_location_trail(-1, cx), cx), 5);
+            assert_eq!(row(project.agent_location(), cx), 5);
+        });
+
+        let (output, events, project) =
+            find_helper_references(None, Some(ReferencesNavigation::Stay), cx).await;
+        output.expect("tool failed");
+        assert_eq!(recorded_locations(&events).len(), 2);
+        project.read_with(cx, |project, _| {
+            assert!(project.agent_location().is_none());
+            assert!(project.agent_location_trail().is_empty());
+        });
+    }
+
+    #[gpui::test]
+    async fn test_navigation_ignored_without_following(cx: &mut TestAppContext) {
+        init_test(cx);
+        let (output, _, project) =
+            find_helper_references(None, Some(ReferencesNavigation::Trail), cx).await;
+        let LanguageModelToolResultContent::Text(text) = output.expect("tool failed") else {
+            panic!("expected text output");
+        };
+        assert!(text.ends_with(IGNORED_NAVIGATION_NOTE), "{text}");
+        project.read_with(cx, |project, _| {
+            assert!(project.agent_location().is_none());
+            assert!(project.agent_location_trail().is_empty());
+        });
+
+        let (output, _, _) = find_helper_references(None, None, cx).await;
+        let LanguageModelToolResultContent::Text(text) = output.expect("tool failed") else {
+            panic!("expected text output");
+        };
+        assert!(!text.contains(IGNORED_NAVIGATION_NOTE), "{text}");
+    }
+
+    /// Finds the references to `helper` in a file that calls it twice, with a fake language server
+    /// that reports both calls.
+    async fn find_helper_references(
+        excerpt_context_lines: Option<u32>,
+        navigation: Option<ReferencesNavigation>,
+        cx: &mut TestAppContext,
+    ) -> (
+        Result<LanguageModelToolResultContent>,
+        Vec<RecordedToolEvent>,
+        Entity<Project>,
+    ) {
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "fn helper() -> u32 {\n    1\n}\n\nfn main() {\n    let first = helper();\n    let second = helper();\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let mut fake_servers = test_project.register_fake_rust_server(
+            lsp::ServerCapabilities {
+                references_provider: Some(lsp::OneOf::Left(true)),
+                ..Default::default()
+            },
+            cx,
+        );
+        let (_buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/main.rs", cx).await;
+        let fake_server = fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+
+        let references = vec![
+            test_project.lsp_location("src/main.rs", (5, 16), (5, 22)),
+            test_project.lsp_location("src/main.rs", (6, 17), (6, 23)),
+        ];
+        fake_server.set_request_handler::<lsp::request::References, _, _>(move |_, _| {
+            let references = references.clone();
+            async move { Ok(Some(references)) }
+        });
+
+        let tool = Arc::new(FindReferencesByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let (output, events) = run_tool(
+            tool,
+            FindReferencesByContextToolInput {
+                anchor: ContextualAnchor {
+                    path: "root/src/main.rs".into(),
+                    context: "let first = helper();".into(),
+                    token: "helper".into(),
+                    index: None,
+                    handle: None,
+                    revision: None,
+                    whole_word: false,
+                    approximate_line: None,
+                    case_insensitive: false,
+                    unicode_normalize: false,
+                    end_token: None,
+                    end_index: None,
+                },
+                excerpt_context_lines,
+                exclude_declaration: false,
+                exclude_patterns: Vec::new(),
+                within: None,
+                offset: 0,
+                max_results: None,
+                navigation,
+                wait_for_ready_ms: 0,
+            },
+            cx,
+        )
+        .await;
+        (output, events, test_project.project)
     }
 }
@@ -0,0 +1,396 @@
+use super::rename_symbol_by_context_tool::revert_transaction;
+use super::support::{DRY_RUN_NOTE, buffer_to_edit};
+use super::{
+    AnchorMemory, ContextualAnchor, display_project_path, resolve_project_path,
+    warm_up_language_servers,
+};
+use crate::{AgentTool, ToolCallAccess, ToolCallEventStream, ToolPreparation};
+use acp_thread::Diff;
+use action_log::ActionLog;
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use collections::HashSet;
+use gpui::{App, AppContext as _, Entity, SharedString, Task};
+use language::Point;
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::lsp_store::{FormatTrigger, LspFormatTarget};
+use project::{Project, WorktreeSettings};
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{collections::BTreeMap, sync::Arc};
+use util::markdown::MarkdownInlineCode;
+
+/// Formats a file, or the lines between two anchors, with the formatter the project is
+/// configured to use, and returns a diff of what changed.
+///
+/// Prefer this over reformatting code by hand.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct FormatToolInput {
+    /// The relative path of the file to format.
+    ///
+    /// This path should never be absolute, and the first component of the path should always be a root directory in a project.
+    pub path: String,
+    /// Optional anchor on the first line to format. Formatting starts at the top of the file when
+    /// omitted. `path` can be left out of the anchor.
+    #[serde(default)]
+    pub start: Option<ContextualAnchor>,
+    /// Optional anchor on the last line to format. Formatting runs to the end of the file when
+    /// omitted. `path` can be left out of the anchor.
+    #[serde(default)]
+    pub end: Option<ContextualAnchor>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FormatToolOutput {
+    pub path: String,
+    /// The 1-based first line that was formatted.
+    pub start_line: u32,
+    /// The 1-based last line that was formatted, inclusive.
+    pub end_line: u32,
+    /// A unified diff of the formatter's changes. Empty when the code was already formatted.
+    pub diff: String,
+    /// Whether the formatter's changes were only computed, without being applied.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl From<FormatToolOutput> for LanguageModelToolResultContent {
+    fn from(output: FormatToolOutput) -> Self {
+        if output.diff.is_empty() {
+            format!(
+                "{} [L{}-{}] was already formatted.",
+                output.path, output.start_line, output.end_line
+            )
+            .into()
+        } else {
+            let verb = if output.dry_run {
+                format!("{DRY_RUN_NOTE} Would format")
+            } else {
+                "Formatted".to_string()
+            };
+            format!(
+                "{verb} {} [L{}-{}]:\n\n```diff\n{}\n```",
+                output.path, output.start_line, output.end_line, output.diff
+            )
+            .into()
+        }
+    }
+}
+
+pub struct FormatTool {
+    project: Entity<Project>,
+    action_log: Entity<ActionLog>,
+    anchor_memory: Entity<AnchorMemory>,
+}
+
+impl FormatTool {
+    pub fn new(
+        project: Entity<Project>,
+        action_log: Entity<ActionLog>,
+        anchor_memory: Entity<AnchorMemory>,
+    ) -> Self {
+        Self {
+            project,
+            action_log,
+            anchor_memory,
+        }
+    }
+
+    fn expand_anchor(
+        &self,
+        anchor: Option<ContextualAnchor>,
+        path: &str,
+        cx: &App,
+    ) -> Result<Option<ContextualAnchor>> {
+        let Some(anchor) = anchor else {
+            return Ok(None);
+        };
+        let mut anchor = self.anchor_memory.read(cx).expand(anchor)?;
+        if anchor.path.is_empty() {
+            anchor.path = path.to_string();
+        }
+        anchor.validate_basic()?;
+        if let Some(revision) = &anchor.revision {
+            return Err(anyhow!(
+                "Only the project's current files can be formatted, not {revision}."
+            ));
+        }
+        let project = self.project.read(cx);
+        if resolve_project_path(project, &anchor.path, cx)?
+            != resolve_project_path(project, path, cx)?
+        {
+            return Err(anyhow!(
+                "The anchor `{}` is in {}, not in {path}.",
+                anchor.token,
+                anchor.path
+            ));
+        }
+        Ok(Some(anchor))
+    }
+}
+
+impl AgentTool for FormatTool {
+    type Input = FormatToolInput;
+    type Output = FormatToolOutput;
+
+    fn name() -> &'static str {
+        "format"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Edit
+    }
+
+    fn call_access(&self, input: &Self::Input, cx: &App) -> ToolCallAccess {
+        ToolCallAccess::mutates_paths(self.project.read(cx), [&input.path], cx)
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => format!("Format {}", MarkdownInlineCode(&input.path)).into(),
+            Err(_) => "Format file".into(),
+        }
+    }
+
+    fn prepare(
+        self: Arc<Self>,
+        input: Self::Input,
+        cx: &mut App,
+    ) -> Option<Task<Result<ToolPreparation>>> {
+        warm_up_language_servers(&self.project, &input.path, cx)
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let action_log = self.action_log.clone();
+
+        let (start, end) = match (
+            self.expand_anchor(input.start, &input.path, cx),
+            self.expand_anchor(input.end, &input.path, cx),
+        ) {
+            (Ok(start), Ok(end)) => (start, end),
+            (Err(error), _) | (_, Err(error)) => return Task::ready(Err(error)),
+        };
+        let is_range = start.is_some() || end.is_some();
+        if is_range && !project.read(cx).is_local() {
+            return Task::ready(Err(anyhow!(
+                "Formatting part of a file is only supported in local projects. Format the whole file instead."
+            )));
+        }
+        let project_path = match resolve_project_path(project.read(cx), &input.path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Task::ready(Err(anyhow!(
+                "Cannot edit file because its path matches the file_scan_exclusions or private_files setting: {}",
+                &input.path
+            )));
+        }
+        let display_path = display_project_path(project.read(cx), &project_path, cx)
+            .unwrap_or_else(|| input.path.clone());
+
+        cx.spawn(async move |cx| {
+            let buffer = project
+                .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+                .await?;
+            // Keep the buffer registered until the formatter has answered.
+            let _lsp_handle = project.update(cx, |project, cx| {
+                project.register_buffer_with_language_servers(&buffer, cx)
+            })?;
+            buffer
+                .read_with(cx, |buffer, _| buffer.parsing_idle())?
+                .await;
+            let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot())?;
+
+            let start_row = match &start {
+                Some(anchor) => {
+                    let offset = anchor.resolve_unique_token_offset(&snapshot, &display_path)?;
+                    snapshot.offset_to_point(offset).row
+                }
+                None => 0,
+            };
+            let end_row = match &end {
+                Some(anchor) => {
+                    let offset = anchor.resolve_unique_token_offset(&snapshot, &display_path)?;
+                    snapshot.offset_to_point(offset).row
+                }
+                None => snapshot.max_point().row,
+            };
+            anyhow::ensure!(
+                start_row <= end_row,
+                "The `start` anchor (line {}) comes after the `end` anchor (line {}).",
+                start_row + 1,
+                end_row + 1
+            );
+            let target = if is_range {
+                let range = snapshot.anchor_before(Point::new(start_row, 0))
+                    ..snapshot.anchor_after(Point::new(end_row, snapshot.line_len(end_row)));
+                LspFormatTarget::Ranges(BTreeMap::from_iter([(snapshot.remote_id(), vec![range])]))
+            } else {
+                LspFormatTarget::Buffers
+            };
+
+            let old_text = snapshot.text();
+            action_log.update(cx, |action_log, cx| {
+                action_log.buffer_read(buffer.clone(), cx)
+            })?;
+            let dry_run = event_stream.dry_run();
+            let edited_buffer = buffer_to_edit(&buffer, &event_stream, cx)?;
+            let diff = cx.new(|cx| Diff::new(edited_buffer.clone(), cx))?;
+            event_stream.update_diff(diff.clone());
+            let transaction = project
+                .update(cx, |project, cx| {
+                    project.format(
+                        HashSet::from_iter([buffer.clone()]),
+                        target,
+                        true,
+                        FormatTrigger::Manual,
+                        cx,
+                    )
+                })?
+                .await?;
+            if dry_run {
+                // Formatters only run on the project's buffers, so their edits are undone there
+                // and made again in the branch.
+                if let Some(transaction) = transaction.0.get(&buffer) {
+                    let edits = buffer
+                        .update(cx, |buffer, cx| revert_transaction(buffer, transaction, cx))?;
+                    edited_buffer.update(cx, |buffer, cx| buffer.edit(edits, None, cx))?;
+                }
+            } else {
+                action_log.update(cx, |action_log, cx| {
+                    action_log.buffer_edited(buffer.clone(), cx)
+                })?;
+            }
+            diff.update(cx, |diff, cx| diff.finalize(cx))?;
+
+            let new_text = edited_buffer.read_with(cx, |buffer, _| buffer.text())?;
+            let unified_diff = cx
+                .background_spawn(async move { language::unified_diff(&old_text, &new_text) })
+                .await;
+
+            Ok(FormatToolOutput {
+                path: display_path,
+                start_line: start_row + 1,
+                end_line: end_row + 1,
+                diff: unified_diff,
+                dry_run,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{RecordedToolEvent, ToolTestProject, init_test, run_tool};
+    use futures::StreamExt as _;
+    use gpui::TestAppContext;
+    use serde_json::json;
+
+    #[gpui::test]
+    async fn test_format_range_between_anchors(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "fn first() {\n    let a=1;\n}\n\nfn second() {\n    let b=2;\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let mut fake_servers = test_project.register_fake_rust_server(
+            lsp::ServerCapabilities {
+                document_range_formatting_provider: Some(lsp::OneOf::Left(true)),
+                ..Default::default()
+            },
+            cx,
+        );
+        let (buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/main.rs", cx).await;
+        let fake_server = fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+        fake_server.set_request_handler::<lsp::request::RangeFormatting, _, _>(
+            |params, _| async move {
+                assert_eq!(
+                    params.range,
+                    lsp::Range::new(lsp::Position::new(4, 0), lsp::Position::new(6, 1))
+                );
+                Ok(Some(vec![lsp::TextEdit {
+                    range: lsp::Range::new(lsp::Position::new(5, 9), lsp::Position::new(5, 10)),
+                    new_text: " = ".into(),
+                }]))
+            },
+        );
+
+        let action_log = cx.new(|_| ActionLog::new(test_project.project.clone()));
+        let tool = Arc::new(FormatTool::new(
+            test_project.project.clone(),
+            action_log.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let anchor = |context: &str, token: &str| ContextualAnchor {
+            path: String::new(),
+            context: context.into(),
+            token: token.into(),
+            index: None,
+            handle: None,
+            revision: None,
+            whole_word: false,
+            approximate_line: None,
+            case_insensitive: false,
+            unicode_normalize: false,
+            end_token: None,
+            end_index: None,
+        };
+        let (output, events) = run_tool(
+            tool,
+            FormatToolInput {
+                path: "root/src/main.rs".into(),
+                start: Some(anchor("fn second() {", "second")),
+                end: Some(anchor("let b=2;\n}", "}")),
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+
+        assert_eq!(output.start_line, 5);
+        assert_eq!(output.end_line, 7);
+        assert!(output.diff.contains("+    let b = 2;"), "{}", output.diff);
+        assert!(events.contains(&RecordedToolEvent::Diff));
+        assert_eq!(
+            buffer.read_with(cx, |buffer, _| buffer.text()),
+            "fn first() {\n    let a=1;\n}\n\nfn second() {\n    let b = 2;\n}\n"
+        );
+        cx.run_until_parked();
+        assert_eq!(
+            action_log.read_with(cx, |log, cx| log.changed_buffers(cx).len()),
+            1
+        );
+    }
+}
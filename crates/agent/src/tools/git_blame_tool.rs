@@ -0,0 +1,442 @@
+use super::{
+    AnchorHandles, AnchorMemory, ContextualAnchor, display_project_path, resolve_project_path,
+};
+use crate::{AgentTool, ToolCallEventStream};
+use agent_client_protocol as acp;
+use anyhow::{Context as _, Result, anyhow};
+use gpui::{App, Entity, SharedString, Task};
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::{Project, WorktreeSettings};
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{fmt::Write as _, ops::Range, sync::Arc};
+
+/// Blaming a whole large file would flood the context with hunks nobody asked about.
+const MAX_BLAME_LINES: u32 = 1000;
+
+/// Shows which commit last changed each line of some code, with the commit's author, date and
+/// summary. Use it to find out when and why code changed before changing it again.
+///
+/// Give either an `anchor`, to blame the lines its `context` spans, or a `path` with an optional
+/// line range.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct GitBlameToolInput {
+    /// An anchor for the code to blame.
+    #[serde(default)]
+    pub anchor: Option<ContextualAnchor>,
+    /// The path of the file to blame, when not giving an `anchor`.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// The 1-based first line to blame, used with `path`. Defaults to the start of the file.
+    #[serde(default)]
+    pub start_line: Option<u32>,
+    /// The 1-based last line to blame, inclusive, used with `path`. Defaults to the end of the
+    /// file, or 1000 lines after `start_line`.
+    #[serde(default)]
+    pub end_line: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct GitBlameToolOutput {
+    pub path: String,
+    /// The 1-based first line that was blamed.
+    pub start_line: u32,
+    /// The 1-based last line that was blamed, inclusive.
+    pub end_line: u32,
+    /// Runs of consecutive lines last changed by the same commit, in file order.
+    pub hunks: Vec<BlameHunk>,
+    /// The handle under which the input anchor was remembered, if one was given.
+    pub handle: Option<String>,
+    /// The symbol handle under which the input anchor was remembered, if it is unambiguous.
+    pub symbol_handle: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct BlameHunk {
+    /// The 1-based first line of the hunk.
+    pub start_line: u32,
+    /// The 1-based last line of the hunk, inclusive.
+    pub end_line: u32,
+    /// The commit's full SHA. Missing for lines that haven't been committed yet.
+    pub commit: Option<String>,
+    pub author: Option<String>,
+    pub author_email: Option<String>,
+    /// The day the commit was authored, as YYYY-MM-DD in UTC.
+    pub date: Option<String>,
+    /// The first line of the commit message.
+    pub summary: Option<String>,
+}
+
+impl From<GitBlameToolOutput> for LanguageModelToolResultContent {
+    fn from(output: GitBlameToolOutput) -> Self {
+        let mut text = format!(
+            "Blame for {} [L{}-L{}]:\n\n",
+            output.path, output.start_line, output.end_line
+        );
+        for hunk in &output.hunks {
+            let lines = if hunk.start_line == hunk.end_line {
+                format!("L{}", hunk.start_line)
+            } else {
+                format!("L{}-L{}", hunk.start_line, hunk.end_line)
+            };
+            let Some(commit) = &hunk.commit else {
+                writeln!(text, "- {lines}: not committed yet").ok();
+                continue;
+            };
+            let short_commit = commit
+                .get(..git::SHORT_SHA_LENGTH)
+                .unwrap_or(commit.as_str());
+            writeln!(
+                text,
+                "- {lines}: {short_commit} by {} on {}: {}",
+                hunk.author.as_deref().unwrap_or("unknown author"),
+                hunk.date.as_deref().unwrap_or("unknown date"),
+                hunk.summary.as_deref().unwrap_or("(no summary)")
+            )
+            .ok();
+        }
+        if let Some(reference) = output.handle {
+            let handles = AnchorHandles {
+                reference,
+                symbol: output.symbol_handle,
+            };
+            text.push('\n');
+            text.push_str(&handles.describe());
+        }
+        text.into()
+    }
+}
+
+pub struct GitBlameTool {
+    project: Entity<Project>,
+    anchor_memory: Entity<AnchorMemory>,
+}
+
+impl GitBlameTool {
+    pub fn new(project: Entity<Project>, anchor_memory: Entity<AnchorMemory>) -> Self {
+        Self {
+            project,
+            anchor_memory,
+        }
+    }
+}
+
+impl AgentTool for GitBlameTool {
+    type Input = GitBlameToolInput;
+    type Output = GitBlameToolOutput;
+
+    fn name() -> &'static str {
+        "git_blame"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Read
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        let Ok(input) = input else {
+            return "Git blame".into();
+        };
+        match (input.anchor, input.path) {
+            (Some(anchor), _) => match anchor.handle {
+                Some(handle) => format!("Blame `{handle}`").into(),
+                None => format!("Blame `{}` in {}", anchor.token, anchor.path).into(),
+            },
+            (None, Some(path)) => format!("Blame {path}").into(),
+            (None, None) => "Git blame".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let anchor_memory = self.anchor_memory.clone();
+
+        let anchor = match input.anchor {
+            Some(anchor) => match anchor_memory.read(cx).expand(anchor) {
+                Ok(anchor) => Some(anchor),
+                Err(error) => return Task::ready(Err(error)),
+            },
+            None => None,
+        };
+        let path = match (&anchor, input.path) {
+            (Some(anchor), _) => {
+                if let Err(error) = anchor.validate_basic() {
+                    return Task::ready(Err(error));
+                }
+                if anchor.revision.is_some() {
+                    return Task::ready(Err(anyhow!(
+                        "Blame shows who last changed the project's current files, so the anchor can't have a `revision`."
+                    )));
+                }
+                anchor.path.clone()
+            }
+            (None, Some(path)) => path,
+            (None, None) => {
+                return Task::ready(Err(anyhow!(
+                    "Provide either an `anchor` or a `path` to blame."
+                )));
+            }
+        };
+        let project_path = match resolve_project_path(project.read(cx), &path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Task::ready(Err(anyhow!(
+                "Cannot blame file because its path matches the file_scan_exclusions or private_files setting: {path}"
+            )));
+        }
+        let display_path =
+            display_project_path(project.read(cx), &project_path, cx).unwrap_or(path);
+        let abs_path = project.read(cx).absolute_path(&project_path, cx);
+
+        cx.spawn(async move |cx| {
+            let buffer = project
+                .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+                .await?;
+            let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot())?;
+            let max_row = snapshot.max_point().row;
+
+            let mut handles = None;
+            let rows = match &anchor {
+                Some(anchor) => {
+                    buffer
+                        .read_with(cx, |buffer, _| buffer.parsing_idle())?
+                        .await;
+                    let token_offset =
+                        anchor.resolve_unique_token_offset(&snapshot, &display_path)?;
+                    handles = Some(
+                        anchor_memory
+                            .update(cx, |anchor_memory, _| anchor_memory.remember(anchor))?,
+                    );
+                    anchor.context_rows(snapshot.offset_to_point(token_offset).row, max_row)
+                }
+                None => {
+                    let start_row = input.start_line.unwrap_or(1).saturating_sub(1);
+                    anyhow::ensure!(
+                        start_row <= max_row,
+                        "`start_line` is past the end of {display_path}, which has {} lines.",
+                        max_row + 1
+                    );
+                    let end_row = input
+                        .end_line
+                        .map(|end_line| end_line.saturating_sub(1))
+                        .unwrap_or(max_row)
+                        .min(max_row)
+                        .min(start_row + MAX_BLAME_LINES - 1);
+                    anyhow::ensure!(
+                        start_row <= end_row,
+                        "`end_line` must not be before `start_line`."
+                    );
+                    start_row..end_row + 1
+                }
+            };
+
+            if let Some(abs_path) = abs_path {
+                event_stream.update_fields(acp::ToolCallUpdateFields::new().locations(vec![
+                    acp::ToolCallLocation::new(abs_path).line(Some(rows.start)),
+                ]));
+            }
+
+            let blame = project
+                .update(cx, |project, cx| project.blame_buffer(&buffer, None, cx))?
+                .await
+                .with_context(|| format!("Couldn't blame {display_path}"))?
+                .with_context(|| format!("{display_path} isn't tracked by git"))?;
+
+            Ok(GitBlameToolOutput {
+                path: display_path,
+                start_line: rows.start + 1,
+                end_line: rows.end,
+                hunks: blame_hunks(&blame, rows),
+                handle: handles.as_ref().map(|handles| handles.reference.clone()),
+                symbol_handle: handles.and_then(|handles| handles.symbol),
+            })
+        })
+    }
+}
+
+/// Clips the blame's entries to `rows` and orders them by line.
+fn blame_hunks(blame: &git::blame::Blame, rows: Range<u32>) -> Vec<BlameHunk> {
+    let mut entries = blame
+        .entries
+        .iter()
+        .filter(|entry| entry.range.start < rows.end && rows.start < entry.range.end)
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|entry| entry.range.start);
+    entries
+        .into_iter()
+        .map(|entry| {
+            let committed = entry.sha.as_bytes().iter().any(|byte| *byte != 0);
+            let summary = entry.summary.clone().or_else(|| {
+                let message = blame.messages.get(&entry.sha)?;
+                Some(message.lines().next()?.to_string())
+            });
+            BlameHunk {
+                start_line: entry.range.start.max(rows.start) + 1,
+                end_line: entry.range.end.min(rows.end),
+                commit: committed.then(|| entry.sha.to_string()),
+                author: entry.author.clone().filter(|_| committed),
+                author_email: entry.author_mail.clone().filter(|_| committed),
+                date: entry
+                    .author_time
+                    .filter(|_| committed)
+                    .and_then(|author_time| chrono::DateTime::from_timestamp(author_time, 0))
+                    .map(|date| date.format("%Y-%m-%d").to_string()),
+                summary: summary.filter(|_| committed),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, recorded_locations, run_tool};
+    use git::{
+        blame::{Blame, BlameEntry},
+        repository::repo_path,
+    };
+    use gpui::{AppContext as _, TestAppContext};
+    use serde_json::json;
+    use util::path;
+
+    fn blame_entry(sha: &str, range: Range<u32>, author: &str, summary: &str) -> BlameEntry {
+        BlameEntry {
+            sha: sha.parse().expect("invalid sha"),
+            range,
+            author: Some(author.into()),
+            author_time: Some(1_700_000_000),
+            summary: Some(summary.into()),
+            ..Default::default()
+        }
+    }
+
+    #[gpui::test]
+    async fn test_git_blame_anchor_and_line_range(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                ".git": {},
+                "src": {
+                    "lib.rs": "fn parse(input: &str) -> u32 {\n    input.len() as u32\n}\n\nfn main() {}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        test_project.fs.set_blame_for_repo(
+            path!("/root/.git").as_ref(),
+            vec![(
+                repo_path("src/lib.rs"),
+                Blame {
+                    entries: vec![
+                        blame_entry("3a3a3a", 3..5, "Ada", "Add main"),
+                        blame_entry("1b1b1b", 0..1, "Grace", "Add parser"),
+                        blame_entry("2c2c2c", 1..3, "Ada", "Count bytes"),
+                    ],
+                    ..Default::default()
+                },
+            )],
+        );
+        cx.run_until_parked();
+        let project = test_project.project.clone();
+        let anchor_memory = cx.new(|_| AnchorMemory::default());
+        let tool = Arc::new(GitBlameTool::new(project, anchor_memory));
+
+        let (output, events) = run_tool(
+            tool.clone(),
+            GitBlameToolInput {
+                anchor: Some(ContextualAnchor {
+                    path: "root/src/lib.rs".into(),
+                    context: "    input.len() as u32\n}".into(),
+                    token: "len".into(),
+                    index: None,
+                    handle: None,
+                    revision: None,
+                    whole_word: false,
+                    approximate_line: None,
+                    case_insensitive: false,
+                    unicode_normalize: false,
+                    end_token: None,
+                    end_index: None,
+                }),
+                path: None,
+                start_line: None,
+                end_line: None,
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+        assert_eq!((output.start_line, output.end_line), (2, 3));
+        assert_eq!(
+            output.hunks,
+            vec![BlameHunk {
+                start_line: 2,
+                end_line: 3,
+                commit: Some("2c2c2c0000000000000000000000000000000000".into()),
+                author: Some("Ada".into()),
+                author_email: None,
+                date: Some("2023-11-14".into()),
+                summary: Some("Count bytes".into()),
+            }]
+        );
+        assert!(output.handle.is_some());
+        assert_eq!(
+            recorded_locations(&events),
+            vec![acp::ToolCallLocation::new(test_project.abs_path("src/lib.rs")).line(Some(1))]
+        );
+
+        let (output, _) = run_tool(
+            tool,
+            GitBlameToolInput {
+                anchor: None,
+                path: Some("root/src/lib.rs".into()),
+                start_line: Some(1),
+                end_line: Some(4),
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+        assert_eq!(
+            output
+                .hunks
+                .iter()
+                .map(|hunk| (
+                    hunk.start_line,
+                    hunk.end_line,
+                    hunk.summary.as_deref().unwrap_or_default()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, 1, "Add parser"),
+                (2, 3, "Count bytes"),
+                (4, 4, "Add main")
+            ]
+        );
+        assert_eq!(output.handle, None);
+    }
+}
@@ -0,0 +1,384 @@
+use super::support::DRY_RUN_NOTE;
+use super::{display_project_path, resolve_project_path};
+use crate::{AgentTool, ToolCallEventStream, ToolPermissionClass};
+use agent_client_protocol as acp;
+use anyhow::{Context as _, Result, anyhow};
+use askpass::AskPassDelegate;
+use git::{
+    repository::CommitOptions,
+    status::{FileStatus, StatusCode},
+};
+use gpui::{App, Entity, SharedString, Task};
+use project::{Project, git_store::Repository};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Write as _, sync::Arc};
+
+/// Commits the changes that are staged in one of the project's git repositories, with the given
+/// message.
+///
+/// Stage the files to commit with `git_stage` first; changes that aren't staged are left in the
+/// working tree. The user sees the message and the staged files before the commit is made.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct GitCommitToolInput {
+    /// The commit message: a short summary line, optionally followed by a blank line and a longer
+    /// description.
+    pub message: String,
+    /// A path in the repository to commit in, such as one of the staged files. Can be omitted
+    /// when the project has a single git repository.
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+pub struct GitCommitTool {
+    project: Entity<Project>,
+}
+
+impl GitCommitTool {
+    pub fn new(project: Entity<Project>) -> Self {
+        Self { project }
+    }
+}
+
+impl AgentTool for GitCommitTool {
+    type Input = GitCommitToolInput;
+    type Output = String;
+
+    fn name() -> &'static str {
+        "git_commit"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Other
+    }
+
+    fn permission_class() -> ToolPermissionClass {
+        ToolPermissionClass::WorkspaceMutating
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => match input.message.lines().next() {
+                Some(summary) if !summary.trim().is_empty() => {
+                    format!("Commit \"{}\"", summary.trim()).into()
+                }
+                _ => "Commit staged changes".into(),
+            },
+            Err(_) => "Commit staged changes".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let message = input.message.trim().to_string();
+        if message.is_empty() {
+            return Task::ready(Err(anyhow!("The commit message must not be empty.")));
+        }
+        let repository = match commit_repository(&self.project, input.path.as_deref(), cx) {
+            Ok(repository) => repository,
+            Err(error) => return Task::ready(Err(error)),
+        };
+
+        let project = self.project.read(cx);
+        let staged_changes = repository
+            .read(cx)
+            .cached_status()
+            .filter(|entry| entry.status.staging().has_staged())
+            .map(|entry| {
+                let path = repository
+                    .read(cx)
+                    .repo_path_to_project_path(&entry.repo_path, cx)
+                    .and_then(|project_path| display_project_path(project, &project_path, cx))
+                    .unwrap_or_else(|| entry.repo_path.as_unix_str().to_string());
+                (path, entry.status)
+            })
+            .collect::<Vec<_>>();
+        if staged_changes.is_empty() {
+            return Task::ready(Err(anyhow!(
+                "Nothing is staged for commit. Stage the files to commit with `git_stage` first."
+            )));
+        }
+
+        // Show what will be committed while the user decides whether to allow it.
+        let mut preview = format!("Commit message:\n\n```\n{message}\n```\n\nStaged changes:\n\n");
+        for (path, status) in &staged_changes {
+            writeln!(preview, "- {} {path}", describe_staged_status(*status)).ok();
+        }
+        event_stream.update_fields(acp::ToolCallUpdateFields::new().content(vec![
+            acp::ToolCallContent::Content(acp::Content::new(preview)),
+        ]));
+        if event_stream.dry_run() {
+            let summary = message.lines().next().unwrap_or_default();
+            return Task::ready(Ok(format!(
+                "{DRY_RUN_NOTE} Would commit {} file(s) with the message \"{summary}\".",
+                staged_changes.len()
+            )));
+        }
+
+        let authorize = event_stream.authorize(
+            self.initial_title(
+                Ok(GitCommitToolInput {
+                    message: message.clone(),
+                    path: input.path,
+                }),
+                cx,
+            ),
+            cx,
+        );
+        cx.spawn(async move |cx| {
+            authorize.await?;
+            // The agent can't answer prompts, so commits that need a passphrase (e.g. for
+            // signing) fail instead of waiting forever.
+            let askpass = AskPassDelegate::new(cx, |_, _, _| {});
+            repository
+                .update(cx, |repository, cx| {
+                    repository.commit(
+                        message.clone().into(),
+                        None,
+                        CommitOptions::default(),
+                        askpass,
+                        cx,
+                    )
+                })?
+                .await?
+                .context("Couldn't commit")?;
+
+            let summary = message.lines().next().unwrap_or_default();
+            Ok(format!(
+                "Committed {} file(s) with the message \"{summary}\".",
+                staged_changes.len()
+            ))
+        })
+    }
+}
+
+/// The repository containing `path`, the same one `git_stage` stages it in, or the project's only
+/// repository when no path is given.
+fn commit_repository(
+    project: &Entity<Project>,
+    path: Option<&str>,
+    cx: &App,
+) -> Result<Entity<Repository>> {
+    let project = project.read(cx);
+    let git_store = project.git_store().read(cx);
+    if let Some(path) = path {
+        let project_path = resolve_project_path(project, path, cx)?;
+        let (repository, _) = git_store
+            .repository_and_path_for_project_path(&project_path, cx)
+            .with_context(|| format!("{path} is not in a git repository."))?;
+        return Ok(repository);
+    }
+    match git_store.repositories().len() {
+        0 => Err(anyhow!("The project doesn't have a git repository.")),
+        1 => git_store
+            .active_repository()
+            .context("The project doesn't have a git repository."),
+        _ => Err(anyhow!(
+            "The project has several git repositories. Pass a `path` in the one to commit in."
+        )),
+    }
+}
+
+fn describe_staged_status(status: FileStatus) -> String {
+    let FileStatus::Tracked(tracked) = status else {
+        return "changed".into();
+    };
+    let description = match tracked.index_status {
+        StatusCode::Added => "added",
+        StatusCode::Deleted => "deleted",
+        StatusCode::Renamed => "renamed",
+        StatusCode::Copied => "copied",
+        StatusCode::Modified | StatusCode::TypeChanged | StatusCode::Unmodified => "modified",
+    };
+    if status.staging().has_unstaged() {
+        format!("{description} (partially staged)")
+    } else {
+        description.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, run_tool};
+    use crate::{GitStageTool, GitStageToolInput};
+    use gpui::TestAppContext;
+    use serde_json::json;
+    use util::path;
+
+    #[gpui::test]
+    async fn test_stage_and_commit(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                ".git": {},
+                "src": {
+                    "lib.rs": "pub fn answer() -> u32 {\n    42\n}\n",
+                    "main.rs": "fn main() {}\n",
+                }
+            }),
+            cx,
+        )
+        .await;
+        test_project.fs.set_head_and_index_for_repo(
+            path!("/root/.git").as_ref(),
+            &[
+                ("src/lib.rs", "pub fn answer() -> u32 {\n    0\n}\n".into()),
+                ("src/main.rs", "fn main() {}\n".into()),
+            ],
+        );
+        cx.run_until_parked();
+        let project = test_project.project.clone();
+        let commit_tool = Arc::new(GitCommitTool::new(project.clone()));
+
+        let (output, _) = run_tool(
+            commit_tool.clone(),
+            GitCommitToolInput {
+                message: "Fix the answer".into(),
+                path: None,
+            },
+            cx,
+        )
+        .await;
+        assert!(
+            output.is_err(),
+            "committing with nothing staged should fail"
+        );
+
+        let (event_stream, mut receiver) = ToolCallEventStream::test();
+        let stage = cx.update(|cx| {
+            Arc::new(GitStageTool::new(project.clone())).run(
+                GitStageToolInput {
+                    paths: vec!["root/src/lib.rs".into()],
+                    unstage: false,
+                },
+                event_stream,
+                cx,
+            )
+        });
+        let authorization = receiver.expect_authorization().await;
+        assert_eq!(
+            authorization.tool_call.fields.title,
+            Some("Stage `root/src/lib.rs`".into())
+        );
+        authorization
+            .response
+            .send(acp::PermissionOptionId::new("allow"))
+            .expect("tool stopped waiting for authorization");
+        assert_eq!(stage.await.expect("tool failed"), "Staged root/src/lib.rs");
+        cx.run_until_parked();
+
+        let (event_stream, mut receiver) = ToolCallEventStream::test();
+        let commit = cx.update(|cx| {
+            commit_tool.run(
+                GitCommitToolInput {
+                    message: "Fix the answer\n\nIt was 0.".into(),
+                    path: None,
+                },
+                event_stream,
+                cx,
+            )
+        });
+        let preview = receiver.expect_update_fields().await;
+        assert_eq!(
+            preview.content,
+            Some(vec![acp::ToolCallContent::Content(acp::Content::new(
+                "Commit message:\n\n```\nFix the answer\n\nIt was 0.\n```\n\nStaged changes:\n\n- modified root/src/lib.rs\n"
+            ))])
+        );
+        let authorization = receiver.expect_authorization().await;
+        assert_eq!(
+            authorization.tool_call.fields.title,
+            Some("Commit \"Fix the answer\"".into())
+        );
+        authorization
+            .response
+            .send(acp::PermissionOptionId::new("allow"))
+            .expect("tool stopped waiting for authorization");
+        assert_eq!(
+            commit.await.expect("tool failed"),
+            "Committed 1 file(s) with the message \"Fix the answer\"."
+        );
+    }
+
+    #[gpui::test]
+    async fn test_commit_in_repository_of_path(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                ".git": {},
+                "main.rs": "fn main() {}\n",
+                "vendor": {
+                    ".git": {},
+                    "lib.rs": "pub fn answer() -> u32 {\n    42\n}\n",
+                }
+            }),
+            cx,
+        )
+        .await;
+        test_project.fs.set_head_and_index_for_repo(
+            path!("/root/.git").as_ref(),
+            &[("main.rs", "fn main() {}\n".into())],
+        );
+        // The vendored repository has its change to lib.rs staged.
+        test_project.fs.set_head_for_repo(
+            path!("/root/vendor/.git").as_ref(),
+            &[("lib.rs", "pub fn answer() -> u32 {\n    0\n}\n".into())],
+            "abc123",
+        );
+        test_project.fs.set_index_for_repo(
+            path!("/root/vendor/.git").as_ref(),
+            &[("lib.rs", "pub fn answer() -> u32 {\n    42\n}\n".into())],
+        );
+        cx.run_until_parked();
+        let commit_tool = Arc::new(GitCommitTool::new(test_project.project.clone()));
+        let commit = |path: Option<&str>| GitCommitToolInput {
+            message: "Fix the answer".into(),
+            path: path.map(Into::into),
+        };
+
+        let (output, _) = run_tool(commit_tool.clone(), commit(None), cx).await;
+        let error = output.expect_err("the repository to commit in is ambiguous");
+        assert!(
+            error.to_string().contains("several git repositories"),
+            "{error}"
+        );
+
+        let (output, _) = run_tool(commit_tool.clone(), commit(Some("root/main.rs")), cx).await;
+        let error = output.expect_err("nothing is staged in the outer repository");
+        assert!(error.to_string().contains("Nothing is staged"), "{error}");
+
+        let (event_stream, mut receiver) = ToolCallEventStream::test();
+        let output =
+            cx.update(|cx| commit_tool.run(commit(Some("root/vendor/lib.rs")), event_stream, cx));
+        let preview = receiver.expect_update_fields().await;
+        assert_eq!(
+            preview.content,
+            Some(vec![acp::ToolCallContent::Content(acp::Content::new(
+                "Commit message:\n\n```\nFix the answer\n```\n\nStaged changes:\n\n- modified root/vendor/lib.rs\n"
+            ))])
+        );
+        receiver
+            .expect_authorization()
+            .await
+            .response
+            .send(acp::PermissionOptionId::new("allow"))
+            .expect("tool stopped waiting for authorization");
+        assert_eq!(
+            output.await.expect("tool failed"),
+            "Committed 1 file(s) with the message \"Fix the answer\"."
+        );
+    }
+}
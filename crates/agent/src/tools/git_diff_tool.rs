@@ -0,0 +1,499 @@
+use super::{display_project_path, resolve_project_path};
+use crate::{AgentTool, ToolCallEventStream};
+use agent_client_protocol as acp;
+use anyhow::{Context as _, Result, anyhow};
+use git::repository::DiffType;
+use gpui::{App, Entity, SharedString, Task};
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::{Project, WorktreeSettings};
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{fmt::Write as _, path::Path, sync::Arc};
+
+const DEFAULT_MAX_DIFF_BYTES: usize = 20 * 1024;
+const MAX_DIFF_BYTES: usize = 200 * 1024;
+
+/// Shows the changes in the project's git repository as a unified diff, for a single path or the
+/// whole repository.
+///
+/// - `base` chooses what the changes are relative to: `head` for all uncommitted changes, `staged`
+///   for only the changes that are staged for commit, or `revision` for everything that changed
+///   since a branch, tag or commit.
+/// - Untracked files aren't included, nor are files matching the `file_scan_exclusions` or
+///   `private_files` settings.
+/// - When the diff is longer than `max_bytes`, only the number of lines added and removed in each
+///   file is returned. Narrow it down with `path` to see the full diff.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct GitDiffToolInput {
+    /// Optional file or directory to limit the diff to. The whole repository is diffed when
+    /// omitted.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// What to compare the working tree against. Defaults to `head`.
+    #[serde(default)]
+    pub base: GitDiffBase,
+    /// The branch, tag or commit to compare against, such as "main" or "HEAD~3". Required when
+    /// `base` is `revision`.
+    #[serde(default)]
+    pub revision: Option<String>,
+    /// Optional maximum size of the returned diff in bytes, up to 200KiB. Defaults to 20KiB.
+    #[serde(default)]
+    pub max_bytes: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GitDiffBase {
+    /// All uncommitted changes, staged or not.
+    #[default]
+    Head,
+    /// Only the changes staged for commit.
+    Staged,
+    /// Everything that changed since `revision`, committed or not.
+    Revision,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct GitDiffToolOutput {
+    /// What the changes are relative to, such as "HEAD", "the index" or "main".
+    pub base: String,
+    /// The files that changed, in the order git listed them.
+    pub files: Vec<GitDiffFile>,
+    /// The unified diff. Missing when it was longer than `max_bytes`.
+    pub diff: Option<String>,
+    /// How many changed files were left out because they match the `file_scan_exclusions` or
+    /// `private_files` setting.
+    #[serde(default)]
+    pub hidden_files: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct GitDiffFile {
+    pub path: String,
+    pub lines_added: u32,
+    pub lines_removed: u32,
+}
+
+impl From<GitDiffToolOutput> for LanguageModelToolResultContent {
+    fn from(output: GitDiffToolOutput) -> Self {
+        let mut text = if output.files.is_empty() {
+            format!("No changes relative to {}.\n", output.base)
+        } else {
+            match output.diff {
+                Some(diff) => format!(
+                    "Changes relative to {}:\n\n```diff\n{}\n```\n",
+                    output.base,
+                    diff.trim_end()
+                ),
+                None => {
+                    let mut text = format!(
+                        "The diff relative to {} is too long to show, so here is a summary of the {} changed file(s). Pass a `path` to see a file's diff.\n\n",
+                        output.base,
+                        output.files.len()
+                    );
+                    for file in &output.files {
+                        writeln!(
+                            text,
+                            "- {} (+{} -{})",
+                            file.path, file.lines_added, file.lines_removed
+                        )
+                        .ok();
+                    }
+                    text
+                }
+            }
+        };
+        if output.hidden_files > 0 {
+            write!(
+                text,
+                "\n{} changed file(s) matching the file_scan_exclusions or private_files setting aren't shown.\n",
+                output.hidden_files
+            )
+            .ok();
+        }
+        text.trim_end().to_string().into()
+    }
+}
+
+pub struct GitDiffTool {
+    project: Entity<Project>,
+}
+
+impl GitDiffTool {
+    pub fn new(project: Entity<Project>) -> Self {
+        Self { project }
+    }
+}
+
+impl AgentTool for GitDiffTool {
+    type Input = GitDiffToolInput;
+    type Output = GitDiffToolOutput;
+
+    fn name() -> &'static str {
+        "git_diff"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Read
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        let Ok(input) = input else {
+            return "Git diff".into();
+        };
+        let target = input.path.unwrap_or_else(|| "repository".into());
+        match (input.base, input.revision) {
+            (GitDiffBase::Staged, _) => format!("Diff staged changes in {target}").into(),
+            (GitDiffBase::Revision, Some(revision)) => {
+                format!("Diff {target} against {revision}").into()
+            }
+            _ => format!("Diff {target}").into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        _event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let (diff_type, base) = match (input.base, input.revision) {
+            (GitDiffBase::Head, _) => (DiffType::HeadToWorktree, "HEAD".to_string()),
+            (GitDiffBase::Staged, _) => (DiffType::HeadToIndex, "the index".to_string()),
+            (GitDiffBase::Revision, Some(revision)) if !revision.trim().is_empty() => {
+                let revision = revision.trim().to_string();
+                (DiffType::RevisionToWorktree(revision.clone()), revision)
+            }
+            (GitDiffBase::Revision, _) => {
+                return Task::ready(Err(anyhow!(
+                    "Provide the `revision` to compare against when `base` is `revision`."
+                )));
+            }
+        };
+        let max_bytes = input
+            .max_bytes
+            .unwrap_or(DEFAULT_MAX_DIFF_BYTES)
+            .min(MAX_DIFF_BYTES);
+
+        let git_store = project.read(cx).git_store().clone();
+        let (repository, path_filter) = match &input.path {
+            Some(path) => {
+                let project_path = match resolve_project_path(project.read(cx), path, cx) {
+                    Ok(project_path) => project_path,
+                    Err(error) => return Task::ready(Err(error)),
+                };
+                let Some((repository, repo_path)) = git_store
+                    .read(cx)
+                    .repository_and_path_for_project_path(&project_path, cx)
+                else {
+                    return Task::ready(Err(anyhow!("{path} is not in a git repository.")));
+                };
+                (repository, Some(repo_path.as_unix_str().to_string()))
+            }
+            None => match git_store.read(cx).active_repository() {
+                Some(repository) => (repository, None),
+                None => {
+                    return Task::ready(Err(anyhow!("The project doesn't have a git repository.")));
+                }
+            },
+        };
+        let work_directory = repository.read(cx).work_directory_abs_path.clone();
+        let diff = repository.update(cx, |repository, cx| repository.diff(diff_type, cx));
+
+        cx.spawn(async move |cx| {
+            let diff = diff
+                .await?
+                .with_context(|| format!("Couldn't diff against {base}"))?;
+            let file_diffs = split_unified_diff(&diff)
+                .into_iter()
+                .filter(|file_diff| match &path_filter {
+                    Some(filter) => path_is_within(&file_diff.path, filter),
+                    None => true,
+                })
+                .collect::<Vec<_>>();
+
+            let (file_diffs, hidden_files) = project.read_with(cx, |project, cx| {
+                visible_file_diffs(project, &work_directory, file_diffs, cx)
+            })?;
+            let diff = file_diffs
+                .iter()
+                .map(|(_, file_diff)| file_diff.text)
+                .collect::<String>();
+
+            Ok(GitDiffToolOutput {
+                base,
+                files: file_diffs.into_iter().map(|(file, _)| file).collect(),
+                diff: (diff.len() <= max_bytes).then_some(diff),
+                hidden_files,
+            })
+        })
+    }
+}
+
+/// One file's section of a unified diff, with the path it has in the working tree, relative to
+/// the repository root.
+#[derive(Debug, PartialEq)]
+struct FileDiff<'a> {
+    path: String,
+    text: &'a str,
+    lines_added: u32,
+    lines_removed: u32,
+}
+
+/// Splits the output of `git diff` at each file's `diff --git` header.
+fn split_unified_diff(diff: &str) -> Vec<FileDiff<'_>> {
+    let mut section_starts = Vec::new();
+    let mut offset = 0;
+    for line in diff.split_inclusive('\n') {
+        if line.starts_with("diff --git ") {
+            section_starts.push(offset);
+        }
+        offset += line.len();
+    }
+
+    let mut file_diffs = Vec::new();
+    for (index, start) in section_starts.iter().enumerate() {
+        let end = section_starts.get(index + 1).copied().unwrap_or(diff.len());
+        let text = &diff[*start..end];
+        let mut file_diff = FileDiff {
+            path: String::new(),
+            text,
+            lines_added: 0,
+            lines_removed: 0,
+        };
+        let mut old_path = None;
+        let mut in_hunk = false;
+        for line in text.lines() {
+            if line.starts_with("@@") {
+                in_hunk = true;
+            } else if in_hunk && line.starts_with('+') {
+                file_diff.lines_added += 1;
+            } else if in_hunk && line.starts_with('-') {
+                file_diff.lines_removed += 1;
+            } else if let Some(path) = line.strip_prefix("--- a/") {
+                old_path = Some(path);
+            } else if let Some(path) = line.strip_prefix("+++ b/") {
+                file_diff.path = path.to_string();
+            } else if let Some(path) = line
+                .strip_prefix("rename to ")
+                .or_else(|| line.strip_prefix("copy to "))
+            {
+                file_diff.path = path.to_string();
+            }
+        }
+        if file_diff.path.is_empty() {
+            // Deleted files have no `+++ b/` path, and binary or mode-only changes have neither,
+            // so fall back to the header's `b/` path.
+            file_diff.path = old_path
+                .map(str::to_string)
+                .or_else(|| header_path(text))
+                .unwrap_or_default();
+        }
+        file_diffs.push(file_diff);
+    }
+    file_diffs
+}
+
+/// Leaves out the diffs of files that match the `file_scan_exclusions` or `private_files` setting,
+/// so that changes to secrets such as `.env` files don't reach the model. Returns the other diffs
+/// with how each file is shown, and how many were left out.
+fn visible_file_diffs<'a>(
+    project: &Project,
+    work_directory: &Path,
+    file_diffs: Vec<FileDiff<'a>>,
+    cx: &App,
+) -> (Vec<(GitDiffFile, FileDiff<'a>)>, usize) {
+    let mut hidden_files = 0;
+    let mut visible = Vec::with_capacity(file_diffs.len());
+    for file_diff in file_diffs {
+        let project_path = project.find_project_path(work_directory.join(&file_diff.path), cx);
+        if let Some(project_path) = &project_path {
+            let worktree_settings = WorktreeSettings::get(Some(project_path.into()), cx);
+            if worktree_settings.is_path_excluded(&project_path.path)
+                || worktree_settings.is_path_private(&project_path.path)
+            {
+                hidden_files += 1;
+                continue;
+            }
+        }
+        let path = project_path
+            .and_then(|project_path| display_project_path(project, &project_path, cx))
+            .unwrap_or_else(|| file_diff.path.clone());
+        let file = GitDiffFile {
+            path,
+            lines_added: file_diff.lines_added,
+            lines_removed: file_diff.lines_removed,
+        };
+        visible.push((file, file_diff));
+    }
+    (visible, hidden_files)
+}
+
+fn header_path(text: &str) -> Option<String> {
+    let header = text.lines().next()?.strip_prefix("diff --git a/")?;
+    let (_, new_path) = header.split_once(" b/")?;
+    Some(new_path.to_string())
+}
+
+fn path_is_within(path: &str, filter: &str) -> bool {
+    filter.is_empty()
+        || path == filter
+        || path
+            .strip_prefix(filter)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test};
+    use gpui::{TestAppContext, UpdateGlobal as _};
+    use indoc::indoc;
+    use serde_json::json;
+    use settings::SettingsStore;
+    use util::path;
+
+    #[gpui::test]
+    async fn test_diffs_of_private_and_excluded_files_are_hidden(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                ".env": "TOKEN=new\n",
+                "src": { "lib.rs": "fn main() {}\n" },
+                "generated": { "out.txt": "new\n" }
+            }),
+            cx,
+        )
+        .await;
+        cx.update(|cx| {
+            SettingsStore::update_global(cx, |store, cx| {
+                store.update_user_settings(cx, |settings| {
+                    settings.project.worktree.file_scan_exclusions =
+                        Some(vec!["**/generated".to_string()]);
+                });
+            });
+        });
+        let diff = indoc! {"
+            diff --git a/.env b/.env
+            --- a/.env
+            +++ b/.env
+            @@ -1 +1 @@
+            -TOKEN=old
+            +TOKEN=new
+            diff --git a/src/lib.rs b/src/lib.rs
+            --- a/src/lib.rs
+            +++ b/src/lib.rs
+            @@ -1 +1 @@
+            -fn main() { }
+            +fn main() {}
+            diff --git a/generated/out.txt b/generated/out.txt
+            --- a/generated/out.txt
+            +++ b/generated/out.txt
+            @@ -1 +1 @@
+            -old
+            +new
+        "};
+
+        let (file_diffs, hidden_files) = test_project.project.read_with(cx, |project, cx| {
+            visible_file_diffs(
+                project,
+                Path::new(path!("/root")),
+                split_unified_diff(diff),
+                cx,
+            )
+        });
+        assert_eq!(hidden_files, 2);
+        assert_eq!(
+            file_diffs
+                .iter()
+                .map(|(file, file_diff)| (file.path.as_str(), file_diff.path.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("root/src/lib.rs", "src/lib.rs")]
+        );
+        assert!(
+            !file_diffs
+                .iter()
+                .any(|(_, file_diff)| file_diff.text.contains("TOKEN"))
+        );
+
+        let output = GitDiffToolOutput {
+            base: "HEAD".into(),
+            files: Vec::new(),
+            diff: Some(String::new()),
+            hidden_files,
+        };
+        let LanguageModelToolResultContent::Text(text) = output.into() else {
+            panic!("expected text output");
+        };
+        assert_eq!(
+            text.as_ref(),
+            "No changes relative to HEAD.\n\n2 changed file(s) matching the file_scan_exclusions or private_files setting aren't shown."
+        );
+    }
+
+    #[test]
+    fn test_split_unified_diff() {
+        let diff = indoc! {"
+            diff --git a/src/lib.rs b/src/lib.rs
+            index 1111111..2222222 100644
+            --- a/src/lib.rs
+            +++ b/src/lib.rs
+            @@ -1,3 +1,3 @@
+             fn main() {
+            -    println!(\"hi\");
+            +    println!(\"hello\");
+            +    println!(\"world\");
+             }
+            diff --git a/src/old.rs b/src/old.rs
+            deleted file mode 100644
+            index 3333333..0000000
+            --- a/src/old.rs
+            +++ /dev/null
+            @@ -1 +0,0 @@
+            --- a comment that looks like a header
+            diff --git a/logo.png b/logo.png
+            index 4444444..5555555 100644
+            Binary files a/logo.png and b/logo.png differ
+        "};
+
+        let file_diffs = split_unified_diff(diff);
+        assert_eq!(
+            file_diffs
+                .iter()
+                .map(|file_diff| (
+                    file_diff.path.as_str(),
+                    file_diff.lines_added,
+                    file_diff.lines_removed
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("src/lib.rs", 2, 1),
+                ("src/old.rs", 0, 1),
+                ("logo.png", 0, 0)
+            ]
+        );
+        assert_eq!(
+            file_diffs
+                .iter()
+                .map(|file_diff| file_diff.text)
+                .collect::<String>(),
+            diff
+        );
+        assert!(path_is_within("src/lib.rs", "src"));
+        assert!(!path_is_within("src2/lib.rs", "src"));
+    }
+}
@@ -0,0 +1,306 @@
+use super::{
+    AnchorHandles, AnchorMemory, ContextualAnchor, display_project_path, resolve_project_path,
+};
+use crate::{AgentTool, ToolCallEventStream};
+use agent_client_protocol as acp;
+use anyhow::{Context as _, Result, anyhow};
+use gpui::{App, Entity, SharedString, Task};
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::{Project, WorktreeSettings};
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{fmt::Write as _, sync::Arc};
+
+const DEFAULT_COMMITS_PER_PAGE: usize = 20;
+const MAX_COMMITS_PER_PAGE: usize = 100;
+
+/// Lists the recent commits that changed a file, or only the lines of an anchor's `context`,
+/// newest first, with each commit's SHA, author, date and message. Use it to find out why code
+/// looks the way it does; follow up with `git_diff` against a commit to see what it changed.
+///
+/// Give either an `anchor` or a `path`. Results are paginated; pass the `offset` from the previous
+/// page to see older commits.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct GitLogToolInput {
+    /// An anchor for the code whose history to list. Only commits that changed the lines its
+    /// `context` spans are returned, even if the lines moved.
+    #[serde(default)]
+    pub anchor: Option<ContextualAnchor>,
+    /// The path of the file whose history to list, when not giving an `anchor`.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// How many of the newest commits to skip. Defaults to 0.
+    #[serde(default)]
+    pub offset: usize,
+    /// How many commits to return, up to 100. Defaults to 20.
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct GitLogToolOutput {
+    pub path: String,
+    /// The 1-based first and last lines whose history was listed, if an anchor was given.
+    pub lines: Option<(u32, u32)>,
+    /// The commits, newest first.
+    pub commits: Vec<GitLogCommit>,
+    /// The number of newer commits that were skipped.
+    pub offset: usize,
+    /// Whether there are older commits after this page.
+    pub has_more: bool,
+    /// The handle under which the input anchor was remembered, if one was given.
+    pub handle: Option<String>,
+    /// The symbol handle under which the input anchor was remembered, if it is unambiguous.
+    pub symbol_handle: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct GitLogCommit {
+    /// The commit's full SHA.
+    pub commit: String,
+    pub author: String,
+    pub author_email: String,
+    /// The day the commit was authored, as YYYY-MM-DD in UTC.
+    pub date: String,
+    /// The whole commit message.
+    pub message: String,
+}
+
+impl From<GitLogToolOutput> for LanguageModelToolResultContent {
+    fn from(output: GitLogToolOutput) -> Self {
+        let mut text = match output.lines {
+            Some((start_line, end_line)) => format!(
+                "# History of {} [L{start_line}-L{end_line}]\n\n",
+                output.path
+            ),
+            None => format!("# History of {}\n\n", output.path),
+        };
+        if output.commits.is_empty() {
+            text.push_str("No commits found.\n");
+        }
+        for commit in &output.commits {
+            let short_commit = commit
+                .commit
+                .get(..git::SHORT_SHA_LENGTH)
+                .unwrap_or(commit.commit.as_str());
+            writeln!(
+                text,
+                "## {short_commit} by {} <{}> on {}\n\n{}\n",
+                commit.author,
+                commit.author_email,
+                commit.date,
+                commit.message.trim()
+            )
+            .ok();
+        }
+
+        let page_start = output.offset + 1;
+        let page_end = output.offset + output.commits.len();
+        if output.has_more {
+            writeln!(
+                text,
+                "Showing commits {page_start}-{page_end} (there were more commits found; use offset: {page_end} to see older commits)"
+            )
+            .ok();
+        } else if !output.commits.is_empty() {
+            writeln!(
+                text,
+                "Showing commits {page_start}-{page_end} (total commits: {page_end})"
+            )
+            .ok();
+        }
+        if let Some(reference) = output.handle {
+            let handles = AnchorHandles {
+                reference,
+                symbol: output.symbol_handle,
+            };
+            text.push('\n');
+            text.push_str(&handles.describe());
+        }
+        text.into()
+    }
+}
+
+pub struct GitLogTool {
+    project: Entity<Project>,
+    anchor_memory: Entity<AnchorMemory>,
+}
+
+impl GitLogTool {
+    pub fn new(project: Entity<Project>, anchor_memory: Entity<AnchorMemory>) -> Self {
+        Self {
+            project,
+            anchor_memory,
+        }
+    }
+}
+
+impl AgentTool for GitLogTool {
+    type Input = GitLogToolInput;
+    type Output = GitLogToolOutput;
+
+    fn name() -> &'static str {
+        "git_log"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Read
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        let Ok(input) = input else {
+            return "Git log".into();
+        };
+        match (input.anchor, input.path) {
+            (Some(anchor), _) => match anchor.handle {
+                Some(handle) => format!("History of `{handle}`").into(),
+                None => format!("History of `{}` in {}", anchor.token, anchor.path).into(),
+            },
+            (None, Some(path)) => format!("History of {path}").into(),
+            (None, None) => "Git log".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let anchor_memory = self.anchor_memory.clone();
+        let offset = input.offset;
+        let limit = input
+            .limit
+            .unwrap_or(DEFAULT_COMMITS_PER_PAGE)
+            .clamp(1, MAX_COMMITS_PER_PAGE);
+
+        let anchor = match input.anchor {
+            Some(anchor) => match anchor_memory.read(cx).expand(anchor) {
+                Ok(anchor) => Some(anchor),
+                Err(error) => return Task::ready(Err(error)),
+            },
+            None => None,
+        };
+        let path = match (&anchor, input.path) {
+            (Some(anchor), _) => {
+                if let Err(error) = anchor.validate_basic() {
+                    return Task::ready(Err(error));
+                }
+                if anchor.revision.is_some() {
+                    return Task::ready(Err(anyhow!(
+                        "The history is traced from the project's current files, so the anchor can't have a `revision`."
+                    )));
+                }
+                anchor.path.clone()
+            }
+            (None, Some(path)) => path,
+            (None, None) => {
+                return Task::ready(Err(anyhow!(
+                    "Provide either an `anchor` or a `path` to list the history of."
+                )));
+            }
+        };
+        let project_path = match resolve_project_path(project.read(cx), &path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Task::ready(Err(anyhow!(
+                "Cannot read file history because its path matches the file_scan_exclusions or private_files setting: {path}"
+            )));
+        }
+        let Some((repository, repo_path)) = project
+            .read(cx)
+            .git_store()
+            .read(cx)
+            .repository_and_path_for_project_path(&project_path, cx)
+        else {
+            return Task::ready(Err(anyhow!("{path} is not in a git repository.")));
+        };
+        let display_path =
+            display_project_path(project.read(cx), &project_path, cx).unwrap_or(path);
+        let abs_path = project.read(cx).absolute_path(&project_path, cx);
+
+        cx.spawn(async move |cx| {
+            let mut handles = None;
+            let mut rows = None;
+            if let Some(anchor) = &anchor {
+                let buffer = project
+                    .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+                    .await?;
+                buffer
+                    .read_with(cx, |buffer, _| buffer.parsing_idle())?
+                    .await;
+                let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot())?;
+                let token_offset = anchor.resolve_unique_token_offset(&snapshot, &display_path)?;
+                handles = Some(
+                    anchor_memory.update(cx, |anchor_memory, _| anchor_memory.remember(anchor))?,
+                );
+                rows = Some(anchor.context_rows(
+                    snapshot.offset_to_point(token_offset).row,
+                    snapshot.max_point().row,
+                ));
+            }
+
+            if let Some(abs_path) = abs_path {
+                let start_row = rows.as_ref().map(|rows| rows.start);
+                event_stream.update_fields(
+                    acp::ToolCallUpdateFields::new()
+                        .locations(vec![acp::ToolCallLocation::new(abs_path).line(start_row)]),
+                );
+            }
+
+            // One extra commit tells whether there is another page.
+            let history = repository.update(cx, |repository, _| match rows.clone() {
+                Some(rows) => repository.line_history(repo_path, rows, offset, Some(limit + 1)),
+                None => repository.file_history_paginated(repo_path, offset, Some(limit + 1)),
+            })?;
+            let history = history
+                .await?
+                .with_context(|| format!("Couldn't list the history of {display_path}"))?;
+
+            let has_more = history.entries.len() > limit;
+            let commits = history
+                .entries
+                .into_iter()
+                .take(limit)
+                .map(|entry| GitLogCommit {
+                    commit: entry.sha.to_string(),
+                    author: entry.author_name.to_string(),
+                    author_email: entry.author_email.to_string(),
+                    date: chrono::DateTime::from_timestamp(entry.commit_timestamp, 0)
+                        .map(|date| date.format("%Y-%m-%d").to_string())
+                        .unwrap_or_default(),
+                    message: entry.message.to_string(),
+                })
+                .collect();
+
+            Ok(GitLogToolOutput {
+                path: display_path,
+                lines: rows.map(|rows| (rows.start + 1, rows.end)),
+                commits,
+                offset,
+                has_more,
+                handle: handles.as_ref().map(|handles| handles.reference.clone()),
+                symbol_handle: handles.and_then(|handles| handles.symbol),
+            })
+        })
+    }
+}
@@ -0,0 +1,132 @@
+use super::resolve_project_path;
+use super::support::DRY_RUN_NOTE;
+use crate::{AgentTool, ToolCallEventStream};
+use agent_client_protocol as acp;
+use anyhow::{Context as _, Result, anyhow};
+use git::repository::RepoPath;
+use gpui::{App, Entity, SharedString, Task};
+use project::{Project, git_store::Repository};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use util::markdown::MarkdownInlineCode;
+
+/// Stages files in the project's git repository so that the next `git_commit` includes them, or
+/// unstages them again. New files must be staged before they can be committed.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct GitStageToolInput {
+    /// The paths of the files to stage. They must all be in the same repository.
+    pub paths: Vec<String>,
+    /// Whether to unstage the paths instead, keeping their changes in the working tree.
+    /// Defaults to false.
+    #[serde(default)]
+    pub unstage: bool,
+}
+
+pub struct GitStageTool {
+    project: Entity<Project>,
+}
+
+impl GitStageTool {
+    pub fn new(project: Entity<Project>) -> Self {
+        Self { project }
+    }
+}
+
+impl AgentTool for GitStageTool {
+    type Input = GitStageToolInput;
+    type Output = String;
+
+    fn name() -> &'static str {
+        "git_stage"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Edit
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        let Ok(input) = input else {
+            return "Stage files".into();
+        };
+        let verb = if input.unstage { "Unstage" } else { "Stage" };
+        match input.paths.as_slice() {
+            [path] => format!("{verb} {}", MarkdownInlineCode(path)).into(),
+            paths => format!("{verb} {} paths", paths.len()).into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let (repository, repo_paths) = match repository_paths(&self.project, &input.paths, cx) {
+            Ok(resolved) => resolved,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let verb = if input.unstage { "unstage" } else { "stage" };
+        if event_stream.dry_run() {
+            return Task::ready(Ok(format!(
+                "{DRY_RUN_NOTE} Would {verb} {}",
+                input.paths.join(", ")
+            )));
+        }
+
+        let authorize = event_stream.authorize(self.initial_title(Ok(input.clone()), cx), cx);
+        cx.spawn(async move |cx| {
+            authorize.await?;
+            repository
+                .update(cx, |repository, cx| {
+                    if input.unstage {
+                        repository.unstage_entries(repo_paths, cx)
+                    } else {
+                        repository.stage_entries(repo_paths, cx)
+                    }
+                })?
+                .await
+                .with_context(|| format!("Couldn't {verb} {}", input.paths.join(", ")))?;
+            let verb = if input.unstage { "Unstaged" } else { "Staged" };
+            Ok(format!("{verb} {}", input.paths.join(", ")))
+        })
+    }
+}
+
+/// Resolves `paths` to the repository they are all in and their paths within it.
+fn repository_paths(
+    project: &Entity<Project>,
+    paths: &[String],
+    cx: &App,
+) -> Result<(Entity<Repository>, Vec<RepoPath>)> {
+    let project = project.read(cx);
+    let git_store = project.git_store().read(cx);
+    let mut repository = None;
+    let mut repo_paths = Vec::new();
+    for path in paths {
+        let project_path = resolve_project_path(project, path, cx)?;
+        let (path_repository, repo_path) = git_store
+            .repository_and_path_for_project_path(&project_path, cx)
+            .with_context(|| format!("{path} is not in a git repository."))?;
+        if repository
+            .as_ref()
+            .is_some_and(|repository| *repository != path_repository)
+        {
+            return Err(anyhow!(
+                "{path} is in a different git repository than the other paths. Stage each repository's paths separately."
+            ));
+        }
+        repository = Some(path_repository);
+        repo_paths.push(repo_path);
+    }
+    let repository = repository.context("Provide at least one path.")?;
+    Ok((repository, repo_paths))
+}
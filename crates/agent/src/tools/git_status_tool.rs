@@ -0,0 +1,311 @@
+use super::{display_project_path, resolve_project_path};
+use crate::{AgentTool, ToolCallEventStream};
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use gpui::{App, Entity, SharedString, Task};
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::Project;
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Write as _, sync::Arc};
+
+/// The operations that leave a `*_HEAD` ref behind while they are in progress, in the order
+/// the repository snapshot lists their heads.
+const IN_PROGRESS_OPERATIONS: [&str; 5] = ["merge", "cherry-pick", "rebase", "revert", "apply"];
+
+/// Reports the state of the project's git repository: the current branch, how far it is ahead of
+/// or behind its upstream, which files are staged, modified, untracked or conflicted, and whether
+/// a merge, rebase or similar operation is in progress.
+///
+/// Check it before making edits when the working tree may be mid-merge: conflicted files should
+/// be resolved before anything else is changed.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct GitStatusToolInput {
+    /// Optional path of a file or directory in the repository to report on, for projects with
+    /// more than one repository. The active repository is used when omitted.
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct GitStatusToolOutput {
+    /// The checked out branch, or none when HEAD is detached.
+    pub branch: Option<String>,
+    /// The branch's upstream, such as "origin/main".
+    pub upstream: Option<String>,
+    /// How many commits the branch has that its upstream doesn't.
+    pub ahead: Option<u32>,
+    /// How many commits the upstream has that the branch doesn't.
+    pub behind: Option<u32>,
+    /// The operation that is in progress, such as "merge" or "rebase".
+    pub operation_in_progress: Option<String>,
+    /// Files with unresolved merge conflicts.
+    pub conflicted: Vec<String>,
+    /// Files with changes staged for commit.
+    pub staged: Vec<String>,
+    /// Tracked files with changes that aren't staged.
+    pub modified: Vec<String>,
+    /// Files that git doesn't track yet.
+    pub untracked: Vec<String>,
+    /// Whether there are no conflicts and no operation in progress, so edits won't interfere
+    /// with git.
+    pub safe_to_edit: bool,
+}
+
+impl From<GitStatusToolOutput> for LanguageModelToolResultContent {
+    fn from(output: GitStatusToolOutput) -> Self {
+        let mut text = match &output.branch {
+            Some(branch) => format!("On branch {branch}"),
+            None => "HEAD is detached".to_string(),
+        };
+        if let Some(upstream) = &output.upstream {
+            match (output.ahead, output.behind) {
+                (Some(ahead), Some(behind)) => write!(
+                    text,
+                    ", {ahead} commit(s) ahead of and {behind} behind {upstream}"
+                )
+                .ok(),
+                _ => write!(text, ", tracking {upstream} (gone)").ok(),
+            };
+        }
+        text.push_str(".\n");
+        if let Some(operation) = &output.operation_in_progress {
+            writeln!(text, "A {operation} is in progress.").ok();
+        }
+
+        for (heading, paths) in [
+            ("Conflicted", &output.conflicted),
+            ("Staged", &output.staged),
+            ("Modified", &output.modified),
+            ("Untracked", &output.untracked),
+        ] {
+            if !paths.is_empty() {
+                writeln!(text, "\n{heading}:").ok();
+                for path in paths {
+                    writeln!(text, "- {path}").ok();
+                }
+            }
+        }
+        if output.conflicted.is_empty()
+            && output.staged.is_empty()
+            && output.modified.is_empty()
+            && output.untracked.is_empty()
+        {
+            text.push_str("\nThe working tree is clean.\n");
+        }
+        if !output.safe_to_edit {
+            text.push_str(
+                "\nResolve the conflicts or finish the operation in progress before making other edits.\n",
+            );
+        }
+        text.into()
+    }
+}
+
+pub struct GitStatusTool {
+    project: Entity<Project>,
+}
+
+impl GitStatusTool {
+    pub fn new(project: Entity<Project>) -> Self {
+        Self { project }
+    }
+}
+
+impl AgentTool for GitStatusTool {
+    type Input = GitStatusToolInput;
+    type Output = GitStatusToolOutput;
+
+    fn name() -> &'static str {
+        "git_status"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Read
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(GitStatusToolInput { path: Some(path) }) => format!("Git status of {path}").into(),
+            _ => "Git status".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        _event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.read(cx);
+        let git_store = project.git_store().read(cx);
+        let repository = match &input.path {
+            Some(path) => {
+                let project_path = match resolve_project_path(project, path, cx) {
+                    Ok(project_path) => project_path,
+                    Err(error) => return Task::ready(Err(error)),
+                };
+                match git_store.repository_and_path_for_project_path(&project_path, cx) {
+                    Some((repository, _)) => repository,
+                    None => {
+                        return Task::ready(Err(anyhow!("{path} is not in a git repository.")));
+                    }
+                }
+            }
+            None => match git_store.active_repository() {
+                Some(repository) => repository,
+                None => {
+                    return Task::ready(Err(anyhow!("The project doesn't have a git repository.")));
+                }
+            },
+        };
+
+        let repository = repository.read(cx);
+        let branch = repository.branch.as_ref();
+        let upstream = branch.and_then(|branch| branch.upstream.as_ref());
+        let tracking = upstream.and_then(|upstream| upstream.tracking.status());
+        let operation_in_progress = repository
+            .merge
+            .heads
+            .iter()
+            .zip(IN_PROGRESS_OPERATIONS)
+            .find_map(|(head, operation)| head.as_ref().map(|_| operation.to_string()));
+
+        let mut conflicted = Vec::new();
+        let mut staged = Vec::new();
+        let mut modified = Vec::new();
+        let mut untracked = Vec::new();
+        for entry in repository.cached_status() {
+            let path = repository
+                .repo_path_to_project_path(&entry.repo_path, cx)
+                .and_then(|project_path| display_project_path(project, &project_path, cx))
+                .unwrap_or_else(|| entry.repo_path.as_unix_str().to_string());
+            if entry.status.is_conflicted() {
+                conflicted.push(path);
+            } else if entry.status.is_untracked() {
+                untracked.push(path);
+            } else {
+                let staging = entry.status.staging();
+                if staging.has_staged() {
+                    staged.push(path.clone());
+                }
+                if staging.has_unstaged() {
+                    modified.push(path);
+                }
+            }
+        }
+
+        let safe_to_edit = conflicted.is_empty() && operation_in_progress.is_none();
+        Task::ready(Ok(GitStatusToolOutput {
+            branch: branch.map(|branch| branch.name().to_string()),
+            upstream: upstream.map(|upstream| {
+                upstream
+                    .stripped_ref_name()
+                    .unwrap_or(upstream.ref_name.as_ref())
+                    .to_string()
+            }),
+            ahead: tracking.map(|tracking| tracking.ahead),
+            behind: tracking.map(|tracking| tracking.behind),
+            operation_in_progress,
+            conflicted,
+            staged,
+            modified,
+            untracked,
+            safe_to_edit,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, run_tool};
+    use git::{
+        repository::repo_path,
+        status::{UnmergedStatus, UnmergedStatusCode},
+    };
+    use gpui::TestAppContext;
+    use serde_json::json;
+    use util::path;
+
+    #[gpui::test]
+    async fn test_git_status(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                ".git": {},
+                "src": {
+                    "lib.rs": "pub fn answer() -> u32 {\n    42\n}\n",
+                    "main.rs": "fn main() {}\n",
+                    "new.rs": "pub fn new() {}\n",
+                }
+            }),
+            cx,
+        )
+        .await;
+        let dot_git = path!("/root/.git").as_ref();
+        test_project.fs.set_head_and_index_for_repo(
+            dot_git,
+            &[
+                ("src/lib.rs", "pub fn answer() -> u32 {\n    0\n}\n".into()),
+                ("src/main.rs", "fn main() {}\n".into()),
+            ],
+        );
+        test_project.fs.set_branch_name(dot_git, Some("feature"));
+        cx.run_until_parked();
+        let tool = Arc::new(GitStatusTool::new(test_project.project.clone()));
+
+        let (output, _) = run_tool(tool.clone(), GitStatusToolInput { path: None }, cx).await;
+        assert_eq!(
+            output.expect("tool failed"),
+            GitStatusToolOutput {
+                branch: Some("feature".into()),
+                upstream: None,
+                ahead: None,
+                behind: None,
+                operation_in_progress: None,
+                conflicted: Vec::new(),
+                staged: Vec::new(),
+                modified: vec!["root/src/lib.rs".into()],
+                untracked: vec!["root/src/new.rs".into()],
+                safe_to_edit: true,
+            }
+        );
+
+        test_project.fs.set_unmerged_paths_for_repo(
+            dot_git,
+            &[(
+                repo_path("src/main.rs"),
+                UnmergedStatus {
+                    first_head: UnmergedStatusCode::Updated,
+                    second_head: UnmergedStatusCode::Updated,
+                },
+            )],
+        );
+        cx.run_until_parked();
+
+        let (output, _) = run_tool(
+            tool,
+            GitStatusToolInput {
+                path: Some("root/src".into()),
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+        assert_eq!(output.conflicted, vec!["root/src/main.rs".to_string()]);
+        assert!(!output.safe_to_edit);
+    }
+}
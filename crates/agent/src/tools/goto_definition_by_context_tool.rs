@@ -1,55 +1,42 @@
-use crate::{AgentTool, ToolCallEventStream};
+use super::{
+    AnchorBuffer, AnchorMatch, AnchorMemory, ContextualAnchor, LanguageServerResult, TokenSyntax,
+    display_project_path, language_server_results, language_server_retry_note,
+    merge_language_server_results, open_anchor_buffer, request_language_server,
+    resolve_project_path, unsaved_changes_note, warm_up_language_servers,
+};
+use crate::{AgentTool, ToolCallEventStream, ToolPreparation};
 use agent_client_protocol as acp;
+use agent_settings::AgentSettings;
 use anyhow::{Result, anyhow};
+use collections::HashSet;
 use gpui::{App, Entity, SharedString, Task};
 use language::Point;
 use language_model::LanguageModelToolResultContent;
-use project::{Project, WorktreeSettings};
-use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use project::{AgentLocation, Project, ProjectPath, WorktreeSettings};
+use std::{sync::Arc, time::Instant};
 use text::OffsetRangeExt;
 use text::ToPoint as _;
 
 const MAX_SCOPE_LINES: usize = 42;
 const PAGINATE_LIMIT: usize = 24;
 
-/// Input used by both goto-definition and find-references tools that locate a token
-/// by searching for a multi-word `context` that must contain the `token`.
-/// The optional `index` disambiguates multiple occurrences.
-///
-/// The `context` MUST contain `token`. `index` is 0-based and selects which occurrence
-/// to use when multiple matches are found.
-#[derive(Debug, Serialize, Deserialize, JsonSchema)]
-pub struct ContextPositionInput {
-    /// Project-relative path to the file containing the symbol (e.g. "src/main.rs").
-    pub path: String,
-
-    /// A multi-word snippet from the file which explicitly contains `token`.
-    /// The tool will search the file for this exact snippet to locate the token's position.
-    pub context: String,
-
-    /// The exact token inside `context` to locate.
-    pub token: String,
-
-    /// Optional 0-based index to disambiguate multiple matches.
-    #[serde(default)]
-    pub index: Option<u32>,
-}
-
 /// Tool: goto_definition_by_context
 pub struct GotoDefinitionByContextTool {
     project: Entity<Project>,
+    anchor_memory: Entity<AnchorMemory>,
 }
 
 impl GotoDefinitionByContextTool {
-    pub fn new(project: Entity<Project>) -> Self {
-        Self { project }
+    pub fn new(project: Entity<Project>, anchor_memory: Entity<AnchorMemory>) -> Self {
+        Self {
+            project,
+            anchor_memory,
+        }
     }
 }
 
 impl AgentTool for GotoDefinitionByContextTool {
-    type Input = ContextPositionInput;
+    type Input = ContextualAnchor;
     type Output = LanguageModelToolResultContent;
 
     fn name() -> &'static str {
@@ -60,37 +47,74 @@ impl AgentTool for GotoDefinitionByContextTool {
         acp::ToolKind::Read
     }
 
+    fn input_examples() -> Vec<Self::Input> {
+        vec![ContextualAnchor {
+            path: "project/src/main.rs".into(),
+            context: "let config = Config::load(&path)?;".into(),
+            token: "load".into(),
+            index: None,
+            handle: None,
+            revision: None,
+            whole_word: false,
+            approximate_line: None,
+            case_insensitive: false,
+            unicode_normalize: false,
+            end_token: None,
+            end_index: None,
+        }]
+    }
+
     fn initial_title(
         &self,
         input: Result<Self::Input, serde_json::Value>,
         _cx: &mut App,
     ) -> SharedString {
         if let Ok(input) = input {
-            format!("Goto definition for `{}` in `{}`", input.token, input.path).into()
+            if let Some(handle) = input.handle {
+                format!("Goto definition for `{handle}`").into()
+            } else {
+                format!("Goto definition for `{}` in `{}`", input.token, input.path).into()
+            }
         } else {
             "Goto definition by context".into()
         }
     }
 
+    fn prepare(
+        self: Arc<Self>,
+        input: Self::Input,
+        cx: &mut App,
+    ) -> Option<Task<Result<ToolPreparation>>> {
+        let input = self.anchor_memory.read(cx).expand(input).ok()?;
+        if input.revision.is_some() {
+            return None;
+        }
+        warm_up_language_servers(&self.project, &input.path, cx)
+    }
+
     fn run(
         self: Arc<Self>,
         input: Self::Input,
-        _event_stream: ToolCallEventStream,
+        event_stream: ToolCallEventStream,
         cx: &mut App,
     ) -> Task<Result<Self::Output>> {
         let project = self.project.clone();
+        let anchor_memory = self.anchor_memory.clone();
 
-        // Validate early
-        if !input.context.contains(&input.token) {
-            return Task::ready(Err(anyhow!(
-                "The provided `context` must contain the `token`."
-            )));
+        let input = match anchor_memory.read(cx).expand(input) {
+            Ok(input) => input,
+            Err(error) => return Task::ready(Err(error)),
+        };
+
+        // Validate early, before the buffer is opened.
+        if let Err(error) = input.validate_basic() {
+            return Task::ready(Err(error));
         }
 
         // Resolve project path and perform WorktreeSettings checks on the foreground thread (cx: &mut App).
-        let project_path = match project.read(cx).find_project_path(&input.path, cx) {
-            Some(p) => p,
-            None => return Task::ready(Err(anyhow!("Path {} not found in project", &input.path))),
+        let project_path = match resolve_project_path(project.read(cx), &input.path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
         };
 
         // Security checks (mirror read_file behavior)
@@ -122,318 +146,169 @@ impl AgentTool for GotoDefinitionByContextTool {
             )));
         }
 
-        // Clone project_path for the async closure and proceed on the async thread.
-        let project_path_clone = project_path.clone();
-        cx.spawn(async move |cx| {
-            let project_path = project_path_clone;
-            // Open buffer
-            let buffer = project
-                .update(cx, |project, cx| {
-                    project.open_buffer(project_path.clone(), cx)
-                })?
-                .await?;
-
-            if buffer.read_with(cx, |buffer, _| {
-                buffer
-                    .file()
-                    .as_ref()
-                    .is_none_or(|file| !file.disk_state().exists())
-            })? {
-                anyhow::bail!("{} not found", &input.path);
-            }
-
-            buffer
-                .read_with(cx, |buffer, _| buffer.parsing_idle())?
-                .await;
-
-            // Find candidates by substring search (cheap) and then rely on tree-sitter for scope/validation.
-            // Validate each token occurrence with Tree-sitter via snapshot.syntax_ancestor so that
-            // substrings inside other identifiers (e.g. `is_path_excluded`) are not treated as the token.
-            let candidates: Vec<usize> = buffer.read_with(cx, |buffer, _| {
-                let text = buffer.text();
-                let mut found = Vec::new();
-                let mut start = 0usize;
-                while let Some(pos) = text[start..].find(&input.context) {
-                    let ctx_start = start + pos;
-                    let ctx_end = ctx_start + input.context.len();
-                    let ctx_slice = &text[ctx_start..ctx_end];
-                    let mut inner = 0usize;
-                    while let Some(tokpos) = ctx_slice[inner..].find(&input.token) {
-                        let tok_abs = ctx_start + inner + tokpos;
-
-                        // Validate candidate using tree-sitter via the buffer snapshot.
-                        // Accept if:
-                        // - there is no syntax node available (permissive fallback), OR
-                        // - the ancestor node is named, not a comment/string, and its text equals the token.
-                        let snapshot = buffer.snapshot();
-                        let pt = snapshot.offset_to_point(tok_abs);
-                        let token_point = Point::new(pt.row, pt.column);
-                        let token_point_end =
-                            Point::new(pt.row, pt.column.saturating_add(input.token.len() as u32));
-                        let token_point_range = token_point..token_point_end;
-
-                        // debug logging removed
-
-                        let mut accept = false;
-                        if let Some(node) = snapshot.syntax_ancestor(token_point_range.clone()) {
-                            if node.is_named() {
-                                // Prefer a named descendant that exactly covers the token's byte range.
-                                let tok_end = tok_abs + input.token.len();
-                                if let Some(desc) =
-                                    node.named_descendant_for_byte_range(tok_abs, tok_end)
-                                {
-                                    let desc_range = desc.byte_range().to_point(&snapshot);
-                                    let desc_text = snapshot
-                                        .text_for_range(
-                                            snapshot.anchor_before(desc_range.start)
-                                                ..snapshot.anchor_after(desc_range.end),
-                                        )
-                                        .collect::<String>();
-                                    let desc_kind = desc.kind();
-                                    if desc_text.trim() == input.token
-                                        && desc_kind != "comment"
-                                        && desc_kind != "string"
-                                    {
-                                        accept = true;
-                                    }
-                                } else {
-                                    // Fallback: compare the enclosing node's text (exact match).
-                                    let node_range = node.byte_range().to_point(&snapshot);
-                                    let node_text = snapshot
-                                        .text_for_range(
-                                            snapshot.anchor_before(node_range.start)
-                                                ..snapshot.anchor_after(node_range.end),
-                                        )
-                                        .collect::<String>();
-                                    let node_kind = node.kind();
-                                    if node_text.trim() == input.token
-                                        && node_kind != "comment"
-                                        && node_kind != "string"
-                                    {
-                                        accept = true;
-                                    }
-                                }
-                            }
-                        } else {
-                            // No parse tree available: permissive fallback — accept candidate.
-                            accept = true;
-                        }
-
-                        if accept {
-                            found.push(tok_abs);
-                        }
-
-                        inner += tokpos + input.token.len();
-                        if inner >= ctx_slice.len() {
-                            break;
-                        }
-                    }
-                    start = ctx_start + 1;
-                    if start >= text.len() {
-                        break;
-                    }
-                }
-                found
-            })?;
+        // Only move the agent location when the user opted in, since following the agent
+        // would otherwise jump to every lookup result.
+        let follow_agent =
+            <AgentSettings as settings::Settings>::get_global(cx).follow_agent_navigation;
 
-            if candidates.is_empty() {
-                anyhow::bail!("context/token not found in file");
-            }
+        let display_path = display_project_path(project.read(cx), &project_path, cx)
+            .unwrap_or_else(|| input.path.clone());
 
-            // Choose a candidate by index or produce an ambiguous listing with richer previews.
-            let chosen_offset = if let Some(idx) = input.index {
-                let idx_usize = idx as usize;
-                if idx_usize >= candidates.len() {
-                    anyhow::bail!("index out of range ({} candidates)", candidates.len());
+        cx.spawn(async move |cx| {
+            let anchor_buffer = open_anchor_buffer(
+                &project,
+                &project_path,
+                input.revision.clone(),
+                &display_path,
+                cx,
+            )
+            .await?;
+            let chosen_offset = match anchor_buffer.resolve(
+                &input,
+                &display_path,
+                Some(TokenSyntax::Symbol),
+                cx,
+            )? {
+                AnchorMatch::Unique(token_match) => token_match.range.start,
+                AnchorMatch::Ambiguous(matches) => {
+                    return Ok(LanguageModelToolResultContent::Text(Arc::from(
+                        input.describe_ambiguous_matches(
+                            &anchor_buffer.snapshot,
+                            &matches,
+                            &display_path,
+                        ),
+                    )));
                 }
-                candidates[idx_usize]
-            } else if candidates.len() == 1 {
-                candidates[0]
-            } else {
-                // Ambiguous: produce multi-line, syntax-aware previews for each match so the LLM can pick index.
-                // Implementation split into two phases:
-                // 1) compute preferred preview ranges (Point start/end) for each candidate and merge overlapping ranges
-                // 2) extract text for each merged range once and then map candidates into those previews
-                let out = buffer.read_with(cx, |buffer, _| {
-                    let snapshot = buffer.snapshot();
-
-                    // Collect candidate info: (candidate_index, token_row, preview_start_point, preview_end_point)
-                    let mut candidate_infos: Vec<(usize, u32, Point, Point)> = Vec::new();
-                    for (i, &off) in candidates.iter().enumerate() {
-                        let pt = snapshot.offset_to_point(off);
-                        let token_point = Point::new(pt.row, pt.column);
-                        let token_point_end =
-                            Point::new(pt.row, pt.column.saturating_add(input.token.len() as u32));
-
-                        // Default small-clamped preview around token row (-2..+2 lines)
-                        let start_row = pt.row.saturating_sub(2);
-                        let end_row = (pt.row + 2).min(snapshot.max_point().row);
-                        let mut preview_start = Point::new(start_row, 0);
-                        let mut preview_end = Point::new(end_row, snapshot.line_len(end_row));
-
-                        // Prefer enclosing syntax node scope; climb to the largest ancestor within MAX_SCOPE_LINES
-                        if let Some(node) = snapshot.syntax_ancestor(token_point..token_point_end) {
-                            let mut candidate_node = node;
-                            loop {
-                                if let Some(parent) = candidate_node.parent() {
-                                    let parent_range = parent.byte_range().to_point(&snapshot);
-                                    let parent_span_lines =
-                                        parent_range.end.row.saturating_sub(parent_range.start.row);
-                                    if (parent_span_lines as usize) <= MAX_SCOPE_LINES {
-                                        candidate_node = parent;
-                                        continue;
-                                    }
-                                }
-                                break;
-                            }
-                            let full_range = candidate_node.byte_range().to_point(&snapshot);
-                            preview_start = Point::new(full_range.start.row, 0);
-                            preview_end = Point::new(
-                                full_range.end.row,
-                                snapshot.line_len(full_range.end.row),
-                            );
-                        }
-
-                        candidate_infos.push((i, pt.row, preview_start, preview_end));
-                    }
-
-                    // Sort candidate infos by preview start row to make merging easy
-                    candidate_infos.sort_by_key(|(_, _tokrow, s, _e)| s.row);
-
-                    // Merge overlapping/adjacent ranges (by row), collecting candidate indices per merged range
-                    let mut merged: Vec<(u32, u32, Point, Point, Vec<usize>)> = Vec::new();
-                    for (idx, _tokrow, s_pt, e_pt) in candidate_infos.iter() {
-                        let srow = s_pt.row;
-                        let erow = e_pt.row;
-                        if let Some(last) = merged.last_mut() {
-                            // if this start row intersects or touches previous range, merge it
-                            if srow <= last.1 {
-                                if erow > last.1 {
-                                    last.1 = erow;
-                                    last.3 = *e_pt;
-                                }
-                                last.4.push(*idx);
-                                continue;
-                            }
-                        }
-                        merged.push((srow, erow, *s_pt, *e_pt, vec![*idx]));
-                    }
-
-                    // Extract text previews for each merged range
-                    let mut merged_previews: Vec<String> = Vec::new();
-                    for (_srow, _erow, start_pt, end_pt, _cidxs) in merged.iter() {
-                        let text = snapshot
-                            .text_for_range(
-                                snapshot.anchor_before(start_pt)..snapshot.anchor_after(end_pt),
-                            )
-                            .collect::<String>();
-                        merged_previews.push(text);
-                    }
-
-                    // Map candidate index -> merged preview index and compute display row
-                    // We'll build a vec entries in candidate order for deterministic output
-                    let mut candidate_to_preview: Vec<(usize, usize, u32)> = Vec::new(); // (candidate_idx, merged_idx, display_row)
-                    for (merged_idx, (_srow, _erow, _s_pt, _e_pt, cidxs)) in
-                        merged.iter().enumerate()
-                    {
-                        for &c in cidxs.iter() {
-                            // find the token row for candidate c from candidate_infos
-                            // candidate_infos was sorted; find the tuple where first element == c
-                            let token_row = candidate_infos
-                                .iter()
-                                .find(|(ci, _, _, _)| *ci == c)
-                                .map(|(_, tok_row, _, _)| *tok_row)
-                                .unwrap_or(0);
-                            candidate_to_preview.push((c, merged_idx, token_row + 1)); // display rows are 1-based
-                        }
-                    }
-
-                    // Sort candidate_to_preview by candidate index so we output in candidate order
-                    candidate_to_preview.sort_by_key(|(c, _, _)| *c);
-
-                    // Build final ambiguous output string
-                    let mut out = format!(
-                        "Ambiguous token: found {} matches in {}:\n\n",
-                        candidates.len(),
-                        input.path
-                    );
-                    for (_c, merged_idx, row) in candidate_to_preview.iter() {
-                        let preview = &merged_previews[*merged_idx];
-                        out.push_str(&format!(
-                            "[{}] L{}:\n\n``` \n{}\n```\n\n",
-                            _c,
-                            row,
-                            preview.trim()
-                        ));
-                    }
-                    out.push_str("\nProvide `index` (0-based) to disambiguate.");
-                    out
-                })?;
-                return Ok(LanguageModelToolResultContent::Text(Arc::from(out)));
             };
+            let AnchorBuffer {
+                buffer,
+                snapshot,
+                unsaved,
+                ..
+            } = anchor_buffer;
+
+            let handles =
+                anchor_memory.update(cx, |anchor_memory, _| anchor_memory.remember(&input))?;
+
+            if let Some(revision) = &input.revision {
+                let output = input.describe_at_revision(
+                    revision,
+                    &snapshot,
+                    chosen_offset,
+                    MAX_SCOPE_LINES as u32,
+                );
+                let output = format!("{output}\n\n{}", handles.describe());
+                return Ok(LanguageModelToolResultContent::Text(Arc::from(output)));
+            }
 
-            // Convert chosen_offset to an anchor suitable for project methods
-            let anchor = buffer.read_with(cx, |buffer, _| {
-                let snapshot = buffer.snapshot();
-                let point = snapshot.offset_to_point(chosen_offset);
-                snapshot.anchor_before(point)
-            })?;
+            let anchor = snapshot.anchor_before(chosen_offset);
 
+            let lsp_span = ztracing::info_span!(
+                "lsp_definitions",
+                duration_ms = ztracing::field::Empty,
+                results = ztracing::field::Empty
+            );
+            let lsp_started_at = Instant::now();
             // Ask project for definitions at this anchor
-            let defs_task =
-                project.update(cx, |project, cx| project.definitions(&buffer, anchor, cx))?;
-            let defs = defs_task.await?;
+            let defs = request_language_server(
+                |cx| {
+                    let definitions = project.update(cx, |project, cx| {
+                        project.definitions_by_language_server(&buffer, anchor, cx)
+                    });
+                    cx.background_spawn(async move {
+                        definitions.await?.map(language_server_results).transpose()
+                    })
+                },
+                &event_stream,
+                cx,
+            )
+            .await?;
+            let timed_out = defs.response.is_none();
+            let retries = defs.retries;
+            let defs = defs.response.transpose()?.flatten();
+            lsp_span.record("duration_ms", lsp_started_at.elapsed().as_millis() as u64);
+            let defs = cx.update(|cx| {
+                merge_language_server_results(
+                    project.read(cx),
+                    defs.unwrap_or_default(),
+                    |link| &link.target,
+                    cx,
+                )
+            })?;
+            lsp_span.record("results", defs.len());
+
+            if follow_agent && let Some(LanguageServerResult { result: first, .. }) = defs.first() {
+                project.update(cx, |project, cx| {
+                    project.set_agent_location(
+                        Some(AgentLocation {
+                            buffer: first.target.buffer.downgrade(),
+                            position: first.target.range.start,
+                        }),
+                        cx,
+                    );
+                })?;
+                let location = first.target.buffer.read_with(cx, |buffer, cx| {
+                    input.tool_call_location(buffer, first.target.range.clone(), cx)
+                })?;
+                if let Some(location) = location {
+                    event_stream
+                        .update_fields(acp::ToolCallUpdateFields::new().locations(vec![location]));
+                }
+            }
 
+            let render_span =
+                ztracing::info_span!("render_definitions", output_len = ztracing::field::Empty);
+            let _enter = render_span.enter();
             // Format results
-            let output = match defs {
-                Some(loc_links) if !loc_links.is_empty() => {
-                    let mut out = String::new();
-                    let total = loc_links.len();
-                    let page_limit = PAGINATE_LIMIT;
-                    for link in loc_links.into_iter().take(page_limit) {
-                        // For each LocationLink, produce a preview preferring Tree-sitter scope
-                        let (start_line, end_line, preview, maybe_path) =
-                            link.target.buffer.read_with(cx, |buffer, cx| {
-                                let snapshot = buffer.snapshot();
-                                let start_pt = link.target.range.start.to_point(&snapshot);
-                                let end_pt = link.target.range.end.to_point(&snapshot);
-                                // Try syntax ancestor for the target range
-                                let point_start = Point::new(start_pt.row, start_pt.column);
-                                let point_end = Point::new(end_pt.row, end_pt.column);
-                                let preview = if let Some(node) =
-                                    snapshot.syntax_ancestor(point_start..point_end)
-                                {
-                                    let full_range = node.byte_range().to_point(&snapshot);
-                                    let span_lines =
-                                        full_range.end.row.saturating_sub(full_range.start.row);
-                                    if (span_lines as usize) <= MAX_SCOPE_LINES {
-                                        let start_anchor = Point::new(full_range.start.row, 0);
-                                        let end_row = full_range.end.row;
-                                        let end_anchor =
-                                            Point::new(end_row, snapshot.line_len(end_row));
-                                        snapshot
-                                            .text_for_range(
-                                                snapshot.anchor_before(start_anchor)
-                                                    ..snapshot.anchor_after(end_anchor),
-                                            )
-                                            .collect::<String>()
-                                    } else {
-                                        // fallback to -10..+9 around start_pt
-                                        let start_row = start_pt.row.saturating_sub(10);
-                                        let end_row =
-                                            (start_pt.row + 9).min(snapshot.max_point().row);
-                                        let start_anchor = Point::new(start_row, 0);
-                                        let end_anchor =
-                                            Point::new(end_row, snapshot.line_len(end_row));
-                                        snapshot
-                                            .text_for_range(
-                                                snapshot.anchor_before(start_anchor)
-                                                    ..snapshot.anchor_after(end_anchor),
-                                            )
-                                            .collect::<String>()
-                                    }
+            // Only name the servers when they disagree, since with a single server every
+            // definition would name the same one.
+            let show_language_servers = defs
+                .iter()
+                .flat_map(|definition| &definition.language_servers)
+                .collect::<HashSet<_>>()
+                .len()
+                > 1;
+            let output = if defs.is_empty() && timed_out {
+                "The language server didn't answer in time (it may still be indexing), so no definitions were found. Try again shortly.".to_string()
+            } else if defs.is_empty() {
+                "No definitions found (or language server not capable)".to_string()
+            } else {
+                let mut out = String::new();
+                let total = defs.len();
+                let page_limit = PAGINATE_LIMIT;
+                for LanguageServerResult {
+                    result: link,
+                    language_servers,
+                } in defs.into_iter().take(page_limit)
+                {
+                    // For each LocationLink, produce a preview preferring Tree-sitter scope
+                    let (start_line, end_line, preview, maybe_path, target_anchor) =
+                        link.target.buffer.read_with(cx, |buffer, cx| {
+                            let snapshot = buffer.snapshot();
+                            let start_pt = link.target.range.start.to_point(&snapshot);
+                            let end_pt = link.target.range.end.to_point(&snapshot);
+                            // Try syntax ancestor for the target range
+                            let point_start = Point::new(start_pt.row, start_pt.column);
+                            let point_end = Point::new(end_pt.row, end_pt.column);
+                            let preview = if let Some(node) =
+                                snapshot.syntax_ancestor(point_start..point_end)
+                            {
+                                let full_range = node.byte_range().to_point(&snapshot);
+                                let span_lines =
+                                    full_range.end.row.saturating_sub(full_range.start.row);
+                                if (span_lines as usize) <= MAX_SCOPE_LINES {
+                                    let start_anchor = Point::new(full_range.start.row, 0);
+                                    let end_row = full_range.end.row;
+                                    let end_anchor =
+                                        Point::new(end_row, snapshot.line_len(end_row));
+                                    snapshot
+                                        .text_for_range(
+                                            snapshot.anchor_before(start_anchor)
+                                                ..snapshot.anchor_after(end_anchor),
+                                        )
+                                        .collect::<String>()
                                 } else {
-                                    // fallback to clamped lines around start_pt
+                                    // fallback to -10..+9 around start_pt
                                     let start_row = start_pt.row.saturating_sub(10);
                                     let end_row = (start_pt.row + 9).min(snapshot.max_point().row);
                                     let start_anchor = Point::new(start_row, 0);
@@ -445,34 +320,267 @@ impl AgentTool for GotoDefinitionByContextTool {
                                                 ..snapshot.anchor_after(end_anchor),
                                         )
                                         .collect::<String>()
-                                };
-                                let path = buffer.file().map(|f| f.full_path(cx));
-                                (start_pt.row + 1, end_pt.row + 1, preview, path)
-                            })?;
-                        let path_display = maybe_path
-                            .as_ref()
-                            .map(|p| p.display().to_string())
-                            .unwrap_or_else(|| "<buffer>".to_string());
-                        out.push_str(&format!(
-                            "{} [L{}-{}]\n\n",
-                            path_display, start_line, end_line
-                        ));
-                        out.push_str("```\n");
-                        out.push_str(&preview);
-                        out.push_str("\n```\n\n");
+                                }
+                            } else {
+                                // fallback to clamped lines around start_pt
+                                let start_row = start_pt.row.saturating_sub(10);
+                                let end_row = (start_pt.row + 9).min(snapshot.max_point().row);
+                                let start_anchor = Point::new(start_row, 0);
+                                let end_anchor = Point::new(end_row, snapshot.line_len(end_row));
+                                snapshot
+                                    .text_for_range(
+                                        snapshot.anchor_before(start_anchor)
+                                            ..snapshot.anchor_after(end_anchor),
+                                    )
+                                    .collect::<String>()
+                            };
+                            let path = buffer.file().and_then(|file| {
+                                display_project_path(
+                                    project.read(cx),
+                                    &ProjectPath::from_file(file.as_ref(), cx),
+                                    cx,
+                                )
+                            });
+                            let target_anchor = path.clone().and_then(|path| {
+                                ContextualAnchor::for_range(
+                                    path,
+                                    &snapshot,
+                                    link.target.range.to_offset(&snapshot),
+                                )
+                            });
+                            (
+                                start_pt.row + 1,
+                                end_pt.row + 1,
+                                preview,
+                                path,
+                                target_anchor,
+                            )
+                        })?;
+                    let path_display = maybe_path.unwrap_or_else(|| "<buffer>".to_string());
+                    out.push_str(&format!("{} [L{}-{}]", path_display, start_line, end_line));
+                    if show_language_servers {
+                        out.push_str(&format!(" ({})", language_servers.join(", ")));
                     }
-                    if total > page_limit {
-                        out.push_str(&format!(
-                            "Showing {} of {} definitions. Request additional pages to see more.\n",
-                            page_limit, total
-                        ));
+                    out.push_str("\n\n");
+                    out.push_str("```\n");
+                    out.push_str(&preview);
+                    out.push_str("\n```\n\n");
+                    // An anchor for the definition lets later tool calls continue from it
+                    // without copying context out of the preview.
+                    if let Some(target_anchor) = target_anchor
+                        && let Ok(target_anchor) = serde_json::to_string(&target_anchor)
+                    {
+                        out.push_str(&format!("Anchor: {target_anchor}\n\n"));
                     }
-                    out
                 }
-                _ => "No definitions found (or language server not capable)".to_string(),
+                if total > page_limit {
+                    out.push_str(&format!(
+                        "Showing {} of {} definitions. Request additional pages to see more.\n",
+                        page_limit, total
+                    ));
+                }
+                out
             };
 
+            let mut output = format!("{}\n\n{}", output.trim_end(), handles.describe());
+            if unsaved {
+                output.push_str(&unsaved_changes_note(&display_path));
+            }
+            if let Some(note) = language_server_retry_note(retries) {
+                output.push_str(&note);
+            }
+            render_span.record("output_len", output.len());
             Ok(LanguageModelToolResultContent::Text(Arc::from(output)))
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, assert_snapshot, init_test, run_tool};
+    use gpui::{AppContext as _, TestAppContext};
+    use serde_json::json;
+    use std::path::Path;
+    use util::path;
+
+    #[gpui::test]
+    async fn test_ambiguous_context_listing(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "fn a() {\n    let x = value;\n}\n\nfn b() {\n    let x = value;\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+
+        let tool = Arc::new(GotoDefinitionByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let (output, events) = run_tool(
+            tool,
+            ContextualAnchor {
+                path: "root/src/main.rs".into(),
+                context: "let x = value;".into(),
+                token: "value".into(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            cx,
+        )
+        .await;
+
+        assert!(events.is_empty(), "{events:?}");
+        let LanguageModelToolResultContent::Text(text) = output.expect("tool failed") else {
+            panic!("expected text output");
+        };
+        assert_snapshot("goto_definition_by_context_ambiguous", &text);
+    }
+
+    #[gpui::test]
+    async fn test_resolved_anchor_can_be_reused_by_handle(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "fn main() {\n    let total = compute(1, 2);\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let tool = Arc::new(GotoDefinitionByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+
+        let (output, _) = run_tool(
+            tool.clone(),
+            ContextualAnchor {
+                path: "root/src/main.rs".into(),
+                context: "let total = compute(1, 2);".into(),
+                token: "compute".into(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            cx,
+        )
+        .await;
+        let LanguageModelToolResultContent::Text(text) = output.expect("tool failed") else {
+            panic!("expected text output");
+        };
+        assert!(
+            text.ends_with("`\"handle\": \"ref#1\"` or `\"handle\": \"sym:compute\"`.\n"),
+            "{text}"
+        );
+
+        for handle in ["ref#1", "sym:compute"] {
+            let (output, _) = run_tool(tool.clone(), handle_only(handle), cx).await;
+            let LanguageModelToolResultContent::Text(reused) = output.expect("tool failed") else {
+                panic!("expected text output");
+            };
+            assert_eq!(reused, text, "{handle}");
+        }
+
+        let (output, _) = run_tool(tool, handle_only("ref#2"), cx).await;
+        assert!(output.is_err());
+    }
+
+    #[gpui::test]
+    async fn test_anchor_resolved_at_git_revision(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                ".git": {},
+                "src": {
+                    "main.rs": "fn main() {\n    let total = sum(1, 2);\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let oid = git::Oid::from_bytes(&[1; 20]).unwrap();
+        test_project
+            .fs
+            .with_git_state(Path::new(path!("/root/.git")), true, |state| {
+                state
+                    .refs
+                    .insert("HEAD~1:src/main.rs".into(), oid.to_string());
+                state.oids.insert(
+                    oid,
+                    "fn main() {\n    let total = compute(1, 2);\n}\n".into(),
+                );
+            })
+            .unwrap();
+        cx.run_until_parked();
+
+        let tool = Arc::new(GotoDefinitionByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let (output, events) = run_tool(
+            tool,
+            ContextualAnchor {
+                path: "root/src/main.rs".into(),
+                context: "let total = compute(1, 2);".into(),
+                token: "compute".into(),
+                index: None,
+                handle: None,
+                revision: Some("HEAD~1".into()),
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            cx,
+        )
+        .await;
+
+        assert!(events.is_empty(), "{events:?}");
+        let LanguageModelToolResultContent::Text(text) = output.expect("tool failed") else {
+            panic!("expected text output");
+        };
+        assert!(
+            text.starts_with("root/src/main.rs at HEAD~1 [L1-"),
+            "{text}"
+        );
+        assert!(text.contains("let total = compute(1, 2);"), "{text}");
+    }
+
+    fn handle_only(handle: &str) -> ContextualAnchor {
+        ContextualAnchor {
+            path: String::new(),
+            context: String::new(),
+            token: String::new(),
+            index: None,
+            handle: Some(handle.into()),
+            revision: None,
+            whole_word: false,
+            approximate_line: None,
+            case_insensitive: false,
+            unicode_normalize: false,
+            end_token: None,
+            end_index: None,
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use super::truncate_results;
 use crate::{AgentTool, ToolCallEventStream};
 use agent_client_protocol as acp;
 use anyhow::{Result, anyhow};
@@ -20,6 +21,8 @@ use util::paths::PathMatcher;
 ///
 /// - Prefer this tool to path search when searching for symbols in the project, because you won't need to guess what path it's in.
 /// - Supports full regex syntax (eg. "log.*Error", "function\\s+\\w+", etc.)
+/// - Set `literal` to search for the exact text instead of a regex, without escaping anything.
+/// - Each match is shown within its enclosing syntax node. Set `context_lines` to show exactly that many lines around each match instead.
 /// - Pass an `include_pattern` if you know how to narrow your search on the files system
 /// - Never use this tool to search for paths. Only search file contents with this tool.
 /// - Use this tool when you need to find files containing specific patterns
@@ -55,6 +58,12 @@ pub struct GrepToolInput {
     /// Whether the regex is case-sensitive. Defaults to false (case-insensitive).
     #[serde(default)]
     pub case_sensitive: bool,
+    /// Whether to search for `regex` as literal text rather than as a regex. Defaults to false.
+    #[serde(default)]
+    pub literal: bool,
+    /// Optional number of lines to show before and after each match, up to 10.
+    #[serde(default)]
+    pub context_lines: Option<u32>,
 }
 
 impl GrepToolInput {
@@ -65,6 +74,7 @@ impl GrepToolInput {
 }
 
 const RESULTS_PER_PAGE: u32 = 20;
+const MAX_CONTEXT_LINES: u32 = 10;
 
 pub struct GrepTool {
     project: Entity<Project>,
@@ -96,7 +106,8 @@ impl AgentTool for GrepTool {
         match input {
             Ok(input) => {
                 let page = input.page();
-                let regex_str = MarkdownInlineCode(&input.regex);
+                let query = MarkdownInlineCode(&input.regex);
+                let query_kind = if input.literal { "text" } else { "regex" };
                 let case_info = if input.case_sensitive {
                     " (case-sensitive)"
                 } else {
@@ -104,9 +115,9 @@ impl AgentTool for GrepTool {
                 };
 
                 if page > 1 {
-                    format!("Get page {page} of search results for regex {regex_str}{case_info}")
+                    format!("Get page {page} of search results for {query_kind} {query}{case_info}")
                 } else {
-                    format!("Search files for regex {regex_str}{case_info}")
+                    format!("Search files for {query_kind} {query}{case_info}")
                 }
             }
             Err(_) => "Search with regex".into(),
@@ -117,7 +128,7 @@ impl AgentTool for GrepTool {
     fn run(
         self: Arc<Self>,
         input: Self::Input,
-        _event_stream: ToolCallEventStream,
+        event_stream: ToolCallEventStream,
         cx: &mut App,
     ) -> Task<Result<Self::Output>> {
         const CONTEXT_LINES: u32 = 2;
@@ -155,17 +166,31 @@ impl AgentTool for GrepTool {
             }
         };
 
-        let query = match SearchQuery::regex(
-            &input.regex,
-            false,
-            input.case_sensitive,
-            false,
-            false,
-            include_matcher,
-            exclude_matcher,
-            true, // Always match file include pattern against *full project paths* that start with a project root.
-            None,
-        ) {
+        let query = if input.literal {
+            SearchQuery::text(
+                &input.regex,
+                false,
+                input.case_sensitive,
+                false,
+                include_matcher,
+                exclude_matcher,
+                true,
+                None,
+            )
+        } else {
+            SearchQuery::regex(
+                &input.regex,
+                false,
+                input.case_sensitive,
+                false,
+                false,
+                include_matcher,
+                exclude_matcher,
+                true, // Always match file include pattern against *full project paths* that start with a project root.
+                None,
+            )
+        };
+        let query = match query {
             Ok(query) => query,
             Err(error) => return Task::ready(Err(error)),
         };
@@ -174,11 +199,15 @@ impl AgentTool for GrepTool {
             .project
             .update(cx, |project, cx| project.search(query, cx));
 
+        let context_lines = input
+            .context_lines
+            .map(|lines| lines.min(MAX_CONTEXT_LINES));
         let project = self.project.downgrade();
         cx.spawn(async move |cx|  {
             futures::pin_mut!(results);
 
             let mut output = String::new();
+            let mut rendered_matches = Vec::new();
             let mut skips_remaining = input.offset;
             let mut matches_found = 0;
             let mut has_more_matches = false;
@@ -220,7 +249,8 @@ impl AgentTool for GrepTool {
                         let full_lines = Point::new(matched.start.row, 0)..Point::new(matched.end.row, matched_end_line_len);
                         let symbols = snapshot.symbols_containing(matched.start, None);
 
-                        if let Some(ancestor_node) = snapshot.syntax_ancestor(full_lines.clone()) {
+                        if context_lines.is_none()
+                            && let Some(ancestor_node) = snapshot.syntax_ancestor(full_lines.clone()) {
                             let full_ancestor_range = ancestor_node.byte_range().to_point(&snapshot);
                             let end_row = full_ancestor_range.end.row.min(full_ancestor_range.start.row + MAX_ANCESTOR_LINES);
                             let end_col = snapshot.line_len(end_row);
@@ -233,11 +263,12 @@ impl AgentTool for GrepTool {
 
                         let mut matched = matched;
                         matched.start.column = 0;
+                        let context_lines = context_lines.unwrap_or(CONTEXT_LINES);
                         matched.start.row =
-                            matched.start.row.saturating_sub(CONTEXT_LINES);
+                            matched.start.row.saturating_sub(context_lines);
                         matched.end.row = cmp::min(
                             snapshot.max_point().row,
-                            matched.end.row + CONTEXT_LINES,
+                            matched.end.row + context_lines,
                         );
                         matched.end.column = snapshot.line_len(matched.end.row);
 
@@ -268,40 +299,65 @@ impl AgentTool for GrepTool {
                         }
                     }
 
+                    // Each match carries its file's header when it's the file's first, so that
+                    // truncation never leaves a header without matches.
+                    let mut rendered_match = String::new();
                     if !file_header_written {
-                        writeln!(output, "\n## Matches in {}", path.display())?;
+                        writeln!(rendered_match, "\n## Matches in {}", path.display())?;
                         file_header_written = true;
                     }
 
                     let end_row = range.end.row;
-                    output.push_str("\n### ");
+                    rendered_match.push_str("\n### ");
 
                     for symbol in parent_symbols {
-                        write!(output, "{} › ", symbol.text)?;
+                        write!(rendered_match, "{} › ", symbol.text)?;
                     }
 
                     if range.start.row == end_row {
-                        writeln!(output, "L{}", range.start.row + 1)?;
+                        writeln!(rendered_match, "L{}", range.start.row + 1)?;
                     } else {
-                        writeln!(output, "L{}-{}", range.start.row + 1, end_row + 1)?;
+                        writeln!(rendered_match, "L{}-{}", range.start.row + 1, end_row + 1)?;
                     }
 
-                    output.push_str("```\n");
-                    output.extend(snapshot.text_for_range(range));
-                    output.push_str("\n```\n");
+                    rendered_match.push_str("```\n");
+                    rendered_match.extend(snapshot.text_for_range(range));
+                    rendered_match.push_str("\n```\n");
 
                     if let Some(ancestor_range) = ancestor_range
                         && end_row < ancestor_range.end.row {
                             let remaining_lines = ancestor_range.end.row - end_row;
-                            writeln!(output, "\n{} lines remaining in ancestor node. Read the file to see all.", remaining_lines)?;
+                            writeln!(rendered_match, "\n{} lines remaining in ancestor node. Read the file to see all.", remaining_lines)?;
                         }
 
+                    output.push_str(&rendered_match);
+                    rendered_matches.push(rendered_match);
                     matches_found += 1;
                 }
+
+                // Show each file's matches as soon as they're found, rather than after the whole
+                // project has been searched.
+                if file_header_written {
+                    event_stream.update_fields(acp::ToolCallUpdateFields::new().content(vec![
+                        acp::ToolCallContent::Content(acp::Content::new(output.clone())),
+                    ]));
+                }
             }
 
             if matches_found == 0 {
-                Ok("No matches found".into())
+                return Ok("No matches found".into());
+            }
+
+            let truncated =
+                truncate_results(rendered_matches, event_stream.token_budget(), cx).await;
+            let output = truncated.text;
+            if truncated.omitted > 0 {
+                Ok(format!(
+                    "Showing matches {}-{} (the rest didn't fit in the context window; use offset: {} to see them):\n{output}",
+                    input.offset + 1,
+                    input.offset + truncated.kept,
+                    input.offset + truncated.kept,
+                ))
             } else if has_more_matches {
                 Ok(format!(
                     "Showing matches {}-{} (there were more matches found; use offset: {} to see next page):\n{output}",
@@ -358,6 +414,8 @@ mod tests {
             include_pattern: Some("root/**/*.rs".to_string()),
             offset: 0,
             case_sensitive: false,
+            literal: false,
+            context_lines: None,
         };
 
         let result = run_grep_tool(input, project.clone(), cx).await;
@@ -377,6 +435,8 @@ mod tests {
             include_pattern: Some("root/**/src/**".to_string()),
             offset: 0,
             case_sensitive: false,
+            literal: false,
+            context_lines: None,
         };
 
         let result = run_grep_tool(input, project.clone(), cx).await;
@@ -399,6 +459,8 @@ mod tests {
             include_pattern: None,
             offset: 0,
             case_sensitive: false,
+            literal: false,
+            context_lines: None,
         };
 
         let result = run_grep_tool(input, project.clone(), cx).await;
@@ -435,6 +497,8 @@ mod tests {
             include_pattern: Some("**/*.txt".to_string()),
             offset: 0,
             case_sensitive: false,
+            literal: false,
+            context_lines: None,
         };
 
         let result = run_grep_tool(input, project.clone(), cx).await;
@@ -449,6 +513,8 @@ mod tests {
             include_pattern: Some("**/*.txt".to_string()),
             offset: 0,
             case_sensitive: true,
+            literal: false,
+            context_lines: None,
         };
 
         let result = run_grep_tool(input, project.clone(), cx).await;
@@ -463,6 +529,8 @@ mod tests {
             include_pattern: Some("**/*.txt".to_string()),
             offset: 0,
             case_sensitive: true,
+            literal: false,
+            context_lines: None,
         };
 
         let result = run_grep_tool(input, project.clone(), cx).await;
@@ -478,6 +546,8 @@ mod tests {
             include_pattern: Some("**/*.txt".to_string()),
             offset: 0,
             case_sensitive: true,
+            literal: false,
+            context_lines: None,
         };
 
         let result = run_grep_tool(input, project.clone(), cx).await;
@@ -579,6 +649,8 @@ mod tests {
             include_pattern: Some("**/*.rs".to_string()),
             offset: 0,
             case_sensitive: false,
+            literal: false,
+            context_lines: None,
         };
 
         let result = run_grep_tool(input, project.clone(), cx).await;
@@ -608,6 +680,8 @@ mod tests {
             include_pattern: Some("**/*.rs".to_string()),
             offset: 0,
             case_sensitive: false,
+            literal: false,
+            context_lines: None,
         };
 
         let result = run_grep_tool(input, project.clone(), cx).await;
@@ -639,6 +713,8 @@ mod tests {
             include_pattern: Some("**/*.rs".to_string()),
             offset: 0,
             case_sensitive: false,
+            literal: false,
+            context_lines: None,
         };
 
         let result = run_grep_tool(input, project.clone(), cx).await;
@@ -674,6 +750,8 @@ mod tests {
             include_pattern: Some("**/*.rs".to_string()),
             offset: 0,
             case_sensitive: false,
+            literal: false,
+            context_lines: None,
         };
 
         let result = run_grep_tool(input, project.clone(), cx).await;
@@ -704,6 +782,8 @@ mod tests {
             include_pattern: Some("**/*.rs".to_string()),
             offset: 0,
             case_sensitive: false,
+            literal: false,
+            context_lines: None,
         };
 
         let result = run_grep_tool(input, project.clone(), cx).await;
@@ -744,6 +824,8 @@ mod tests {
             include_pattern: Some("**/*.rs".to_string()),
             offset: 0,
             case_sensitive: false,
+            literal: false,
+            context_lines: None,
         };
 
         let result = run_grep_tool(input, project.clone(), cx).await;
@@ -765,6 +847,47 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[gpui::test]
+    async fn test_grep_literal_with_context_lines(cx: &mut TestAppContext) {
+        use crate::test_support::{RecordedToolEvent, run_tool};
+        use unindent::Unindent;
+        let project = setup_syntax_test(cx).await;
+
+        // The query is full of regex syntax, which a literal search matches as plain text.
+        let input = GrepToolInput {
+            regex: "format!(\"Processed: {}\"".to_string(),
+            include_pattern: Some("**/*.rs".to_string()),
+            offset: 0,
+            case_sensitive: true,
+            literal: true,
+            context_lines: Some(1),
+        };
+
+        let (result, events) = run_tool(Arc::new(GrepTool { project }), input, cx).await;
+        let result = result.expect("grep failed").replace("root\\", "root/");
+        let expected = r#"
+            Found 1 matches:
+
+            ## Matches in root/test_syntax.rs
+
+            ### impl Processor for MyStruct › fn process › L52-54
+            ```
+                fn process(&self, input: &str) -> String {
+                    format!("Processed: {}", input)
+                }
+            ```
+            "#
+        .unindent();
+        assert_eq!(result, expected);
+        assert!(
+            events.iter().any(|event| matches!(
+                event,
+                RecordedToolEvent::Fields(fields) if fields.content.is_some()
+            )),
+            "matches should be streamed as they're found"
+        );
+    }
+
     async fn run_grep_tool(
         input: GrepToolInput,
         project: Entity<Project>,
@@ -851,6 +974,8 @@ mod tests {
                 include_pattern: None,
                 offset: 0,
                 case_sensitive: false,
+                literal: false,
+                context_lines: None,
             },
             project.clone(),
             cx,
@@ -869,6 +994,8 @@ mod tests {
                 include_pattern: None,
                 offset: 0,
                 case_sensitive: false,
+                literal: false,
+                context_lines: None,
             },
             project.clone(),
             cx,
@@ -887,6 +1014,8 @@ mod tests {
                 include_pattern: None,
                 offset: 0,
                 case_sensitive: false,
+                literal: false,
+                context_lines: None,
             },
             project.clone(),
             cx,
@@ -904,6 +1033,8 @@ mod tests {
                 include_pattern: None,
                 offset: 0,
                 case_sensitive: false,
+                literal: false,
+                context_lines: None,
             },
             project.clone(),
             cx,
@@ -922,6 +1053,8 @@ mod tests {
                 include_pattern: None,
                 offset: 0,
                 case_sensitive: false,
+                literal: false,
+                context_lines: None,
             },
             project.clone(),
             cx,
@@ -939,6 +1072,8 @@ mod tests {
                 include_pattern: None,
                 offset: 0,
                 case_sensitive: false,
+                literal: false,
+                context_lines: None,
             },
             project.clone(),
             cx,
@@ -957,6 +1092,8 @@ mod tests {
                 include_pattern: None,
                 offset: 0,
                 case_sensitive: false,
+                literal: false,
+                context_lines: None,
             },
             project.clone(),
             cx,
@@ -975,6 +1112,8 @@ mod tests {
                 include_pattern: None,
                 offset: 0,
                 case_sensitive: false,
+                literal: false,
+                context_lines: None,
             },
             project.clone(),
             cx,
@@ -993,6 +1132,8 @@ mod tests {
                 include_pattern: Some("../outside_project/**/*.rs".to_string()),
                 offset: 0,
                 case_sensitive: false,
+                literal: false,
+                context_lines: None,
             },
             project.clone(),
             cx,
@@ -1086,6 +1227,8 @@ mod tests {
                 include_pattern: None,
                 offset: 0,
                 case_sensitive: false,
+                literal: false,
+                context_lines: None,
             },
             project.clone(),
             cx,
@@ -1140,6 +1283,8 @@ mod tests {
                 include_pattern: Some("worktree1/**/*.rs".to_string()),
                 offset: 0,
                 case_sensitive: false,
+                literal: false,
+                context_lines: None,
             },
             project.clone(),
             cx,
@@ -0,0 +1,434 @@
+use super::{
+    AnchorHandles, AnchorMemory, ContextualAnchor, display_project_path, resolve_project_path,
+    wait_for_language_server, warm_up_language_servers,
+};
+use crate::{AgentTool, ToolCallEventStream, ToolPreparation};
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use gpui::{App, Entity, SharedString, Task};
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::{HoverBlockKind, Project, WorktreeSettings};
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::sync::Arc;
+
+/// Tool: hover_info_by_context
+///
+/// Returns what the language server shows when hovering a symbol: usually its type or signature
+/// and its documentation. This is the cheapest way to learn a symbol's type.
+pub struct HoverInfoByContextTool {
+    project: Entity<Project>,
+    anchor_memory: Entity<AnchorMemory>,
+}
+
+impl HoverInfoByContextTool {
+    pub fn new(project: Entity<Project>, anchor_memory: Entity<AnchorMemory>) -> Self {
+        Self {
+            project,
+            anchor_memory,
+        }
+    }
+}
+
+/// The language server's hover for the symbol an anchor refers to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct HoverInfoByContextToolOutput {
+    /// The path of the file containing the anchor.
+    pub path: String,
+    /// The 1-based line of the anchor.
+    pub line: u32,
+    pub token: String,
+    /// The code blocks the hover starts with, which language servers use for the type or
+    /// signature.
+    pub signature: Option<String>,
+    /// The rest of the hover, usually the symbol's documentation.
+    pub documentation: Option<String>,
+    /// The whole hover as markdown.
+    pub markdown: String,
+    /// The handle under which the input anchor was remembered.
+    pub handle: String,
+    /// The symbol handle under which the input anchor was remembered, if it is unambiguous.
+    pub symbol_handle: Option<String>,
+    /// Whether the language server didn't answer in time, in which case there is no hover.
+    pub timed_out: bool,
+}
+
+impl From<HoverInfoByContextToolOutput> for LanguageModelToolResultContent {
+    fn from(output: HoverInfoByContextToolOutput) -> Self {
+        let handles = AnchorHandles {
+            reference: output.handle,
+            symbol: output.symbol_handle,
+        };
+        if output.timed_out {
+            return format!(
+                "The language server didn't answer in time (it may still be indexing), so there is no hover for `{}` in {} [L{}]. Try again shortly.\n\n{}",
+                output.token,
+                output.path,
+                output.line,
+                handles.describe()
+            )
+            .into();
+        }
+        format!(
+            "Hover for `{}` in {} [L{}]:\n\n{}\n\n{}",
+            output.token,
+            output.path,
+            output.line,
+            output.markdown,
+            handles.describe()
+        )
+        .into()
+    }
+}
+
+impl AgentTool for HoverInfoByContextTool {
+    type Input = ContextualAnchor;
+    type Output = HoverInfoByContextToolOutput;
+
+    fn name() -> &'static str {
+        "hover_info_by_context"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Read
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn input_examples() -> Vec<Self::Input> {
+        vec![ContextualAnchor {
+            path: "project/src/main.rs".into(),
+            context: "let config = Config::load(&path)?;".into(),
+            token: "config".into(),
+            index: None,
+            handle: None,
+            revision: None,
+            whole_word: false,
+            approximate_line: None,
+            case_insensitive: false,
+            unicode_normalize: false,
+            end_token: None,
+            end_index: None,
+        }]
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => match input.handle {
+                Some(handle) => format!("Hover `{handle}`").into(),
+                None => format!("Hover `{}`", input.token).into(),
+            },
+            Err(_) => "Hover symbol".into(),
+        }
+    }
+
+    fn prepare(
+        self: Arc<Self>,
+        input: Self::Input,
+        cx: &mut App,
+    ) -> Option<Task<Result<ToolPreparation>>> {
+        let input = self.anchor_memory.read(cx).expand(input).ok()?;
+        if input.revision.is_some() {
+            return None;
+        }
+        warm_up_language_servers(&self.project, &input.path, cx)
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let anchor_memory = self.anchor_memory.clone();
+
+        let input = match anchor_memory.read(cx).expand(input) {
+            Ok(input) => input,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        if let Err(error) = input.validate_basic() {
+            return Task::ready(Err(error));
+        }
+        if let Some(revision) = &input.revision {
+            return Task::ready(Err(anyhow!(
+                "Language servers only know the project's current files, so they can't hover at {revision}."
+            )));
+        }
+        let project_path = match resolve_project_path(project.read(cx), &input.path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Task::ready(Err(anyhow!(
+                "Cannot read file because its path matches the file_scan_exclusions or private_files setting: {}",
+                &input.path
+            )));
+        }
+        let display_path = display_project_path(project.read(cx), &project_path, cx)
+            .unwrap_or_else(|| input.path.clone());
+
+        cx.spawn(async move |cx| {
+            let buffer = project
+                .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+                .await?;
+            buffer
+                .read_with(cx, |buffer, _| buffer.parsing_idle())?
+                .await;
+            let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot())?;
+            let token_offset = input.resolve_unique_token_offset(&snapshot, &display_path)?;
+            let handles =
+                anchor_memory.update(cx, |anchor_memory, _| anchor_memory.remember(&input))?;
+
+            let hovers =
+                project.update(cx, |project, cx| project.hover(&buffer, token_offset, cx))?;
+            let hovers = wait_for_language_server(hovers, &event_stream, cx).await?;
+            let timed_out = hovers.is_none();
+            let contents = HoverContents::from_hovers(&hovers.flatten().unwrap_or_default());
+            if contents.markdown.is_empty() && !timed_out {
+                anyhow::bail!(
+                    "No hover information for `{}` (or language server not capable)",
+                    input.token
+                );
+            }
+
+            Ok(HoverInfoByContextToolOutput {
+                path: display_path,
+                line: snapshot.offset_to_point(token_offset).row + 1,
+                token: input.token,
+                signature: contents.signature,
+                documentation: contents.documentation,
+                markdown: contents.markdown,
+                handle: handles.reference,
+                symbol_handle: handles.symbol,
+                timed_out,
+            })
+        })
+    }
+}
+
+/// Hover blocks split into the leading code that describes the symbol and the prose after it.
+#[derive(Debug, PartialEq)]
+struct HoverContents {
+    signature: Option<String>,
+    documentation: Option<String>,
+    markdown: String,
+}
+
+impl HoverContents {
+    fn from_hovers(hovers: &[project::Hover]) -> Self {
+        let mut signature = Vec::new();
+        let mut documentation = Vec::new();
+        let mut markdown = Vec::new();
+        for block in hovers.iter().flat_map(|hover| &hover.contents) {
+            let text = block.text.trim();
+            if text.is_empty() {
+                continue;
+            }
+            match &block.kind {
+                HoverBlockKind::Code { language } => {
+                    markdown.push(format!("```{language}\n{text}\n```"));
+                    if documentation.is_empty() {
+                        signature.push(text.to_string());
+                    }
+                }
+                HoverBlockKind::Markdown => {
+                    markdown.push(text.to_string());
+                    let mut rest = text;
+                    if documentation.is_empty() {
+                        while let Some((code, after)) = split_leading_code_block(rest) {
+                            signature.push(code.to_string());
+                            rest = after;
+                        }
+                    }
+                    if !rest.is_empty() {
+                        documentation.push(rest.to_string());
+                    }
+                }
+                HoverBlockKind::PlainText => {
+                    markdown.push(text.to_string());
+                    documentation.push(text.to_string());
+                }
+            }
+        }
+        Self {
+            signature: (!signature.is_empty()).then(|| signature.join("\n\n")),
+            documentation: (!documentation.is_empty()).then(|| documentation.join("\n\n")),
+            markdown: markdown.join("\n\n"),
+        }
+    }
+}
+
+/// Splits a fenced code block off the start of `markdown`, skipping the horizontal rules that
+/// language servers put between a hover's code and its prose.
+fn split_leading_code_block(markdown: &str) -> Option<(&str, &str)> {
+    let fenced = markdown.strip_prefix("```")?;
+    let (_, code) = fenced.split_once('\n')?;
+    let (code, rest) = code.split_once("```")?;
+    let mut rest = rest.trim_start();
+    while let Some(after_rule) = rest.strip_prefix("---") {
+        rest = after_rule.trim_start();
+    }
+    Some((code.trim(), rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, run_tool};
+    use futures::StreamExt as _;
+    use gpui::{AppContext as _, TestAppContext};
+    use serde_json::json;
+
+    #[gpui::test]
+    async fn test_hover_info_splits_signature_and_documentation(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "fn helper() -> u32 {\n    1\n}\n\nfn main() {\n    let first = helper();\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let mut fake_servers = test_project.register_fake_rust_server(
+            lsp::ServerCapabilities {
+                hover_provider: Some(lsp::HoverProviderCapability::Simple(true)),
+                ..Default::default()
+            },
+            cx,
+        );
+        let (_buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/main.rs", cx).await;
+        let fake_server = fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+        fake_server.set_request_handler::<lsp::request::HoverRequest, _, _>(|_, _| async move {
+            Ok(Some(lsp::Hover {
+                contents: lsp::HoverContents::Markup(lsp::MarkupContent {
+                    kind: lsp::MarkupKind::Markdown,
+                    value: "```rust\nmain\n```\n\n```rust\nfn helper() -> u32\n```\n\n---\n\nReturns one."
+                        .into(),
+                }),
+                range: None,
+            }))
+        });
+
+        let tool = Arc::new(HoverInfoByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let (output, _) = run_tool(
+            tool,
+            ContextualAnchor {
+                path: "root/src/main.rs".into(),
+                context: "let first = helper();".into(),
+                token: "helper".into(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+
+        assert_eq!(output.path, "root/src/main.rs");
+        assert_eq!(output.line, 6);
+        assert_eq!(
+            output.signature.as_deref(),
+            Some("main\n\nfn helper() -> u32")
+        );
+        assert_eq!(output.documentation.as_deref(), Some("Returns one."));
+        assert!(
+            output.markdown.contains("```rust\nfn helper() -> u32\n```"),
+            "{}",
+            output.markdown
+        );
+    }
+
+    #[gpui::test]
+    async fn test_hover_info_gives_up_on_busy_language_server(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "fn helper() -> u32 {\n    1\n}\n\nfn main() {\n    let first = helper();\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let mut fake_servers = test_project.register_fake_rust_server(
+            lsp::ServerCapabilities {
+                hover_provider: Some(lsp::HoverProviderCapability::Simple(true)),
+                ..Default::default()
+            },
+            cx,
+        );
+        let (_buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/main.rs", cx).await;
+        let fake_server = fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+        fake_server.set_request_handler::<lsp::request::HoverRequest, _, _>(|_, _| {
+            futures::future::pending()
+        });
+
+        let tool = Arc::new(HoverInfoByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let (output, _) = run_tool(
+            tool,
+            ContextualAnchor {
+                path: "root/src/main.rs".into(),
+                context: "let first = helper();".into(),
+                token: "helper".into(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+
+        assert!(output.timed_out);
+        assert_eq!(output.markdown, "");
+        let LanguageModelToolResultContent::Text(text) = output.into() else {
+            panic!("expected text output");
+        };
+        assert!(
+            text.starts_with("The language server didn't answer in time"),
+            "{text}"
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use super::{display_project_path, resolve_project_path, warm_up_language_servers};
+use crate::{AgentTool, ToolCallEventStream, ToolPreparation};
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use futures::future::join_all;
+use gpui::{App, Entity, SharedString, Task};
+use language::{Point, ToOffset as _, language_settings::InlayHintKind};
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::{InvalidationStrategy, Project, WorktreeSettings};
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::sync::Arc;
+use util::markdown::MarkdownInlineCode;
+
+const MAX_LINES: u32 = 200;
+
+/// Returns a range of a file's lines with the language server's inlay hints, such as inferred
+/// types and parameter names, merged in as `⟨hint⟩`.
+///
+/// The hints are not part of the file, so leave them out of anchors and edits.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct InlayHintsToolInput {
+    /// The relative path of the file.
+    ///
+    /// This path should never be absolute, and the first component of the path should always be a root directory in a project.
+    pub path: String,
+    /// Optional line number to start on (1-based index)
+    #[serde(default)]
+    pub start_line: Option<u32>,
+    /// Optional line number to end on (1-based index, inclusive). At most 200 lines are returned.
+    #[serde(default)]
+    pub end_line: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct InlayHintsToolOutput {
+    pub path: String,
+    /// The 1-based first line of the excerpt.
+    pub start_line: u32,
+    /// The 1-based last line of the excerpt, inclusive.
+    pub end_line: u32,
+    /// The lines with each hint inserted where the editor shows it, as `⟨hint⟩`.
+    pub excerpt: String,
+    pub hints: Vec<InlayHintSummary>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct InlayHintSummary {
+    /// The 1-based line the hint is shown on.
+    pub line: u32,
+    pub label: String,
+    /// `type` or `parameter`, if the server reported a kind.
+    pub kind: Option<String>,
+}
+
+impl From<InlayHintsToolOutput> for LanguageModelToolResultContent {
+    fn from(output: InlayHintsToolOutput) -> Self {
+        let header = if output.hints.is_empty() {
+            format!(
+                "No inlay hints in {} [L{}-{}] (or language server not capable).",
+                output.path, output.start_line, output.end_line
+            )
+        } else {
+            format!(
+                "{} [L{}-{}] with {} inlay hint(s) shown as `⟨hint⟩`:",
+                output.path,
+                output.start_line,
+                output.end_line,
+                output.hints.len()
+            )
+        };
+        format!("{header}\n\n```\n{}\n```", output.excerpt).into()
+    }
+}
+
+pub struct InlayHintsTool {
+    project: Entity<Project>,
+}
+
+impl InlayHintsTool {
+    pub fn new(project: Entity<Project>) -> Self {
+        Self { project }
+    }
+}
+
+impl AgentTool for InlayHintsTool {
+    type Input = InlayHintsToolInput;
+    type Output = InlayHintsToolOutput;
+
+    fn name() -> &'static str {
+        "inlay_hints"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Read
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => format!("Inlay hints in {}", MarkdownInlineCode(&input.path)).into(),
+            Err(_) => "Inlay hints".into(),
+        }
+    }
+
+    fn prepare(
+        self: Arc<Self>,
+        input: Self::Input,
+        cx: &mut App,
+    ) -> Option<Task<Result<ToolPreparation>>> {
+        warm_up_language_servers(&self.project, &input.path, cx)
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        _event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let project_path = match resolve_project_path(project.read(cx), &input.path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Task::ready(Err(anyhow!(
+                "Cannot read file because its path matches the file_scan_exclusions or private_files setting: {}",
+                &input.path
+            )));
+        }
+        let display_path = display_project_path(project.read(cx), &project_path, cx)
+            .unwrap_or_else(|| input.path.clone());
+
+        cx.spawn(async move |cx| {
+            let buffer = project
+                .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+                .await?;
+            // Keep the buffer registered until the server has answered.
+            let _lsp_handle = project.update(cx, |project, cx| {
+                project.register_buffer_with_language_servers(&buffer, cx)
+            })?;
+            let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot())?;
+
+            let last_row = snapshot.max_point().row;
+            let start_row = input.start_line.unwrap_or(1).max(1) - 1;
+            if start_row > last_row {
+                anyhow::bail!(
+                    "`start_line` {} is past the end of {display_path}, which has {} line(s).",
+                    start_row + 1,
+                    last_row + 1
+                );
+            }
+            let end_row = input
+                .end_line
+                .map_or(last_row, |end_line| end_line.saturating_sub(1))
+                .clamp(start_row, last_row)
+                .min(start_row + MAX_LINES - 1);
+            let range = Point::new(start_row, 0)..Point::new(end_row, snapshot.line_len(end_row));
+
+            let hint_tasks = project.update(cx, |project, cx| {
+                project.lsp_store().update(cx, |lsp_store, cx| {
+                    lsp_store.inlay_hints(
+                        InvalidationStrategy::None,
+                        buffer.clone(),
+                        vec![snapshot.anchor_before(range.start)..snapshot.anchor_after(range.end)],
+                        None,
+                        cx,
+                    )
+                })
+            })?;
+            let mut hints = Vec::new();
+            for hints_by_server in join_all(hint_tasks.into_values()).await {
+                for (_, hint) in hints_by_server?.into_values().flatten() {
+                    let offset = hint.position.to_offset(&snapshot);
+                    if (range.start.to_offset(&snapshot)..=range.end.to_offset(&snapshot))
+                        .contains(&offset)
+                    {
+                        hints.push((offset, hint));
+                    }
+                }
+            }
+            hints.sort_by_key(|(offset, _)| *offset);
+
+            let mut excerpt = String::new();
+            let mut summaries = Vec::new();
+            let mut cursor = range.start.to_offset(&snapshot);
+            for (offset, hint) in &hints {
+                excerpt.extend(snapshot.text_for_range(cursor..*offset));
+                cursor = *offset;
+                let label = hint.text().to_string();
+                excerpt.push('⟨');
+                if hint.padding_left {
+                    excerpt.push(' ');
+                }
+                excerpt.push_str(&label);
+                if hint.padding_right {
+                    excerpt.push(' ');
+                }
+                excerpt.push('⟩');
+                summaries.push(InlayHintSummary {
+                    line: snapshot.offset_to_point(*offset).row + 1,
+                    label,
+                    kind: hint.kind.map(|kind| match kind {
+                        InlayHintKind::Type => "type".to_string(),
+                        InlayHintKind::Parameter => "parameter".to_string(),
+                    }),
+                });
+            }
+            excerpt.extend(snapshot.text_for_range(cursor..range.end.to_offset(&snapshot)));
+
+            Ok(InlayHintsToolOutput {
+                path: display_path,
+                start_line: start_row + 1,
+                end_line: end_row + 1,
+                excerpt,
+                hints: summaries,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, run_tool};
+    use futures::StreamExt as _;
+    use gpui::TestAppContext;
+    use serde_json::json;
+
+    #[gpui::test]
+    async fn test_inlay_hints_are_merged_into_excerpt(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "fn main() {\n    let total = add(1, 2);\n    println!(\"{total}\");\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let mut fake_servers = test_project.register_fake_rust_server(
+            lsp::ServerCapabilities {
+                inlay_hint_provider: Some(lsp::OneOf::Left(true)),
+                ..Default::default()
+            },
+            cx,
+        );
+        let (_buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/main.rs", cx).await;
+        let fake_server = fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+        fake_server.set_request_handler::<lsp::request::InlayHintRequest, _, _>(
+            |_, _| async move {
+                let hint = |line, character, label: &str, kind, padding_left, padding_right| {
+                    lsp::InlayHint {
+                        position: lsp::Position::new(line, character),
+                        label: lsp::InlayHintLabel::String(label.into()),
+                        kind: Some(kind),
+                        text_edits: None,
+                        tooltip: None,
+                        padding_left: Some(padding_left),
+                        padding_right: Some(padding_right),
+                        data: None,
+                    }
+                };
+                Ok(Some(vec![
+                    hint(1, 13, ": i32", lsp::InlayHintKind::TYPE, false, false),
+                    hint(1, 20, "left:", lsp::InlayHintKind::PARAMETER, false, true),
+                    hint(1, 23, "right:", lsp::InlayHintKind::PARAMETER, false, true),
+                ]))
+            },
+        );
+
+        let tool = Arc::new(InlayHintsTool::new(test_project.project.clone()));
+        let (output, _) = run_tool(
+            tool,
+            InlayHintsToolInput {
+                path: "root/src/main.rs".into(),
+                start_line: Some(2),
+                end_line: Some(2),
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+
+        assert_eq!(output.start_line, 2);
+        assert_eq!(output.end_line, 2);
+        assert_eq!(
+            output.excerpt,
+            "    let total⟨: i32⟩ = add(⟨left: ⟩1, ⟨right: ⟩2);"
+        );
+        assert_eq!(
+            output.hints,
+            vec![
+                InlayHintSummary {
+                    line: 2,
+                    label: ": i32".into(),
+                    kind: Some("type".into()),
+                },
+                InlayHintSummary {
+                    line: 2,
+                    label: "left:".into(),
+                    kind: Some("parameter".into()),
+                },
+                InlayHintSummary {
+                    line: 2,
+                    label: "right:".into(),
+                    kind: Some("parameter".into()),
+                },
+            ]
+        );
+    }
+}
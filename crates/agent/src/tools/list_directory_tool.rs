@@ -9,8 +9,11 @@ use settings::Settings;
 use std::fmt::Write;
 use std::sync::Arc;
 use util::markdown::MarkdownInlineCode;
+use util::size::format_file_size;
 
 /// Lists files and directories in a given path. Prefer the `grep` or `find_path` tools when searching the codebase.
+///
+/// Files ignored by git are left out unless `include_ignored` is set.
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ListDirectoryToolInput {
     /// The fully-qualified path of the directory to list in the project.
@@ -35,8 +38,21 @@ pub struct ListDirectoryToolInput {
     /// If you wanna list contents in the directory `foo/baz`, you should use the path `foo/baz`.
     /// </example>
     pub path: String,
+    /// How many levels of subdirectories to list, from 1 (only the directory's own entries) to 5.
+    /// Defaults to 1.
+    #[serde(default)]
+    pub depth: Option<u32>,
+    /// Whether to include entries ignored by git, such as build output. Defaults to false.
+    #[serde(default)]
+    pub include_ignored: bool,
+    /// Whether to show each file's size, and mark symlinks. Defaults to false.
+    #[serde(default)]
+    pub details: bool,
 }
 
+const MAX_DEPTH: u32 = 5;
+const MAX_ENTRIES: usize = 1000;
+
 pub struct ListDirectoryTool {
     project: Entity<Project>,
 }
@@ -153,35 +169,60 @@ impl AgentTool for ListDirectoryTool {
             return Task::ready(Err(anyhow!("{} is not a directory.", input.path)));
         }
         let worktree_snapshot = worktree.read(cx).snapshot();
+        let depth = input.depth.unwrap_or(1).clamp(1, MAX_DEPTH);
 
         let mut folders = Vec::new();
         let mut files = Vec::new();
+        let mut truncated = false;
+        let mut directories = vec![(project_path.path.clone(), 1)];
+
+        'outer: while let Some((directory, level)) = directories.pop() {
+            for entry in worktree_snapshot.child_entries(&directory) {
+                // Skip private and excluded files and directories
+                if global_settings.is_path_private(&entry.path)
+                    || global_settings.is_path_excluded(&entry.path)
+                {
+                    continue;
+                }
 
-        for entry in worktree_snapshot.child_entries(&project_path.path) {
-            // Skip private and excluded files and directories
-            if global_settings.is_path_private(&entry.path)
-                || global_settings.is_path_excluded(&entry.path)
-            {
-                continue;
-            }
+                let project_path: ProjectPath = (worktree_snapshot.id(), entry.path.clone()).into();
+                if worktree_settings.is_path_excluded(&project_path.path)
+                    || worktree_settings.is_path_private(&project_path.path)
+                {
+                    continue;
+                }
 
-            let project_path: ProjectPath = (worktree_snapshot.id(), entry.path.clone()).into();
-            if worktree_settings.is_path_excluded(&project_path.path)
-                || worktree_settings.is_path_private(&project_path.path)
-            {
-                continue;
-            }
+                if entry.is_ignored && !input.include_ignored {
+                    continue;
+                }
+
+                if folders.len() + files.len() >= MAX_ENTRIES {
+                    truncated = true;
+                    break 'outer;
+                }
 
-            let full_path = worktree_root_name
-                .join(&entry.path)
-                .display(worktree_snapshot.path_style())
-                .into_owned();
-            if entry.is_dir() {
-                folders.push(full_path);
-            } else {
-                files.push(full_path);
+                let mut full_path = worktree_root_name
+                    .join(&entry.path)
+                    .display(worktree_snapshot.path_style())
+                    .into_owned();
+                if input.details && entry.canonical_path.is_some() {
+                    full_path.push_str(" (symlink)");
+                }
+                if entry.is_dir() {
+                    folders.push(full_path);
+                    if level < depth {
+                        directories.push((entry.path.clone(), level + 1));
+                    }
+                } else {
+                    if input.details {
+                        write!(full_path, " ({})", format_file_size(entry.size, false)).ok();
+                    }
+                    files.push(full_path);
+                }
             }
         }
+        folders.sort();
+        files.sort();
 
         let mut output = String::new();
 
@@ -197,6 +238,14 @@ impl AgentTool for ListDirectoryTool {
             writeln!(output, "{} is empty.", input.path).unwrap();
         }
 
+        if truncated {
+            writeln!(
+                output,
+                "\nOnly the first {MAX_ENTRIES} entries are shown. List a subdirectory or use a smaller depth to see the rest."
+            )
+            .ok();
+        }
+
         Task::ready(Ok(output))
     }
 }
@@ -260,6 +309,9 @@ mod tests {
         // Test listing root directory
         let input = ListDirectoryToolInput {
             path: "project".into(),
+            depth: None,
+            include_ignored: false,
+            details: false,
         };
         let output = cx
             .update(|cx| tool.clone().run(input, ToolCallEventStream::test().0, cx))
@@ -281,6 +333,9 @@ mod tests {
         // Test listing src directory
         let input = ListDirectoryToolInput {
             path: "project/src".into(),
+            depth: None,
+            include_ignored: false,
+            details: false,
         };
         let output = cx
             .update(|cx| tool.clone().run(input, ToolCallEventStream::test().0, cx))
@@ -302,6 +357,9 @@ mod tests {
         // Test listing directory with only files
         let input = ListDirectoryToolInput {
             path: "project/tests".into(),
+            depth: None,
+            include_ignored: false,
+            details: false,
         };
         let output = cx
             .update(|cx| tool.clone().run(input, ToolCallEventStream::test().0, cx))
@@ -330,6 +388,9 @@ mod tests {
 
         let input = ListDirectoryToolInput {
             path: "project/empty_dir".into(),
+            depth: None,
+            include_ignored: false,
+            details: false,
         };
         let output = cx
             .update(|cx| tool.clone().run(input, ToolCallEventStream::test().0, cx))
@@ -338,6 +399,69 @@ mod tests {
         assert_eq!(output, "project/empty_dir is empty.\n");
     }
 
+    #[gpui::test]
+    async fn test_list_directory_depth_and_ignored_entries(cx: &mut TestAppContext) {
+        init_test(cx);
+
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(
+            path!("/project"),
+            json!({
+                ".git": {},
+                ".gitignore": "target\n",
+                "src": {
+                    "main.rs": "fn main() {}",
+                    "nested": {
+                        "deep": {
+                            "leaf.rs": "fn leaf() {}"
+                        }
+                    }
+                },
+                "target": {
+                    "app": "binary"
+                }
+            }),
+        )
+        .await;
+
+        let project = Project::test(fs.clone(), [path!("/project").as_ref()], cx).await;
+        let tool = Arc::new(ListDirectoryTool::new(project));
+
+        let input = ListDirectoryToolInput {
+            path: "project".into(),
+            depth: Some(2),
+            include_ignored: false,
+            details: true,
+        };
+        let output = cx
+            .update(|cx| tool.clone().run(input, ToolCallEventStream::test().0, cx))
+            .await
+            .unwrap();
+        assert!(output.contains(&platform_paths("project/src/nested\n")));
+        assert!(output.contains(&platform_paths("project/src/main.rs (12B)")));
+        assert!(
+            !output.contains("deep"),
+            "entries deeper than the depth limit should be left out"
+        );
+        assert!(
+            !output.contains("target"),
+            "entries ignored by git should be left out"
+        );
+
+        let input = ListDirectoryToolInput {
+            path: "project".into(),
+            depth: None,
+            include_ignored: true,
+            details: false,
+        };
+        let output = cx
+            .update(|cx| tool.run(input, ToolCallEventStream::test().0, cx))
+            .await
+            .unwrap();
+        assert!(output.contains(&platform_paths("project/target")));
+        assert!(!output.contains(&platform_paths("project/src/main.rs")));
+    }
+
     #[gpui::test]
     async fn test_list_directory_error_cases(cx: &mut TestAppContext) {
         init_test(cx);
@@ -357,6 +481,9 @@ mod tests {
         // Test non-existent path
         let input = ListDirectoryToolInput {
             path: "project/nonexistent".into(),
+            depth: None,
+            include_ignored: false,
+            details: false,
         };
         let output = cx
             .update(|cx| tool.clone().run(input, ToolCallEventStream::test().0, cx))
@@ -366,6 +493,9 @@ mod tests {
         // Test trying to list a file instead of directory
         let input = ListDirectoryToolInput {
             path: "project/file.txt".into(),
+            depth: None,
+            include_ignored: false,
+            details: false,
         };
         let output = cx
             .update(|cx| tool.run(input, ToolCallEventStream::test().0, cx))
@@ -435,6 +565,9 @@ mod tests {
         // Listing root directory should exclude private and excluded files
         let input = ListDirectoryToolInput {
             path: "project".into(),
+            depth: None,
+            include_ignored: false,
+            details: false,
         };
         let output = cx
             .update(|cx| tool.clone().run(input, ToolCallEventStream::test().0, cx))
@@ -462,6 +595,9 @@ mod tests {
         // Trying to list an excluded directory should fail
         let input = ListDirectoryToolInput {
             path: "project/.secretdir".into(),
+            depth: None,
+            include_ignored: false,
+            details: false,
         };
         let output = cx
             .update(|cx| tool.clone().run(input, ToolCallEventStream::test().0, cx))
@@ -477,6 +613,9 @@ mod tests {
         // Listing a directory should exclude private files within it
         let input = ListDirectoryToolInput {
             path: "project/visible_dir".into(),
+            depth: None,
+            include_ignored: false,
+            details: false,
         };
         let output = cx
             .update(|cx| tool.clone().run(input, ToolCallEventStream::test().0, cx))
@@ -582,6 +721,9 @@ mod tests {
         // Test listing worktree1/src - should exclude secret.rs and config.toml based on local settings
         let input = ListDirectoryToolInput {
             path: "worktree1/src".into(),
+            depth: None,
+            include_ignored: false,
+            details: false,
         };
         let output = cx
             .update(|cx| tool.clone().run(input, ToolCallEventStream::test().0, cx))
@@ -600,6 +742,9 @@ mod tests {
         // Test listing worktree1/tests - should exclude fixture.sql based on local settings
         let input = ListDirectoryToolInput {
             path: "worktree1/tests".into(),
+            depth: None,
+            include_ignored: false,
+            details: false,
         };
         let output = cx
             .update(|cx| tool.clone().run(input, ToolCallEventStream::test().0, cx))
@@ -614,6 +759,9 @@ mod tests {
         // Test listing worktree2/lib - should exclude private.js and data.json based on local settings
         let input = ListDirectoryToolInput {
             path: "worktree2/lib".into(),
+            depth: None,
+            include_ignored: false,
+            details: false,
         };
         let output = cx
             .update(|cx| tool.clone().run(input, ToolCallEventStream::test().0, cx))
@@ -632,6 +780,9 @@ mod tests {
         // Test listing worktree2/docs - should exclude internal.md based on local settings
         let input = ListDirectoryToolInput {
             path: "worktree2/docs".into(),
+            depth: None,
+            include_ignored: false,
+            details: false,
         };
         let output = cx
             .update(|cx| tool.clone().run(input, ToolCallEventStream::test().0, cx))
@@ -646,6 +797,9 @@ mod tests {
         // Test trying to list an excluded directory directly
         let input = ListDirectoryToolInput {
             path: "worktree1/src/secret.rs".into(),
+            depth: None,
+            include_ignored: false,
+            details: false,
         };
         let output = cx
             .update(|cx| tool.clone().run(input, ToolCallEventStream::test().0, cx))
@@ -1,7 +1,8 @@
+use super::support::DRY_RUN_NOTE;
 use crate::{AgentTool, ToolCallEventStream};
-use agent_client_protocol::ToolKind;
+use agent_client_protocol::{self as acp, ToolKind};
 use anyhow::{Context as _, Result, anyhow};
-use gpui::{App, AppContext, Entity, SharedString, Task};
+use gpui::{App, Entity, SharedString, Task};
 use project::Project;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -89,32 +90,51 @@ impl AgentTool for MovePathTool {
     fn run(
         self: Arc<Self>,
         input: Self::Input,
-        _event_stream: ToolCallEventStream,
+        event_stream: ToolCallEventStream,
         cx: &mut App,
     ) -> Task<Result<Self::Output>> {
+        let dry_run = event_stream.dry_run();
+        let mut destination_abs_path = None;
+        // No task means the move was validated, but not made because this is a dry run.
         let rename_task = self.project.update(cx, |project, cx| {
             match project
                 .find_project_path(&input.source_path, cx)
                 .and_then(|project_path| project.entry_for_path(&project_path, cx))
             {
                 Some(entity) => match project.find_project_path(&input.destination_path, cx) {
-                    Some(project_path) => project.rename_entry(entity.id, project_path, cx),
-                    None => Task::ready(Err(anyhow!(
+                    Some(_) if dry_run => None,
+                    Some(project_path) => {
+                        destination_abs_path = project.absolute_path(&project_path, cx);
+                        Some(project.rename_entry(entity.id, project_path, cx))
+                    }
+                    None => Some(Task::ready(Err(anyhow!(
                         "Destination path {} was outside the project.",
                         input.destination_path
-                    ))),
+                    )))),
                 },
-                None => Task::ready(Err(anyhow!(
+                None => Some(Task::ready(Err(anyhow!(
                     "Source path {} was not found in the project.",
                     input.source_path
-                ))),
+                )))),
             }
         });
 
-        cx.background_spawn(async move {
+        cx.spawn(async move |_cx| {
+            let Some(rename_task) = rename_task else {
+                return Ok(format!(
+                    "{DRY_RUN_NOTE} Would move {} to {}.",
+                    input.source_path, input.destination_path
+                ));
+            };
             let _ = rename_task.await.with_context(|| {
                 format!("Moving {} to {}", input.source_path, input.destination_path)
             })?;
+            if let Some(destination_abs_path) = destination_abs_path {
+                event_stream.update_fields(
+                    acp::ToolCallUpdateFields::new()
+                        .locations(vec![acp::ToolCallLocation::new(destination_abs_path)]),
+                );
+            }
             Ok(format!(
                 "Moved {} to {}",
                 input.source_path, input.destination_path
@@ -0,0 +1,242 @@
+use super::{AnchorMemory, ContextualAnchor, display_project_path, resolve_project_path};
+use crate::{AgentTool, ToolCallEventStream};
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use gpui::{App, Entity, SharedString, Task};
+use language_model::LanguageModelToolResultContent;
+use project::{AgentLocation, Project, WorktreeSettings};
+use settings::Settings as _;
+use std::sync::Arc;
+
+/// Tool: navigate_by_context
+///
+/// Points the user's editor at an anchor, without asking a language server anything. Use it to
+/// show the user the code an explanation refers to ("look here").
+pub struct NavigateByContextTool {
+    project: Entity<Project>,
+    anchor_memory: Entity<AnchorMemory>,
+}
+
+impl NavigateByContextTool {
+    pub fn new(project: Entity<Project>, anchor_memory: Entity<AnchorMemory>) -> Self {
+        Self {
+            project,
+            anchor_memory,
+        }
+    }
+}
+
+impl AgentTool for NavigateByContextTool {
+    type Input = ContextualAnchor;
+    type Output = LanguageModelToolResultContent;
+
+    fn name() -> &'static str {
+        "navigate_by_context"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Read
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn input_examples() -> Vec<Self::Input> {
+        vec![ContextualAnchor {
+            path: "project/src/main.rs".into(),
+            context: "let config = Config::load(&path)?;".into(),
+            token: "load".into(),
+            index: None,
+            handle: None,
+            revision: None,
+            whole_word: false,
+            approximate_line: None,
+            case_insensitive: false,
+            unicode_normalize: false,
+            end_token: None,
+            end_index: None,
+        }]
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => match input.handle {
+                Some(handle) => format!("Show `{handle}`").into(),
+                None => format!("Show `{}` in `{}`", input.token, input.path).into(),
+            },
+            Err(_) => "Show location".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let anchor_memory = self.anchor_memory.clone();
+
+        let input = match anchor_memory.read(cx).expand(input) {
+            Ok(input) => input,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        if let Err(error) = input.validate_basic() {
+            return Task::ready(Err(error));
+        }
+        if let Some(revision) = &input.revision {
+            return Task::ready(Err(anyhow!(
+                "The editor only shows the project's current files, so it can't be pointed at {revision}."
+            )));
+        }
+        let project_path = match resolve_project_path(project.read(cx), &input.path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Task::ready(Err(anyhow!(
+                "Cannot read file because its path matches the file_scan_exclusions or private_files setting: {}",
+                &input.path
+            )));
+        }
+        let display_path = display_project_path(project.read(cx), &project_path, cx)
+            .unwrap_or_else(|| input.path.clone());
+
+        cx.spawn(async move |cx| {
+            let buffer = project
+                .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+                .await?;
+            buffer
+                .read_with(cx, |buffer, _| buffer.parsing_idle())?
+                .await;
+            let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot())?;
+            let token_offset = input.resolve_unique_token_offset(&snapshot, &display_path)?;
+            let handles =
+                anchor_memory.update(cx, |anchor_memory, _| anchor_memory.remember(&input))?;
+
+            let token_range = snapshot.anchor_before(token_offset)
+                ..snapshot.anchor_after(input.token_range(&snapshot, token_offset).end);
+            // Unlike the lookup tools, moving the agent location is this tool's whole purpose, so
+            // it doesn't depend on `follow_agent_navigation`.
+            project.update(cx, |project, cx| {
+                project.set_agent_location(
+                    Some(AgentLocation {
+                        buffer: buffer.downgrade(),
+                        position: token_range.start,
+                    }),
+                    cx,
+                );
+            })?;
+            let location = buffer.read_with(cx, |buffer, cx| {
+                input.tool_call_location(buffer, token_range, cx)
+            })?;
+            if let Some(location) = location {
+                event_stream
+                    .update_fields(acp::ToolCallUpdateFields::new().locations(vec![location]));
+            }
+
+            let point = snapshot.offset_to_point(token_offset);
+            let line = snapshot
+                .text_for_range(
+                    language::Point::new(point.row, 0)
+                        ..language::Point::new(point.row, snapshot.line_len(point.row)),
+                )
+                .collect::<String>();
+            let output = format!(
+                "Showed the user `{}` in {display_path} [L{}]: `{}`\n\n{}",
+                input.token,
+                point.row + 1,
+                line.trim(),
+                handles.describe()
+            );
+            Ok(LanguageModelToolResultContent::Text(Arc::from(output)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, recorded_locations, run_tool};
+    use acp_thread::LocationDetail;
+    use gpui::{AppContext as _, TestAppContext};
+    use serde_json::json;
+    use std::path::Path;
+    use text::ToPoint as _;
+    use util::path;
+
+    #[gpui::test]
+    async fn test_navigate_sets_agent_location(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "fn main() {\n    let total = compute(1, 2);\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let tool = Arc::new(NavigateByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+
+        let (output, events) = run_tool(
+            tool,
+            ContextualAnchor {
+                path: "root/src/main.rs".into(),
+                context: "let total = compute(1, 2);".into(),
+                token: "compute".into(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            cx,
+        )
+        .await;
+        let LanguageModelToolResultContent::Text(text) = output.expect("tool failed") else {
+            panic!("expected text output");
+        };
+        assert!(
+            text.starts_with(
+                "Showed the user `compute` in root/src/main.rs [L2]: `let total = compute(1, 2);`"
+            ),
+            "{text}"
+        );
+
+        let locations = recorded_locations(&events);
+        let [location] = locations.as_slice() else {
+            panic!("expected a single location, got {locations:?}");
+        };
+        assert_eq!(location.path, Path::new(path!("/root/src/main.rs")));
+        assert_eq!(location.line, Some(1));
+        let detail = LocationDetail::from_location(location).expect("location has no detail");
+        assert_eq!(detail.columns, Some(16..23));
+
+        let agent_location = test_project
+            .project
+            .read_with(cx, |project, _| project.agent_location())
+            .expect("agent location was not set");
+        let position = agent_location
+            .buffer
+            .upgrade()
+            .expect("buffer was released")
+            .read_with(cx, |buffer, _| agent_location.position.to_point(buffer));
+        assert_eq!(position, language::Point::new(1, 16));
+    }
+}
@@ -0,0 +1,319 @@
+use super::code_actions_by_context_tool::{apply_code_action_for_review, is_disabled};
+use super::support::DRY_RUN_NOTE;
+use super::{display_project_path, resolve_project_path, warm_up_language_servers};
+use crate::{AgentTool, ToolCallAccess, ToolCallEventStream, ToolPreparation};
+use action_log::ActionLog;
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use gpui::{App, AppContext as _, Entity, SharedString, Task};
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::{CodeAction, LspAction, Project, WorktreeSettings};
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{fmt::Write as _, sync::Arc};
+use util::markdown::MarkdownInlineCode;
+
+/// Sorts and deduplicates a file's imports, and removes unused ones, using the language server's
+/// organize imports action. Returns a diff of what changed.
+///
+/// Prefer this over editing import blocks by hand.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct OrganizeImportsToolInput {
+    /// The relative path of the file whose imports to organize.
+    ///
+    /// This path should never be absolute, and the first component of the path should always be a root directory in a project.
+    pub path: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct OrganizeImportsToolOutput {
+    pub path: String,
+    /// The files the action changed, which is usually only `path`.
+    pub changes: Vec<OrganizedFile>,
+    /// Whether the changes were only computed, without being applied.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct OrganizedFile {
+    pub path: String,
+    /// A unified diff of the changes to this file.
+    pub diff: String,
+}
+
+impl From<OrganizeImportsToolOutput> for LanguageModelToolResultContent {
+    fn from(output: OrganizeImportsToolOutput) -> Self {
+        if output.changes.is_empty() {
+            return format!("The imports in {} were already organized.", output.path).into();
+        }
+        let mut text = String::new();
+        let verb = if output.dry_run {
+            writeln!(text, "{DRY_RUN_NOTE}\n").ok();
+            "Would organize"
+        } else {
+            "Organized"
+        };
+        for change in &output.changes {
+            writeln!(
+                text,
+                "{verb} imports in {}:\n\n```diff\n{}\n```\n",
+                change.path, change.diff
+            )
+            .ok();
+        }
+        text.into()
+    }
+}
+
+pub struct OrganizeImportsTool {
+    project: Entity<Project>,
+    action_log: Entity<ActionLog>,
+}
+
+impl OrganizeImportsTool {
+    pub fn new(project: Entity<Project>, action_log: Entity<ActionLog>) -> Self {
+        Self {
+            project,
+            action_log,
+        }
+    }
+}
+
+impl AgentTool for OrganizeImportsTool {
+    type Input = OrganizeImportsToolInput;
+    type Output = OrganizeImportsToolOutput;
+
+    fn name() -> &'static str {
+        "organize_imports"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Edit
+    }
+
+    fn call_access(&self, input: &Self::Input, cx: &App) -> ToolCallAccess {
+        ToolCallAccess::mutates_paths(self.project.read(cx), [&input.path], cx)
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => format!("Organize imports in {}", MarkdownInlineCode(&input.path)).into(),
+            Err(_) => "Organize imports".into(),
+        }
+    }
+
+    fn prepare(
+        self: Arc<Self>,
+        input: Self::Input,
+        cx: &mut App,
+    ) -> Option<Task<Result<ToolPreparation>>> {
+        warm_up_language_servers(&self.project, &input.path, cx)
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let action_log = self.action_log.clone();
+        let project_path = match resolve_project_path(project.read(cx), &input.path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Task::ready(Err(anyhow!(
+                "Cannot edit file because its path matches the file_scan_exclusions or private_files setting: {}",
+                &input.path
+            )));
+        }
+        let display_path = display_project_path(project.read(cx), &project_path, cx)
+            .unwrap_or_else(|| input.path.clone());
+
+        cx.spawn(async move |cx| {
+            let buffer = project
+                .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+                .await?;
+            // Keep the buffer registered until the action has been applied.
+            let _lsp_handle = project.update(cx, |project, cx| {
+                project.register_buffer_with_language_servers(&buffer, cx)
+            })?;
+            let len = buffer.read_with(cx, |buffer, _| buffer.len())?;
+            let action = project
+                .update(cx, |project, cx| {
+                    project.code_actions(
+                        &buffer,
+                        0..len,
+                        Some(vec![lsp::CodeActionKind::SOURCE_ORGANIZE_IMPORTS]),
+                        cx,
+                    )
+                })?
+                .await?
+                .unwrap_or_default()
+                .into_iter()
+                .find(|action| !is_disabled(action) && organizes_imports(action));
+            let Some(action) = action else {
+                anyhow::bail!(
+                    "No organize imports action is available for {display_path} (or language server not capable)"
+                );
+            };
+
+            let changed_buffers = apply_code_action_for_review(
+                &project,
+                &action_log,
+                buffer,
+                action,
+                &event_stream,
+                cx,
+            )
+            .await?;
+            let mut changes = Vec::new();
+            for changed in changed_buffers {
+                let diff = cx
+                    .background_spawn(async move {
+                        language::unified_diff(&changed.old_text, &changed.new_text)
+                    })
+                    .await;
+                changes.push(OrganizedFile {
+                    path: changed.path,
+                    diff,
+                });
+            }
+
+            Ok(OrganizeImportsToolOutput {
+                path: display_path,
+                changes,
+                dry_run: event_stream.dry_run(),
+            })
+        })
+    }
+}
+
+/// Servers may ignore the requested kinds, so the returned actions are checked again.
+fn organizes_imports(action: &CodeAction) -> bool {
+    let organize_imports = lsp::CodeActionKind::SOURCE_ORGANIZE_IMPORTS.as_str();
+    matches!(
+        &action.lsp_action,
+        LspAction::Action(action) if action.kind.as_ref().is_some_and(|kind| {
+            kind.as_str() == organize_imports
+                || kind
+                    .as_str()
+                    .strip_prefix(organize_imports)
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{RecordedToolEvent, ToolTestProject, init_test, run_tool};
+    use futures::StreamExt as _;
+    use gpui::TestAppContext;
+    use serde_json::json;
+
+    #[gpui::test]
+    async fn test_organize_imports_applies_source_action(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "use std::fmt;\nuse std::collections::HashMap;\nuse std::fmt;\n\nfn main() {}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let mut fake_servers = test_project.register_fake_rust_server(
+            lsp::ServerCapabilities {
+                code_action_provider: Some(lsp::CodeActionProviderCapability::Simple(true)),
+                ..Default::default()
+            },
+            cx,
+        );
+        let (buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/main.rs", cx).await;
+        let fake_server = fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+        let uri = lsp::Uri::from_file_path(test_project.abs_path("src/main.rs"))
+            .expect("fake project paths are absolute");
+        fake_server.set_request_handler::<lsp::request::CodeActionRequest, _, _>(
+            move |params, _| {
+                let uri = uri.clone();
+                async move {
+                    assert_eq!(
+                        params.context.only,
+                        Some(vec![lsp::CodeActionKind::SOURCE_ORGANIZE_IMPORTS])
+                    );
+                    let edit = lsp::TextEdit::new(
+                        lsp::Range::new(lsp::Position::new(0, 0), lsp::Position::new(3, 0)),
+                        "use std::collections::HashMap;\nuse std::fmt;\n".into(),
+                    );
+                    Ok(Some(vec![
+                        lsp::CodeActionOrCommand::CodeAction(lsp::CodeAction {
+                            title: "Extract function".into(),
+                            kind: Some(lsp::CodeActionKind::REFACTOR_EXTRACT),
+                            ..Default::default()
+                        }),
+                        lsp::CodeActionOrCommand::CodeAction(lsp::CodeAction {
+                            title: "Organize imports".into(),
+                            kind: Some(lsp::CodeActionKind::SOURCE_ORGANIZE_IMPORTS),
+                            edit: Some(lsp::WorkspaceEdit {
+                                changes: Some([(uri, vec![edit])].into_iter().collect()),
+                                ..Default::default()
+                            }),
+                            ..Default::default()
+                        }),
+                    ]))
+                }
+            },
+        );
+
+        let action_log = cx.new(|_| ActionLog::new(test_project.project.clone()));
+        let tool = Arc::new(OrganizeImportsTool::new(
+            test_project.project.clone(),
+            action_log.clone(),
+        ));
+        let (output, events) = run_tool(
+            tool,
+            OrganizeImportsToolInput {
+                path: "root/src/main.rs".into(),
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+
+        assert_eq!(output.changes.len(), 1);
+        assert_eq!(output.changes[0].path, "root/src/main.rs");
+        assert!(
+            output.changes[0].diff.contains("-use std::fmt;"),
+            "{}",
+            output.changes[0].diff
+        );
+        assert!(events.contains(&RecordedToolEvent::Diff));
+        assert_eq!(
+            buffer.read_with(cx, |buffer, _| buffer.text()),
+            "use std::collections::HashMap;\nuse std::fmt;\n\nfn main() {}\n"
+        );
+    }
+}
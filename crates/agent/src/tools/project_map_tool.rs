@@ -0,0 +1,240 @@
+use super::{display_project_path, resolve_project_path};
+use crate::{AgentTool, ToolCallEventStream, outline};
+use agent_client_protocol as acp;
+use agent_settings::AgentSettings;
+use anyhow::Result;
+use futures::StreamExt as _;
+use gpui::{App, Entity, SharedString, Task};
+use project::{Project, ProjectPath, WorktreeSettings};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{fmt::Write as _, sync::Arc};
+use util::{ResultExt as _, rel_path::RelPath};
+
+/// How large the map may get, in bytes, before the remaining files are left out.
+const MAX_MAP_SIZE: usize = 32 * 1024;
+/// How many files are outlined at most.
+const MAX_FILES: usize = 500;
+/// How many files are opened and outlined at the same time.
+const CONCURRENT_OUTLINES: usize = 8;
+
+/// Gives an overview of the code in a project: the symbols of every source file in the given
+/// directories, with their line numbers, in a single call.
+///
+/// Use it to get your bearings in an unfamiliar codebase, then `file_outline` or `expand_symbol`
+/// to look into single files. Files ignored by git are left out.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ProjectMapToolInput {
+    /// The directories to map, e.g. `["project/src"]`. Defaults to all of the project's root
+    /// directories.
+    #[serde(default)]
+    pub paths: Vec<String>,
+
+    /// How deeply nested the listed symbols may be, where 0 (the default) lists only top-level
+    /// symbols, e.g. types but not their methods.
+    #[serde(default)]
+    pub max_depth: u32,
+}
+
+pub struct ProjectMapTool {
+    project: Entity<Project>,
+}
+
+impl ProjectMapTool {
+    pub fn new(project: Entity<Project>) -> Self {
+        Self { project }
+    }
+}
+
+impl AgentTool for ProjectMapTool {
+    type Input = ProjectMapToolInput;
+    type Output = String;
+
+    fn name() -> &'static str {
+        "project_map"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Read
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) if !input.paths.is_empty() => {
+                format!("Map `{}`", input.paths.join("`, `")).into()
+            }
+            _ => "Map project".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        _event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let mut directories = Vec::new();
+        if input.paths.is_empty() {
+            for worktree in project.read(cx).visible_worktrees(cx) {
+                directories.push(ProjectPath {
+                    worktree_id: worktree.read(cx).id(),
+                    path: RelPath::empty().into(),
+                });
+            }
+        } else {
+            for path in &input.paths {
+                match resolve_project_path(project.read(cx), path, cx) {
+                    Ok(project_path) => directories.push(project_path),
+                    Err(error) => return Task::ready(Err(error)),
+                }
+            }
+        }
+
+        // Only files in a known language can have an outline, so other files aren't opened.
+        let languages = project.read(cx).languages().clone();
+        let mut files = Vec::new();
+        let mut total_files = 0;
+        for directory in &directories {
+            let Some(worktree) = project.read(cx).worktree_for_id(directory.worktree_id, cx) else {
+                continue;
+            };
+            let snapshot = worktree.read(cx).snapshot();
+            for entry in snapshot
+                .traverse_from_path(true, false, false, &directory.path)
+                .take_while(|entry| entry.path.starts_with(&directory.path))
+            {
+                let project_path = ProjectPath {
+                    worktree_id: directory.worktree_id,
+                    path: entry.path.clone(),
+                };
+                let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+                if worktree_settings.is_path_excluded(&project_path.path)
+                    || worktree_settings.is_path_private(&project_path.path)
+                    || languages
+                        .language_for_file_path(entry.path.as_std_path())
+                        .is_none()
+                {
+                    continue;
+                }
+                total_files += 1;
+                if files.len() < MAX_FILES {
+                    files.push(project_path);
+                }
+            }
+        }
+        let max_depth = input.max_depth as usize;
+        let legacy_outline_format = AgentSettings::get_global(cx).legacy_outline_format;
+
+        cx.spawn(async move |cx| {
+            let outlines = futures::stream::iter(files.into_iter().map(|project_path| {
+                let project = project.clone();
+                let mut cx = cx.clone();
+                async move {
+                    let display_path = project.read_with(&cx, |project, cx| {
+                        display_project_path(project, &project_path, cx)
+                    })?;
+                    let buffer = project
+                        .update(&mut cx, |project, cx| project.open_buffer(project_path, cx))?
+                        .await?;
+                    let entries = outline::buffer_outline_entries(buffer, &cx).await?;
+                    anyhow::Ok((display_path, entries))
+                }
+            }))
+            .buffered(CONCURRENT_OUTLINES)
+            .collect::<Vec<_>>()
+            .await;
+
+            let mut output = String::new();
+            let mut examined_files = 0;
+            for outline in outlines {
+                let section = outline.log_err().and_then(|(display_path, entries)| {
+                    let entries = entries
+                        .into_iter()
+                        .filter(|entry| entry.item.depth <= max_depth)
+                        .collect::<Vec<_>>();
+                    let mut section = format!("## {}\n", display_path?);
+                    let layout = outline::OutlineLayout::new(&entries, legacy_outline_format, false);
+                    (outline::render_entries(&mut section, entries, layout) > 0)
+                        .then_some(section)
+                });
+                if let Some(section) = section {
+                    if output.len() + section.len() + 1 > MAX_MAP_SIZE {
+                        break;
+                    }
+                    output.push_str(&section);
+                    output.push('\n');
+                }
+                examined_files += 1;
+            }
+
+            if output.is_empty() {
+                return Ok("No source files with an outline found.".into());
+            }
+            let left_out = total_files - examined_files;
+            if left_out > 0 {
+                writeln!(
+                    output,
+                    "{left_out} more source files were left out to keep the map short. Map fewer `paths` at a time, or use a lower `max_depth`."
+                )?;
+            }
+            Ok(output)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, run_tool};
+    use gpui::TestAppContext;
+    use serde_json::json;
+
+    #[gpui::test]
+    async fn test_project_map(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "README.md": "# Project\n",
+                "src": {
+                    "lib.rs": "pub struct Config {\n    value: u32,\n}\n",
+                    "main.rs": "fn main() {}\n",
+                }
+            }),
+            cx,
+        )
+        .await;
+        let tool = Arc::new(ProjectMapTool::new(test_project.project.clone()));
+        let input = |paths: &[&str], max_depth| ProjectMapToolInput {
+            paths: paths.iter().map(|path| path.to_string()).collect(),
+            max_depth,
+        };
+
+        let (output, _) = run_tool(tool.clone(), input(&[], 0), cx).await;
+        assert_eq!(
+            output.expect("tool failed"),
+            "## root/src/lib.rs\npub struct Config [L1-3]\n\n## root/src/main.rs\nfn main(...) [L1]\n\n"
+        );
+
+        let (output, _) = run_tool(tool.clone(), input(&["root/src/lib.rs"], 1), cx).await;
+        assert_eq!(
+            output.expect("tool failed"),
+            "## root/src/lib.rs\npub struct Config [L1-3]\n  field value     [L2]\n\n"
+        );
+
+        let (output, _) = run_tool(tool, input(&["root/missing"], 0), cx).await;
+        assert_eq!(
+            output.expect("tool failed"),
+            "No source files with an outline found."
+        );
+    }
+}
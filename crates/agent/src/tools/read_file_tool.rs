@@ -1,3 +1,4 @@
+use acp_thread::LocationDetail;
 use action_log::ActionLog;
 use agent_client_protocol::{self as acp, ToolCallUpdateFields};
 use anyhow::{Context as _, Result, anyhow};
@@ -12,7 +13,8 @@ use settings::Settings;
 use std::sync::Arc;
 use util::markdown::MarkdownCodeBlock;
 
-use crate::{AgentTool, Thread, ToolCallEventStream, outline};
+use super::resolve_project_path;
+use crate::{AgentTool, BufferVersion, Thread, ToolCallEventStream, outline};
 
 /// Reads the content of the given file in the project.
 ///
@@ -20,6 +22,7 @@ use crate::{AgentTool, Thread, ToolCallEventStream, outline};
 /// - For large files, this tool returns a file outline with symbol names and line numbers instead of the full content.
 ///   This outline IS a successful response - use the line numbers to read specific sections with start_line/end_line.
 ///   Do NOT retry reading the same file without line numbers if you receive an outline.
+/// - Set `mode` to `outline` to get the outline of a file of any size, or to `full` to get all of a large file's content.
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ReadFileToolInput {
     /// The relative path of the file to read.
@@ -42,6 +45,28 @@ pub struct ReadFileToolInput {
     /// Optional line number to end reading on (1-based index, inclusive)
     #[serde(default)]
     pub end_line: Option<u32>,
+    /// How to read the file. Defaults to `auto`.
+    #[serde(default)]
+    pub mode: ReadFileMode,
+    /// With the `outline` mode, only list symbols nested at most this many levels deep, where 0
+    /// is the top level. Use it to skim very large files.
+    #[serde(default)]
+    pub max_depth: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadFileMode {
+    /// Read the lines between `start_line` and `end_line` when either is given. Otherwise read the
+    /// whole file, or its outline if the file is large.
+    #[default]
+    Auto,
+    /// Read the whole file, however large it is.
+    Full,
+    /// Read the lines between `start_line` and `end_line`. At least one of them is required.
+    Range,
+    /// Read the file's outline of symbols with their line numbers, however small the file is.
+    Outline,
 }
 
 pub struct ReadFileTool {
@@ -89,6 +114,7 @@ impl AgentTool for ReadFileTool {
                 .short_full_path_for_project_path(&project_path, cx)
         {
             match (input.start_line, input.end_line) {
+                _ if input.mode == ReadFileMode::Outline => format!("Read outline of `{path}`"),
                 (Some(start), Some(end)) => {
                     format!("Read file `{path}` (lines {}-{})", start, end,)
                 }
@@ -109,8 +135,23 @@ impl AgentTool for ReadFileTool {
         event_stream: ToolCallEventStream,
         cx: &mut App,
     ) -> Task<Result<LanguageModelToolResultContent>> {
-        let Some(project_path) = self.project.read(cx).find_project_path(&input.path, cx) else {
-            return Task::ready(Err(anyhow!("Path {} not found in project", &input.path)));
+        let has_line_range = input.start_line.is_some() || input.end_line.is_some();
+        match input.mode {
+            ReadFileMode::Range if !has_line_range => {
+                return Task::ready(Err(anyhow!(
+                    "The `range` mode needs a start_line, an end_line, or both."
+                )));
+            }
+            ReadFileMode::Full | ReadFileMode::Outline if has_line_range => {
+                return Task::ready(Err(anyhow!(
+                    "start_line and end_line can only be used with the `range` and `auto` modes."
+                )));
+            }
+            _ => {}
+        }
+        let project_path = match resolve_project_path(self.project.read(cx), &input.path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
         };
         let Some(abs_path) = self.project.read(cx).absolute_path(&project_path, cx) else {
             return Task::ready(Err(anyhow!(
@@ -213,8 +254,7 @@ impl AgentTool for ReadFileTool {
 
             let mut anchor = None;
 
-            // Check if specific line ranges are provided
-            let result = if input.start_line.is_some() || input.end_line.is_some() {
+            let result = if has_line_range {
                 let result = buffer.read_with(cx, |buffer, _cx| {
                     // .max(1) because despite instructions to be 1-indexed, sometimes the model passes 0.
                     let start = input.start_line.unwrap_or(1).max(1);
@@ -238,11 +278,42 @@ impl AgentTool for ReadFileTool {
                 })?;
 
                 Ok(result.into())
+            } else if input.mode == ReadFileMode::Full {
+                let text = buffer.read_with(cx, |buffer, _| buffer.text())?;
+                action_log.update(cx, |log, cx| {
+                    log.buffer_read(buffer.clone(), cx);
+                })?;
+                Ok(text.into())
+            } else if input.mode == ReadFileMode::Outline {
+                match outline::get_buffer_outline(
+                    buffer.clone(),
+                    Some(&abs_path.to_string_lossy()),
+                    input.max_depth.map(|max_depth| max_depth as usize),
+                    cx,
+                )
+                .await?
+                {
+                    Some(text) => {
+                        action_log.update(cx, |log, cx| {
+                            log.buffer_read(buffer.clone(), cx);
+                        })?;
+                        Ok(text.into())
+                    }
+                    None => Err(anyhow!(
+                        "{file_path} has no outline. Read it with the `range` or `full` mode instead."
+                    )),
+                }
             } else {
-                // No line ranges specified, so check file size to see if it's too big.
+                // No line ranges specified, so check whether the file fits in the model's context.
+                let token_budget = self
+                    .thread
+                    .read_with(cx, |thread, _| thread.file_token_budget())
+                    .ok()
+                    .flatten();
                 let buffer_content = outline::get_buffer_content_or_outline(
                     buffer.clone(),
                     Some(&abs_path.to_string_lossy()),
+                    token_budget,
                     cx,
                 )
                 .await?;
@@ -253,7 +324,7 @@ impl AgentTool for ReadFileTool {
 
                 if buffer_content.is_outline {
                     Ok(formatdoc! {"
-                        SUCCESS: File outline retrieved. This file is too large to read all at once, so the outline below shows the file's structure with line numbers.
+                        SUCCESS: File outline retrieved. This file ({}) is too large to read all at once, so the outline below shows the file's structure with line numbers.
 
                         IMPORTANT: Do NOT retry this call without line numbers - you will get the same outline.
                         Instead, use the line numbers below to read specific sections by calling this tool again with start_line and end_line parameters.
@@ -261,7 +332,14 @@ impl AgentTool for ReadFileTool {
                         {}
 
                         NEXT STEPS: To read a specific symbol's implementation, call read_file with the same path plus start_line and end_line from the outline above.
-                        For example, to read a function shown as [L100-150], use start_line: 100 and end_line: 150.", buffer_content.text
+                        For example, to read a function shown as [L100-150], use start_line: 100 and end_line: 150.", buffer_content.file_summary(), buffer_content.text
+                    }
+                    .into())
+                } else if let Some(truncated_at) = buffer_content.truncated_at {
+                    Ok(formatdoc! {"
+                        NOTE: Only the first {truncated_at} of {} bytes of this file ({}) are shown below. Read the rest by calling this tool again with start_line and end_line parameters.
+
+                        {}", buffer_content.total_bytes, buffer_content.file_summary(), buffer_content.text
                     }
                     .into())
                 } else {
@@ -285,9 +363,21 @@ impl AgentTool for ReadFileTool {
                         text,
                     }
                     .to_string();
-                    event_stream.update_fields(ToolCallUpdateFields::new().content(vec![
-                        acp::ToolCallContent::Content(acp::Content::new(markdown)),
-                    ]));
+                    let location = LocationDetail {
+                        version: Some(BufferVersion::of(buffer.read(cx)).to_string()),
+                        ..Default::default()
+                    }
+                    .attach_to(
+                        acp::ToolCallLocation::new(&abs_path)
+                            .line(input.start_line.map(|line| line.saturating_sub(1))),
+                    );
+                    event_stream.update_fields(
+                        ToolCallUpdateFields::new()
+                            .locations(vec![location])
+                            .content(vec![acp::ToolCallContent::Content(acp::Content::new(
+                                markdown,
+                            ))]),
+                    );
                 }
             })?;
 
@@ -299,6 +389,7 @@ impl AgentTool for ReadFileTool {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::test_support::{RecordedToolEvent, recorded_locations, run_tool};
     use crate::{ContextServerRegistry, Templates, Thread};
     use gpui::{AppContext, TestAppContext, UpdateGlobal as _};
     use language_model::fake_provider::FakeLanguageModel;
@@ -339,6 +430,8 @@ mod test {
                     path: "root/nonexistent_file.txt".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.run(input, event_stream, cx)
             })
@@ -349,6 +442,71 @@ mod test {
         );
     }
 
+    #[gpui::test]
+    async fn test_read_file_reports_location(cx: &mut TestAppContext) {
+        init_test(cx);
+
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(
+            path!("/root"),
+            json!({
+                "multiline.txt": "Line 1\nLine 2\nLine 3"
+            }),
+        )
+        .await;
+        let project = Project::test(fs.clone(), [path!("/root").as_ref()], cx).await;
+        let action_log = cx.new(|_| ActionLog::new(project.clone()));
+        let context_server_registry =
+            cx.new(|cx| ContextServerRegistry::new(project.read(cx).context_server_store(), cx));
+        let model = Arc::new(FakeLanguageModel::default());
+        let thread = cx.new(|cx| {
+            Thread::new(
+                project.clone(),
+                cx.new(|_cx| ProjectContext::default()),
+                context_server_registry,
+                Templates::new(),
+                Some(model),
+                cx,
+            )
+        });
+        let tool = Arc::new(ReadFileTool::new(thread.downgrade(), project, action_log));
+
+        let (result, events) = run_tool(
+            tool,
+            ReadFileToolInput {
+                path: "root/multiline.txt".to_string(),
+                start_line: Some(2),
+                end_line: Some(2),
+                mode: ReadFileMode::Auto,
+                max_depth: None,
+            },
+            cx,
+        )
+        .await;
+        assert!(result.is_ok());
+        let locations = recorded_locations(&events);
+        let [initial_location, read_location] = locations.as_slice() else {
+            panic!("expected two location updates, got {locations:?}");
+        };
+        assert_eq!(
+            initial_location,
+            &acp::ToolCallLocation::new(path!("/root/multiline.txt")).line(Some(1))
+        );
+        assert_eq!(read_location.path, initial_location.path);
+        assert_eq!(read_location.line, Some(1));
+        assert!(
+            LocationDetail::from_location(read_location)
+                .and_then(|detail| detail.version)
+                .is_some(),
+            "{read_location:?}"
+        );
+        assert_eq!(events.len(), 2);
+        assert!(
+            matches!(&events[1], RecordedToolEvent::Fields(fields) if fields.content.is_some()),
+            "{events:?}"
+        );
+    }
+
     #[gpui::test]
     async fn test_read_small_file(cx: &mut TestAppContext) {
         init_test(cx);
@@ -383,6 +541,8 @@ mod test {
                     path: "root/small_file.txt".into(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.run(input, ToolCallEventStream::test().0, cx)
             })
@@ -408,14 +568,14 @@ mod test {
         let action_log = cx.new(|_| ActionLog::new(project.clone()));
         let context_server_registry =
             cx.new(|cx| ContextServerRegistry::new(project.read(cx).context_server_store(), cx));
-        let model = Arc::new(FakeLanguageModel::default());
+        // Without a model to count tokens, the file's size decides whether it is outlined.
         let thread = cx.new(|cx| {
             Thread::new(
                 project.clone(),
                 cx.new(|_cx| ProjectContext::default()),
                 context_server_registry,
                 Templates::new(),
-                Some(model),
+                None,
                 cx,
             )
         });
@@ -426,6 +586,8 @@ mod test {
                     path: "root/large_file.rs".into(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.clone().run(input, ToolCallEventStream::test().0, cx)
             })
@@ -436,12 +598,12 @@ mod test {
         assert_eq!(
             content.lines().skip(7).take(6).collect::<Vec<_>>(),
             vec![
-                "struct Test0 [L1-4]",
-                " a [L2]",
-                " b [L3]",
-                "struct Test1 [L5-8]",
-                " a [L6]",
-                " b [L7]",
+                "struct Test0   [L1-4]",
+                "  field a      [L2]",
+                "  field b      [L3]",
+                "struct Test1   [L5-8]",
+                "  field a      [L6]",
+                "  field b      [L7]",
             ]
         );
 
@@ -451,6 +613,8 @@ mod test {
                     path: "root/large_file.rs".into(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.run(input, ToolCallEventStream::test().0, cx)
             })
@@ -460,9 +624,14 @@ mod test {
         let expected_content = (0..1000)
             .flat_map(|i| {
                 vec![
-                    format!("struct Test{} [L{}-{}]", i, i * 4 + 1, i * 4 + 4),
-                    format!(" a [L{}]", i * 4 + 2),
-                    format!(" b [L{}]", i * 4 + 3),
+                    format!(
+                        "{:<14} [L{}-{}]",
+                        format!("struct Test{i}"),
+                        i * 4 + 1,
+                        i * 4 + 4
+                    ),
+                    format!("{:<14} [L{}]", "  field a", i * 4 + 2),
+                    format!("{:<14} [L{}]", "  field b", i * 4 + 3),
                 ]
             })
             .collect::<Vec<_>>();
@@ -476,6 +645,124 @@ mod test {
         );
     }
 
+    #[gpui::test]
+    async fn test_read_file_modes(cx: &mut TestAppContext) {
+        init_test(cx);
+
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(
+            path!("/root"),
+            json!({
+                "small_file.rs": "struct Point {\n    x: u32,\n    y: u32,\n}\n",
+                "large_file.rs": (0..1000).map(|i| format!("struct Test{} {{\n    a: u32,\n}}", i)).collect::<Vec<_>>().join("\n")
+            }),
+        )
+        .await;
+        let project = Project::test(fs.clone(), [path!("/root").as_ref()], cx).await;
+        let language_registry = project.read_with(cx, |project, _| project.languages().clone());
+        language_registry.add(language::rust_lang());
+        let action_log = cx.new(|_| ActionLog::new(project.clone()));
+        let context_server_registry =
+            cx.new(|cx| ContextServerRegistry::new(project.read(cx).context_server_store(), cx));
+        // Without a model to count tokens, the file's size decides whether it is outlined.
+        let thread = cx.new(|cx| {
+            Thread::new(
+                project.clone(),
+                cx.new(|_cx| ProjectContext::default()),
+                context_server_registry,
+                Templates::new(),
+                None,
+                cx,
+            )
+        });
+        let tool = Arc::new(ReadFileTool::new(thread.downgrade(), project, action_log));
+        let read = |path: &str, start_line, end_line, mode, cx: &mut TestAppContext| {
+            let input = ReadFileToolInput {
+                path: path.into(),
+                start_line,
+                end_line,
+                mode,
+                max_depth: None,
+            };
+            cx.update(|cx| tool.clone().run(input, ToolCallEventStream::test().0, cx))
+        };
+
+        let outline = read("root/small_file.rs", None, None, ReadFileMode::Outline, cx)
+            .await
+            .unwrap();
+        assert_eq!(
+            outline
+                .to_str()
+                .unwrap()
+                .lines()
+                .skip(2)
+                .take(3)
+                .collect::<Vec<_>>(),
+            vec![
+                "struct Point [L1-4]",
+                "  field x    [L2]",
+                "  field y    [L3]"
+            ]
+        );
+
+        let top_level_outline = cx
+            .update(|cx| {
+                let input = ReadFileToolInput {
+                    path: "root/small_file.rs".into(),
+                    start_line: None,
+                    end_line: None,
+                    mode: ReadFileMode::Outline,
+                    max_depth: Some(0),
+                };
+                tool.clone().run(input, ToolCallEventStream::test().0, cx)
+            })
+            .await
+            .unwrap();
+        assert!(
+            top_level_outline.to_str().unwrap().ends_with(
+                "struct Point [L1-4]\n\nShowing symbols 1-1 (total symbols: 1)\n2 more deeply nested symbols are hidden; pass a larger `max_depth` to see them.\n"
+            )
+        );
+
+        let full = read("root/large_file.rs", None, None, ReadFileMode::Full, cx)
+            .await
+            .unwrap();
+        assert!(
+            full.to_str()
+                .unwrap()
+                .starts_with("struct Test0 {\n    a: u32,\n}\n")
+        );
+        assert!(
+            full.to_str()
+                .unwrap()
+                .ends_with("struct Test999 {\n    a: u32,\n}")
+        );
+
+        let range = read(
+            "root/large_file.rs",
+            Some(4),
+            Some(5),
+            ReadFileMode::Range,
+            cx,
+        )
+        .await
+        .unwrap();
+        assert_eq!(range, "struct Test1 {\n    a: u32,\n".into());
+
+        read("root/large_file.rs", None, None, ReadFileMode::Range, cx)
+            .await
+            .expect_err("range mode without lines should fail");
+        read(
+            "root/large_file.rs",
+            Some(1),
+            None,
+            ReadFileMode::Outline,
+            cx,
+        )
+        .await
+        .expect_err("outline mode with lines should fail");
+    }
+
     #[gpui::test]
     async fn test_read_file_with_line_range(cx: &mut TestAppContext) {
         init_test(cx);
@@ -511,6 +798,8 @@ mod test {
                     path: "root/multiline.txt".to_string(),
                     start_line: Some(2),
                     end_line: Some(4),
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.run(input, ToolCallEventStream::test().0, cx)
             })
@@ -554,6 +843,8 @@ mod test {
                     path: "root/multiline.txt".to_string(),
                     start_line: Some(0),
                     end_line: Some(2),
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.clone().run(input, ToolCallEventStream::test().0, cx)
             })
@@ -567,6 +858,8 @@ mod test {
                     path: "root/multiline.txt".to_string(),
                     start_line: Some(1),
                     end_line: Some(0),
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.clone().run(input, ToolCallEventStream::test().0, cx)
             })
@@ -580,6 +873,8 @@ mod test {
                     path: "root/multiline.txt".to_string(),
                     start_line: Some(3),
                     end_line: Some(2),
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.clone().run(input, ToolCallEventStream::test().0, cx)
             })
@@ -668,6 +963,8 @@ mod test {
                     path: "/outside_project/sensitive_file.txt".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.clone().run(input, ToolCallEventStream::test().0, cx)
             })
@@ -684,6 +981,8 @@ mod test {
                     path: "project_root/allowed_file.txt".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.clone().run(input, ToolCallEventStream::test().0, cx)
             })
@@ -700,6 +999,8 @@ mod test {
                     path: "project_root/.secretdir/config".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.clone().run(input, ToolCallEventStream::test().0, cx)
             })
@@ -715,6 +1016,8 @@ mod test {
                     path: "project_root/.mymetadata".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.clone().run(input, ToolCallEventStream::test().0, cx)
             })
@@ -731,6 +1034,8 @@ mod test {
                     path: "project_root/.mysecrets".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.clone().run(input, ToolCallEventStream::test().0, cx)
             })
@@ -746,6 +1051,8 @@ mod test {
                     path: "project_root/subdir/special.privatekey".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.clone().run(input, ToolCallEventStream::test().0, cx)
             })
@@ -761,6 +1068,8 @@ mod test {
                     path: "project_root/subdir/data.mysensitive".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.clone().run(input, ToolCallEventStream::test().0, cx)
             })
@@ -777,6 +1086,8 @@ mod test {
                     path: "project_root/subdir/normal_file.txt".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.clone().run(input, ToolCallEventStream::test().0, cx)
             })
@@ -791,6 +1102,8 @@ mod test {
                     path: "project_root/../outside_project/sensitive_file.txt".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.run(input, ToolCallEventStream::test().0, cx)
             })
@@ -899,6 +1212,8 @@ mod test {
                     path: "worktree1/src/main.rs".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.clone().run(input, ToolCallEventStream::test().0, cx)
             })
@@ -917,6 +1232,8 @@ mod test {
                     path: "worktree1/src/secret.rs".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.clone().run(input, ToolCallEventStream::test().0, cx)
             })
@@ -938,6 +1255,8 @@ mod test {
                     path: "worktree1/tests/fixture.sql".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.clone().run(input, ToolCallEventStream::test().0, cx)
             })
@@ -959,6 +1278,8 @@ mod test {
                     path: "worktree2/lib/public.js".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.clone().run(input, ToolCallEventStream::test().0, cx)
             })
@@ -977,6 +1298,8 @@ mod test {
                     path: "worktree2/lib/private.js".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.clone().run(input, ToolCallEventStream::test().0, cx)
             })
@@ -998,6 +1321,8 @@ mod test {
                     path: "worktree2/docs/internal.md".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.clone().run(input, ToolCallEventStream::test().0, cx)
             })
@@ -1020,6 +1345,8 @@ mod test {
                     path: "worktree1/src/config.toml".to_string(),
                     start_line: None,
                     end_line: None,
+                    mode: ReadFileMode::Auto,
+                    max_depth: None,
                 };
                 tool.clone().run(input, ToolCallEventStream::test().0, cx)
             })
@@ -0,0 +1,511 @@
+use super::support::DRY_RUN_NOTE;
+use super::{
+    AnchorMemory, ContextualAnchor, TokenSyntax, display_project_path, resolve_project_path,
+    resolve_unique_in_buffer, warm_up_language_servers,
+};
+use crate::{AgentTool, ToolCallEventStream, ToolPreparation};
+use action_log::ActionLog;
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use gpui::{App, Context, Entity, SharedString, Task};
+use language::{Buffer, Point, Transaction};
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::{PrepareRenameResponse, Project, ProjectPath, WorktreeSettings};
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{fmt::Write as _, ops::Range, sync::Arc};
+
+/// Renames the symbol at an anchor everywhere it is used, using the language server's rename, which
+/// understands scopes, shadowing and imports unlike a text search-and-replace.
+///
+/// By default the tool only reports every line the rename would change. Set `apply` to make the
+/// changes once the preview looks right.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RenameSymbolByContextToolInput {
+    #[serde(flatten)]
+    pub anchor: ContextualAnchor,
+    /// The new name for the symbol.
+    pub new_name: String,
+    /// Whether to make the changes. When false, the tool only reports what would change.
+    #[serde(default)]
+    pub apply: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RenameSymbolByContextToolOutput {
+    pub old_name: String,
+    pub new_name: String,
+    /// Whether the changes were made, rather than only previewed.
+    pub applied: bool,
+    pub files: Vec<RenamedFile>,
+    /// Whether the changes were asked to be made, but only previewed because this was a dry run.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RenamedFile {
+    pub path: String,
+    pub lines: Vec<RenamedLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RenamedLine {
+    /// The 1-based line number after the rename.
+    pub line: u32,
+    /// The line's text after the rename.
+    pub text: String,
+}
+
+impl From<RenameSymbolByContextToolOutput> for LanguageModelToolResultContent {
+    fn from(output: RenameSymbolByContextToolOutput) -> Self {
+        if output.files.is_empty() {
+            return format!(
+                "The language server reported no changes for renaming `{}` to `{}`.",
+                output.old_name, output.new_name
+            )
+            .into();
+        }
+
+        let line_count = output
+            .files
+            .iter()
+            .map(|file| file.lines.len())
+            .sum::<usize>();
+        let mut text = format!(
+            "{} `{}` to `{}` {} {line_count} lines in {} files:\n",
+            if output.applied {
+                "Renamed"
+            } else {
+                "Renaming"
+            },
+            output.old_name,
+            output.new_name,
+            if output.applied {
+                "changed"
+            } else {
+                "would change"
+            },
+            output.files.len()
+        );
+        for file in &output.files {
+            write!(text, "\n{}\n", file.path).ok();
+            for line in &file.lines {
+                writeln!(text, "- L{}: `{}`", line.line, line.text.trim()).ok();
+            }
+        }
+        if output.dry_run {
+            writeln!(text, "\n{DRY_RUN_NOTE}").ok();
+        } else if !output.applied {
+            text.push_str("\nNothing was changed. Call the tool again with `\"apply\": true` to make these changes.\n");
+        }
+        text.into()
+    }
+}
+
+/// Tool: rename_symbol_by_context
+pub struct RenameSymbolByContextTool {
+    project: Entity<Project>,
+    action_log: Entity<ActionLog>,
+    anchor_memory: Entity<AnchorMemory>,
+}
+
+impl RenameSymbolByContextTool {
+    pub fn new(
+        project: Entity<Project>,
+        action_log: Entity<ActionLog>,
+        anchor_memory: Entity<AnchorMemory>,
+    ) -> Self {
+        Self {
+            project,
+            action_log,
+            anchor_memory,
+        }
+    }
+}
+
+/// The edits a rename makes to one buffer, as offsets into the buffer before the rename.
+struct BufferRename {
+    buffer: Entity<Buffer>,
+    edits: Vec<(Range<usize>, String)>,
+    file: RenamedFile,
+}
+
+impl AgentTool for RenameSymbolByContextTool {
+    type Input = RenameSymbolByContextToolInput;
+    type Output = RenameSymbolByContextToolOutput;
+
+    fn name() -> &'static str {
+        "rename_symbol_by_context"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Edit
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn input_examples() -> Vec<Self::Input> {
+        vec![RenameSymbolByContextToolInput {
+            anchor: ContextualAnchor {
+                path: "project/src/config.rs".into(),
+                context: "pub fn load(path: &Path) -> Result<Self> {".into(),
+                token: "load".into(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            new_name: "load_from_path".into(),
+            apply: false,
+        }]
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => {
+                let symbol = input.anchor.handle.unwrap_or(input.anchor.token);
+                if input.apply {
+                    format!("Rename `{symbol}` to `{}`", input.new_name).into()
+                } else {
+                    format!("Preview renaming `{symbol}` to `{}`", input.new_name).into()
+                }
+            }
+            Err(_) => "Rename symbol".into(),
+        }
+    }
+
+    fn prepare(
+        self: Arc<Self>,
+        input: Self::Input,
+        cx: &mut App,
+    ) -> Option<Task<Result<ToolPreparation>>> {
+        let anchor = self.anchor_memory.read(cx).expand(input.anchor).ok()?;
+        if anchor.revision.is_some() {
+            return None;
+        }
+        warm_up_language_servers(&self.project, &anchor.path, cx)
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let action_log = self.action_log.clone();
+        let dry_run = input.apply && event_stream.dry_run();
+        let apply = input.apply && !dry_run;
+
+        let anchor = match self.anchor_memory.read(cx).expand(input.anchor) {
+            Ok(anchor) => anchor,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        if let Err(error) = anchor.validate_basic() {
+            return Task::ready(Err(error));
+        }
+        if anchor.revision.is_some() {
+            return Task::ready(Err(anyhow!(
+                "Symbols can only be renamed in the project's current files, not at a git revision."
+            )));
+        }
+        let new_name = input.new_name.trim().to_string();
+        if new_name.is_empty() {
+            return Task::ready(Err(anyhow!("`new_name` must not be empty.")));
+        }
+        let project_path = match resolve_project_path(project.read(cx), &anchor.path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Task::ready(Err(anyhow!(
+                "Cannot edit file because its path matches the file_scan_exclusions or private_files setting: {}",
+                &anchor.path
+            )));
+        }
+        let display_path = display_project_path(project.read(cx), &project_path, cx)
+            .unwrap_or_else(|| anchor.path.clone());
+
+        cx.spawn(async move |cx| {
+            let buffer = project
+                .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+                .await?;
+            buffer
+                .read_with(cx, |buffer, _| buffer.parsing_idle())?
+                .await;
+            let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot())?;
+            let token_offset = resolve_unique_in_buffer(
+                &anchor,
+                &snapshot,
+                &display_path,
+                Some(TokenSyntax::Symbol),
+            )?
+            .range
+            .start;
+
+            let preparation = project
+                .update(cx, |project, cx| {
+                    project.prepare_rename(buffer.clone(), token_offset, cx)
+                })?
+                .await?;
+            if let PrepareRenameResponse::InvalidPosition = preparation {
+                anyhow::bail!(
+                    "`{}` can't be renamed here (or no language server supports renaming it).",
+                    anchor.token
+                );
+            }
+
+            // The language server's workspace edit can only be obtained by applying it, so it is
+            // applied, recorded as plain edits and undone. That way a preview leaves every buffer
+            // as it was, and applied edits go through the action log like any other agent edit.
+            let transaction = project
+                .update(cx, |project, cx| {
+                    project.perform_rename(buffer.clone(), token_offset, new_name.clone(), cx)
+                })?
+                .await?;
+            let mut renames = Vec::new();
+            for (buffer, transaction) in transaction.0 {
+                let rename = buffer.update(cx, |buffer, cx| {
+                    let snapshot = buffer.snapshot();
+                    let mut lines = Vec::new();
+                    for edit in snapshot.edits_since::<usize>(&transaction.start) {
+                        let row = snapshot.offset_to_point(edit.new.start).row;
+                        if lines
+                            .last()
+                            .is_none_or(|line: &RenamedLine| line.line != row + 1)
+                        {
+                            lines.push(RenamedLine {
+                                line: row + 1,
+                                text: snapshot
+                                    .text_for_range(
+                                        Point::new(row, 0)..Point::new(row, snapshot.line_len(row)),
+                                    )
+                                    .collect(),
+                            });
+                        }
+                    }
+                    let edits = revert_transaction(buffer, &transaction, cx);
+                    let path = buffer
+                        .file()
+                        .and_then(|file| {
+                            display_project_path(
+                                project.read(cx),
+                                &ProjectPath::from_file(file.as_ref(), cx),
+                                cx,
+                            )
+                        })
+                        .unwrap_or_else(|| "<buffer>".to_string());
+                    BufferRename {
+                        buffer: cx.entity(),
+                        edits,
+                        file: RenamedFile { path, lines },
+                    }
+                })?;
+                if !rename.edits.is_empty() {
+                    renames.push(rename);
+                }
+            }
+            renames.sort_by(|left, right| left.file.path.cmp(&right.file.path));
+
+            if apply {
+                for rename in &renames {
+                    action_log.update(cx, |action_log, cx| {
+                        action_log.buffer_read(rename.buffer.clone(), cx)
+                    })?;
+                    rename.buffer.update(cx, |buffer, cx| {
+                        buffer.edit(rename.edits.iter().cloned(), None, cx);
+                    })?;
+                    action_log.update(cx, |action_log, cx| {
+                        action_log.buffer_edited(rename.buffer.clone(), cx)
+                    })?;
+                }
+            }
+
+            Ok(RenameSymbolByContextToolOutput {
+                old_name: anchor.token,
+                new_name,
+                applied: apply,
+                files: renames.into_iter().map(|rename| rename.file).collect(),
+                dry_run,
+            })
+        })
+    }
+}
+
+/// Undoes and forgets `transaction`, returning its edits as replacements of ranges in the text
+/// from before it, so they can be applied again later.
+pub(crate) fn revert_transaction(
+    buffer: &mut Buffer,
+    transaction: &Transaction,
+    cx: &mut Context<Buffer>,
+) -> Vec<(Range<usize>, String)> {
+    let snapshot = buffer.snapshot();
+    let edits = snapshot
+        .edits_since::<usize>(&transaction.start)
+        .map(|edit| (edit.old, snapshot.text_for_range(edit.new).collect()))
+        .collect();
+    buffer.undo_transaction(transaction.id, cx);
+    buffer.forget_transaction(transaction.id);
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, run_tool, run_tool_dry_run};
+    use futures::StreamExt as _;
+    use gpui::{AppContext as _, TestAppContext};
+    use serde_json::json;
+
+    #[gpui::test]
+    async fn test_rename_preview_and_apply(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "main.rs": "fn helper() -> u32 {\n    1\n}\n\nfn main() {\n    let first = helper();\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let mut fake_servers = test_project.register_fake_rust_server(
+            lsp::ServerCapabilities {
+                rename_provider: Some(lsp::OneOf::Left(true)),
+                ..Default::default()
+            },
+            cx,
+        );
+        let (buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/main.rs", cx).await;
+        let fake_server = fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+
+        let uri = lsp::Uri::from_file_path(test_project.abs_path("src/main.rs"))
+            .expect("fake project paths are absolute");
+        fake_server.set_request_handler::<lsp::request::Rename, _, _>(move |params, _| {
+            let uri = uri.clone();
+            async move {
+                let edit = |line, start, end| {
+                    lsp::TextEdit::new(
+                        lsp::Range::new(
+                            lsp::Position::new(line, start),
+                            lsp::Position::new(line, end),
+                        ),
+                        params.new_name.clone(),
+                    )
+                };
+                Ok(Some(lsp::WorkspaceEdit {
+                    changes: Some(
+                        [(uri, vec![edit(0, 3, 9), edit(5, 16, 22)])]
+                            .into_iter()
+                            .collect(),
+                    ),
+                    ..Default::default()
+                }))
+            }
+        });
+
+        let action_log = cx.new(|_| ActionLog::new(test_project.project.clone()));
+        let tool = Arc::new(RenameSymbolByContextTool::new(
+            test_project.project.clone(),
+            action_log.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let original_text = buffer.read_with(cx, |buffer, _| buffer.text());
+        let input = |apply| RenameSymbolByContextToolInput {
+            anchor: ContextualAnchor {
+                path: "root/src/main.rs".into(),
+                context: "let first = helper();".into(),
+                token: "helper".into(),
+                index: None,
+                handle: None,
+                revision: None,
+                whole_word: false,
+                approximate_line: None,
+                case_insensitive: false,
+                unicode_normalize: false,
+                end_token: None,
+                end_index: None,
+            },
+            new_name: "compute".into(),
+            apply,
+        };
+
+        let (output, _) = run_tool(tool.clone(), input(false), cx).await;
+        let expected_files = vec![RenamedFile {
+            path: "root/src/main.rs".into(),
+            lines: vec![
+                RenamedLine {
+                    line: 1,
+                    text: "fn compute() -> u32 {".into(),
+                },
+                RenamedLine {
+                    line: 6,
+                    text: "    let first = compute();".into(),
+                },
+            ],
+        }];
+        assert_eq!(
+            output.expect("tool failed"),
+            RenameSymbolByContextToolOutput {
+                old_name: "helper".into(),
+                new_name: "compute".into(),
+                applied: false,
+                files: expected_files.clone(),
+                dry_run: false,
+            }
+        );
+        assert_eq!(
+            buffer.read_with(cx, |buffer, _| buffer.text()),
+            original_text
+        );
+        assert!(action_log.read_with(cx, |log, cx| log.changed_buffers(cx).is_empty()));
+
+        let (output, _) = run_tool_dry_run(tool.clone(), input(true), cx).await;
+        let output = output.expect("tool failed");
+        assert!(!output.applied);
+        assert!(output.dry_run);
+        assert_eq!(output.files, expected_files);
+        assert_eq!(
+            buffer.read_with(cx, |buffer, _| buffer.text()),
+            original_text
+        );
+        assert!(action_log.read_with(cx, |log, cx| log.changed_buffers(cx).is_empty()));
+
+        let (output, _) = run_tool(tool, input(true), cx).await;
+        let output = output.expect("tool failed");
+        assert!(output.applied);
+        assert_eq!(output.files, expected_files);
+        assert_eq!(
+            buffer.read_with(cx, |buffer, _| buffer.text()),
+            "fn compute() -> u32 {\n    1\n}\n\nfn main() {\n    let first = compute();\n}\n"
+        );
+        cx.run_until_parked();
+        assert_eq!(
+            action_log.read_with(cx, |log, cx| log.changed_buffers(cx).len()),
+            1
+        );
+    }
+}
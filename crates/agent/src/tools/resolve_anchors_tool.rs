@@ -0,0 +1,560 @@
+use super::{AnchorInput, AnchorMemory, AnchorResolutionError, resolve_anchors};
+use crate::{AgentTool, ToolCallEventStream};
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use gpui::{App, Entity, SharedString, Task};
+use language::PointUtf16;
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::Project;
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Write as _, ops::Range, sync::Arc};
+
+const MAX_ANCHORS: usize = 100;
+
+/// Resolves several anchors at once, reporting for each where its token is or why it couldn't be
+/// found. Use it to check a set of anchors before passing them to other tools, or to get handles
+/// for all of them in one call.
+///
+/// An anchor can also be given as a `symbol_path` through the file's outline, such as
+/// `["impl Config", "load"]`. The handle reported for it then works in every tool that takes an
+/// anchor.
+///
+/// Each file is only read once, however many anchors point into it, and one anchor failing
+/// doesn't affect the others.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ResolveAnchorsToolInput {
+    /// The anchors to resolve, up to 100.
+    pub anchors: Vec<AnchorInput>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ResolveAnchorsToolOutput {
+    /// One result per input anchor, in the same order.
+    pub anchors: Vec<ResolveAnchorsResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ResolveAnchorsResult {
+    pub path: String,
+    pub token: String,
+    /// Where the token is, if the anchor resolved.
+    pub location: Option<ResolvedLocation>,
+    /// Where the span from the token to the anchor's `end_token` is, if the anchor resolved and
+    /// has an `end_token`.
+    #[serde(default)]
+    pub span: Option<ResolvedLocation>,
+    /// Why the anchor didn't resolve.
+    pub error: Option<String>,
+    /// The reason the anchor didn't resolve, when it is one that can be acted on without reading
+    /// `error`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_reason: Option<AnchorResolutionError>,
+    /// The handle under which the anchor was remembered, if it resolved.
+    pub handle: Option<String>,
+    /// The symbol handle under which the anchor was remembered, if it is unambiguous.
+    pub symbol_handle: Option<String>,
+    /// Whether the file has unsaved edits, which the location reflects but commands that read the
+    /// file from disk won't see until it is saved.
+    #[serde(default)]
+    pub unsaved: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ResolvedLocation {
+    /// The byte offset of the token's start in the file.
+    pub start_offset: usize,
+    /// The byte offset just past the token's end in the file.
+    pub end_offset: usize,
+    /// The token's start as a 0-based row and UTF-16 column, as language servers count them.
+    pub start: Utf16Position,
+    /// The position just past the token's end as a 0-based row and UTF-16 column.
+    pub end: Utf16Position,
+}
+
+impl ResolveAnchorsResult {
+    fn fail(&mut self, error: anyhow::Error) {
+        self.error = Some(format!("{error:#}"));
+        self.error_reason = error.downcast::<AnchorResolutionError>().ok();
+    }
+}
+
+impl ResolvedLocation {
+    fn new(range: Range<usize>, range_utf16: Range<PointUtf16>) -> Self {
+        Self {
+            start_offset: range.start,
+            end_offset: range.end,
+            start: Utf16Position {
+                row: range_utf16.start.row,
+                column: range_utf16.start.column,
+            },
+            end: Utf16Position {
+                row: range_utf16.end.row,
+                column: range_utf16.end.column,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Utf16Position {
+    pub row: u32,
+    pub column: u32,
+}
+
+impl From<ResolveAnchorsToolOutput> for LanguageModelToolResultContent {
+    fn from(output: ResolveAnchorsToolOutput) -> Self {
+        let resolved_count = output
+            .anchors
+            .iter()
+            .filter(|resolution| resolution.location.is_some())
+            .count();
+        let mut text = format!(
+            "Resolved {resolved_count} of {} anchor(s).\n\n",
+            output.anchors.len()
+        );
+        for (index, resolution) in output.anchors.iter().enumerate() {
+            write!(
+                text,
+                "{}. `{}` in {}: ",
+                index + 1,
+                resolution.token,
+                resolution.path
+            )
+            .ok();
+            match (&resolution.location, &resolution.error) {
+                (Some(location), _) => {
+                    write!(
+                        text,
+                        "L{}:{} (bytes {}-{})",
+                        location.start.row + 1,
+                        location.start.column + 1,
+                        location.start_offset,
+                        location.end_offset
+                    )
+                    .ok();
+                    if let Some(span) = &resolution.span {
+                        write!(
+                            text,
+                            ", span L{}-L{} (bytes {}-{})",
+                            span.start.row + 1,
+                            span.end.row + 1,
+                            span.start_offset,
+                            span.end_offset
+                        )
+                        .ok();
+                    }
+                    match (&resolution.handle, &resolution.symbol_handle) {
+                        (Some(handle), Some(symbol_handle)) => {
+                            write!(text, ", handles `{handle}` and `{symbol_handle}`").ok();
+                        }
+                        (Some(handle), None) => {
+                            write!(text, ", handle `{handle}`").ok();
+                        }
+                        _ => {}
+                    }
+                    if resolution.unsaved {
+                        text.push_str(" (unsaved changes)");
+                    }
+                    text.push('\n');
+                }
+                (None, error) => {
+                    writeln!(
+                        text,
+                        "failed: {}",
+                        error.as_deref().unwrap_or("unknown error")
+                    )
+                    .ok();
+                }
+            }
+        }
+        if output.anchors.iter().any(|resolution| resolution.unsaved) {
+            text.push_str(
+                "\nSave the files with unsaved changes before running commands that read them from disk.\n",
+            );
+        }
+        text.into()
+    }
+}
+
+pub struct ResolveAnchorsTool {
+    project: Entity<Project>,
+    anchor_memory: Entity<AnchorMemory>,
+}
+
+impl ResolveAnchorsTool {
+    pub fn new(project: Entity<Project>, anchor_memory: Entity<AnchorMemory>) -> Self {
+        Self {
+            project,
+            anchor_memory,
+        }
+    }
+}
+
+impl AgentTool for ResolveAnchorsTool {
+    type Input = ResolveAnchorsToolInput;
+    type Output = ResolveAnchorsToolOutput;
+
+    fn name() -> &'static str {
+        "resolve_anchors"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Read
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => format!("Resolve {} anchor(s)", input.anchors.len()).into(),
+            Err(_) => "Resolve anchors".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        _event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        if input.anchors.is_empty() {
+            return Task::ready(Err(anyhow!("Provide at least one anchor.")));
+        }
+        if input.anchors.len() > MAX_ANCHORS {
+            return Task::ready(Err(anyhow!(
+                "At most {MAX_ANCHORS} anchors can be resolved at once, but {} were given.",
+                input.anchors.len()
+            )));
+        }
+
+        let mut resolutions = Vec::new();
+        let mut expanded_anchors = Vec::new();
+        let mut expanded_indices = Vec::new();
+        for (index, anchor) in input.anchors.into_iter().enumerate() {
+            let mut resolution = ResolveAnchorsResult {
+                path: anchor.path().to_string(),
+                token: anchor.token(),
+                location: None,
+                span: None,
+                error: None,
+                error_reason: None,
+                handle: None,
+                symbol_handle: None,
+                unsaved: false,
+            };
+            let expanded = match anchor {
+                AnchorInput::Contextual(anchor) => self
+                    .anchor_memory
+                    .read(cx)
+                    .expand(anchor)
+                    .map(AnchorInput::Contextual),
+                AnchorInput::Symbol(anchor) => Ok(AnchorInput::Symbol(anchor)),
+            };
+            match expanded {
+                Ok(anchor) => {
+                    resolution.path = anchor.path().to_string();
+                    resolution.token = anchor.token();
+                    expanded_anchors.push(anchor);
+                    expanded_indices.push(index);
+                }
+                Err(error) => resolution.fail(error),
+            }
+            resolutions.push(resolution);
+        }
+
+        let resolved = resolve_anchors(&self.project, expanded_anchors, cx);
+        let anchor_memory = self.anchor_memory.clone();
+        cx.spawn(async move |cx| {
+            let resolved = resolved.await;
+            for (index, result) in expanded_indices.into_iter().zip(resolved) {
+                let resolution = &mut resolutions[index];
+                match result {
+                    Ok(resolved) => {
+                        let handles = anchor_memory.update(cx, |anchor_memory, _| {
+                            anchor_memory.remember(&resolved.anchor)
+                        })?;
+                        resolution.path = resolved.display_path;
+                        resolution.location =
+                            Some(ResolvedLocation::new(resolved.range, resolved.range_utf16));
+                        if resolved.anchor.end_token.is_some() {
+                            resolution.span =
+                                Some(ResolvedLocation::new(resolved.span, resolved.span_utf16));
+                        }
+                        resolution.handle = Some(handles.reference);
+                        resolution.symbol_handle = handles.symbol;
+                        resolution.unsaved = resolved.unsaved;
+                    }
+                    Err(error) => resolution.fail(error),
+                }
+            }
+            Ok(ResolveAnchorsToolOutput {
+                anchors: resolutions,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, run_tool};
+    use crate::{ContextualAnchor, SymbolAnchor};
+    use gpui::{AppContext as _, TestAppContext};
+    use serde_json::json;
+
+    fn anchor(path: &str, context: &str, token: &str) -> ContextualAnchor {
+        ContextualAnchor {
+            path: path.into(),
+            context: context.into(),
+            token: token.into(),
+            index: None,
+            handle: None,
+            revision: None,
+            whole_word: false,
+            approximate_line: None,
+            case_insensitive: false,
+            unicode_normalize: false,
+            end_token: None,
+            end_index: None,
+        }
+    }
+
+    #[gpui::test]
+    async fn test_resolve_anchors(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "lib.rs": "// lib\npub fn compute(value: u32) -> u32 {\n    value + 1\n}\n",
+                    "main.rs": "fn main() {\n    let café = lib::compute(1);\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let tool = Arc::new(ResolveAnchorsTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+
+        let (output, _) = run_tool(
+            tool,
+            ResolveAnchorsToolInput {
+                anchors: vec![
+                    anchor("root/src/lib.rs", "pub fn compute(value: u32)", "compute"),
+                    anchor("root/src/main.rs", "let café = lib::compute(1);", "lib"),
+                    anchor("root/src/lib.rs", "pub fn missing(value: u32)", "missing"),
+                    anchor("root/src/lib.rs", "value + 1", "value"),
+                    ContextualAnchor {
+                        end_token: Some("}".into()),
+                        ..anchor(
+                            "root/src/lib.rs",
+                            "pub fn compute(value: u32) -> u32 {\n    value + 1\n}",
+                            "fn",
+                        )
+                    },
+                ]
+                .into_iter()
+                .map(AnchorInput::Contextual)
+                .collect(),
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+
+        assert_eq!(
+            output.anchors[0].location,
+            Some(ResolvedLocation {
+                start_offset: 14,
+                end_offset: 21,
+                start: Utf16Position { row: 1, column: 7 },
+                end: Utf16Position { row: 1, column: 14 },
+            })
+        );
+        assert_eq!(output.anchors[0].span, None);
+        assert_eq!(output.anchors[0].handle.as_deref(), Some("ref#1"));
+        assert_eq!(
+            output.anchors[0].symbol_handle.as_deref(),
+            Some("sym:compute")
+        );
+        // `é` is two bytes but one UTF-16 code unit.
+        let location = output.anchors[1]
+            .location
+            .clone()
+            .expect("anchor should resolve");
+        assert_eq!(location.start_offset, 28);
+        assert_eq!(location.start, Utf16Position { row: 1, column: 15 });
+        assert_eq!(output.anchors[2].location, None);
+        assert!(
+            output.anchors[2].error.as_deref().is_some_and(
+                |error| error.starts_with("context/token not found in root/src/lib.rs")
+            ),
+            "{:?}",
+            output.anchors[2].error
+        );
+        assert!(matches!(
+            output.anchors[2].error_reason,
+            Some(AnchorResolutionError::NoContextMatch { .. })
+        ));
+        assert_eq!(
+            output.anchors[3]
+                .location
+                .as_ref()
+                .map(|location| location.start),
+            Some(Utf16Position { row: 2, column: 4 })
+        );
+        assert_eq!(
+            output.anchors[4].span,
+            Some(ResolvedLocation {
+                start_offset: 11,
+                end_offset: 58,
+                start: Utf16Position { row: 1, column: 4 },
+                end: Utf16Position { row: 3, column: 1 },
+            })
+        );
+    }
+
+    #[gpui::test]
+    async fn test_resolve_anchors_in_unsaved_buffer(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "lib.rs": "pub fn compute(value: u32) -> u32 {\n    value + 1\n}\n",
+                    "main.rs": "fn main() {\n    let total = lib::compute(1);\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let buffer = test_project
+            .project
+            .update(cx, |project, cx| {
+                project.open_local_buffer(test_project.abs_path("src/lib.rs"), cx)
+            })
+            .await
+            .expect("failed to open buffer");
+        buffer.update(cx, |buffer, cx| {
+            buffer.edit([(0..0, "pub fn added(value: u32) {}\n")], None, cx);
+        });
+        let tool = Arc::new(ResolveAnchorsTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+
+        let (output, _) = run_tool(
+            tool,
+            ResolveAnchorsToolInput {
+                anchors: vec![
+                    anchor("root/src/lib.rs", "pub fn added(value: u32)", "added"),
+                    anchor(
+                        "root/src/main.rs",
+                        "let total = lib::compute(1);",
+                        "compute",
+                    ),
+                ]
+                .into_iter()
+                .map(AnchorInput::Contextual)
+                .collect(),
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+
+        assert_eq!(
+            output.anchors[0]
+                .location
+                .as_ref()
+                .map(|location| location.start),
+            Some(Utf16Position { row: 0, column: 7 })
+        );
+        assert!(output.anchors[0].unsaved);
+        assert!(output.anchors[1].location.is_some());
+        assert!(!output.anchors[1].unsaved);
+    }
+
+    #[gpui::test]
+    async fn test_resolve_symbol_anchors(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "lib.rs": "pub struct Config;\n\nimpl Config {\n    pub fn load() -> Self {\n        Config\n    }\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let anchor_memory = cx.new(|_| AnchorMemory::default());
+        let tool = Arc::new(ResolveAnchorsTool::new(
+            test_project.project.clone(),
+            anchor_memory.clone(),
+        ));
+        let symbol = |symbol_path: &[&str]| {
+            AnchorInput::Symbol(SymbolAnchor {
+                path: "root/src/lib.rs".into(),
+                symbol_path: symbol_path
+                    .iter()
+                    .map(|element| element.to_string())
+                    .collect(),
+            })
+        };
+
+        let (output, _) = run_tool(
+            tool,
+            ResolveAnchorsToolInput {
+                anchors: vec![
+                    symbol(&["impl Config", "load"]),
+                    symbol(&["impl Config", "save"]),
+                ],
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+
+        assert_eq!(output.anchors[0].token, "impl Config > load");
+        assert_eq!(
+            output.anchors[0]
+                .location
+                .as_ref()
+                .map(|location| location.start),
+            Some(Utf16Position { row: 3, column: 11 })
+        );
+        let handle = output.anchors[0]
+            .handle
+            .clone()
+            .expect("anchor should be remembered");
+        let remembered = anchor_memory.read_with(cx, |anchor_memory, _| {
+            anchor_memory.expand(ContextualAnchor {
+                handle: Some(handle),
+                ..anchor("", "", "")
+            })
+        });
+        assert_eq!(
+            remembered.expect("handle should expand").context,
+            "pub fn load() -> Self {"
+        );
+        assert!(
+            output.anchors[1]
+                .error
+                .as_deref()
+                .is_some_and(|error| error.starts_with("No symbol `impl Config > save`")),
+            "{:?}",
+            output.anchors[1].error
+        );
+    }
+}
@@ -9,7 +9,8 @@ use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use crate::{AgentTool, ToolCallEventStream};
+use super::support::DRY_RUN_NOTE;
+use crate::{AgentTool, ToolCallAccess, ToolCallEventStream, ToolPermissionClass};
 
 /// Discards unsaved changes in open buffers by reloading file contents from disk.
 ///
@@ -46,6 +47,14 @@ impl AgentTool for RestoreFileFromDiskTool {
         acp::ToolKind::Other
     }
 
+    fn permission_class() -> ToolPermissionClass {
+        ToolPermissionClass::WorkspaceMutating
+    }
+
+    fn call_access(&self, input: &Self::Input, cx: &App) -> ToolCallAccess {
+        ToolCallAccess::mutates_paths(self.project.read(cx), &input.paths, cx)
+    }
+
     fn initial_title(
         &self,
         input: Result<Self::Input, serde_json::Value>,
@@ -61,11 +70,12 @@ impl AgentTool for RestoreFileFromDiskTool {
     fn run(
         self: Arc<Self>,
         input: Self::Input,
-        _event_stream: ToolCallEventStream,
+        event_stream: ToolCallEventStream,
         cx: &mut App,
     ) -> Task<Result<String>> {
         let project = self.project.clone();
         let input_paths = input.paths;
+        let dry_run = event_stream.dry_run();
 
         cx.spawn(async move |cx| {
             let mut buffers_to_reload: FxHashSet<Entity<Buffer>> = FxHashSet::default();
@@ -119,7 +129,9 @@ impl AgentTool for RestoreFileFromDiskTool {
                 };
 
                 if is_dirty {
-                    buffers_to_reload.insert(buffer);
+                    if !dry_run {
+                        buffers_to_reload.insert(buffer);
+                    }
                     restored_paths.push(path);
                 } else {
                     clean_paths.push(path);
@@ -145,7 +157,12 @@ impl AgentTool for RestoreFileFromDiskTool {
 
             let mut lines: Vec<String> = Vec::new();
 
-            if !restored_paths.is_empty() {
+            if dry_run {
+                lines.push(DRY_RUN_NOTE.to_string());
+                if !restored_paths.is_empty() {
+                    lines.push(format!("Would restore {} file(s).", restored_paths.len()));
+                }
+            } else if !restored_paths.is_empty() {
                 lines.push(format!("Restored {} file(s).", restored_paths.len()));
             }
             if !clean_paths.is_empty() {
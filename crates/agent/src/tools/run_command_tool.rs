@@ -0,0 +1,210 @@
+use super::support::DRY_RUN_NOTE;
+use super::terminal_tool::working_dir;
+use crate::{AgentTool, ThreadEnvironment, ToolCallEventStream};
+use agent_client_protocol as acp;
+use anyhow::Result;
+use futures::FutureExt as _;
+use gpui::{App, Entity, SharedString, Task};
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::Project;
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use std::{rc::Rc, sync::Arc, time::Duration};
+use util::markdown::MarkdownInlineCode;
+
+const COMMAND_OUTPUT_LIMIT: u64 = 16 * 1024;
+const DEFAULT_TIMEOUT_MS: u64 = 2 * 60 * 1000;
+const MAX_TIMEOUT_MS: u64 = 10 * 60 * 1000;
+const OUTPUT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Runs a shell command in a project root directory and returns its exit code and output.
+///
+/// - The command runs in the user's shell, in a new process each time, so no state carries over between calls.
+/// - stdout and stderr are combined, in the order they were written. Only the first 16KiB of output is kept.
+/// - The command is killed if it runs longer than `timeout_ms`, which defaults to 2 minutes. Never run commands that don't exit on their own, such as servers or file watchers.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RunCommandToolInput {
+    /// The command to run.
+    pub command: String,
+    /// The project root directory to run the command in. Can be omitted when the project has a
+    /// single root directory.
+    #[serde(default)]
+    pub cd: Option<String>,
+    /// Optional maximum runtime in milliseconds, up to 10 minutes. Defaults to 2 minutes.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RunCommandToolOutput {
+    pub command: String,
+    /// The command's exit code. Missing when it was killed or terminated by a signal.
+    pub exit_code: Option<u32>,
+    /// Whether the command was killed for running longer than its timeout.
+    pub timed_out: bool,
+    /// The combined stdout and stderr.
+    pub output: String,
+    /// Whether `output` was cut short because the command wrote too much.
+    pub truncated: bool,
+    /// Whether the command wasn't run because this was a dry run.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl From<RunCommandToolOutput> for LanguageModelToolResultContent {
+    fn from(output: RunCommandToolOutput) -> Self {
+        if output.dry_run {
+            return format!("{DRY_RUN_NOTE} Would run \"{}\".", output.command).into();
+        }
+        let status = match (output.timed_out, output.exit_code) {
+            (true, _) => "timed out and was killed".to_string(),
+            (false, Some(0)) => "succeeded".to_string(),
+            (false, Some(exit_code)) => format!("failed with exit code {exit_code}"),
+            (false, None) => "was interrupted".to_string(),
+        };
+        let content = output.output.trim();
+        let mut text = format!("Command \"{}\" {status}.", output.command);
+        if !content.is_empty() {
+            if output.truncated {
+                text.push_str(" Its output was too long, so only the beginning is shown.");
+            }
+            text.push_str(&format!("\n\n```\n{content}\n```"));
+        }
+        text.into()
+    }
+}
+
+pub struct RunCommandTool {
+    project: Entity<Project>,
+    environment: Rc<dyn ThreadEnvironment>,
+}
+
+impl RunCommandTool {
+    pub fn new(project: Entity<Project>, environment: Rc<dyn ThreadEnvironment>) -> Self {
+        Self {
+            project,
+            environment,
+        }
+    }
+}
+
+impl AgentTool for RunCommandTool {
+    type Input = RunCommandToolInput;
+    type Output = RunCommandToolOutput;
+
+    fn name() -> &'static str {
+        "run_command"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Execute
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => {
+                let first_line = input.command.lines().next().unwrap_or_default();
+                format!("Run {}", MarkdownInlineCode(first_line)).into()
+            }
+            Err(_) => "Run command".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let working_dir = match working_dir(input.cd.as_deref().unwrap_or("."), &self.project, cx) {
+            Ok(dir) => dir,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let timeout = Duration::from_millis(
+            input
+                .timeout_ms
+                .unwrap_or(DEFAULT_TIMEOUT_MS)
+                .min(MAX_TIMEOUT_MS),
+        );
+        if event_stream.dry_run() {
+            return Task::ready(Ok(RunCommandToolOutput {
+                command: input.command,
+                exit_code: None,
+                timed_out: false,
+                output: String::new(),
+                truncated: false,
+                dry_run: true,
+            }));
+        }
+
+        let authorize = event_stream.authorize(self.initial_title(Ok(input.clone()), cx), cx);
+        cx.spawn(async move |cx| {
+            authorize.await?;
+
+            let terminal = self
+                .environment
+                .create_terminal(
+                    input.command.clone(),
+                    working_dir,
+                    Some(COMMAND_OUTPUT_LIMIT),
+                    cx,
+                )
+                .await?;
+            let terminal_id = terminal.id(cx)?;
+            event_stream.update_fields(acp::ToolCallUpdateFields::new().content(vec![
+                acp::ToolCallContent::Terminal(acp::Terminal::new(terminal_id)),
+            ]));
+
+            let wait_for_exit = terminal.wait_for_exit(cx)?;
+            let mut exited = wait_for_exit.clone().fuse();
+            let mut deadline = smol::Timer::after(timeout).fuse();
+            let mut streamed_len = 0;
+            let mut timed_out = false;
+            let exit_status = loop {
+                let mut poll = smol::Timer::after(OUTPUT_POLL_INTERVAL).fuse();
+                futures::select! {
+                    status = exited => break status,
+                    _ = deadline => {
+                        terminal.kill(cx)?;
+                        timed_out = true;
+                        break wait_for_exit.await;
+                    }
+                    _ = poll => {
+                        let output = terminal.current_output(cx)?.output;
+                        if let Some(chunk) = output.get(streamed_len..)
+                            && !chunk.is_empty()
+                        {
+                            event_stream.update_fields(
+                                acp::ToolCallUpdateFields::new()
+                                    .raw_output(serde_json::json!({ "chunk": chunk })),
+                            );
+                            streamed_len = output.len();
+                        }
+                    }
+                }
+            };
+
+            let output = terminal.current_output(cx)?;
+            Ok(RunCommandToolOutput {
+                command: input.command,
+                exit_code: exit_status.exit_code,
+                timed_out,
+                output: output.output,
+                truncated: output.truncated,
+                dry_run: false,
+            })
+        })
+    }
+}
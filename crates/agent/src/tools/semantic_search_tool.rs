@@ -0,0 +1,728 @@
+use crate::{AgentTool, ContextualAnchor, ToolCallEventStream};
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use collections::HashMap;
+use futures::{FutureExt as _, future::Shared};
+use gpui::{App, AsyncApp, Entity, EntityId, Global, SharedString, Subscription, Task};
+use language::{Point, ToOffset as _};
+use language_model::LanguageModelToolResultContent;
+use project::{Project, ProjectPath, WorktreeSettings};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use settings::{Settings as _, SettingsStore};
+use std::{fmt::Write as _, ops::Range, path::PathBuf, sync::Arc};
+use util::{ResultExt as _, paths::PathMatcher, rel_path::RelPath};
+
+/// Searches the project for code related to a natural-language description, such as "where do we debounce keystrokes", and returns the best matching chunks ranked by relevance.
+///
+/// - Use this tool when you don't know the names of the symbols involved. Prefer `grep` when you know an exact identifier or string.
+/// - Identifiers are split into words (e.g. `debounce_keystroke` and `debounceKeystroke` both match "debounce keystrokes"), so describe the behavior in plain words.
+/// - Each result includes an anchor that can be passed to `goto_definition_by_context` or `find_references_by_context`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SemanticSearchToolInput {
+    /// A natural-language description of the code to find.
+    pub query: String,
+    /// A glob pattern for the paths of files to include in the search, matched against the full path including the project root directory (e.g. "backend/**/*.rs").
+    /// If omitted, all files in the project will be searched.
+    pub include_pattern: Option<String>,
+}
+
+/// How many ranked chunks are returned.
+const MAX_RESULTS: usize = 8;
+/// Chunks are windows of this many lines, overlapping by half, so that code near a window's
+/// boundary is still seen together with its surroundings.
+const CHUNK_LINES: usize = 30;
+/// Larger files are usually generated or data, and would dominate the index.
+const MAX_FILE_SIZE: u64 = 512 * 1024;
+/// How many lines around a chunk's best line are shown in the output.
+const PREVIEW_LINES: usize = 12;
+
+/// Words too common in natural-language queries to say anything about the code being sought.
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "code", "do", "does", "for", "from", "how",
+    "in", "is", "it", "of", "on", "or", "that", "the", "this", "to", "we", "what", "when", "where",
+    "which", "who", "why", "with",
+];
+
+pub struct SemanticSearchTool {
+    project: Entity<Project>,
+}
+
+impl SemanticSearchTool {
+    pub fn new(project: Entity<Project>) -> Self {
+        Self { project }
+    }
+}
+
+impl AgentTool for SemanticSearchTool {
+    type Input = SemanticSearchToolInput;
+    type Output = LanguageModelToolResultContent;
+
+    fn name() -> &'static str {
+        "semantic_search"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Search
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn input_examples() -> Vec<Self::Input> {
+        vec![SemanticSearchToolInput {
+            query: "where do we debounce keystrokes".into(),
+            include_pattern: Some("project/src/**/*.rs".into()),
+        }]
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => format!("Search for \"{}\"", input.query).into(),
+            Err(_) => "Semantic search".into(),
+        }
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        _event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let query_terms = query_terms(&input.query);
+        if query_terms.is_empty() {
+            return Task::ready(Err(anyhow!(
+                "The query has no searchable words. Describe the code you're looking for."
+            )));
+        }
+
+        let path_style = self.project.read(cx).path_style(cx);
+        let include_matcher = match &input.include_pattern {
+            Some(pattern) => match PathMatcher::new([pattern], path_style) {
+                Ok(matcher) => Some(matcher),
+                Err(error) => {
+                    return Task::ready(Err(anyhow!("invalid include glob pattern: {error}")));
+                }
+            },
+            None => None,
+        };
+
+        let index = match ChunkIndexCache::index(&self.project, cx) {
+            Ok(index) => index,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let project = self.project.clone();
+        cx.spawn(async move |cx| {
+            let index = index.await;
+            let results = cx
+                .background_spawn({
+                    let index = index.clone();
+                    let query_terms = query_terms.clone();
+                    async move { index.search(&query_terms, include_matcher.as_ref()) }
+                })
+                .await;
+            if results.is_empty() {
+                return Ok(LanguageModelToolResultContent::Text(
+                    "No code matched the query. Try describing it with other words.".into(),
+                ));
+            }
+
+            let mut output = String::new();
+            for (rank, result) in results.iter().enumerate() {
+                let anchor = result_anchor(&project, &index, result, &query_terms, cx).await;
+                result
+                    .render(&index, rank + 1, anchor.as_ref(), &mut output)
+                    .log_err();
+            }
+            Ok(LanguageModelToolResultContent::Text(output.into()))
+        })
+    }
+}
+
+type ChunkIndexTask = Shared<Task<Arc<ChunkIndex>>>;
+
+/// The chunk index of each project, so that searching again doesn't read and split every file in
+/// the project again.
+///
+/// An index is dropped as soon as a worktree of its project changes, or the settings do, since
+/// they decide which files are private or excluded. The next search builds it again.
+#[derive(Default)]
+struct ChunkIndexCache {
+    projects: HashMap<EntityId, CachedChunkIndex>,
+}
+
+struct CachedChunkIndex {
+    index: Option<ChunkIndexTask>,
+    _subscriptions: [Subscription; 3],
+}
+
+impl Global for ChunkIndexCache {}
+
+impl ChunkIndexCache {
+    /// Returns the index of `project`'s files, building it unless it's cached or being built.
+    fn index(project: &Entity<Project>, cx: &mut App) -> Result<ChunkIndexTask> {
+        let project_id = project.entity_id();
+        if let Some(index) = cx
+            .default_global::<Self>()
+            .projects
+            .get(&project_id)
+            .and_then(|cached| cached.index.clone())
+        {
+            return Ok(index);
+        }
+
+        let files = indexed_files(project.read(cx), cx)?;
+        let fs = project.read(cx).fs().clone();
+        let index = cx
+            .background_spawn(async move {
+                let mut index = ChunkIndex::default();
+                for file in files {
+                    // Binary and non-UTF-8 files can't be loaded as text, and aren't worth
+                    // searching.
+                    if let Ok(text) = fs.load(&file.abs_path).await {
+                        index.add_file(file, &text);
+                    }
+                }
+                Arc::new(index)
+            })
+            .shared();
+
+        if let Some(cached) = cx.default_global::<Self>().projects.get_mut(&project_id) {
+            cached.index = Some(index.clone());
+            return Ok(index);
+        }
+        let subscriptions = [
+            cx.subscribe(project, move |_, event: &project::Event, cx| {
+                if matches!(
+                    event,
+                    project::Event::WorktreeAdded(_)
+                        | project::Event::WorktreeRemoved(_)
+                        | project::Event::WorktreeUpdatedEntries(..)
+                ) {
+                    cx.default_global::<Self>().invalidate(project_id);
+                }
+            }),
+            cx.observe_global::<SettingsStore>(move |cx| {
+                cx.default_global::<Self>().invalidate(project_id);
+            }),
+            cx.observe_release(project, move |_, cx| {
+                cx.default_global::<Self>().projects.remove(&project_id);
+            }),
+        ];
+        cx.default_global::<Self>().projects.insert(
+            project_id,
+            CachedChunkIndex {
+                index: Some(index.clone()),
+                _subscriptions: subscriptions,
+            },
+        );
+        Ok(index)
+    }
+
+    fn invalidate(&mut self, project_id: EntityId) {
+        if let Some(cached) = self.projects.get_mut(&project_id) {
+            cached.index = None;
+        }
+    }
+}
+
+/// Lists the files of `project` worth indexing: those that aren't too large, ignored, excluded
+/// or private.
+fn indexed_files(project: &Project, cx: &App) -> Result<Vec<IndexedFile>> {
+    let mut files = Vec::new();
+    for worktree in project.visible_worktrees(cx) {
+        let worktree = worktree.read(cx);
+        if !worktree.is_local() {
+            return Err(anyhow!(
+                "Semantic search is only available in local projects."
+            ));
+        }
+        for entry in worktree.files(false, 0) {
+            if entry.size > MAX_FILE_SIZE {
+                continue;
+            }
+            let settings = WorktreeSettings::get(
+                Some(settings::SettingsLocation {
+                    worktree_id: worktree.id(),
+                    path: &entry.path,
+                }),
+                cx,
+            );
+            if settings.is_path_excluded(&entry.path) || settings.is_path_private(&entry.path) {
+                continue;
+            }
+            let full_path = worktree.root_name().join(&entry.path);
+            files.push(IndexedFile {
+                display_path: full_path.display(project.path_style(cx)).into(),
+                full_path,
+                project_path: ProjectPath {
+                    worktree_id: worktree.id(),
+                    path: entry.path.clone(),
+                },
+                abs_path: worktree.absolutize(&entry.path),
+            });
+        }
+    }
+    Ok(files)
+}
+
+struct IndexedFile {
+    display_path: Arc<str>,
+    /// The path including the worktree's root directory, which include patterns are matched
+    /// against.
+    full_path: Arc<RelPath>,
+    project_path: ProjectPath,
+    abs_path: PathBuf,
+}
+
+/// A chunk of a file and the terms it contains, for BM25 ranking.
+struct Chunk {
+    /// The index of the chunk's file in [`ChunkIndex::files`].
+    file_ix: usize,
+    /// 0-based row of the chunk's first line.
+    start_row: usize,
+    lines: Vec<String>,
+    term_counts: HashMap<String, u32>,
+    term_total: u32,
+}
+
+#[derive(Default)]
+struct ChunkIndex {
+    files: Vec<IndexedFile>,
+    chunks: Vec<Chunk>,
+    /// How many chunks contain each term.
+    document_frequencies: HashMap<String, u32>,
+}
+
+struct SearchResult {
+    chunk_ix: usize,
+    score: f32,
+    best_row: usize,
+}
+
+impl ChunkIndex {
+    fn add_file(&mut self, file: IndexedFile, text: &str) {
+        let file_ix = self.files.len();
+        self.files.push(file);
+        let lines = text.lines().collect::<Vec<_>>();
+        let step = CHUNK_LINES / 2;
+        let mut start_row = 0;
+        while start_row < lines.len() {
+            let end_row = (start_row + CHUNK_LINES).min(lines.len());
+            let mut term_counts = HashMap::default();
+            for line in &lines[start_row..end_row] {
+                for term in line_terms(line) {
+                    *term_counts.entry(term).or_insert(0) += 1;
+                }
+            }
+            if !term_counts.is_empty() {
+                for term in term_counts.keys() {
+                    *self.document_frequencies.entry(term.clone()).or_insert(0) += 1;
+                }
+                self.chunks.push(Chunk {
+                    file_ix,
+                    start_row,
+                    lines: lines[start_row..end_row]
+                        .iter()
+                        .map(|line| line.to_string())
+                        .collect(),
+                    term_total: term_counts.values().sum(),
+                    term_counts,
+                });
+            }
+            if end_row == lines.len() {
+                break;
+            }
+            start_row += step;
+        }
+    }
+
+    fn idf(&self, term: &str) -> f32 {
+        let chunk_count = self.chunks.len() as f32;
+        let frequency = self.document_frequencies.get(term).copied().unwrap_or(0) as f32;
+        ((chunk_count - frequency + 0.5) / (frequency + 0.5) + 1.0).ln()
+    }
+
+    /// Ranks the chunks of files matching `include_matcher` by BM25 against `query_terms`,
+    /// skipping chunks that overlap a better one.
+    fn search(
+        &self,
+        query_terms: &[String],
+        include_matcher: Option<&PathMatcher>,
+    ) -> Vec<SearchResult> {
+        const K1: f32 = 1.2;
+        const B: f32 = 0.75;
+
+        if self.chunks.is_empty() {
+            return Vec::new();
+        }
+        let average_length = self
+            .chunks
+            .iter()
+            .map(|chunk| chunk.term_total as f32)
+            .sum::<f32>()
+            / self.chunks.len() as f32;
+        let idfs = query_terms
+            .iter()
+            .map(|term| self.idf(term))
+            .collect::<Vec<_>>();
+
+        let mut scored = self
+            .chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| {
+                include_matcher
+                    .is_none_or(|matcher| matcher.is_match(&self.files[chunk.file_ix].full_path))
+            })
+            .filter_map(|(chunk_ix, chunk)| {
+                let length_norm = 1.0 - B + B * chunk.term_total as f32 / average_length;
+                let score = query_terms
+                    .iter()
+                    .zip(&idfs)
+                    .map(|(term, idf)| {
+                        let count = chunk.term_counts.get(term).copied().unwrap_or(0) as f32;
+                        idf * count * (K1 + 1.0) / (count + K1 * length_norm)
+                    })
+                    .sum::<f32>();
+                (score > 0.0).then_some((chunk_ix, score))
+            })
+            .collect::<Vec<_>>();
+        scored.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+        let mut results: Vec<SearchResult> = Vec::new();
+        for (chunk_ix, score) in scored {
+            let chunk = &self.chunks[chunk_ix];
+            let overlaps_better_result = results.iter().any(|result| {
+                let other = &self.chunks[result.chunk_ix];
+                other.file_ix == chunk.file_ix
+                    && other.start_row < chunk.start_row + chunk.lines.len()
+                    && chunk.start_row < other.start_row + other.lines.len()
+            });
+            if overlaps_better_result {
+                continue;
+            }
+            let best_row = chunk.start_row + best_line(chunk, query_terms, &idfs);
+            results.push(SearchResult {
+                chunk_ix,
+                score,
+                best_row,
+            });
+            if results.len() == MAX_RESULTS {
+                break;
+            }
+        }
+        results
+    }
+}
+
+/// Builds an anchor for the identifier on `result`'s best line that matches the query, minimized
+/// against the file as it is now. Returns `None` when the line has no such identifier, or the
+/// file has changed there since it was indexed.
+async fn result_anchor(
+    project: &Entity<Project>,
+    index: &ChunkIndex,
+    result: &SearchResult,
+    query_terms: &[String],
+    cx: &mut AsyncApp,
+) -> Option<ContextualAnchor> {
+    let chunk = &index.chunks[result.chunk_ix];
+    let file = &index.files[chunk.file_ix];
+    let line = &chunk.lines[result.best_row - chunk.start_row];
+    let token_range = identifier_ranges(line).find(|range| {
+        identifier_terms(&line[range.clone()]).any(|term| query_terms.contains(&term))
+    })?;
+    let buffer = project
+        .update(cx, |project, cx| {
+            project.open_buffer(file.project_path.clone(), cx)
+        })
+        .ok()?
+        .await
+        .log_err()?;
+    buffer
+        .read_with(cx, |buffer, _| {
+            let snapshot = buffer.snapshot();
+            let row = u32::try_from(result.best_row).ok()?;
+            if row > snapshot.max_point().row || token_range.end > snapshot.line_len(row) as usize {
+                return None;
+            }
+            let line_start = Point::new(row, 0).to_offset(&snapshot);
+            let range = line_start + token_range.start..line_start + token_range.end;
+            if snapshot.text_for_range(range.clone()).collect::<String>() != line[token_range] {
+                return None;
+            }
+            ContextualAnchor::for_range(file.display_path.to_string(), &snapshot, range)
+        })
+        .ok()?
+}
+
+/// Returns the index of the line in `chunk` with the most informative query terms.
+fn best_line(chunk: &Chunk, query_terms: &[String], idfs: &[f32]) -> usize {
+    let mut best = (0, 0.0);
+    for (ix, line) in chunk.lines.iter().enumerate() {
+        let terms = line_terms(line).collect::<Vec<_>>();
+        let score = query_terms
+            .iter()
+            .zip(idfs)
+            .filter(|(term, _)| terms.contains(term))
+            .map(|(_, idf)| idf)
+            .sum::<f32>();
+        if score > best.1 {
+            best = (ix, score);
+        }
+    }
+    best.0
+}
+
+impl SearchResult {
+    fn render(
+        &self,
+        index: &ChunkIndex,
+        rank: usize,
+        anchor: Option<&ContextualAnchor>,
+        output: &mut String,
+    ) -> Result<()> {
+        let chunk = &index.chunks[self.chunk_ix];
+        let best_ix = self.best_row - chunk.start_row;
+        let preview_start = best_ix.saturating_sub(PREVIEW_LINES / 2);
+        let preview_end = (preview_start + PREVIEW_LINES).min(chunk.lines.len());
+        writeln!(
+            output,
+            "## {rank}. {} [L{}-{}] (score {:.2})\n",
+            index.files[chunk.file_ix].display_path,
+            chunk.start_row + preview_start + 1,
+            chunk.start_row + preview_end,
+            self.score
+        )?;
+
+        if let Some(anchor) = anchor {
+            writeln!(output, "Anchor: {}\n", serde_json::to_string(anchor)?)?;
+        }
+
+        writeln!(output, "```")?;
+        for line in &chunk.lines[preview_start..preview_end] {
+            writeln!(output, "{line}")?;
+        }
+        writeln!(output, "```\n")?;
+        Ok(())
+    }
+}
+
+/// Splits a natural-language query into normalized terms, without stop words or duplicates.
+fn query_terms(query: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    for term in identifiers(query).flat_map(identifier_terms) {
+        if !STOP_WORDS.contains(&term.as_str()) && !terms.contains(&term) {
+            terms.push(term);
+        }
+    }
+    terms
+}
+
+fn line_terms(line: &str) -> impl Iterator<Item = String> + '_ {
+    identifiers(line).flat_map(identifier_terms)
+}
+
+fn identifiers(text: &str) -> impl Iterator<Item = &str> {
+    identifier_ranges(text).map(|range| &text[range])
+}
+
+/// The byte ranges of the runs of alphanumeric characters and underscores in `text`.
+fn identifier_ranges(text: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut start = None;
+    text.char_indices()
+        .chain([(text.len(), ' ')])
+        .filter_map(move |(offset, character)| {
+            if character.is_alphanumeric() || character == '_' {
+                start.get_or_insert(offset);
+                None
+            } else {
+                start.take().map(|start| start..offset)
+            }
+        })
+}
+
+/// Splits an identifier at underscores and case changes (e.g. `debounceKeystrokes` into
+/// `debounce` and `keystroke`) and normalizes each word.
+fn identifier_terms(identifier: &str) -> impl Iterator<Item = String> + '_ {
+    let mut words = Vec::new();
+    for part in identifier.split('_').filter(|part| !part.is_empty()) {
+        let mut word_start = 0;
+        let characters = part.char_indices().collect::<Vec<_>>();
+        for window in characters.windows(2) {
+            let [(_, previous), (offset, current)] = window else {
+                continue;
+            };
+            if previous.is_lowercase() && current.is_uppercase() {
+                words.push(&part[word_start..*offset]);
+                word_start = *offset;
+            }
+        }
+        words.push(&part[word_start..]);
+    }
+    words
+        .into_iter()
+        .filter(|word| word.chars().any(char::is_alphabetic))
+        .map(|word| stem(&word.to_lowercase()))
+}
+
+/// Strips common English suffixes so that e.g. "keystrokes" matches `keystroke` and "debouncing"
+/// matches `debounce`. Words are only ever compared with other stemmed words, so the stems don't
+/// need to be real words.
+fn stem(word: &str) -> String {
+    for suffix in ["ing", "ed", "es", "s", "e"] {
+        if let Some(stem) = word.strip_suffix(suffix)
+            && stem.len() >= 3
+        {
+            return stem.to_string();
+        }
+    }
+    word.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, run_tool};
+    use gpui::TestAppContext;
+    use serde_json::json;
+    use util::path;
+
+    #[test]
+    fn test_query_terms_match_identifier_terms() {
+        assert_eq!(
+            query_terms("Where do we debounce keystrokes?"),
+            vec!["debounc", "keystrok"]
+        );
+        assert_eq!(
+            identifier_terms("debounceKeystroke").collect::<Vec<_>>(),
+            vec!["debounc", "keystrok"]
+        );
+        assert_eq!(
+            identifier_terms("DEBOUNCE_keystrokes").collect::<Vec<_>>(),
+            vec!["debounc", "keystrok"]
+        );
+        assert_eq!(
+            identifier_terms("debouncing").collect::<Vec<_>>(),
+            vec!["debounc"]
+        );
+    }
+
+    #[gpui::test]
+    async fn test_semantic_search_ranks_matching_chunk_first(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "input.rs": "pub struct Input;\n\nimpl Input {\n    fn debounce_keystroke(&mut self, keystroke: Keystroke) {\n        self.pending = Some(keystroke);\n    }\n}\n",
+                    "render.rs": "fn render_frame() {\n    paint();\n}\n",
+                    "keys.rs": "// Keystroke names.\nconst ESCAPE: &str = \"escape\";\n"
+                }
+            }),
+            cx,
+        )
+        .await;
+        let tool = Arc::new(SemanticSearchTool::new(test_project.project.clone()));
+
+        let (output, _) = run_tool(
+            tool.clone(),
+            SemanticSearchToolInput {
+                query: "where do we debounce keystrokes".into(),
+                include_pattern: None,
+            },
+            cx,
+        )
+        .await;
+        let LanguageModelToolResultContent::Text(text) = output.expect("tool failed") else {
+            panic!("expected text output");
+        };
+        assert!(text.starts_with("## 1. root/src/input.rs [L1-7]"), "{text}");
+        assert!(
+            text.contains(
+                r#"Anchor: {"path":"root/src/input.rs","context":"fn debounce_keystroke(","token":"debounce_keystroke"}"#
+            ),
+            "{text}"
+        );
+        assert!(!text.contains("render.rs"), "{text}");
+
+        let (output, _) = run_tool(
+            tool,
+            SemanticSearchToolInput {
+                query: "keystrokes".into(),
+                include_pattern: Some("root/src/keys.rs".into()),
+            },
+            cx,
+        )
+        .await;
+        let LanguageModelToolResultContent::Text(text) = output.expect("tool failed") else {
+            panic!("expected text output");
+        };
+        assert!(text.starts_with("## 1. root/src/keys.rs"), "{text}");
+        assert!(!text.contains("input.rs"), "{text}");
+    }
+
+    async fn search(
+        tool: Arc<SemanticSearchTool>,
+        query: &str,
+        cx: &mut TestAppContext,
+    ) -> Arc<str> {
+        let input = SemanticSearchToolInput {
+            query: query.into(),
+            include_pattern: None,
+        };
+        let (output, _) = run_tool(tool, input, cx).await;
+        let LanguageModelToolResultContent::Text(text) = output.expect("tool failed") else {
+            panic!("expected text output");
+        };
+        text
+    }
+
+    #[gpui::test]
+    async fn test_semantic_search_index_is_rebuilt_when_files_change(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project = ToolTestProject::new(
+            json!({
+                "src": {
+                    "input.rs": "fn debounce_keystroke() {}\n",
+                }
+            }),
+            cx,
+        )
+        .await;
+        let project_id = test_project.project.entity_id();
+        let tool = Arc::new(SemanticSearchTool::new(test_project.project.clone()));
+        let is_cached = |cx: &mut TestAppContext| {
+            cx.update(|cx| {
+                cx.default_global::<ChunkIndexCache>()
+                    .projects
+                    .get(&project_id)
+                    .is_some_and(|cached| cached.index.is_some())
+            })
+        };
+
+        let text = search(tool.clone(), "debounce", cx).await;
+        assert!(text.contains("input.rs"), "{text}");
+        assert!(!text.contains("render.rs"), "{text}");
+        cx.run_until_parked();
+        assert!(is_cached(cx));
+
+        test_project
+            .fs
+            .insert_file(
+                path!("/root/src/render.rs"),
+                "fn debounce_render() {}\n".into(),
+            )
+            .await;
+        cx.run_until_parked();
+        assert!(!is_cached(cx));
+
+        let text = search(tool.clone(), "debounce", cx).await;
+        assert!(text.contains("render.rs"), "{text}");
+        assert!(is_cached(cx));
+    }
+}
@@ -0,0 +1,252 @@
+//! The steps that tools looking something up at a [`ContextualAnchor`] share: checking that the
+//! anchor's file may be read, opening it and resolving the anchor, showing excerpts of what was
+//! found, and reporting locations for the user to follow. Also what mutating tools share to honor
+//! [`ToolCallEventStream::dry_run`].
+
+use super::{
+    AnchorMatch, AnchorMemory, ContextualAnchor, TokenSyntax, display_project_path,
+    open_anchor_buffer, resolve_project_path,
+};
+use crate::ToolCallEventStream;
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use gpui::{App, AsyncApp, Entity};
+use language::{Buffer, BufferSnapshot, Point};
+use project::{Location, Project, ProjectPath, WorktreeSettings};
+use settings::Settings as _;
+use std::ops::Range;
+use text::OffsetRangeExt as _;
+
+/// An anchor whose file the agent may read, to be opened with [`AnchorTarget::open`].
+pub(crate) struct AnchorTarget {
+    /// The anchor, with its handle expanded.
+    pub anchor: ContextualAnchor,
+    pub project_path: ProjectPath,
+    pub display_path: String,
+}
+
+impl AnchorTarget {
+    /// Expands `anchor`'s handle, validates it, and checks that its file doesn't match the
+    /// `file_scan_exclusions` or `private_files` settings, before anything is opened.
+    pub(crate) fn new(
+        project: &Project,
+        anchor_memory: &AnchorMemory,
+        anchor: ContextualAnchor,
+        cx: &App,
+    ) -> Result<Self> {
+        let anchor = anchor_memory.expand(anchor)?;
+        anchor.validate_basic()?;
+        let project_path = resolve_project_path(project, &anchor.path, cx)?;
+
+        // Mirror read_file's checks, so that no tool reads what read_file wouldn't.
+        let global_settings = WorktreeSettings::get_global(cx);
+        if global_settings.is_path_excluded(&project_path.path) {
+            return Err(anyhow!(
+                "Cannot read file because its path matches global file_scan_exclusions: {}",
+                &anchor.path
+            ));
+        }
+        if global_settings.is_path_private(&project_path.path) {
+            return Err(anyhow!(
+                "Cannot read file because its path matches global private_files: {}",
+                &anchor.path
+            ));
+        }
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path) {
+            return Err(anyhow!(
+                "Cannot read file because its path matches worktree file_scan_exclusions: {}",
+                &anchor.path
+            ));
+        }
+        if worktree_settings.is_path_private(&project_path.path) {
+            return Err(anyhow!(
+                "Cannot read file because its path matches worktree private_files: {}",
+                &anchor.path
+            ));
+        }
+
+        let display_path =
+            display_project_path(project, &project_path, cx).unwrap_or_else(|| anchor.path.clone());
+        Ok(Self {
+            anchor,
+            project_path,
+            display_path,
+        })
+    }
+
+    /// Opens the anchor's file, at the anchor's `revision` if it has one, and resolves the anchor
+    /// to a token with the given `syntax`.
+    pub(crate) async fn open(
+        self,
+        project: &Entity<Project>,
+        syntax: Option<TokenSyntax>,
+        cx: &mut AsyncApp,
+    ) -> Result<AnchorResolution> {
+        let anchor_buffer = open_anchor_buffer(
+            project,
+            &self.project_path,
+            self.anchor.revision.clone(),
+            &self.display_path,
+            cx,
+        )
+        .await?;
+        let offset = match anchor_buffer.resolve(&self.anchor, &self.display_path, syntax, cx)? {
+            AnchorMatch::Unique(token_match) => token_match.range.start,
+            AnchorMatch::Ambiguous(matches) => {
+                return Ok(AnchorResolution::Ambiguous(
+                    self.anchor.describe_ambiguous_matches(
+                        &anchor_buffer.snapshot,
+                        &matches,
+                        &self.display_path,
+                    ),
+                ));
+            }
+        };
+        Ok(AnchorResolution::Resolved(ResolvedToken {
+            anchor: self.anchor,
+            display_path: self.display_path,
+            buffer: anchor_buffer.buffer,
+            snapshot: anchor_buffer.snapshot,
+            unsaved: anchor_buffer.unsaved,
+            offset,
+        }))
+    }
+}
+
+/// What an [`AnchorTarget`] resolved to.
+pub(crate) enum AnchorResolution {
+    Resolved(ResolvedToken),
+    /// The anchor matched several tokens, which this describes for the model to pick one.
+    Ambiguous(String),
+}
+
+/// The token an anchor resolved to, in the buffer it was opened in.
+pub(crate) struct ResolvedToken {
+    pub anchor: ContextualAnchor,
+    pub display_path: String,
+    /// The buffer the anchor resolved in, which is detached from the project when the anchor has
+    /// a `revision`.
+    pub buffer: Entity<Buffer>,
+    pub snapshot: BufferSnapshot,
+    /// Whether the buffer has edits that aren't saved to disk.
+    pub unsaved: bool,
+    /// Where the token starts in the buffer.
+    pub offset: usize,
+}
+
+/// Some lines of a buffer around a result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Excerpt {
+    /// The 0-based first row of the result's scope.
+    pub start_row: u32,
+    /// The 0-based last row of the result's scope.
+    pub end_row: u32,
+    /// The excerpt's text, which is only the start of the scope when the scope is longer than
+    /// allowed.
+    pub text: String,
+}
+
+impl Excerpt {
+    /// The excerpt around `range`: `context_lines` before and after it or, by default, the largest
+    /// syntax node around it that spans at most `max_scope_lines` lines.
+    pub(crate) fn around(
+        snapshot: &BufferSnapshot,
+        range: Range<Point>,
+        context_lines: Option<u32>,
+        max_scope_lines: usize,
+    ) -> Self {
+        let (start_row, end_row, text_rows) = if let Some(context_lines) = context_lines {
+            let start_row = range.start.row.saturating_sub(context_lines);
+            let end_row = (range.end.row + context_lines).min(snapshot.max_point().row);
+            (start_row, end_row, start_row..end_row)
+        } else if let Some(node) = snapshot.syntax_ancestor(range.clone()) {
+            // Climb to the largest ancestor within max_scope_lines, so we return the most useful
+            // context that's still small enough.
+            let mut candidate = node;
+            while let Some(parent) = candidate.parent() {
+                let parent_range = parent.byte_range().to_point(snapshot);
+                let parent_span_lines = parent_range.end.row.saturating_sub(parent_range.start.row);
+                if parent_span_lines as usize > max_scope_lines {
+                    break;
+                }
+                candidate = parent;
+            }
+            let node_range = candidate.byte_range().to_point(snapshot);
+            let span_lines = node_range.end.row.saturating_sub(node_range.start.row);
+            if span_lines as usize <= max_scope_lines {
+                (
+                    node_range.start.row,
+                    node_range.end.row,
+                    node_range.start.row..node_range.end.row,
+                )
+            } else {
+                // If the syntax node is too large, provide a clamped preview around node start
+                let preview_start_row = node_range.start.row.saturating_sub(10);
+                let preview_end_row = (node_range.start.row + 9).min(snapshot.max_point().row);
+                (
+                    node_range.start.row,
+                    node_range.end.row,
+                    preview_start_row..preview_end_row,
+                )
+            }
+        } else {
+            // fallback to -10..+9 around the range's start
+            let preview_start_row = range.start.row.saturating_sub(10);
+            let preview_end_row = (range.start.row + 9).min(snapshot.max_point().row);
+            (
+                range.start.row,
+                range.end.row,
+                preview_start_row..preview_end_row,
+            )
+        };
+        let text = snapshot
+            .text_for_range(
+                snapshot.anchor_before(Point::new(text_rows.start, 0))
+                    ..snapshot
+                        .anchor_after(Point::new(text_rows.end, snapshot.line_len(text_rows.end))),
+            )
+            .collect::<String>();
+        Self {
+            start_row,
+            end_row,
+            text,
+        }
+    }
+}
+
+/// Reports `locations` as the tool call's locations, so that clients can show and open them.
+/// Locations in buffers without a local file are left out.
+pub(crate) fn report_locations<'a>(
+    event_stream: &ToolCallEventStream,
+    anchor: &ContextualAnchor,
+    locations: impl IntoIterator<Item = &'a Location>,
+    cx: &App,
+) {
+    let tool_call_locations = locations
+        .into_iter()
+        .filter_map(|location| {
+            anchor.tool_call_location(location.buffer.read(cx), location.range.clone(), cx)
+        })
+        .collect::<Vec<_>>();
+    if !tool_call_locations.is_empty() {
+        event_stream.update_fields(acp::ToolCallUpdateFields::new().locations(tool_call_locations));
+    }
+}
+
+/// Starts the result of a mutating tool that ran as a dry run.
+pub(crate) const DRY_RUN_NOTE: &str = "Dry run, so nothing was changed.";
+
+/// The buffer a mutating tool should edit: `buffer` itself or, in a dry run, a branch of it, so
+/// the edits can be diffed and shown without touching the project's buffer.
+pub(crate) fn buffer_to_edit(
+    buffer: &Entity<Buffer>,
+    event_stream: &ToolCallEventStream,
+    cx: &mut AsyncApp,
+) -> Result<Entity<Buffer>> {
+    if event_stream.dry_run() {
+        buffer.update(cx, |buffer, cx| buffer.branch(cx))
+    } else {
+        Ok(buffer.clone())
+    }
+}
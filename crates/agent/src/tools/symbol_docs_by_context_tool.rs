@@ -0,0 +1,640 @@
+use super::{
+    AnchorHandles, AnchorMemory, ContextualAnchor, TokenSyntax, display_project_path,
+    open_buffer_at_revision, resolve_project_path, resolve_unique_in_buffer,
+    warm_up_language_servers,
+};
+use crate::{AgentTool, ToolCallEventStream, ToolPreparation};
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use gpui::{App, Entity, SharedString, Task};
+use language::{BufferSnapshot, Node};
+use language_model::{LanguageModelToolResultContent, LanguageModelToolSchemaFormat};
+use project::{HoverBlockKind, Project, ProjectPath, WorktreeSettings};
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use settings::Settings as _;
+use std::{fmt::Write as _, ops::Range, sync::Arc};
+use text::ToOffset as _;
+
+/// Signatures longer than this are cut off, since they are usually long `where` clauses or
+/// declarations without a body.
+const MAX_SIGNATURE_LINES: usize = 12;
+
+/// Tool: symbol_docs_by_context
+pub struct SymbolDocsByContextTool {
+    project: Entity<Project>,
+    anchor_memory: Entity<AnchorMemory>,
+}
+
+impl SymbolDocsByContextTool {
+    pub fn new(project: Entity<Project>, anchor_memory: Entity<AnchorMemory>) -> Self {
+        Self {
+            project,
+            anchor_memory,
+        }
+    }
+}
+
+/// Where a symbol's documentation was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentationSource {
+    /// The comments immediately preceding the declaration.
+    DocComment,
+    /// The language server's hover, used when the declaration has no doc comment.
+    Hover,
+}
+
+/// The documentation of the symbol that an anchor refers to, as written at its declaration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SymbolDocsByContextToolOutput {
+    /// The path of the file containing the declaration.
+    pub path: String,
+    /// The 1-based line range of the declaration, including its doc comment and attributes.
+    pub start_line: u32,
+    pub end_line: u32,
+    /// The syntax node kind of the declaration (e.g. `function_item` or `struct_item`).
+    pub kind: String,
+    /// The declaration's explicit visibility modifier (e.g. `pub(crate)`), if it has one.
+    pub visibility: Option<String>,
+    /// The attributes, derives or decorators applied to the declaration, in source order.
+    pub attributes: Vec<String>,
+    /// The declaration up to its body.
+    pub signature: String,
+    /// The documentation text, with comment markers removed.
+    pub documentation: Option<String>,
+    pub documentation_source: Option<DocumentationSource>,
+    /// The handle under which the input anchor was remembered.
+    pub handle: String,
+    /// The symbol handle under which the input anchor was remembered, if it is unambiguous.
+    pub symbol_handle: Option<String>,
+}
+
+impl From<SymbolDocsByContextToolOutput> for LanguageModelToolResultContent {
+    fn from(output: SymbolDocsByContextToolOutput) -> Self {
+        let mut text = format!(
+            "{} [L{}-{}] ({})\n\n",
+            output.path, output.start_line, output.end_line, output.kind
+        );
+        if let Some(visibility) = &output.visibility {
+            writeln!(text, "Visibility: `{visibility}`").ok();
+        }
+        if !output.attributes.is_empty() {
+            writeln!(text, "Attributes:").ok();
+            for attribute in &output.attributes {
+                writeln!(text, "- `{attribute}`").ok();
+            }
+        }
+        writeln!(text, "\n```\n{}\n```\n", output.signature).ok();
+        match (&output.documentation, output.documentation_source) {
+            (Some(documentation), Some(DocumentationSource::Hover)) => {
+                writeln!(text, "Documentation (from hover):\n\n{documentation}\n").ok();
+            }
+            (Some(documentation), _) => {
+                writeln!(text, "Documentation:\n\n{documentation}\n").ok();
+            }
+            (None, _) => {
+                writeln!(text, "The declaration has no documentation.\n").ok();
+            }
+        }
+        let handles = AnchorHandles {
+            reference: output.handle,
+            symbol: output.symbol_handle,
+        };
+        text.push_str(&handles.describe());
+        text.into()
+    }
+}
+
+impl AgentTool for SymbolDocsByContextTool {
+    type Input = ContextualAnchor;
+    type Output = SymbolDocsByContextToolOutput;
+
+    fn name() -> &'static str {
+        "symbol_docs_by_context"
+    }
+
+    fn kind() -> acp::ToolKind {
+        acp::ToolKind::Read
+    }
+
+    fn is_experimental() -> bool {
+        true
+    }
+
+    fn output_schema(format: LanguageModelToolSchemaFormat) -> Option<Schema> {
+        Some(language_model::tool_schema::root_schema_for::<Self::Output>(format))
+    }
+
+    fn input_examples() -> Vec<Self::Input> {
+        vec![ContextualAnchor {
+            path: "project/src/main.rs".into(),
+            context: "let config = Config::load(&path)?;".into(),
+            token: "load".into(),
+            index: None,
+            handle: None,
+            revision: None,
+            whole_word: false,
+            approximate_line: None,
+            case_insensitive: false,
+            unicode_normalize: false,
+            end_token: None,
+            end_index: None,
+        }]
+    }
+
+    fn initial_title(
+        &self,
+        input: Result<Self::Input, serde_json::Value>,
+        _cx: &mut App,
+    ) -> SharedString {
+        match input {
+            Ok(input) => match input.handle {
+                Some(handle) => format!("Read documentation for `{handle}`").into(),
+                None => format!("Read documentation for `{}`", input.token).into(),
+            },
+            Err(_) => "Read symbol documentation".into(),
+        }
+    }
+
+    fn prepare(
+        self: Arc<Self>,
+        input: Self::Input,
+        cx: &mut App,
+    ) -> Option<Task<Result<ToolPreparation>>> {
+        let input = self.anchor_memory.read(cx).expand(input).ok()?;
+        if input.revision.is_some() {
+            return None;
+        }
+        warm_up_language_servers(&self.project, &input.path, cx)
+    }
+
+    fn run(
+        self: Arc<Self>,
+        input: Self::Input,
+        _event_stream: ToolCallEventStream,
+        cx: &mut App,
+    ) -> Task<Result<Self::Output>> {
+        let project = self.project.clone();
+        let anchor_memory = self.anchor_memory.clone();
+
+        let input = match anchor_memory.read(cx).expand(input) {
+            Ok(input) => input,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        if let Err(error) = input.validate_basic() {
+            return Task::ready(Err(error));
+        }
+        let project_path = match resolve_project_path(project.read(cx), &input.path, cx) {
+            Ok(project_path) => project_path,
+            Err(error) => return Task::ready(Err(error)),
+        };
+        let worktree_settings = WorktreeSettings::get(Some((&project_path).into()), cx);
+        if worktree_settings.is_path_excluded(&project_path.path)
+            || worktree_settings.is_path_private(&project_path.path)
+        {
+            return Task::ready(Err(anyhow!(
+                "Cannot read file because its path matches the file_scan_exclusions or private_files setting: {}",
+                &input.path
+            )));
+        }
+        let display_path = display_project_path(project.read(cx), &project_path, cx)
+            .unwrap_or_else(|| input.path.clone());
+
+        cx.spawn(async move |cx| {
+            let buffer = if let Some(revision) = input.revision.clone() {
+                cx.update(|cx| open_buffer_at_revision(&project, &project_path, revision, cx))?
+                    .await?
+            } else {
+                project
+                    .update(cx, |project, cx| {
+                        project.open_buffer(project_path.clone(), cx)
+                    })?
+                    .await?
+            };
+            buffer
+                .read_with(cx, |buffer, _| buffer.parsing_idle())?
+                .await;
+
+            let snapshot = buffer.read_with(cx, |buffer, _| buffer.snapshot())?;
+            let token_range = resolve_unique_in_buffer(
+                &input,
+                &snapshot,
+                &display_path,
+                Some(TokenSyntax::Symbol),
+            )?
+            .range;
+            let token_offset = token_range.start;
+
+            let handles =
+                anchor_memory.update(cx, |anchor_memory, _| anchor_memory.remember(&input))?;
+
+            // The anchor usually points at a use of the symbol, so follow it to its declaration,
+            // unless it already is the declaration's name.
+            let (declaration_buffer, declaration_path, name_range) =
+                if declaration_for_name(&snapshot, token_range.clone()).is_some() {
+                    (buffer.clone(), display_path, token_range)
+                } else if let Some(revision) = &input.revision {
+                    anyhow::bail!(
+                        "`{}` is not declared at this anchor, and language servers can't follow it at {revision}. Anchor the declaration's name instead.",
+                        input.token
+                    );
+                } else {
+                    let definitions = project
+                        .update(cx, |project, cx| {
+                            project.definitions(&buffer, token_offset, cx)
+                        })?
+                        .await?
+                        .unwrap_or_default();
+                    let Some(definition) = definitions.into_iter().next() else {
+                        anyhow::bail!(
+                            "No definition found for `{}` (or language server not capable)",
+                            input.token
+                        );
+                    };
+                    let target_buffer = definition.target.buffer;
+                    target_buffer
+                        .read_with(cx, |buffer, _| buffer.parsing_idle())?
+                        .await;
+                    let (path, name_range) = target_buffer.read_with(cx, |buffer, cx| {
+                        let path = buffer.file().and_then(|file| {
+                            display_project_path(
+                                project.read(cx),
+                                &ProjectPath::from_file(file.as_ref(), cx),
+                                cx,
+                            )
+                        });
+                        let snapshot = buffer.snapshot();
+                        let name_range = definition.target.range.start.to_offset(&snapshot)
+                            ..definition.target.range.end.to_offset(&snapshot);
+                        (path, name_range)
+                    })?;
+                    (
+                        target_buffer,
+                        path.unwrap_or_else(|| "<buffer>".to_string()),
+                        name_range,
+                    )
+                };
+
+            let (docs, hover_offset) = declaration_buffer.read_with(cx, |buffer, _| {
+                let snapshot = buffer.snapshot();
+                let declaration = declaration_for_name(&snapshot, name_range.clone())
+                    .ok_or_else(|| {
+                        anyhow!("Couldn't find the declaration of `{}`", input.token)
+                    })?;
+                anyhow::Ok((
+                    SymbolDocs::extract(&snapshot, declaration),
+                    name_range.start,
+                ))
+            })??;
+
+            let mut documentation = docs.doc_comment;
+            let mut documentation_source = documentation
+                .as_ref()
+                .map(|_| DocumentationSource::DocComment);
+            if documentation.is_none() && input.revision.is_none() {
+                let hovers = project
+                    .update(cx, |project, cx| {
+                        project.hover(&declaration_buffer, hover_offset, cx)
+                    })?
+                    .await
+                    .unwrap_or_default();
+                documentation = hover_documentation(&hovers);
+                documentation_source = documentation.as_ref().map(|_| DocumentationSource::Hover);
+            }
+
+            Ok(SymbolDocsByContextToolOutput {
+                path: declaration_path,
+                start_line: docs.rows.start + 1,
+                end_line: docs.rows.end + 1,
+                kind: docs.kind,
+                visibility: docs.visibility,
+                attributes: docs.attributes,
+                signature: docs.signature,
+                documentation,
+                documentation_source,
+                handle: handles.reference,
+                symbol_handle: handles.symbol,
+            })
+        })
+    }
+}
+
+/// Returns the declaration whose name is exactly `name_range`, if there is one.
+pub(crate) fn declaration_for_name(
+    snapshot: &BufferSnapshot,
+    name_range: Range<usize>,
+) -> Option<Node<'_>> {
+    let ancestor = snapshot.syntax_ancestor(name_range.clone())?;
+    let name = ancestor
+        .named_descendant_for_byte_range(name_range.start, name_range.end)
+        .unwrap_or(ancestor);
+    // The name is usually a direct child of the declaration, but some grammars wrap it once more
+    // (e.g. in a `declarator`).
+    let mut node = name.parent();
+    for _ in 0..2 {
+        let candidate = node?;
+        if candidate
+            .child_by_field_name("name")
+            .is_some_and(|candidate_name| candidate_name.byte_range() == name_range)
+        {
+            return Some(candidate);
+        }
+        node = candidate.parent();
+    }
+    None
+}
+
+/// What a declaration says about itself, as written in the source.
+pub(crate) struct SymbolDocs {
+    pub rows: Range<u32>,
+    pub kind: String,
+    pub visibility: Option<String>,
+    pub attributes: Vec<String>,
+    pub signature: String,
+    pub doc_comment: Option<String>,
+}
+
+impl SymbolDocs {
+    pub fn extract(snapshot: &BufferSnapshot, declaration: Node) -> Self {
+        let node_text = |node: Node| {
+            snapshot
+                .text_for_range(node.byte_range())
+                .collect::<String>()
+        };
+
+        // Doc comments and attributes precede the declaration as siblings. Stop at the first
+        // other node, or at a blank line, since a comment separated by one doesn't belong to it.
+        let mut comments = Vec::new();
+        let mut attributes = Vec::new();
+        let mut first_row = declaration.start_position().row;
+        let mut sibling = declaration.prev_sibling();
+        while let Some(node) = sibling {
+            if last_row(node) + 1 < first_row {
+                break;
+            }
+            if node.kind().contains("comment") {
+                comments.push(node_text(node));
+            } else if is_attribute(node) {
+                attributes.push(node_text(node));
+            } else {
+                break;
+            }
+            first_row = node.start_position().row;
+            sibling = node.prev_sibling();
+        }
+        comments.reverse();
+        attributes.reverse();
+        let has_doc_comment_syntax = snapshot
+            .language_scope_at(declaration.start_byte())
+            .is_some_and(|scope| {
+                scope
+                    .line_comment_prefixes()
+                    .iter()
+                    .any(|prefix| prefix.starts_with("///"))
+            });
+
+        let mut visibility = None;
+        let mut cursor = declaration.walk();
+        for child in declaration.named_children(&mut cursor) {
+            if is_attribute(child) {
+                attributes.push(node_text(child));
+            } else if matches!(
+                child.kind(),
+                "visibility_modifier" | "accessibility_modifier"
+            ) {
+                visibility = Some(node_text(child));
+            }
+        }
+
+        let signature_end = declaration
+            .child_by_field_name("body")
+            .map_or(declaration.end_byte(), |body| body.start_byte());
+        let signature = snapshot
+            .text_for_range(declaration.start_byte()..signature_end)
+            .collect::<String>();
+        let mut signature_lines = signature.trim_end().lines();
+        let mut signature = signature_lines
+            .by_ref()
+            .take(MAX_SIGNATURE_LINES)
+            .collect::<Vec<_>>()
+            .join("\n");
+        if signature_lines.next().is_some() {
+            signature.push_str("\n…");
+        }
+
+        Self {
+            rows: first_row as u32..last_row(declaration) as u32,
+            kind: declaration.kind().to_string(),
+            visibility,
+            attributes,
+            signature,
+            doc_comment: doc_comment_text(&comments, has_doc_comment_syntax),
+        }
+    }
+}
+
+/// Returns the row of the last character of `node`. Comment nodes often include their trailing
+/// newline, which would otherwise make them end on the next row.
+fn last_row(node: Node) -> usize {
+    let end = node.end_position();
+    if end.column == 0 && end.row > node.start_position().row {
+        end.row - 1
+    } else {
+        end.row
+    }
+}
+
+fn is_attribute(node: Node) -> bool {
+    let kind = node.kind();
+    kind.contains("attribute") || kind == "decorator" || kind.contains("annotation")
+}
+
+/// Joins `comments` into documentation text without comment markers. In languages with doc
+/// comments (e.g. `///` or `/** */`), plain comments aren't documentation, so they are left out
+/// when the language has `///` comments or when any of `comments` is a doc comment.
+fn doc_comment_text(comments: &[String], has_doc_comment_syntax: bool) -> Option<String> {
+    const DOC_COMMENT_PREFIXES: &[&str] = &["///", "//!", "/**", "/*!"];
+    let is_doc_comment = |comment: &String| {
+        DOC_COMMENT_PREFIXES
+            .iter()
+            .any(|prefix| comment.starts_with(prefix))
+    };
+    let only_doc_comments = has_doc_comment_syntax || comments.iter().any(is_doc_comment);
+
+    let mut lines = Vec::new();
+    for comment in comments {
+        if only_doc_comments && !is_doc_comment(comment) {
+            continue;
+        }
+        let is_block_comment = comment.starts_with("/*");
+        for line in comment.lines() {
+            let mut line = line.trim();
+            if is_block_comment {
+                line = line.strip_suffix("*/").unwrap_or(line);
+                line = ["/**", "/*!", "/*", "*"]
+                    .iter()
+                    .find_map(|marker| line.strip_prefix(marker))
+                    .unwrap_or(line);
+            } else {
+                line = ["///", "//!", "//", "#"]
+                    .iter()
+                    .find_map(|marker| line.strip_prefix(marker))
+                    .unwrap_or(line);
+            }
+            lines.push(line.strip_prefix(' ').unwrap_or(line).trim_end());
+        }
+    }
+
+    while lines.first().is_some_and(|line| line.is_empty()) {
+        lines.remove(0);
+    }
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+/// Returns the prose blocks of `hovers`, leaving out the code blocks that repeat the signature.
+fn hover_documentation(hovers: &[project::Hover]) -> Option<String> {
+    let blocks = hovers
+        .iter()
+        .flat_map(|hover| &hover.contents)
+        .filter(|block| !matches!(block.kind, HoverBlockKind::Code { .. }))
+        .map(|block| block.text.trim())
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>();
+    (!blocks.is_empty()).then(|| blocks.join("\n\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{ToolTestProject, init_test, run_tool};
+    use futures::StreamExt as _;
+    use gpui::{AppContext as _, TestAppContext};
+    use serde_json::json;
+
+    const SOURCE: &str = "use std::path::Path;
+
+/// Settings loaded from disk.
+///
+/// Missing fields fall back to their defaults.
+#[derive(Debug, Clone)]
+#[serde(default)]
+pub(crate) struct Config {
+    pub name: String,
+}
+
+// Not documentation.
+fn helper() -> Config {
+    Config::default()
+}
+";
+
+    fn anchor(context: &str, token: &str) -> ContextualAnchor {
+        ContextualAnchor {
+            path: "root/src/config.rs".into(),
+            context: context.into(),
+            token: token.into(),
+            index: None,
+            handle: None,
+            revision: None,
+            whole_word: false,
+            approximate_line: None,
+            case_insensitive: false,
+            unicode_normalize: false,
+            end_token: None,
+            end_index: None,
+        }
+    }
+
+    #[gpui::test]
+    async fn test_symbol_docs_at_declaration(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project =
+            ToolTestProject::new(json!({ "src": { "config.rs": SOURCE } }), cx).await;
+        let tool = Arc::new(SymbolDocsByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+
+        let (output, _) = run_tool(
+            tool.clone(),
+            anchor("pub(crate) struct Config {", "Config"),
+            cx,
+        )
+        .await;
+        assert_eq!(
+            output.expect("tool failed"),
+            SymbolDocsByContextToolOutput {
+                path: "root/src/config.rs".into(),
+                start_line: 3,
+                end_line: 10,
+                kind: "struct_item".into(),
+                visibility: Some("pub(crate)".into()),
+                attributes: vec!["#[derive(Debug, Clone)]".into(), "#[serde(default)]".into()],
+                signature: "pub(crate) struct Config".into(),
+                documentation: Some(
+                    "Settings loaded from disk.\n\nMissing fields fall back to their defaults."
+                        .into()
+                ),
+                documentation_source: Some(DocumentationSource::DocComment),
+                handle: "ref#1".into(),
+                symbol_handle: Some("sym:Config".into()),
+            }
+        );
+
+        // Plain comments aren't documentation in Rust, and there's no language server to hover.
+        let (output, _) = run_tool(tool, anchor("fn helper() -> Config {", "helper"), cx).await;
+        let output = output.expect("tool failed");
+        assert_eq!(output.visibility, None);
+        assert_eq!(output.signature, "fn helper() -> Config");
+        assert_eq!(output.documentation, None);
+    }
+
+    #[gpui::test]
+    async fn test_symbol_docs_follow_definition(cx: &mut TestAppContext) {
+        init_test(cx);
+        let test_project =
+            ToolTestProject::new(json!({ "src": { "config.rs": SOURCE } }), cx).await;
+        let mut fake_servers = test_project.register_fake_rust_server(
+            lsp::ServerCapabilities {
+                definition_provider: Some(lsp::OneOf::Left(true)),
+                ..Default::default()
+            },
+            cx,
+        );
+        let (_buffer, _lsp_handle) = test_project.open_buffer_with_lsp("src/config.rs", cx).await;
+        let fake_server = fake_servers
+            .next()
+            .await
+            .expect("fake server was not started");
+        let definition = test_project.lsp_location("src/config.rs", (7, 18), (7, 24));
+        fake_server.set_request_handler::<lsp::request::GotoDefinition, _, _>(move |_, _| {
+            let definition = definition.clone();
+            async move { Ok(Some(lsp::GotoDefinitionResponse::Scalar(definition))) }
+        });
+
+        let tool = Arc::new(SymbolDocsByContextTool::new(
+            test_project.project.clone(),
+            cx.new(|_| AnchorMemory::default()),
+        ));
+        let (output, _) = run_tool(tool, anchor("fn helper() -> Config {", "Config"), cx).await;
+        let LanguageModelToolResultContent::Text(text) =
+            LanguageModelToolResultContent::from(output.expect("tool failed"))
+        else {
+            panic!("expected text output");
+        };
+        assert!(
+            text.starts_with(
+                "root/src/config.rs [L3-10] (struct_item)\n\nVisibility: `pub(crate)`\n"
+            ),
+            "{text}"
+        );
+        assert!(
+            text.contains("Documentation:\n\nSettings loaded from disk."),
+            "{text}"
+        );
+    }
+}
@@ -13,6 +13,7 @@ use std::{
 };
 use util::markdown::MarkdownInlineCode;
 
+use super::support::DRY_RUN_NOTE;
 use crate::{AgentTool, ThreadEnvironment, ToolCallEventStream};
 
 const COMMAND_OUTPUT_LIMIT: u64 = 16 * 1024;
@@ -98,10 +99,17 @@ impl AgentTool for TerminalTool {
         event_stream: ToolCallEventStream,
         cx: &mut App,
     ) -> Task<Result<Self::Output>> {
-        let working_dir = match working_dir(&input, &self.project, cx) {
+        let working_dir = match working_dir(&input.cd, &self.project, cx) {
             Ok(dir) => dir,
             Err(err) => return Task::ready(Err(err)),
         };
+        if event_stream.dry_run() {
+            return Task::ready(Ok(format!(
+                "{DRY_RUN_NOTE} Would run {} in {}.",
+                MarkdownInlineCode(&input.command),
+                MarkdownInlineCode(&input.cd)
+            )));
+        }
 
         let authorize = event_stream.authorize(self.initial_title(Ok(input.clone()), cx), cx);
         cx.spawn(async move |cx| {
@@ -195,13 +203,12 @@ fn process_content(
     content
 }
 
-fn working_dir(
-    input: &TerminalToolInput,
+pub(crate) fn working_dir(
+    cd: &str,
     project: &Entity<Project>,
     cx: &mut App,
 ) -> Result<Option<PathBuf>> {
     let project = project.read(cx);
-    let cd = &input.cd;
 
     if cd == "." || cd.is_empty() {
         // Accept "." or "" as meaning "the one worktree" if we only have one worktree.
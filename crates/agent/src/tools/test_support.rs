@@ -0,0 +1,240 @@
+use crate::{
+    AgentTool, TerminalHandle, ThreadEnvironment, ThreadEvent, ToolCallEventStream,
+    ToolCallEventStreamReceiver,
+};
+use agent_client_protocol as acp;
+use anyhow::{Result, anyhow};
+use fs::FakeFs;
+use futures::channel::mpsc::UnboundedReceiver;
+use gpui::{AsyncApp, Entity, Task, TestAppContext};
+use language::{Buffer, FakeLspAdapter};
+use lsp::FakeLanguageServer;
+use project::{Project, lsp_store::OpenLspBufferHandle};
+use settings::SettingsStore;
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
+};
+use util::path;
+
+/// Set this environment variable to write the actual output of failing snapshot assertions back
+/// to their golden files, instead of failing.
+pub const UPDATE_SNAPSHOTS_ENV_VAR: &str = "UPDATE_SNAPSHOTS";
+
+pub const FAKE_RUST_SERVER_NAME: &str = "fake-rust-analyzer";
+
+pub fn init_test(cx: &mut TestAppContext) {
+    cx.update(|cx| {
+        let settings_store = SettingsStore::test(cx);
+        cx.set_global(settings_store);
+    });
+}
+
+/// A project with a single worktree named `root`, backed by a `FakeFs` and with the test Rust
+/// language registered, so that outlines, syntax-aware anchors and fake language servers work.
+pub struct ToolTestProject {
+    pub fs: Arc<FakeFs>,
+    pub project: Entity<Project>,
+}
+
+impl ToolTestProject {
+    pub async fn new(tree: serde_json::Value, cx: &mut TestAppContext) -> Self {
+        let fs = FakeFs::new(cx.executor());
+        fs.insert_tree(path!("/root"), tree).await;
+        let project = Project::test(fs.clone(), [path!("/root").as_ref()], cx).await;
+        project.read_with(cx, |project, _| {
+            project.languages().add(language::rust_lang());
+        });
+        Self { fs, project }
+    }
+
+    /// Returns the absolute path of `relative_path` inside the `root` worktree.
+    pub fn abs_path(&self, relative_path: &str) -> PathBuf {
+        PathBuf::from(path!("/root")).join(relative_path)
+    }
+
+    /// Registers a fake Rust language server. It starts once a Rust buffer is opened via
+    /// [`Self::open_buffer_with_lsp`], and is yielded by the returned receiver so that tests can
+    /// script its responses with `FakeLanguageServer::set_request_handler`.
+    pub fn register_fake_rust_server(
+        &self,
+        capabilities: lsp::ServerCapabilities,
+        cx: &mut TestAppContext,
+    ) -> UnboundedReceiver<FakeLanguageServer> {
+        self.project.read_with(cx, |project, _| {
+            project.languages().register_fake_lsp(
+                "Rust",
+                FakeLspAdapter {
+                    name: FAKE_RUST_SERVER_NAME,
+                    capabilities,
+                    ..Default::default()
+                },
+            )
+        })
+    }
+
+    /// Opens `relative_path` and registers it with its language servers. The buffer stays
+    /// registered for as long as the returned handle is alive.
+    pub async fn open_buffer_with_lsp(
+        &self,
+        relative_path: &str,
+        cx: &mut TestAppContext,
+    ) -> (Entity<Buffer>, OpenLspBufferHandle) {
+        let abs_path = self.abs_path(relative_path);
+        self.project
+            .update(cx, |project, cx| {
+                project.open_local_buffer_with_lsp(abs_path, cx)
+            })
+            .await
+            .expect("failed to open buffer with language servers")
+    }
+
+    /// Builds an LSP location in `relative_path`, for scripting fake language server responses.
+    pub fn lsp_location(
+        &self,
+        relative_path: &str,
+        start: (u32, u32),
+        end: (u32, u32),
+    ) -> lsp::Location {
+        lsp::Location {
+            uri: lsp::Uri::from_file_path(self.abs_path(relative_path))
+                .expect("fake project paths are absolute"),
+            range: lsp::Range::new(
+                lsp::Position::new(start.0, start.1),
+                lsp::Position::new(end.0, end.1),
+            ),
+        }
+    }
+}
+
+/// A comparable record of one event a tool sent through its `ToolCallEventStream`, so tests can
+/// assert exactly what a tool communicated to the thread and the UI rather than only its output.
+#[derive(Debug, PartialEq)]
+pub enum RecordedToolEvent {
+    /// The tool called `update_fields`, e.g. to set its title, locations or content.
+    Fields(acp::ToolCallUpdateFields),
+    /// The tool attached a diff.
+    Diff,
+    /// The tool attached a terminal.
+    Terminal,
+    /// The tool asked for authorization, with the title shown to the user.
+    Authorization(Option<String>),
+    /// The stream carried an error instead of an event.
+    Error(String),
+}
+
+impl RecordedToolEvent {
+    fn from_event(event: Result<ThreadEvent>) -> Self {
+        match event {
+            Ok(ThreadEvent::ToolCallUpdate(acp_thread::ToolCallUpdate::UpdateFields(update))) => {
+                Self::Fields(update.fields)
+            }
+            Ok(ThreadEvent::ToolCallUpdate(acp_thread::ToolCallUpdate::UpdateDiff(_))) => {
+                Self::Diff
+            }
+            Ok(ThreadEvent::ToolCallUpdate(acp_thread::ToolCallUpdate::UpdateTerminal(_))) => {
+                Self::Terminal
+            }
+            Ok(ThreadEvent::ToolCallAuthorization(authorization)) => {
+                Self::Authorization(authorization.tool_call.fields.title)
+            }
+            Ok(event) => panic!("unexpected event on a tool call stream: {event:?}"),
+            Err(error) => Self::Error(error.to_string()),
+        }
+    }
+}
+
+/// Returns every event currently buffered in `receiver`, in the order the tool sent them.
+pub fn drain_events(receiver: &mut ToolCallEventStreamReceiver) -> Vec<RecordedToolEvent> {
+    let mut events = Vec::new();
+    while let Ok(Some(event)) = receiver.try_next() {
+        events.push(RecordedToolEvent::from_event(event));
+    }
+    events
+}
+
+/// Returns the locations reported by `events`, in the order they were reported.
+pub fn recorded_locations(events: &[RecordedToolEvent]) -> Vec<acp::ToolCallLocation> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            RecordedToolEvent::Fields(fields) => fields.locations.clone(),
+            _ => None,
+        })
+        .flatten()
+        .collect()
+}
+
+/// Runs `tool` with `input` to completion, returning its output along with every event it sent
+/// through its `ToolCallEventStream`, in order.
+///
+/// Authorization requests are recorded but never answered, so this is only suitable for tools
+/// that don't need authorization for `input`.
+pub async fn run_tool<T: AgentTool>(
+    tool: Arc<T>,
+    input: T::Input,
+    cx: &mut TestAppContext,
+) -> (Result<T::Output>, Vec<RecordedToolEvent>) {
+    let (event_stream, mut receiver) = ToolCallEventStream::test();
+    let output = cx.update(|cx| tool.run(input, event_stream, cx)).await;
+    (output, drain_events(&mut receiver))
+}
+
+/// Like [`run_tool`], but as a dry run, in which mutating tools only report what they would change.
+pub async fn run_tool_dry_run<T: AgentTool>(
+    tool: Arc<T>,
+    input: T::Input,
+    cx: &mut TestAppContext,
+) -> (Result<T::Output>, Vec<RecordedToolEvent>) {
+    let (event_stream, mut receiver) = ToolCallEventStream::test_with_dry_run(true);
+    let output = cx.update(|cx| tool.run(input, event_stream, cx)).await;
+    (output, drain_events(&mut receiver))
+}
+
+/// A thread environment that fails to create terminals, for checking that tools which run
+/// commands don't run them, e.g. in a dry run.
+pub struct NoTerminalEnvironment;
+
+impl ThreadEnvironment for NoTerminalEnvironment {
+    fn create_terminal(
+        &self,
+        command: String,
+        _cwd: Option<PathBuf>,
+        _output_byte_limit: Option<u64>,
+        _cx: &mut AsyncApp,
+    ) -> Task<Result<Rc<dyn TerminalHandle>>> {
+        Task::ready(Err(anyhow!("tried to run {command:?}")))
+    }
+}
+
+/// Compares `actual` against the golden file `src/tools/snapshots/{name}.snap`.
+///
+/// Tool output is what the model reads, so changes to its formatting should be reviewed as
+/// deliberately as code. Re-run the failing tests with `UPDATE_SNAPSHOTS=1` to accept new output,
+/// then review the resulting diff of the golden files.
+pub fn assert_snapshot(name: &str, actual: &str) {
+    let snapshot_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("src/tools/snapshots")
+        .join(format!("{name}.snap"));
+
+    if std::env::var_os(UPDATE_SNAPSHOTS_ENV_VAR).is_some() {
+        if let Some(parent) = snapshot_path.parent() {
+            std::fs::create_dir_all(parent).expect("failed to create snapshot directory");
+        }
+        std::fs::write(&snapshot_path, actual).expect("failed to write snapshot");
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&snapshot_path).unwrap_or_else(|error| {
+        panic!(
+            "failed to read snapshot {}: {error}. Run with {UPDATE_SNAPSHOTS_ENV_VAR}=1 to create it.",
+            snapshot_path.display()
+        )
+    });
+    assert!(
+        expected == actual,
+        "output does not match snapshot {}. Run with {UPDATE_SNAPSHOTS_ENV_VAR}=1 to update it.\n\n--- expected\n{expected}\n--- actual\n{actual}",
+        snapshot_path.display()
+    );
+}
@@ -0,0 +1,127 @@
+//! Cuts a tool's rendered results down to what fits in the model's context, saying how many were
+//! left out so that the model can refine its query or ask for the next page.
+
+use crate::outline::TokenBudget;
+use anyhow::Result;
+use gpui::AsyncApp;
+use util::ResultExt as _;
+
+/// Rendered results, cut down to what fits in a token budget.
+#[derive(Debug, PartialEq)]
+pub(crate) struct TruncatedResults {
+    /// The results that fit, followed by a note on how many were omitted, if any were.
+    pub text: String,
+    pub kept: usize,
+    pub omitted: usize,
+}
+
+/// Keeps as many of `results`, each rendered on its own, as fit in `token_budget` together with a
+/// note on how many were omitted. All of them are kept without a budget, or when the model can't
+/// count tokens.
+pub(crate) async fn truncate_results(
+    results: Vec<String>,
+    token_budget: Option<&TokenBudget>,
+    cx: &AsyncApp,
+) -> TruncatedResults {
+    let Some(token_budget) = token_budget else {
+        return keep_all(&results);
+    };
+    truncate_results_with(results, token_budget.max_tokens, async |text| {
+        token_budget.count_tokens(text, cx).await
+    })
+    .await
+}
+
+async fn truncate_results_with(
+    results: Vec<String>,
+    max_tokens: u64,
+    mut count_tokens: impl AsyncFnMut(&str) -> Result<u64>,
+) -> TruncatedResults {
+    let all_results = keep_all(&results);
+    match count_tokens(&all_results.text).await.log_err() {
+        Some(tokens) if tokens > max_tokens => {}
+        _ => return all_results,
+    }
+
+    // Counting can mean asking the model's provider, so search for how many results fit rather
+    // than trying each count in turn.
+    let mut fitting = 0;
+    let mut overflowing = results.len();
+    while overflowing - fitting > 1 {
+        let kept = (fitting + overflowing) / 2;
+        let Some(tokens) = count_tokens(&render(&results, kept)).await.log_err() else {
+            return all_results;
+        };
+        if tokens <= max_tokens {
+            fitting = kept;
+        } else {
+            overflowing = kept;
+        }
+    }
+    TruncatedResults {
+        text: render(&results, fitting),
+        kept: fitting,
+        omitted: results.len() - fitting,
+    }
+}
+
+fn keep_all(results: &[String]) -> TruncatedResults {
+    TruncatedResults {
+        text: results.concat(),
+        kept: results.len(),
+        omitted: 0,
+    }
+}
+
+fn render(results: &[String], kept: usize) -> String {
+    let mut text = results[..kept].concat();
+    let omitted = results.len() - kept;
+    if omitted > 0 {
+        text.push_str(&omitted_note(omitted));
+    }
+    text
+}
+
+/// The note that follows results truncated by [`truncate_results`], for tools that render their
+/// output after truncating it.
+pub(crate) fn omitted_note(omitted: usize) -> String {
+    format!("\n…{omitted} additional results omitted; refine query or paginate\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count_words(text: &str) -> Result<u64> {
+        Ok(text.split_whitespace().count() as u64)
+    }
+
+    #[gpui::test]
+    async fn test_truncate_results() {
+        let results = (1..=10)
+            .map(|index| format!("result {index}\n"))
+            .collect::<Vec<_>>();
+
+        let all_results =
+            truncate_results_with(results.clone(), 100, async |text| count_words(text)).await;
+        assert_eq!(all_results.kept, 10);
+        assert_eq!(all_results.omitted, 0);
+        assert_eq!(all_results.text, results.concat());
+
+        // Each result is two words, and the note takes up eight.
+        let truncated =
+            truncate_results_with(results.clone(), 16, async |text| count_words(text)).await;
+        assert_eq!(truncated.kept, 4);
+        assert_eq!(truncated.omitted, 6);
+        assert_eq!(
+            truncated.text,
+            "result 1\nresult 2\nresult 3\nresult 4\n\n…6 additional results omitted; refine query or paginate\n"
+        );
+
+        let unknown_size = truncate_results_with(results.clone(), 16, async |_| {
+            Err(anyhow::anyhow!("can't count tokens"))
+        })
+        .await;
+        assert_eq!(unknown_size.kept, 10);
+    }
+}
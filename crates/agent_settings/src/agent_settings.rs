@@ -1,6 +1,6 @@
 mod agent_profile;
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use agent_client_protocol::ModelId;
 use collections::{HashSet, IndexMap};
@@ -11,7 +11,7 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use settings::{
     DefaultAgentView, DockPosition, DockSide, LanguageModelParameters, LanguageModelSelection,
-    NotifyWhenAgentWaiting, RegisterSetting, Settings,
+    NotifyWhenAgentWaiting, RegisterSetting, Settings, ToolPermissionsContent,
 };
 
 pub use crate::agent_profile::*;
@@ -39,6 +39,7 @@ pub struct AgentSettings {
     pub default_view: DefaultAgentView,
     pub profiles: IndexMap<AgentProfileId, AgentProfileSettings>,
     pub always_allow_tool_actions: bool,
+    pub tool_permissions: ToolPermissionsContent,
     pub notify_when_agent_waiting: NotifyWhenAgentWaiting,
     pub play_sound_when_agent_done: bool,
     pub single_file_review: bool,
@@ -47,6 +48,11 @@ pub struct AgentSettings {
     pub enable_feedback: bool,
     pub expand_edit_card: bool,
     pub expand_terminal_card: bool,
+    pub follow_agent_navigation: bool,
+    pub language_server_timeout: Duration,
+    pub enable_experimental_tools: bool,
+    pub auto_outline_size: usize,
+    pub legacy_outline_format: bool,
     pub use_modifier_to_send: bool,
     pub message_editor_min_lines: usize,
 }
@@ -183,6 +189,7 @@ impl Settings for AgentSettings {
                 .map(|(key, val)| (AgentProfileId(key), val.into()))
                 .collect(),
             always_allow_tool_actions: agent.always_allow_tool_actions.unwrap(),
+            tool_permissions: agent.tool_permissions.unwrap(),
             notify_when_agent_waiting: agent.notify_when_agent_waiting.unwrap(),
             play_sound_when_agent_done: agent.play_sound_when_agent_done.unwrap(),
             single_file_review: agent.single_file_review.unwrap(),
@@ -191,6 +198,13 @@ impl Settings for AgentSettings {
             enable_feedback: agent.enable_feedback.unwrap(),
             expand_edit_card: agent.expand_edit_card.unwrap(),
             expand_terminal_card: agent.expand_terminal_card.unwrap(),
+            follow_agent_navigation: agent.follow_agent_navigation.unwrap(),
+            language_server_timeout: Duration::from_millis(
+                agent.language_server_timeout_ms.unwrap(),
+            ),
+            enable_experimental_tools: agent.enable_experimental_tools.unwrap(),
+            auto_outline_size: agent.auto_outline_size.unwrap(),
+            legacy_outline_format: agent.legacy_outline_format.unwrap(),
             use_modifier_to_send: agent.use_modifier_to_send.unwrap(),
             message_editor_min_lines: agent.message_editor_min_lines.unwrap(),
         }
@@ -1047,8 +1047,9 @@ mod tests {
     use std::{cell::RefCell, ops::Range, path::Path, rc::Rc, sync::Arc};
 
     use acp_thread::MentionUri;
-    use agent::{HistoryStore, outline};
+    use agent::HistoryStore;
     use agent_client_protocol as acp;
+    use agent_settings::AgentSettings;
     use assistant_text_thread::TextThreadStore;
     use editor::{AnchorRangeExt as _, Editor, EditorMode, MultiBufferOffset};
     use fs::FakeFs;
@@ -1060,6 +1061,7 @@ mod tests {
     use lsp::{CompletionContext, CompletionTriggerKind};
     use project::{CompletionIntent, Project, ProjectPath};
     use serde_json::json;
+    use settings::Settings as _;
     use text::Point;
     use ui::{App, Context, IntoElement, Render, SharedString, Window};
     use util::{path, paths::PathStyle, rel_path::rel_path};
@@ -2042,15 +2044,16 @@ mod tests {
 
         let fs = FakeFs::new(cx.executor());
 
-        // Create a large file that exceeds AUTO_OUTLINE_SIZE
+        // Create a large file that exceeds the auto_outline_size setting
         // Using plain text without a configured language, so no outline is available
+        let auto_outline_size = cx.update(|cx| AgentSettings::get_global(cx).auto_outline_size);
         const LINE: &str = "This is a line of text in the file\n";
-        let large_content = LINE.repeat(2 * (outline::AUTO_OUTLINE_SIZE / LINE.len()));
-        assert!(large_content.len() > outline::AUTO_OUTLINE_SIZE);
+        let large_content = LINE.repeat(2 * (auto_outline_size / LINE.len()));
+        assert!(large_content.len() > auto_outline_size);
 
-        // Create a small file that doesn't exceed AUTO_OUTLINE_SIZE
+        // Create a small file that doesn't exceed the auto_outline_size setting
         let small_content = "fn small_function() { /* small */ }\n";
-        assert!(small_content.len() < outline::AUTO_OUTLINE_SIZE);
+        assert!(small_content.len() < auto_outline_size);
 
         fs.insert_tree(
             "/project",
@@ -19,8 +19,9 @@ use crate::ManageProfiles;
 use crate::ui::{AcpOnboardingModal, ClaudeCodeOnboardingModal};
 use crate::{
     AddContextServer, AgentDiffPane, Follow, InlineAssistant, NewTextThread, NewThread,
-    OpenActiveThreadAsMarkdown, OpenAgentDiff, OpenHistory, ResetTrialEndUpsell, ResetTrialUpsell,
-    ToggleNavigationMenu, ToggleNewThreadMenu, ToggleOptionsMenu,
+    NextAgentLocation, OpenActiveThreadAsMarkdown, OpenAgentDiff, OpenHistory,
+    PreviousAgentLocation, ResetTrialEndUpsell, ResetTrialUpsell, ToggleNavigationMenu,
+    ToggleNewThreadMenu, ToggleOptionsMenu,
     acp::AcpThreadView,
     agent_configuration::{AgentConfiguration, AssistantConfigurationEvent},
     slash_command::SlashCommandCompletionProvider,
@@ -144,6 +145,16 @@ pub fn init(cx: &mut App) {
                 .register_action(|workspace, _: &Follow, window, cx| {
                     workspace.follow(CollaboratorId::Agent, window, cx);
                 })
+                .register_action(|workspace, _: &NextAgentLocation, _window, cx| {
+                    workspace.project().update(cx, |project, cx| {
+                        project.step_agent_location_trail(1, cx);
+                    });
+                })
+                .register_action(|workspace, _: &PreviousAgentLocation, _window, cx| {
+                    workspace.project().update(cx, |project, cx| {
+                        project.step_agent_location_trail(-1, cx);
+                    });
+                })
                 .register_action(|workspace, _: &OpenAgentDiff, window, cx| {
                     let thread = workspace
                         .panel::<AgentPanel>(cx)
@@ -22,6 +22,7 @@ mod ui;
 
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Duration;
 
 use agent_settings::{AgentProfileId, AgentSettings};
 use assistant_slash_command::SlashCommandRegistry;
@@ -114,6 +115,11 @@ actions!(
         RejectOnce,
         /// Follows the agent's suggestions.
         Follow,
+        /// Moves the agent location to the next stop of the trail the agent left, e.g. the next
+        /// of the references it found.
+        NextAgentLocation,
+        /// Moves the agent location to the previous stop of the trail the agent left.
+        PreviousAgentLocation,
         /// Resets the trial upsell notification.
         ResetTrialUpsell,
         /// Resets the trial end upsell notification.
@@ -466,6 +472,7 @@ mod tests {
             default_view: DefaultAgentView::Thread,
             profiles: Default::default(),
             always_allow_tool_actions: false,
+            tool_permissions: Default::default(),
             notify_when_agent_waiting: NotifyWhenAgentWaiting::default(),
             play_sound_when_agent_done: false,
             single_file_review: false,
@@ -474,6 +481,11 @@ mod tests {
             enable_feedback: false,
             expand_edit_card: true,
             expand_terminal_card: true,
+            follow_agent_navigation: false,
+            language_server_timeout: Duration::from_secs(30),
+            enable_experimental_tools: false,
+            auto_outline_size: 16384,
+            legacy_outline_format: false,
             use_modifier_to_send: true,
             message_editor_min_lines: 1,
         };
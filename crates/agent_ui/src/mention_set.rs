@@ -312,6 +312,7 @@ impl MentionSet {
             let buffer_content = outline::get_buffer_content_or_outline(
                 buffer.clone(),
                 Some(&abs_path.to_string_lossy()),
+                None,
                 &cx,
             )
             .await?;
@@ -861,6 +862,7 @@ fn full_mention_for_directory(
                     let buffer_content = outline::get_buffer_content_or_outline(
                         buffer.clone(),
                         Some(&full_path),
+                        None,
                         &cx,
                     )
                     .await
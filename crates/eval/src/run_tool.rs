@@ -0,0 +1,266 @@
+//! Runs a single agent tool against a local project, without a language model or the editor UI,
+//! and prints what the tool produced. This makes it possible to debug anchor resolution and
+//! language server interactions in isolation.
+
+use ::fs::RealFs;
+use agent::{AgentToolOutput, ContextServerRegistry, ThreadEnvironment, ThreadEvent};
+use anyhow::{Context as _, Result, anyhow};
+use clap::Parser;
+use client::{Client, UserStore};
+use extension::ExtensionHostProxy;
+use gpui::{App, AppContext as _, Application, AsyncApp, Entity, Task, UpdateGlobal as _};
+use gpui_tokio::Tokio;
+use language::LanguageRegistry;
+use language_extension::LspAccess;
+use language_model::LanguageModelToolResultContent;
+use node_runtime::{NodeBinaryOptions, NodeRuntime};
+use project::Project;
+use prompt_store::{ProjectContext, WorktreeContext};
+use release_channel::AppVersion;
+use reqwest_client::ReqwestClient;
+use settings::SettingsStore;
+use std::{io::Read as _, path::PathBuf, rc::Rc, sync::Arc, time::Duration};
+use util::ResultExt as _;
+
+#[derive(Parser, Debug)]
+#[command(name = "run_tool", disable_version_flag = true)]
+struct Args {
+    /// Directory to open as the project's only worktree.
+    #[arg(long)]
+    worktree: PathBuf,
+    /// Name of the tool to run, e.g. `find_references_by_context`.
+    tool: String,
+    /// The tool's input as JSON. Read from stdin when omitted.
+    input: Option<String>,
+    /// Seconds to wait after starting language servers for the input's `path`, so they can index
+    /// the project before the tool queries them.
+    #[arg(long, default_value = "0")]
+    lsp_warmup: u64,
+}
+
+fn main() {
+    let args = Args::parse();
+    env_logger::init();
+
+    let input = match &args.input {
+        Some(input) => input.clone(),
+        None => {
+            let mut input = String::new();
+            if let Err(error) = std::io::stdin().read_to_string(&mut input) {
+                eprintln!("Failed to read tool input from stdin: {error}");
+                std::process::exit(1);
+            }
+            input
+        }
+    };
+    let input: serde_json::Value = match serde_json::from_str(&input) {
+        Ok(input) => input,
+        Err(error) => {
+            eprintln!("Tool input is not valid JSON: {error}");
+            std::process::exit(1);
+        }
+    };
+
+    let http_client = Arc::new(ReqwestClient::new());
+    let app = Application::headless().with_http_client(http_client);
+    app.run(move |cx| {
+        let project = init(cx);
+        cx.spawn(async move |cx| {
+            let exit_code = match run(args, input, project, cx).await {
+                Ok(report) => {
+                    println!("{report}");
+                    0
+                }
+                Err(error) => {
+                    eprintln!("{error:#}");
+                    1
+                }
+            };
+            std::process::exit(exit_code);
+        })
+        .detach();
+    });
+}
+
+async fn run(
+    args: Args,
+    input: serde_json::Value,
+    project: Entity<Project>,
+    cx: &mut AsyncApp,
+) -> Result<String> {
+    let worktree = project
+        .update(cx, |project, cx| {
+            project.create_worktree(&args.worktree, true, cx)
+        })?
+        .await?;
+    let scan_complete = worktree.read_with(cx, |worktree, _| {
+        worktree.as_local().map(|worktree| worktree.scan_complete())
+    })?;
+    if let Some(scan_complete) = scan_complete {
+        scan_complete.await;
+    }
+
+    // Tools open buffers without registering them with language servers, so start the servers
+    // for the input's file up front and keep them attached while the tool runs.
+    let _lsp_handle = if let Some(path) = input.get("path").and_then(|path| path.as_str()) {
+        let project_path = project
+            .read_with(cx, |project, cx| project.find_project_path(path, cx))?
+            .with_context(|| format!("{path} is not in the project"))?;
+        let buffer = project
+            .update(cx, |project, cx| project.open_buffer(project_path, cx))?
+            .await?;
+        let lsp_handle = project.update(cx, |project, cx| {
+            project.register_buffer_with_language_servers(&buffer, cx)
+        })?;
+        if args.lsp_warmup > 0 {
+            cx.background_executor()
+                .timer(Duration::from_secs(args.lsp_warmup))
+                .await;
+        }
+        Some(lsp_handle)
+    } else {
+        None
+    };
+
+    let (tool, event_stream, mut events) = cx.update(|cx| {
+        let worktrees = project
+            .read(cx)
+            .visible_worktrees(cx)
+            .map(|worktree| WorktreeContext {
+                root_name: worktree.read(cx).root_name_str().into(),
+                abs_path: worktree.read(cx).abs_path(),
+                rules_file: None,
+            })
+            .collect::<Vec<_>>();
+        let project_context = cx.new(|_| ProjectContext::new(worktrees, vec![]));
+        let context_server_registry =
+            cx.new(|cx| ContextServerRegistry::new(project.read(cx).context_server_store(), cx));
+        let thread = cx.new(|cx| {
+            agent::Thread::new(
+                project.clone(),
+                project_context,
+                context_server_registry,
+                agent::Templates::new(),
+                None,
+                cx,
+            )
+        });
+        let tool = thread.update(cx, |thread, cx| {
+            thread.add_default_tools(Rc::new(HeadlessThreadEnvironment), cx);
+            thread.registered_tool(&args.tool)
+        });
+        let fs = project.read(cx).fs().clone();
+        let (event_stream, events) =
+            agent::ToolCallEventStream::detached("run_tool".into(), Some(fs));
+        (tool, event_stream, events)
+    })?;
+    let tool = tool.with_context(|| format!("No tool named {:?}", args.tool))?;
+
+    let AgentToolOutput {
+        llm_output,
+        raw_output,
+    } = cx.update(|cx| tool.run(input, event_stream, cx))?.await?;
+
+    let mut updates = Vec::new();
+    while let Ok(Some(event)) = events.try_next() {
+        match event? {
+            ThreadEvent::ToolCallUpdate(acp_thread::ToolCallUpdate::UpdateFields(update)) => {
+                updates.push(serde_json::to_value(update.fields)?);
+            }
+            event => eprintln!("Tool sent {event:?}"),
+        }
+    }
+
+    let llm_output = match llm_output {
+        LanguageModelToolResultContent::Text(text)
+        | LanguageModelToolResultContent::Structured { text, .. } => {
+            serde_json::Value::from(text.as_ref())
+        }
+        LanguageModelToolResultContent::Image(_) => serde_json::Value::from("<image>"),
+    };
+    Ok(serde_json::to_string_pretty(&serde_json::json!({
+        "llm_output": llm_output,
+        "raw_output": raw_output,
+        "updates": updates,
+    }))?)
+}
+
+/// Terminals need a thread to render into, which this harness doesn't have.
+struct HeadlessThreadEnvironment;
+
+impl ThreadEnvironment for HeadlessThreadEnvironment {
+    fn create_terminal(
+        &self,
+        _command: String,
+        _cwd: Option<PathBuf>,
+        _output_byte_limit: Option<u64>,
+        _cx: &mut AsyncApp,
+    ) -> Task<Result<Rc<dyn agent::TerminalHandle>>> {
+        Task::ready(Err(anyhow!("run_tool does not support terminals")))
+    }
+}
+
+fn init(cx: &mut App) -> Entity<Project> {
+    let app_version = AppVersion::load(env!("ZED_PKG_VERSION"), None, None);
+    release_channel::init(app_version, cx);
+    gpui_tokio::init(cx);
+
+    let settings_store = SettingsStore::new(cx, &settings::default_settings());
+    cx.set_global(settings_store);
+    SettingsStore::update_global(cx, |store, cx| {
+        store.set_user_settings(include_str!("../runner_settings.json"), cx)
+    })
+    .log_err();
+    // Experimental tools are the ones most in need of debugging, so don't hide them behind
+    // feature flags that a headless client never receives.
+    SettingsStore::update_global(cx, |store, cx| {
+        store.update_user_settings(cx, |settings| {
+            settings
+                .agent
+                .get_or_insert_default()
+                .enable_experimental_tools = Some(true);
+        })
+    });
+
+    let http = {
+        let _guard = Tokio::handle(cx).enter();
+        ReqwestClient::user_agent("Zed Agent Tool Runner").expect("could not start HTTP client")
+    };
+    cx.set_http_client(Arc::new(http));
+
+    let client = Client::production(cx);
+    cx.set_http_client(client.http_client());
+
+    let fs = Arc::new(RealFs::new(None, cx.background_executor().clone()));
+    let mut languages = LanguageRegistry::new(cx.background_executor().clone());
+    languages.set_language_server_download_dir(paths::languages_dir().clone());
+    let languages = Arc::new(languages);
+    let user_store = cx.new(|cx| UserStore::new(client.clone(), cx));
+
+    let (mut node_options_tx, node_options_rx) = watch::channel(None);
+    node_options_tx
+        .send(Some(NodeBinaryOptions {
+            allow_path_lookup: true,
+            allow_binary_download: true,
+            use_paths: None,
+        }))
+        .log_err();
+    let node_runtime = NodeRuntime::new(client.http_client(), None, node_options_rx);
+
+    extension::init(cx);
+    let extension_host_proxy = ExtensionHostProxy::global(cx);
+    language_extension::init(LspAccess::Noop, extension_host_proxy, languages.clone());
+    languages::init(languages.clone(), fs.clone(), node_runtime.clone(), cx);
+    prompt_store::init(cx);
+
+    Project::local(
+        client,
+        node_runtime,
+        user_store,
+        languages,
+        fs,
+        None,
+        false,
+        cx,
+    )
+}
@@ -23,3 +23,9 @@ pub struct AgentV2FeatureFlag;
 impl FeatureFlag for AgentV2FeatureFlag {
     const NAME: &'static str = "agent-v2";
 }
+
+pub struct AgentExperimentalToolsFeatureFlag;
+
+impl FeatureFlag for AgentExperimentalToolsFeatureFlag {
+    const NAME: &'static str = "agent-experimental-tools";
+}
@@ -20,6 +20,7 @@ use parking_lot::Mutex;
 use rope::Rope;
 use smol::future::FutureExt as _;
 use std::{
+    ops::Range,
     path::PathBuf,
     sync::{Arc, LazyLock},
 };
@@ -495,6 +496,22 @@ impl GitRepository for FakeGitRepository {
         .boxed()
     }
 
+    fn line_history(
+        &self,
+        path: RepoPath,
+        _lines: Range<u32>,
+        _skip: usize,
+        _limit: Option<usize>,
+    ) -> BoxFuture<'_, Result<git::repository::FileHistory>> {
+        async move {
+            Ok(git::repository::FileHistory {
+                entries: Vec::new(),
+                path,
+            })
+        }
+        .boxed()
+    }
+
     fn stage_paths(
         &self,
         paths: Vec<RepoPath>,
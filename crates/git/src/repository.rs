@@ -22,6 +22,7 @@ use std::process::{ExitStatus, Stdio};
 use std::{
     cmp::Ordering,
     future,
+    ops::Range,
     path::{Path, PathBuf},
     sync::Arc,
 };
@@ -502,6 +503,16 @@ pub trait GitRepository: Send + Sync {
         limit: Option<usize>,
     ) -> BoxFuture<'_, Result<FileHistory>>;
 
+    /// Returns the commits that changed the 0-based `lines` of the file at `path`, newest first,
+    /// following those lines back through earlier versions of the file.
+    fn line_history(
+        &self,
+        path: RepoPath,
+        lines: Range<u32>,
+        skip: usize,
+        limit: Option<usize>,
+    ) -> BoxFuture<'_, Result<FileHistory>>;
+
     /// Returns the absolute path to the repository. For worktrees, this will be the path to the
     /// worktree's gitdir within the main repository (typically `.git/worktrees/<name>`).
     fn path(&self) -> PathBuf;
@@ -640,6 +651,9 @@ pub trait GitRepository: Send + Sync {
 pub enum DiffType {
     HeadToIndex,
     HeadToWorktree,
+    /// Compares the working tree against a revision, which can be anything `git rev-parse`
+    /// accepts (e.g. `main` or `HEAD~3`).
+    RevisionToWorktree(String),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
@@ -1630,14 +1644,65 @@ impl GitRepository for RealGitRepository {
             .boxed()
     }
 
+    fn line_history(
+        &self,
+        path: RepoPath,
+        lines: Range<u32>,
+        skip: usize,
+        limit: Option<usize>,
+    ) -> BoxFuture<'_, Result<FileHistory>> {
+        let working_directory = self.working_directory();
+        let git_binary_path = self.any_git_binary_path.clone();
+        self.executor
+            .spawn(async move {
+                anyhow::ensure!(!lines.is_empty(), "The line range must not be empty");
+                let mut args = vec![
+                    "--no-optional-locks".to_string(),
+                    "log".to_string(),
+                    format!("--pretty=format:%x1e{LINE_HISTORY_FORMAT}"),
+                    format!("-L{},{}:{}", lines.start + 1, lines.end, path.as_unix_str()),
+                ];
+                if skip > 0 {
+                    args.push(format!("--skip={skip}"));
+                }
+                if let Some(limit) = limit {
+                    args.push(format!("-n{limit}"));
+                }
+
+                let output = new_smol_command(&git_binary_path)
+                    .current_dir(&working_directory?)
+                    .args(&args)
+                    .output()
+                    .await?;
+                if !output.status.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    bail!("git log failed: {stderr}");
+                }
+
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                Ok(FileHistory {
+                    entries: parse_line_history(&stdout),
+                    path,
+                })
+            })
+            .boxed()
+    }
+
     fn diff(&self, diff: DiffType) -> BoxFuture<'_, Result<String>> {
         let working_directory = self.working_directory();
         let git_binary_path = self.any_git_binary_path.clone();
         self.executor
             .spawn(async move {
                 let args = match diff {
-                    DiffType::HeadToIndex => Some("--staged"),
-                    DiffType::HeadToWorktree => None,
+                    DiffType::HeadToIndex => vec!["--staged".to_string()],
+                    DiffType::HeadToWorktree => Vec::new(),
+                    DiffType::RevisionToWorktree(revision) => {
+                        anyhow::ensure!(
+                            !revision.starts_with('-'),
+                            "Invalid git revision: {revision}"
+                        );
+                        vec![revision, "--".to_string()]
+                    }
                 };
 
                 let output = new_smol_command(&git_binary_path)
@@ -2808,6 +2873,38 @@ fn checkpoint_author_envs() -> HashMap<String, String> {
     ])
 }
 
+/// The fields of each commit that `git log -L` prints, each terminated by a NUL so that the
+/// patch git prints after them can be told apart from the message.
+const LINE_HISTORY_FORMAT: &str = "%H%x00%s%x00%B%x00%at%x00%an%x00%ae%x00";
+
+/// Parses the output of `git log -L`, where each commit starts with a record separator and is
+/// followed by the patch to the traced lines.
+fn parse_line_history(output: &str) -> Vec<FileHistoryEntry> {
+    output
+        .split('\x1e')
+        .filter_map(|commit_block| {
+            let mut fields = commit_block.splitn(7, '\0');
+            let sha = fields.next()?.trim();
+            if sha.is_empty() {
+                return None;
+            }
+            let subject = fields.next()?.trim();
+            let message = fields.next()?.trim();
+            let commit_timestamp = fields.next()?.trim().parse().unwrap_or(0);
+            let author_name = fields.next()?.trim();
+            let author_email = fields.next()?.trim();
+            Some(FileHistoryEntry {
+                sha: sha.to_string().into(),
+                subject: subject.to_string().into(),
+                message: message.to_string().into(),
+                commit_timestamp,
+                author_name: author_name.to_string().into(),
+                author_email: author_email.to_string().into(),
+            })
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -3061,6 +3158,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_line_history_parsing() {
+        let output = "\x1e2c2c2c\0Count bytes\0Count bytes\n\nLengths are in bytes.\0\
+            1700000000\0Ada\0ada@example.com\0\n\ndiff --git a/src/lib.rs b/src/lib.rs\n\
+            @@ -2,1 +2,1 @@\n-    0\n+    input.len() as u32\n\
+            \x1e1b1b1b\0Add parser\0Add parser\0\
+            1690000000\0Grace\0grace@example.com\0\n\ndiff --git a/src/lib.rs b/src/lib.rs\n";
+        let entries = parse_line_history(output);
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| (
+                    entry.sha.as_ref(),
+                    entry.subject.as_ref(),
+                    entry.message.as_ref(),
+                    entry.commit_timestamp,
+                    entry.author_name.as_ref(),
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "2c2c2c",
+                    "Count bytes",
+                    "Count bytes\n\nLengths are in bytes.",
+                    1700000000,
+                    "Ada"
+                ),
+                ("1b1b1b", "Add parser", "Add parser", 1690000000, "Grace"),
+            ]
+        );
+    }
+
     #[test]
     fn test_branches_parsing() {
         // suppress "help: octal escapes are not supported, `\0` is always null"
@@ -239,6 +239,12 @@ pub struct LanguageModelToolResult {
 pub enum LanguageModelToolResultContent {
     Text(Arc<str>),
     Image(LanguageModelImage),
+    /// A text rendering of a tool's output along with the structured output itself. Providers
+    /// that accept structured tool results send `json`, and all others send `text`.
+    Structured {
+        text: Arc<str>,
+        json: serde_json::Value,
+    },
 }
 
 impl<'de> Deserialize<'de> for LanguageModelToolResultContent {
@@ -289,6 +295,19 @@ impl<'de> Deserialize<'de> for LanguageModelToolResultContent {
                 }
             }
 
+            // Check for wrapped Structured variant: { "structured": { "text": "...", "json": ... } }
+            if let Some((_key, value)) = obj.iter().find(|(k, _)| k.to_lowercase() == "structured")
+                && obj.len() == 1
+                && let Some(structured_obj) = value.as_object()
+                && let Some(text) = get_field(structured_obj, "text").and_then(|text| text.as_str())
+                && let Some(json) = get_field(structured_obj, "json")
+            {
+                return Ok(Self::Structured {
+                    text: Arc::from(text),
+                    json: json.clone(),
+                });
+            }
+
             // Check for wrapped Image variant: { "image": { "source": "...", "size": ... } }
             if let Some((_key, value)) = obj.iter().find(|(k, _)| k.to_lowercase() == "image")
                 && obj.len() == 1
@@ -320,17 +339,28 @@ impl<'de> Deserialize<'de> for LanguageModelToolResultContent {
 impl LanguageModelToolResultContent {
     pub fn to_str(&self) -> Option<&str> {
         match self {
-            Self::Text(text) => Some(text),
+            Self::Text(text) | Self::Structured { text, .. } => Some(text),
             Self::Image(_) => None,
         }
     }
 
     pub fn is_empty(&self) -> bool {
         match self {
-            Self::Text(text) => text.chars().all(|c| c.is_whitespace()),
+            Self::Text(text) | Self::Structured { text, .. } => {
+                text.chars().all(|c| c.is_whitespace())
+            }
             Self::Image(_) => false,
         }
     }
+
+    /// Attaches `json`, the structured output that this content renders as text, for providers
+    /// that accept structured tool results. Images are left as they are.
+    pub fn with_structured_output(self, json: serde_json::Value) -> Self {
+        match self {
+            Self::Text(text) | Self::Structured { text, .. } => Self::Structured { text, json },
+            image @ Self::Image(_) => image,
+        }
+    }
 }
 
 impl From<&str> for LanguageModelToolResultContent {
@@ -624,6 +654,15 @@ mod tests {
             _ => panic!("Expected Image variant"),
         }
 
+        // Test wrapped Structured variant, as it's serialized
+        let content = LanguageModelToolResultContent::Structured {
+            text: "2 matches".into(),
+            json: serde_json::json!({"matches": [1, 2]}),
+        };
+        let json = serde_json::to_string(&content).unwrap();
+        let result: LanguageModelToolResultContent = serde_json::from_str(&json).unwrap();
+        assert_eq!(result, content);
+
         // Test that wrapped text with wrong type fails
         let json = r#"{"type": "blahblah", "text": "This should fail"}"#;
         let result: Result<LanguageModelToolResultContent, _> = serde_json::from_str(json);
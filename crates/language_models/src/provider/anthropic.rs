@@ -296,7 +296,8 @@ pub fn into_anthropic_count_tokens_request(
                                 tool_use_id: tool_result.tool_use_id.to_string(),
                                 is_error: tool_result.is_error,
                                 content: match tool_result.content {
-                                    LanguageModelToolResultContent::Text(text) => {
+                                    LanguageModelToolResultContent::Text(text)
+                                    | LanguageModelToolResultContent::Structured { text, .. } => {
                                         ToolResultContent::Plain(text.to_string())
                                     }
                                     LanguageModelToolResultContent::Image(image) => {
@@ -400,7 +401,8 @@ pub fn count_anthropic_tokens_with_tiktoken(request: LanguageModelRequest) -> Re
                     // TODO: Estimate token usage from tool uses.
                 }
                 MessageContent::ToolResult(tool_result) => match &tool_result.content {
-                    LanguageModelToolResultContent::Text(text) => {
+                    LanguageModelToolResultContent::Text(text)
+                    | LanguageModelToolResultContent::Structured { text, .. } => {
                         string_contents.push_str(text);
                     }
                     LanguageModelToolResultContent::Image(image) => {
@@ -684,7 +686,8 @@ pub fn into_anthropic(
                                 tool_use_id: tool_result.tool_use_id.to_string(),
                                 is_error: tool_result.is_error,
                                 content: match tool_result.content {
-                                    LanguageModelToolResultContent::Text(text) => {
+                                    LanguageModelToolResultContent::Text(text)
+                                    | LanguageModelToolResultContent::Structured { text, .. } => {
                                         ToolResultContent::Plain(text.to_string())
                                     }
                                     LanguageModelToolResultContent::Image(image) => {
@@ -761,7 +761,10 @@ fn into_copilot_chat(
                 for content in &message.content {
                     if let MessageContent::ToolResult(tool_result) = content {
                         let content = match &tool_result.content {
-                            LanguageModelToolResultContent::Text(text) => text.to_string().into(),
+                            LanguageModelToolResultContent::Text(text)
+                            | LanguageModelToolResultContent::Structured { text, .. } => {
+                                text.to_string().into()
+                            }
                             LanguageModelToolResultContent::Image(image) => {
                                 if model.supports_vision() {
                                     ChatMessageContent::Multipart(vec![ChatMessagePart::Image {
@@ -952,7 +955,8 @@ fn into_copilot_responses(
                             }
                         } else {
                             match &tool_result.content {
-                                LanguageModelToolResultContent::Text(text) => {
+                                LanguageModelToolResultContent::Text(text)
+                                | LanguageModelToolResultContent::Structured { text, .. } => {
                                     responses::ResponseFunctionOutput::Text(text.to_string())
                                 }
                                 LanguageModelToolResultContent::Image(image) => {
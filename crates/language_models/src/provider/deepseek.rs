@@ -369,7 +369,8 @@ pub fn into_deepseek(
                 }
                 MessageContent::ToolResult(tool_result) => {
                     match &tool_result.content {
-                        LanguageModelToolResultContent::Text(text) => {
+                        LanguageModelToolResultContent::Text(text)
+                        | LanguageModelToolResultContent::Structured { text, .. } => {
                             messages.push(deepseek::RequestMessage::Tool {
                                 content: text.to_string(),
                                 tool_call_id: tool_result.tool_use_id.to_string(),
@@ -454,6 +454,21 @@ pub fn into_google(
                                 },
                             )]
                         }
+                        language_model::LanguageModelToolResultContent::Structured {
+                            json, ..
+                        } => {
+                            vec![Part::FunctionResponsePart(
+                                google_ai::FunctionResponsePart {
+                                    function_response: google_ai::FunctionResponse {
+                                        name: tool_result.tool_name.to_string(),
+                                        // Gemini takes structured results, so skip the text form
+                                        response: serde_json::json!({
+                                            "output": json
+                                        }),
+                                    },
+                                },
+                            )]
+                        }
                         language_model::LanguageModelToolResultContent::Image(image) => {
                             vec![
                                 Part::FunctionResponsePart(google_ai::FunctionResponsePart {
@@ -314,7 +314,8 @@ impl LmStudioLanguageModel {
                     }
                     MessageContent::ToolResult(tool_result) => {
                         let content = match &tool_result.content {
-                            LanguageModelToolResultContent::Text(text) => {
+                            LanguageModelToolResultContent::Text(text)
+                            | LanguageModelToolResultContent::Structured { text, .. } => {
                                 vec![lmstudio::MessagePart::Text {
                                     text: text.to_string(),
                                 }]
@@ -425,7 +425,10 @@ pub fn into_mistral(
                         }
                         MessageContent::ToolResult(tool_result) => {
                             let tool_content = match &tool_result.content {
-                                LanguageModelToolResultContent::Text(text) => text.to_string(),
+                                LanguageModelToolResultContent::Text(text)
+                                | LanguageModelToolResultContent::Structured { text, .. } => {
+                                    text.to_string()
+                                }
                                 LanguageModelToolResultContent::Image(_) => {
                                     "[Tool responded with an image, but Zed doesn't support these in Mistral models yet]".to_string()
                                 }
@@ -398,7 +398,8 @@ pub fn into_open_ai(
                 }
                 MessageContent::ToolResult(tool_result) => {
                     let content = match &tool_result.content {
-                        LanguageModelToolResultContent::Text(text) => {
+                        LanguageModelToolResultContent::Text(text)
+                        | LanguageModelToolResultContent::Structured { text, .. } => {
                             vec![open_ai::MessagePart::Text {
                                 text: text.to_string(),
                             }]
@@ -450,7 +450,8 @@ pub fn into_open_router(
                 }
                 MessageContent::ToolResult(tool_result) => {
                     let content = match &tool_result.content {
-                        LanguageModelToolResultContent::Text(text) => {
+                        LanguageModelToolResultContent::Text(text)
+                        | LanguageModelToolResultContent::Structured { text, .. } => {
                             vec![open_router::MessagePart::Text {
                                 text: text.to_string(),
                             }]
@@ -270,6 +270,22 @@ struct Error {
     data: Option<serde_json::Value>,
 }
 
+/// The error a language server answered a request with, kept as an error rather than only its
+/// message so that callers can tell errors apart by their [`error_codes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LspResponseError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl std::fmt::Display for LspResponseError {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for LspResponseError {}
+
 pub trait LspRequestFuture<O>: Future<Output = ConnectionResult<O>> {
     fn id(&self) -> i32;
 }
@@ -1107,7 +1123,12 @@ impl LanguageServer {
                                                 jsonrpc: JSON_RPC_VERSION,
                                                 id,
                                                 value: LspResult::Error(Some(Error {
-                                                    code: lsp_types::error_codes::REQUEST_FAILED,
+                                                    code: error
+                                                        .downcast_ref::<LspResponseError>()
+                                                        .map_or(
+                                                            lsp_types::error_codes::REQUEST_FAILED,
+                                                            |error| error.code,
+                                                        ),
                                                     message: error.to_string(),
                                                     data: None,
                                                 })),
@@ -1285,7 +1306,11 @@ impl LanguageServer {
                                             Err(error).context("failed to deserialize response")
                                         }
                                     }
-                                    Err(error) => Err(anyhow!("{}", error.message)),
+                                    Err(error) => Err(LspResponseError {
+                                        code: error.code,
+                                        message: error.message,
+                                    }
+                                    .into()),
                                 };
                                 _ = tx.send(response);
                             })
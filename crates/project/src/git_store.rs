@@ -4085,6 +4085,35 @@ impl Repository {
         })
     }
 
+    /// Loads the contents of `path` as of `revision`, which can be anything `git rev-parse`
+    /// accepts (e.g. `HEAD~1` or a commit SHA).
+    pub fn load_text_at_revision(
+        &mut self,
+        revision: String,
+        path: RepoPath,
+    ) -> oneshot::Receiver<Result<String>> {
+        self.send_job(None, move |git_repo, _cx| async move {
+            match git_repo {
+                RepositoryState::Local(LocalRepositoryState { backend, .. }) => {
+                    let object = format!("{revision}:{}", path.as_unix_str());
+                    let oid = backend
+                        .revparse_batch(vec![object])
+                        .await?
+                        .into_iter()
+                        .next()
+                        .flatten()
+                        .with_context(|| {
+                            format!("{} does not exist at {revision}", path.as_unix_str())
+                        })?;
+                    backend.load_blob_content(oid.parse()?).await
+                }
+                RepositoryState::Remote(_) => {
+                    bail!("loading files at a git revision is not supported in remote projects")
+                }
+            }
+        })
+    }
+
     pub fn load_commit_diff(&mut self, commit: String) -> oneshot::Receiver<Result<CommitDiff>> {
         let id = self.id;
         self.send_job(None, move |git_repo, cx| async move {
@@ -4169,6 +4198,26 @@ impl Repository {
         })
     }
 
+    /// Returns the commits that changed the 0-based `lines` of the file at `path`, newest first.
+    pub fn line_history(
+        &mut self,
+        path: RepoPath,
+        lines: Range<u32>,
+        skip: usize,
+        limit: Option<usize>,
+    ) -> oneshot::Receiver<Result<git::repository::FileHistory>> {
+        self.send_job(None, move |git_repo, _cx| async move {
+            match git_repo {
+                RepositoryState::Local(LocalRepositoryState { backend, .. }) => {
+                    backend.line_history(path, lines, skip, limit).await
+                }
+                RepositoryState::Remote(_) => {
+                    bail!("the history of a line range is not supported in remote projects")
+                }
+            }
+        })
+    }
+
     fn buffer_store(&self, cx: &App) -> Option<Entity<BufferStore>> {
         Some(self.git_store.upgrade()?.read(cx).buffer_store.clone())
     }
@@ -5256,18 +5305,20 @@ impl Repository {
                     backend.diff(diff_type).await
                 }
                 RepositoryState::Remote(RemoteRepositoryState { project_id, client }) => {
+                    let diff_type = match diff_type {
+                        DiffType::HeadToIndex => proto::git_diff::DiffType::HeadToIndex,
+                        DiffType::HeadToWorktree => proto::git_diff::DiffType::HeadToWorktree,
+                        DiffType::RevisionToWorktree(_) => {
+                            bail!(
+                                "diffing against a git revision is not supported in remote projects"
+                            )
+                        }
+                    };
                     let response = client
                         .request(proto::GitDiff {
                             project_id: project_id.0,
                             repository_id: id.to_proto(),
-                            diff_type: match diff_type {
-                                DiffType::HeadToIndex => {
-                                    proto::git_diff::DiffType::HeadToIndex.into()
-                                }
-                                DiffType::HeadToWorktree => {
-                                    proto::git_diff::DiffType::HeadToWorktree.into()
-                                }
-                            },
+                            diff_type: diff_type.into(),
                         })
                         .await?;
 
@@ -1605,6 +1605,12 @@ impl LspCommand for GetDocumentHighlights {
     }
 }
 
+/// Servers mark deprecated symbols with a tag, or with the `deprecated` flag that the tag replaced.
+fn is_deprecated(tags: Option<&[lsp::SymbolTag]>, deprecated: Option<bool>) -> bool {
+    deprecated.unwrap_or(false)
+        || tags.is_some_and(|tags| tags.contains(&lsp::SymbolTag::DEPRECATED))
+}
+
 #[async_trait(?Send)]
 impl LspCommand for GetDocumentSymbols {
     type Response = Vec<DocumentSymbol>;
@@ -1654,21 +1660,33 @@ impl LspCommand for GetDocumentSymbols {
         let symbols: Vec<_> = match lsp_symbols {
             lsp::DocumentSymbolResponse::Flat(symbol_information) => symbol_information
                 .into_iter()
-                .map(|lsp_symbol| DocumentSymbol {
-                    name: lsp_symbol.name,
-                    kind: lsp_symbol.kind,
-                    range: range_from_lsp(lsp_symbol.location.range),
-                    selection_range: range_from_lsp(lsp_symbol.location.range),
-                    children: Vec::new(),
+                .map(|lsp_symbol| {
+                    #[allow(deprecated)]
+                    let deprecated =
+                        is_deprecated(lsp_symbol.tags.as_deref(), lsp_symbol.deprecated);
+                    DocumentSymbol {
+                        name: lsp_symbol.name,
+                        kind: lsp_symbol.kind,
+                        range: range_from_lsp(lsp_symbol.location.range),
+                        selection_range: range_from_lsp(lsp_symbol.location.range),
+                        detail: None,
+                        deprecated,
+                        children: Vec::new(),
+                    }
                 })
                 .collect(),
             lsp::DocumentSymbolResponse::Nested(nested_responses) => {
                 fn convert_symbol(lsp_symbol: lsp::DocumentSymbol) -> DocumentSymbol {
+                    #[allow(deprecated)]
+                    let deprecated =
+                        is_deprecated(lsp_symbol.tags.as_deref(), lsp_symbol.deprecated);
                     DocumentSymbol {
                         name: lsp_symbol.name,
                         kind: lsp_symbol.kind,
                         range: range_from_lsp(lsp_symbol.range),
                         selection_range: range_from_lsp(lsp_symbol.selection_range),
+                        detail: lsp_symbol.detail,
+                        deprecated,
                         children: lsp_symbol
                             .children
                             .map(|children| {
@@ -1735,6 +1753,8 @@ impl LspCommand for GetDocumentSymbols {
                             row: symbol.selection_range.end.0.row,
                             column: symbol.selection_range.end.0.column,
                         }),
+                        detail: symbol.detail,
+                        deprecated: symbol.deprecated,
                         children: symbol
                             .children
                             .into_iter()
@@ -1784,6 +1804,8 @@ impl LspCommand for GetDocumentSymbols {
                         selection_start.column,
                     ))
                         ..Unclipped(PointUtf16::new(selection_end.row, selection_end.column)),
+                    detail: serialized_symbol.detail,
+                    deprecated: serialized_symbol.deprecated,
                     children: serialized_symbol
                         .children
                         .into_iter()
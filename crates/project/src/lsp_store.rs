@@ -51,7 +51,7 @@ use futures::{
     AsyncWriteExt, Future, FutureExt, StreamExt,
     future::{Either, Shared, join_all, pending, select},
     select, select_biased,
-    stream::FuturesUnordered,
+    stream::{self, BoxStream, FuturesUnordered},
 };
 use globset::{Glob, GlobBuilder, GlobMatcher, GlobSet, GlobSetBuilder};
 use gpui::{
@@ -4845,7 +4845,14 @@ impl LspStore {
                 if !message.ends_with("content modified") {
                     log::warn!("{message}");
                 }
-                anyhow::anyhow!(message)
+                // Keep the server's error code, which callers may decide on, e.g. to retry.
+                match err.downcast_ref::<lsp::LspResponseError>() {
+                    Some(response_error) => anyhow::Error::new(lsp::LspResponseError {
+                        code: response_error.code,
+                        message,
+                    }),
+                    None => anyhow::anyhow!(message),
+                }
             })?;
 
             request
@@ -5587,11 +5594,32 @@ impl LspStore {
         position: PointUtf16,
         cx: &mut Context<Self>,
     ) -> Task<Result<Option<Vec<LocationLink>>>> {
+        let responses = self.definitions_by_language_server(buffer, position, cx);
+        cx.background_spawn(async move {
+            Ok(responses.await?.map(|responses| {
+                successful_responses(&GetDefinitions { position }, responses)
+                    .into_iter()
+                    .flat_map(|(_, definitions)| definitions)
+                    .dedup()
+                    .collect()
+            }))
+        })
+    }
+
+    /// Like [`Self::definitions`], but keeps apart the results of each language server, so that
+    /// callers can tell which server reported what and which failed.
+    pub fn definitions_by_language_server(
+        &mut self,
+        buffer: &Entity<Buffer>,
+        position: PointUtf16,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<Option<Vec<(LanguageServerId, Result<Vec<LocationLink>>)>>>> {
         if let Some((upstream_client, project_id)) = self.upstream_client() {
             let request = GetDefinitions { position };
             if !self.is_capable_for_proto_request(buffer, &request, cx) {
                 return Task::ready(Ok(None));
             }
+
             let request_task = upstream_client.request_lsp(
                 project_id,
                 None,
@@ -5607,43 +5635,32 @@ impl LspStore {
                 let Some(responses) = request_task.await? else {
                     return Ok(None);
                 };
-                let actions = join_all(responses.payload.into_iter().map(|response| {
-                    GetDefinitions { position }.response_from_proto(
-                        response.response,
-                        lsp_store.clone(),
-                        buffer.clone(),
-                        cx.clone(),
-                    )
+
+                let definitions = join_all(responses.payload.into_iter().map(|response| {
+                    let lsp_store = lsp_store.clone();
+                    let buffer = buffer.clone();
+                    let cx = cx.clone();
+                    async move {
+                        let definitions = GetDefinitions { position }
+                            .response_from_proto(response.response, lsp_store, buffer, cx)
+                            .await;
+                        (
+                            LanguageServerId::from_proto(response.server_id),
+                            definitions,
+                        )
+                    }
                 }))
                 .await;
-
-                Ok(Some(
-                    actions
-                        .into_iter()
-                        .collect::<Result<Vec<Vec<_>>>>()?
-                        .into_iter()
-                        .flatten()
-                        .dedup()
-                        .collect(),
-                ))
+                Ok(Some(definitions))
             })
         } else {
-            let definitions_task = self.request_multiple_lsp_locally(
+            let definitions = self.request_multiple_lsp_locally_by_server(
                 buffer,
                 Some(position),
                 GetDefinitions { position },
                 cx,
             );
-            cx.background_spawn(async move {
-                Ok(Some(
-                    definitions_task
-                        .await
-                        .into_iter()
-                        .flat_map(|(_, definitions)| definitions)
-                        .dedup()
-                        .collect(),
-                ))
-            })
+            cx.background_spawn(async move { Ok(Some(definitions.collect::<Vec<_>>().await)) })
         }
     }
 
@@ -5851,6 +5868,34 @@ impl LspStore {
         position: PointUtf16,
         cx: &mut Context<Self>,
     ) -> Task<Result<Option<Vec<Location>>>> {
+        let responses = self.references_by_language_server(buffer, position, cx);
+        cx.background_spawn(async move {
+            let Some(responses) = responses.await? else {
+                return Ok(None);
+            };
+            Ok(Some(
+                successful_responses(
+                    &GetReferences { position },
+                    responses.collect::<Vec<_>>().await,
+                )
+                .into_iter()
+                .flat_map(|(_, references)| references)
+                .dedup()
+                .collect(),
+            ))
+        })
+    }
+
+    /// Like [`Self::references`], but keeps apart the results of each language server, so that
+    /// callers can tell which server reported what and which failed. Searching for references
+    /// can take a while in large projects, so each server's results are yielded as soon as it
+    /// answers.
+    pub fn references_by_language_server(
+        &mut self,
+        buffer: &Entity<Buffer>,
+        position: PointUtf16,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<Option<LanguageServerResponses<Vec<Location>>>>> {
         if let Some((upstream_client, project_id)) = self.upstream_client() {
             let request = GetReferences { position };
             if !self.is_capable_for_proto_request(buffer, &request, cx) {
@@ -5873,40 +5918,27 @@ impl LspStore {
                     return Ok(None);
                 };
 
-                let locations = join_all(responses.payload.into_iter().map(|lsp_response| {
-                    GetReferences { position }.response_from_proto(
-                        lsp_response.response,
-                        lsp_store.clone(),
-                        buffer.clone(),
-                        cx.clone(),
-                    )
+                let references = join_all(responses.payload.into_iter().map(|response| {
+                    let lsp_store = lsp_store.clone();
+                    let buffer = buffer.clone();
+                    let cx = cx.clone();
+                    async move {
+                        let references = GetReferences { position }
+                            .response_from_proto(response.response, lsp_store, buffer, cx)
+                            .await;
+                        (LanguageServerId::from_proto(response.server_id), references)
+                    }
                 }))
-                .await
-                .into_iter()
-                .collect::<Result<Vec<Vec<_>>>>()?
-                .into_iter()
-                .flatten()
-                .dedup()
-                .collect();
-                Ok(Some(locations))
+                .await;
+                Ok(Some(stream::iter(references).boxed()))
             })
         } else {
-            let references_task = self.request_multiple_lsp_locally(
+            Task::ready(Ok(Some(self.request_multiple_lsp_locally_by_server(
                 buffer,
                 Some(position),
                 GetReferences { position },
                 cx,
-            );
-            cx.background_spawn(async move {
-                Ok(Some(
-                    references_task
-                        .await
-                        .into_iter()
-                        .flat_map(|(_, references)| references)
-                        .dedup()
-                        .collect(),
-                ))
-            })
+            ))))
         }
     }
 
@@ -8749,6 +8781,28 @@ impl LspStore {
         request: R,
         cx: &mut Context<Self>,
     ) -> Task<Vec<(LanguageServerId, R::Response)>>
+    where
+        P: ToOffset,
+        R: LspCommand + Clone,
+        <R::LspRequest as lsp::request::Request>::Result: Send,
+        <R::LspRequest as lsp::request::Request>::Params: Send,
+    {
+        let responses =
+            self.request_multiple_lsp_locally_by_server(buffer, position, request.clone(), cx);
+        cx.background_spawn(async move {
+            successful_responses(&request, responses.collect::<Vec<_>>().await)
+        })
+    }
+
+    /// Like [`Self::request_multiple_lsp_locally`], but keeps the errors of the servers that
+    /// failed, yielding each server's response as soon as it answers.
+    fn request_multiple_lsp_locally_by_server<P, R>(
+        &mut self,
+        buffer: &Entity<Buffer>,
+        position: Option<P>,
+        request: R,
+        cx: &mut Context<Self>,
+    ) -> LanguageServerResponses<R::Response>
     where
         P: ToOffset,
         R: LspCommand + Clone,
@@ -8756,7 +8810,7 @@ impl LspStore {
         <R::LspRequest as lsp::request::Request>::Params: Send,
     {
         let Some(local) = self.as_local() else {
-            return Task::ready(Vec::new());
+            return stream::empty().boxed();
         };
 
         let snapshot = buffer.read(cx).snapshot();
@@ -8783,7 +8837,7 @@ impl LspStore {
                 .collect::<Vec<_>>()
         });
 
-        let mut response_results = server_ids
+        server_ids
             .into_iter()
             .map(|server_id| {
                 let task = self.request_lsp(
@@ -8794,20 +8848,8 @@ impl LspStore {
                 );
                 async move { (server_id, task.await) }
             })
-            .collect::<FuturesUnordered<_>>();
-
-        cx.background_spawn(async move {
-            let mut responses = Vec::with_capacity(response_results.len());
-            while let Some((server_id, response_result)) = response_results.next().await {
-                match response_result {
-                    Ok(response) => responses.push((server_id, response)),
-                    // rust-analyzer likes to error with this when its still loading up
-                    Err(e) if format!("{e:#}").ends_with("content modified") => (),
-                    Err(e) => log::error!("Error handling response for request {request:?}: {e:#}"),
-                }
-            }
-            responses
-        })
+            .collect::<FuturesUnordered<_>>()
+            .boxed()
     }
 
     async fn handle_lsp_get_completions(
@@ -13671,6 +13713,29 @@ impl LanguageServerState {
     }
 }
 
+/// The responses of the language servers a request was sent to, each with the server that sent
+/// it, in the order they arrive.
+pub type LanguageServerResponses<T> = BoxStream<'static, (LanguageServerId, Result<T>)>;
+
+/// Drops the responses of the servers that failed `request`, logging why.
+fn successful_responses<T>(
+    request: &impl std::fmt::Debug,
+    responses: Vec<(LanguageServerId, Result<T>)>,
+) -> Vec<(LanguageServerId, T)> {
+    responses
+        .into_iter()
+        .filter_map(|(server_id, response)| match response {
+            Ok(response) => Some((server_id, response)),
+            // rust-analyzer likes to error with this when its still loading up
+            Err(e) if format!("{e:#}").ends_with("content modified") => None,
+            Err(e) => {
+                log::error!("Error handling response for request {request:?}: {e:#}");
+                None
+            }
+        })
+        .collect()
+}
+
 impl std::fmt::Debug for LanguageServerState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
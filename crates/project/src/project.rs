@@ -152,8 +152,9 @@ pub use task_inventory::{
 pub use buffer_store::ProjectTransaction;
 pub use lsp_store::{
     DiagnosticSummary, InvalidationStrategy, LanguageServerLogType, LanguageServerProgress,
-    LanguageServerPromptRequest, LanguageServerStatus, LanguageServerToQuery, LspStore,
-    LspStoreEvent, ProgressToken, SERVER_PROGRESS_THROTTLE_TIMEOUT,
+    LanguageServerPromptRequest, LanguageServerResponses, LanguageServerStatus,
+    LanguageServerToQuery, LspStore, LspStoreEvent, ProgressToken,
+    SERVER_PROGRESS_THROTTLE_TIMEOUT,
 };
 pub use toolchain_store::{ToolchainStore, Toolchains};
 const MAX_PROJECT_SEARCH_HISTORY_SIZE: usize = 500;
@@ -220,6 +221,9 @@ pub struct Project {
     settings_observer: Entity<SettingsObserver>,
     toolchain_store: Option<Entity<ToolchainStore>>,
     agent_location: Option<AgentLocation>,
+    /// Locations the agent visited in order, which the user can step through.
+    agent_location_trail: Vec<AgentLocation>,
+    agent_location_trail_index: usize,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -794,6 +798,9 @@ pub struct DocumentSymbol {
     pub kind: lsp::SymbolKind,
     pub range: Range<Unclipped<PointUtf16>>,
     pub selection_range: Range<Unclipped<PointUtf16>>,
+    /// Extra information the language server shows next to the name, such as a signature.
+    pub detail: Option<String>,
+    pub deprecated: bool,
     pub children: Vec<DocumentSymbol>,
 }
 
@@ -1253,6 +1260,8 @@ impl Project {
                 toolchain_store: Some(toolchain_store),
 
                 agent_location: None,
+                agent_location_trail: Vec::new(),
+                agent_location_trail_index: 0,
             }
         })
     }
@@ -1466,6 +1475,8 @@ impl Project {
 
                 toolchain_store: Some(toolchain_store),
                 agent_location: None,
+                agent_location_trail: Vec::new(),
+                agent_location_trail_index: 0,
             };
 
             // remote server -> local machine handlers
@@ -1723,6 +1734,8 @@ impl Project {
                 remotely_created_models: Arc::new(Mutex::new(RemotelyCreatedModels::default())),
                 toolchain_store: None,
                 agent_location: None,
+                agent_location_trail: Vec::new(),
+                agent_location_trail_index: 0,
             };
             project.set_role(role, cx);
             for worktree in worktrees {
@@ -3751,6 +3764,26 @@ impl Project {
         })
     }
 
+    /// Like [`Self::definitions`], but keeps apart the results of each language server, so that
+    /// callers can tell which server reported what and which failed.
+    pub fn definitions_by_language_server<T: ToPointUtf16>(
+        &mut self,
+        buffer: &Entity<Buffer>,
+        position: T,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<Option<Vec<(LanguageServerId, Result<Vec<LocationLink>>)>>>> {
+        let position = position.to_point_utf16(buffer.read(cx));
+        let guard = self.retain_remotely_created_models(cx);
+        let task = self.lsp_store.update(cx, |lsp_store, cx| {
+            lsp_store.definitions_by_language_server(buffer, position, cx)
+        });
+        cx.background_spawn(async move {
+            let result = task.await;
+            drop(guard);
+            result
+        })
+    }
+
     pub fn declarations<T: ToPointUtf16>(
         &mut self,
         buffer: &Entity<Buffer>,
@@ -3823,6 +3856,27 @@ impl Project {
         })
     }
 
+    /// Like [`Self::references`], but keeps apart the results of each language server, yielding
+    /// them as each server answers, so that callers can tell which server reported what and
+    /// which failed.
+    pub fn references_by_language_server<T: ToPointUtf16>(
+        &mut self,
+        buffer: &Entity<Buffer>,
+        position: T,
+        cx: &mut Context<Self>,
+    ) -> Task<Result<Option<LanguageServerResponses<Vec<Location>>>>> {
+        let position = position.to_point_utf16(buffer.read(cx));
+        let guard = self.retain_remotely_created_models(cx);
+        let task = self.lsp_store.update(cx, |lsp_store, cx| {
+            lsp_store.references_by_language_server(buffer, position, cx)
+        });
+        cx.background_spawn(async move {
+            let result = task.await;
+            drop(guard);
+            result
+        })
+    }
+
     pub fn document_highlights<T: ToPointUtf16>(
         &mut self,
         buffer: &Entity<Buffer>,
@@ -4552,9 +4606,76 @@ impl Project {
         None
     }
 
+    /// Returns every project path that `path` could refer to under the rules of
+    /// [`Self::find_project_path`], which only returns the first. More than one result means that
+    /// `path` is ambiguous across worktrees, e.g. because two worktrees share a root name or both
+    /// contain a file at the given worktree-relative path.
+    pub fn find_project_paths(&self, path: impl AsRef<Path>, cx: &App) -> Vec<ProjectPath> {
+        let path_style = self.path_style(cx);
+        let path = path.as_ref();
+        let worktree_store = self.worktree_store.read(cx);
+
+        if is_absolute(&path.to_string_lossy(), path_style) {
+            return worktree_store
+                .visible_worktrees(cx)
+                .filter_map(|worktree| {
+                    let relative_path = path.strip_prefix(worktree.read(cx).abs_path()).ok()?;
+                    let path = RelPath::new(relative_path, path_style).ok()?;
+                    Some(ProjectPath {
+                        worktree_id: worktree.read(cx).id(),
+                        path: path.into_arc(),
+                    })
+                })
+                .collect();
+        }
+
+        let mut prefixed_paths = worktree_store
+            .visible_worktrees(cx)
+            .filter_map(|worktree| {
+                let worktree = worktree.read(cx);
+                let relative_path = path.strip_prefix(worktree.root_name().as_std_path()).ok()?;
+                let path = RelPath::new(relative_path, path_style).ok()?;
+                let exists = worktree.entry_for_path(&path).is_some();
+                Some((
+                    ProjectPath {
+                        worktree_id: worktree.id(),
+                        path: path.into_arc(),
+                    },
+                    exists,
+                ))
+            })
+            .collect::<Vec<_>>();
+        if !prefixed_paths.is_empty() {
+            // Worktrees sharing a root name only make a path ambiguous if it exists in several of them.
+            if prefixed_paths.len() > 1 && prefixed_paths.iter().any(|(_, exists)| *exists) {
+                prefixed_paths.retain(|(_, exists)| *exists);
+            }
+            return prefixed_paths
+                .into_iter()
+                .map(|(project_path, _)| project_path)
+                .collect();
+        }
+
+        let Ok(path) = RelPath::new(path, path_style) else {
+            return Vec::new();
+        };
+        worktree_store
+            .visible_worktrees(cx)
+            .filter_map(|worktree| {
+                let worktree = worktree.read(cx);
+                let entry = worktree.entry_for_path(&path)?;
+                Some(ProjectPath {
+                    worktree_id: worktree.id(),
+                    path: entry.path.clone(),
+                })
+            })
+            .collect()
+    }
+
     /// If there's only one visible worktree, returns the given worktree-relative path with no prefix.
     ///
-    /// Otherwise, returns the full path for the project path (obtained by prefixing the worktree-relative path with the name of the worktree).
+    /// Otherwise, returns the full path for the project path (obtained by prefixing the worktree-relative path with the name of the worktree),
+    /// or the absolute path when another visible worktree has the same name, so that the result always identifies a single path.
     pub fn short_full_path_for_project_path(
         &self,
         project_path: &ProjectPath,
@@ -4564,14 +4685,28 @@ impl Project {
         if self.visible_worktrees(cx).take(2).count() < 2 {
             return Some(project_path.path.display(path_style).to_string());
         }
-        self.worktree_for_id(project_path.worktree_id, cx)
-            .map(|worktree| {
-                let worktree_name = worktree.read(cx).root_name();
-                worktree_name
+        let worktree = self.worktree_for_id(project_path.worktree_id, cx)?;
+        let worktree = worktree.read(cx);
+        let root_name = worktree.root_name();
+        let root_name_is_shared = self.visible_worktrees(cx).any(|other| {
+            let other = other.read(cx);
+            other.id() != worktree.id() && other.root_name() == root_name
+        });
+        if root_name_is_shared {
+            Some(
+                worktree
+                    .absolutize(&project_path.path)
+                    .to_string_lossy()
+                    .into_owned(),
+            )
+        } else {
+            Some(
+                root_name
                     .join(&project_path.path)
                     .display(path_style)
-                    .to_string()
-            })
+                    .to_string(),
+            )
+        }
     }
 
     pub fn project_path_for_absolute_path(&self, abs_path: &Path, cx: &App) -> Option<ProjectPath> {
@@ -5394,6 +5529,45 @@ impl Project {
         new_location: Option<AgentLocation>,
         cx: &mut Context<Self>,
     ) {
+        self.agent_location_trail.clear();
+        self.agent_location_trail_index = 0;
+        self.move_agent_location(new_location, cx);
+    }
+
+    /// Moves the agent location to the first of `trail` and remembers all of them, in order, so
+    /// that the user can step through them, e.g. through every reference the agent found.
+    pub fn set_agent_location_trail(&mut self, trail: Vec<AgentLocation>, cx: &mut Context<Self>) {
+        let first = trail.first().cloned();
+        self.agent_location_trail = trail;
+        self.agent_location_trail_index = 0;
+        self.move_agent_location(first, cx);
+    }
+
+    pub fn agent_location_trail(&self) -> &[AgentLocation] {
+        &self.agent_location_trail
+    }
+
+    /// Moves the agent location `steps` entries along its trail, e.g. -1 to go back one, stopping
+    /// at either end. Returns the new location, or `None` when there is no trail.
+    pub fn step_agent_location_trail(
+        &mut self,
+        steps: isize,
+        cx: &mut Context<Self>,
+    ) -> Option<AgentLocation> {
+        let last_index = self.agent_location_trail.len().checked_sub(1)?;
+        self.agent_location_trail_index = self
+            .agent_location_trail_index
+            .saturating_add_signed(steps)
+            .min(last_index);
+        let location = self
+            .agent_location_trail
+            .get(self.agent_location_trail_index)
+            .cloned();
+        self.move_agent_location(location.clone(), cx);
+        location
+    }
+
+    fn move_agent_location(&mut self, new_location: Option<AgentLocation>, cx: &mut Context<Self>) {
         if let Some(old_location) = self.agent_location.as_ref() {
             old_location
                 .buffer
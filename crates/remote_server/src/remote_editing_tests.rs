@@ -2,7 +2,10 @@
 /// The tests in this file assume that server_cx is running on Windows too.
 /// We neead to find a way to test Windows-Non-Windows interactions.
 use crate::headless_project::HeadlessProject;
-use agent::{AgentTool, ReadFileTool, ReadFileToolInput, Templates, Thread, ToolCallEventStream};
+use agent::{
+    AgentTool, ReadFileMode, ReadFileTool, ReadFileToolInput, Templates, Thread,
+    ToolCallEventStream,
+};
 use client::{Client, UserStore};
 use clock::FakeSystemClock;
 use collections::{HashMap, HashSet};
@@ -1799,6 +1802,8 @@ async fn test_remote_agent_fs_tool_calls(cx: &mut TestAppContext, server_cx: &mu
         path: "project/b.txt".into(),
         start_line: None,
         end_line: None,
+        mode: ReadFileMode::Auto,
+        max_depth: None,
     };
     let read_tool = Arc::new(ReadFileTool::new(thread.downgrade(), project, action_log));
     let (event_stream, _) = ToolCallEventStream::test();
@@ -1811,6 +1816,8 @@ async fn test_remote_agent_fs_tool_calls(cx: &mut TestAppContext, server_cx: &mu
         path: "project/c.txt".into(),
         start_line: None,
         end_line: None,
+        mode: ReadFileMode::Auto,
+        max_depth: None,
     };
     let does_not_exist_result = cx.update(|cx| read_tool.run(input, event_stream, cx));
     does_not_exist_result.await.unwrap_err();
@@ -74,6 +74,9 @@ pub struct AgentSettingsContent {
     ///
     /// Default: false
     pub always_allow_tool_actions: Option<bool>,
+    /// Whether tools run without asking, ask for confirmation first, or are refused, by what they
+    /// can do or by name. Unset, tools only ask before the actions they consider risky.
+    pub tool_permissions: Option<ToolPermissionsContent>,
     /// Where to show a popup notification when the agent is waiting for user input.
     ///
     /// Default: "primary_screen"
@@ -111,6 +114,32 @@ pub struct AgentSettingsContent {
     ///
     /// Default: true
     pub expand_terminal_card: Option<bool>,
+    /// Whether navigation tools (such as go to definition and find references) move the
+    /// agent's location to their first result. When you are following the agent, this
+    /// also moves your editor to that result.
+    ///
+    /// Default: false
+    pub follow_agent_navigation: Option<bool>,
+    /// How many milliseconds tools that ask a language server (such as go to definition, find
+    /// references and hover) wait for its answer before returning what they have.
+    ///
+    /// Default: 30000
+    pub language_server_timeout_ms: Option<u64>,
+    /// Whether to give the agent tools that are still experimental, even if they haven't been
+    /// rolled out to you yet.
+    ///
+    /// Default: false
+    pub enable_experimental_tools: Option<bool>,
+    /// Files larger than this many bytes are given to the agent as an outline of their symbols
+    /// instead of in full, when there is no model whose context window can decide that.
+    ///
+    /// Default: 16384
+    pub auto_outline_size: Option<usize>,
+    /// Whether to lay out file outlines shown to the agent as before: indented by one space per
+    /// level, with line numbers right after each symbol rather than aligned into a column.
+    ///
+    /// Default: false
+    pub legacy_outline_format: Option<bool>,
     /// Whether to always use cmd-enter (or ctrl-enter on Linux or Windows) to send messages in the agent panel.
     ///
     /// Default: false
@@ -211,6 +240,33 @@ pub struct ContextServerPresetContent {
     pub tools: IndexMap<Arc<str>, bool>,
 }
 
+#[with_fallible_options]
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize, JsonSchema, MergeFrom)]
+pub struct ToolPermissionsContent {
+    /// What to do before running tools that only read the project or the web.
+    pub read_only: Option<ToolPermissionMode>,
+    /// What to do before running tools that change the project's files or git state.
+    pub workspace_mutating: Option<ToolPermissionMode>,
+    /// What to do before running tools that run commands, open things outside of Zed, or call
+    /// context servers.
+    pub system_executing: Option<ToolPermissionMode>,
+    /// What to do before running individual tools, by name. Takes precedence over the tool's
+    /// group.
+    #[serde(default)]
+    pub tools: IndexMap<Arc<str>, ToolPermissionMode>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema, MergeFrom)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolPermissionMode {
+    /// Run the tool without asking.
+    Allow,
+    /// Ask for confirmation before running the tool.
+    Ask,
+    /// Never run the tool, even when `always_allow_tool_actions` is enabled.
+    Deny,
+}
+
 #[derive(Copy, Clone, Default, Debug, PartialEq, Serialize, Deserialize, JsonSchema, MergeFrom)]
 #[serde(rename_all = "snake_case")]
 pub enum DefaultAgentView {
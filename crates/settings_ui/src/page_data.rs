@@ -6151,6 +6151,116 @@ pub(crate) fn settings_data(cx: &App) -> Vec<SettingsPage> {
                         metadata: None,
                         files: USER,
                     }),
+                    SettingsPageItem::SettingItem(SettingItem {
+                        title: "Follow Agent Navigation",
+                        description: "Whether navigation tools move the agent's location to their first result.",
+                        field: Box::new(SettingField {
+                            json_path: Some("agent.follow_agent_navigation"),
+                            pick: |settings_content| {
+                                settings_content
+                                    .agent
+                                    .as_ref()?
+                                    .follow_agent_navigation
+                                    .as_ref()
+                            },
+                            write: |settings_content, value| {
+                                settings_content
+                                    .agent
+                                    .get_or_insert_default()
+                                    .follow_agent_navigation = value;
+                            },
+                        }),
+                        metadata: None,
+                        files: USER,
+                    }),
+                    SettingsPageItem::SettingItem(SettingItem {
+                        title: "Language Server Timeout",
+                        description: "How many milliseconds tools that ask a language server wait for its answer.",
+                        field: Box::new(SettingField {
+                            json_path: Some("agent.language_server_timeout_ms"),
+                            pick: |settings_content| {
+                                settings_content
+                                    .agent
+                                    .as_ref()?
+                                    .language_server_timeout_ms
+                                    .as_ref()
+                            },
+                            write: |settings_content, value| {
+                                settings_content
+                                    .agent
+                                    .get_or_insert_default()
+                                    .language_server_timeout_ms = value;
+                            },
+                        }),
+                        metadata: None,
+                        files: USER,
+                    }),
+                    SettingsPageItem::SettingItem(SettingItem {
+                        title: "Enable Experimental Tools",
+                        description: "Whether to give the agent tools that are still experimental.",
+                        field: Box::new(SettingField {
+                            json_path: Some("agent.enable_experimental_tools"),
+                            pick: |settings_content| {
+                                settings_content
+                                    .agent
+                                    .as_ref()?
+                                    .enable_experimental_tools
+                                    .as_ref()
+                            },
+                            write: |settings_content, value| {
+                                settings_content
+                                    .agent
+                                    .get_or_insert_default()
+                                    .enable_experimental_tools = value;
+                            },
+                        }),
+                        metadata: None,
+                        files: USER,
+                    }),
+                    SettingsPageItem::SettingItem(SettingItem {
+                        title: "Auto Outline Size",
+                        description: "Files larger than this many bytes are given to the agent as an outline, unless the model's context window has room for them.",
+                        field: Box::new(SettingField {
+                            json_path: Some("agent.auto_outline_size"),
+                            pick: |settings_content| {
+                                settings_content
+                                    .agent
+                                    .as_ref()?
+                                    .auto_outline_size
+                                    .as_ref()
+                            },
+                            write: |settings_content, value| {
+                                settings_content
+                                    .agent
+                                    .get_or_insert_default()
+                                    .auto_outline_size = value;
+                            },
+                        }),
+                        metadata: None,
+                        files: USER,
+                    }),
+                    SettingsPageItem::SettingItem(SettingItem {
+                        title: "Legacy Outline Format",
+                        description: "Whether to show the agent file outlines indented by one space per level, with line numbers right after each symbol, as before.",
+                        field: Box::new(SettingField {
+                            json_path: Some("agent.legacy_outline_format"),
+                            pick: |settings_content| {
+                                settings_content
+                                    .agent
+                                    .as_ref()?
+                                    .legacy_outline_format
+                                    .as_ref()
+                            },
+                            write: |settings_content, value| {
+                                settings_content
+                                    .agent
+                                    .get_or_insert_default()
+                                    .legacy_outline_format = value;
+                            },
+                        }),
+                        metadata: None,
+                        files: USER,
+                    }),
                     SettingsPageItem::SettingItem(SettingItem {
                         title: "Use Modifier To Send",
                         description: "Whether to always use cmd-enter (or ctrl-enter on Linux or Windows) to send messages.",